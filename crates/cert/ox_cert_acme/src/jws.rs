@@ -0,0 +1,194 @@
+//! Flattened JWS parsing and signature verification for ACME requests
+//! (RFC 8555 §6.2, RFC 7515).
+
+use ring::hmac;
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+
+/// Algorithms accepted in the protected header, as advertised in
+/// `badSignatureAlgorithm` problems.
+pub const SUPPORTED_ALGS: &[&str] = &["RS256", "ES256", "ES384", "EdDSA"];
+
+/// MAC algorithms accepted for External Account Binding (RFC 8555 §7.3.4).
+pub const EAB_ALGS: &[&str] = &["HS256", "HS384", "HS512"];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum JwsError {
+    /// The body is not a well-formed flattened JWS.
+    Malformed(String),
+    /// The `alg` header names an algorithm this server does not accept.
+    BadSignatureAlgorithm(String),
+    /// The signature does not verify against the supplied key.
+    BadSignature,
+}
+
+impl std::fmt::Display for JwsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JwsError::Malformed(m) => write!(f, "{}", m),
+            JwsError::BadSignatureAlgorithm(alg) => write!(
+                f, "unsupported JWS algorithm '{}' (supported: {})", alg, SUPPORTED_ALGS.join(", ")
+            ),
+            JwsError::BadSignature => write!(f, "JWS signature verification failed"),
+        }
+    }
+}
+
+/// A parsed ACME request body.
+#[derive(Debug)]
+pub struct Jws {
+    pub alg: String,
+    pub nonce: Option<String>,
+    pub url: Option<String>,
+    pub kid: Option<String>,
+    pub jwk: Option<serde_json::Value>,
+    /// Decoded payload; empty for POST-as-GET.
+    pub payload: Vec<u8>,
+    signing_input: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+pub fn b64_decode(s: &str) -> Option<Vec<u8>> {
+    base64::Engine::decode(&base64::engine::general_purpose::URL_SAFE_NO_PAD, s.as_bytes()).ok()
+}
+
+fn malformed(msg: &str) -> JwsError {
+    JwsError::Malformed(msg.to_string())
+}
+
+pub fn parse(body: &str) -> Result<Jws, JwsError> {
+    let v: serde_json::Value = serde_json::from_str(body)
+        .map_err(|_| malformed("request body is not a JSON object"))?;
+    parse_value(&v, SUPPORTED_ALGS)
+}
+
+/// Parse the `externalAccountBinding` member of a new-account payload.
+pub fn parse_eab(v: &serde_json::Value) -> Result<Jws, JwsError> {
+    parse_value(v, EAB_ALGS)
+}
+
+fn parse_value(v: &serde_json::Value, algs: &[&str]) -> Result<Jws, JwsError> {
+    // ACME only permits the flattened JSON serialization (RFC 8555 §6.2)
+    if v.get("signatures").is_some() || v.get("header").is_some() {
+        return Err(malformed("only flattened JWS without unprotected headers is accepted"));
+    }
+    let protected_b64 = v.get("protected").and_then(|p| p.as_str())
+        .ok_or_else(|| malformed("missing protected header"))?;
+    let payload_b64 = v.get("payload").and_then(|p| p.as_str())
+        .ok_or_else(|| malformed("missing payload"))?;
+    let signature_b64 = v.get("signature").and_then(|s| s.as_str())
+        .ok_or_else(|| malformed("missing signature"))?;
+
+    let protected: serde_json::Value = b64_decode(protected_b64)
+        .and_then(|b| serde_json::from_slice(&b).ok())
+        .ok_or_else(|| malformed("protected header is not base64url-encoded JSON"))?;
+    let payload = b64_decode(payload_b64).ok_or_else(|| malformed("payload is not base64url"))?;
+    let signature = b64_decode(signature_b64).ok_or_else(|| malformed("signature is not base64url"))?;
+
+    let header_str = |name: &str| protected.get(name).and_then(|h| h.as_str()).map(|s| s.to_string());
+    let alg = header_str("alg").ok_or_else(|| malformed("missing alg in protected header"))?;
+    if !algs.contains(&alg.as_str()) {
+        return Err(JwsError::BadSignatureAlgorithm(alg));
+    }
+
+    Ok(Jws {
+        alg,
+        nonce: header_str("nonce"),
+        url: header_str("url"),
+        kid: header_str("kid"),
+        jwk: protected.get("jwk").cloned(),
+        payload,
+        signing_input: format!("{}.{}", protected_b64, payload_b64).into_bytes(),
+        signature,
+    })
+}
+
+impl Jws {
+    pub fn payload_json(&self) -> Option<serde_json::Value> {
+        serde_json::from_slice(&self.payload).ok()
+    }
+
+    /// Verify the signature with `jwk`, which must be a key of the type
+    /// implied by `alg`.
+    pub fn verify(&self, jwk: &serde_json::Value) -> Result<(), JwsError> {
+        let field = |name: &str| -> Result<Vec<u8>, JwsError> {
+            jwk.get(name).and_then(|f| f.as_str()).and_then(b64_decode)
+                .ok_or_else(|| JwsError::Malformed(format!("jwk is missing '{}'", name)))
+        };
+        let kty = jwk.get("kty").and_then(|k| k.as_str()).unwrap_or("");
+        let crv = jwk.get("crv").and_then(|c| c.as_str()).unwrap_or("");
+
+        let verified = match (self.alg.as_str(), kty, crv) {
+            ("RS256", "RSA", _) => {
+                let key = RsaPublicKeyComponents { n: field("n")?, e: field("e")? };
+                key.verify(&signature::RSA_PKCS1_2048_8192_SHA256, &self.signing_input, &self.signature)
+            }
+            ("ES256", "EC", "P-256") => {
+                let point = ec_point(&field("x")?, &field("y")?);
+                UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_FIXED, point)
+                    .verify(&self.signing_input, &self.signature)
+            }
+            ("ES384", "EC", "P-384") => {
+                let point = ec_point(&field("x")?, &field("y")?);
+                UnparsedPublicKey::new(&signature::ECDSA_P384_SHA384_FIXED, point)
+                    .verify(&self.signing_input, &self.signature)
+            }
+            ("EdDSA", "OKP", "Ed25519") => {
+                UnparsedPublicKey::new(&signature::ED25519, field("x")?)
+                    .verify(&self.signing_input, &self.signature)
+            }
+            _ => return Err(JwsError::Malformed(format!(
+                "alg {} does not match jwk (kty {}, crv {})", self.alg, kty, crv
+            ))),
+        };
+        verified.map_err(|_| JwsError::BadSignature)
+    }
+
+    /// Verify an HS256/HS384/HS512 MAC with the shared `key`.
+    pub fn verify_mac(&self, key: &[u8]) -> Result<(), JwsError> {
+        let alg = match self.alg.as_str() {
+            "HS256" => hmac::HMAC_SHA256,
+            "HS384" => hmac::HMAC_SHA384,
+            "HS512" => hmac::HMAC_SHA512,
+            other => return Err(JwsError::BadSignatureAlgorithm(other.to_string())),
+        };
+        hmac::verify(&hmac::Key::new(alg, key), &self.signing_input, &self.signature)
+            .map_err(|_| JwsError::BadSignature)
+    }
+}
+
+/// Uncompressed SEC1 point `04 || x || y`.
+fn ec_point(x: &[u8], y: &[u8]) -> Vec<u8> {
+    let mut point = Vec::with_capacity(1 + x.len() + y.len());
+    point.push(0x04);
+    point.extend_from_slice(x);
+    point.extend_from_slice(y);
+    point
+}
+
+fn strip_leading_zeros(b: &[u8]) -> &[u8] {
+    let start = b.iter().position(|&x| x != 0).unwrap_or(b.len());
+    &b[start..]
+}
+
+/// Whether `jwk` is the same public key as the certificate's subject key.
+/// Used to authorize revocation requests signed with the certificate key.
+pub fn jwk_matches_spki(jwk: &serde_json::Value, spki: &x509_parser::x509::SubjectPublicKeyInfo) -> bool {
+    use x509_parser::public_key::PublicKey;
+
+    let field = |name: &str| jwk.get(name).and_then(|f| f.as_str()).and_then(b64_decode);
+    let kty = jwk.get("kty").and_then(|k| k.as_str()).unwrap_or("");
+    match (kty, spki.parsed()) {
+        ("RSA", Ok(PublicKey::RSA(rsa))) => match (field("n"), field("e")) {
+            (Some(n), Some(e)) => strip_leading_zeros(&n) == strip_leading_zeros(rsa.modulus)
+                && strip_leading_zeros(&e) == strip_leading_zeros(rsa.exponent),
+            _ => false,
+        },
+        ("EC", Ok(PublicKey::EC(point))) => match (field("x"), field("y")) {
+            (Some(x), Some(y)) => ec_point(&x, &y) == point.data(),
+            _ => false,
+        },
+        // x509-parser does not decode Ed25519 keys; the raw key is the bit string
+        ("OKP", _) => field("x").is_some_and(|x| x == spki.subject_public_key.data.as_ref()),
+        _ => false,
+    }
+}
@@ -2,11 +2,13 @@ use ox_cert_core::{
     issuer_params_from_cert_pem,
     model::{
         AcmeAccount, AcmeAccountStatus, AcmeAuthorization, AcmeAuthzStatus,
-        AcmeChallenge, AcmeChallengeStatus, AcmeIdentifier, AcmeOrder, AcmeOrderStatus,
-        AuditAction, AuditEvent, CertStoreConfig, ChallengeType, KeyStoreConfig,
+        AcmeChallenge, AcmeChallengeStatus, AcmeEabKey, AcmeIdentifier, AcmeOrder, AcmeOrderStatus,
+        AuditAction, AuditEvent, CertStatus, CertStoreConfig, CrlPartitionConfig, ChallengeType, CtConfig,
+        KeyStoreConfig, RevocationReason,
     },
-    open_keystore,
+    ct, open_keystore,
     sign_csr,
+    CertError, IssuanceOptions, KeyStoreSigner,
     store::{CertStore, OxPersistenceCertStore},
 };
 use serde::Deserialize;
@@ -15,6 +17,11 @@ use std::sync::RwLock;
 use time::OffsetDateTime;
 use uuid::Uuid;
 
+mod jws;
+mod tls_alpn;
+#[cfg(test)]
+mod tests;
+
 #[derive(Debug, Deserialize)]
 pub struct AcmeRateLimitConfig {
     #[serde(default = "default_orders_per_hour")]
@@ -49,8 +56,13 @@ pub struct AcmeConfig {
     pub keystore: KeyStoreConfig,
     pub ca_intermediate_key_id: String,
     pub ca_intermediate_cert_path: String,
-    #[allow(dead_code)]
     pub ca_root_cert_path: String,
+    /// Certificate Transparency for issued certificates.
+    #[serde(default)]
+    pub ct: Option<CtConfig>,
+    /// Partitioned CRLs for issued certificates; must match `ox_cert_crl`.
+    #[serde(default)]
+    pub crl_partitions: Option<CrlPartitionConfig>,
     #[serde(default)]
     pub extensions: AcmeExtensionsConfig,
     pub tos_url: Option<String>,
@@ -64,9 +76,13 @@ pub struct AcmeConfig {
     /// Timeout for outbound challenge validation HTTP/DNS requests (seconds)
     #[serde(default = "default_validation_timeout")]
     pub validation_timeout_secs: u64,
+    /// Port dialled for tls-alpn-01 validation (RFC 8737 mandates 443)
+    #[serde(default = "default_tls_alpn_port")]
+    pub tls_alpn_port: u16,
 }
 
 fn default_validation_timeout() -> u64 { 30 }
+fn default_tls_alpn_port() -> u16 { 443 }
 
 #[derive(Debug, Deserialize, Default)]
 pub struct AcmeExtensionsConfig {
@@ -75,6 +91,9 @@ pub struct AcmeExtensionsConfig {
     pub cdp_url: Option<String>,
 }
 
+/// Nonces older than this are no longer accepted.
+const NONCE_TTL: std::time::Duration = std::time::Duration::from_secs(3600);
+
 pub struct NonceStore {
     nonces: RwLock<HashMap<String, std::time::Instant>>,
 }
@@ -86,12 +105,16 @@ impl NonceStore {
     pub fn issue(&self) -> String {
         let nonce = Uuid::new_v4().to_string();
         if let Ok(mut m) = self.nonces.write() {
+            m.retain(|_, issued| issued.elapsed() < NONCE_TTL);
             m.insert(nonce.clone(), std::time::Instant::now());
         }
         nonce
     }
+    /// Single-use: returns true only the first time a live nonce is presented.
     pub fn consume(&self, nonce: &str) -> bool {
-        self.nonces.write().map(|mut m| m.remove(nonce).is_some()).unwrap_or(false)
+        self.nonces.write()
+            .map(|mut m| m.remove(nonce).is_some_and(|issued| issued.elapsed() < NONCE_TTL))
+            .unwrap_or(false)
     }
 }
 
@@ -127,7 +150,9 @@ fn acme_error(problem_type: &str, detail: &str) -> String {
 // JWK thumbprint (RFC 7638)
 // ---------------------------------------------------------------------------
 
-fn jwk_thumbprint(jwk_json: &str) -> Option<String> {
+/// The JWK reduced to its required members, sorted by name, without
+/// whitespace. Account keys are stored in this form so they compare by value.
+fn canonical_jwk(jwk_json: &str) -> Option<String> {
     let jwk: serde_json::Value = serde_json::from_str(jwk_json).ok()?;
     let kty = jwk.get("kty")?.as_str()?;
     // Canonical JSON: only required fields, sorted by member name
@@ -150,6 +175,11 @@ fn jwk_thumbprint(jwk_json: &str) -> Option<String> {
         }
         _ => return None,
     };
+    Some(canonical)
+}
+
+fn jwk_thumbprint(jwk_json: &str) -> Option<String> {
+    let canonical = canonical_jwk(jwk_json)?;
     use sha2::Digest;
     let hash = sha2::Sha256::digest(canonical.as_bytes());
     Some(base64::Engine::encode(&base64::engine::general_purpose::URL_SAFE_NO_PAD, &hash))
@@ -226,6 +256,15 @@ pub fn handle(ctx: &AcmeContext, method: &str, path: &str, body: &str) -> AcmeOu
         };
     }
 
+    macro_rules! authenticated {
+        ($key_id:expr) => {
+            match authenticate(ctx, path, body, $key_id, &new_nonce) {
+                Ok(req) => req,
+                Err(outcome) => return outcome,
+            }
+        };
+    }
+
     match (method, segs.get(1).copied(), segs.get(2).copied(), segs.get(3).copied()) {
         ("GET",        Some("directory"), _, _) => handle_directory(ctx, new_nonce),
         ("HEAD"|"POST", Some("new-nonce"), _, _) => {
@@ -238,27 +277,56 @@ pub fn handle(ctx: &AcmeContext, method: &str, path: &str, body: &str) -> AcmeOu
                 content_type: "application/json".to_string(),
             }
         }
-        ("POST", Some("new-account"), _, _) => handle_new_account(ctx, body, new_nonce),
-        ("POST", Some("new-order"),   _, _) => handle_new_order(ctx, body, new_nonce),
-        ("POST", Some("order"), Some(order_id), Some("finalize")) =>
-            handle_finalize(ctx, order_id, body, new_nonce),
-        ("POST", Some("order"), Some(order_id), _) =>
-            handle_get_order(ctx, order_id, new_nonce),
-        // Authorization: GET or POST-as-GET
+        ("POST", Some("new-account"), _, _) => {
+            let req = authenticated!(KeyId::Jwk);
+            handle_new_account(ctx, &req, new_nonce)
+        }
+        ("POST", Some("account"), Some(account_id), _) => {
+            let req = authenticated!(KeyId::Kid);
+            handle_account(ctx, account_id, &req, new_nonce)
+        }
+        ("POST", Some("key-change"), _, _) => {
+            let req = authenticated!(KeyId::Kid);
+            handle_key_change(ctx, &req, new_nonce)
+        }
+        ("POST", Some("new-order"), _, _) => {
+            let req = authenticated!(KeyId::Kid);
+            handle_new_order(ctx, &req, new_nonce)
+        }
+        ("POST", Some("order"), Some(order_id), Some("finalize")) => {
+            let req = authenticated!(KeyId::Kid);
+            handle_finalize(ctx, order_id, &req, new_nonce)
+        }
+        ("POST", Some("order"), Some(order_id), _) => {
+            let req = authenticated!(KeyId::Kid);
+            handle_get_order(ctx, order_id, &req, new_nonce)
+        }
+        // Authorization: POST-as-GET
         ("POST", Some("authz"), Some(authz_id), Some("challenge")) => {
             // /acme/authz/{authz_id}/challenge/{challenge_idx}
             let challenge_idx: usize = segs.get(4)
                 .and_then(|s| s.parse().ok())
                 .unwrap_or(0);
-            handle_challenge(ctx, authz_id, challenge_idx, body, new_nonce)
+            let req = authenticated!(KeyId::Kid);
+            handle_challenge(ctx, authz_id, challenge_idx, &req, new_nonce)
+        }
+        ("POST", Some("authz"), Some(authz_id), _) => {
+            let req = authenticated!(KeyId::Kid);
+            handle_get_authz(ctx, authz_id, &req, new_nonce)
         }
-        ("POST", Some("authz"), Some(authz_id), _) =>
-            handle_get_authz(ctx, authz_id, new_nonce),
         // Certificate download
-        ("POST", Some("cert"), Some(order_id), _) =>
-            handle_get_cert(ctx, order_id, new_nonce),
-        ("POST", Some("revoke-cert"), _, _) =>
-            handle_revoke_cert(ctx, body, new_nonce),
+        ("POST", Some("cert"), Some(order_id), _) => {
+            let req = authenticated!(KeyId::Kid);
+            handle_get_cert(ctx, order_id, &req, new_nonce)
+        }
+        // Revocation may be signed by the account or by the certificate key
+        ("POST", Some("revoke-cert"), _, _) => {
+            let req = authenticated!(KeyId::Either);
+            handle_revoke_cert(ctx, &req, new_nonce)
+        }
+        // ACME Renewal Information (draft-ietf-acme-ari): unauthenticated GET
+        ("GET", Some("renewal-info"), Some(cert_id), _) =>
+            handle_renewal_info(ctx, cert_id, new_nonce),
         _ => AcmeOutcome {
             http_status: 404,
             body_json: acme_error("notFound", "endpoint not found"),
@@ -270,33 +338,134 @@ pub fn handle(ctx: &AcmeContext, method: &str, path: &str, body: &str) -> AcmeOu
 }
 
 // ---------------------------------------------------------------------------
-// JWS helpers
+// Request authentication (RFC 8555 §6.2 - §6.5)
 // ---------------------------------------------------------------------------
 
-fn b64_decode(s: &str) -> Option<Vec<u8>> {
-    base64::Engine::decode(&base64::engine::general_purpose::URL_SAFE_NO_PAD, s.as_bytes()).ok()
+/// Which key reference a request type must carry in its protected header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyId {
+    /// Embedded `jwk` (new-account).
+    Jwk,
+    /// Account URL in `kid` (everything tied to an existing account).
+    Kid,
+    /// Either one (revoke-cert, by account or by certificate key).
+    Either,
 }
 
-fn extract_jws_payload(body: &str) -> Option<serde_json::Value> {
-    let v: serde_json::Value = serde_json::from_str(body).ok()?;
-    let bytes = b64_decode(v.get("payload")?.as_str()?)?;
-    serde_json::from_slice(&bytes).ok()
+/// A POST whose JWS signature, nonce and URL have been verified.
+struct AcmeRequest {
+    jws: jws::Jws,
+    /// Account named by `kid`; `None` when signed with an embedded `jwk`.
+    account: Option<AcmeAccount>,
+    /// The key that signed the request.
+    jwk: serde_json::Value,
 }
 
-fn extract_jws_kid(body: &str) -> Option<String> {
-    let v: serde_json::Value = serde_json::from_str(body).ok()?;
-    let protected: serde_json::Value = serde_json::from_slice(
-        &b64_decode(v.get("protected")?.as_str()?)?,
-    ).ok()?;
-    protected.get("kid")?.as_str().map(|s| s.to_string())
+impl AcmeRequest {
+    fn payload(&self) -> Option<serde_json::Value> {
+        self.jws.payload_json()
+    }
+
+    fn account_id(&self) -> Option<&str> {
+        self.account.as_ref().map(|a| a.id.as_str())
+    }
 }
 
-fn extract_jws_jwk(body: &str) -> Option<String> {
-    let v: serde_json::Value = serde_json::from_str(body).ok()?;
-    let protected: serde_json::Value = serde_json::from_slice(
-        &b64_decode(v.get("protected")?.as_str()?)?,
-    ).ok()?;
-    protected.get("jwk").map(|j| j.to_string())
+fn account_url(ctx: &AcmeContext, account_id: &str) -> String {
+    format!("{}/acme/account/{}", base_url(ctx), account_id)
+}
+
+fn authenticate(
+    ctx: &AcmeContext,
+    path: &str,
+    body: &str,
+    key_id: KeyId,
+    nonce: &str,
+) -> Result<AcmeRequest, AcmeOutcome> {
+    let fail = |status: u16, problem_type: &str, detail: &str| AcmeOutcome {
+        http_status: status,
+        body_json: acme_error(problem_type, detail),
+        replay_nonce: Some(nonce.to_string()),
+        location: None,
+        content_type: "application/problem+json".to_string(),
+    };
+
+    let parsed = jws::parse(body).map_err(|e| match e {
+        jws::JwsError::BadSignatureAlgorithm(_) => fail(400, "badSignatureAlgorithm", &e.to_string()),
+        _ => fail(400, "malformed", &e.to_string()),
+    })?;
+
+    match parsed.nonce.as_deref() {
+        Some(n) if ctx.nonces.consume(n) => {}
+        Some(_) => return Err(fail(400, "badNonce", "nonce is unknown, expired or already used")),
+        None => return Err(fail(400, "badNonce", "missing nonce in protected header")),
+    }
+
+    let expected_url = format!("{}{}", base_url(ctx), path);
+    if parsed.url.as_deref() != Some(expected_url.as_str()) {
+        return Err(fail(403, "unauthorized", "url in protected header does not match the request URL"));
+    }
+
+    let (account, jwk) = match (&parsed.jwk, &parsed.kid, key_id) {
+        (Some(_), Some(_), _) =>
+            return Err(fail(400, "malformed", "jwk and kid are mutually exclusive")),
+        (None, None, _) =>
+            return Err(fail(400, "malformed", "protected header must contain jwk or kid")),
+        (Some(_), None, KeyId::Kid) =>
+            return Err(fail(400, "malformed", "request must be signed by an account (kid), not a jwk")),
+        (None, Some(_), KeyId::Jwk) =>
+            return Err(fail(400, "malformed", "new-account must be signed with a jwk, not a kid")),
+        (Some(jwk), None, _) => (None, jwk.clone()),
+        (None, Some(kid), _) => {
+            let prefix = account_url(ctx, "");
+            let account_id = kid.strip_prefix(prefix.as_str()).unwrap_or("");
+            let store = OxPersistenceCertStore::open(ctx.config.store.db_path())
+                .map_err(|e| fail(500, "serverInternal", &e.to_string()))?;
+            let account = match store.get_acme_account(&ctx.config.tenant_id, account_id) {
+                Ok(Some(a)) if !account_id.is_empty() => a,
+                Ok(_) => return Err(fail(400, "accountDoesNotExist", "no account for kid")),
+                Err(e) => return Err(fail(500, "serverInternal", &e.to_string())),
+            };
+            let jwk: serde_json::Value = serde_json::from_str(&account.jwk)
+                .map_err(|e| fail(500, "serverInternal", &format!("stored account jwk: {}", e)))?;
+            (Some(account), jwk)
+        }
+    };
+
+    parsed.verify(&jwk).map_err(|e| fail(400, "malformed", &e.to_string()))?;
+
+    if let Some(account) = &account {
+        if account.status != AcmeAccountStatus::Valid {
+            return Err(fail(403, "unauthorized", "account is not valid"));
+        }
+    }
+
+    Ok(AcmeRequest { jws: parsed, account, jwk })
+}
+
+/// Whether the authenticated account owns `order_id`.
+fn owns_order(store: &OxPersistenceCertStore, tenant: &str, req: &AcmeRequest, order_id: &str) -> bool {
+    match (store.get_acme_order(tenant, order_id), req.account_id()) {
+        (Ok(Some(order)), Some(account_id)) => order.account_id == account_id,
+        _ => false,
+    }
+}
+
+/// The EAB key the authenticated account was bound with, if any. An account whose
+/// key has since been revoked or deleted may no longer order certificates.
+fn bound_eab_key(
+    store: &OxPersistenceCertStore,
+    tenant: &str,
+    req: &AcmeRequest,
+) -> Result<Option<AcmeEabKey>, (u16, &'static str, String)> {
+    let Some(kid) = req.account.as_ref().and_then(|a| a.eab_kid.as_deref()) else {
+        return Ok(None);
+    };
+    match store.get_acme_eab_key(tenant, kid) {
+        Ok(Some(k)) if !k.revoked => Ok(Some(k)),
+        Ok(_) => Err((403, "unauthorized", "external account binding has been revoked".to_string())),
+        Err(e) => Err((500, "serverInternal", e.to_string())),
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -318,6 +487,8 @@ fn handle_directory(ctx: &AcmeContext, nonce: String) -> AcmeOutcome {
             "newAccount": format!("{}/acme/new-account", base),
             "newOrder":   format!("{}/acme/new-order", base),
             "revokeCert": format!("{}/acme/revoke-cert", base),
+            "keyChange":  format!("{}/acme/key-change", base),
+            "renewalInfo": format!("{}/acme/renewal-info", base),
             "meta": meta,
         }).to_string(),
         replay_nonce: Some(nonce),
@@ -326,9 +497,8 @@ fn handle_directory(ctx: &AcmeContext, nonce: String) -> AcmeOutcome {
     }
 }
 
-fn handle_new_account(ctx: &AcmeContext, body: &str, nonce: String) -> AcmeOutcome {
+fn handle_new_account(ctx: &AcmeContext, req: &AcmeRequest, nonce: String) -> AcmeOutcome {
     let tenant = &ctx.config.tenant_id;
-    let base   = base_url(ctx);
 
     macro_rules! err {
         ($status:expr, $type:expr, $msg:expr) => {
@@ -342,24 +512,82 @@ fn handle_new_account(ctx: &AcmeContext, body: &str, nonce: String) -> AcmeOutco
         };
     }
 
-    let payload = match extract_jws_payload(body) {
+    let payload = match req.payload() {
         Some(p) => p,
-        None => err!(400, "malformed", "invalid JWS"),
+        None => err!(400, "malformed", "payload must be a JSON object"),
     };
-    let jwk = match extract_jws_jwk(body) {
+    let jwk = match canonical_jwk(&req.jwk.to_string()) {
         Some(j) => j,
-        None => err!(400, "malformed", "missing jwk in protected header"),
+        None => err!(400, "malformed", "unsupported jwk"),
     };
 
-    if ctx.config.external_account_required && payload.get("externalAccountBinding").is_none() {
-        err!(403, "externalAccountRequired", "external account binding required");
-    }
-
     let store = match OxPersistenceCertStore::open(ctx.config.store.db_path()) {
         Ok(s) => s,
         Err(e) => err!(500, "serverInternal", &e.to_string()),
     };
 
+    // One account per key: a repeat registration returns the existing account,
+    // and a deactivated account's key is refused for good (RFC 8555 §7.3.6)
+    match store.find_acme_account_by_jwk(tenant, &jwk) {
+        Ok(Some(existing)) if existing.status != AcmeAccountStatus::Valid =>
+            err!(403, "unauthorized", "account for this key is not valid"),
+        Ok(Some(existing)) => return AcmeOutcome {
+            http_status: 200,
+            body_json: account_json(&existing),
+            replay_nonce: Some(nonce),
+            location: Some(account_url(ctx, &existing.id)),
+            content_type: "application/json".to_string(),
+        },
+        Ok(None) => {}
+        Err(e) => err!(500, "serverInternal", &e.to_string()),
+    }
+    if payload.get("onlyReturnExisting").and_then(|v| v.as_bool()) == Some(true) {
+        err!(400, "accountDoesNotExist", "no account exists for this key");
+    }
+
+    // External Account Binding (RFC 8555 §7.3.4): an HMAC over the account key,
+    // keyed by a credential provisioned through the admin API
+    let eab_key = match payload.get("externalAccountBinding") {
+        Some(binding) => {
+            let inner = match jws::parse_eab(binding) {
+                Ok(j) => j,
+                Err(jws::JwsError::BadSignatureAlgorithm(alg)) => err!(
+                    400, "malformed",
+                    &format!("externalAccountBinding alg '{}' is not one of {}", alg, jws::EAB_ALGS.join(", "))
+                ),
+                Err(e) => err!(400, "malformed", &format!("externalAccountBinding: {}", e)),
+            };
+            let kid = match (&inner.kid, &inner.jwk, &inner.nonce) {
+                (Some(kid), None, None) => kid.clone(),
+                _ => err!(400, "malformed", "externalAccountBinding must carry kid and no jwk or nonce"),
+            };
+            if inner.url != req.jws.url {
+                err!(400, "malformed", "externalAccountBinding url must match the outer url");
+            }
+            let bound_key = inner.payload_json().and_then(|k| canonical_jwk(&k.to_string()));
+            if bound_key.as_deref() != Some(jwk.as_str()) {
+                err!(400, "malformed", "externalAccountBinding payload is not the account key");
+            }
+            let eab = match store.get_acme_eab_key(tenant, &kid) {
+                Ok(Some(k)) if !k.revoked && k.account_id.is_none() => k,
+                Ok(Some(k)) if k.revoked => err!(403, "unauthorized", "external account key has been revoked"),
+                Ok(Some(_)) => err!(403, "unauthorized", "external account key is already bound to an account"),
+                Ok(None) => err!(403, "unauthorized", "unknown external account key id"),
+                Err(e) => err!(500, "serverInternal", &e.to_string()),
+            };
+            let Some(hmac_key) = jws::b64_decode(&eab.hmac_key) else {
+                err!(500, "serverInternal", "stored external account key is not base64url");
+            };
+            if inner.verify_mac(&hmac_key).is_err() {
+                err!(403, "unauthorized", "externalAccountBinding MAC verification failed");
+            }
+            Some(eab)
+        }
+        None if ctx.config.external_account_required =>
+            err!(403, "externalAccountRequired", "external account binding required"),
+        None => None,
+    };
+
     let id = Uuid::new_v4().to_string();
     let contact: Vec<String> = payload.get("contact")
         .and_then(|c| serde_json::from_value(c.clone()).ok())
@@ -371,21 +599,174 @@ fn handle_new_account(ctx: &AcmeContext, body: &str, nonce: String) -> AcmeOutco
         jwk,
         contact: contact.clone(),
         status: AcmeAccountStatus::Valid,
-        eab_kid: None,
+        eab_kid: eab_key.as_ref().map(|k| k.kid.clone()),
         created_at: OffsetDateTime::now_utc(),
     };
-    let _ = store.store_acme_account(tenant, &account);
+    // The key is bound with a conditional update in the same transaction as the
+    // account insert, so two requests racing on one key cannot both succeed
+    match &eab_key {
+        Some(eab) => match store.bind_acme_account(tenant, &eab.kid, &account) {
+            Ok(true) => {}
+            Ok(false) => err!(403, "unauthorized", "external account key is already bound to an account"),
+            Err(e) => err!(500, "serverInternal", &e.to_string()),
+        },
+        None => if let Err(e) = store.store_acme_account(tenant, &account) {
+            err!(500, "serverInternal", &e.to_string());
+        },
+    }
 
     AcmeOutcome {
         http_status: 201,
-        body_json: serde_json::json!({ "status": "valid", "contact": contact }).to_string(),
+        body_json: account_json(&account),
+        replay_nonce: Some(nonce),
+        location: Some(account_url(ctx, &id)),
+        content_type: "application/json".to_string(),
+    }
+}
+
+fn account_json(account: &AcmeAccount) -> String {
+    let status = match account.status {
+        AcmeAccountStatus::Valid       => "valid",
+        AcmeAccountStatus::Deactivated => "deactivated",
+        AcmeAccountStatus::Revoked     => "revoked",
+    };
+    serde_json::json!({ "status": status, "contact": account.contact }).to_string()
+}
+
+/// POST /acme/account/{id}: POST-as-GET, contact update or deactivation (RFC 8555 §7.3.2, §7.3.6).
+fn handle_account(ctx: &AcmeContext, account_id: &str, req: &AcmeRequest, nonce: String) -> AcmeOutcome {
+    let tenant = &ctx.config.tenant_id;
+
+    macro_rules! err {
+        ($status:expr, $type:expr, $msg:expr) => {
+            return AcmeOutcome {
+                http_status: $status,
+                body_json: acme_error($type, $msg),
+                replay_nonce: Some(nonce),
+                location: None,
+                content_type: "application/problem+json".to_string(),
+            }
+        };
+    }
+
+    let mut account = match &req.account {
+        Some(a) if a.id == account_id => a.clone(),
+        _ => err!(403, "unauthorized", "kid does not match the account URL"),
+    };
+
+    if let Some(payload) = req.payload() {
+        if let Some(contact) = payload.get("contact") {
+            match serde_json::from_value::<Vec<String>>(contact.clone()) {
+                Ok(c) => account.contact = c,
+                Err(_) => err!(400, "malformed", "contact must be an array of URLs"),
+            }
+        }
+        match payload.get("status").and_then(|s| s.as_str()) {
+            None => {}
+            Some("deactivated") => account.status = AcmeAccountStatus::Deactivated,
+            Some(_) => err!(400, "malformed", "status may only be set to deactivated"),
+        }
+
+        let store = match OxPersistenceCertStore::open(ctx.config.store.db_path()) {
+            Ok(s) => s,
+            Err(e) => err!(500, "serverInternal", &e.to_string()),
+        };
+        if let Err(e) = store.store_acme_account(tenant, &account) {
+            err!(500, "serverInternal", &e.to_string());
+        }
+    }
+
+    AcmeOutcome {
+        http_status: 200,
+        body_json: account_json(&account),
+        replay_nonce: Some(nonce),
+        location: Some(account_url(ctx, &account.id)),
+        content_type: "application/json".to_string(),
+    }
+}
+
+/// POST /acme/key-change: account key rollover (RFC 8555 §7.3.5).
+///
+/// The outer JWS is signed by the current key (`kid`); its payload is an inner
+/// JWS signed by the new key (`jwk`) over `{account, oldKey}`.
+fn handle_key_change(ctx: &AcmeContext, req: &AcmeRequest, nonce: String) -> AcmeOutcome {
+    let tenant = &ctx.config.tenant_id;
+
+    macro_rules! err {
+        ($status:expr, $type:expr, $msg:expr) => {
+            return AcmeOutcome {
+                http_status: $status,
+                body_json: acme_error($type, $msg),
+                replay_nonce: Some(nonce),
+                location: None,
+                content_type: "application/problem+json".to_string(),
+            }
+        };
+    }
+
+    let Some(mut account) = req.account.clone() else {
+        err!(400, "malformed", "key-change must be signed by an account (kid)");
+    };
+    let inner = match std::str::from_utf8(&req.jws.payload).ok().map(jws::parse) {
+        Some(Ok(j)) => j,
+        Some(Err(e)) => err!(400, "malformed", &format!("inner JWS: {}", e)),
+        None => err!(400, "malformed", "payload must be a JWS signed by the new key"),
+    };
+    let new_jwk = match (&inner.jwk, &inner.kid, &inner.nonce) {
+        (Some(jwk), None, None) => jwk.clone(),
+        _ => err!(400, "malformed", "inner JWS must carry jwk and no kid or nonce"),
+    };
+    if inner.url != req.jws.url {
+        err!(400, "malformed", "inner JWS url must match the outer url");
+    }
+    if let Err(e) = inner.verify(&new_jwk) {
+        err!(400, "malformed", &format!("inner JWS: {}", e));
+    }
+
+    let key_change = inner.payload_json().unwrap_or_default();
+    if key_change.get("account").and_then(|a| a.as_str()) != Some(account_url(ctx, &account.id).as_str()) {
+        err!(400, "malformed", "account does not match the outer kid");
+    }
+    let old_key = key_change.get("oldKey").and_then(|k| canonical_jwk(&k.to_string()));
+    if old_key.is_none() || old_key != canonical_jwk(&account.jwk) {
+        err!(400, "malformed", "oldKey is not the current account key");
+    }
+    let new_key = match canonical_jwk(&new_jwk.to_string()) {
+        Some(k) => k,
+        None => err!(400, "malformed", "unsupported jwk"),
+    };
+
+    let store = match OxPersistenceCertStore::open(ctx.config.store.db_path()) {
+        Ok(s) => s,
+        Err(e) => err!(500, "serverInternal", &e.to_string()),
+    };
+    match store.find_acme_account_by_jwk(tenant, &new_key) {
+        Ok(Some(existing)) => return AcmeOutcome {
+            http_status: 409,
+            body_json: acme_error("conflict", "new key is already in use by an account"),
+            replay_nonce: Some(nonce),
+            location: Some(account_url(ctx, &existing.id)),
+            content_type: "application/problem+json".to_string(),
+        },
+        Ok(None) => {}
+        Err(e) => err!(500, "serverInternal", &e.to_string()),
+    }
+
+    account.jwk = new_key;
+    if let Err(e) = store.store_acme_account(tenant, &account) {
+        err!(500, "serverInternal", &e.to_string());
+    }
+
+    AcmeOutcome {
+        http_status: 200,
+        body_json: account_json(&account),
         replay_nonce: Some(nonce),
-        location: Some(format!("{}/acme/account/{}", base, id)),
+        location: Some(account_url(ctx, &account.id)),
         content_type: "application/json".to_string(),
     }
 }
 
-fn handle_new_order(ctx: &AcmeContext, body: &str, nonce: String) -> AcmeOutcome {
+fn handle_new_order(ctx: &AcmeContext, req: &AcmeRequest, nonce: String) -> AcmeOutcome {
     let tenant = &ctx.config.tenant_id;
     let base   = base_url(ctx);
 
@@ -401,9 +782,9 @@ fn handle_new_order(ctx: &AcmeContext, body: &str, nonce: String) -> AcmeOutcome
         };
     }
 
-    let payload = match extract_jws_payload(body) {
+    let payload = match req.payload() {
         Some(p) => p,
-        None => err!(400, "malformed", "invalid JWS"),
+        None => err!(400, "malformed", "payload must be a JSON object"),
     };
     let identifiers: Vec<serde_json::Value> = payload.get("identifiers")
         .and_then(|i| i.as_array()).cloned().unwrap_or_default();
@@ -414,6 +795,21 @@ fn handle_new_order(ctx: &AcmeContext, body: &str, nonce: String) -> AcmeOutcome
         Err(e) => err!(500, "serverInternal", &e.to_string()),
     };
 
+    let eab = match bound_eab_key(&store, tenant, req) {
+        Ok(k) => k,
+        Err((status, problem, detail)) => err!(status, problem, &detail),
+    };
+    if let Some(eab) = &eab {
+        for ident in &identifiers {
+            let ident_type = ident.get("type").and_then(|t| t.as_str()).unwrap_or("dns");
+            let value = ident.get("value").and_then(|v| v.as_str()).unwrap_or("");
+            if ident_type != "dns" || !eab.allows_identifier(value) {
+                err!(403, "rejectedIdentifier",
+                    &format!("'{}' is not permitted for this external account", value));
+            }
+        }
+    }
+
     let now = OffsetDateTime::now_utc();
     let order_id = Uuid::new_v4().to_string();
     let mut acme_identifiers = Vec::new();
@@ -427,6 +823,7 @@ fn handle_new_order(ctx: &AcmeContext, body: &str, nonce: String) -> AcmeOutcome
         let authz_id = Uuid::new_v4().to_string();
         let http_token = Uuid::new_v4().to_string().replace('-', "");
         let dns_token  = Uuid::new_v4().to_string().replace('-', "");
+        let alpn_token = Uuid::new_v4().to_string().replace('-', "");
 
         let challenges = vec![
             AcmeChallenge {
@@ -445,6 +842,14 @@ fn handle_new_order(ctx: &AcmeContext, body: &str, nonce: String) -> AcmeOutcome
                 validated_at: None,
                 error: None,
             },
+            AcmeChallenge {
+                id: Uuid::new_v4().to_string(),
+                challenge_type: ChallengeType::TlsAlpn01,
+                token: alpn_token,
+                status: AcmeChallengeStatus::Pending,
+                validated_at: None,
+                error: None,
+            },
         ];
 
         let authz = AcmeAuthorization {
@@ -464,7 +869,7 @@ fn handle_new_order(ctx: &AcmeContext, body: &str, nonce: String) -> AcmeOutcome
     let order = AcmeOrder {
         id: order_id.clone(),
         tenant_id: tenant.clone(),
-        account_id: extract_jws_kid(body).unwrap_or_else(|| "unknown".to_string()),
+        account_id: req.account_id().unwrap_or_default().to_string(),
         status: AcmeOrderStatus::Pending,
         identifiers: acme_identifiers,
         not_before: None,
@@ -490,7 +895,7 @@ fn handle_new_order(ctx: &AcmeContext, body: &str, nonce: String) -> AcmeOutcome
     }
 }
 
-fn handle_get_order(ctx: &AcmeContext, order_id: &str, nonce: String) -> AcmeOutcome {
+fn handle_get_order(ctx: &AcmeContext, order_id: &str, req: &AcmeRequest, nonce: String) -> AcmeOutcome {
     let tenant = &ctx.config.tenant_id;
     let store = match OxPersistenceCertStore::open(ctx.config.store.db_path()) {
         Ok(s) => s,
@@ -500,6 +905,10 @@ fn handle_get_order(ctx: &AcmeContext, order_id: &str, nonce: String) -> AcmeOut
         },
     };
     match store.get_acme_order(tenant, order_id) {
+        Ok(Some(order)) if Some(order.account_id.as_str()) != req.account_id() => AcmeOutcome {
+            http_status: 403, body_json: acme_error("unauthorized", "order belongs to another account"),
+            replay_nonce: Some(nonce), location: None, content_type: "application/problem+json".to_string(),
+        },
         Ok(Some(order)) => AcmeOutcome {
             http_status: 200,
             body_json: serde_json::to_string(&order).unwrap_or_default(),
@@ -516,7 +925,7 @@ fn handle_get_order(ctx: &AcmeContext, order_id: &str, nonce: String) -> AcmeOut
     }
 }
 
-fn handle_get_authz(ctx: &AcmeContext, authz_id: &str, nonce: String) -> AcmeOutcome {
+fn handle_get_authz(ctx: &AcmeContext, authz_id: &str, req: &AcmeRequest, nonce: String) -> AcmeOutcome {
     let tenant = &ctx.config.tenant_id;
     let base   = base_url(ctx);
     let store = match OxPersistenceCertStore::open(ctx.config.store.db_path()) {
@@ -527,6 +936,10 @@ fn handle_get_authz(ctx: &AcmeContext, authz_id: &str, nonce: String) -> AcmeOut
         },
     };
     match store.get_acme_authorization(tenant, authz_id) {
+        Ok(Some(authz)) if !owns_order(&store, tenant, req, &authz.order_id) => AcmeOutcome {
+            http_status: 403, body_json: acme_error("unauthorized", "authorization belongs to another account"),
+            replay_nonce: Some(nonce), location: None, content_type: "application/problem+json".to_string(),
+        },
         Ok(Some(authz)) => {
             // Build JSON with challenge URLs embedded
             let mut authz_json = serde_json::to_value(&authz).unwrap_or_default();
@@ -560,7 +973,7 @@ fn handle_challenge(
     ctx: &AcmeContext,
     authz_id: &str,
     challenge_idx: usize,
-    body: &str,
+    req: &AcmeRequest,
     nonce: String,
 ) -> AcmeOutcome {
     let tenant  = &ctx.config.tenant_id;
@@ -589,6 +1002,9 @@ fn handle_challenge(
         Ok(None) => err!(404, "notFound", "authorization not found"),
         Err(e)  => err!(500, "serverInternal", &e.to_string()),
     };
+    if !owns_order(&store, tenant, req, &authz.order_id) {
+        err!(403, "unauthorized", "authorization belongs to another account");
+    }
 
     if challenge_idx >= authz.challenges.len() {
         err!(404, "notFound", "challenge not found");
@@ -611,15 +1027,9 @@ fn handle_challenge(
         };
     }
 
-    // Resolve account JWK for key-authorization computation
-    let kid = extract_jws_kid(body).unwrap_or_default();
-    // kid is the account URL; extract account ID (last path segment)
-    let account_id = kid.split('/').last().unwrap_or("").to_string();
-    let account_jwk = store.get_acme_account(tenant, &account_id)
-        .ok()
-        .flatten()
-        .map(|a| a.jwk)
-        .unwrap_or_default();
+    // The request was verified against the account key, which is also the
+    // key the key authorization is bound to
+    let account_jwk = req.jwk.to_string();
 
     let token = challenge.token.clone();
     let challenge_type = challenge.challenge_type.clone();
@@ -641,7 +1051,9 @@ fn handle_challenge(
             );
             validate_dns01(&domain, &dns_value)
         }
-        ChallengeType::TlsAlpn01 => false,
+        ChallengeType::TlsAlpn01 => {
+            tls_alpn::validate(&domain, ctx.config.tls_alpn_port, &key_auth, timeout)
+        }
     };
 
     let now = OffsetDateTime::now_utc();
@@ -717,7 +1129,7 @@ fn maybe_advance_order_to_ready(
     let _ = store.update_acme_order_status(tenant, order_id, AcmeOrderStatus::Ready);
 }
 
-fn handle_finalize(ctx: &AcmeContext, order_id: &str, body: &str, nonce: String) -> AcmeOutcome {
+fn handle_finalize(ctx: &AcmeContext, order_id: &str, req: &AcmeRequest, nonce: String) -> AcmeOutcome {
     let tenant = &ctx.config.tenant_id;
     let base   = base_url(ctx);
 
@@ -733,9 +1145,9 @@ fn handle_finalize(ctx: &AcmeContext, order_id: &str, body: &str, nonce: String)
         };
     }
 
-    let payload = match extract_jws_payload(body) {
+    let payload = match req.payload() {
         Some(p) => p,
-        None => err!(400, "malformed", "invalid JWS"),
+        None => err!(400, "malformed", "payload must be a JSON object"),
     };
 
     let store = match OxPersistenceCertStore::open(ctx.config.store.db_path()) {
@@ -748,6 +1160,9 @@ fn handle_finalize(ctx: &AcmeContext, order_id: &str, body: &str, nonce: String)
         Ok(None) => err!(404, "notFound", "order not found"),
         Err(e)  => err!(500, "serverInternal", &e.to_string()),
     };
+    if Some(order.account_id.as_str()) != req.account_id() {
+        err!(403, "unauthorized", "order belongs to another account");
+    }
 
     if order.status != AcmeOrderStatus::Ready && order.status != AcmeOrderStatus::Pending {
         err!(403, "orderNotReady", "order is not ready for finalization");
@@ -765,6 +1180,29 @@ fn handle_finalize(ctx: &AcmeContext, order_id: &str, body: &str, nonce: String)
         Err(e) => err!(400, "badCSR", &format!("base64 decode: {}", e)),
     };
 
+    // The CSR must name exactly the identifiers the order was authorized for
+    // (RFC 8555 §7.4); SAN types other than dNSName and iPAddress are refused
+    let Some(requested) = csr_identifiers(&csr_der) else {
+        err!(400, "badCSR", "CSR could not be parsed or requests an unsupported name type");
+    };
+    if identifier_set(&requested) != identifier_set(&order.identifiers) {
+        err!(400, "badCSR", "CSR identifiers do not match the order");
+    }
+    let eab = match bound_eab_key(&store, tenant, req) {
+        Ok(k) => k,
+        Err((status, problem, detail)) => err!(status, problem, &detail),
+    };
+    if let Some(eab) = &eab {
+        if let Some(ident) = requested.iter()
+            .find(|i| i.identifier_type != "dns" || !eab.allows_identifier(&i.value))
+        {
+            err!(403, "rejectedIdentifier",
+                &format!("'{}' is not permitted for this external account", ident.value));
+        }
+    }
+    let profile = eab.as_ref().and_then(|k| k.profile.clone()).unwrap_or_else(|| "acme".to_string());
+    let validity_days = eab.as_ref().and_then(|k| k.validity_days).unwrap_or(90);
+
     let csr_pem = pem::encode(&pem::Pem::new("CERTIFICATE REQUEST", csr_der));
 
     let ca_cert_pem = match std::fs::read_to_string(&ctx.config.ca_intermediate_cert_path) {
@@ -779,17 +1217,27 @@ fn handle_finalize(ctx: &AcmeContext, order_id: &str, body: &str, nonce: String)
         Ok(k) => k,
         Err(e) => err!(500, "serverInternal", &e.to_string()),
     };
-    let ca_key_pem = match ks.load_key_pem(tenant, &ctx.config.ca_intermediate_key_id) {
-        Ok(p) => p,
+    let ca_keypair = match KeyStoreSigner::new(ks.as_ref(), tenant, &ctx.config.ca_intermediate_key_id) {
+        Ok(k) => k,
         Err(e) => err!(500, "serverInternal", &e.to_string()),
     };
-    let ca_keypair = match rcgen::KeyPair::from_pem(&ca_key_pem) {
-        Ok(k) => k,
+
+    let ct_chain = match ct::issuer_chain(ctx.config.ct.as_ref(), &ca_cert_pem, &ctx.config.ca_root_cert_path) {
+        Ok(c) => c,
         Err(e) => err!(500, "serverInternal", &e.to_string()),
     };
+    let options = IssuanceOptions {
+        ct: ctx.config.ct.as_ref(),
+        ct_issuer_chain: &ct_chain,
+        crl_partitions: ctx.config.crl_partitions.as_ref(),
+        ..Default::default()
+    };
 
-    let cert_record = match sign_csr(&csr_pem, tenant, "acme", 90 * 86400, None, &issuer_params, &ca_keypair) {
+    let cert_record = match sign_csr(
+        &csr_pem, tenant, &profile, validity_days as u64 * 86400, None, &issuer_params, &ca_keypair, &options,
+    ) {
         Ok(r) => r,
+        Err(CertError::CtFailure(msg)) => err!(500, "serverInternal", &format!("Certificate Transparency: {}", msg)),
         Err(e) => err!(400, "badCSR", &e.to_string()),
     };
 
@@ -798,7 +1246,7 @@ fn handle_finalize(ctx: &AcmeContext, order_id: &str, body: &str, nonce: String)
 
     order.status = AcmeOrderStatus::Valid;
     order.certificate_serial = Some(serial.clone());
-    let _ = store.update_acme_order_status(tenant, order_id, AcmeOrderStatus::Valid);
+    let _ = store.store_acme_order(tenant, &order);
 
     let now = OffsetDateTime::now_utc();
     let _ = store.store_audit_event(tenant, &AuditEvent {
@@ -819,7 +1267,7 @@ fn handle_finalize(ctx: &AcmeContext, order_id: &str, body: &str, nonce: String)
     }
 }
 
-fn handle_get_cert(ctx: &AcmeContext, order_id: &str, nonce: String) -> AcmeOutcome {
+fn handle_get_cert(ctx: &AcmeContext, order_id: &str, req: &AcmeRequest, nonce: String) -> AcmeOutcome {
     let tenant = &ctx.config.tenant_id;
     let store = match OxPersistenceCertStore::open(ctx.config.store.db_path()) {
         Ok(s) => s,
@@ -839,6 +1287,12 @@ fn handle_get_cert(ctx: &AcmeContext, order_id: &str, nonce: String) -> AcmeOutc
             replay_nonce: Some(nonce), location: None, content_type: "application/problem+json".to_string(),
         },
     };
+    if Some(order.account_id.as_str()) != req.account_id() {
+        return AcmeOutcome {
+            http_status: 403, body_json: acme_error("unauthorized", "order belongs to another account"),
+            replay_nonce: Some(nonce), location: None, content_type: "application/problem+json".to_string(),
+        };
+    }
     if order.status != AcmeOrderStatus::Valid {
         return AcmeOutcome {
             http_status: 403, body_json: acme_error("orderNotReady", "certificate not yet issued"),
@@ -870,7 +1324,7 @@ fn handle_get_cert(ctx: &AcmeContext, order_id: &str, nonce: String) -> AcmeOutc
     }
 }
 
-fn handle_revoke_cert(ctx: &AcmeContext, body: &str, nonce: String) -> AcmeOutcome {
+fn handle_revoke_cert(ctx: &AcmeContext, req: &AcmeRequest, nonce: String) -> AcmeOutcome {
     let tenant = &ctx.config.tenant_id;
 
     macro_rules! err {
@@ -885,9 +1339,9 @@ fn handle_revoke_cert(ctx: &AcmeContext, body: &str, nonce: String) -> AcmeOutco
         };
     }
 
-    let payload = match extract_jws_payload(body) {
+    let payload = match req.payload() {
         Some(p) => p,
-        None => err!(400, "malformed", "invalid JWS"),
+        None => err!(400, "malformed", "payload must be a JSON object"),
     };
     let cert_b64 = match payload.get("certificate").and_then(|c| c.as_str()) {
         Some(c) => c,
@@ -900,6 +1354,14 @@ fn handle_revoke_cert(ctx: &AcmeContext, body: &str, nonce: String) -> AcmeOutco
         Err(e) => err!(400, "malformed", &format!("base64: {}", e)),
     };
 
+    let reason = match payload.get("reason") {
+        None => RevocationReason::Unspecified,
+        Some(code) => match code.as_u64().and_then(subscriber_revocation_reason) {
+            Some(r) => r,
+            None => err!(400, "badRevocationReason", "reason must be one of 0, 1, 3, 4 or 5"),
+        },
+    };
+
     let serial = parse_serial_from_cert_der(&cert_der).unwrap_or_default();
     if serial.is_empty() { err!(400, "malformed", "cannot parse cert serial"); }
 
@@ -907,12 +1369,42 @@ fn handle_revoke_cert(ctx: &AcmeContext, body: &str, nonce: String) -> AcmeOutco
         Ok(s) => s,
         Err(e) => err!(500, "serverInternal", &e.to_string()),
     };
+    let record = match store.get_cert_by_serial(tenant, &serial) {
+        Ok(Some(r)) => r,
+        Ok(None) => err!(404, "notFound", "certificate not found"),
+        Err(e) => err!(500, "serverInternal", &e.to_string()),
+    };
+    // Anyone can mint a certificate carrying an issued serial, so only the
+    // exact certificate this CA signed identifies what is being revoked
+    if ::pem::parse(&record.pem).ok().map(|p| p.into_contents()).as_deref() != Some(cert_der.as_slice()) {
+        err!(404, "notFound", "certificate not found");
+    }
+    if record.status == CertStatus::Revoked {
+        err!(400, "alreadyRevoked", "certificate is already revoked");
+    }
+
+    // RFC 8555 §7.6: the certificate key, the account that ordered the
+    // certificate, or an account authorized for every name in it
+    let authorized = match &req.account {
+        None => cert_key_matches_jwk(&cert_der, &req.jwk),
+        Some(account) => match account_may_revoke(&store, tenant, &account.id, &serial, &cert_der) {
+            Ok(b) => b,
+            Err(e) => err!(500, "serverInternal", &e.to_string()),
+        },
+    };
+    if !authorized {
+        err!(403, "unauthorized", "requester is not authorized to revoke this certificate");
+    }
+
     let now = OffsetDateTime::now_utc();
-    let _ = store.mark_revoked(tenant, &serial, ox_cert_core::model::RevocationReason::Unspecified, now);
+    if let Err(e) = store.mark_revoked(tenant, &serial, reason, now) {
+        err!(500, "serverInternal", &e.to_string());
+    }
     let _ = store.store_audit_event(tenant, &AuditEvent {
         id: 0, tenant_id: tenant.clone(), timestamp: now,
         action: AuditAction::AcmeRevoke, serial: Some(serial),
-        actor: String::new(), details: serde_json::json!({}),
+        actor: req.account_id().unwrap_or_default().to_string(),
+        details: serde_json::json!({ "reason": reason as u8 }),
     });
 
     AcmeOutcome {
@@ -922,6 +1414,194 @@ fn handle_revoke_cert(ctx: &AcmeContext, body: &str, nonce: String) -> AcmeOutco
     }
 }
 
+/// Reason codes a subscriber may give when revoking (RFC 5280 §5.3.1).
+/// CA-side reasons such as `cACompromise` or `certificateHold` are refused.
+fn subscriber_revocation_reason(code: u64) -> Option<RevocationReason> {
+    match code {
+        0 => Some(RevocationReason::Unspecified),
+        1 => Some(RevocationReason::KeyCompromise),
+        3 => Some(RevocationReason::AffiliationChanged),
+        4 => Some(RevocationReason::Superseded),
+        5 => Some(RevocationReason::CessationOfOperation),
+        _ => None,
+    }
+}
+
+/// Whether the account ordered the certificate, or holds a valid, unexpired
+/// authorization for every identifier it names.
+fn account_may_revoke(
+    store: &OxPersistenceCertStore,
+    tenant: &str,
+    account_id: &str,
+    serial: &str,
+    cert_der: &[u8],
+) -> Result<bool, ox_cert_core::CertError> {
+    if store.find_acme_order_by_cert_serial(tenant, serial)?.is_some_and(|o| o.account_id == account_id) {
+        return Ok(true);
+    }
+    let Some(names) = cert_identifiers(cert_der) else { return Ok(false) };
+    let now = OffsetDateTime::now_utc();
+    let authzs = store.list_acme_authorizations_for_account(tenant, account_id)?;
+    Ok(!names.is_empty() && names.iter().all(|name| authzs.iter().any(|a| {
+        a.status == AcmeAuthzStatus::Valid && a.expires > now && a.identifier_value.eq_ignore_ascii_case(name)
+    })))
+}
+
+/// Subject CN, dNSName and iPAddress SANs of a DER certificate; `None` if it
+/// cannot be parsed or names anything an ACME authorization cannot cover.
+fn cert_identifiers(der: &[u8]) -> Option<Vec<String>> {
+    use x509_parser::prelude::*;
+
+    let (_, cert) = X509Certificate::from_der(der).ok()?;
+    let mut names: Vec<String> = cert.subject().iter_common_name()
+        .filter_map(|cn| cn.as_str().ok().map(|s| s.to_string()))
+        .collect();
+    if let Ok(Some(san)) = cert.subject_alternative_name() {
+        for name in &san.value.general_names {
+            match name {
+                GeneralName::DNSName(dns) => names.push(dns.to_string()),
+                GeneralName::IPAddress(ip) => names.push(ip_to_string(ip)?),
+                _ => return None,
+            }
+        }
+    }
+    Some(names)
+}
+
+fn ip_to_string(bytes: &[u8]) -> Option<String> {
+    match bytes.len() {
+        4 => Some(std::net::Ipv4Addr::from(<[u8; 4]>::try_from(bytes).ok()?).to_string()),
+        16 => Some(std::net::Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?).to_string()),
+        _ => None,
+    }
+}
+
+fn cert_key_matches_jwk(der: &[u8], jwk: &serde_json::Value) -> bool {
+    use x509_parser::prelude::*;
+    X509Certificate::from_der(der)
+        .map(|(_, cert)| jws::jwk_matches_spki(jwk, cert.public_key()))
+        .unwrap_or(false)
+}
+
+/// GET /acme/renewal-info/{certID} (draft-ietf-acme-ari).
+///
+/// `certID` is `base64url(AKI keyIdentifier) "." base64url(serial)`. The
+/// suggested window spans two thirds to five sixths of the validity period;
+/// revoked certificates get a window in the past so clients renew at once.
+fn handle_renewal_info(ctx: &AcmeContext, cert_id: &str, nonce: String) -> AcmeOutcome {
+    let tenant = &ctx.config.tenant_id;
+
+    macro_rules! err {
+        ($status:expr, $type:expr, $msg:expr) => {
+            return AcmeOutcome {
+                http_status: $status,
+                body_json: acme_error($type, $msg),
+                replay_nonce: Some(nonce),
+                location: None,
+                content_type: "application/problem+json".to_string(),
+            }
+        };
+    }
+
+    let (aki, serial) = match cert_id.split_once('.')
+        .and_then(|(a, s)| Some((jws::b64_decode(a)?, jws::b64_decode(s)?)))
+    {
+        Some(parts) => parts,
+        None => err!(400, "malformed", "certID must be base64url(keyIdentifier).base64url(serial)"),
+    };
+    // DER integers gain a leading zero when the high bit is set
+    let serial = serial.strip_prefix(&[0u8]).unwrap_or(&serial);
+    let serial = match <[u8; 16]>::try_from(serial) {
+        Ok(bytes) => Uuid::from_bytes(bytes).to_string(),
+        Err(_) => err!(404, "notFound", "certificate not found"),
+    };
+
+    let store = match OxPersistenceCertStore::open(ctx.config.store.db_path()) {
+        Ok(s) => s,
+        Err(e) => err!(500, "serverInternal", &e.to_string()),
+    };
+    let record = match store.get_cert_by_serial(tenant, &serial) {
+        Ok(Some(r)) => r,
+        Ok(None) => err!(404, "notFound", "certificate not found"),
+        Err(e) => err!(500, "serverInternal", &e.to_string()),
+    };
+    if cert_authority_key_id(&record.pem).as_deref() != Some(aki.as_slice()) {
+        err!(404, "notFound", "certificate not found");
+    }
+
+    let now = OffsetDateTime::now_utc();
+    let (start, end) = if record.status == CertStatus::Revoked {
+        (now - time::Duration::hours(2), now - time::Duration::hours(1))
+    } else {
+        let lifetime = record.not_after - record.not_before;
+        (record.not_before + lifetime * 2 / 3, record.not_before + lifetime * 5 / 6)
+    };
+    let rfc3339 = |t: OffsetDateTime| {
+        t.format(&time::format_description::well_known::Rfc3339).unwrap_or_default()
+    };
+
+    AcmeOutcome {
+        http_status: 200,
+        body_json: serde_json::json!({
+            "suggestedWindow": { "start": rfc3339(start), "end": rfc3339(end) },
+        }).to_string(),
+        replay_nonce: Some(nonce),
+        location: None,
+        content_type: "application/json".to_string(),
+    }
+}
+
+fn cert_authority_key_id(cert_pem: &str) -> Option<Vec<u8>> {
+    use x509_parser::prelude::*;
+    let der = ::pem::parse(cert_pem).ok()?.into_contents();
+    let (_, cert) = X509Certificate::from_der(&der).ok()?;
+    cert.extensions().iter().find_map(|ext| match ext.parsed_extension() {
+        ParsedExtension::AuthorityKeyIdentifier(aki) => aki.key_identifier.as_ref().map(|k| k.0.to_vec()),
+        _ => None,
+    })
+}
+
+/// Identifiers requested by a DER CSR: the subject CN and the dNSName and
+/// iPAddress SANs. `None` if the CSR does not parse or asks for any other SAN
+/// type, since ACME has no way to authorize URI, email or directory names.
+fn csr_identifiers(der: &[u8]) -> Option<Vec<AcmeIdentifier>> {
+    use x509_parser::prelude::*;
+
+    let ident = |identifier_type: &str, value: String| AcmeIdentifier {
+        identifier_type: identifier_type.to_string(), value,
+    };
+    let (_, csr) = X509CertificationRequest::from_der(der).ok()?;
+    let info = &csr.certification_request_info;
+    let mut idents: Vec<AcmeIdentifier> = info.subject.iter_common_name()
+        .filter_map(|cn| cn.as_str().ok())
+        .map(|cn| match cn.parse::<std::net::IpAddr>() {
+            Ok(ip) => ident("ip", ip.to_string()),
+            Err(_) => ident("dns", cn.to_string()),
+        })
+        .collect();
+    for ext in csr.requested_extensions().into_iter().flatten() {
+        match ext {
+            ParsedExtension::SubjectAlternativeName(san) => for name in &san.general_names {
+                match name {
+                    GeneralName::DNSName(dns) => idents.push(ident("dns", dns.to_string())),
+                    GeneralName::IPAddress(ip) => idents.push(ident("ip", ip_to_string(ip)?)),
+                    _ => return None,
+                }
+            },
+            ParsedExtension::ParseError { .. } => return None,
+            _ => {}
+        }
+    }
+    Some(idents)
+}
+
+/// Identifiers compared case-insensitively with duplicates removed.
+fn identifier_set(idents: &[AcmeIdentifier]) -> std::collections::BTreeSet<(String, String)> {
+    idents.iter()
+        .map(|i| (i.identifier_type.to_ascii_lowercase(), i.value.to_ascii_lowercase()))
+        .collect()
+}
+
 fn parse_serial_from_cert_der(der: &[u8]) -> Option<String> {
     use x509_parser::prelude::*;
     let (_, cert) = X509Certificate::from_der(der).ok()?;
@@ -0,0 +1,142 @@
+//! tls-alpn-01 challenge validation (RFC 8737).
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{CertificateError, ClientConfig, ClientConnection, DigitallySignedStruct, SignatureScheme};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const ACME_TLS_ALPN: &[u8] = b"acme-tls/1";
+
+/// id-pe-acmeIdentifier
+const ACME_IDENTIFIER_OID: &str = "1.3.6.1.5.5.7.1.31";
+
+/// The validation certificate is self-signed and is checked by
+/// `check_validation_cert`, not against a trust chain. Handshake signatures are
+/// still verified so the responder must hold the certificate key. webpki
+/// refuses to parse certificates with the critical acmeIdentifier extension,
+/// so the key is taken from the certificate with x509-parser instead.
+#[derive(Debug)]
+struct AcceptAnyCert(Arc<CryptoProvider>);
+
+impl AcceptAnyCert {
+    fn verify_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        use x509_parser::prelude::*;
+
+        let (_, parsed) = X509Certificate::from_der(cert.as_ref())
+            .map_err(|_| rustls::Error::InvalidCertificate(CertificateError::BadEncoding))?;
+        let public_key = parsed.public_key().subject_public_key.data.as_ref();
+        let algs = self.0.signature_verification_algorithms.mapping.iter()
+            .find(|(scheme, _)| *scheme == dss.scheme)
+            .map(|(_, algs)| *algs)
+            .unwrap_or_default();
+        if algs.iter().any(|alg| alg.verify_signature(public_key, message, dss.signature()).is_ok()) {
+            Ok(HandshakeSignatureValid::assertion())
+        } else {
+            Err(rustls::Error::InvalidCertificate(CertificateError::BadSignature))
+        }
+    }
+}
+
+impl ServerCertVerifier for AcceptAnyCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.verify_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.verify_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// Connect to `domain:port` offering only `acme-tls/1` and check the
+/// certificate the responder presents.
+pub fn validate(domain: &str, port: u16, key_auth: &str, timeout_secs: u64) -> bool {
+    fetch_validation_cert(domain, port, timeout_secs)
+        .is_some_and(|der| check_validation_cert(&der, domain, key_auth))
+}
+
+fn fetch_validation_cert(domain: &str, port: u16, timeout_secs: u64) -> Option<Vec<u8>> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut config = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions().ok()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(AcceptAnyCert(provider)))
+        .with_no_client_auth();
+    config.alpn_protocols = vec![ACME_TLS_ALPN.to_vec()];
+
+    let server_name = ServerName::try_from(domain.to_string()).ok()?;
+    let mut conn = ClientConnection::new(Arc::new(config), server_name).ok()?;
+
+    let timeout = Duration::from_secs(timeout_secs);
+    let mut sock = (domain, port).to_socket_addrs().ok()?
+        .find_map(|addr| TcpStream::connect_timeout(&addr, timeout).ok())?;
+    sock.set_read_timeout(Some(timeout)).ok()?;
+    sock.set_write_timeout(Some(timeout)).ok()?;
+
+    while conn.is_handshaking() {
+        conn.complete_io(&mut sock).ok()?;
+    }
+    // The responder must have negotiated the ACME protocol, not just any TLS
+    if conn.alpn_protocol() != Some(ACME_TLS_ALPN) {
+        return None;
+    }
+    conn.peer_certificates()?.first().map(|c| c.as_ref().to_vec())
+}
+
+/// RFC 8737 §3: exactly one dNSName SAN equal to the identifier, and a
+/// critical acmeIdentifier extension holding SHA-256 of the key authorization.
+pub fn check_validation_cert(der: &[u8], domain: &str, key_auth: &str) -> bool {
+    use sha2::Digest;
+    use x509_parser::prelude::*;
+
+    let Ok((_, cert)) = X509Certificate::from_der(der) else { return false };
+
+    let san_ok = match cert.subject_alternative_name() {
+        Ok(Some(san)) => matches!(
+            san.value.general_names.as_slice(),
+            [GeneralName::DNSName(name)] if name.eq_ignore_ascii_case(domain)
+        ),
+        _ => false,
+    };
+
+    // extnValue is the DER OCTET STRING wrapping the 32-byte digest
+    let mut expected = vec![0x04, 0x20];
+    expected.extend_from_slice(&sha2::Sha256::digest(key_auth.as_bytes()));
+    let ext_ok = cert.extensions().iter().any(|ext| {
+        ext.oid.to_id_string() == ACME_IDENTIFIER_OID && ext.critical && ext.value == expected.as_slice()
+    });
+
+    san_ok && ext_ok
+}
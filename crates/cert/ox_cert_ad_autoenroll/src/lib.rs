@@ -0,0 +1,1173 @@
+// ox_cert_ad_autoenroll — Windows autoenrollment over MS-XCEP and MS-WSTEP
+
+pub mod soap;
+
+#[cfg(test)]
+mod tests;
+
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use ox_cert_core::{
+    builder::{issuer_params_from_cert_pem, parse_csr, sign_csr},
+    model::{AuditAction, AuditEvent, CertStoreConfig, CrlPartitionConfig, CsrInfo, CtConfig, CustomExtension,
+            EnrollmentProfile, EnrollmentProtocol, IssuancePolicy, IssuancePolicyConfig, KeyStoreConfig,
+            KeyType, SanType},
+    ct, open_keystore,
+    store::{CertStore, OxPersistenceCertStore},
+    CertError, IssuanceOptions, KeyStoreSigner,
+};
+use ox_security_auth::{KerberosAuthDriver, KerberosConfig, KerberosTicketValidatorFn, LdapAuthDriver, LdapConfig};
+use ox_security_core::{AuthDriver, AuthPipelineContext, AuthResult, Credentials, TenantId};
+use serde::Deserialize;
+use time::OffsetDateTime;
+use x509_parser::prelude::FromDer;
+use yasna::models::ObjectIdentifier;
+use yasna::Tag;
+
+// ---------------------------------------------------------------------------
+// Config
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Deserialize)]
+pub struct AdAutoenrollConfig {
+    pub tenant_id: String,
+    pub store: CertStoreConfig,
+    pub keystore: KeyStoreConfig,
+    pub ca_intermediate_key_id: String,
+    pub ca_intermediate_cert_path: String,
+    pub ca_root_cert_path: String,
+    /// Certificate Transparency for issued certificates.
+    #[serde(default)]
+    pub ct: Option<CtConfig>,
+    /// Partitioned CRLs for issued certificates; must match `ox_cert_crl`.
+    #[serde(default)]
+    pub crl_partitions: Option<CrlPartitionConfig>,
+    pub auth_mode: AdAuthMode,
+    /// Active Directory DNS domain (e.g. "corp.example.com"). Its upper-case
+    /// form is the Kerberos realm.
+    pub domain: String,
+    /// Service principal and keytab. Required in Kerberos mode.
+    #[serde(default)]
+    pub kerberos: Option<AdKerberosConfig>,
+    /// Directory holding the requesters' computer accounts, which supply group
+    /// membership and the issued names. Required.
+    #[serde(default)]
+    pub ldap: Option<AdLdapConfig>,
+    /// Public URL of the WSTEP endpoint, advertised to clients in the policy.
+    pub enrollment_uri: String,
+    /// Policy ID reported to clients. Defaults to `ox-cert-{tenant_id}`.
+    #[serde(default)]
+    pub policy_id: Option<String>,
+    /// How long clients may cache the policy.
+    #[serde(default = "default_next_update_hours")]
+    pub next_update_hours: u32,
+    pub policy: IssuancePolicyConfig,
+    /// Profiles referenced by `templates`.
+    pub profiles: Vec<EnrollmentProfile>,
+    pub templates: Vec<AdTemplate>,
+}
+
+fn default_next_update_hours() -> u32 { 8 }
+fn default_group_attr() -> String { "memberOf".to_string() }
+fn default_renewal_days() -> u32 { 42 }
+fn default_subject_name_flags() -> u32 {
+    SUBJECT_REQUIRE_DNS_AS_CN | SUBJECT_ALT_REQUIRE_DNS
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum AdAuthMode {
+    /// The TLS client certificate identifies the requester: its CN is looked up as
+    /// the `dNSHostName` of a computer account.
+    ClientCert,
+    /// SPNEGO (`Authorization: Negotiate`); the principal is looked up by `sAMAccountName`.
+    Kerberos,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdKerberosConfig {
+    /// e.g. "HTTP/pki.corp.example.com@CORP.EXAMPLE.COM"
+    pub service_principal: String,
+    pub keytab_path: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdLdapConfig {
+    pub url: String,
+    pub base_dn: String,
+    /// Service account used for group lookups.
+    pub bind_dn: String,
+    /// Environment variable holding the service account password.
+    pub bind_password_env: String,
+    #[serde(default = "default_group_attr")]
+    pub group_attr: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AdTemplate {
+    /// Template common name, as shown to clients.
+    pub name: String,
+    /// Template OID (e.g. "1.3.6.1.4.1.311.21.8.1.1").
+    pub oid: String,
+    /// Directory group whose members may enroll.
+    pub autoenroll_group: String,
+    /// Entry in `profiles` that supplies validity, key types and usages.
+    pub profile: String,
+    /// Days before expiry at which clients renew.
+    #[serde(default = "default_renewal_days")]
+    pub renewal_days: u32,
+    /// msPKI-Certificate-Name-Flag value sent to clients.
+    #[serde(default = "default_subject_name_flags")]
+    pub subject_name_flags: u32,
+}
+
+// msPKI-Certificate-Name-Flag bits (MS-CRTD §2.28)
+const SUBJECT_ALT_REQUIRE_DNS: u32 = 0x0800_0000;
+const SUBJECT_REQUIRE_DNS_AS_CN: u32 = 0x1000_0000;
+
+// msPKI-Enrollment-Flag: CT_FLAG_AUTO_ENROLLMENT (MS-CRTD §2.26)
+const ENROLLMENT_FLAG_AUTO_ENROLLMENT: u32 = 0x20;
+
+// XCEP clientAuthentication values (MS-XCEP §3.1.4.1.3.4)
+const CLIENT_AUTH_KERBEROS: u32 = 2;
+const CLIENT_AUTH_CERTIFICATE: u32 = 8;
+
+// OID groups (MS-XCEP §3.1.4.1.3.16)
+const OID_GROUP_HASH: u32 = 1;
+const OID_GROUP_PUBKEY: u32 = 3;
+const OID_GROUP_EXTENSION: u32 = 6;
+const OID_GROUP_ENHANCED_KEY_USAGE: u32 = 7;
+const OID_GROUP_TEMPLATE: u32 = 9;
+
+const OID_SHA256: &str = "2.16.840.1.101.3.4.2.1";
+const OID_KEY_USAGE: &str = "2.5.29.15";
+const OID_EXTENDED_KEY_USAGE: &str = "2.5.29.37";
+/// szOID_CERTIFICATE_TEMPLATE: template OID and revision.
+const OID_CERTIFICATE_TEMPLATE: &str = "1.3.6.1.4.1.311.21.7";
+/// szOID_APPLICATION_CERT_POLICIES: Microsoft's copy of the EKU list.
+const OID_APPLICATION_POLICIES: &str = "1.3.6.1.4.1.311.21.10";
+/// szOID_ENROLL_CERTTYPE_EXTENSION: v1 template name as a BMPString.
+const OID_ENROLL_CERTTYPE: &str = "1.3.6.1.4.1.311.20.2";
+
+const OID_PKCS7_DATA: &[u64] = &[1, 2, 840, 113549, 1, 7, 1];
+const OID_PKCS7_SIGNED_DATA: &[u64] = &[1, 2, 840, 113549, 1, 7, 2];
+
+/// Template revision reported in policies and the template extension.
+const TEMPLATE_MAJOR_REVISION: u32 = 100;
+
+// ---------------------------------------------------------------------------
+// Context
+// ---------------------------------------------------------------------------
+
+pub struct AdAutoenrollContext {
+    config: AdAutoenrollConfig,
+    kerberos: Option<KerberosAuthDriver>,
+    directory: Option<LdapAuthDriver>,
+    ldap_password: String,
+    runtime: tokio::runtime::Runtime,
+}
+
+impl AdAutoenrollContext {
+    /// Validate the config and set up the directory connection and, in Kerberos
+    /// mode, the ticket validator.
+    pub fn new(config: AdAutoenrollConfig) -> Result<Self, CertError> {
+        let kerberos = match config.auth_mode {
+            AdAuthMode::ClientCert => None,
+            AdAuthMode::Kerberos => {
+                let krb = config.kerberos.as_ref().ok_or_else(|| {
+                    CertError::Validation("auth_mode Kerberos needs a kerberos section".to_string())
+                })?;
+                let driver_config = KerberosConfig {
+                    service_principal: krb.service_principal.clone(),
+                    keytab_path: krb.keytab_path.clone(),
+                    realm: config.domain.to_uppercase(),
+                    tenant_id: TenantId::from(config.tenant_id.as_str()),
+                };
+                Some(KerberosAuthDriver::new(driver_config, gssapi_validator(krb)?))
+            }
+        };
+        let directory = config.ldap.as_ref().map(|l| LdapAuthDriver::new(LdapConfig {
+            url: l.url.clone(),
+            bind_dn_template: l.bind_dn.clone(),
+            base_dn: l.base_dn.clone(),
+            group_attr: l.group_attr.clone(),
+            tenant_id: TenantId::from(config.tenant_id.as_str()),
+        }));
+        Self::with_drivers(config, kerberos, directory)
+    }
+
+    /// Like `new`, with the Kerberos and LDAP drivers supplied by the caller.
+    pub fn with_drivers(
+        config: AdAutoenrollConfig,
+        kerberos: Option<KerberosAuthDriver>,
+        directory: Option<LdapAuthDriver>,
+    ) -> Result<Self, CertError> {
+        for template in &config.templates {
+            if !config.profiles.iter().any(|p| p.name == template.profile) {
+                return Err(CertError::Validation(format!(
+                    "template '{}' references unknown profile '{}'", template.name, template.profile
+                )));
+            }
+            oid_arcs(&template.oid)?;
+        }
+        if config.auth_mode == AdAuthMode::Kerberos && kerberos.is_none() {
+            return Err(CertError::Validation("auth_mode Kerberos needs a kerberos section".to_string()));
+        }
+        // Neither tickets nor client certificates carry groups or a trustworthy
+        // hostname, so every requester is resolved in the directory
+        let ldap = config.ldap.as_ref().filter(|_| directory.is_some()).ok_or_else(|| {
+            CertError::Validation("an ldap section is required to look up requesters".to_string())
+        })?;
+        let ldap_password = std::env::var(&ldap.bind_password_env).map_err(|_| {
+            CertError::Validation(format!("environment variable {} is not set", ldap.bind_password_env))
+        })?;
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| CertError::Storage(format!("failed to start runtime: {}", e)))?;
+        Ok(Self { config, kerberos, directory, ldap_password, runtime })
+    }
+
+    fn profile(&self, name: &str) -> Option<&EnrollmentProfile> {
+        self.config.profiles.iter().find(|p| p.name == name)
+    }
+
+    fn policy_id(&self) -> String {
+        self.config.policy_id.clone().unwrap_or_else(|| format!("ox-cert-{}", self.config.tenant_id))
+    }
+}
+
+/// Accept SPNEGO tokens with the system GSSAPI library. The acceptor reads
+/// its keytab from `KRB5_KTNAME`.
+#[cfg(feature = "kerberos")]
+fn gssapi_validator(krb: &AdKerberosConfig) -> Result<KerberosTicketValidatorFn, CertError> {
+    use cross_krb5::{AcceptFlags, K5ServerCtx, ServerCtx, Step};
+
+    std::env::set_var("KRB5_KTNAME", &krb.keytab_path);
+    let spn = krb.service_principal.clone();
+    Ok(std::sync::Arc::new(move |token: &[u8]| {
+        let pending = ServerCtx::new(AcceptFlags::empty(), Some(&spn)).map_err(|e| e.to_string())?;
+        match pending.step(token).map_err(|e| e.to_string())? {
+            Step::Finished((mut ctx, _)) => ctx.client().map_err(|e| e.to_string()),
+            // Windows sends a complete Kerberos AP-REQ; NTLM fallback would land here
+            Step::Continue(_) => Err("SPNEGO negotiation did not complete in one round".to_string()),
+        }
+    }))
+}
+
+#[cfg(not(feature = "kerberos"))]
+fn gssapi_validator(_krb: &AdKerberosConfig) -> Result<KerberosTicketValidatorFn, CertError> {
+    Err(CertError::Validation(
+        "auth_mode Kerberos needs ox_cert_ad_autoenroll built with the `kerberos` feature".to_string(),
+    ))
+}
+
+// ---------------------------------------------------------------------------
+// Response type
+// ---------------------------------------------------------------------------
+
+pub struct AdOutcome {
+    pub http_status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+    /// `WWW-Authenticate` challenge to send with a 401.
+    pub www_authenticate: Option<&'static str>,
+}
+
+impl AdOutcome {
+    fn text(status: u16, msg: &str) -> Self {
+        Self { http_status: status, content_type: "text/plain", body: msg.as_bytes().to_vec(), www_authenticate: None }
+    }
+
+    fn soap(status: u16, xml: String) -> Self {
+        Self {
+            http_status: status,
+            content_type: "application/soap+xml; charset=utf-8",
+            body: xml.into_bytes(),
+            www_authenticate: None,
+        }
+    }
+}
+
+/// A SOAP fault with its HTTP status.
+#[derive(Debug)]
+struct Fault {
+    status: u16,
+    subcode: &'static str,
+    reason: String,
+}
+
+impl Fault {
+    fn authentication(reason: impl Into<String>) -> Self {
+        Self { status: 401, subcode: "AuthenticationFailure", reason: reason.into() }
+    }
+    fn policy_not_found(reason: impl Into<String>) -> Self {
+        Self { status: 404, subcode: "PolicyNotFound", reason: reason.into() }
+    }
+    fn invalid(reason: impl Into<String>) -> Self {
+        Self { status: 400, subcode: "InvalidArgument", reason: reason.into() }
+    }
+    fn denied(reason: impl Into<String>) -> Self {
+        Self { status: 403, subcode: "AccessDenied", reason: reason.into() }
+    }
+    fn system(status: u16, reason: impl Into<String>) -> Self {
+        Self { status, subcode: "SystemError", reason: reason.into() }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Authentication
+// ---------------------------------------------------------------------------
+
+/// Directory attribute holding a computer account's FQDN.
+const ATTR_DNS_HOST_NAME: &str = "dNSHostName";
+
+/// The authenticated client and its directory account.
+struct Requester {
+    /// Client certificate DN or Kerberos principal.
+    identity: String,
+    /// Directory groups of the account.
+    groups: Vec<String>,
+    /// The account's `dNSHostName`; absent for user accounts.
+    dns_host_name: Option<String>,
+}
+
+impl Requester {
+    fn may_enroll(&self, template: &AdTemplate) -> bool {
+        self.groups.iter().any(|g| group_matches(g, &template.autoenroll_group))
+    }
+}
+
+/// `group` matches a `memberOf` value given either as the full DN or as its CN.
+fn group_matches(member_of: &str, group: &str) -> bool {
+    if member_of.eq_ignore_ascii_case(group) {
+        return true;
+    }
+    member_of.split(',').next()
+        .and_then(|rdn| rdn.split_once('='))
+        .is_some_and(|(k, v)| k.trim().eq_ignore_ascii_case("CN") && v.trim().eq_ignore_ascii_case(group))
+}
+
+fn authenticate(ctx: &AdAutoenrollContext, client_cert_dn: &str, authorization: &str) -> Result<Requester, Fault> {
+    match ctx.config.auth_mode {
+        AdAuthMode::ClientCert => {
+            if client_cert_dn.is_empty() {
+                return Err(Fault::authentication("client certificate required"));
+            }
+            let host = dn_common_name(client_cert_dn)
+                .ok_or_else(|| Fault::authentication("client certificate has no common name"))?;
+            resolve(ctx, client_cert_dn, ATTR_DNS_HOST_NAME, host)
+        }
+        AdAuthMode::Kerberos => {
+            let Some(driver) = &ctx.kerberos else {
+                return Err(Fault::system(503, "Kerberos authentication is not configured"));
+            };
+            let token = authorization.split_once(' ')
+                .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("Negotiate"))
+                .and_then(|(_, t)| B64.decode(t.trim()).ok())
+                .ok_or_else(|| Fault::authentication("Negotiate authorization required"))?;
+
+            let mut auth_ctx = AuthPipelineContext {
+                partial_principal: None,
+                tenant_id: TenantId::from(ctx.config.tenant_id.as_str()),
+                source_ip: std::net::IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED),
+            };
+            let creds = Credentials::KerberosTicket { ticket: token };
+            let principal = match ctx.runtime.block_on(driver.authenticate(&creds, &mut auth_ctx)) {
+                AuthResult::Authenticated(p) => p.display_name,
+                AuthResult::Reject(reason) => return Err(Fault::authentication(reason)),
+                _ => return Err(Fault::authentication("Kerberos ticket not accepted")),
+            };
+
+            // "HOST$@CORP.EXAMPLE.COM" is sAMAccountName "HOST$" in the directory
+            let account = principal.split('@').next().unwrap_or(&principal);
+            resolve(ctx, &principal, "sAMAccountName", account)
+        }
+    }
+}
+
+/// Look up the account whose `attr` is `value` as the service account.
+fn resolve(ctx: &AdAutoenrollContext, identity: &str, attr: &str, value: &str) -> Result<Requester, Fault> {
+    let (Some(directory), Some(ldap)) = (&ctx.directory, &ctx.config.ldap) else {
+        return Err(Fault::system(503, "directory is not configured"));
+    };
+    let entry = ctx.runtime
+        .block_on(directory.lookup_entry(&ldap.bind_dn, &ctx.ldap_password, attr, value, &[ATTR_DNS_HOST_NAME]))
+        .map_err(|e| Fault::system(503, format!("directory lookup failed: {}", e)))?;
+    Ok(Requester {
+        identity: identity.to_string(),
+        dns_host_name: entry.attribute(ATTR_DNS_HOST_NAME).map(str::to_string),
+        groups: entry.groups,
+    })
+}
+
+/// Value of the first CN in `dn`.
+fn dn_common_name(dn: &str) -> Option<&str> {
+    dn.split(',')
+        .filter_map(|rdn| rdn.split_once('='))
+        .find(|(k, _)| k.trim().eq_ignore_ascii_case("CN"))
+        .map(|(_, v)| v.trim())
+        .filter(|v| !v.is_empty())
+}
+
+// ---------------------------------------------------------------------------
+// DER helpers
+// ---------------------------------------------------------------------------
+
+fn oid_arcs(oid: &str) -> Result<Vec<u64>, CertError> {
+    let arcs: Vec<u64> = oid.split('.').map(str::parse).collect::<Result<_, _>>()
+        .map_err(|_| CertError::Validation(format!("invalid OID '{}'", oid)))?;
+    if arcs.len() < 2 {
+        return Err(CertError::Validation(format!("invalid OID '{}'", oid)));
+    }
+    Ok(arcs)
+}
+
+fn oid_string(oid: &ObjectIdentifier) -> String {
+    oid.components().iter().map(u64::to_string).collect::<Vec<_>>().join(".")
+}
+
+/// EKU OID for an RFC 5280 purpose name, or the name itself if it is an OID.
+fn eku_oid(name: &str) -> Result<String, CertError> {
+    let oid = match name {
+        "serverAuth" => "1.3.6.1.5.5.7.3.1",
+        "clientAuth" => "1.3.6.1.5.5.7.3.2",
+        "codeSigning" => "1.3.6.1.5.5.7.3.3",
+        "emailProtection" => "1.3.6.1.5.5.7.3.4",
+        "timeStamping" => "1.3.6.1.5.5.7.3.8",
+        "OCSPSigning" | "ocspSigning" => "1.3.6.1.5.5.7.3.9",
+        other => {
+            oid_arcs(other)?;
+            other
+        }
+    };
+    Ok(oid.to_string())
+}
+
+fn key_usage_bit(name: &str) -> Result<usize, CertError> {
+    Ok(match name {
+        "digitalSignature" => 0,
+        "nonRepudiation" | "contentCommitment" => 1,
+        "keyEncipherment" => 2,
+        "dataEncipherment" => 3,
+        "keyAgreement" => 4,
+        "keyCertSign" => 5,
+        "cRLSign" => 6,
+        "encipherOnly" => 7,
+        "decipherOnly" => 8,
+        other => return Err(CertError::Validation(format!("unknown key usage '{}'", other))),
+    })
+}
+
+fn profile_ekus(profile: &EnrollmentProfile) -> Result<Vec<String>, CertError> {
+    profile.extended_key_usage.iter().map(|n| eku_oid(n)).collect()
+}
+
+/// KeyUsage ::= BIT STRING
+fn key_usage_value(names: &[String]) -> Result<Option<Vec<u8>>, CertError> {
+    let bits = names.iter().map(|n| key_usage_bit(n)).collect::<Result<Vec<_>, _>>()?;
+    let Some(len) = bits.iter().max().map(|b| b + 1) else { return Ok(None) };
+    let mut bytes = vec![0u8; len.div_ceil(8)];
+    for b in bits {
+        bytes[b / 8] |= 0x80 >> (b % 8);
+    }
+    Ok(Some(yasna::construct_der(|w| w.write_bitvec_bytes(&bytes, len))))
+}
+
+/// ExtKeyUsageSyntax ::= SEQUENCE OF KeyPurposeId
+fn eku_value(oids: &[String]) -> Result<Vec<u8>, CertError> {
+    let arcs = oids.iter().map(|o| oid_arcs(o)).collect::<Result<Vec<_>, _>>()?;
+    Ok(yasna::construct_der(|w| w.write_sequence(|w| {
+        for a in &arcs {
+            w.next().write_oid(&ObjectIdentifier::from_slice(a));
+        }
+    })))
+}
+
+/// CertificatePolicies-shaped list of application policies, one
+/// PolicyInformation per EKU.
+fn application_policies_value(oids: &[String]) -> Result<Vec<u8>, CertError> {
+    let arcs = oids.iter().map(|o| oid_arcs(o)).collect::<Result<Vec<_>, _>>()?;
+    Ok(yasna::construct_der(|w| w.write_sequence(|w| {
+        for a in &arcs {
+            w.next().write_sequence(|w| w.next().write_oid(&ObjectIdentifier::from_slice(a)));
+        }
+    })))
+}
+
+/// CertificateTemplate ::= SEQUENCE { templateID, templateMajorVersion, templateMinorVersion }
+fn template_value(oid: &str) -> Result<Vec<u8>, CertError> {
+    let arcs = oid_arcs(oid)?;
+    Ok(yasna::construct_der(|w| w.write_sequence(|w| {
+        w.next().write_oid(&ObjectIdentifier::from_slice(&arcs));
+        w.next().write_u32(TEMPLATE_MAJOR_REVISION);
+        w.next().write_u32(0);
+    })))
+}
+
+/// Extensions the CA adds to every certificate issued for `template`.
+fn issued_extensions(template: &AdTemplate, profile: &EnrollmentProfile) -> Result<Vec<CustomExtension>, CertError> {
+    let mut exts = vec![CustomExtension {
+        oid: OID_CERTIFICATE_TEMPLATE.to_string(),
+        critical: false,
+        value: template_value(&template.oid)?,
+    }];
+    let ekus = profile_ekus(profile)?;
+    if !ekus.is_empty() {
+        exts.push(CustomExtension {
+            oid: OID_APPLICATION_POLICIES.to_string(),
+            critical: false,
+            value: application_policies_value(&ekus)?,
+        });
+    }
+    Ok(exts)
+}
+
+/// Degenerate SignedData holding only certificates (RFC 5652 §5.2).
+fn certs_only(certs: &[Vec<u8>]) -> Vec<u8> {
+    yasna::construct_der(|w| w.write_sequence(|w| {
+        w.next().write_oid(&ObjectIdentifier::from_slice(OID_PKCS7_SIGNED_DATA));
+        w.next().write_tagged(Tag::context(0), |w| w.write_sequence(|w| {
+            w.next().write_u32(1);
+            w.next().write_set(|_| {});
+            w.next().write_sequence(|w| w.next().write_oid(&ObjectIdentifier::from_slice(OID_PKCS7_DATA)));
+            w.next().write_tagged_implicit(Tag::context(0), |w| w.write_set(|w| {
+                for c in certs {
+                    w.next().write_der(c);
+                }
+            }));
+            w.next().write_set(|_| {});
+        }));
+    }))
+}
+
+/// The encapsulated content of a CMS SignedData. The outer signature is not
+/// checked: Windows signs new requests with the request key itself, which the
+/// CSR signature already proves.
+fn signed_data_content(der: &[u8]) -> Option<Vec<u8>> {
+    yasna::parse_ber(der, |r| r.read_sequence(|r| {
+        let content_type = r.next().read_oid()?;
+        if content_type.components().as_slice() != OID_PKCS7_SIGNED_DATA {
+            return Err(yasna::ASN1Error::new(yasna::ASN1ErrorKind::Invalid));
+        }
+        r.next().read_tagged(Tag::context(0), |r| r.read_sequence(|r| {
+            r.next().read_u32()?;
+            r.next().read_der()?;
+            let content = r.next().read_sequence(|r| {
+                r.next().read_oid()?;
+                r.read_optional(|r| r.read_tagged(Tag::context(0), |r| r.read_bytes()))
+            })?;
+            while r.read_optional(|r| r.read_der())?.is_some() {}
+            Ok(content)
+        }))
+    })).ok().flatten()
+}
+
+/// The first TaggedCertificationRequest in a CMC PKIData (RFC 5272 §3.2).
+fn cmc_certification_request(der: &[u8]) -> Option<Vec<u8>> {
+    yasna::parse_ber(der, |r| r.read_sequence(|r| {
+        r.next().read_der()?;
+        let csr = r.next().read_sequence(|r| {
+            let csr = r.next().read_tagged_implicit(Tag::context(0), |r| r.read_sequence(|r| {
+                r.next().read_der()?;
+                r.next().read_der()
+            }))?;
+            while r.read_optional(|r| r.read_der())?.is_some() {}
+            Ok(csr)
+        })?;
+        while r.read_optional(|r| r.read_der())?.is_some() {}
+        Ok(csr)
+    })).ok()
+}
+
+/// PKCS#10 DER from a WSTEP BinarySecurityToken.
+fn token_csr(value_type: &str, token: &str) -> Result<Vec<u8>, Fault> {
+    let stripped: String = token.chars().filter(|c| !c.is_whitespace()).collect();
+    let der = B64.decode(stripped).map_err(|_| Fault::invalid("BinarySecurityToken is not base64"))?;
+    let kind = value_type.rsplit('#').next().unwrap_or("");
+    match kind {
+        "PKCS10" => Ok(der),
+        "PKCS7" | "CMC" => {
+            let content = signed_data_content(&der)
+                .ok_or_else(|| Fault::invalid("request is not a CMS SignedData"))?;
+            // PKCS#7 renewal requests wrap a PKCS#10; CMC wraps a PKIData
+            if x509_parser::certification_request::X509CertificationRequest::from_der(&content).is_ok() {
+                return Ok(content);
+            }
+            cmc_certification_request(&content).ok_or_else(|| Fault::invalid("no certification request in CMC message"))
+        }
+        _ => Err(Fault::invalid(format!("unsupported token type '{}'", value_type))),
+    }
+}
+
+/// Extensions a Windows client copies from the policy into its request.
+#[derive(Default)]
+struct RequestedExtensions {
+    template_oid: Option<String>,
+    template_name: Option<String>,
+    ekus: Vec<String>,
+}
+
+fn requested_extensions(csr_der: &[u8]) -> RequestedExtensions {
+    use x509_parser::certification_request::X509CertificationRequest;
+    use x509_parser::cri_attributes::ParsedCriAttribute;
+
+    let mut out = RequestedExtensions::default();
+    let Ok((_, csr)) = X509CertificationRequest::from_der(csr_der) else { return out };
+    for attr in csr.certification_request_info.iter_attributes() {
+        let ParsedCriAttribute::ExtensionRequest(req) = attr.parsed_attribute() else { continue };
+        for ext in &req.extensions {
+            match ext.oid.to_id_string().as_str() {
+                OID_CERTIFICATE_TEMPLATE => {
+                    out.template_oid = yasna::parse_der(ext.value, |r| r.read_sequence(|r| {
+                        let oid = r.next().read_oid()?;
+                        while r.read_optional(|r| r.read_der())?.is_some() {}
+                        Ok(oid_string(&oid))
+                    })).ok();
+                }
+                OID_ENROLL_CERTTYPE => {
+                    out.template_name = yasna::parse_der(ext.value, |r| r.read_bmp_string()).ok();
+                }
+                OID_EXTENDED_KEY_USAGE => {
+                    out.ekus = yasna::parse_der(ext.value, |r| r.collect_sequence_of(|r| r.read_oid()))
+                        .map(|oids| oids.iter().map(oid_string).collect())
+                        .unwrap_or_default();
+                }
+                _ => {}
+            }
+        }
+    }
+    out
+}
+
+// ---------------------------------------------------------------------------
+// MS-XCEP: GetPolicies
+// ---------------------------------------------------------------------------
+
+/// The `oIDs` table of a GetPoliciesResponse; other elements refer to entries
+/// by index.
+#[derive(Default)]
+struct OidTable {
+    entries: Vec<(String, u32, String)>,
+}
+
+impl OidTable {
+    fn reference(&mut self, value: &str, group: u32, name: &str) -> usize {
+        if let Some(i) = self.entries.iter().position(|(v, g, _)| v == value && *g == group) {
+            return i;
+        }
+        self.entries.push((value.to_string(), group, name.to_string()));
+        self.entries.len() - 1
+    }
+
+    fn render(&self) -> String {
+        self.entries.iter().enumerate().map(|(i, (value, group, name))| format!(
+            "<oID><value>{}</value><group>{}</group><oIDReferenceID>{}</oIDReferenceID>\
+             <defaultName>{}</defaultName></oID>",
+            value, group, i, esc(name)
+        )).collect()
+    }
+}
+
+fn esc(s: &str) -> std::borrow::Cow<'_, str> {
+    quick_xml::escape::escape(s)
+}
+
+fn key_bits(kt: &KeyType) -> u32 {
+    match kt {
+        KeyType::Rsa2048 => 2048,
+        KeyType::Rsa3072 => 3072,
+        KeyType::Rsa4096 => 4096,
+        KeyType::EcP256 | KeyType::Ed25519 => 256,
+        KeyType::EcP384 => 384,
+        KeyType::EcP521 => 521,
+    }
+}
+
+fn is_rsa(kt: &KeyType) -> bool {
+    matches!(kt, KeyType::Rsa2048 | KeyType::Rsa3072 | KeyType::Rsa4096)
+}
+
+/// CNG algorithm for an EC key type, as (OID, name).
+fn ec_algorithm(kt: &KeyType) -> (&'static str, &'static str) {
+    match kt {
+        KeyType::EcP384 => ("1.3.132.0.34", "ECDSA_P384"),
+        KeyType::EcP521 => ("1.3.132.0.35", "ECDSA_P521"),
+        KeyType::Ed25519 => ("1.3.101.112", "Ed25519"),
+        _ => ("1.2.840.10045.3.1.7", "ECDSA_P256"),
+    }
+}
+
+fn extension_name(oid: &str) -> &'static str {
+    match oid {
+        OID_KEY_USAGE => "Key Usage",
+        OID_EXTENDED_KEY_USAGE => "Enhanced Key Usage",
+        OID_CERTIFICATE_TEMPLATE => "Certificate Template Information",
+        OID_APPLICATION_POLICIES => "Application Policies",
+        _ => "",
+    }
+}
+
+fn render_policy(
+    template: &AdTemplate,
+    profile: &EnrollmentProfile,
+    oids: &mut OidTable,
+) -> Result<String, CertError> {
+    let template_ref = oids.reference(&template.oid, OID_GROUP_TEMPLATE, &template.name);
+
+    // RSA whenever the profile allows it; otherwise the first EC curve
+    let rsa_bits = profile.allowed_key_types.iter().filter(|k| is_rsa(k)).map(key_bits).min();
+    let (schema, min_bits, key_spec, algorithm) = match (rsa_bits, profile.allowed_key_types.first()) {
+        (Some(bits), _) => (2, bits, 1, String::from("<algorithmOIDReference xsi:nil=\"true\"/>")),
+        (None, None) => (2, 2048, 1, String::from("<algorithmOIDReference xsi:nil=\"true\"/>")),
+        (None, Some(kt)) => {
+            let (oid, name) = ec_algorithm(kt);
+            let r = oids.reference(oid, OID_GROUP_PUBKEY, name);
+            (3, key_bits(kt), 2, format!("<algorithmOIDReference>{}</algorithmOIDReference>", r))
+        }
+    };
+    let hash_ref = oids.reference(OID_SHA256, OID_GROUP_HASH, "sha256");
+
+    let mut extensions = Vec::new();
+    let ekus = profile_ekus(profile)?;
+    if !ekus.is_empty() {
+        for eku in &ekus {
+            oids.reference(eku, OID_GROUP_ENHANCED_KEY_USAGE, eku);
+        }
+        extensions.push(CustomExtension {
+            oid: OID_EXTENDED_KEY_USAGE.to_string(),
+            critical: false,
+            value: eku_value(&ekus)?,
+        });
+    }
+    if let Some(value) = key_usage_value(&profile.key_usage)? {
+        extensions.push(CustomExtension { oid: OID_KEY_USAGE.to_string(), critical: true, value });
+    }
+    extensions.extend(issued_extensions(template, profile)?);
+    let extensions: String = extensions.into_iter().map(|ext| format!(
+        "<extension><oIDReference>{}</oIDReference><critical>{}</critical><value>{}</value></extension>",
+        oids.reference(&ext.oid, OID_GROUP_EXTENSION, extension_name(&ext.oid)),
+        ext.critical,
+        B64.encode(&ext.value)
+    )).collect();
+
+    Ok(format!(
+        "<policy><policyOIDReference>{template_ref}</policyOIDReference><cAs><cAReference>0</cAReference></cAs>\
+         <attributes><commonName>{name}</commonName><policySchema>{schema}</policySchema>\
+         <certificateValidity><validityPeriodSeconds>{validity}</validityPeriodSeconds>\
+         <renewalPeriodSeconds>{renewal}</renewalPeriodSeconds></certificateValidity>\
+         <permission><enroll>true</enroll><autoEnroll>true</autoEnroll></permission>\
+         <privateKeyAttributes><minimalKeyLength>{min_bits}</minimalKeyLength><keySpec>{key_spec}</keySpec>\
+         <keyUsageProperty xsi:nil=\"true\"/><permissions xsi:nil=\"true\"/>{algorithm}\
+         <cryptoProviders xsi:nil=\"true\"/></privateKeyAttributes>\
+         <revision><majorRevision>{major}</majorRevision><minorRevision>0</minorRevision></revision>\
+         <supersededPolicies xsi:nil=\"true\"/><privateKeyFlags>0</privateKeyFlags>\
+         <subjectNameFlags>{name_flags}</subjectNameFlags><enrollmentFlags>{enroll_flags}</enrollmentFlags>\
+         <generalFlags>0</generalFlags><hashAlgorithmOIDReference>{hash_ref}</hashAlgorithmOIDReference>\
+         <rARequirements xsi:nil=\"true\"/><keyArchivalAttributes xsi:nil=\"true\"/>\
+         <extensions>{extensions}</extensions></attributes></policy>",
+        name = esc(&template.name),
+        validity = profile.validity_seconds,
+        renewal = template.renewal_days as u64 * 86400,
+        major = TEMPLATE_MAJOR_REVISION,
+        name_flags = template.subject_name_flags,
+        enroll_flags = ENROLLMENT_FLAG_AUTO_ENROLLMENT,
+    ))
+}
+
+fn get_policies(ctx: &AdAutoenrollContext, requester: &Requester) -> Result<String, Fault> {
+    let config = &ctx.config;
+    let ca_der = read_cert_der(&config.ca_intermediate_cert_path)
+        .map_err(|e| Fault::system(503, format!("CA not ready: {}", e)))?;
+
+    let mut oids = OidTable::default();
+    let mut policies = String::new();
+    for template in config.templates.iter().filter(|t| requester.may_enroll(t)) {
+        let Some(profile) = ctx.profile(&template.profile) else { continue };
+        policies.push_str(&render_policy(template, profile, &mut oids)
+            .map_err(|e| Fault::system(500, format!("template '{}': {}", template.name, e)))?);
+    }
+    let client_auth = match config.auth_mode {
+        AdAuthMode::ClientCert => CLIENT_AUTH_CERTIFICATE,
+        AdAuthMode::Kerberos => CLIENT_AUTH_KERBEROS,
+    };
+
+    Ok(format!(
+        "<GetPoliciesResponse xmlns=\"{ns}\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\
+         <response><policyID>{policy_id}</policyID><policyFriendlyName>{tenant}</policyFriendlyName>\
+         <nextUpdateHours>{next_update}</nextUpdateHours><policiesNotChanged xsi:nil=\"true\"/>\
+         <policies>{policies}</policies></response>\
+         <cAs><cA><uris><cAURI><clientAuthentication>{client_auth}</clientAuthentication>\
+         <uri>{uri}</uri><priority>1</priority><renewalOnly>false</renewalOnly></cAURI></uris>\
+         <certificate>{ca}</certificate><enrollPermission>true</enrollPermission>\
+         <cAReferenceID>0</cAReferenceID></cA></cAs><oIDs>{oids}</oIDs></GetPoliciesResponse>",
+        ns = soap::NS_XCEP,
+        policy_id = esc(&ctx.policy_id()),
+        tenant = esc(&config.tenant_id),
+        next_update = config.next_update_hours,
+        uri = esc(&config.enrollment_uri),
+        ca = B64.encode(&ca_der),
+        oids = oids.render(),
+    ))
+}
+
+// ---------------------------------------------------------------------------
+// MS-WSTEP: RequestSecurityToken
+// ---------------------------------------------------------------------------
+
+fn read_cert_der(path: &str) -> Result<Vec<u8>, CertError> {
+    let pem_str = std::fs::read_to_string(path)
+        .map_err(|e| CertError::Crypto(format!("failed to read {}: {}", path, e)))?;
+    ::pem::parse(pem_str.as_bytes())
+        .map(|p| p.into_contents())
+        .map_err(|e| CertError::Crypto(format!("invalid PEM in {}: {}", path, e)))
+}
+
+/// Template named by the request: the `CertificateTemplate` context item, then
+/// the template extension, then the v1 template name extension.
+fn requested_template<'a>(
+    config: &'a AdAutoenrollConfig,
+    req: &soap::SoapRequest,
+    exts: &RequestedExtensions,
+) -> Result<&'a AdTemplate, Fault> {
+    let wanted = req.context_item("CertificateTemplate")
+        .or(exts.template_oid.as_deref())
+        .or(exts.template_name.as_deref())
+        .ok_or_else(|| Fault::policy_not_found("request names no certificate template"))?;
+    config.templates.iter()
+        .find(|t| t.oid == wanted || t.name.eq_ignore_ascii_case(wanted))
+        .ok_or_else(|| Fault::policy_not_found(format!("unknown certificate template '{}'", wanted)))
+}
+
+/// Template limits beyond the tenant-wide `IssuancePolicy`.
+fn check_profile(profile: &EnrollmentProfile, csr: &CsrInfo, requested_ekus: &[String]) -> Result<(), Fault> {
+    if !profile.allowed_key_types.is_empty() && !profile.allowed_key_types.contains(&csr.key_type) {
+        return Err(Fault::denied(format!("key type {:?} not allowed by the template", csr.key_type)));
+    }
+    if csr.sans.len() > profile.max_san_count {
+        return Err(Fault::denied(format!("too many SANs: {} > {}", csr.sans.len(), profile.max_san_count)));
+    }
+    if !profile.wildcard_allowed
+        && csr.sans.iter().any(|s| matches!(s, SanType::Dns(d) if d.starts_with("*.")))
+    {
+        return Err(Fault::denied("wildcard SANs not allowed by the template"));
+    }
+    let allowed = profile_ekus(profile).map_err(|e| Fault::system(500, e.to_string()))?;
+    if let Some(eku) = requested_ekus.iter().find(|e| !allowed.contains(e)) {
+        return Err(Fault::denied(format!("extended key usage {} not allowed by the template", eku)));
+    }
+    Ok(())
+}
+
+fn enroll(ctx: &AdAutoenrollContext, requester: &Requester, req: &soap::SoapRequest) -> Result<String, Fault> {
+    let config = &ctx.config;
+    let tenant = &config.tenant_id;
+
+    if req.request_type != soap::REQUEST_TYPE_ISSUE {
+        return Err(Fault::invalid(format!("unsupported RequestType '{}'", req.request_type)));
+    }
+    let csr_der = token_csr(&req.token_value_type, &req.token)?;
+    let csr_pem = ::pem::encode(&::pem::Pem::new("CERTIFICATE REQUEST", csr_der.clone()));
+    let csr_info = parse_csr(&csr_pem).map_err(|e| Fault::invalid(format!("invalid CSR: {}", e)))?;
+    let exts = requested_extensions(&csr_der);
+
+    let template = requested_template(config, req, &exts)?;
+    if !requester.may_enroll(template) {
+        return Err(Fault::denied(format!("'{}' may not enroll for '{}'", requester.identity, template.name)));
+    }
+    let profile = ctx.profile(&template.profile)
+        .ok_or_else(|| Fault::system(500, format!("profile '{}' missing", template.profile)))?;
+
+    // Names come from the requester's account, never from the CSR, so a CSR cannot
+    // claim another identity (ESC1)
+    let host = requester.dns_host_name.as_deref().ok_or_else(|| {
+        Fault::denied(format!("'{}' has no {} in the directory", requester.identity, ATTR_DNS_HOST_NAME))
+    })?;
+    let subject = format!("CN={}", host);
+    let sans = if template.subject_name_flags & SUBJECT_ALT_REQUIRE_DNS != 0 {
+        vec![SanType::Dns(host.to_string())]
+    } else {
+        vec![]
+    };
+    let issued = CsrInfo { subject_dn: subject.clone(), subject_cn: host.to_string(), sans: sans.clone(), ..csr_info };
+
+    check_profile(profile, &issued, &exts.ekus)?;
+    let policy = IssuancePolicy::from_config(&config.policy)
+        .map_err(|e| Fault::system(500, format!("invalid policy: {}", e)))?;
+    policy.validate_csr(&issued).map_err(|e| Fault::denied(e.to_string()))?;
+
+    let unavailable = |e: String| Fault::system(503, format!("CA not ready: {}", e));
+    let ca_cert_pem = std::fs::read_to_string(&config.ca_intermediate_cert_path)
+        .map_err(|e| unavailable(e.to_string()))?;
+    let ca_params = issuer_params_from_cert_pem(&ca_cert_pem).map_err(|e| unavailable(e.to_string()))?;
+    let ks = open_keystore(&config.keystore).map_err(|e| unavailable(e.to_string()))?;
+    let ca_key = KeyStoreSigner::new(ks.as_ref(), tenant, &config.ca_intermediate_key_id)
+        .map_err(|e| unavailable(e.to_string()))?;
+
+    let ct_chain = ct::issuer_chain(config.ct.as_ref(), &ca_cert_pem, &config.ca_root_cert_path)
+        .map_err(|e| unavailable(e.to_string()))?;
+
+    let extensions = issued_extensions(template, profile).map_err(|e| Fault::system(500, e.to_string()))?;
+    let options = IssuanceOptions {
+        subject: Some(&subject),
+        extensions: &extensions,
+        ct: config.ct.as_ref(),
+        ct_issuer_chain: &ct_chain,
+        crl_partitions: config.crl_partitions.as_ref(),
+    };
+    let mut cert = sign_csr(&csr_pem, tenant, &profile.name, profile.validity_seconds, Some(&sans), &ca_params, &ca_key, &options)
+        .map_err(|e| match e {
+            CertError::CtFailure(msg) => Fault::system(502, format!("Certificate Transparency: {}", msg)),
+            e => Fault::invalid(format!("cannot issue for this CSR: {}", e)),
+        })?;
+    cert.enrollment_protocol = Some(EnrollmentProtocol::Ad);
+
+    let store = OxPersistenceCertStore::open(config.store.db_path())
+        .map_err(|e| Fault::system(500, format!("store error: {}", e)))?;
+    store.store_cert(tenant, &cert).map_err(|e| Fault::system(500, format!("store error: {}", e)))?;
+    let _ = store.store_audit_event(tenant, &AuditEvent {
+        id: 0,
+        tenant_id: tenant.clone(),
+        timestamp: OffsetDateTime::now_utc(),
+        action: AuditAction::AdEnroll,
+        serial: Some(cert.serial.clone()),
+        actor: requester.identity.clone(),
+        details: serde_json::json!({
+            "protocol": "wstep",
+            "template": template.name,
+            "template_oid": template.oid,
+            "profile": profile.name,
+            "auth_mode": format!("{:?}", config.auth_mode),
+        }),
+    });
+
+    let cert_der = ::pem::parse(cert.pem.as_bytes())
+        .map(|p| p.into_contents())
+        .map_err(|e| Fault::system(500, format!("cert encoding failed: {}", e)))?;
+    let mut chain = vec![cert_der.clone()];
+    for path in [&config.ca_intermediate_cert_path, &config.ca_root_cert_path] {
+        let der = read_cert_der(path).map_err(|e| unavailable(e.to_string()))?;
+        if !chain.contains(&der) {
+            chain.push(der);
+        }
+    }
+
+    Ok(format!(
+        "<RequestSecurityTokenResponseCollection xmlns=\"{trust}\">\
+         <RequestSecurityTokenResponse><TokenType>{token_type}</TokenType>\
+         <DispositionMessage xml:lang=\"en-US\" xmlns=\"{enroll}\">Issued</DispositionMessage>\
+         <BinarySecurityToken ValueType=\"{pkcs7}\" EncodingType=\"{encoding}\" xmlns=\"{wsse}\">{chain}</BinarySecurityToken>\
+         <RequestedSecurityToken><BinarySecurityToken ValueType=\"{token_type}\" EncodingType=\"{encoding}\" \
+         xmlns=\"{wsse}\">{cert}</BinarySecurityToken></RequestedSecurityToken>\
+         <RequestID xmlns=\"{enroll}\">{serial}</RequestID></RequestSecurityTokenResponse>\
+         </RequestSecurityTokenResponseCollection>",
+        trust = soap::NS_WSTRUST,
+        enroll = soap::NS_ENROLLMENT,
+        wsse = soap::NS_WSSE,
+        token_type = soap::TOKEN_TYPE_X509V3,
+        pkcs7 = soap::VALUE_TYPE_PKCS7,
+        encoding = soap::ENCODING_BASE64,
+        chain = B64.encode(certs_only(&chain)),
+        cert = B64.encode(&cert_der),
+        serial = cert.serial,
+    ))
+}
+
+// ---------------------------------------------------------------------------
+// Dispatcher
+// ---------------------------------------------------------------------------
+
+/// Handle a request under `/certsrv/`. The last path segment selects the
+/// service: `CEP` (policy) or `CES` (enrollment).
+pub fn handle(
+    ctx: &AdAutoenrollContext,
+    method: &str,
+    path: &str,
+    client_cert_dn: &str,
+    authorization: &str,
+    body: &[u8],
+) -> AdOutcome {
+    let service = path.trim_end_matches('/').rsplit('/').next().unwrap_or("").to_ascii_uppercase();
+    if service != "CEP" && service != "CES" {
+        return AdOutcome::text(404, "not found");
+    }
+    if method != "POST" {
+        return AdOutcome::text(405, "SOAP endpoints accept POST only");
+    }
+    let req = match std::str::from_utf8(body).map_err(|e| e.to_string()).and_then(soap::parse_request) {
+        Ok(r) => r,
+        Err(e) => return AdOutcome::soap(400, soap::fault("", true, "InvalidArgument", &e)),
+    };
+
+    let result = match (service.as_str(), req.operation.as_str()) {
+        ("CEP", "GetPolicies") => authenticate(ctx, client_cert_dn, authorization)
+            .and_then(|r| get_policies(ctx, &r))
+            .map(|body| (soap::ACTION_GET_POLICIES_RESPONSE, body)),
+        ("CES", "RequestSecurityToken") => authenticate(ctx, client_cert_dn, authorization)
+            .and_then(|r| enroll(ctx, &r, &req))
+            .map(|body| (soap::ACTION_RSTRC, body)),
+        _ => Err(Fault::invalid(format!("unsupported operation '{}' on {}", req.operation, service))),
+    };
+
+    match result {
+        Ok((action, body)) => AdOutcome::soap(200, soap::envelope(action, &req.message_id, &body)),
+        Err(f) => {
+            let xml = soap::fault(&req.message_id, f.status < 500, f.subcode, &f.reason);
+            let mut outcome = AdOutcome::soap(f.status, xml);
+            if f.status == 401 && ctx.config.auth_mode == AdAuthMode::Kerberos {
+                outcome.www_authenticate = Some("Negotiate");
+            }
+            outcome
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Plugin ABI
+// ---------------------------------------------------------------------------
+
+pub mod plugin {
+    use super::*;
+    use std::ffi::{c_char, c_void, CStr, CString};
+    use std::panic;
+    use std::path::Path;
+    use ox_workflow_abi::{
+        CoreHostApi, FlowControl, FLOW_CONTROL_CONTINUE, OX_LOG_ERROR, OX_LOG_INFO,
+        OX_WORKFLOW_ABI_VERSION,
+    };
+
+    struct PluginState {
+        api: CoreHostApi,
+        ctx: AdAutoenrollContext,
+    }
+    unsafe impl Send for PluginState {}
+    unsafe impl Sync for PluginState {}
+
+    fn log(api: &CoreHostApi, ctx: *mut c_void, level: u8, msg: &str) {
+        if let Ok(c) = CString::new(msg) { (api.log)(ctx, level, c.as_ptr()); }
+    }
+
+    fn get(api: &CoreHostApi, ctx: *mut c_void, key: &str) -> String {
+        let Ok(k) = CString::new(key) else { return String::new() };
+        let ptr = (api.get_field)(ctx, k.as_ptr());
+        if ptr.is_null() { return String::new(); }
+        unsafe { CStr::from_ptr(ptr).to_string_lossy().into_owned() }
+    }
+
+    fn set(api: &CoreHostApi, ctx: *mut c_void, key: &str, val: &str) {
+        if let (Ok(k), Ok(v)) = (CString::new(key), CString::new(val)) {
+            (api.set_field)(ctx, k.as_ptr(), v.as_ptr());
+        }
+    }
+
+    fn get_bytes(api: &CoreHostApi, ctx: *mut c_void, key: &str) -> Vec<u8> {
+        let Ok(k) = CString::new(key) else { return vec![] };
+        let mut len: usize = 0;
+        let ptr = (api.get_field_bytes)(ctx, k.as_ptr(), &mut len);
+        if ptr.is_null() || len == 0 { return vec![]; }
+        unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec()
+    }
+
+    fn set_bytes(api: &CoreHostApi, ctx: *mut c_void, key: &str, data: &[u8]) {
+        if let Ok(k) = CString::new(key) {
+            (api.set_field_bytes)(ctx, k.as_ptr(), data.as_ptr(), data.len());
+        }
+    }
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn ox_plugin_init(
+        config_ptr: *const c_char,
+        api_ptr: *const CoreHostApi,
+        abi_version: u32,
+    ) -> *mut c_void {
+        if abi_version != OX_WORKFLOW_ABI_VERSION || api_ptr.is_null() {
+            return std::ptr::null_mut();
+        }
+        let api = unsafe { *api_ptr };
+        let params_str = if !config_ptr.is_null() {
+            unsafe { CStr::from_ptr(config_ptr).to_string_lossy().to_string() }
+        } else {
+            String::new()
+        };
+        let params: serde_json::Value =
+            serde_json::from_str(&params_str).unwrap_or(serde_json::Value::Null);
+        let config_path = match params.get("config_file").and_then(|v| v.as_str()) {
+            Some(p) => p.to_string(),
+            None => {
+                log(&api, std::ptr::null_mut(), OX_LOG_ERROR, "ox_cert_ad_autoenroll: missing config_file param");
+                return std::ptr::null_mut();
+            }
+        };
+        let config: AdAutoenrollConfig = match ox_fileproc::process_file(Path::new(&config_path), 5) {
+            Ok(v) => match serde_json::from_value(v) {
+                Ok(c) => c,
+                Err(e) => {
+                    log(&api, std::ptr::null_mut(), OX_LOG_ERROR,
+                        &format!("ox_cert_ad_autoenroll: config error: {}", e));
+                    return std::ptr::null_mut();
+                }
+            },
+            Err(e) => {
+                log(&api, std::ptr::null_mut(), OX_LOG_ERROR,
+                    &format!("ox_cert_ad_autoenroll: failed to load config: {}", e));
+                return std::ptr::null_mut();
+            }
+        };
+        let tenant_id = config.tenant_id.clone();
+        let ctx = match AdAutoenrollContext::new(config) {
+            Ok(c) => c,
+            Err(e) => {
+                log(&api, std::ptr::null_mut(), OX_LOG_ERROR,
+                    &format!("ox_cert_ad_autoenroll: {}", e));
+                return std::ptr::null_mut();
+            }
+        };
+        log(&api, std::ptr::null_mut(), OX_LOG_INFO,
+            &format!("ox_cert_ad_autoenroll: initialized for tenant '{}'", tenant_id));
+        Box::into_raw(Box::new(PluginState { api, ctx })) as *mut c_void
+    }
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn ox_plugin_process(
+        plugin_ctx: *mut c_void,
+        task_ctx: *mut c_void,
+    ) -> FlowControl {
+        let cont = FlowControl { code: FLOW_CONTROL_CONTINUE, payload: std::ptr::null() };
+        if plugin_ctx.is_null() { return cont; }
+        let state = unsafe { &*(plugin_ctx as *mut PluginState) };
+
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            let method = get(&state.api, task_ctx, "request.method").to_uppercase();
+            let path   = get(&state.api, task_ctx, "request.path");
+            let dn     = get(&state.api, task_ctx, "request.tls.client_cert_dn");
+            let authz  = get(&state.api, task_ctx, "request.header.Authorization");
+            let body   = get_bytes(&state.api, task_ctx, "request.body");
+
+            let outcome = handle(&state.ctx, &method, &path, &dn, &authz, &body);
+
+            set(&state.api, task_ctx, "response.status", &outcome.http_status.to_string());
+            set(&state.api, task_ctx, "response.header.Content-Type", outcome.content_type);
+            if let Some(challenge) = outcome.www_authenticate {
+                set(&state.api, task_ctx, "response.header.WWW-Authenticate", challenge);
+            }
+            set_bytes(&state.api, task_ctx, "response.body", &outcome.body);
+            cont
+        }));
+
+        match result {
+            Ok(fc) => fc,
+            Err(_) => {
+                log(&state.api, task_ctx, OX_LOG_ERROR, "ox_cert_ad_autoenroll: panic in process");
+                cont
+            }
+        }
+    }
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn ox_plugin_error(_ctx: *mut c_void, _task: *mut c_void) {}
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn ox_plugin_destroy(plugin_ctx: *mut c_void) {
+        if !plugin_ctx.is_null() {
+            unsafe { drop(Box::from_raw(plugin_ctx as *mut PluginState)); }
+        }
+    }
+}
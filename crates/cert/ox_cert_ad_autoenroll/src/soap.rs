@@ -0,0 +1,179 @@
+// SOAP 1.2 envelopes for MS-XCEP and MS-WSTEP
+//
+// Only the parts of the messages the plugin acts on are read: the WS-Addressing
+// Action and MessageID, the Body operation, and for WSTEP the RequestType,
+// BinarySecurityToken and AdditionalContext items. Elements are matched by local
+// name, so namespace prefixes chosen by the client do not matter.
+
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+pub const NS_SOAP: &str = "http://www.w3.org/2003/05/soap-envelope";
+pub const NS_ADDRESSING: &str = "http://www.w3.org/2005/08/addressing";
+pub const NS_XCEP: &str = "http://schemas.microsoft.com/windows/pki/2009/01/enrollmentpolicy";
+pub const NS_WSTRUST: &str = "http://docs.oasis-open.org/ws-sx/ws-trust/200512";
+pub const NS_ENROLLMENT: &str = "http://schemas.microsoft.com/windows/pki/2009/01/enrollment";
+pub const NS_WSSE: &str =
+    "http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-wssecurity-secext-1.0.xsd";
+
+pub const ACTION_GET_POLICIES: &str =
+    "http://schemas.microsoft.com/windows/pki/2009/01/enrollmentpolicy/IPolicy/GetPolicies";
+pub const ACTION_GET_POLICIES_RESPONSE: &str =
+    "http://schemas.microsoft.com/windows/pki/2009/01/enrollmentpolicy/IPolicy/GetPoliciesResponse";
+pub const ACTION_RST: &str = "http://schemas.microsoft.com/windows/pki/2009/01/enrollment/RST/wstep";
+pub const ACTION_RSTRC: &str = "http://schemas.microsoft.com/windows/pki/2009/01/enrollment/RSTRC/wstep";
+pub const ACTION_FAULT: &str = "http://www.w3.org/2005/08/addressing/soap/fault";
+
+pub const REQUEST_TYPE_ISSUE: &str = "http://docs.oasis-open.org/ws-sx/ws-trust/200512/Issue";
+pub const TOKEN_TYPE_X509V3: &str =
+    "http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-x509-token-profile-1.0#X509v3";
+pub const VALUE_TYPE_PKCS7: &str = "http://schemas.microsoft.com/windows/pki/2009/01/enrollment#PKCS7";
+pub const ENCODING_BASE64: &str =
+    "http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-wssecurity-secext-1.0.xsd#base64binary";
+
+/// The fields of an incoming request that the plugin uses.
+#[derive(Debug, Default)]
+pub struct SoapRequest {
+    /// WS-Addressing `Action` header.
+    pub action: String,
+    /// WS-Addressing `MessageID` header, echoed as `RelatesTo`.
+    pub message_id: String,
+    /// Local name of the first Body child, e.g. `GetPolicies` or `RequestSecurityToken`.
+    pub operation: String,
+    pub request_type: String,
+    /// `ValueType` attribute of the `BinarySecurityToken`.
+    pub token_value_type: String,
+    /// Base64 content of the `BinarySecurityToken`.
+    pub token: String,
+    /// `AdditionalContext` items as (name, value).
+    pub context: Vec<(String, String)>,
+}
+
+impl SoapRequest {
+    /// Value of the `AdditionalContext` item `name`, compared case-insensitively.
+    pub fn context_item(&self, name: &str) -> Option<&str> {
+        self.context.iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+fn local_name(e: &BytesStart) -> String {
+    String::from_utf8_lossy(e.local_name().as_ref()).into_owned()
+}
+
+fn attribute(e: &BytesStart, name: &str) -> Result<Option<String>, String> {
+    for attr in e.attributes() {
+        let attr = attr.map_err(|e| format!("malformed attribute: {}", e))?;
+        if attr.key.local_name().as_ref() == name.as_bytes() {
+            let value = attr.unescape_value().map_err(|e| format!("malformed attribute: {}", e))?;
+            return Ok(Some(value.into_owned()));
+        }
+    }
+    Ok(None)
+}
+
+/// Parse a SOAP request envelope.
+pub fn parse_request(xml: &str) -> Result<SoapRequest, String> {
+    let malformed = |e: quick_xml::Error| format!("malformed XML: {}", e);
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut req = SoapRequest::default();
+    let mut path: Vec<String> = Vec::new();
+    let mut text = String::new();
+    let mut context_name: Option<String> = None;
+
+    loop {
+        let event = reader.read_event().map_err(malformed)?;
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let name = local_name(e);
+                if path.last().map(String::as_str) == Some("Body") && req.operation.is_empty() {
+                    req.operation = name.clone();
+                }
+                match name.as_str() {
+                    "BinarySecurityToken" => {
+                        req.token_value_type = attribute(e, "ValueType")?.unwrap_or_default();
+                    }
+                    "ContextItem" => context_name = attribute(e, "Name")?,
+                    _ => {}
+                }
+                if matches!(event, Event::Start(_)) {
+                    path.push(name);
+                    text.clear();
+                }
+            }
+            Event::Text(t) => {
+                text.push_str(&t.decode().map_err(|e| format!("malformed text: {}", e))?);
+            }
+            Event::CData(c) => {
+                text.push_str(&c.decode().map_err(|e| format!("malformed CDATA: {}", e))?);
+            }
+            Event::GeneralRef(r) => {
+                if let Some(ch) = r.resolve_char_ref().map_err(|e| format!("malformed reference: {}", e))? {
+                    text.push(ch);
+                } else {
+                    let name = r.decode().map_err(|e| format!("malformed reference: {}", e))?;
+                    let resolved = quick_xml::escape::resolve_predefined_entity(&name)
+                        .ok_or_else(|| format!("unknown entity '&{};'", name))?;
+                    text.push_str(resolved);
+                }
+            }
+            Event::End(_) => {
+                let name = path.pop().unwrap_or_default();
+                let parent = path.last().map(String::as_str).unwrap_or("");
+                let value = text.trim().to_string();
+                let in_body = path.iter().any(|p| p == "Body");
+                match name.as_str() {
+                    "Action" if parent == "Header" => req.action = value,
+                    "MessageID" if parent == "Header" => req.message_id = value,
+                    "RequestType" if in_body => req.request_type = value,
+                    "BinarySecurityToken" if in_body => req.token = value,
+                    "Value" if parent == "ContextItem" => {
+                        if let Some(n) = context_name.take() {
+                            req.context.push((n, value));
+                        }
+                    }
+                    _ => {}
+                }
+                text.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if req.operation.is_empty() {
+        return Err("SOAP envelope has no Body".to_string());
+    }
+    Ok(req)
+}
+
+/// A SOAP 1.2 envelope carrying `body`, addressed as a reply to `relates_to`.
+pub fn envelope(action: &str, relates_to: &str, body: &str) -> String {
+    let relates = if relates_to.is_empty() {
+        String::new()
+    } else {
+        format!("<a:RelatesTo>{}</a:RelatesTo>", escape(relates_to))
+    };
+    format!(
+        "<s:Envelope xmlns:s=\"{}\" xmlns:a=\"{}\"><s:Header>\
+         <a:Action s:mustUnderstand=\"1\">{}</a:Action>{}</s:Header>\
+         <s:Body>{}</s:Body></s:Envelope>",
+        NS_SOAP, NS_ADDRESSING, escape(action), relates, body
+    )
+}
+
+/// A SOAP 1.2 fault. `sender` selects the `Sender` code (client error) over
+/// `Receiver` (server error); `subcode` is the MS-WSTEP/MS-XCEP fault name.
+pub fn fault(relates_to: &str, sender: bool, subcode: &str, reason: &str) -> String {
+    let code = if sender { "s:Sender" } else { "s:Receiver" };
+    let body = format!(
+        "<s:Fault><s:Code><s:Value>{}</s:Value><s:Subcode><s:Value>{}</s:Value></s:Subcode></s:Code>\
+         <s:Reason><s:Text xml:lang=\"en-US\">{}</s:Text></s:Reason></s:Fault>",
+        code, escape(subcode), escape(reason)
+    );
+    envelope(ACTION_FAULT, relates_to, &body)
+}
@@ -0,0 +1,498 @@
+//! MS-XCEP and MS-WSTEP exchanges as a Windows client sends them, with
+//! Kerberos and LDAP replaced by in-process stand-ins.
+
+use crate::*;
+use ox_cert_core::model::{AuditFilter, Pagination};
+use ox_security_auth::drivers::ldap::{LdapSearchResult, MockLdapAdapter};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const MACHINE_OID: &str = "1.3.6.1.4.1.311.21.8.1.1";
+const WEB_OID: &str = "1.3.6.1.4.1.311.21.8.1.2";
+const LDAP_PASSWORD_ENV: &str = "OX_AD_AUTOENROLL_TEST_LDAP_PASS";
+const COMPUTERS_GROUP: &str = "CN=Autoenroll Computers,CN=Users,DC=corp,DC=example,DC=com";
+const WEB_GROUP: &str = "CN=Web Servers,CN=Users,DC=corp,DC=example,DC=com";
+
+fn make_config(dir: &tempfile::TempDir, overrides: Value) -> AdAutoenrollConfig {
+    std::env::set_var(LDAP_PASSWORD_ENV, "service-pass");
+    let key_dir = dir.path().join("keys");
+    std::fs::create_dir_all(key_dir.join("t1")).unwrap();
+
+    let ca_key = rcgen::KeyPair::generate().unwrap();
+    let mut params = rcgen::CertificateParams::default();
+    params.distinguished_name.push(rcgen::DnType::CommonName, "Test Intermediate CA");
+    params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+    let ca_cert = params.self_signed(&ca_key).unwrap();
+    std::fs::write(key_dir.join("t1").join("intermediate.key.pem"), ca_key.serialize_pem()).unwrap();
+    let ca_path = dir.path().join("ca.crt");
+    std::fs::write(&ca_path, ca_cert.pem()).unwrap();
+
+    let mut config = json!({
+        "tenant_id": "t1",
+        "store": { "driver": "sqlite", "path": dir.path().join("cert.db").to_str().unwrap() },
+        "keystore": { "store_type": "software", "key_dir": key_dir.to_str().unwrap() },
+        "ca_intermediate_key_id": "intermediate",
+        "ca_intermediate_cert_path": ca_path.to_str().unwrap(),
+        "ca_root_cert_path": ca_path.to_str().unwrap(),
+        "auth_mode": "ClientCert",
+        "domain": "corp.example.com",
+        "ldap": {
+            "url": "ldap://dc1.corp.example.com",
+            "base_dn": "DC=corp,DC=example,DC=com",
+            "bind_dn": "CN=svc-pki,CN=Users,DC=corp,DC=example,DC=com",
+            "bind_password_env": LDAP_PASSWORD_ENV,
+        },
+        "enrollment_uri": "https://pki.corp.example.com/certsrv/CES",
+        "policy": {
+            "domain_allowlist": [], "domain_blocklist": [], "max_san_count": 10,
+            "wildcard_allowed": false, "min_rsa_bits": 2048, "require_ra_approval": false,
+        },
+        "profiles": [
+            {
+                "name": "machine", "validity_seconds": 31536000,
+                "key_usage": ["digitalSignature", "keyEncipherment"],
+                "extended_key_usage": ["clientAuth", "serverAuth"],
+                "allowed_key_types": ["EcP256", "Rsa2048"], "max_san_count": 5,
+                "wildcard_allowed": false, "require_ra_approval": false, "policy_oids": [],
+                "is_ca": false,
+            },
+            {
+                "name": "web", "validity_seconds": 7776000,
+                "key_usage": ["digitalSignature"], "extended_key_usage": ["serverAuth"],
+                "allowed_key_types": ["EcP256"], "max_san_count": 5,
+                "wildcard_allowed": false, "require_ra_approval": false, "policy_oids": [],
+                "is_ca": false,
+            },
+        ],
+        "templates": [
+            { "name": "Machine", "oid": MACHINE_OID, "autoenroll_group": "Autoenroll Computers", "profile": "machine" },
+            { "name": "WebServer", "oid": WEB_OID, "autoenroll_group": "Web Servers", "profile": "web" },
+        ],
+    });
+    for (k, v) in overrides.as_object().unwrap() {
+        config[k] = v.clone();
+    }
+    serde_json::from_value(config).unwrap()
+}
+
+/// A mock directory whose every lookup finds one account with `groups` and,
+/// for a computer, `dns_host_name`.
+fn directory(groups: &[&str], dns_host_name: Option<&str>) -> LdapAuthDriver {
+    let mut attrs = HashMap::from([
+        ("memberOf".to_string(), groups.iter().map(|g| g.to_string()).collect()),
+    ]);
+    if let Some(host) = dns_host_name {
+        attrs.insert("dNSHostName".to_string(), vec![host.to_string()]);
+    }
+    LdapAuthDriver::with_mock(
+        LdapConfig {
+            url: "ldap://dc1.corp.example.com".to_string(),
+            bind_dn_template: String::new(),
+            base_dn: "DC=corp,DC=example,DC=com".to_string(),
+            group_attr: "memberOf".to_string(),
+            tenant_id: TenantId::from("t1"),
+        },
+        MockLdapAdapter::with_search(LdapSearchResult::Found(attrs)),
+    )
+}
+
+/// ClientCert mode; the certificate's host is in both template groups.
+fn client_cert_ctx(dir: &tempfile::TempDir) -> AdAutoenrollContext {
+    let directory = directory(&[COMPUTERS_GROUP, WEB_GROUP], Some("ws01.corp.example.com"));
+    AdAutoenrollContext::with_drivers(make_config(dir, json!({})), None, Some(directory)).unwrap()
+}
+
+/// Kerberos mode: tickets are accepted by a stand-in validator, groups come
+/// from a mock directory.
+fn kerberos_ctx(dir: &tempfile::TempDir, groups: &[&str]) -> AdAutoenrollContext {
+    let config = make_config(dir, json!({
+        "auth_mode": "Kerberos",
+        "kerberos": {
+            "service_principal": "HTTP/pki.corp.example.com@CORP.EXAMPLE.COM",
+            "keytab_path": "/etc/krb5.keytab",
+        },
+    }));
+    let kerberos = KerberosAuthDriver::new(
+        KerberosConfig {
+            service_principal: "HTTP/pki.corp.example.com@CORP.EXAMPLE.COM".to_string(),
+            keytab_path: "/etc/krb5.keytab".to_string(),
+            realm: "CORP.EXAMPLE.COM".to_string(),
+            tenant_id: TenantId::from("t1"),
+        },
+        Arc::new(|ticket: &[u8]| match ticket {
+            b"ticket-ws01" => Ok("WS01$@CORP.EXAMPLE.COM".to_string()),
+            _ => Err("ticket not valid for this service".to_string()),
+        }),
+    );
+    AdAutoenrollContext::with_drivers(config, Some(kerberos), Some(directory(groups, Some("ws01.corp.example.com")))).unwrap()
+}
+
+fn negotiate(ticket: &[u8]) -> String {
+    format!("Negotiate {}", B64.encode(ticket))
+}
+
+fn get_policies_request() -> Vec<u8> {
+    format!(
+        "<s:Envelope xmlns:s=\"{}\" xmlns:a=\"{}\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\
+         <s:Header><a:Action s:mustUnderstand=\"1\">{}</a:Action>\
+         <a:MessageID>urn:uuid:6389e4a1-0001-4b1c-9f7e-3c4a5d6e7f80</a:MessageID></s:Header>\
+         <s:Body><GetPolicies xmlns=\"{}\"><client><lastUpdate xsi:nil=\"true\"/>\
+         <preferredLanguage xsi:nil=\"true\"/></client><requestFilter xsi:nil=\"true\"/></GetPolicies>\
+         </s:Body></s:Envelope>",
+        soap::NS_SOAP, soap::NS_ADDRESSING, soap::ACTION_GET_POLICIES, soap::NS_XCEP
+    ).into_bytes()
+}
+
+/// A WSTEP RequestSecurityToken. The token is split into lines with `&#xD;`
+/// entities, as Windows sends it.
+fn rst_request(value_type: &str, token: &[u8], template: Option<&str>) -> Vec<u8> {
+    let b64 = B64.encode(token);
+    let lines: Vec<&str> = b64.as_bytes().chunks(64).map(|c| std::str::from_utf8(c).unwrap()).collect();
+    let context = template.map(|t| format!(
+        "<AdditionalContext xmlns=\"http://schemas.xmlsoap.org/ws/2006/12/authorization\">\
+         <ContextItem Name=\"CertificateTemplate\"><Value>{}</Value></ContextItem></AdditionalContext>", t
+    )).unwrap_or_default();
+    format!(
+        "<s:Envelope xmlns:s=\"{}\" xmlns:a=\"{}\"><s:Header><a:Action s:mustUnderstand=\"1\">{}</a:Action>\
+         <a:MessageID>urn:uuid:6389e4a1-0002-4b1c-9f7e-3c4a5d6e7f80</a:MessageID></s:Header>\
+         <s:Body><RequestSecurityToken PreferredLanguage=\"en-US\" xmlns=\"{}\">\
+         <TokenType>{}</TokenType><RequestType>{}</RequestType>\
+         <BinarySecurityToken ValueType=\"http://schemas.microsoft.com/windows/pki/2009/01/enrollment#{}\" \
+         EncodingType=\"{}\" xmlns=\"{}\">{}</BinarySecurityToken>{}</RequestSecurityToken></s:Body></s:Envelope>",
+        soap::NS_SOAP, soap::NS_ADDRESSING, soap::ACTION_RST, soap::NS_WSTRUST,
+        soap::TOKEN_TYPE_X509V3, soap::REQUEST_TYPE_ISSUE, value_type, soap::ENCODING_BASE64, soap::NS_WSSE,
+        lines.join("&#xD;\n"), context
+    ).into_bytes()
+}
+
+/// An EC P-256 CSR for ws01 carrying the template extension and the
+/// requested EKUs, like certreq builds from a policy.
+fn machine_csr(template_oid: Option<&str>, ekus: Vec<rcgen::ExtendedKeyUsagePurpose>) -> Vec<u8> {
+    let key = rcgen::KeyPair::generate().unwrap();
+    let mut params = rcgen::CertificateParams::new(vec!["ws01.corp.example.com".to_string()]).unwrap();
+    params.distinguished_name.push(rcgen::DnType::CommonName, "ws01.corp.example.com");
+    params.extended_key_usages = ekus;
+    if let Some(oid) = template_oid {
+        params.custom_extensions.push(rcgen::CustomExtension::from_oid_content(
+            &[1, 3, 6, 1, 4, 1, 311, 21, 7],
+            template_value(oid).unwrap(),
+        ));
+    }
+    params.serialize_request(&key).unwrap().der().to_vec()
+}
+
+/// CMC PKIData holding `csr` (RFC 5272), in an unsigned SignedData.
+fn cmc_request(csr: &[u8]) -> Vec<u8> {
+    let pki_data = yasna::construct_der(|w| w.write_sequence(|w| {
+        w.next().write_sequence(|_| {});
+        w.next().write_sequence(|w| {
+            w.next().write_tagged_implicit(Tag::context(0), |w| w.write_sequence(|w| {
+                w.next().write_u32(1);
+                w.next().write_der(csr);
+            }));
+        });
+        w.next().write_sequence(|_| {});
+        w.next().write_sequence(|_| {});
+    }));
+    yasna::construct_der(|w| w.write_sequence(|w| {
+        w.next().write_oid(&ObjectIdentifier::from_slice(OID_PKCS7_SIGNED_DATA));
+        w.next().write_tagged(Tag::context(0), |w| w.write_sequence(|w| {
+            w.next().write_u32(3);
+            w.next().write_set(|_| {});
+            w.next().write_sequence(|w| {
+                w.next().write_oid(&ObjectIdentifier::from_slice(&[1, 3, 6, 1, 5, 5, 7, 12, 2]));
+                w.next().write_tagged(Tag::context(0), |w| w.write_bytes(&pki_data));
+            });
+            w.next().write_set(|_| {});
+        }));
+    }))
+}
+
+fn body(outcome: &AdOutcome) -> String {
+    String::from_utf8(outcome.body.clone()).unwrap()
+}
+
+/// Text content of the first `<tag ...>` element after `after`.
+fn element_text<'a>(xml: &'a str, after: &str, tag: &str) -> &'a str {
+    let rest = &xml[xml.find(after).unwrap()..];
+    let start = rest.find(&format!("<{}", tag)).unwrap();
+    let rest = &rest[start..];
+    let open_end = rest.find('>').unwrap() + 1;
+    let close = rest.find(&format!("</{}>", tag)).unwrap();
+    &rest[open_end..close]
+}
+
+fn issued_cert(outcome: &AdOutcome) -> Vec<u8> {
+    let xml = body(outcome);
+    B64.decode(element_text(&xml, "<RequestedSecurityToken>", "BinarySecurityToken")).unwrap()
+}
+
+fn extension_value(cert_der: &[u8], oid: &str) -> Option<Vec<u8>> {
+    let (_, cert) = x509_parser::certificate::X509Certificate::from_der(cert_der).unwrap();
+    cert.extensions().iter().find(|e| e.oid.to_id_string() == oid).map(|e| e.value.to_vec())
+}
+
+// ---------------------------------------------------------------------------
+// MS-XCEP
+// ---------------------------------------------------------------------------
+
+#[test]
+fn test_get_policies_with_client_certificate() {
+    let dir = tempfile::tempdir().unwrap();
+    let ctx = client_cert_ctx(&dir);
+
+    let outcome = handle(&ctx, "POST", "/certsrv/CEP", "CN=ws01.corp.example.com", "", &get_policies_request());
+    assert_eq!(outcome.http_status, 200);
+    assert_eq!(outcome.content_type, "application/soap+xml; charset=utf-8");
+    let xml = body(&outcome);
+    assert!(xml.contains(soap::ACTION_GET_POLICIES_RESPONSE));
+    assert!(xml.contains("<a:RelatesTo>urn:uuid:6389e4a1-0001-4b1c-9f7e-3c4a5d6e7f80</a:RelatesTo>"));
+    assert!(xml.contains("<policyID>ox-cert-t1</policyID>"));
+
+    // The host's account is in both template groups
+    assert!(xml.contains("<commonName>Machine</commonName>"));
+    assert!(xml.contains("<commonName>WebServer</commonName>"));
+    assert!(xml.contains(&format!("<value>{}</value><group>9</group>", MACHINE_OID)));
+    assert!(xml.contains("<clientAuthentication>8</clientAuthentication>"));
+    assert!(xml.contains("<uri>https://pki.corp.example.com/certsrv/CES</uri>"));
+    assert!(xml.contains("<validityPeriodSeconds>31536000</validityPeriodSeconds>"));
+    assert!(xml.contains("<renewalPeriodSeconds>3628800</renewalPeriodSeconds>"));
+    // Machine allows RSA; WebServer is P-256 only
+    assert!(xml.contains("<minimalKeyLength>2048</minimalKeyLength><keySpec>1</keySpec>"));
+    assert!(xml.contains("<value>1.2.840.10045.3.1.7</value><group>3</group>"));
+
+    let ca_der = read_cert_der(&ctx.config.ca_intermediate_cert_path).unwrap();
+    assert_eq!(B64.decode(element_text(&xml, "<cAs><cA>", "certificate")).unwrap(), ca_der);
+
+    // The template extension in the policy names the template
+    let ext = B64.encode(template_value(MACHINE_OID).unwrap());
+    assert!(xml.contains(&ext));
+
+    // Without a client certificate
+    let outcome = handle(&ctx, "POST", "/certsrv/CEP", "", "", &get_policies_request());
+    assert_eq!(outcome.http_status, 401);
+    assert!(body(&outcome).contains("<s:Value>AuthenticationFailure</s:Value>"));
+    assert!(outcome.www_authenticate.is_none());
+
+    // Template groups apply to client certificates too
+    let ctx = AdAutoenrollContext::with_drivers(
+        make_config(&dir, json!({})),
+        None,
+        Some(directory(&[], Some("ws01.corp.example.com"))),
+    ).unwrap();
+    let outcome = handle(&ctx, "POST", "/certsrv/CEP", "CN=ws01.corp.example.com", "", &get_policies_request());
+    assert_eq!(outcome.http_status, 200);
+    assert!(!body(&outcome).contains("<commonName>"));
+    let csr = machine_csr(Some(MACHINE_OID), vec![]);
+    let outcome = handle(&ctx, "POST", "/certsrv/CES", "CN=ws01.corp.example.com", "", &rst_request("PKCS10", &csr, None));
+    assert_eq!(outcome.http_status, 403);
+}
+
+#[test]
+fn test_kerberos_policies_follow_group_membership() {
+    let dir = tempfile::tempdir().unwrap();
+    let ctx = kerberos_ctx(&dir, &[COMPUTERS_GROUP]);
+
+    let outcome = handle(&ctx, "POST", "/certsrv/CEP", "", &negotiate(b"ticket-ws01"), &get_policies_request());
+    assert_eq!(outcome.http_status, 200);
+    let xml = body(&outcome);
+    assert!(xml.contains("<commonName>Machine</commonName>"));
+    assert!(!xml.contains("<commonName>WebServer</commonName>"));
+    assert!(xml.contains("<clientAuthentication>2</clientAuthentication>"));
+
+    // Missing or rejected tickets are challenged
+    let outcome = handle(&ctx, "POST", "/certsrv/CEP", "", "", &get_policies_request());
+    assert_eq!(outcome.http_status, 401);
+    assert_eq!(outcome.www_authenticate, Some("Negotiate"));
+    let outcome = handle(&ctx, "POST", "/certsrv/CEP", "", &negotiate(b"forged"), &get_policies_request());
+    assert_eq!(outcome.http_status, 401);
+    assert!(body(&outcome).contains("ticket not valid for this service"));
+
+    // Group membership also gates enrollment
+    let csr = machine_csr(Some(WEB_OID), vec![rcgen::ExtendedKeyUsagePurpose::ServerAuth]);
+    let outcome = handle(&ctx, "POST", "/certsrv/CES", "", &negotiate(b"ticket-ws01"), &rst_request("PKCS10", &csr, None));
+    assert_eq!(outcome.http_status, 403);
+    assert!(body(&outcome).contains("<s:Value>AccessDenied</s:Value>"));
+
+    let csr = machine_csr(Some(MACHINE_OID), vec![rcgen::ExtendedKeyUsagePurpose::ClientAuth]);
+    let outcome = handle(&ctx, "POST", "/certsrv/CES", "", &negotiate(b"ticket-ws01"), &rst_request("PKCS10", &csr, None));
+    assert_eq!(outcome.http_status, 200, "{}", body(&outcome));
+    let store = OxPersistenceCertStore::open(dir.path().join("cert.db").to_str().unwrap()).unwrap();
+    let filter = AuditFilter { action: Some(AuditAction::AdEnroll), ..Default::default() };
+    let events = store.get_audit_log("t1", &filter, &Pagination::default()).unwrap().items;
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].actor, "WS01$@CORP.EXAMPLE.COM");
+}
+
+#[test]
+fn test_directory_is_required() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = make_config(&dir, json!({
+        "auth_mode": "Kerberos",
+        "kerberos": { "service_principal": "HTTP/pki@CORP.EXAMPLE.COM", "keytab_path": "/etc/krb5.keytab" },
+        "ldap": null,
+    }));
+    let kerberos = KerberosAuthDriver::new(
+        KerberosConfig {
+            service_principal: "HTTP/pki@CORP.EXAMPLE.COM".to_string(),
+            keytab_path: "/etc/krb5.keytab".to_string(),
+            realm: "CORP.EXAMPLE.COM".to_string(),
+            tenant_id: TenantId::from("t1"),
+        },
+        Arc::new(|_: &[u8]| Ok("WS01$@CORP.EXAMPLE.COM".to_string())),
+    );
+    let err = AdAutoenrollContext::with_drivers(config, Some(kerberos), None).err().unwrap();
+    assert!(err.to_string().contains("ldap"));
+
+    // Client certificates are resolved in the directory as well
+    let config = make_config(&dir, json!({ "ldap": null }));
+    let err = AdAutoenrollContext::with_drivers(config, None, Some(directory(&[], None))).err().unwrap();
+    assert!(err.to_string().contains("ldap"));
+
+    let config = make_config(&dir, json!({ "templates": [
+        { "name": "Machine", "oid": MACHINE_OID, "autoenroll_group": "Autoenroll Computers", "profile": "missing" },
+    ]}));
+    assert!(AdAutoenrollContext::new(config).is_err());
+}
+
+// ---------------------------------------------------------------------------
+// MS-WSTEP
+// ---------------------------------------------------------------------------
+
+#[test]
+fn test_pkcs10_enrollment_adds_template_extensions() {
+    let dir = tempfile::tempdir().unwrap();
+    let ctx = client_cert_ctx(&dir);
+
+    let csr = machine_csr(Some(MACHINE_OID), vec![rcgen::ExtendedKeyUsagePurpose::ClientAuth]);
+    let outcome = handle(&ctx, "POST", "/certsrv/CES", "CN=ws01.corp.example.com", "", &rst_request("PKCS10", &csr, None));
+    assert_eq!(outcome.http_status, 200, "{}", body(&outcome));
+    let xml = body(&outcome);
+    assert!(xml.contains(soap::ACTION_RSTRC));
+    assert!(xml.contains(">Issued</DispositionMessage>"));
+
+    let cert_der = issued_cert(&outcome);
+    let template = extension_value(&cert_der, OID_CERTIFICATE_TEMPLATE).unwrap();
+    assert_eq!(template, template_value(MACHINE_OID).unwrap());
+    let policies = extension_value(&cert_der, OID_APPLICATION_POLICIES).unwrap();
+    assert_eq!(policies, application_policies_value(&profile_ekus(ctx.profile("machine").unwrap()).unwrap()).unwrap());
+    let (_, cert) = x509_parser::certificate::X509Certificate::from_der(&cert_der).unwrap();
+    assert!(cert.extended_key_usage().unwrap().unwrap().value.client_auth);
+
+    // The PKCS#7 token carries the issued certificate and the CA
+    let chain = B64.decode(element_text(&xml, "<RequestSecurityTokenResponse>", "BinarySecurityToken")).unwrap();
+    let ca_der = read_cert_der(&ctx.config.ca_intermediate_cert_path).unwrap();
+    assert!(chain.windows(cert_der.len()).any(|w| w == cert_der.as_slice()));
+    assert!(chain.windows(ca_der.len()).any(|w| w == ca_der.as_slice()));
+
+    let serial = element_text(&xml, "<RequestSecurityTokenResponse>", "RequestID");
+    let store = OxPersistenceCertStore::open(dir.path().join("cert.db").to_str().unwrap()).unwrap();
+    let record = store.get_cert_by_serial("t1", serial).unwrap().unwrap();
+    assert_eq!(record.enrollment_protocol, Some(EnrollmentProtocol::Ad));
+    assert_eq!(record.profile, "machine");
+    let filter = AuditFilter { action: Some(AuditAction::AdEnroll), ..Default::default() };
+    let events = store.get_audit_log("t1", &filter, &Pagination::default()).unwrap().items;
+    assert_eq!(events[0].actor, "CN=ws01.corp.example.com");
+    assert_eq!(events[0].details["template"], "Machine");
+}
+
+#[test]
+fn test_cmc_enrollment_by_template_name() {
+    let dir = tempfile::tempdir().unwrap();
+    let ctx = client_cert_ctx(&dir);
+
+    let csr = machine_csr(None, vec![rcgen::ExtendedKeyUsagePurpose::ServerAuth]);
+    let request = rst_request("CMC", &cmc_request(&csr), Some("WebServer"));
+    let outcome = handle(&ctx, "POST", "/certsrv/CES", "CN=ws01.corp.example.com", "", &request);
+    assert_eq!(outcome.http_status, 200, "{}", body(&outcome));
+    let cert_der = issued_cert(&outcome);
+    assert_eq!(extension_value(&cert_der, OID_CERTIFICATE_TEMPLATE).unwrap(), template_value(WEB_OID).unwrap());
+}
+
+#[test]
+fn test_issued_names_come_from_the_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let ctx = client_cert_ctx(&dir);
+
+    // A CSR naming another host and a user is issued for the requester's account
+    let key = rcgen::KeyPair::generate().unwrap();
+    let mut params = rcgen::CertificateParams::new(vec![
+        "dc01.corp.example.com".to_string(),
+        "administrator@corp.example.com".to_string(),
+    ]).unwrap();
+    params.distinguished_name.push(rcgen::DnType::CommonName, "Administrator");
+    let csr = params.serialize_request(&key).unwrap().der().to_vec();
+    let outcome = handle(&ctx, "POST", "/certsrv/CES", "CN=ws01.corp.example.com", "", &rst_request("PKCS10", &csr, Some("Machine")));
+    assert_eq!(outcome.http_status, 200, "{}", body(&outcome));
+    let cert_der = issued_cert(&outcome);
+    let (_, cert) = x509_parser::certificate::X509Certificate::from_der(&cert_der).unwrap();
+    assert_eq!(cert.subject().to_string(), "CN=ws01.corp.example.com");
+    let sans: Vec<String> = cert.subject_alternative_name().unwrap().unwrap().value.general_names
+        .iter().map(|n| n.to_string()).collect();
+    assert_eq!(sans, vec!["DNSName(ws01.corp.example.com)"]);
+
+    // Without SUBJECT_ALT_REQUIRE_DNS there is no SAN
+    let config = make_config(&dir, json!({ "templates": [
+        { "name": "Machine", "oid": MACHINE_OID, "autoenroll_group": "Autoenroll Computers",
+          "profile": "machine", "subject_name_flags": 0x1000_0000u32 },
+    ]}));
+    let ctx = AdAutoenrollContext::with_drivers(
+        config, None, Some(directory(&[COMPUTERS_GROUP], Some("ws01.corp.example.com"))),
+    ).unwrap();
+    let outcome = handle(&ctx, "POST", "/certsrv/CES", "CN=ws01.corp.example.com", "", &rst_request("PKCS10", &csr, Some("Machine")));
+    assert_eq!(outcome.http_status, 200, "{}", body(&outcome));
+    let cert_der = issued_cert(&outcome);
+    let (_, cert) = x509_parser::certificate::X509Certificate::from_der(&cert_der).unwrap();
+    assert!(cert.subject_alternative_name().unwrap().is_none());
+
+    // An account without a dNSHostName (a user) gets nothing
+    let ctx = AdAutoenrollContext::with_drivers(
+        make_config(&dir, json!({})), None, Some(directory(&[COMPUTERS_GROUP], None)),
+    ).unwrap();
+    let outcome = handle(&ctx, "POST", "/certsrv/CES", "CN=ws01.corp.example.com", "", &rst_request("PKCS10", &csr, Some("Machine")));
+    assert_eq!(outcome.http_status, 403);
+    assert!(body(&outcome).contains("dNSHostName"));
+}
+
+#[test]
+fn test_enrollment_faults() {
+    let dir = tempfile::tempdir().unwrap();
+    let ctx = client_cert_ctx(&dir);
+    let dn = "CN=ws01.corp.example.com";
+    let send = |request: Vec<u8>| handle(&ctx, "POST", "/certsrv/CES", dn, "", &request);
+
+    // Unknown template
+    let csr = machine_csr(Some("1.3.6.1.4.1.311.21.8.9.9"), vec![]);
+    let outcome = send(rst_request("PKCS10", &csr, None));
+    assert_eq!(outcome.http_status, 404);
+    assert!(body(&outcome).contains("<s:Value>PolicyNotFound</s:Value>"));
+    assert!(body(&outcome).contains("<a:RelatesTo>urn:uuid:6389e4a1-0002-4b1c-9f7e-3c4a5d6e7f80</a:RelatesTo>"));
+
+    // An EKU the template does not grant
+    let csr = machine_csr(Some(WEB_OID), vec![rcgen::ExtendedKeyUsagePurpose::CodeSigning]);
+    let outcome = send(rst_request("PKCS10", &csr, None));
+    assert_eq!(outcome.http_status, 403);
+    assert!(body(&outcome).contains("<s:Value>s:Sender</s:Value>"));
+
+    // Not a CSR
+    let outcome = send(rst_request("PKCS10", b"not a csr", Some("Machine")));
+    assert_eq!(outcome.http_status, 400);
+    assert!(body(&outcome).contains("<s:Value>InvalidArgument</s:Value>"));
+
+    // Only Issue is supported
+    let csr = machine_csr(Some(MACHINE_OID), vec![]);
+    let request = String::from_utf8(rst_request("PKCS10", &csr, None)).unwrap()
+        .replace(soap::REQUEST_TYPE_ISSUE, "http://schemas.microsoft.com/windows/pki/2009/01/enrollment/QueryTokenStatus");
+    assert_eq!(send(request.into_bytes()).http_status, 400);
+
+    // Not SOAP, and not a SOAP endpoint
+    assert_eq!(send(b"<html/>".to_vec()).http_status, 400);
+    assert_eq!(handle(&ctx, "POST", "/certsrv/other", dn, "", &get_policies_request()).http_status, 404);
+
+    // CA key missing
+    std::fs::remove_file(dir.path().join("keys").join("t1").join("intermediate.key.pem")).unwrap();
+    let outcome = send(rst_request("PKCS10", &csr, None));
+    assert_eq!(outcome.http_status, 503);
+    assert!(body(&outcome).contains("<s:Value>s:Receiver</s:Value>"));
+}
@@ -1,12 +1,13 @@
 use ox_cert_core::{
     issuer_params_from_cert_pem,
     model::{
-        AuditAction, AuditEvent, AuditFilter, CaKeyRecord, CaKeyStatus, CertFilter,
-        CertStoreConfig, CertificateRecord, KeyStoreConfig, KeyType, Pagination, ScepChallenge,
+        AcmeEabKey, AuditAction, AuditEvent, AuditFilter, CaKeyRecord, CaKeyStatus, CertFilter,
+        CertStoreConfig, CertificateRecord, KeyStoreConfig, KeyStoreType, KeyType, Pagination,
+        ScepChallenge,
     },
     open_keystore,
     store::{CertStore, OxPersistenceCertStore},
-    CertError,
+    CertError, KeyStoreSigner,
 };
 use serde::Deserialize;
 use time::OffsetDateTime;
@@ -190,6 +191,45 @@ pub fn handle(config: &AdminConfig, method: &str, path: &str, query: &str, body:
             }
         }
 
+        // POST /api/v1/acme/eab
+        ("POST", ["api", "v1", "acme", "eab"]) => {
+            handle_eab_provision(config, &store, body, &request_id)
+        }
+
+        // GET /api/v1/acme/eab
+        ("GET", ["api", "v1", "acme", "eab"]) => {
+            match store.list_acme_eab_keys(tenant) {
+                Ok(keys) => AdminOutcome {
+                    http_status: 200,
+                    body_json: serde_json::json!({
+                        "data": keys.iter().map(eab_key_to_json).collect::<Vec<_>>(),
+                        "meta": { "tenant_id": tenant, "request_id": request_id }
+                    }).to_string(),
+                },
+                Err(e) => err!(500, "INTERNAL_ERROR", e.to_string()),
+            }
+        }
+
+        // DELETE /api/v1/acme/eab/{kid}
+        ("DELETE", ["api", "v1", "acme", "eab", kid]) => {
+            let mut key = match store.get_acme_eab_key(tenant, kid) {
+                Ok(Some(k)) => k,
+                Ok(None) => err!(404, "NOT_FOUND", format!("EAB key '{}' not found", kid)),
+                Err(e) => err!(500, "INTERNAL_ERROR", e.to_string()),
+            };
+            key.revoked = true;
+            if let Err(e) = store.store_acme_eab_key(tenant, &key) {
+                err!(500, "INTERNAL_ERROR", e.to_string());
+            }
+            AdminOutcome {
+                http_status: 200,
+                body_json: serde_json::json!({
+                    "data": eab_key_to_json(&key),
+                    "meta": { "tenant_id": tenant, "request_id": request_id }
+                }).to_string(),
+            }
+        }
+
         // GET /api/v1/ssh/ca
         ("GET", ["api", "v1", "ssh", "ca"]) => {
             AdminOutcome {
@@ -441,12 +481,28 @@ fn handle_cross_sign(config: &AdminConfig, store: &OxPersistenceCertStore, body:
         },
     };
 
-    let ca_key_pem = match ks.load_key_pem(tenant, ca_key_id) {
-        Ok(p) => p,
-        Err(e) => return AdminOutcome {
-            http_status: 503,
-            body_json: serde_json::json!({ "error": { "code": "CA_NOT_READY", "message": e.to_string() } }).to_string(),
-        },
+    // A software key is handed to OpenSSL, which keeps multi-valued RDNs from the CSR
+    // subject. An HSM key cannot leave the token, so it signs through the keystore.
+    let signed = if matches!(config.keystore.store_type, KeyStoreType::Pkcs11) {
+        let ca_key = match KeyStoreSigner::new(ks.as_ref(), tenant, ca_key_id) {
+            Ok(k) => k,
+            Err(e) => return AdminOutcome {
+                http_status: 503,
+                body_json: serde_json::json!({ "error": { "code": "CA_NOT_READY", "message": e.to_string() } }).to_string(),
+            },
+        };
+        issuer_params_from_cert_pem(&ca_cert_pem).and_then(|ca_params| {
+            ox_cert_core::cross_sign_csr(&csr_pem, tenant, 3 * 365 * 86400, &ca_params, &ca_key)
+        })
+    } else {
+        let ca_key_pem = match ks.load_key_pem(tenant, ca_key_id) {
+            Ok(p) => p,
+            Err(e) => return AdminOutcome {
+                http_status: 503,
+                body_json: serde_json::json!({ "error": { "code": "CA_NOT_READY", "message": e.to_string() } }).to_string(),
+            },
+        };
+        ox_cert_core::cross_sign_csr_with_pem(&csr_pem, &ca_cert_pem, &ca_key_pem, tenant, 3 * 365)
     };
 
     let safe_error_msg = |e: &CertError| -> String {
@@ -454,7 +510,7 @@ fn handle_cross_sign(config: &AdminConfig, store: &OxPersistenceCertStore, body:
         e.to_string().replace('\0', "<NUL>")
     };
 
-    match ox_cert_core::cross_sign_csr_with_pem(&csr_pem, &ca_cert_pem, &ca_key_pem, tenant, 3 * 365) {
+    match signed {
         Ok(record) => {
             let _ = store.store_cert(tenant, &record);
             let now = OffsetDateTime::now_utc();
@@ -512,6 +568,84 @@ fn handle_scep_provision(config: &AdminConfig, store: &OxPersistenceCertStore, r
     }
 }
 
+#[derive(Debug, Deserialize, Default)]
+struct EabProvisionRequest {
+    profile: Option<String>,
+    validity_days: Option<u32>,
+    #[serde(default)]
+    allowed_domains: Vec<String>,
+    #[serde(default)]
+    wildcard_allowed: bool,
+}
+
+fn handle_eab_provision(config: &AdminConfig, store: &OxPersistenceCertStore, body: &str, request_id: &str) -> AdminOutcome {
+    use rand::RngCore;
+
+    let tenant = &config.tenant_id;
+    let req: EabProvisionRequest = if body.trim().is_empty() {
+        EabProvisionRequest::default()
+    } else {
+        match serde_json::from_str(body) {
+            Ok(r) => r,
+            Err(e) => return AdminOutcome {
+                http_status: 400,
+                body_json: serde_json::json!({
+                    "error": { "code": "INVALID_REQUEST", "message": e.to_string() },
+                    "meta": { "tenant_id": tenant, "request_id": request_id }
+                }).to_string(),
+            },
+        }
+    };
+
+    let mut hmac_key = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut hmac_key);
+    let key = AcmeEabKey {
+        kid: Uuid::new_v4().to_string(),
+        tenant_id: tenant.clone(),
+        hmac_key: base64::Engine::encode(&base64::engine::general_purpose::URL_SAFE_NO_PAD, hmac_key),
+        profile: req.profile,
+        validity_days: req.validity_days,
+        allowed_domains: req.allowed_domains,
+        wildcard_allowed: req.wildcard_allowed,
+        account_id: None,
+        revoked: false,
+        created_at: OffsetDateTime::now_utc(),
+    };
+    if let Err(e) = store.store_acme_eab_key(tenant, &key) {
+        return AdminOutcome {
+            http_status: 500,
+            body_json: serde_json::json!({
+                "error": { "code": "INTERNAL_ERROR", "message": e.to_string() },
+                "meta": { "tenant_id": tenant, "request_id": request_id }
+            }).to_string(),
+        };
+    }
+
+    // The HMAC key is only ever returned here; list and revoke omit it
+    let mut data = eab_key_to_json(&key);
+    data["hmac_key"] = serde_json::Value::String(key.hmac_key.clone());
+    AdminOutcome {
+        http_status: 201,
+        body_json: serde_json::json!({
+            "data": data,
+            "meta": { "tenant_id": tenant, "request_id": request_id }
+        }).to_string(),
+    }
+}
+
+fn eab_key_to_json(key: &AcmeEabKey) -> serde_json::Value {
+    serde_json::json!({
+        "kid": key.kid,
+        "profile": key.profile,
+        "validity_days": key.validity_days,
+        "allowed_domains": key.allowed_domains,
+        "wildcard_allowed": key.wildcard_allowed,
+        "account_id": key.account_id,
+        "revoked": key.revoked,
+        "created_at": fmt_dt(key.created_at),
+    })
+}
+
 fn random_password(len: usize) -> String {
     use rand::Rng;
     const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
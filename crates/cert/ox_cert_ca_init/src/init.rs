@@ -4,7 +4,7 @@ use ox_cert_core::{
     model::{CaKeyRecord, CaKeyStatus, KeyType, NameConstraints},
     open_keystore,
     store::{CertStore, OxPersistenceCertStore},
-    CertError,
+    CertError, KeyStoreSigner,
 };
 use time::OffsetDateTime;
 
@@ -22,15 +22,15 @@ fn parse_key_type(s: &str) -> Result<KeyType, CertError> {
     }
 }
 
-/// Load or generate a `rcgen::KeyPair` for the given CA cert config.
+/// Load or generate the keystore key for the given CA cert config.
 /// If the key doesn't exist and `auto_generate` is true, it is generated.
-/// Returns the key pair and whether it was freshly generated.
-fn load_or_generate_key(
-    ks: &dyn ox_cert_core::KeyStore,
+/// Returns a signer for the key and whether it was freshly generated.
+fn load_or_generate_key<'a>(
+    ks: &'a dyn ox_cert_core::KeyStore,
     tenant_id: &str,
     cfg: &CaCertConfig,
     auto_generate: bool,
-) -> Result<(rcgen::KeyPair, bool), CertError> {
+) -> Result<(KeyStoreSigner<&'a dyn ox_cert_core::KeyStore>, bool), CertError> {
     let key_id = &cfg.key_path;
     let exists = ks.key_exists(tenant_id, key_id)?;
 
@@ -46,10 +46,7 @@ fn load_or_generate_key(
         tracing::info!("generated new {} key '{}'", cfg.key_type, key_id);
     }
 
-    let pem = ks.load_key_pem(tenant_id, key_id)?;
-    let kp = rcgen::KeyPair::from_pem(&pem)
-        .map_err(|e| CertError::Crypto(format!("failed to load key pair from PEM: {}", e)))?;
-    Ok((kp, !exists))
+    Ok((KeyStoreSigner::new(ks, tenant_id, key_id)?, !exists))
 }
 
 /// Core CA initialization logic, independent of the plugin ABI.
@@ -1,8 +1,12 @@
-use crate::model::{CertificateRecord, CertStatus, EnrollmentProfile, SanType};
+use crate::model::{
+    CertificateRecord, CertStatus, CrlPartitionConfig, CtConfig, CustomExtension, EnrollmentProfile,
+    SanType,
+};
 use crate::CertError;
 use rcgen::{
-    BasicConstraints, CertificateParams, DistinguishedName as RcgenDN, DnType,
-    ExtendedKeyUsagePurpose, IsCa, Issuer, KeyPair, KeyUsagePurpose, SanType as RcgenSan,
+    BasicConstraints, CertificateParams, CrlDistributionPoint, DistinguishedName as RcgenDN, DnType,
+    ExtendedKeyUsagePurpose, IsCa, Issuer, KeyUsagePurpose, PublicKeyData, SigningKey,
+    SanType as RcgenSan,
 };
 use time::OffsetDateTime;
 use uuid::Uuid;
@@ -21,6 +25,7 @@ pub struct CertBuilder {
     path_length: Option<u32>,
     policy_oids: Vec<String>,
     profile: String,
+    crl_partitions: Option<CrlPartitionConfig>,
 }
 
 impl CertBuilder {
@@ -33,6 +38,7 @@ impl CertBuilder {
             path_length: None,
             policy_oids: vec![],
             profile: "standard".to_string(),
+            crl_partitions: None,
         }
     }
 
@@ -67,6 +73,12 @@ impl CertBuilder {
         self
     }
 
+    /// Assign the certificate a CRL shard and point its CRL distribution point at it.
+    pub fn crl_partitions(mut self, partitions: Option<&CrlPartitionConfig>) -> Self {
+        self.crl_partitions = partitions.cloned();
+        self
+    }
+
     pub fn apply_profile(self, p: &EnrollmentProfile) -> Self {
         self.validity_seconds(p.validity_seconds)
             .is_ca(p.is_ca, p.path_length)
@@ -76,10 +88,18 @@ impl CertBuilder {
 
     /// Build `rcgen::CertificateParams` from the current builder state.
     pub fn build_params(&self) -> Result<CertificateParams, CertError> {
+        self.params_for_serial(Uuid::new_v4())
+    }
+
+    fn params_for_serial(&self, serial: Uuid) -> Result<CertificateParams, CertError> {
         let mut params = CertificateParams::default();
 
-        let serial = Uuid::new_v4();
         params.serial_number = Some(rcgen::SerialNumber::from_slice(serial.as_bytes()));
+        if let Some(p) = &self.crl_partitions {
+            params.crl_distribution_points = vec![CrlDistributionPoint {
+                uris: vec![p.url(p.shard_for(&serial))],
+            }];
+        }
 
         let now = OffsetDateTime::now_utc();
         let not_after = now + time::Duration::seconds(self.validity_seconds as i64);
@@ -144,10 +164,10 @@ impl CertBuilder {
     pub fn self_sign(
         &self,
         tenant_id: &str,
-        key_pair: &KeyPair,
+        key_pair: &impl SigningKey,
     ) -> Result<CertificateRecord, CertError> {
-        let params = self.build_params()?;
-        let serial = Uuid::new_v4().to_string();
+        let serial = Uuid::new_v4();
+        let params = self.params_for_serial(serial)?;
         let now = OffsetDateTime::now_utc();
         let not_after = now + time::Duration::seconds(self.validity_seconds as i64);
 
@@ -155,7 +175,7 @@ impl CertBuilder {
             .self_signed(key_pair)
             .map_err(|e| CertError::Crypto(format!("self-sign failed: {}", e)))?;
 
-        Ok(self.into_record(tenant_id, &serial, now, not_after, cert.pem(), self.subject.as_deref().unwrap_or("").to_string(), key_pair))
+        Ok(self.into_record(tenant_id, serial, now, not_after, cert.pem(), self.subject.as_deref().unwrap_or("").to_string(), key_pair))
     }
 
     /// Sign with an issuing CA.
@@ -163,12 +183,12 @@ impl CertBuilder {
     pub fn sign_with_issuer(
         &self,
         tenant_id: &str,
-        subject_key: &KeyPair,
+        subject_key: &impl PublicKeyData,
         issuer_params: &CertificateParams,
-        issuer_key: &KeyPair,
+        issuer_key: &impl SigningKey,
     ) -> Result<CertificateRecord, CertError> {
-        let params = self.build_params()?;
-        let serial = Uuid::new_v4().to_string();
+        let serial = Uuid::new_v4();
+        let params = self.params_for_serial(serial)?;
         let now = OffsetDateTime::now_utc();
         let not_after = now + time::Duration::seconds(self.validity_seconds as i64);
 
@@ -178,18 +198,18 @@ impl CertBuilder {
             .map_err(|e| CertError::Crypto(format!("CA signing failed: {}", e)))?;
 
         let issuer_dn_str = dn_to_string(&issuer_params.distinguished_name);
-        Ok(self.into_record(tenant_id, &serial, now, not_after, cert.pem(), issuer_dn_str, subject_key))
+        Ok(self.into_record(tenant_id, serial, now, not_after, cert.pem(), issuer_dn_str, subject_key))
     }
 
     fn into_record(
         &self,
         tenant_id: &str,
-        serial: &str,
+        serial: Uuid,
         now: OffsetDateTime,
         not_after: OffsetDateTime,
         pem: String,
         issuer_dn: String,
-        key_pair: &KeyPair,
+        key_pair: &impl PublicKeyData,
     ) -> CertificateRecord {
         CertificateRecord {
             serial: serial.to_string(),
@@ -211,6 +231,7 @@ impl CertBuilder {
             scts: vec![],
             policy_oids: self.policy_oids.clone(),
             enrollment_protocol: None,
+            crl_shard: self.crl_partitions.as_ref().map(|p| p.shard_for(&serial)),
             created_at: now,
         }
     }
@@ -224,9 +245,27 @@ impl Default for CertBuilder {
 // CSR signing (sign an external CSR with a CA key)
 // ---------------------------------------------------------------------------
 
+/// What the issuing CA adds to a certificate beyond the CSR. Every enrollment protocol
+/// signs through `sign_csr`, so these apply the same way to all of them.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct IssuanceOptions<'a> {
+    /// Subject DN replacing the CSR's, for protocols where the CA names the subject.
+    pub subject: Option<&'a str>,
+    /// Extensions the CA decides, such as the Microsoft certificate template extensions.
+    pub extensions: &'a [CustomExtension],
+    /// Certificate Transparency; SCTs are embedded after `extensions` so the logs see the
+    /// final TBS.
+    pub ct: Option<&'a CtConfig>,
+    /// Issuer chain passed to the CT logs, issuing CA first. See [`crate::ct::issuer_chain`].
+    pub ct_issuer_chain: &'a [String],
+    /// Partitioned CRLs: the certificate gets a shard and that shard's CRL distribution point.
+    pub crl_partitions: Option<&'a CrlPartitionConfig>,
+}
+
 /// Sign a PEM-encoded CSR with the provided CA key pair and params.
 /// If `override_sans` is Some, the CSR's SANs are replaced with the provided list
 /// (enrichment/server-side SAN injection).
+#[allow(clippy::too_many_arguments)]
 pub fn sign_csr(
     csr_pem: &str,
     tenant_id: &str,
@@ -234,20 +273,31 @@ pub fn sign_csr(
     validity_seconds: u64,
     override_sans: Option<&[SanType]>,
     ca_params: &CertificateParams,
-    ca_key: &KeyPair,
+    ca_key: &impl SigningKey,
+    options: &IssuanceOptions,
 ) -> Result<CertificateRecord, CertError> {
-    use rcgen::{CertificateSigningRequestParams, SanType as RcgenSan};
-
-    let csr_params = CertificateSigningRequestParams::from_pem(csr_pem)
-        .map_err(|e| CertError::Crypto(format!("CSR parse failed: {}", e)))?;
+    let (mut params, public_key) = read_csr(csr_pem)?;
 
     let now = OffsetDateTime::now_utc();
     let not_after = now + time::Duration::seconds(validity_seconds as i64);
 
-    let mut params = csr_params.params;
     params.not_before = rcgen::date_time_ymd(now.year(), now.month() as u8, now.day());
     params.not_after = rcgen::date_time_ymd(not_after.year(), not_after.month() as u8, not_after.day());
-    params.serial_number = Some(rcgen::SerialNumber::from_slice(Uuid::new_v4().as_bytes()));
+    // The record serial is the UUID form of the certificate serial, so
+    // lookups by a serial parsed from the certificate (revocation, OCSP, ARI) succeed
+    let serial_uuid = Uuid::new_v4();
+    params.serial_number = Some(rcgen::SerialNumber::from_slice(serial_uuid.as_bytes()));
+    params.use_authority_key_identifier_extension = true;
+    let crl_shard = options.crl_partitions.map(|p| p.shard_for(&serial_uuid));
+    if let (Some(p), Some(shard)) = (options.crl_partitions, crl_shard) {
+        params.crl_distribution_points = vec![CrlDistributionPoint { uris: vec![p.url(shard)] }];
+    }
+
+    if let Some(subject) = options.subject {
+        let mut dn = RcgenDN::new();
+        parse_dn_into(&mut dn, subject);
+        params.distinguished_name = dn;
+    }
 
     // Replace SANs if caller provided overrides.
     if let Some(sans) = override_sans {
@@ -269,7 +319,7 @@ pub fn sign_csr(
         }
     }
 
-    let serial = Uuid::new_v4().to_string();
+    let serial = serial_uuid.to_string();
     let subject_dn = dn_to_string(&params.distinguished_name);
     let subject_cn = extract_cn(&subject_dn);
     let san_strings: Vec<String> = params.subject_alt_names.iter().map(|s| match s {
@@ -282,10 +332,10 @@ pub fn sign_csr(
 
     let issuer = Issuer::from_params(ca_params, ca_key);
     let cert = params
-        .signed_by(&csr_params.public_key, &issuer)
+        .signed_by(&public_key.as_ref(), &issuer)
         .map_err(|e| CertError::Crypto(format!("CSR signing failed: {}", e)))?;
 
-    Ok(CertificateRecord {
+    let mut record = CertificateRecord {
         serial,
         tenant_id: tenant_id.to_string(),
         subject_cn,
@@ -305,8 +355,40 @@ pub fn sign_csr(
         scts: vec![],
         policy_oids: vec![],
         enrollment_protocol: None,
+        crl_shard,
         created_at: now,
-    })
+    };
+    add_extensions(&mut record, options.extensions, ca_key)?;
+    if let Some(ct) = options.ct {
+        let chain: Vec<&str> = options.ct_issuer_chain.iter().map(String::as_str).collect();
+        crate::ct::add_scts(&mut record, &chain, ca_key, ct)?;
+    }
+    Ok(record)
+}
+
+/// Append extensions to an issued certificate and re-sign it with the issuing key.
+/// `sign_csr` applies `IssuanceOptions::extensions` through this.
+pub fn add_extensions(
+    record: &mut CertificateRecord,
+    extensions: &[CustomExtension],
+    ca_key: &impl SigningKey,
+) -> Result<(), CertError> {
+    if extensions.is_empty() {
+        return Ok(());
+    }
+    let cert_der = ::pem::parse(record.pem.as_bytes())
+        .map_err(|e| CertError::Crypto(format!("certificate PEM decode: {}", e)))?
+        .into_contents();
+    let mut encoded = Vec::new();
+    for ext in extensions {
+        encoded.extend(crate::ct::encode_extension(&ext.oid, ext.critical, &ext.value)?);
+    }
+    let final_der = crate::ct::resign(&cert_der, &encoded, ca_key)?;
+    record.pem = ::pem::encode_config(
+        &::pem::Pem::new("CERTIFICATE", final_der),
+        ::pem::EncodeConfig::new().set_line_ending(::pem::LineEnding::LF),
+    );
+    Ok(())
 }
 
 /// Sign a CSR as a CA (intermediate) certificate, explicitly setting CA extensions.
@@ -322,7 +404,7 @@ pub fn cross_sign_csr(
     tenant_id: &str,
     validity_seconds: u64,
     ca_params: &CertificateParams,
-    ca_key: &KeyPair,
+    ca_key: &impl SigningKey,
 ) -> Result<CertificateRecord, CertError> {
     use rcgen::SubjectPublicKeyInfo;
     use x509_parser::prelude::FromDer;
@@ -368,6 +450,7 @@ pub fn cross_sign_csr(
         scts: vec![],
         policy_oids: vec![],
         enrollment_protocol: None,
+        crl_shard: None,
         created_at: now,
     })
 }
@@ -478,6 +561,7 @@ pub fn cross_sign_csr_with_pem(
             scts:                 vec![],
             policy_oids:          vec![],
             enrollment_protocol:  None,
+            crl_shard:            None,
             created_at:           now,
         })
     })();
@@ -532,12 +616,9 @@ pub fn issuer_params_from_cert_pem(cert_pem: &str) -> Result<CertificateParams,
 
 /// Parse a PEM CSR and extract subject DN, SANs, and key info.
 pub fn parse_csr(csr_pem: &str) -> Result<crate::model::CsrInfo, CertError> {
-    use rcgen::{CertificateSigningRequestParams, SanType as RcgenSan};
-
-    let csr = CertificateSigningRequestParams::from_pem(csr_pem)
-        .map_err(|e| CertError::Crypto(format!("CSR parse: {}", e)))?;
+    use x509_parser::prelude::FromDer;
 
-    let params = &csr.params;
+    let (params, _) = read_csr(csr_pem)?;
     let subject_dn = dn_to_string(&params.distinguished_name);
     let subject_cn = extract_cn(&subject_dn);
 
@@ -553,16 +634,17 @@ pub fn parse_csr(csr_pem: &str) -> Result<crate::model::CsrInfo, CertError> {
         })
         .collect();
 
-    use rcgen::PublicKeyData;
-    let public_key_der = csr.public_key.der_bytes().to_vec();
-    let (key_type, key_bits) = detect_csr_key_type(&public_key_der);
-
     let raw_der = {
         use ::pem::parse as pem_parse;
         pem_parse(csr_pem)
             .map_err(|e| CertError::Crypto(format!("PEM decode: {}", e)))?
             .into_contents()
     };
+    // Key type detection needs the full SubjectPublicKeyInfo, algorithm OID included
+    let (_, csr) = x509_parser::certification_request::X509CertificationRequest::from_der(&raw_der)
+        .map_err(|e| CertError::Crypto(format!("CSR parse: {}", e)))?;
+    let public_key_der = csr.certification_request_info.subject_pki.raw.to_vec();
+    let (key_type, key_bits) = detect_csr_key_type(&public_key_der);
 
     Ok(crate::model::CsrInfo {
         subject_dn,
@@ -575,6 +657,108 @@ pub fn parse_csr(csr_pem: &str) -> Result<crate::model::CsrInfo, CertError> {
     })
 }
 
+/// Certificate params and public key requested by a PEM CSR.
+///
+/// rcgen refuses CSRs that request extensions it cannot map, and Windows clients always
+/// request some (certificate template, S/MIME capabilities, subject key identifier).
+/// Those CSRs are read with x509-parser instead: subject, SANs, key usage and EKU are
+/// kept, other non-critical extensions are dropped and critical ones are refused.
+fn read_csr(csr_pem: &str) -> Result<(CertificateParams, Box<dyn PublicKeyData>), CertError> {
+    use rcgen::CertificateSigningRequestParams;
+
+    match CertificateSigningRequestParams::from_pem(csr_pem) {
+        Ok(csr) => Ok((csr.params, Box::new(csr.public_key))),
+        Err(rcgen::Error::UnsupportedExtension) => read_csr_lenient(csr_pem),
+        Err(e) => Err(CertError::Crypto(format!("CSR parse failed: {}", e))),
+    }
+}
+
+fn read_csr_lenient(csr_pem: &str) -> Result<(CertificateParams, Box<dyn PublicKeyData>), CertError> {
+    use rcgen::SubjectPublicKeyInfo;
+    use x509_parser::certification_request::X509CertificationRequest;
+    use x509_parser::cri_attributes::ParsedCriAttribute;
+    use x509_parser::extensions::{GeneralName, ParsedExtension};
+    use x509_parser::prelude::FromDer;
+
+    let der = ::pem::parse(csr_pem)
+        .map_err(|e| CertError::Crypto(format!("CSR PEM decode: {}", e)))?
+        .into_contents();
+    let (_, csr) = X509CertificationRequest::from_der(&der)
+        .map_err(|e| CertError::Crypto(format!("CSR parse failed: {}", e)))?;
+    csr.verify_signature()
+        .map_err(|_| CertError::Crypto("CSR parse failed: signature does not verify".to_string()))?;
+    let info = &csr.certification_request_info;
+    let public_key = SubjectPublicKeyInfo::from_der(info.subject_pki.raw)
+        .map_err(|e| CertError::Crypto(format!("SPKI parse: {}", e)))?;
+
+    let mut params = CertificateParams::default();
+    let mut dn = RcgenDN::new();
+    parse_dn_into(&mut dn, &info.subject.to_string());
+    params.distinguished_name = dn;
+
+    let invalid = |what: &str| CertError::Validation(format!("invalid {} SAN in CSR", what));
+    let requested = info.iter_attributes().filter_map(|a| match a.parsed_attribute() {
+        ParsedCriAttribute::ExtensionRequest(req) => Some(req.extensions.iter()),
+        _ => None,
+    });
+    for ext in requested.flatten() {
+        match ext.parsed_extension() {
+            ParsedExtension::SubjectAlternativeName(san) => {
+                for name in &san.general_names {
+                    let mapped = match name {
+                        GeneralName::DNSName(n) => RcgenSan::DnsName((*n).try_into().map_err(|_| invalid("DNS"))?),
+                        GeneralName::RFC822Name(e) => RcgenSan::Rfc822Name((*e).try_into().map_err(|_| invalid("email"))?),
+                        GeneralName::URI(u) => RcgenSan::URI((*u).try_into().map_err(|_| invalid("URI"))?),
+                        GeneralName::IPAddress(ip) => match ip.len() {
+                            4 => RcgenSan::IpAddress(<[u8; 4]>::try_from(*ip).map_err(|_| invalid("IP"))?.into()),
+                            16 => RcgenSan::IpAddress(<[u8; 16]>::try_from(*ip).map_err(|_| invalid("IP"))?.into()),
+                            _ => return Err(invalid("IP")),
+                        },
+                        _ => continue,
+                    };
+                    params.subject_alt_names.push(mapped);
+                }
+            }
+            ParsedExtension::KeyUsage(ku) => {
+                let flags = [
+                    (ku.digital_signature(), KeyUsagePurpose::DigitalSignature),
+                    (ku.non_repudiation(), KeyUsagePurpose::ContentCommitment),
+                    (ku.key_encipherment(), KeyUsagePurpose::KeyEncipherment),
+                    (ku.data_encipherment(), KeyUsagePurpose::DataEncipherment),
+                    (ku.key_agreement(), KeyUsagePurpose::KeyAgreement),
+                    (ku.key_cert_sign(), KeyUsagePurpose::KeyCertSign),
+                    (ku.crl_sign(), KeyUsagePurpose::CrlSign),
+                    (ku.encipher_only(), KeyUsagePurpose::EncipherOnly),
+                    (ku.decipher_only(), KeyUsagePurpose::DecipherOnly),
+                ];
+                params.key_usages = flags.into_iter().filter(|(set, _)| *set).map(|(_, k)| k).collect();
+            }
+            ParsedExtension::ExtendedKeyUsage(eku) => {
+                let known = [
+                    (eku.any, ExtendedKeyUsagePurpose::Any),
+                    (eku.server_auth, ExtendedKeyUsagePurpose::ServerAuth),
+                    (eku.client_auth, ExtendedKeyUsagePurpose::ClientAuth),
+                    (eku.code_signing, ExtendedKeyUsagePurpose::CodeSigning),
+                    (eku.email_protection, ExtendedKeyUsagePurpose::EmailProtection),
+                    (eku.time_stamping, ExtendedKeyUsagePurpose::TimeStamping),
+                    (eku.ocsp_signing, ExtendedKeyUsagePurpose::OcspSigning),
+                ];
+                params.extended_key_usages = known.into_iter().filter(|(set, _)| *set).map(|(_, p)| p).collect();
+                for oid in &eku.other {
+                    if let Some(arcs) = oid.iter() {
+                        params.extended_key_usages.push(ExtendedKeyUsagePurpose::Other(arcs.collect()));
+                    }
+                }
+            }
+            _ if ext.critical => {
+                return Err(CertError::Validation(format!("unsupported critical extension {} in CSR", ext.oid)));
+            }
+            _ => {}
+        }
+    }
+    Ok((params, Box::new(public_key)))
+}
+
 // ---------------------------------------------------------------------------
 // Helpers
 // ---------------------------------------------------------------------------
@@ -657,7 +841,7 @@ fn sans_to_strings(sans: &[SanType]) -> Vec<String> {
         .collect()
 }
 
-fn detect_key_type_str(kp: &KeyPair) -> String {
+fn detect_key_type_str(kp: &impl PublicKeyData) -> String {
     let alg = kp.algorithm();
     let name = format!("{:?}", alg);
     if name.contains("Ed25519") {
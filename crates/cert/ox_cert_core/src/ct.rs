@@ -0,0 +1,380 @@
+//! Certificate Transparency (RFC 6962) precertificate submission and SCT embedding.
+//!
+//! `sign_csr` signs the certificate as usual, then hands it to [`add_scts`]. The TBS is
+//! re-signed with the critical poison extension to form the precertificate, which is
+//! posted to each log's `add-pre-chain`. Every returned SCT is verified against the
+//! log's `public_key_b64` before it counts towards `min_scts`. The final certificate is
+//! the original TBS with the SCT list extension appended, re-signed by the same CA key,
+//! so the TBS the logs signed is exactly the final TBS minus the SCT list.
+
+use crate::model::{CertificateRecord, CtConfig, CtFailureMode, CtLogConfig, Sct};
+use crate::CertError;
+use base64::engine::general_purpose::STANDARD as B64;
+use base64::Engine;
+use rcgen::SigningKey;
+
+/// Precertificate poison, 1.3.6.1.4.1.11129.2.4.3 (DER, without tag and length).
+pub const POISON_OID: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0xd6, 0x79, 0x02, 0x04, 0x03];
+/// Embedded SCT list, 1.3.6.1.4.1.11129.2.4.2 (DER, without tag and length).
+pub const SCT_LIST_OID: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0xd6, 0x79, 0x02, 0x04, 0x02];
+
+// TLS `HashAlgorithm` / `SignatureAlgorithm` codes used in DigitallySigned.
+const HASH_SHA256: u8 = 4;
+const SIG_RSA: u8 = 1;
+const SIG_ECDSA: u8 = 3;
+
+/// Submit the certificate's precertificate to every configured log and apply the
+/// `min_scts` / `on_failure` rules. Returns the verified SCTs, which may be fewer than
+/// `min_scts` (or none) only under `on_failure: warn`.
+///
+/// `issuer_chain_der` starts with the issuing CA certificate; any further certificates
+/// (up to the root) are passed on to the logs.
+pub fn submit_to_ct_logs(
+    cert_der: &[u8],
+    issuer_chain_der: &[Vec<u8>],
+    ca_key: &impl SigningKey,
+    config: &CtConfig,
+) -> Result<Vec<Sct>, CertError> {
+    let issuer_der = issuer_chain_der
+        .first()
+        .ok_or_else(|| CertError::Validation("CT submission needs the issuer certificate".to_string()))?;
+    let (tbs, _) = split_certificate(cert_der)?;
+    let issuer_key_hash = issuer_key_hash(issuer_der)?;
+
+    let poison = der_tlv(0x30, &[
+        der_tlv(0x06, POISON_OID),
+        vec![0x01, 0x01, 0xff],
+        der_tlv(0x04, &[0x05, 0x00]),
+    ].concat());
+    let precert = resign(cert_der, &poison, ca_key)?;
+
+    let mut chain = vec![B64.encode(&precert)];
+    chain.extend(issuer_chain_der.iter().map(|c| B64.encode(c)));
+    let body = serde_json::json!({ "chain": chain });
+
+    let agent = ureq::AgentBuilder::new()
+        .timeout(std::time::Duration::from_secs(config.timeout_secs))
+        .build();
+
+    let mut scts = Vec::new();
+    for log in &config.logs {
+        match submit_to_log(&agent, log, &body, tbs, &issuer_key_hash) {
+            Ok(sct) => scts.push(sct),
+            Err(e) => tracing::warn!("CT log {} failed: {}", log.name, e),
+        }
+    }
+
+    if scts.len() < config.min_scts {
+        let msg = format!("{} of {} required SCTs obtained", scts.len(), config.min_scts);
+        match config.on_failure {
+            CtFailureMode::Block => return Err(CertError::CtFailure(msg)),
+            CtFailureMode::Warn => tracing::warn!("issuing without enough SCTs: {}", msg),
+        }
+    }
+    Ok(scts)
+}
+
+/// Append the SCT list extension to the certificate's TBS and re-sign it. With no SCTs
+/// the certificate is returned unchanged.
+pub fn embed_scts(cert_der: &[u8], scts: &[Sct], ca_key: &impl SigningKey) -> Result<Vec<u8>, CertError> {
+    if scts.is_empty() {
+        return Ok(cert_der.to_vec());
+    }
+
+    let mut list = Vec::new();
+    for sct in scts {
+        let serialized = serialize_sct(sct)?;
+        list.extend_from_slice(&(serialized.len() as u16).to_be_bytes());
+        list.extend_from_slice(&serialized);
+    }
+    let mut tls_list = (list.len() as u16).to_be_bytes().to_vec();
+    tls_list.extend_from_slice(&list);
+
+    let extension = der_tlv(0x30, &[
+        der_tlv(0x06, SCT_LIST_OID),
+        der_tlv(0x04, &der_tlv(0x04, &tls_list)),
+    ].concat());
+    resign(cert_der, &extension, ca_key)
+}
+
+/// Run CT for an issued record when `config.enabled`: submit, embed, and replace the
+/// record's PEM and SCT list. `issuer_chain_pem` holds the issuing CA first.
+pub fn add_scts(
+    record: &mut CertificateRecord,
+    issuer_chain_pem: &[&str],
+    ca_key: &impl SigningKey,
+    config: &CtConfig,
+) -> Result<(), CertError> {
+    if !config.enabled {
+        return Ok(());
+    }
+    let cert_der = pem::parse(record.pem.as_bytes())
+        .map_err(|e| CertError::Crypto(format!("certificate PEM decode: {}", e)))?
+        .into_contents();
+    let mut issuer_chain = Vec::new();
+    for pem_str in issuer_chain_pem {
+        let items = pem::parse_many(pem_str.as_bytes())
+            .map_err(|e| CertError::Crypto(format!("issuer PEM decode: {}", e)))?;
+        issuer_chain.extend(items.into_iter().map(|p| p.into_contents()));
+    }
+
+    let scts = submit_to_ct_logs(&cert_der, &issuer_chain, ca_key, config)?;
+    if !scts.is_empty() {
+        let final_der = embed_scts(&cert_der, &scts, ca_key)?;
+        record.pem = pem::encode_config(
+            &pem::Pem::new("CERTIFICATE", final_der),
+            pem::EncodeConfig::new().set_line_ending(pem::LineEnding::LF),
+        );
+    }
+    record.scts = scts;
+    Ok(())
+}
+
+/// Issuer chain for log submission: the issuing CA PEM followed by the root CA read from
+/// `root_cert_path`. Empty unless CT is enabled, so the root is only required then.
+pub fn issuer_chain(
+    config: Option<&CtConfig>,
+    ca_cert_pem: &str,
+    root_cert_path: &str,
+) -> Result<Vec<String>, CertError> {
+    if !config.is_some_and(|c| c.enabled) {
+        return Ok(vec![]);
+    }
+    let root_pem = std::fs::read_to_string(root_cert_path)
+        .map_err(|e| CertError::NotFound(format!("root CA certificate {}: {}", root_cert_path, e)))?;
+    Ok(vec![ca_cert_pem.to_string(), root_pem])
+}
+
+// ---------------------------------------------------------------------------
+// Log submission and SCT verification
+// ---------------------------------------------------------------------------
+
+#[derive(serde::Deserialize)]
+struct AddChainResponse {
+    sct_version: u8,
+    id: String,
+    timestamp: u64,
+    #[serde(default)]
+    extensions: String,
+    signature: String,
+}
+
+fn submit_to_log(
+    agent: &ureq::Agent,
+    log: &CtLogConfig,
+    body: &serde_json::Value,
+    tbs: &[u8],
+    issuer_key_hash: &[u8],
+) -> Result<Sct, String> {
+    let url = format!("{}/ct/v1/add-pre-chain", log.url.trim_end_matches('/'));
+    let resp: AddChainResponse = agent
+        .post(&url)
+        .send_json(body.clone())
+        .map_err(|e| e.to_string())?
+        .into_json()
+        .map_err(|e| format!("invalid add-pre-chain response: {}", e))?;
+
+    if resp.sct_version != 0 {
+        return Err(format!("unsupported SCT version {}", resp.sct_version));
+    }
+    let log_key = B64.decode(log.public_key_b64.trim()).map_err(|e| format!("public_key_b64: {}", e))?;
+    let log_id = B64.decode(&resp.id).map_err(|e| format!("SCT id: {}", e))?;
+    if log_id != ring::digest::digest(&ring::digest::SHA256, &log_key).as_ref() {
+        return Err("SCT log id does not match the configured log key".to_string());
+    }
+    let extensions = B64.decode(&resp.extensions).map_err(|e| format!("SCT extensions: {}", e))?;
+    let signature = B64.decode(&resp.signature).map_err(|e| format!("SCT signature: {}", e))?;
+
+    let mut signed = vec![0u8, 0u8];
+    signed.extend_from_slice(&resp.timestamp.to_be_bytes());
+    signed.extend_from_slice(&[0x00, 0x01]); // precert_entry
+    signed.extend_from_slice(issuer_key_hash);
+    signed.extend_from_slice(&(tbs.len() as u32).to_be_bytes()[1..]);
+    signed.extend_from_slice(tbs);
+    signed.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+    signed.extend_from_slice(&extensions);
+    verify_digitally_signed(&log_key, &signed, &signature)?;
+
+    let timestamp = time::OffsetDateTime::from_unix_timestamp_nanos(resp.timestamp as i128 * 1_000_000)
+        .map_err(|e| format!("SCT timestamp: {}", e))?;
+    Ok(Sct {
+        log_id: resp.id,
+        log_name: log.name.clone(),
+        timestamp,
+        signature: resp.signature,
+        extensions: resp.extensions,
+    })
+}
+
+/// Check a TLS `DigitallySigned` struct (RFC 5246 §4.7) made with the log key, which is
+/// a DER SubjectPublicKeyInfo.
+fn verify_digitally_signed(log_spki: &[u8], message: &[u8], digitally_signed: &[u8]) -> Result<(), String> {
+    use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_ASN1, RSA_PKCS1_2048_8192_SHA256};
+    use x509_parser::prelude::FromDer;
+
+    let [hash, sig_alg, len_hi, len_lo, sig @ ..] = digitally_signed else {
+        return Err("truncated SCT signature".to_string());
+    };
+    if sig.len() != u16::from_be_bytes([*len_hi, *len_lo]) as usize {
+        return Err("SCT signature length mismatch".to_string());
+    }
+    if *hash != HASH_SHA256 {
+        return Err(format!("unsupported SCT hash algorithm {}", hash));
+    }
+    let (_, spki) = x509_parser::x509::SubjectPublicKeyInfo::from_der(log_spki)
+        .map_err(|e| format!("log public key: {}", e))?;
+    let key = spki.subject_public_key.data.as_ref();
+    let result = match *sig_alg {
+        SIG_ECDSA => UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, key).verify(message, sig),
+        SIG_RSA => UnparsedPublicKey::new(&RSA_PKCS1_2048_8192_SHA256, key).verify(message, sig),
+        other => return Err(format!("unsupported SCT signature algorithm {}", other)),
+    };
+    result.map_err(|_| "SCT signature does not verify".to_string())
+}
+
+fn serialize_sct(sct: &Sct) -> Result<Vec<u8>, CertError> {
+    let decode = |field: &str, value: &str| {
+        B64.decode(value)
+            .map_err(|e| CertError::Validation(format!("SCT {}: {}", field, e)))
+    };
+    let log_id = decode("log_id", &sct.log_id)?;
+    let extensions = decode("extensions", &sct.extensions)?;
+    let signature = decode("signature", &sct.signature)?;
+    let timestamp_ms = (sct.timestamp.unix_timestamp_nanos() / 1_000_000) as u64;
+
+    let mut out = vec![0u8]; // v1
+    out.extend_from_slice(&log_id);
+    out.extend_from_slice(&timestamp_ms.to_be_bytes());
+    out.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+    out.extend_from_slice(&extensions);
+    out.extend_from_slice(&signature);
+    Ok(out)
+}
+
+fn issuer_key_hash(issuer_der: &[u8]) -> Result<Vec<u8>, CertError> {
+    use x509_parser::prelude::FromDer;
+    let (_, issuer) = x509_parser::certificate::X509Certificate::from_der(issuer_der)
+        .map_err(|e| CertError::Crypto(format!("issuer certificate parse: {}", e)))?;
+    let spki = issuer.tbs_certificate.subject_pki.raw;
+    Ok(ring::digest::digest(&ring::digest::SHA256, spki).as_ref().to_vec())
+}
+
+// ---------------------------------------------------------------------------
+// DER surgery
+// ---------------------------------------------------------------------------
+
+/// Re-sign the certificate's TBS with `extension` (one or more encoded extensions)
+/// appended to its extension list, keeping the signature algorithm.
+pub(crate) fn resign(cert_der: &[u8], extension: &[u8], ca_key: &impl SigningKey) -> Result<Vec<u8>, CertError> {
+    let (tbs, sig_alg) = split_certificate(cert_der)?;
+    let tbs = append_extension(tbs, extension)?;
+    let signature = ca_key
+        .sign(&tbs)
+        .map_err(|e| CertError::Crypto(format!("certificate re-sign failed: {}", e)))?;
+    let mut bit_string = vec![0u8];
+    bit_string.extend_from_slice(&signature);
+    Ok(der_tlv(0x30, &[tbs, sig_alg.to_vec(), der_tlv(0x03, &bit_string)].concat()))
+}
+
+/// DER `Extension` for a dotted-decimal OID and an already-encoded value.
+pub(crate) fn encode_extension(oid: &str, critical: bool, value: &[u8]) -> Result<Vec<u8>, CertError> {
+    let invalid = || CertError::Validation(format!("invalid extension OID '{}'", oid));
+    let arcs = oid.split('.').map(|a| a.parse::<u64>()).collect::<Result<Vec<_>, _>>().map_err(|_| invalid())?;
+    if arcs.len() < 2 || arcs[0] > 2 {
+        return Err(invalid());
+    }
+    let mut oid_der = Vec::new();
+    for arc in std::iter::once(arcs[0] * 40 + arcs[1]).chain(arcs[2..].iter().copied()) {
+        let mut groups = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            groups.push((rest & 0x7f) as u8 | 0x80);
+            rest >>= 7;
+        }
+        oid_der.extend(groups.into_iter().rev());
+    }
+    let mut content = der_tlv(0x06, &oid_der);
+    if critical {
+        content.extend_from_slice(&[0x01, 0x01, 0xff]);
+    }
+    content.extend(der_tlv(0x04, value));
+    Ok(der_tlv(0x30, &content))
+}
+
+/// `(tbsCertificate, signatureAlgorithm)` as complete TLVs.
+fn split_certificate(cert_der: &[u8]) -> Result<(&[u8], &[u8]), CertError> {
+    let content = der_content(cert_der, 0x30)?;
+    let elements = der_elements(content)?;
+    match elements.as_slice() {
+        [tbs, sig_alg, _] => Ok((tbs, sig_alg)),
+        _ => Err(CertError::Crypto("malformed certificate".to_string())),
+    }
+}
+
+fn append_extension(tbs: &[u8], extension: &[u8]) -> Result<Vec<u8>, CertError> {
+    let content = der_content(tbs, 0x30)?;
+    let mut elements: Vec<Vec<u8>> = der_elements(content)?.into_iter().map(|e| e.to_vec()).collect();
+    match elements.iter_mut().find(|e| e.first() == Some(&0xa3)) {
+        Some(explicit) => {
+            let existing = der_content(der_content(explicit, 0xa3)?, 0x30)?;
+            let list = der_tlv(0x30, &[existing, extension].concat());
+            *explicit = der_tlv(0xa3, &list);
+        }
+        None => elements.push(der_tlv(0xa3, &der_tlv(0x30, extension))),
+    }
+    Ok(der_tlv(0x30, &elements.concat()))
+}
+
+/// Content of a single TLV with the expected tag, which must span all of `der`.
+fn der_content(der: &[u8], tag: u8) -> Result<&[u8], CertError> {
+    let (header, len) = der_header(der)?;
+    if der[0] != tag || header + len != der.len() {
+        return Err(CertError::Crypto(format!("malformed DER: expected tag 0x{:02x}", tag)));
+    }
+    Ok(&der[header..])
+}
+
+/// Split concatenated TLVs into complete elements.
+fn der_elements(mut der: &[u8]) -> Result<Vec<&[u8]>, CertError> {
+    let mut out = Vec::new();
+    while !der.is_empty() {
+        let (header, len) = der_header(der)?;
+        let (element, rest) = der.split_at(header + len);
+        out.push(element);
+        der = rest;
+    }
+    Ok(out)
+}
+
+/// `(header length, content length)` of the TLV at the start of `der`.
+fn der_header(der: &[u8]) -> Result<(usize, usize), CertError> {
+    let malformed = || CertError::Crypto("malformed DER length".to_string());
+    let first = *der.get(1).ok_or_else(malformed)?;
+    let (header, len) = if first < 0x80 {
+        (2, first as usize)
+    } else {
+        let n = (first & 0x7f) as usize;
+        if n == 0 || n > 4 || der.len() < 2 + n {
+            return Err(malformed());
+        }
+        (2 + n, der[2..2 + n].iter().fold(0usize, |acc, b| (acc << 8) | *b as usize))
+    };
+    if der.len() < header + len {
+        return Err(malformed());
+    }
+    Ok((header, len))
+}
+
+fn der_tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len.to_be_bytes().into_iter().skip_while(|b| *b == 0).collect();
+        out.push(0x80 | bytes.len() as u8);
+        out.extend_from_slice(&bytes);
+    }
+    out.extend_from_slice(content);
+    out
+}
@@ -4,15 +4,18 @@ use thiserror::Error;
 
 pub mod builder;
 pub mod crypto;
+pub mod ct;
 pub mod keystore;
 pub mod model;
+pub mod pkcs11;
 pub mod store;
 mod tests;
 
 pub use model::*;
-pub use keystore::{KeyStore, SoftwareKeyStore, open_keystore, encrypt_private_key, decrypt_private_key};
+pub use keystore::{KeyStore, KeyStoreSigner, SoftwareKeyStore, open_keystore, encrypt_private_key, decrypt_private_key};
+pub use pkcs11::Pkcs11KeyStore;
 pub use store::{CertStore, OxPersistenceCertStore};
-pub use builder::{CertBuilder, parse_csr, sign_csr, cross_sign_csr, cross_sign_csr_with_pem, issuer_params_from_cert_pem};
+pub use builder::{CertBuilder, IssuanceOptions, parse_csr, sign_csr, cross_sign_csr, cross_sign_csr_with_pem, issuer_params_from_cert_pem};
 
 #[derive(Error, Debug)]
 pub enum CertError {
@@ -26,6 +29,8 @@ pub enum CertError {
     NotFound(String),
     #[error("Internal error: {0}")]
     Internal(String),
+    #[error("CT failure: {0}")]
+    CtFailure(String),
 }
 
 /// Parse a plugin config JSON string from a raw C string pointer.
@@ -78,6 +83,7 @@ fn register_certificate_schema(dict: &mut DataDictionary) -> Result<(), CertErro
     schema.add_field(FieldDescriptor::new("scts", ValueType::Json));
     schema.add_field(FieldDescriptor::new("policy_oids", ValueType::Json));
     schema.add_field(FieldDescriptor::new("enrollment_protocol", ValueType::Text));
+    schema.add_field(FieldDescriptor::new("crl_shard", ValueType::Integer).indexed());
     schema.add_field(FieldDescriptor::new("created_at", ValueType::Timestamp));
     
     dict.register_schema(schema)
@@ -98,6 +104,8 @@ fn register_ssh_certificate_schema(dict: &mut DataDictionary) -> Result<(), Cert
     schema.add_field(FieldDescriptor::new("critical_options", ValueType::Json));
     schema.add_field(FieldDescriptor::new("extensions", ValueType::Json));
     schema.add_field(FieldDescriptor::new("certificate", ValueType::Text));
+    schema.add_field(FieldDescriptor::new("revoked_at", ValueType::Timestamp));
+    schema.add_field(FieldDescriptor::new("revocation_reason", ValueType::Text));
     schema.add_field(FieldDescriptor::new("created_at", ValueType::Timestamp));
     
     dict.register_schema(schema)
@@ -171,6 +179,20 @@ fn register_acme_schemas(dict: &mut DataDictionary) -> Result<(), CertError> {
     authz.add_field(FieldDescriptor::new("expires", ValueType::Timestamp));
     dict.register_schema(authz).map_err(|e| CertError::Internal(e.to_string()))?;
 
+    // Acme External Account Binding key
+    let mut eab = DataObjectSchema::new("acme_eab_key");
+    eab.add_field(FieldDescriptor::new("kid", ValueType::Text).primary_key());
+    eab.add_field(FieldDescriptor::new("tenant_id", ValueType::Text).indexed());
+    eab.add_field(FieldDescriptor::new("hmac_key", ValueType::Text));
+    eab.add_field(FieldDescriptor::new("profile", ValueType::Text));
+    eab.add_field(FieldDescriptor::new("validity_days", ValueType::Integer));
+    eab.add_field(FieldDescriptor::new("allowed_domains", ValueType::Json));
+    eab.add_field(FieldDescriptor::new("wildcard_allowed", ValueType::Boolean));
+    eab.add_field(FieldDescriptor::new("account_id", ValueType::Text).indexed());
+    eab.add_field(FieldDescriptor::new("revoked", ValueType::Boolean));
+    eab.add_field(FieldDescriptor::new("created_at", ValueType::Timestamp));
+    dict.register_schema(eab).map_err(|e| CertError::Internal(e.to_string()))?;
+
     Ok(())
 }
 
@@ -61,6 +61,9 @@ pub struct Sct {
     pub log_name: String,
     pub timestamp: time::OffsetDateTime,
     pub signature: String,
+    /// Base64 SCT extensions as returned by the log; usually empty.
+    #[serde(default)]
+    pub extensions: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -84,6 +87,8 @@ pub struct CertificateRecord {
     pub scts: Vec<Sct>,
     pub policy_oids: Vec<String>,
     pub enrollment_protocol: Option<EnrollmentProtocol>,
+    /// CRL partition the certificate was assigned at issuance, if partitioning was on.
+    pub crl_shard: Option<u32>,
     pub created_at: time::OffsetDateTime,
 }
 
@@ -162,6 +167,9 @@ pub struct SshCertRecord {
     pub critical_options: HashMap<String, String>,
     pub extensions: HashMap<String, String>,
     pub certificate: String,
+    /// Set by `mark_ssh_revoked`; revoked certificates are listed in the KRL.
+    pub revoked_at: Option<time::OffsetDateTime>,
+    pub revocation_reason: Option<RevocationReason>,
     pub created_at: time::OffsetDateTime,
 }
 
@@ -223,6 +231,45 @@ pub struct AcmeAccount {
     pub created_at: time::OffsetDateTime,
 }
 
+/// External Account Binding credential (RFC 8555 §7.3.4) provisioned through the
+/// admin API. The HMAC key is kept, not hashed, because every binding is verified
+/// against it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcmeEabKey {
+    pub kid: String,
+    pub tenant_id: String,
+    /// base64url-encoded HMAC key.
+    pub hmac_key: String,
+    /// Profile recorded on certificates issued to the bound account.
+    pub profile: Option<String>,
+    /// Validity of certificates issued to the bound account; `None` keeps the ACME default.
+    pub validity_days: Option<u32>,
+    /// DNS names the bound account may order. An entry matches itself and its
+    /// subdomains; an empty list allows any name.
+    pub allowed_domains: Vec<String>,
+    pub wildcard_allowed: bool,
+    /// ACME account this key has been bound to. A key binds exactly one account.
+    pub account_id: Option<String>,
+    pub revoked: bool,
+    pub created_at: time::OffsetDateTime,
+}
+
+impl AcmeEabKey {
+    /// Whether the EAB policy lets the bound account order `name`.
+    pub fn allows_identifier(&self, name: &str) -> bool {
+        let name = name.to_ascii_lowercase();
+        let base = match name.strip_prefix("*.") {
+            Some(rest) if self.wildcard_allowed => rest,
+            Some(_) => return false,
+            None => name.as_str(),
+        };
+        self.allowed_domains.is_empty() || self.allowed_domains.iter().any(|d| {
+            let d = d.trim_start_matches('.').to_ascii_lowercase();
+            base == d || base.ends_with(&format!(".{}", d))
+        })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum AcmeOrderStatus {
     Pending,
@@ -340,6 +387,39 @@ pub struct ScepChallenge {
     pub created_at: time::OffsetDateTime,
 }
 
+/// Certificate issued for a SCEP transaction, looked up again by CertPoll.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScepTransaction {
+    pub transaction_id: String,
+    pub tenant_id: String,
+    pub serial: String,
+    /// Hex SHA-256 of the SPKI that signed the request; polls must be signed by
+    /// the same key.
+    pub requester_key_sha256: String,
+    pub created_at: time::OffsetDateTime,
+}
+
+// ---------------------------------------------------------------------------
+// OCSP types
+// ---------------------------------------------------------------------------
+
+/// A signed OCSP response kept for reuse until `expires_at`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OcspCachedResponse {
+    pub serial: String,
+    /// DER `CertID` the response answers. Clients match it against their request,
+    /// so the cached response only serves requests with the same `CertID`.
+    pub cert_id: Vec<u8>,
+    /// DER `OCSPResponse`.
+    pub der: Vec<u8>,
+    /// Certificate status and revocation time the response was built from. The
+    /// store only caches the response while the certificate still matches them.
+    pub cert_status: CertStatus,
+    pub revoked_at: Option<time::OffsetDateTime>,
+    pub produced_at: time::OffsetDateTime,
+    pub expires_at: time::OffsetDateTime,
+}
+
 // ---------------------------------------------------------------------------
 // Notification types
 // ---------------------------------------------------------------------------
@@ -620,19 +700,56 @@ impl CertStoreConfig {
     }
 }
 
+// ---------------------------------------------------------------------------
+// CRL partitioning
+// ---------------------------------------------------------------------------
+
+/// Splits a tenant's CRL into shards. Issuers assign each certificate a shard and
+/// write that shard's URL as its CRL distribution point; `ox_cert_crl` publishes
+/// each shard with a matching issuing distribution point. Both must use the same
+/// settings.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CrlPartitionConfig {
+    /// Number of shards new certificates are spread over. Certificates keep the
+    /// shard they were issued with, so this may grow but must not shrink.
+    pub shards: u32,
+    /// Shard CRL URL; `{shard}` is replaced by the shard number.
+    pub url_template: String,
+}
+
+impl CrlPartitionConfig {
+    /// Shard for a certificate serial, taken from its low 32 bits.
+    pub fn shard_for(&self, serial: &uuid::Uuid) -> u32 {
+        let b = serial.as_bytes();
+        u32::from_be_bytes([b[12], b[13], b[14], b[15]]) % self.shards.max(1)
+    }
+
+    pub fn url(&self, shard: u32) -> String {
+        self.url_template.replace("{shard}", &shard.to_string())
+    }
+}
+
 // ---------------------------------------------------------------------------
 // CT types
 // ---------------------------------------------------------------------------
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct CtConfig {
+    #[serde(default)]
     pub enabled: bool,
     pub logs: Vec<CtLogConfig>,
+    #[serde(default = "default_min_scts")]
     pub min_scts: usize,
+    #[serde(default = "default_ct_timeout_secs")]
     pub timeout_secs: u64,
+    #[serde(default = "default_ct_on_failure")]
     pub on_failure: CtFailureMode,
 }
 
+fn default_min_scts() -> usize { 2 }
+fn default_ct_timeout_secs() -> u64 { 10 }
+fn default_ct_on_failure() -> CtFailureMode { CtFailureMode::Warn }
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct CtLogConfig {
     pub name: String,
@@ -0,0 +1,381 @@
+//! PKCS#11 `KeyStore` backed by an HSM module loaded at runtime through `cryptoki`.
+//!
+//! Each key is a private/public object pair whose `CKA_LABEL` and `CKA_ID` are
+//! `{tenant_id}:{key_id}`. Private keys are created sensitive and non-extractable, so
+//! `load_key_pem` always fails for this keystore; callers sign through
+//! `KeyStoreSigner`, which goes through `sign`.
+
+use crate::keystore::KeyStore;
+use crate::model::{KeyInfo, KeyStoreConfig, KeyType, SigningAlgorithm};
+use crate::CertError;
+use cryptoki::context::{CInitializeArgs, Pkcs11};
+use cryptoki::error::{Error as CkError, RvError};
+use cryptoki::mechanism::Mechanism;
+use cryptoki::object::{Attribute, AttributeType, KeyType as CkKeyType, ObjectClass, ObjectHandle};
+use cryptoki::session::{Session, UserType};
+use cryptoki::types::{AuthPin, Date};
+use std::collections::HashMap;
+use std::os::raw::c_ulong;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+// DER-encoded named curve OIDs for CKA_EC_PARAMS.
+const P256_PARAMS: &[u8] = &[0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const P384_PARAMS: &[u8] = &[0x06, 0x05, 0x2b, 0x81, 0x04, 0x00, 0x22];
+const P521_PARAMS: &[u8] = &[0x06, 0x05, 0x2b, 0x81, 0x04, 0x00, 0x23];
+const ED25519_PARAMS: &[u8] = &[0x06, 0x03, 0x2b, 0x65, 0x70];
+
+fn ck_err(call: &str) -> impl Fn(CkError) -> CertError + '_ {
+    move |e| CertError::Crypto(format!("{} failed: {}", call, e))
+}
+
+/// One initialized context per module, kept for the life of the process. Other
+/// plugins in the process may share the module, and `cryptoki` finalizes the
+/// library when a context is dropped, so contexts are never dropped.
+fn context(module_path: &Path) -> Result<Pkcs11, CertError> {
+    static CONTEXTS: OnceLock<Mutex<HashMap<PathBuf, Pkcs11>>> = OnceLock::new();
+    let mut contexts = CONTEXTS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .map_err(|_| CertError::Internal("PKCS#11 context lock poisoned".to_string()))?;
+    if let Some(ctx) = contexts.get(module_path) {
+        return Ok(ctx.clone());
+    }
+
+    let ctx = Pkcs11::new(module_path).map_err(|e| {
+        CertError::Crypto(format!("failed to load PKCS#11 module {}: {}", module_path.display(), e))
+    })?;
+    match ctx.initialize(CInitializeArgs::OsThreads) {
+        Ok(()) | Err(CkError::Pkcs11(RvError::CryptokiAlreadyInitialized, ..)) => {}
+        Err(e) => return Err(ck_err("C_Initialize")(e)),
+    }
+    contexts.insert(module_path.to_path_buf(), ctx.clone());
+    Ok(ctx)
+}
+
+// ---------------------------------------------------------------------------
+// Pkcs11KeyStore
+// ---------------------------------------------------------------------------
+
+pub struct Pkcs11KeyStore {
+    // A session is not Sync; every call goes through the mutex.
+    session: Mutex<Session>,
+}
+
+impl Pkcs11KeyStore {
+    /// Load `pkcs11_module`, open a session on `pkcs11_slot` and log in with the PIN
+    /// from `pkcs11_pin_env`. Without a slot, the first slot holding an initialized
+    /// token is used.
+    pub fn open(config: &KeyStoreConfig) -> Result<Self, CertError> {
+        let module_path = config.pkcs11_module.as_ref().ok_or_else(|| {
+            CertError::Crypto("pkcs11_module not configured for PKCS#11 keystore".to_string())
+        })?;
+        let pin_env = config.pkcs11_pin_env.as_ref().ok_or_else(|| {
+            CertError::Crypto("pkcs11_pin_env not configured for PKCS#11 keystore".to_string())
+        })?;
+        let pin = std::env::var(pin_env)
+            .map_err(|_| CertError::Crypto(format!("PKCS#11 PIN variable {} is not set", pin_env)))?;
+
+        let ctx = context(module_path)?;
+        let slots = ctx.get_slots_with_initialized_token().map_err(ck_err("C_GetSlotList"))?;
+        let slot = match config.pkcs11_slot {
+            Some(id) => slots.into_iter().find(|s| s.id() == id).ok_or_else(|| {
+                CertError::Crypto(format!("PKCS#11 slot {} holds no initialized token", id))
+            })?,
+            None => slots.into_iter().next().ok_or_else(|| {
+                CertError::Crypto("no PKCS#11 slot holds an initialized token".to_string())
+            })?,
+        };
+
+        let session = ctx.open_rw_session(slot).map_err(ck_err("C_OpenSession"))?;
+        match session.login(UserType::User, Some(&AuthPin::new(pin))) {
+            Ok(()) | Err(CkError::Pkcs11(RvError::UserAlreadyLoggedIn, ..)) => {}
+            Err(e) => return Err(ck_err("C_Login")(e)),
+        }
+
+        Ok(Self { session: Mutex::new(session) })
+    }
+
+    fn session(&self) -> Result<std::sync::MutexGuard<'_, Session>, CertError> {
+        self.session
+            .lock()
+            .map_err(|_| CertError::Internal("PKCS#11 session lock poisoned".to_string()))
+    }
+
+    fn label(tenant_id: &str, key_id: &str) -> String {
+        format!("{}:{}", tenant_id, key_id)
+    }
+
+    /// All objects of `class` carrying the key's label.
+    fn find(session: &Session, class: ObjectClass, label: &str) -> Result<Vec<ObjectHandle>, CertError> {
+        session
+            .find_objects(&[Attribute::Class(class), Attribute::Label(label.as_bytes().to_vec())])
+            .map_err(ck_err("C_FindObjects"))
+    }
+
+    fn find_one(session: &Session, class: ObjectClass, tenant_id: &str, key_id: &str) -> Result<ObjectHandle, CertError> {
+        Self::find(session, class, &Self::label(tenant_id, key_id))?
+            .into_iter()
+            .next()
+            .ok_or_else(|| CertError::NotFound(format!("key {}/{}", tenant_id, key_id)))
+    }
+
+    fn attributes(session: &Session, object: ObjectHandle, types: &[AttributeType]) -> Result<Vec<Attribute>, CertError> {
+        session.get_attributes(object, types).map_err(ck_err("C_GetAttributeValue"))
+    }
+
+    fn key_type_of(session: &Session, public: ObjectHandle) -> Result<KeyType, CertError> {
+        let attrs = Self::attributes(session, public, &[AttributeType::KeyType])?;
+        let ck_type = attrs.iter().find_map(|a| match a {
+            Attribute::KeyType(t) => Some(*t),
+            _ => None,
+        });
+        match ck_type {
+            Some(CkKeyType::RSA) => {
+                let attrs = Self::attributes(session, public, &[AttributeType::ModulusBits])?;
+                let bits = attrs.iter().find_map(|a| match a {
+                    Attribute::ModulusBits(bits) => Some(c_ulong::from(*bits)),
+                    _ => None,
+                });
+                Ok(match bits {
+                    Some(2048) => KeyType::Rsa2048,
+                    Some(3072) => KeyType::Rsa3072,
+                    _ => KeyType::Rsa4096,
+                })
+            }
+            Some(CkKeyType::EC) => {
+                let attrs = Self::attributes(session, public, &[AttributeType::EcParams])?;
+                let params = attrs.iter().find_map(|a| match a {
+                    Attribute::EcParams(p) => Some(p.as_slice()),
+                    _ => None,
+                });
+                match params {
+                    Some(P256_PARAMS) => Ok(KeyType::EcP256),
+                    Some(P384_PARAMS) => Ok(KeyType::EcP384),
+                    Some(P521_PARAMS) => Ok(KeyType::EcP521),
+                    _ => Err(CertError::Crypto("unsupported EC curve on PKCS#11 key".to_string())),
+                }
+            }
+            Some(CkKeyType::EC_EDWARDS) => Ok(KeyType::Ed25519),
+            Some(other) => Err(CertError::Crypto(format!("unsupported PKCS#11 key type {}", other))),
+            None => Err(CertError::Crypto("PKCS#11 key has no CKA_KEY_TYPE".to_string())),
+        }
+    }
+
+    fn destroy_all(session: &Session, label: &str) -> Result<(), CertError> {
+        for class in [ObjectClass::PRIVATE_KEY, ObjectClass::PUBLIC_KEY] {
+            for object in Self::find(session, class, label)? {
+                session.destroy_object(object).map_err(ck_err("C_DestroyObject"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl KeyStore for Pkcs11KeyStore {
+    fn generate_key(
+        &self,
+        tenant_id: &str,
+        key_id: &str,
+        key_type: KeyType,
+        overwrite: bool,
+    ) -> Result<(), CertError> {
+        let session = self.session()?;
+        let label = Self::label(tenant_id, key_id);
+        if !Self::find(&session, ObjectClass::PRIVATE_KEY, &label)?.is_empty() {
+            if !overwrite {
+                return Ok(());
+            }
+            Self::destroy_all(&session, &label)?;
+        }
+
+        let (mechanism, modulus_bits, ec_params): (Mechanism, c_ulong, Option<&[u8]>) = match key_type {
+            KeyType::Rsa2048 => (Mechanism::RsaPkcsKeyPairGen, 2048, None),
+            KeyType::Rsa3072 => (Mechanism::RsaPkcsKeyPairGen, 3072, None),
+            KeyType::Rsa4096 => (Mechanism::RsaPkcsKeyPairGen, 4096, None),
+            KeyType::EcP256 => (Mechanism::EccKeyPairGen, 0, Some(P256_PARAMS)),
+            KeyType::EcP384 => (Mechanism::EccKeyPairGen, 0, Some(P384_PARAMS)),
+            KeyType::EcP521 => (Mechanism::EccKeyPairGen, 0, Some(P521_PARAMS)),
+            KeyType::Ed25519 => (Mechanism::EccEdwardsKeyPairGen, 0, Some(ED25519_PARAMS)),
+        };
+
+        let today = time::OffsetDateTime::now_utc().date();
+        let start_date = Date::new_from_str_slice(
+            &format!("{:04}", today.year()),
+            &format!("{:02}", u8::from(today.month())),
+            &format!("{:02}", today.day()),
+        )
+        .map_err(ck_err("CK_DATE"))?;
+
+        let mut public_template = vec![
+            Attribute::Token(true),
+            Attribute::Verify(true),
+            Attribute::Label(label.as_bytes().to_vec()),
+            Attribute::Id(label.as_bytes().to_vec()),
+            Attribute::StartDate(start_date),
+        ];
+        match ec_params {
+            Some(params) => public_template.push(Attribute::EcParams(params.to_vec())),
+            None => {
+                public_template.push(Attribute::ModulusBits(modulus_bits.into()));
+                public_template.push(Attribute::PublicExponent(vec![0x01, 0x00, 0x01]));
+            }
+        }
+        let mut private_template = vec![
+            Attribute::Token(true),
+            Attribute::Private(true),
+            Attribute::Sensitive(true),
+            Attribute::Extractable(false),
+            Attribute::Sign(true),
+            Attribute::Label(label.as_bytes().to_vec()),
+            Attribute::Id(label.as_bytes().to_vec()),
+            Attribute::StartDate(start_date),
+        ];
+        // RSA keys also unwrap SCEP key transports
+        if ec_params.is_none() {
+            private_template.push(Attribute::Decrypt(true));
+        }
+
+        session
+            .generate_key_pair(&mechanism, &public_template, &private_template)
+            .map(|_| ())
+            .map_err(ck_err("C_GenerateKeyPair"))
+    }
+
+    fn sign(
+        &self,
+        tenant_id: &str,
+        key_id: &str,
+        algorithm: SigningAlgorithm,
+        data: &[u8],
+    ) -> Result<Vec<u8>, CertError> {
+        use ring::digest;
+
+        // ECDSA hashes locally and signs the digest with CKM_ECDSA, which every HSM
+        // supports; the result is the fixed-width r||s the software keystore returns.
+        let (mechanism, input) = match algorithm {
+            SigningAlgorithm::Sha256WithRsa => (Mechanism::Sha256RsaPkcs, data.to_vec()),
+            SigningAlgorithm::Sha384WithRsa => (Mechanism::Sha384RsaPkcs, data.to_vec()),
+            SigningAlgorithm::Sha512WithRsa => (Mechanism::Sha512RsaPkcs, data.to_vec()),
+            SigningAlgorithm::EcdsaWithSha256 => (Mechanism::Ecdsa, digest::digest(&digest::SHA256, data).as_ref().to_vec()),
+            SigningAlgorithm::EcdsaWithSha384 => (Mechanism::Ecdsa, digest::digest(&digest::SHA384, data).as_ref().to_vec()),
+            SigningAlgorithm::EcdsaWithSha512 => (Mechanism::Ecdsa, digest::digest(&digest::SHA512, data).as_ref().to_vec()),
+            SigningAlgorithm::Ed25519 => (Mechanism::Eddsa, data.to_vec()),
+        };
+
+        let session = self.session()?;
+        let key = Self::find_one(&session, ObjectClass::PRIVATE_KEY, tenant_id, key_id)?;
+        session.sign(&mechanism, key, &input).map_err(ck_err("C_Sign"))
+    }
+
+    fn decrypt(&self, tenant_id: &str, key_id: &str, ciphertext: &[u8]) -> Result<Vec<u8>, CertError> {
+        let session = self.session()?;
+        let key = Self::find_one(&session, ObjectClass::PRIVATE_KEY, tenant_id, key_id)?;
+        session.decrypt(&Mechanism::RsaPkcs, key, ciphertext).map_err(ck_err("C_Decrypt"))
+    }
+
+    /// Same encoding as `SoftwareKeyStore`: PKCS#1 `RSAPublicKey` DER for RSA, the
+    /// uncompressed point for EC and the raw 32 bytes for Ed25519.
+    fn public_key(&self, tenant_id: &str, key_id: &str) -> Result<Vec<u8>, CertError> {
+        let session = self.session()?;
+        let public = Self::find_one(&session, ObjectClass::PUBLIC_KEY, tenant_id, key_id)?;
+        let key_type = Self::key_type_of(&session, public)?;
+
+        let point_len = match key_type {
+            KeyType::Rsa2048 | KeyType::Rsa3072 | KeyType::Rsa4096 => {
+                use rsa::pkcs1::EncodeRsaPublicKey;
+                let attrs = Self::attributes(&session, public, &[AttributeType::Modulus, AttributeType::PublicExponent])?;
+                let (mut n, mut e) = (None, None);
+                for attr in attrs {
+                    match attr {
+                        Attribute::Modulus(v) => n = Some(v),
+                        Attribute::PublicExponent(v) => e = Some(v),
+                        _ => {}
+                    }
+                }
+                let (Some(n), Some(e)) = (n, e) else {
+                    return Err(CertError::Crypto("PKCS#11 RSA key lacks modulus or exponent".to_string()));
+                };
+                let key = rsa::RsaPublicKey::new(
+                    rsa::BigUint::from_bytes_be(&n),
+                    rsa::BigUint::from_bytes_be(&e),
+                )
+                .map_err(|e| CertError::Crypto(format!("invalid RSA public key: {}", e)))?;
+                let der = key
+                    .to_pkcs1_der()
+                    .map_err(|e| CertError::Crypto(format!("RSA public key encode failed: {}", e)))?;
+                return Ok(der.as_bytes().to_vec());
+            }
+            KeyType::EcP256 => 65,
+            KeyType::EcP384 => 97,
+            KeyType::EcP521 => 133,
+            KeyType::Ed25519 => 32,
+        };
+
+        // CKA_EC_POINT is a DER OCTET STRING, though some modules return the bare point.
+        let attrs = Self::attributes(&session, public, &[AttributeType::EcPoint])?;
+        let point = attrs
+            .into_iter()
+            .find_map(|a| match a {
+                Attribute::EcPoint(p) => Some(p),
+                _ => None,
+            })
+            .ok_or_else(|| CertError::Crypto("PKCS#11 EC key lacks CKA_EC_POINT".to_string()))?;
+        if point.len() == point_len {
+            return Ok(point);
+        }
+        match point.as_slice() {
+            [0x04, len, rest @ ..] if *len as usize == point_len && rest.len() == point_len => Ok(rest.to_vec()),
+            [0x04, 0x81, len, rest @ ..] if *len as usize == point_len && rest.len() == point_len => Ok(rest.to_vec()),
+            _ => Err(CertError::Crypto("unexpected CKA_EC_POINT encoding".to_string())),
+        }
+    }
+
+    fn key_exists(&self, tenant_id: &str, key_id: &str) -> Result<bool, CertError> {
+        let session = self.session()?;
+        Ok(!Self::find(&session, ObjectClass::PRIVATE_KEY, &Self::label(tenant_id, key_id))?.is_empty())
+    }
+
+    fn key_info(&self, tenant_id: &str, key_id: &str) -> Result<KeyInfo, CertError> {
+        let session = self.session()?;
+        let public = Self::find_one(&session, ObjectClass::PUBLIC_KEY, tenant_id, key_id)?;
+        let key_type = Self::key_type_of(&session, public)?;
+        let created_at = Self::attributes(&session, public, &[AttributeType::StartDate])
+            .ok()
+            .and_then(|attrs| {
+                attrs.into_iter().find_map(|a| match a {
+                    Attribute::StartDate(date) if !date.is_empty() => parse_ck_date(&date),
+                    _ => None,
+                })
+            })
+            .unwrap_or(time::OffsetDateTime::UNIX_EPOCH);
+        Ok(KeyInfo {
+            key_id: key_id.to_string(),
+            tenant_id: tenant_id.to_string(),
+            key_type,
+            created_at,
+        })
+    }
+
+    fn delete_key(&self, tenant_id: &str, key_id: &str) -> Result<(), CertError> {
+        let session = self.session()?;
+        Self::destroy_all(&session, &Self::label(tenant_id, key_id))
+    }
+
+    fn load_key_pem(&self, tenant_id: &str, key_id: &str) -> Result<String, CertError> {
+        Err(CertError::Crypto(format!(
+            "key {}/{} is held in a PKCS#11 token and cannot be exported",
+            tenant_id, key_id
+        )))
+    }
+}
+
+/// `CK_DATE` holds "YYYY", "MM" and "DD" as ASCII digits.
+fn parse_ck_date(date: &Date) -> Option<time::OffsetDateTime> {
+    let digits = |raw: &[u8]| std::str::from_utf8(raw).ok()?.parse::<u16>().ok();
+    let year = digits(&date.year)? as i32;
+    let month = time::Month::try_from(digits(&date.month)? as u8).ok()?;
+    let day = digits(&date.day)? as u8;
+    let date = time::Date::from_calendar_date(year, month, day).ok()?;
+    Some(date.midnight().assume_utc())
+}
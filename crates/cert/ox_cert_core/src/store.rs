@@ -30,6 +30,14 @@ pub trait CertStore: Send + Sync {
     fn list_revoked(&self, tenant_id: &str) -> Result<Vec<CertificateRecord>, CertError>;
     fn list_revoked_since(&self, tenant_id: &str, since: time::OffsetDateTime)
         -> Result<Vec<CertificateRecord>, CertError>;
+    /// Revoked certificates in CRL shard `shard`, limited to those revoked at or
+    /// after `since` if given.
+    fn list_revoked_in_shard(
+        &self,
+        tenant_id: &str,
+        shard: u32,
+        since: Option<time::OffsetDateTime>,
+    ) -> Result<Vec<CertificateRecord>, CertError>;
     fn list_expiring(&self, tenant_id: &str, within_days: u32)
         -> Result<Vec<CertificateRecord>, CertError>;
     fn update_status_expired(&self, tenant_id: &str) -> Result<u64, CertError>;
@@ -41,6 +49,14 @@ pub trait CertStore: Send + Sync {
     fn list_ssh_certs(&self, tenant_id: &str, filter: &SshCertFilter, page: &Pagination)
         -> Result<PagedResult<SshCertRecord>, CertError>;
     fn get_next_ssh_serial(&self, tenant_id: &str) -> Result<u64, CertError>;
+    fn mark_ssh_revoked(
+        &self,
+        tenant_id: &str,
+        serial: u64,
+        reason: RevocationReason,
+        timestamp: time::OffsetDateTime,
+    ) -> Result<(), CertError>;
+    fn list_revoked_ssh_certs(&self, tenant_id: &str) -> Result<Vec<SshCertRecord>, CertError>;
 
     // --- CA Keys ---
     fn store_ca_key(&self, tenant_id: &str, key: &CaKeyRecord) -> Result<(), CertError>;
@@ -54,8 +70,14 @@ pub trait CertStore: Send + Sync {
     fn store_acme_account(&self, tenant_id: &str, account: &AcmeAccount) -> Result<(), CertError>;
     fn get_acme_account(&self, tenant_id: &str, id: &str)
         -> Result<Option<AcmeAccount>, CertError>;
+    /// Look up the account registered with exactly this JWK string.
+    fn find_acme_account_by_jwk(&self, tenant_id: &str, jwk: &str)
+        -> Result<Option<AcmeAccount>, CertError>;
     fn store_acme_order(&self, tenant_id: &str, order: &AcmeOrder) -> Result<(), CertError>;
     fn get_acme_order(&self, tenant_id: &str, id: &str) -> Result<Option<AcmeOrder>, CertError>;
+    /// The order the certificate with this serial was issued for.
+    fn find_acme_order_by_cert_serial(&self, tenant_id: &str, serial: &str)
+        -> Result<Option<AcmeOrder>, CertError>;
     fn update_acme_order_status(&self, tenant_id: &str, id: &str, status: AcmeOrderStatus)
         -> Result<(), CertError>;
     fn store_acme_authorization(&self, tenant_id: &str, authz: &AcmeAuthorization)
@@ -64,6 +86,17 @@ pub trait CertStore: Send + Sync {
         -> Result<Option<AcmeAuthorization>, CertError>;
     fn update_acme_authorization(&self, tenant_id: &str, authz: &AcmeAuthorization)
         -> Result<(), CertError>;
+    /// Authorizations of every order placed by the account.
+    fn list_acme_authorizations_for_account(&self, tenant_id: &str, account_id: &str)
+        -> Result<Vec<AcmeAuthorization>, CertError>;
+    fn store_acme_eab_key(&self, tenant_id: &str, key: &AcmeEabKey) -> Result<(), CertError>;
+    fn get_acme_eab_key(&self, tenant_id: &str, kid: &str)
+        -> Result<Option<AcmeEabKey>, CertError>;
+    fn list_acme_eab_keys(&self, tenant_id: &str) -> Result<Vec<AcmeEabKey>, CertError>;
+    /// Store a new account and bind the EAB key `kid` to it in one transaction.
+    /// Stores nothing and returns false if the key is unknown, revoked or already bound.
+    fn bind_acme_account(&self, tenant_id: &str, kid: &str, account: &AcmeAccount)
+        -> Result<bool, CertError>;
 
     // --- RA ---
     fn store_ra_request(&self, tenant_id: &str, request: &ApprovalRequest)
@@ -86,6 +119,25 @@ pub trait CertStore: Send + Sync {
         -> Result<(), CertError>;
     fn consume_scep_challenge(&self, tenant_id: &str, password_hash: &str)
         -> Result<bool, CertError>;
+    fn store_scep_transaction(&self, tenant_id: &str, transaction: &ScepTransaction)
+        -> Result<(), CertError>;
+    fn get_scep_transaction(&self, tenant_id: &str, transaction_id: &str)
+        -> Result<Option<ScepTransaction>, CertError>;
+
+    // --- OCSP response cache ---
+    /// Cache `response` if the certificate still has the `cert_status` and
+    /// `revoked_at` it was built from, checked atomically with the insert.
+    /// Returns `false` when the certificate changed since it was read, so a
+    /// response signed before a concurrent revocation is never cached.
+    fn store_ocsp_response(&self, tenant_id: &str, response: &OcspCachedResponse)
+        -> Result<bool, CertError>;
+    /// The cached response for `serial`, if one exists and has not expired.
+    fn get_ocsp_response(&self, tenant_id: &str, serial: &str)
+        -> Result<Option<OcspCachedResponse>, CertError>;
+    /// Drop the cached response for `serial`. `mark_revoked` calls this, so a
+    /// revocation is never masked by a response signed while the cert was good.
+    fn invalidate_ocsp_response(&self, tenant_id: &str, serial: &str)
+        -> Result<(), CertError>;
 
     // --- Notifications ---
     fn store_notification(&self, tenant_id: &str, notification: &NotificationRecord)
@@ -205,6 +257,8 @@ impl CertStore for OxPersistenceCertStore {
             );
             CREATE INDEX IF NOT EXISTS idx_cert_ts ON certificate(tenant_id, status);
             CREATE INDEX IF NOT EXISTS idx_cert_na ON certificate(not_after);
+            CREATE INDEX IF NOT EXISTS idx_cert_shard
+                ON certificate(tenant_id, json_extract(data, '$.crl_shard'));
 
             CREATE TABLE IF NOT EXISTS ssh_certificate (
                 serial    INTEGER PRIMARY KEY,
@@ -248,6 +302,12 @@ impl CertStore for OxPersistenceCertStore {
             );
             CREATE INDEX IF NOT EXISTS idx_aa_ord ON acme_authorization(order_id);
 
+            CREATE TABLE IF NOT EXISTS acme_eab_key (
+                kid       TEXT PRIMARY KEY,
+                tenant_id TEXT NOT NULL,
+                data      TEXT NOT NULL
+            );
+
             CREATE TABLE IF NOT EXISTS ra_request (
                 id        TEXT PRIMARY KEY,
                 tenant_id TEXT NOT NULL,
@@ -266,6 +326,21 @@ impl CertStore for OxPersistenceCertStore {
             );
             CREATE INDEX IF NOT EXISTS idx_sc_hash ON scep_challenge(password_hash);
 
+            CREATE TABLE IF NOT EXISTS scep_transaction (
+                transaction_id TEXT NOT NULL,
+                tenant_id      TEXT NOT NULL,
+                data           TEXT NOT NULL,
+                PRIMARY KEY (tenant_id, transaction_id)
+            );
+
+            CREATE TABLE IF NOT EXISTS ocsp_response (
+                serial     TEXT    NOT NULL,
+                tenant_id  TEXT    NOT NULL,
+                expires_at INTEGER NOT NULL,
+                data       TEXT    NOT NULL,
+                PRIMARY KEY (tenant_id, serial)
+            );
+
             CREATE TABLE IF NOT EXISTS notification (
                 id             INTEGER PRIMARY KEY AUTOINCREMENT,
                 tenant_id      TEXT    NOT NULL,
@@ -415,7 +490,8 @@ impl CertStore for OxPersistenceCertStore {
         record.status = CertStatus::Revoked;
         record.revoked_at = Some(timestamp);
         record.revocation_reason = Some(reason);
-        self.store_cert(tenant_id, &record)
+        self.store_cert(tenant_id, &record)?;
+        self.invalidate_ocsp_response(tenant_id, serial)
     }
 
     fn list_revoked(&self, tenant_id: &str) -> Result<Vec<CertificateRecord>, CertError> {
@@ -437,6 +513,18 @@ impl CertStore for OxPersistenceCertStore {
         collect_records(&mut stmt, params![tenant_id, to_ts(since)])
     }
 
+    fn list_revoked_in_shard(
+        &self, tenant_id: &str, shard: u32, since: Option<time::OffsetDateTime>,
+    ) -> Result<Vec<CertificateRecord>, CertError> {
+        let conn = db!(self);
+        let mut stmt = conn.prepare(
+            "SELECT data FROM certificate \
+             WHERE tenant_id = ?1 AND json_extract(data, '$.crl_shard') = ?2 \
+             AND status = 'Revoked' AND revoked_at >= ?3"
+        ).map_err(|e| CertError::Storage(e.to_string()))?;
+        collect_records(&mut stmt, params![tenant_id, shard, since.map(to_ts).unwrap_or(i64::MIN)])
+    }
+
     fn list_expiring(&self, tenant_id: &str, within_days: u32)
         -> Result<Vec<CertificateRecord>, CertError>
     {
@@ -559,6 +647,33 @@ impl CertStore for OxPersistenceCertStore {
         Ok((max + 1) as u64)
     }
 
+    fn mark_ssh_revoked(
+        &self, tenant_id: &str, serial: u64,
+        reason: RevocationReason, timestamp: time::OffsetDateTime,
+    ) -> Result<(), CertError> {
+        let mut record = self.get_ssh_cert_by_serial(tenant_id, serial)?
+            .ok_or_else(|| CertError::NotFound(serial.to_string()))?;
+        record.revoked_at = Some(timestamp);
+        record.revocation_reason = Some(reason);
+        self.store_ssh_cert(tenant_id, &record)
+    }
+
+    fn list_revoked_ssh_certs(&self, tenant_id: &str) -> Result<Vec<SshCertRecord>, CertError> {
+        let conn = db!(self);
+        let mut stmt = conn.prepare(
+            "SELECT data FROM ssh_certificate \
+             WHERE tenant_id = ?1 AND json_extract(data, '$.revoked_at') IS NOT NULL \
+             ORDER BY serial"
+        ).map_err(|e| CertError::Storage(e.to_string()))?;
+        let items = stmt
+            .query_map(params![tenant_id], |r| r.get::<_, String>(0))
+            .map_err(|e| CertError::Storage(e.to_string()))?
+            .filter_map(|r| r.ok())
+            .filter_map(|data| de::<SshCertRecord>(&data).ok())
+            .collect();
+        Ok(items)
+    }
+
     // ─── CA Keys ─────────────────────────────────────────────────────────────
 
     fn store_ca_key(&self, tenant_id: &str, key: &CaKeyRecord) -> Result<(), CertError> {
@@ -642,6 +757,18 @@ impl CertStore for OxPersistenceCertStore {
         row.as_deref().map(de).transpose()
     }
 
+    fn find_acme_account_by_jwk(&self, tenant_id: &str, jwk: &str)
+        -> Result<Option<AcmeAccount>, CertError>
+    {
+        let conn = db!(self);
+        let row: Option<String> = conn.query_row(
+            "SELECT data FROM acme_account WHERE tenant_id = ?1 AND json_extract(data, '$.jwk') = ?2",
+            params![tenant_id, jwk],
+            |r| r.get(0),
+        ).optional().map_err(|e| CertError::Storage(e.to_string()))?;
+        row.as_deref().map(de).transpose()
+    }
+
     fn store_acme_order(&self, tenant_id: &str, order: &AcmeOrder) -> Result<(), CertError> {
         let conn = db!(self);
         conn.execute(
@@ -662,6 +789,19 @@ impl CertStore for OxPersistenceCertStore {
         row.as_deref().map(de).transpose()
     }
 
+    fn find_acme_order_by_cert_serial(&self, tenant_id: &str, serial: &str)
+        -> Result<Option<AcmeOrder>, CertError>
+    {
+        let conn = db!(self);
+        let row: Option<String> = conn.query_row(
+            "SELECT data FROM acme_order \
+             WHERE tenant_id = ?1 AND json_extract(data, '$.certificate_serial') = ?2",
+            params![tenant_id, serial],
+            |r| r.get(0),
+        ).optional().map_err(|e| CertError::Storage(e.to_string()))?;
+        row.as_deref().map(de).transpose()
+    }
+
     fn update_acme_order_status(&self, tenant_id: &str, id: &str, status: AcmeOrderStatus)
         -> Result<(), CertError>
     {
@@ -701,6 +841,85 @@ impl CertStore for OxPersistenceCertStore {
         self.store_acme_authorization(tenant_id, authz)
     }
 
+    fn list_acme_authorizations_for_account(&self, tenant_id: &str, account_id: &str)
+        -> Result<Vec<AcmeAuthorization>, CertError>
+    {
+        let conn = db!(self);
+        let mut stmt = conn.prepare(
+            "SELECT z.data FROM acme_authorization z \
+             JOIN acme_order o ON o.id = z.order_id AND o.tenant_id = z.tenant_id \
+             WHERE z.tenant_id = ?1 AND o.account_id = ?2",
+        ).map_err(|e| CertError::Storage(e.to_string()))?;
+        let rows = stmt.query_map(params![tenant_id, account_id], |r| r.get::<_, String>(0))
+            .map_err(|e| CertError::Storage(e.to_string()))?;
+        let mut out = Vec::new();
+        for row in rows {
+            let data = row.map_err(|e| CertError::Storage(e.to_string()))?;
+            out.push(de::<AcmeAuthorization>(&data)?);
+        }
+        Ok(out)
+    }
+
+    fn store_acme_eab_key(&self, tenant_id: &str, key: &AcmeEabKey) -> Result<(), CertError> {
+        let conn = db!(self);
+        conn.execute(
+            "INSERT OR REPLACE INTO acme_eab_key (kid, tenant_id, data) VALUES (?1, ?2, ?3)",
+            params![key.kid, tenant_id, ser(key)?],
+        ).map_err(|e| CertError::Storage(e.to_string()))?;
+        Ok(())
+    }
+
+    fn get_acme_eab_key(&self, tenant_id: &str, kid: &str)
+        -> Result<Option<AcmeEabKey>, CertError>
+    {
+        let conn = db!(self);
+        let row: Option<String> = conn.query_row(
+            "SELECT data FROM acme_eab_key WHERE kid = ?1 AND tenant_id = ?2",
+            params![kid, tenant_id],
+            |r| r.get(0),
+        ).optional().map_err(|e| CertError::Storage(e.to_string()))?;
+        row.as_deref().map(de).transpose()
+    }
+
+    fn list_acme_eab_keys(&self, tenant_id: &str) -> Result<Vec<AcmeEabKey>, CertError> {
+        let conn = db!(self);
+        let mut stmt = conn.prepare(
+            "SELECT data FROM acme_eab_key WHERE tenant_id = ?1 ORDER BY rowid",
+        ).map_err(|e| CertError::Storage(e.to_string()))?;
+        let rows = stmt.query_map(params![tenant_id], |r| r.get::<_, String>(0))
+            .map_err(|e| CertError::Storage(e.to_string()))?;
+        let mut out = Vec::new();
+        for row in rows {
+            let data = row.map_err(|e| CertError::Storage(e.to_string()))?;
+            out.push(de::<AcmeEabKey>(&data)?);
+        }
+        Ok(out)
+    }
+
+    fn bind_acme_account(&self, tenant_id: &str, kid: &str, account: &AcmeAccount)
+        -> Result<bool, CertError>
+    {
+        let mut conn = db!(self);
+        let tx = conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)
+            .map_err(|e| CertError::Storage(e.to_string()))?;
+        let bound = tx.execute(
+            "UPDATE acme_eab_key SET data = json_set(data, '$.account_id', ?3) \
+             WHERE kid = ?1 AND tenant_id = ?2 \
+             AND json_extract(data, '$.account_id') IS NULL \
+             AND NOT json_extract(data, '$.revoked')",
+            params![kid, tenant_id, account.id],
+        ).map_err(|e| CertError::Storage(e.to_string()))?;
+        if bound == 0 {
+            return Ok(false);
+        }
+        tx.execute(
+            "INSERT INTO acme_account (id, tenant_id, data) VALUES (?1, ?2, ?3)",
+            params![account.id, tenant_id, ser(account)?],
+        ).map_err(|e| CertError::Storage(e.to_string()))?;
+        tx.commit().map_err(|e| CertError::Storage(e.to_string()))?;
+        Ok(true)
+    }
+
     // ─── RA ──────────────────────────────────────────────────────────────────
 
     fn store_ra_request(&self, tenant_id: &str, request: &ApprovalRequest)
@@ -805,6 +1024,78 @@ impl CertStore for OxPersistenceCertStore {
         }
     }
 
+    fn store_scep_transaction(&self, tenant_id: &str, transaction: &ScepTransaction)
+        -> Result<(), CertError>
+    {
+        let conn = db!(self);
+        conn.execute(
+            "INSERT OR REPLACE INTO scep_transaction (transaction_id, tenant_id, data) \
+             VALUES (?1, ?2, ?3)",
+            params![transaction.transaction_id, tenant_id, ser(transaction)?],
+        ).map_err(|e| CertError::Storage(e.to_string()))?;
+        Ok(())
+    }
+
+    fn get_scep_transaction(&self, tenant_id: &str, transaction_id: &str)
+        -> Result<Option<ScepTransaction>, CertError>
+    {
+        let conn = db!(self);
+        let data: Option<String> = conn.query_row(
+            "SELECT data FROM scep_transaction WHERE tenant_id = ?1 AND transaction_id = ?2",
+            params![tenant_id, transaction_id],
+            |r| r.get(0),
+        ).optional().map_err(|e| CertError::Storage(e.to_string()))?;
+        data.map(|d| de(&d)).transpose()
+    }
+
+    // ─── OCSP response cache ─────────────────────────────────────────────────
+
+    fn store_ocsp_response(&self, tenant_id: &str, response: &OcspCachedResponse)
+        -> Result<bool, CertError>
+    {
+        let conn = db!(self);
+        let stored = conn.execute(
+            "INSERT OR REPLACE INTO ocsp_response (serial, tenant_id, expires_at, data) \
+             SELECT ?1, ?2, ?3, ?4 WHERE EXISTS ( \
+                 SELECT 1 FROM certificate \
+                 WHERE tenant_id = ?2 AND serial = ?1 AND status = ?5 AND revoked_at IS ?6)",
+            params![
+                response.serial,
+                tenant_id,
+                to_ts(response.expires_at),
+                ser(response)?,
+                cert_status_str(&response.cert_status),
+                response.revoked_at.map(to_ts),
+            ],
+        ).map_err(|e| CertError::Storage(e.to_string()))?;
+        Ok(stored > 0)
+    }
+
+    fn get_ocsp_response(&self, tenant_id: &str, serial: &str)
+        -> Result<Option<OcspCachedResponse>, CertError>
+    {
+        let now = to_ts(time::OffsetDateTime::now_utc());
+        let conn = db!(self);
+        let data: Option<String> = conn.query_row(
+            "SELECT data FROM ocsp_response \
+             WHERE tenant_id = ?1 AND serial = ?2 AND expires_at > ?3",
+            params![tenant_id, serial, now],
+            |r| r.get(0),
+        ).optional().map_err(|e| CertError::Storage(e.to_string()))?;
+        data.map(|d| de(&d)).transpose()
+    }
+
+    fn invalidate_ocsp_response(&self, tenant_id: &str, serial: &str)
+        -> Result<(), CertError>
+    {
+        let conn = db!(self);
+        conn.execute(
+            "DELETE FROM ocsp_response WHERE tenant_id = ?1 AND serial = ?2",
+            params![tenant_id, serial],
+        ).map_err(|e| CertError::Storage(e.to_string()))?;
+        Ok(())
+    }
+
     // ─── Notifications ────────────────────────────────────────────────────────
 
     fn store_notification(&self, tenant_id: &str, notification: &NotificationRecord)
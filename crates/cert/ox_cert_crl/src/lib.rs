@@ -1,16 +1,24 @@
+#[cfg(test)]
+mod tests;
+
 use ox_cert_core::{
     issuer_params_from_cert_pem,
-    model::{CertStoreConfig, KeyStoreConfig, RevocationReason as OxRevocationReason},
+    model::{
+        CertStoreConfig, CrlPartitionConfig, KeyStoreConfig,
+        RevocationReason as OxRevocationReason,
+    },
     open_keystore,
     store::{CertStore, OxPersistenceCertStore},
-    CertError,
+    CertError, KeyStoreSigner,
 };
 use rcgen::{
-    CertificateRevocationListParams, KeyIdMethod, RevokedCertParams,
-    RevocationReason as RcgenRevocationReason, SerialNumber, Issuer,
+    CertificateRevocationListParams, CrlDistributionPoint, CrlIssuingDistributionPoint,
+    KeyIdMethod, RevokedCertParams, RevocationReason as RcgenRevocationReason, SerialNumber,
+    Issuer,
 };
 use serde::Deserialize;
-use std::sync::{Arc, RwLock};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
 use time::OffsetDateTime;
 
 #[derive(Debug, Deserialize)]
@@ -31,6 +39,9 @@ pub struct CrlConfig {
     pub node_id: Option<String>,
     #[serde(default)]
     pub background_pregenerate: bool,
+    /// Serve one CRL per shard under `/crl/shard/{n}`. Must match the issuers'
+    /// `extensions.cdp.partitions`.
+    pub partitions: Option<CrlPartitionConfig>,
 }
 
 fn default_update_interval() -> u64 { 3600 }
@@ -45,10 +56,31 @@ pub struct CachedCrl {
     pub crl_number: u64,
 }
 
+/// One CRL the plugin publishes: the tenant-wide CRL (`shard: None`) or one
+/// partition, each as a full or a delta CRL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CrlKind {
+    pub shard: Option<u32>,
+    pub delta: bool,
+}
+
+impl CrlKind {
+    /// Key for the advisory lock in the cert store.
+    fn lock_key(&self) -> String {
+        let base = if self.delta { "delta_crl" } else { "full_crl" };
+        match self.shard {
+            Some(n) => format!("{}:shard:{}", base, n),
+            None => base.to_string(),
+        }
+    }
+}
+
+type CrlCache = Arc<RwLock<Option<CachedCrl>>>;
+
 pub struct CrlContext {
     config: CrlConfig,
-    full_crl: Arc<RwLock<Option<CachedCrl>>>,
-    delta_crl: Arc<RwLock<Option<CachedCrl>>>,
+    /// One cache per CRL, so regenerating one shard never blocks another.
+    caches: Mutex<HashMap<CrlKind, CrlCache>>,
     holder_id: String,
     shutdown: Arc<std::sync::atomic::AtomicBool>,
 }
@@ -60,32 +92,44 @@ impl CrlContext {
         let holder_id = config.node_id.clone().unwrap_or_else(|| {
             format!("{}:{}", hostname(), std::process::id())
         });
-        let full_crl = Arc::new(RwLock::new(None));
-        let delta_crl = Arc::new(RwLock::new(None));
         let shutdown = Arc::new(std::sync::atomic::AtomicBool::new(false));
 
         let ctx = Arc::new(Self {
             config,
-            full_crl,
-            delta_crl,
+            caches: Mutex::new(HashMap::new()),
             holder_id,
             shutdown,
         });
 
         if ctx.config.background_pregenerate {
             let ctx_clone = Arc::clone(&ctx);
-            let full_clone = Arc::clone(&ctx.full_crl);
-            let delta_clone = Arc::clone(&ctx.delta_crl);
             let update_secs = ctx.config.crl_update_interval_secs;
             let delta_secs = ctx.config.crl_delta_interval_secs;
             let sd = Arc::clone(&ctx.shutdown);
             std::thread::spawn(move || {
-                bg_loop(ctx_clone, update_secs, delta_secs, full_clone, delta_clone, sd);
+                bg_loop(ctx_clone, update_secs, delta_secs, sd);
             });
         }
 
         ctx
     }
+
+    fn cache(&self, kind: CrlKind) -> CrlCache {
+        let mut caches = self.caches.lock().unwrap();
+        Arc::clone(caches.entry(kind).or_default())
+    }
+
+    /// Every CRL published: the tenant-wide pair, then each shard's.
+    fn kinds(&self) -> Vec<CrlKind> {
+        let shards = self.config.partitions.as_ref().map(|p| p.shards).unwrap_or(0);
+        std::iter::once(None).chain((0..shards).map(Some))
+            .flat_map(|shard| [CrlKind { shard, delta: false }, CrlKind { shard, delta: true }])
+            .collect()
+    }
+
+    fn update_secs(&self, kind: CrlKind) -> u64 {
+        if kind.delta { self.config.crl_delta_interval_secs } else { self.config.crl_update_interval_secs }
+    }
 }
 
 impl Drop for CrlContext {
@@ -102,8 +146,6 @@ fn bg_loop(
     ctx: Arc<CrlContext>,
     update_secs: u64,
     delta_secs: u64,
-    full_crl: Arc<RwLock<Option<CachedCrl>>>,
-    delta_crl: Arc<RwLock<Option<CachedCrl>>>,
     shutdown: Arc<std::sync::atomic::AtomicBool>,
 ) {
     let sleep_secs = (update_secs.min(delta_secs) / 2).max(30);
@@ -117,23 +159,17 @@ fn bg_loop(
         };
 
         let now = OffsetDateTime::now_utc();
-        let should_full = {
-            let guard = full_crl.read().unwrap();
-            guard.as_ref().map(|c| c.next_update - ::time::Duration::seconds(60) < now).unwrap_or(true)
-        };
-        if should_full {
-            if let Ok(cached) = generate_crl_with_store(&ctx, &store, update_secs, false) {
-                *full_crl.write().unwrap() = Some(cached);
-            }
-        }
-
-        let should_delta = {
-            let guard = delta_crl.read().unwrap();
-            guard.as_ref().map(|c| c.next_update - ::time::Duration::seconds(30) < now).unwrap_or(true)
-        };
-        if should_delta {
-            if let Ok(cached) = generate_crl_with_store(&ctx, &store, delta_secs, true) {
-                *delta_crl.write().unwrap() = Some(cached);
+        for kind in ctx.kinds() {
+            let margin = if kind.delta { 30 } else { 60 };
+            let cache = ctx.cache(kind);
+            let stale = {
+                let guard = cache.read().unwrap();
+                guard.as_ref().map(|c| c.next_update - ::time::Duration::seconds(margin) < now).unwrap_or(true)
+            };
+            if stale {
+                if let Ok(cached) = generate_crl_with_store(&ctx, &store, ctx.update_secs(kind), kind) {
+                    *cache.write().unwrap() = Some(cached);
+                }
             }
         }
     }
@@ -154,13 +190,40 @@ fn ox_reason_to_rcgen(r: OxRevocationReason) -> RcgenRevocationReason {
     }
 }
 
-pub fn handle_crl_request(ctx: &CrlContext, path: &str) -> CrlResponse {
+/// Parse `/crl`, `/crl.der`, `/crl.pem` and `/crl/delta`, or the same forms under
+/// `/crl/shard/{n}` (which also accepts `.crl`). Returns the CRL and whether PEM
+/// was asked for.
+fn parse_crl_path(path: &str) -> Option<(CrlKind, bool)> {
     let is_delta = path.contains("delta");
     let is_pem = path.ends_with(".pem");
+    let shard = match path.strip_prefix("/crl/shard/") {
+        Some(rest) => {
+            let digits = rest.split(['/', '.']).next().unwrap_or("");
+            Some(digits.parse::<u32>().ok()?)
+        }
+        None => None,
+    };
+    Some((CrlKind { shard, delta: is_delta }, is_pem))
+}
 
-    let cache = if is_delta { &ctx.delta_crl } else { &ctx.full_crl };
-    let lock_key = if is_delta { "delta_crl" } else { "full_crl" };
-    let update_secs = if is_delta { ctx.config.crl_delta_interval_secs } else { ctx.config.crl_update_interval_secs };
+pub fn handle_crl_request(ctx: &CrlContext, path: &str) -> CrlResponse {
+    let not_found = || CrlResponse {
+        http_status: 404,
+        content_type: "application/json".to_string(),
+        body: b"{\"error\":\"NOT_FOUND\",\"message\":\"no such CRL\"}".to_vec(),
+        warning: None,
+    };
+    let Some((kind, is_pem)) = parse_crl_path(path) else { return not_found() };
+    if let Some(n) = kind.shard {
+        match &ctx.config.partitions {
+            Some(p) if n < p.shards => {}
+            _ => return not_found(),
+        }
+    }
+
+    let cache = ctx.cache(kind);
+    let lock_key = kind.lock_key();
+    let update_secs = ctx.update_secs(kind);
 
     // Check cache freshness
     {
@@ -183,14 +246,14 @@ pub fn handle_crl_request(ctx: &CrlContext, path: &str) -> CrlResponse {
         },
     };
 
-    match store.acquire_crl_lock(&ctx.config.tenant_id, lock_key, &ctx.holder_id, ctx.config.crl_lock_ttl_secs) {
+    match store.acquire_crl_lock(&ctx.config.tenant_id, &lock_key, &ctx.holder_id, ctx.config.crl_lock_ttl_secs) {
         Ok(Some(_crl_number)) => {
             // We got the lock, regenerate
-            match generate_crl_with_store(ctx, &store, update_secs, is_delta) {
+            match generate_crl_with_store(ctx, &store, update_secs, kind) {
                 Ok(cached) => {
                     let response = serve_cached(&cached, is_pem, false);
                     *cache.write().unwrap() = Some(cached);
-                    let _ = store.release_crl_lock(&ctx.config.tenant_id, lock_key, &ctx.holder_id);
+                    let _ = store.release_crl_lock(&ctx.config.tenant_id, &lock_key, &ctx.holder_id);
                     response
                 }
                 Err(e) => CrlResponse {
@@ -236,16 +299,25 @@ fn generate_crl_with_store(
     ctx: &CrlContext,
     store: &OxPersistenceCertStore,
     next_update_secs: u64,
-    is_delta: bool,
+    kind: CrlKind,
 ) -> Result<CachedCrl, CertError> {
     let tenant = &ctx.config.tenant_id;
     let now = OffsetDateTime::now_utc();
 
-    let revoked = if is_delta {
-        let since = now - ::time::Duration::seconds(next_update_secs as i64 * 2);
-        store.list_revoked_since(tenant, since)?
-    } else {
-        store.list_revoked(tenant)?
+    let since = kind.delta.then(|| now - ::time::Duration::seconds(next_update_secs as i64 * 2));
+    let revoked = match (kind.shard, since) {
+        (Some(shard), since) => store.list_revoked_in_shard(tenant, shard, since)?,
+        (None, Some(since)) => store.list_revoked_since(tenant, since)?,
+        (None, None) => store.list_revoked(tenant)?,
+    };
+
+    // A shard CRL covers only certificates whose CDP names it
+    let issuing_distribution_point = match (kind.shard, &ctx.config.partitions) {
+        (Some(shard), Some(p)) => Some(CrlIssuingDistributionPoint {
+            distribution_point: CrlDistributionPoint { uris: vec![p.url(shard)] },
+            scope: None,
+        }),
+        _ => None,
     };
 
     let ca_cert_pem = std::fs::read_to_string(&ctx.config.ca_intermediate_cert_path)
@@ -253,9 +325,7 @@ fn generate_crl_with_store(
     let issuer_params = issuer_params_from_cert_pem(&ca_cert_pem)?;
 
     let ks = open_keystore(&ctx.config.keystore)?;
-    let ca_key_pem = ks.load_key_pem(tenant, &ctx.config.ca_intermediate_key_id)?;
-    let ca_keypair = rcgen::KeyPair::from_pem(&ca_key_pem)
-        .map_err(|e| CertError::Crypto(e.to_string()))?;
+    let ca_keypair = KeyStoreSigner::new(ks.as_ref(), tenant, &ctx.config.ca_intermediate_key_id)?;
 
     let revoked_certs: Vec<RevokedCertParams> = revoked.iter().filter_map(|c| {
         let serial_bytes = uuid::Uuid::parse_str(&c.serial).ok()?.as_bytes().to_vec();
@@ -276,7 +346,7 @@ fn generate_crl_with_store(
         this_update: now,
         next_update,
         crl_number: SerialNumber::from_slice(&crl_number.to_be_bytes()),
-        issuing_distribution_point: None,
+        issuing_distribution_point,
         revoked_certs,
         key_identifier_method: KeyIdMethod::Sha256,
     };
@@ -0,0 +1,158 @@
+//! Partitioned CRLs: each shard CRL lists only its own revocations and names
+//! itself in an issuing distribution point matching the certificates' CDP.
+
+use crate::*;
+use ox_cert_core::builder::CertBuilder;
+use ox_cert_core::model::RevocationReason;
+use rcgen::KeyPair;
+use serde_json::{json, Value};
+use x509_parser::prelude::{
+    CertificateRevocationList, DistributionPointName, FromDer, GeneralName, ParsedExtension,
+};
+
+const URL_TEMPLATE: &str = "http://pki.example.com/crl/shard/{shard}";
+
+/// A fresh EC intermediate CA whose key is `intermediate` in the keystore.
+fn make_ca(dir: &tempfile::TempDir) -> (KeyPair, rcgen::CertificateParams) {
+    let key_dir = dir.path().join("keys").join("t1");
+    std::fs::create_dir_all(&key_dir).unwrap();
+    let key = KeyPair::generate().unwrap();
+    let mut params = rcgen::CertificateParams::default();
+    params.distinguished_name.push(rcgen::DnType::CommonName, "Test Intermediate CA");
+    params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+    let cert = params.self_signed(&key).unwrap();
+    std::fs::write(key_dir.join("intermediate.key.pem"), key.serialize_pem()).unwrap();
+    std::fs::write(dir.path().join("ca.crt"), cert.pem()).unwrap();
+    (key, params)
+}
+
+fn make_ctx(dir: &tempfile::TempDir, overrides: Value) -> Arc<CrlContext> {
+    let mut config = json!({
+        "tenant_id": "t1",
+        "store": { "driver": "sqlite", "path": dir.path().join("cert.db").to_str().unwrap() },
+        "keystore": { "store_type": "software", "key_dir": dir.path().join("keys").to_str().unwrap() },
+        "ca_intermediate_key_id": "intermediate",
+        "ca_intermediate_cert_path": dir.path().join("ca.crt").to_str().unwrap(),
+    });
+    for (k, v) in overrides.as_object().unwrap() {
+        config[k] = v.clone();
+    }
+    CrlContext::new(serde_json::from_value(config).unwrap())
+}
+
+fn open_store(dir: &tempfile::TempDir) -> OxPersistenceCertStore {
+    OxPersistenceCertStore::open(dir.path().join("cert.db").to_str().unwrap()).unwrap()
+}
+
+/// Issue and revoke `count` partitioned certificates; returns (serial, shard).
+fn issue_revoked(
+    dir: &tempfile::TempDir,
+    ca: &(KeyPair, rcgen::CertificateParams),
+    partitions: &CrlPartitionConfig,
+    count: usize,
+) -> Vec<(uuid::Uuid, u32)> {
+    let store = open_store(dir);
+    (0..count).map(|i| {
+        let key = KeyPair::generate().unwrap();
+        let record = CertBuilder::new()
+            .subject(&format!("CN=host{}.example.com", i))
+            .crl_partitions(Some(partitions))
+            .sign_with_issuer("t1", &key, &ca.1, &ca.0)
+            .unwrap();
+        store.store_cert("t1", &record).unwrap();
+        store.mark_revoked("t1", &record.serial, RevocationReason::KeyCompromise,
+            OffsetDateTime::now_utc()).unwrap();
+        (uuid::Uuid::parse_str(&record.serial).unwrap(), record.crl_shard.unwrap())
+    }).collect()
+}
+
+/// Revoked serials (lowercase hex) and IDP URIs of a DER CRL.
+fn read_crl(der: &[u8]) -> (Vec<String>, Vec<String>) {
+    let (_, crl) = CertificateRevocationList::from_der(der).unwrap();
+    let serials = crl.iter_revoked_certificates()
+        .map(|r| r.user_certificate.to_str_radix(16))
+        .collect();
+    let uris = crl.extensions().iter()
+        .filter_map(|e| match e.parsed_extension() {
+            ParsedExtension::IssuingDistributionPoint(idp) => idp.distribution_point.clone(),
+            _ => None,
+        })
+        .filter_map(|dp| match dp {
+            DistributionPointName::FullName(names) => Some(names),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|n| match n {
+            GeneralName::URI(u) => Some(u.to_string()),
+            _ => None,
+        })
+        .collect();
+    (serials, uris)
+}
+
+fn hex(serial: &uuid::Uuid) -> String {
+    format!("{:x}", serial.as_u128())
+}
+
+#[test]
+fn test_shard_crl_lists_only_its_partition() {
+    let dir = tempfile::tempdir().unwrap();
+    let ca = make_ca(&dir);
+    let partitions = CrlPartitionConfig { shards: 2, url_template: URL_TEMPLATE.to_string() };
+    let ctx = make_ctx(&dir, json!({ "partitions": { "shards": 2, "url_template": URL_TEMPLATE } }));
+    let issued = issue_revoked(&dir, &ca, &partitions, 8);
+
+    for shard in 0..2 {
+        let resp = handle_crl_request(&ctx, &format!("/crl/shard/{}", shard));
+        assert_eq!(resp.http_status, 200);
+        let (mut serials, uris) = read_crl(&resp.body);
+        let mut expected: Vec<String> = issued.iter()
+            .filter(|(_, s)| *s == shard)
+            .map(|(serial, _)| hex(serial))
+            .collect();
+        serials.sort();
+        expected.sort();
+        assert_eq!(serials, expected, "shard {} lists exactly its own revocations", shard);
+        assert_eq!(uris, vec![partitions.url(shard)], "IDP matches the certificates' CDP");
+    }
+
+    let pem = handle_crl_request(&ctx, "/crl/shard/1.pem");
+    assert_eq!(pem.http_status, 200);
+    assert!(String::from_utf8(pem.body).unwrap().starts_with("-----BEGIN X509 CRL-----"));
+
+    let delta = handle_crl_request(&ctx, "/crl/shard/0/delta");
+    assert_eq!(delta.http_status, 200);
+    let expected = issued.iter().filter(|(_, s)| *s == 0).count();
+    assert_eq!(read_crl(&delta.body).0.len(), expected);
+}
+
+#[test]
+fn test_full_crl_lists_every_partition() {
+    let dir = tempfile::tempdir().unwrap();
+    let ca = make_ca(&dir);
+    let partitions = CrlPartitionConfig { shards: 3, url_template: URL_TEMPLATE.to_string() };
+    let ctx = make_ctx(&dir, json!({ "partitions": { "shards": 3, "url_template": URL_TEMPLATE } }));
+    let issued = issue_revoked(&dir, &ca, &partitions, 6);
+
+    let resp = handle_crl_request(&ctx, "/crl");
+    assert_eq!(resp.http_status, 200);
+    let (serials, uris) = read_crl(&resp.body);
+    assert_eq!(serials.len(), issued.len());
+    assert!(uris.is_empty(), "the tenant-wide CRL has no IDP");
+}
+
+#[test]
+fn test_unknown_shard_not_found() {
+    let dir = tempfile::tempdir().unwrap();
+    make_ca(&dir);
+
+    let ctx = make_ctx(&dir, json!({ "partitions": { "shards": 2, "url_template": URL_TEMPLATE } }));
+    assert_eq!(handle_crl_request(&ctx, "/crl/shard/2").http_status, 404);
+    assert_eq!(handle_crl_request(&ctx, "/crl/shard/x").http_status, 404);
+
+    let unpartitioned = make_ctx(&dir, json!({}));
+    let resp = handle_crl_request(&unpartitioned, "/crl/shard/0");
+    assert_eq!(resp.http_status, 404);
+    assert!(String::from_utf8(resp.body).unwrap().contains("NOT_FOUND"));
+    assert_eq!(handle_crl_request(&unpartitioned, "/crl").http_status, 200);
+}
@@ -3,10 +3,11 @@
 use base64::{engine::general_purpose::STANDARD as B64, Engine};
 use ox_cert_core::{
     builder::{issuer_params_from_cert_pem, parse_csr, sign_csr},
-    model::{AuditAction, AuditEvent, CertStoreConfig, EnrollmentProtocol, IssuancePolicyConfig,
-            KeyStoreConfig},
-    open_keystore,
+    model::{AuditAction, AuditEvent, CertStoreConfig, CrlPartitionConfig, CtConfig, EnrollmentProtocol,
+            IssuancePolicyConfig, KeyStoreConfig},
+    ct, open_keystore,
     store::{CertStore, OxPersistenceCertStore},
+    CertError, IssuanceOptions, KeyStoreSigner,
 };
 use serde::Deserialize;
 use std::collections::HashMap;
@@ -27,6 +28,12 @@ pub struct EstConfig {
     pub ca_intermediate_key_id: String,
     pub ca_intermediate_cert_path: String,
     pub ca_root_cert_path: String,
+    /// Certificate Transparency for issued certificates.
+    #[serde(default)]
+    pub ct: Option<CtConfig>,
+    /// Partitioned CRLs for issued certificates; must match `ox_cert_crl`.
+    #[serde(default)]
+    pub crl_partitions: Option<CrlPartitionConfig>,
     /// Require client certificate (mTLS) for enrollment endpoints.
     #[serde(default)]
     pub require_client_cert: bool,
@@ -317,19 +324,27 @@ fn handle_enroll(
         Ok(p) => p,
         Err(e) => return EstResponse::err(503, &e.to_string()),
     };
-    let ca_key_pem = match ks.load_key_pem(&config.tenant_id, &config.ca_intermediate_key_id) {
-        Ok(p) => p,
+    let ca_key = match KeyStoreSigner::new(ks.as_ref(), &config.tenant_id, &config.ca_intermediate_key_id) {
+        Ok(k) => k,
         Err(e) => return EstResponse::err(503, &format!("CA key unavailable: {}", e)),
     };
-    let ca_key = match rcgen::KeyPair::from_pem(&ca_key_pem) {
-        Ok(k) => k,
-        Err(e) => return EstResponse::err(503, &e.to_string()),
+
+    let ct_chain = match ct::issuer_chain(config.ct.as_ref(), &ca_cert_pem, &config.ca_root_cert_path) {
+        Ok(c) => c,
+        Err(e) => return EstResponse::err(503, &format!("CA not ready: {}", e)),
+    };
+    let options = IssuanceOptions {
+        ct: config.ct.as_ref(),
+        ct_issuer_chain: &ct_chain,
+        crl_partitions: config.crl_partitions.as_ref(),
+        ..Default::default()
     };
 
     // Sign
     let profile = resolve_profile(config, label);
-    let mut cert = match sign_csr(&csr_pem, &config.tenant_id, profile, 365 * 86400, None, &ca_params, &ca_key) {
+    let mut cert = match sign_csr(&csr_pem, &config.tenant_id, profile, 365 * 86400, None, &ca_params, &ca_key, &options) {
         Ok(c) => c,
+        Err(CertError::CtFailure(msg)) => return EstResponse::err(502, &format!("Certificate Transparency: {}", msg)),
         Err(e) => return EstResponse::err(500, &e.to_string()),
     };
     cert.enrollment_protocol = Some(EnrollmentProtocol::Est);
@@ -5,7 +5,8 @@ use ox_cert_core::{
         ApprovalRequest, ApprovalStatus, AuditAction, AuditEvent, CertificateRecord,
         EnrollmentProtocol, IssuancePolicy, KeyType, SanType,
     },
-    open_keystore,
+    ct, open_keystore,
+    IssuanceOptions, KeyStoreSigner,
     store::{CertStore, OxPersistenceCertStore},
     CertError,
 };
@@ -213,19 +214,29 @@ pub fn handle_issue(
         message: e.to_string(),
     })?;
 
-    let ca_pem = ks
-        .load_key_pem(tenant, &config.ca_intermediate_key_id)
-        .map_err(|e| IssueError {
+    let ca_key = KeyStoreSigner::new(ks.as_ref(), tenant, &config.ca_intermediate_key_id).map_err(|e| {
+        IssueError {
             http_status: 503,
             error_code: "CA_NOT_READY",
             message: format!("intermediate key load failed: {}", e),
-        })?;
-    let ca_key = rcgen::KeyPair::from_pem(&ca_pem).map_err(|e| IssueError {
-        http_status: 503,
-        error_code: "CA_NOT_READY",
-        message: format!("intermediate key parse failed: {}", e),
+        }
     })?;
 
+    // Certificate Transparency runs inside signing, so it needs the issuer chain up front
+    let ct_chain = ct::issuer_chain(config.ct.as_ref(), &ca_cert_pem, &config.ca_root_cert_path)
+        .map_err(|e| IssueError {
+            http_status: 503,
+            error_code: "CA_NOT_READY",
+            message: e.to_string(),
+        })?;
+    let crl_partitions = config.extensions.cdp.as_ref().and_then(|c| c.partitions.as_ref());
+    let options = IssuanceOptions {
+        ct: config.ct.as_ref(),
+        ct_issuer_chain: &ct_chain,
+        crl_partitions,
+        ..Default::default()
+    };
+
     let record = if let Some(csr_pem) = &req.csr {
         // ------------------------------------------------------------------
         // CSR-based issuance
@@ -298,12 +309,9 @@ pub fn handle_issue(
             override_sans.as_deref(),
             &ca_params,
             &ca_key,
+            &options,
         )
-        .map_err(|e| IssueError {
-            http_status: 500,
-            error_code: "INTERNAL_ERROR",
-            message: e.to_string(),
-        })?;
+        .map_err(signing_error)?;
         cert.enrollment_protocol = Some(EnrollmentProtocol::Rest);
         cert
     } else if let Some(key_type_str) = &req.key_type {
@@ -324,12 +332,7 @@ pub fn handle_issue(
                 message: format!("key generation failed: {}", e),
             })?;
 
-        let key_pem = ks.load_key_pem(tenant, &serial).map_err(|e| IssueError {
-            http_status: 500,
-            error_code: "INTERNAL_ERROR",
-            message: e.to_string(),
-        })?;
-        let subject_key = rcgen::KeyPair::from_pem(&key_pem).map_err(|e| IssueError {
+        let subject_key = KeyStoreSigner::new(ks.as_ref(), tenant, &serial).map_err(|e| IssueError {
             http_status: 500,
             error_code: "INTERNAL_ERROR",
             message: e.to_string(),
@@ -358,12 +361,13 @@ pub fn handle_issue(
             .sans(effective_sans)
             .validity_seconds(validity_seconds)
             .profile(&profile_name)
+            .crl_partitions(crl_partitions)
             .sign_with_issuer(tenant, &subject_key, &ca_params, &ca_key)
-            .map_err(|e| IssueError {
-                http_status: 500,
-                error_code: "INTERNAL_ERROR",
-                message: e.to_string(),
-            })?;
+            .map_err(signing_error)?;
+        if let Some(ct) = options.ct {
+            let chain: Vec<&str> = ct_chain.iter().map(String::as_str).collect();
+            ct::add_scts(&mut cert, &chain, &ca_key, ct).map_err(signing_error)?;
+        }
         cert.enrollment_protocol = Some(EnrollmentProtocol::Rest);
         cert
     } else {
@@ -422,6 +426,23 @@ impl IssueError {
     }
 }
 
+/// Signing failures; a Certificate Transparency shortfall under `on_failure: block`
+/// is reported separately from internal errors.
+fn signing_error(e: CertError) -> IssueError {
+    match e {
+        CertError::CtFailure(msg) => IssueError {
+            http_status: 502,
+            error_code: "CT_FAILURE",
+            message: msg,
+        },
+        other => IssueError {
+            http_status: 500,
+            error_code: "INTERNAL_ERROR",
+            message: other.to_string(),
+        },
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Helpers
 // ---------------------------------------------------------------------------
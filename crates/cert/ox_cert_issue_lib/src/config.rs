@@ -1,4 +1,6 @@
-use ox_cert_core::model::{CertStoreConfig, CtConfig, IssuancePolicyConfig, KeyStoreConfig};
+use ox_cert_core::model::{
+    CertStoreConfig, CrlPartitionConfig, CtConfig, IssuancePolicyConfig, KeyStoreConfig,
+};
 use serde::Deserialize;
 
 #[derive(Debug, Deserialize)]
@@ -35,4 +37,6 @@ pub struct AiaConfig {
 #[derive(Debug, Deserialize)]
 pub struct CdpConfig {
     pub url: Option<String>,
+    /// Partitioned CRLs: issued certificates get a shard and its CDP.
+    pub partitions: Option<CrlPartitionConfig>,
 }
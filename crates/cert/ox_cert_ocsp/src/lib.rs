@@ -1,11 +1,18 @@
+// ox_cert_ocsp — OCSP Responder (RFC 6960)
+
+#[cfg(test)]
+mod tests;
+
 use ox_cert_core::{
-    model::{CertStatus, CertStoreConfig, KeyStoreConfig},
+    model::{CertStatus, CertStoreConfig, CertificateRecord, KeyStoreConfig, OcspCachedResponse},
     open_keystore,
     store::{CertStore, OxPersistenceCertStore},
+    CertError, KeyStore, KeyStoreSigner,
 };
-use rcgen::{KeyPair, SigningKey};
+use rcgen::{PublicKeyData, SigningKey};
 use serde::Deserialize;
 use time::OffsetDateTime;
+use yasna::Tag;
 
 #[derive(Debug, Deserialize)]
 pub struct OcspConfig {
@@ -13,15 +20,118 @@ pub struct OcspConfig {
     pub store: CertStoreConfig,
     pub keystore: KeyStoreConfig,
     pub responder_key_id: String,
+    /// Delegated responder certificate for `responder_key_id`. Must carry the
+    /// id-kp-OCSPSigning EKU; it is sent in `certs` of every response.
     pub delegated_cert_path: Option<String>,
+    /// Issuing CA certificate. Needed to check request signatures, and used to
+    /// check that the delegated certificate was issued by this CA.
+    pub ca_cert_path: Option<String>,
     #[serde(default = "default_max_age")]
     pub max_age_secs: u64,
     #[serde(default = "default_next_update")]
     pub next_update_secs: u64,
+    /// Echo the RFC 8954 nonce extension.
+    #[serde(default = "default_true")]
+    pub include_nonce: bool,
+    /// Answer unsigned requests with `sigRequired`.
+    #[serde(default)]
+    pub require_signed_requests: bool,
+    /// Reuse a signed response per serial for this long. 0 disables the cache.
+    #[serde(default)]
+    pub cache_secs: u64,
 }
 
 fn default_max_age() -> u64 { 3600 }
 fn default_next_update() -> u64 { 86400 }
+fn default_true() -> bool { true }
+
+/// id-pkix-ocsp-nonce
+const OID_OCSP_NONCE: &[u64] = &[1, 3, 6, 1, 5, 5, 7, 48, 1, 2];
+/// Longest nonce accepted, RFC 8954 §2.1.
+const MAX_NONCE_LEN: usize = 32;
+
+// OCSPResponseStatus values
+const STATUS_MALFORMED_REQUEST: u8 = 1;
+const STATUS_INTERNAL_ERROR: u8 = 2;
+const STATUS_TRY_LATER: u8 = 3;
+const STATUS_SIG_REQUIRED: u8 = 5;
+const STATUS_UNAUTHORIZED: u8 = 6;
+
+// ---------------------------------------------------------------------------
+// Context
+// ---------------------------------------------------------------------------
+
+/// Responder state loaded once at init.
+pub struct OcspContext {
+    pub config: OcspConfig,
+    keypair: KeyStoreSigner<Box<dyn KeyStore>>,
+    /// DER of the delegated responder certificate.
+    responder_cert: Option<Vec<u8>>,
+    /// DER of the issuing CA certificate.
+    ca_cert: Option<Vec<u8>>,
+}
+
+impl OcspContext {
+    /// Load the responder key and, if configured, the delegated and CA certificates.
+    pub fn new(config: OcspConfig) -> Result<Self, CertError> {
+        let ks = open_keystore(&config.keystore)?;
+        let keypair = KeyStoreSigner::new(ks, &config.tenant_id, &config.responder_key_id)
+            .map_err(|e| CertError::Crypto(format!("responder key: {}", e)))?;
+
+        let ca_cert = config.ca_cert_path.as_deref().map(read_cert_der).transpose()?;
+        if config.require_signed_requests && ca_cert.is_none() {
+            return Err(CertError::Validation(
+                "require_signed_requests needs ca_cert_path".to_string(),
+            ));
+        }
+        if config.cache_secs > config.next_update_secs {
+            return Err(CertError::Validation(
+                "cache_secs must not exceed next_update_secs".to_string(),
+            ));
+        }
+
+        let responder_cert = match config.delegated_cert_path.as_deref() {
+            Some(path) => {
+                let der = read_cert_der(path)?;
+                check_delegated_cert(&der, &keypair, ca_cert.as_deref())
+                    .map_err(|e| CertError::Validation(format!("{}: {}", path, e)))?;
+                Some(der)
+            }
+            None => None,
+        };
+        Ok(Self { config, keypair, responder_cert, ca_cert })
+    }
+}
+
+fn read_cert_der(path: &str) -> Result<Vec<u8>, CertError> {
+    let pem_str = std::fs::read_to_string(path)
+        .map_err(|e| CertError::Crypto(format!("failed to read {}: {}", path, e)))?;
+    ::pem::parse(pem_str.as_bytes())
+        .map(|p| p.into_contents())
+        .map_err(|e| CertError::Crypto(format!("invalid PEM in {}: {}", path, e)))
+}
+
+/// A delegated responder certificate must be for the responder key, carry
+/// id-kp-OCSPSigning and, when the CA certificate is known, be issued by it.
+fn check_delegated_cert(der: &[u8], keypair: &impl PublicKeyData, ca_cert: Option<&[u8]>) -> Result<(), String> {
+    use x509_parser::prelude::*;
+    let (_, cert) = X509Certificate::from_der(der).map_err(|e| format!("invalid certificate: {}", e))?;
+    if cert.public_key().subject_public_key.data.as_ref() != keypair.der_bytes() {
+        return Err("certificate does not match responder_key_id".to_string());
+    }
+    let ocsp_signing = cert.extended_key_usage().ok().flatten()
+        .map(|eku| eku.value.ocsp_signing)
+        .unwrap_or(false);
+    if !ocsp_signing {
+        return Err("certificate lacks the id-kp-OCSPSigning extended key usage".to_string());
+    }
+    if let Some(ca_der) = ca_cert {
+        let (_, ca) = X509Certificate::from_der(ca_der).map_err(|e| format!("invalid CA certificate: {}", e))?;
+        cert.verify_signature(Some(ca.public_key()))
+            .map_err(|_| "certificate is not issued by the CA".to_string())?;
+    }
+    Ok(())
+}
 
 // ---------------------------------------------------------------------------
 // DER encoding helpers
@@ -48,8 +158,6 @@ fn der_tlv(tag: u8, content: &[u8]) -> Vec<u8> {
 
 fn der_sequence(content: &[u8]) -> Vec<u8> { der_tlv(0x30, content) }
 fn der_octet_string(b: &[u8]) -> Vec<u8> { der_tlv(0x04, b) }
-#[allow(dead_code)]
-fn der_null() -> Vec<u8> { vec![0x05, 0x00] }
 fn der_bit_string(b: &[u8]) -> Vec<u8> {
     let mut content = vec![0x00u8]; // 0 unused bits
     content.extend_from_slice(b);
@@ -66,7 +174,7 @@ fn der_generalized_time(dt: &OffsetDateTime) -> Vec<u8> {
 }
 
 /// AlgorithmIdentifier DER for the signing key's algorithm.
-fn sig_alg_der(kp: &KeyPair) -> Vec<u8> {
+fn sig_alg_der(kp: &impl PublicKeyData) -> Vec<u8> {
     let alg = kp.algorithm();
     if alg == &rcgen::PKCS_ECDSA_P256_SHA256 {
         // ecdsaWithSHA256  1.2.840.10045.4.3.2
@@ -84,71 +192,136 @@ fn sig_alg_der(kp: &KeyPair) -> Vec<u8> {
 }
 
 // ---------------------------------------------------------------------------
-// DER parsing helpers (OCSP request)
+// OCSP request parsing
 // ---------------------------------------------------------------------------
 
-fn der_length(data: &[u8]) -> (usize, usize) {
-    if data.is_empty() { return (0, 1); }
-    if data[0] & 0x80 == 0 {
-        (data[0] as usize, 1)
-    } else {
-        let n = (data[0] & 0x7f) as usize;
-        if n == 0 || n > 4 || data.len() < n + 1 { return (0, 1); }
-        let mut len = 0usize;
-        for i in 0..n { len = (len << 8) | data[1 + i] as usize; }
-        (len, 1 + n)
-    }
+struct CertId {
+    /// The CertID as sent, echoed in the SingleResponse.
+    der: Vec<u8>,
+    /// serialNumber INTEGER contents.
+    serial: Vec<u8>,
 }
 
-/// Parse the direct TLV children of a DER value, returning (tag, content_bytes, full_tlv_bytes).
-fn parse_children(data: &[u8]) -> Vec<(u8, &[u8], &[u8])> {
-    let mut items = Vec::new();
-    let mut i = 0;
-    while i + 2 <= data.len() {
-        let tag = data[i];
-        let (len, hdr) = der_length(&data[i + 1..]);
-        let end = i + 1 + hdr + len;
-        if end > data.len() { break; }
-        items.push((tag, &data[i + 1 + hdr..end], &data[i..end]));
-        i = end;
-    }
-    items
+struct RequestSignature {
+    algorithm: Vec<u8>,
+    value: Vec<u8>,
+    certs: Vec<Vec<u8>>,
 }
 
-/// Walk DER tree, find all SEQUENCE nodes matching the CertID pattern:
-///   SEQUENCE { SEQUENCE (AlgID), OCTET STRING, OCTET STRING, INTEGER }
-/// Returns Vec<(certid_der, serial_bytes)>.
-fn extract_cert_ids(data: &[u8], out: &mut Vec<(Vec<u8>, Vec<u8>)>) {
-    let children = parse_children(data);
-    for (tag, content, full) in &children {
-        if *tag == 0x30 {
-            // Check if this matches CertID pattern
-            let inner = parse_children(content);
-            if inner.len() >= 4
-                && inner[0].0 == 0x30  // AlgorithmIdentifier
-                && inner[1].0 == 0x04  // issuerNameHash OCTET STRING
-                && inner[2].0 == 0x04  // issuerKeyHash OCTET STRING
-                && inner[3].0 == 0x02  // serialNumber INTEGER
-            {
-                out.push((full.to_vec(), inner[3].1.to_vec()));
-            } else {
-                extract_cert_ids(content, out);
-            }
-        } else if matches!(*tag, 0x31 | 0xa0 | 0xa1 | 0xa2 | 0xa3 | 0xa4 | 0xa5) {
-            extract_cert_ids(content, out);
+struct OcspRequest {
+    /// DER of `tbsRequest`, the signed bytes.
+    tbs: Vec<u8>,
+    cert_ids: Vec<CertId>,
+    /// The nonce `Extension` as sent, and the nonce length.
+    nonce: Option<(Vec<u8>, usize)>,
+    signature: Option<RequestSignature>,
+}
+
+/// Parse an `OCSPRequest` (RFC 6960 §4.1.1).
+fn parse_request(der: &[u8]) -> Option<OcspRequest> {
+    let (tbs, signature) = yasna::parse_der(der, |r| r.read_sequence(|r| {
+        let tbs = r.next().read_der()?;
+        // optionalSignature [0] EXPLICIT Signature
+        let signature = r.read_optional(|r| r.read_tagged(Tag::context(0), |r| r.read_sequence(|r| {
+            let algorithm = r.next().read_der()?;
+            let (value, _) = r.next().read_bitvec_bytes()?;
+            let certs = r.read_optional(|r| r.read_tagged(Tag::context(0), |r| {
+                r.collect_sequence_of(|r| r.read_der())
+            }))?.unwrap_or_default();
+            Ok(RequestSignature { algorithm, value, certs })
+        })))?;
+        Ok((tbs, signature))
+    })).ok()?;
+
+    let (cert_ids, extensions) = yasna::parse_der(&tbs, |r| r.read_sequence(|r| {
+        // version [0] EXPLICIT, requestorName [1] EXPLICIT: not used
+        r.read_optional(|r| r.read_tagged(Tag::context(0), |r| r.read_u8()))?;
+        r.read_optional(|r| r.read_tagged(Tag::context(1), |r| r.read_der()))?;
+        let cert_ids = r.next().collect_sequence_of(|r| r.read_sequence(|r| {
+            let der = r.next().read_der()?;
+            // singleRequestExtensions [0] EXPLICIT: none supported
+            r.read_optional(|r| r.read_tagged(Tag::context(0), |r| r.read_der()))?;
+            Ok(der)
+        }))?;
+        let extensions = r.read_optional(|r| r.read_tagged(Tag::context(2), |r| {
+            r.collect_sequence_of(|r| r.read_der())
+        }))?.unwrap_or_default();
+        Ok((cert_ids, extensions))
+    })).ok()?;
+
+    let cert_ids = cert_ids.into_iter()
+        .map(|der| {
+            let serial = yasna::parse_der(&der, |r| r.read_sequence(|r| {
+                r.next().read_der()?;   // hashAlgorithm
+                r.next().read_bytes()?; // issuerNameHash
+                r.next().read_bytes()?; // issuerKeyHash
+                Ok(r.next().read_bigint_bytes()?.0)
+            })).ok()?;
+            Some(CertId { der, serial })
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let mut nonce = None;
+    for ext in extensions {
+        let (oid, value) = yasna::parse_der(&ext, |r| r.read_sequence(|r| {
+            let oid = r.next().read_oid()?;
+            r.read_optional(|r| r.read_bool())?;
+            Ok((oid, r.next().read_bytes()?))
+        })).ok()?;
+        if oid.components().as_slice() == OID_OCSP_NONCE {
+            // extnValue holds the nonce OCTET STRING; RFC 6960 clients sent it bare
+            let len = yasna::parse_der(&value, |r| r.read_bytes()).map(|n| n.len()).unwrap_or(value.len());
+            nonce = Some((ext, len));
         }
     }
+
+    Some(OcspRequest { tbs, cert_ids, nonce, signature })
 }
 
-/// Convert serial bytes (DER INTEGER value) to UUID string.
-/// Handles leading 0x00 padding byte from DER unsigned encoding.
+/// Convert serial bytes (DER INTEGER contents) to the UUID string used in the store.
 fn serial_bytes_to_uuid(bytes: &[u8]) -> Option<String> {
-    let b = if bytes.len() == 17 && bytes[0] == 0x00 { &bytes[1..] } else { bytes };
-    if b.len() != 16 { return None; }
-    let arr: [u8; 16] = b.try_into().ok()?;
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    let b = &bytes[start..];
+    if b.len() > 16 { return None; }
+    let mut arr = [0u8; 16];
+    arr[16 - b.len()..].copy_from_slice(b);
     Some(uuid::Uuid::from_bytes(arr).to_string())
 }
 
+// ---------------------------------------------------------------------------
+// Request signatures
+// ---------------------------------------------------------------------------
+
+/// Check a signed request: the first certificate in `certs` must be issued by
+/// the CA, currently valid and not revoked, and its key must verify the
+/// signature over `tbsRequest`.
+fn verify_request_signature(
+    request: &OcspRequest,
+    ca_der: &[u8],
+    store: &dyn CertStore,
+    tenant: &str,
+) -> Result<bool, CertError> {
+    use x509_parser::prelude::*;
+    let Some(sig) = &request.signature else { return Ok(false) };
+    let Some(signer_der) = sig.certs.first() else { return Ok(false) };
+    let Ok((_, signer)) = X509Certificate::from_der(signer_der) else { return Ok(false) };
+    let (_, ca) = X509Certificate::from_der(ca_der)
+        .map_err(|e| CertError::Crypto(format!("invalid CA certificate: {}", e)))?;
+    if signer.verify_signature(Some(ca.public_key())).is_err() || !signer.validity().is_valid() {
+        return Ok(false);
+    }
+    if let Some(serial) = serial_bytes_to_uuid(signer.raw_serial()) {
+        if let Some(rec) = store.get_cert_by_serial(tenant, &serial)? {
+            if rec.status == CertStatus::Revoked {
+                return Ok(false);
+            }
+        }
+    }
+    let Ok((_, algorithm)) = AlgorithmIdentifier::from_der(&sig.algorithm) else { return Ok(false) };
+    let value = x509_parser::der_parser::asn1_rs::BitString::new(0, &sig.value);
+    Ok(x509_parser::verify::verify_signature(signer.public_key(), &algorithm, &value, &request.tbs).is_ok())
+}
+
 // ---------------------------------------------------------------------------
 // OCSP cert status
 // ---------------------------------------------------------------------------
@@ -159,17 +332,38 @@ enum OcspCertStatus {
     Unknown,
 }
 
+fn lookup_cert(
+    store: &dyn CertStore,
+    tenant: &str,
+    serial: Option<&str>,
+) -> Result<Option<CertificateRecord>, CertError> {
+    match serial {
+        Some(serial) => store.get_cert_by_serial(tenant, serial),
+        None => Ok(None),
+    }
+}
+
+fn ocsp_status(cert: Option<&CertificateRecord>, now: OffsetDateTime) -> OcspCertStatus {
+    match cert {
+        Some(cert) if cert.status == CertStatus::Revoked => OcspCertStatus::Revoked {
+            at: cert.revoked_at.unwrap_or(now),
+            reason: cert.revocation_reason.map(|r| r as u8).unwrap_or(0),
+        },
+        Some(_) => OcspCertStatus::Good,
+        None => OcspCertStatus::Unknown,
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Build RFC 6960 signed BasicOCSPResponse
 // ---------------------------------------------------------------------------
 
-fn build_basic_ocsp_response(
-    keypair: &KeyPair,
+fn build_single_response(
     cert_id_der: &[u8],
     cert_status: &OcspCertStatus,
     now: OffsetDateTime,
     next_update: OffsetDateTime,
-) -> Result<Vec<u8>, String> {
+) -> Vec<u8> {
     // CertStatus encoding
     let cert_status_der = match cert_status {
         OcspCertStatus::Good => vec![0x80, 0x00],
@@ -189,28 +383,43 @@ fn build_basic_ocsp_response(
     single.extend_from_slice(&cert_status_der);
     single.extend_from_slice(&der_generalized_time(&now));
     single.extend_from_slice(&der_explicit(0, &der_generalized_time(&next_update)));
-    let single = der_sequence(&single);
+    der_sequence(&single)
+}
+
+fn build_basic_ocsp_response(
+    ctx: &OcspContext,
+    singles: &[Vec<u8>],
+    nonce_ext: Option<&[u8]>,
+    now: OffsetDateTime,
+) -> Result<Vec<u8>, String> {
+    let keypair = &ctx.keypair;
 
     // ResponderID ::= [2] EXPLICIT KeyHash (SHA-1 of subjectPublicKey)
     use sha1::Digest;
-    let key_hash = sha1::Sha1::digest(keypair.public_key_raw());
+    let key_hash = sha1::Sha1::digest(keypair.der_bytes());
     let responder_id = der_tlv(0xa2, &der_octet_string(&key_hash));
 
-    // ResponseData ::= SEQUENCE { responderID, producedAt, responses }
+    // ResponseData ::= SEQUENCE { responderID, producedAt, responses, responseExtensions [1] }
     let mut tbs = Vec::new();
     tbs.extend_from_slice(&responder_id);
     tbs.extend_from_slice(&der_generalized_time(&now));
-    tbs.extend_from_slice(&der_sequence(&single)); // responses SEQUENCE OF SingleResponse
+    tbs.extend_from_slice(&der_sequence(&singles.concat())); // responses SEQUENCE OF SingleResponse
+    if let Some(ext) = nonce_ext {
+        tbs.extend_from_slice(&der_tlv(0xa1, &der_sequence(ext)));
+    }
     let tbs = der_sequence(&tbs);
 
     // Sign tbsResponseData
     let signature = keypair.sign(&tbs).map_err(|e| e.to_string())?;
 
-    // BasicOCSPResponse ::= SEQUENCE { tbsResponseData, signatureAlgorithm, signature }
+    // BasicOCSPResponse ::= SEQUENCE { tbsResponseData, signatureAlgorithm, signature, certs [0] }
     let mut basic = Vec::new();
     basic.extend_from_slice(&tbs);
     basic.extend_from_slice(&sig_alg_der(keypair));
     basic.extend_from_slice(&der_bit_string(&signature));
+    if let Some(cert) = &ctx.responder_cert {
+        basic.extend_from_slice(&der_explicit(0, &der_sequence(cert)));
+    }
     let basic = der_sequence(&basic);
 
     Ok(basic)
@@ -240,72 +449,99 @@ pub struct OcspOutcome {
     pub max_age_secs: u64,
 }
 
-pub fn handle_ocsp(config: &OcspConfig, request_der: &[u8]) -> OcspOutcome {
+pub fn handle_ocsp(ctx: &OcspContext, request_der: &[u8]) -> OcspOutcome {
     let err = |status: u8| OcspOutcome {
         http_status: 200,
         body: build_ocsp_error_response(status),
         max_age_secs: 0,
     };
+    let config = &ctx.config;
 
-    if request_der.is_empty() { return err(1); /* MalformedRequest */ }
-
-    // Extract CertIDs from request
-    let mut cert_id_pairs: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
-    extract_cert_ids(request_der, &mut cert_id_pairs);
-    if cert_id_pairs.is_empty() { return err(1); }
+    let Some(request) = parse_request(request_der) else { return err(STATUS_MALFORMED_REQUEST) };
+    if request.cert_ids.is_empty() { return err(STATUS_MALFORMED_REQUEST); }
 
-    // Open keystore and load responder key
-    let ks = match open_keystore(&config.keystore) {
-        Ok(k) => k,
-        Err(_) => return err(2), // InternalError
-    };
-    let key_pem = match ks.load_key_pem(&config.tenant_id, &config.responder_key_id) {
-        Ok(p) => p,
-        Err(_) => return err(2),
-    };
-    let keypair = match KeyPair::from_pem(&key_pem) {
-        Ok(k) => k,
-        Err(_) => return err(2),
+    let nonce = match &request.nonce {
+        Some((ext, len)) if config.include_nonce => {
+            if *len == 0 || *len > MAX_NONCE_LEN { return err(STATUS_MALFORMED_REQUEST); }
+            Some(ext.as_slice())
+        }
+        _ => None,
     };
 
     // Open cert store
     let store = match OxPersistenceCertStore::open(config.store.db_path()) {
         Ok(s) => s,
-        Err(_) => return err(2),
+        Err(_) => return err(STATUS_INTERNAL_ERROR),
     };
     let tenant = &config.tenant_id;
 
+    if config.require_signed_requests {
+        if request.signature.is_none() { return err(STATUS_SIG_REQUIRED); }
+        let ca_der = ctx.ca_cert.as_deref().unwrap_or_default();
+        match verify_request_signature(&request, ca_der, &store, tenant) {
+            Ok(true) => {}
+            Ok(false) => return err(STATUS_UNAUTHORIZED),
+            Err(_) => return err(STATUS_TRY_LATER),
+        }
+    }
+
     let now = OffsetDateTime::now_utc();
     let next_update = now + time::Duration::seconds(config.next_update_secs as i64);
-
-    // Build a response for the first serial (batch support: clients typically request one)
-    let (cert_id_der, serial_bytes) = &cert_id_pairs[0];
-
-    let cert_status = match serial_bytes_to_uuid(serial_bytes) {
-        Some(uuid_str) => {
-            match store.get_cert_by_serial(tenant, &uuid_str) {
-                Ok(Some(cert)) if cert.status == CertStatus::Revoked => {
-                    OcspCertStatus::Revoked {
-                        at: cert.revoked_at.unwrap_or(now),
-                        reason: cert.revocation_reason.map(|r| r as u8).unwrap_or(0),
-                    }
-                }
-                Ok(Some(_)) => OcspCertStatus::Good,
-                Ok(None) => OcspCertStatus::Unknown,
-                Err(_) => return err(3), // TryLater
+    let serials: Vec<Option<String>> = request.cert_ids.iter()
+        .map(|id| serial_bytes_to_uuid(&id.serial))
+        .collect();
+
+    // A nonce makes every response unique; only single-serial, nonce-less requests are cached
+    let cache_key = match (serials.as_slice(), nonce) {
+        ([Some(serial)], None) if config.cache_secs > 0 => Some(serial.as_str()),
+        _ => None,
+    };
+    if let Some(serial) = cache_key {
+        if let Ok(Some(cached)) = store.get_ocsp_response(tenant, serial) {
+            if cached.cert_id == request.cert_ids[0].der {
+                let remaining = (cached.expires_at - now).whole_seconds().max(0) as u64;
+                return OcspOutcome {
+                    http_status: 200,
+                    body: cached.der,
+                    max_age_secs: config.max_age_secs.min(remaining),
+                };
             }
         }
-        None => OcspCertStatus::Unknown,
+    }
+
+    let mut singles = Vec::with_capacity(request.cert_ids.len());
+    let mut certs = Vec::with_capacity(request.cert_ids.len());
+    for (id, serial) in request.cert_ids.iter().zip(&serials) {
+        let cert = match lookup_cert(&store, tenant, serial.as_deref()) {
+            Ok(c) => c,
+            Err(_) => return err(STATUS_TRY_LATER),
+        };
+        singles.push(build_single_response(&id.der, &ocsp_status(cert.as_ref(), now), now, next_update));
+        certs.push(cert);
+    }
+
+    let body = match build_basic_ocsp_response(ctx, &singles, nonce, now) {
+        Ok(basic_der) => build_ocsp_response_successful(&basic_der),
+        Err(_) => return err(STATUS_INTERNAL_ERROR),
     };
 
-    match build_basic_ocsp_response(&keypair, cert_id_der, &cert_status, now, next_update) {
-        Ok(basic_der) => OcspOutcome {
-            http_status: 200,
-            body: build_ocsp_response_successful(&basic_der),
-            max_age_secs: config.max_age_secs,
-        },
-        Err(_) => err(2),
+    // Unknown serials are not cached: the cert may be stored moments later
+    if let (Some(serial), [Some(cert)]) = (cache_key, certs.as_slice()) {
+        let cached = OcspCachedResponse {
+            serial: serial.to_string(),
+            cert_id: request.cert_ids[0].der.clone(),
+            der: body.clone(),
+            cert_status: cert.status.clone(),
+            revoked_at: cert.revoked_at,
+            produced_at: now,
+            expires_at: now + time::Duration::seconds(config.cache_secs as i64),
+        };
+        // The store refuses the write if the cert was revoked since it was read.
+        // A skipped or failed cache write only costs a signature on the next request.
+        let _ = store.store_ocsp_response(tenant, &cached);
     }
+
+    OcspOutcome { http_status: 200, body, max_age_secs: config.max_age_secs }
 }
 
 // ---------------------------------------------------------------------------
@@ -324,7 +560,7 @@ pub mod plugin {
 
     struct PluginState {
         api: CoreHostApi,
-        config: OcspConfig,
+        ctx: OcspContext,
     }
     unsafe impl Send for PluginState {}
     unsafe impl Sync for PluginState {}
@@ -396,10 +632,18 @@ pub mod plugin {
                 return std::ptr::null_mut();
             }
         };
+        let ctx = match OcspContext::new(config) {
+            Ok(c) => c,
+            Err(e) => {
+                log(&api, std::ptr::null_mut(), OX_LOG_ERROR,
+                    &format!("ox_cert_ocsp: init failed: {}", e));
+                return std::ptr::null_mut();
+            }
+        };
         log(&api, std::ptr::null_mut(), OX_LOG_INFO,
             &format!("ox_cert_ocsp: initialized for tenant '{}', responder key '{}'",
-                config.tenant_id, config.responder_key_id));
-        Box::into_raw(Box::new(PluginState { api, config })) as *mut c_void
+                ctx.config.tenant_id, ctx.config.responder_key_id));
+        Box::into_raw(Box::new(PluginState { api, ctx })) as *mut c_void
     }
 
     #[unsafe(no_mangle)]
@@ -455,7 +699,7 @@ pub mod plugin {
                 ).unwrap_or_default()
             };
 
-            let outcome = handle_ocsp(&state.config, &request_der);
+            let outcome = handle_ocsp(&state.ctx, &request_der);
 
             set(&state.api, task_ctx, "response.status", &outcome.http_status.to_string());
             set(&state.api, task_ctx, "response.header.Content-Type", "application/ocsp-response");
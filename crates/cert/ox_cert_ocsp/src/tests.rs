@@ -0,0 +1,392 @@
+//! RFC 6960 / RFC 8954 conformance, driven by requests built with `yasna` and
+//! responses checked against the CA or delegated responder key.
+
+use crate::*;
+use rcgen::KeyPair;
+use ox_cert_core::model::{CertificateRecord, EnrollmentProtocol, RevocationReason};
+use serde_json::{json, Value};
+use uuid::Uuid;
+use x509_parser::der_parser::asn1_rs::BitString;
+use x509_parser::prelude::{AlgorithmIdentifier, FromDer, X509Certificate};
+
+struct Ca {
+    key: KeyPair,
+    params: rcgen::CertificateParams,
+    der: Vec<u8>,
+}
+
+/// A fresh EC intermediate CA whose key is `intermediate` in the keystore.
+fn make_ca(dir: &tempfile::TempDir) -> Ca {
+    let key_dir = dir.path().join("keys").join("t1");
+    std::fs::create_dir_all(&key_dir).unwrap();
+    let key = KeyPair::generate().unwrap();
+    let mut params = rcgen::CertificateParams::default();
+    params.distinguished_name.push(rcgen::DnType::CommonName, "Test Intermediate CA");
+    params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+    let cert = params.self_signed(&key).unwrap();
+    std::fs::write(key_dir.join("intermediate.key.pem"), key.serialize_pem()).unwrap();
+    std::fs::write(dir.path().join("ca.crt"), cert.pem()).unwrap();
+    Ca { key, params, der: cert.der().to_vec() }
+}
+
+fn make_config(dir: &tempfile::TempDir, overrides: Value) -> OcspConfig {
+    let mut config = json!({
+        "tenant_id": "t1",
+        "store": { "driver": "sqlite", "path": dir.path().join("cert.db").to_str().unwrap() },
+        "keystore": { "store_type": "software", "key_dir": dir.path().join("keys").to_str().unwrap() },
+        "responder_key_id": "intermediate",
+    });
+    for (k, v) in overrides.as_object().unwrap() {
+        config[k] = v.clone();
+    }
+    serde_json::from_value(config).unwrap()
+}
+
+fn make_ctx(dir: &tempfile::TempDir, overrides: Value) -> OcspContext {
+    OcspContext::new(make_config(dir, overrides)).unwrap()
+}
+
+fn ca_path(dir: &tempfile::TempDir) -> String {
+    dir.path().join("ca.crt").to_str().unwrap().to_string()
+}
+
+fn open_store(dir: &tempfile::TempDir) -> OxPersistenceCertStore {
+    OxPersistenceCertStore::open(dir.path().join("cert.db").to_str().unwrap()).unwrap()
+}
+
+/// Store an active certificate record under `serial`.
+fn store_record(dir: &tempfile::TempDir, serial: Uuid) {
+    let now = OffsetDateTime::now_utc();
+    open_store(dir).store_cert("t1", &CertificateRecord {
+        serial: serial.to_string(),
+        tenant_id: "t1".to_string(),
+        subject_cn: "host.example.com".to_string(),
+        subject_dn: "CN=host.example.com".to_string(),
+        sans: vec!["host.example.com".to_string()],
+        issuer_dn: "CN=Test Intermediate CA".to_string(),
+        not_before: now,
+        not_after: now + time::Duration::days(90),
+        key_type: "ecc-p256".to_string(),
+        profile: "standard".to_string(),
+        pem: String::new(),
+        csr_pem: None,
+        private_key_encrypted: None,
+        status: CertStatus::Active,
+        revoked_at: None,
+        revocation_reason: None,
+        scts: vec![],
+        policy_oids: vec![],
+        enrollment_protocol: Some(EnrollmentProtocol::Rest),
+        crl_shard: None,
+        created_at: now,
+    }).unwrap();
+}
+
+/// Issue a certificate for `key` from the CA with the given serial and EKUs.
+fn issue(ca: &Ca, key: &KeyPair, serial: Uuid, ekus: Vec<rcgen::ExtendedKeyUsagePurpose>) -> Vec<u8> {
+    let mut params = rcgen::CertificateParams::default();
+    params.distinguished_name.push(rcgen::DnType::CommonName, "OCSP Test");
+    params.serial_number = Some(rcgen::SerialNumber::from_slice(serial.as_bytes()));
+    params.extended_key_usages = ekus;
+    params.signed_by(key, &rcgen::Issuer::from_params(&ca.params, &ca.key)).unwrap().der().to_vec()
+}
+
+// ---------------------------------------------------------------------------
+// Local client
+// ---------------------------------------------------------------------------
+
+fn cert_id(serial: Uuid) -> Vec<u8> {
+    yasna::construct_der(|w| w.write_sequence(|w| {
+        w.next().write_sequence(|w| {
+            // id-sha1
+            w.next().write_oid(&yasna::models::ObjectIdentifier::from_slice(&[1, 3, 14, 3, 2, 26]));
+            w.next().write_null();
+        });
+        w.next().write_bytes(&[0x11; 20]);
+        w.next().write_bytes(&[0x22; 20]);
+        w.next().write_bigint_bytes(serial.as_bytes(), true);
+    }))
+}
+
+fn nonce_ext(nonce: &[u8]) -> Vec<u8> {
+    yasna::construct_der(|w| w.write_sequence(|w| {
+        w.next().write_oid(&yasna::models::ObjectIdentifier::from_slice(OID_OCSP_NONCE));
+        w.next().write_bytes(&yasna::construct_der(|w| w.write_bytes(nonce)));
+    }))
+}
+
+/// An `OCSPRequest` for `ids`, optionally with a nonce and signed by `signer`.
+fn request(ids: &[Vec<u8>], nonce: Option<&[u8]>, signer: Option<(&KeyPair, &[u8])>) -> Vec<u8> {
+    let tbs = yasna::construct_der(|w| w.write_sequence(|w| {
+        w.next().write_sequence_of(|w| {
+            for id in ids {
+                w.next().write_sequence(|w| w.next().write_der(id));
+            }
+        });
+        if let Some(n) = nonce {
+            w.next().write_tagged(Tag::context(2), |w| {
+                w.write_sequence(|w| w.next().write_der(&nonce_ext(n)))
+            });
+        }
+    }));
+    yasna::construct_der(|w| w.write_sequence(|w| {
+        w.next().write_der(&tbs);
+        if let Some((key, cert)) = signer {
+            let sig = key.sign(&tbs).unwrap();
+            w.next().write_tagged(Tag::context(0), |w| w.write_sequence(|w| {
+                w.next().write_der(&sig_alg_der(key));
+                w.next().write_bitvec_bytes(&sig, sig.len() * 8);
+                w.next().write_tagged(Tag::context(0), |w| {
+                    w.write_sequence(|w| w.next().write_der(cert))
+                });
+            }));
+        }
+    }))
+}
+
+struct Response {
+    status: i64,
+    tbs: Vec<u8>,
+    sig_alg: Vec<u8>,
+    signature: Vec<u8>,
+    certs: Vec<Vec<u8>>,
+    /// (CertID DER, certStatus tag) per SingleResponse.
+    singles: Vec<(Vec<u8>, u8)>,
+    extensions: Vec<Vec<u8>>,
+}
+
+fn parse_response(der: &[u8]) -> Response {
+    let (status, basic) = yasna::parse_der(der, |r| r.read_sequence(|r| {
+        let status = r.next().read_enum()?;
+        let basic = r.read_optional(|r| r.read_tagged(Tag::context(0), |r| r.read_sequence(|r| {
+            r.next().read_oid()?;
+            r.next().read_bytes()
+        })))?;
+        Ok((status, basic))
+    })).unwrap();
+    let mut resp = Response {
+        status, tbs: vec![], sig_alg: vec![], signature: vec![], certs: vec![],
+        singles: vec![], extensions: vec![],
+    };
+    let Some(basic) = basic else { return resp };
+
+    (resp.tbs, resp.sig_alg, resp.signature, resp.certs) = yasna::parse_der(&basic, |r| r.read_sequence(|r| {
+        let tbs = r.next().read_der()?;
+        let alg = r.next().read_der()?;
+        let (sig, _) = r.next().read_bitvec_bytes()?;
+        let certs = r.read_optional(|r| r.read_tagged(Tag::context(0), |r| {
+            r.collect_sequence_of(|r| r.read_der())
+        }))?.unwrap_or_default();
+        Ok((tbs, alg, sig, certs))
+    })).unwrap();
+
+    (resp.singles, resp.extensions) = yasna::parse_der(&resp.tbs, |r| r.read_sequence(|r| {
+        r.next().read_der()?; // responderID
+        r.next().read_der()?;
+        let singles = r.next().collect_sequence_of(|r| r.read_sequence(|r| {
+            let id = r.next().read_der()?;
+            let status = r.next().read_der()?;
+            r.next().read_der()?;
+            r.read_optional(|r| r.read_tagged(Tag::context(0), |r| r.read_der()))?;
+            Ok((id, status[0]))
+        }))?;
+        let extensions = r.read_optional(|r| r.read_tagged(Tag::context(1), |r| {
+            r.collect_sequence_of(|r| r.read_der())
+        }))?.unwrap_or_default();
+        Ok((singles, extensions))
+    })).unwrap();
+    resp
+}
+
+/// Check the response signature with the key of `signer_der`.
+fn verify_response(resp: &Response, signer_der: &[u8]) {
+    let (_, signer) = X509Certificate::from_der(signer_der).unwrap();
+    let (_, alg) = AlgorithmIdentifier::from_der(&resp.sig_alg).unwrap();
+    x509_parser::verify::verify_signature(
+        signer.public_key(), &alg, &BitString::new(0, &resp.signature), &resp.tbs,
+    ).expect("response signature");
+}
+
+const GOOD: u8 = 0x80;
+const REVOKED: u8 = 0xa1;
+const UNKNOWN: u8 = 0x82;
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[test]
+fn batch_request_answers_every_cert_id() {
+    let dir = tempfile::tempdir().unwrap();
+    let ca = make_ca(&dir);
+    let ctx = make_ctx(&dir, json!({}));
+    let (good, revoked, unknown) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    store_record(&dir, good);
+    store_record(&dir, revoked);
+    open_store(&dir).mark_revoked("t1", &revoked.to_string(), RevocationReason::KeyCompromise,
+        OffsetDateTime::now_utc()).unwrap();
+
+    let ids = [cert_id(good), cert_id(revoked), cert_id(unknown)];
+    let outcome = handle_ocsp(&ctx, &request(&ids, None, None));
+    assert_eq!(outcome.max_age_secs, 3600);
+    let resp = parse_response(&outcome.body);
+    assert_eq!(resp.status, 0);
+    assert_eq!(resp.singles, vec![
+        (ids[0].clone(), GOOD), (ids[1].clone(), REVOKED), (ids[2].clone(), UNKNOWN),
+    ]);
+    assert!(resp.certs.is_empty(), "CA-signed responses carry no certs");
+    assert!(resp.extensions.is_empty());
+    verify_response(&resp, &ca.der);
+
+    let garbage = handle_ocsp(&ctx, &[0x30, 0x03, 0x02, 0x01, 0x00]);
+    assert_eq!(parse_response(&garbage.body).status, STATUS_MALFORMED_REQUEST as i64);
+    assert_eq!(garbage.max_age_secs, 0);
+}
+
+#[test]
+fn nonce_is_echoed_and_checked() {
+    let dir = tempfile::tempdir().unwrap();
+    make_ca(&dir);
+    let ctx = make_ctx(&dir, json!({}));
+    let serial = Uuid::new_v4();
+    store_record(&dir, serial);
+    let ids = [cert_id(serial)];
+
+    let nonce = [0x5a; 16];
+    let resp = parse_response(&handle_ocsp(&ctx, &request(&ids, Some(&nonce), None)).body);
+    assert_eq!(resp.status, 0);
+    assert_eq!(resp.extensions, vec![nonce_ext(&nonce)]);
+
+    for bad in [&[][..], &[0x5a; 33][..]] {
+        let resp = parse_response(&handle_ocsp(&ctx, &request(&ids, Some(bad), None)).body);
+        assert_eq!(resp.status, STATUS_MALFORMED_REQUEST as i64, "nonce of {} bytes", bad.len());
+    }
+
+    let ctx = make_ctx(&dir, json!({ "include_nonce": false }));
+    let resp = parse_response(&handle_ocsp(&ctx, &request(&ids, Some(&nonce), None)).body);
+    assert_eq!(resp.status, 0);
+    assert!(resp.extensions.is_empty());
+}
+
+#[test]
+fn signed_requests_are_enforced() {
+    let dir = tempfile::tempdir().unwrap();
+    let ca = make_ca(&dir);
+    assert!(OcspContext::new(make_config(&dir, json!({ "require_signed_requests": true }))).is_err(),
+        "signature checks need the CA certificate");
+    let ctx = make_ctx(&dir, json!({ "require_signed_requests": true, "ca_cert_path": ca_path(&dir) }));
+    let serial = Uuid::new_v4();
+    store_record(&dir, serial);
+    let ids = [cert_id(serial)];
+
+    let status = |req: Vec<u8>| parse_response(&handle_ocsp(&ctx, &req).body).status;
+    assert_eq!(status(request(&ids, None, None)), STATUS_SIG_REQUIRED as i64);
+
+    let client_key = KeyPair::generate().unwrap();
+    let client_serial = Uuid::new_v4();
+    let client_cert = issue(&ca, &client_key, client_serial, vec![]);
+    assert_eq!(status(request(&ids, None, Some((&client_key, &client_cert)))), 0);
+
+    // Signed by a key other than the certificate's
+    let other_key = KeyPair::generate().unwrap();
+    assert_eq!(status(request(&ids, None, Some((&other_key, &client_cert)))), STATUS_UNAUTHORIZED as i64);
+
+    // Certificate not issued by the CA
+    let mut params = rcgen::CertificateParams::default();
+    params.distinguished_name.push(rcgen::DnType::CommonName, "Stranger");
+    let stranger = params.self_signed(&other_key).unwrap().der().to_vec();
+    assert_eq!(status(request(&ids, None, Some((&other_key, &stranger)))), STATUS_UNAUTHORIZED as i64);
+
+    // Revoked signer
+    store_record(&dir, client_serial);
+    open_store(&dir).mark_revoked("t1", &client_serial.to_string(), RevocationReason::KeyCompromise,
+        OffsetDateTime::now_utc()).unwrap();
+    assert_eq!(status(request(&ids, None, Some((&client_key, &client_cert)))), STATUS_UNAUTHORIZED as i64);
+}
+
+#[test]
+fn delegated_responder_cert_is_included() {
+    let dir = tempfile::tempdir().unwrap();
+    let ca = make_ca(&dir);
+    let responder_key = KeyPair::generate().unwrap();
+    std::fs::write(dir.path().join("keys/t1/ocsp.key.pem"), responder_key.serialize_pem()).unwrap();
+    let write_cert = |name: &str, der: &[u8]| {
+        let path = dir.path().join(name);
+        std::fs::write(&path, ::pem::encode(&::pem::Pem::new("CERTIFICATE", der.to_vec()))).unwrap();
+        path.to_str().unwrap().to_string()
+    };
+    let delegated = issue(&ca, &responder_key, Uuid::new_v4(), vec![rcgen::ExtendedKeyUsagePurpose::OcspSigning]);
+    let delegated_path = write_cert("ocsp.crt", &delegated);
+
+    let ctx = make_ctx(&dir, json!({
+        "responder_key_id": "ocsp",
+        "delegated_cert_path": delegated_path,
+        "ca_cert_path": ca_path(&dir),
+    }));
+    let serial = Uuid::new_v4();
+    store_record(&dir, serial);
+    let resp = parse_response(&handle_ocsp(&ctx, &request(&[cert_id(serial)], None, None)).body);
+    assert_eq!(resp.status, 0);
+    assert_eq!(resp.certs, vec![delegated.clone()]);
+    verify_response(&resp, &delegated);
+
+    // Without id-kp-OCSPSigning
+    let no_eku = issue(&ca, &responder_key, Uuid::new_v4(), vec![rcgen::ExtendedKeyUsagePurpose::ClientAuth]);
+    let config = make_config(&dir, json!({
+        "responder_key_id": "ocsp", "delegated_cert_path": write_cert("no-eku.crt", &no_eku),
+    }));
+    assert!(OcspContext::new(config).is_err());
+
+    // For a different key
+    let config = make_config(&dir, json!({ "delegated_cert_path": delegated_path }));
+    assert!(OcspContext::new(config).is_err());
+}
+
+#[test]
+fn cached_response_is_reused_until_revoked() {
+    let dir = tempfile::tempdir().unwrap();
+    make_ca(&dir);
+    assert!(OcspContext::new(make_config(&dir, json!({ "cache_secs": 90000 }))).is_err(),
+        "cached responses must not outlive nextUpdate");
+    let ctx = make_ctx(&dir, json!({ "cache_secs": 600 }));
+    let serial = Uuid::new_v4();
+    store_record(&dir, serial);
+    let ids = [cert_id(serial)];
+    let store = open_store(&dir);
+
+    let first = handle_ocsp(&ctx, &request(&ids, None, None));
+    assert_eq!(parse_response(&first.body).singles[0].1, GOOD);
+    let cached = store.get_ocsp_response("t1", &serial.to_string()).unwrap().expect("response cached");
+    assert_eq!(cached.der, first.body);
+
+    // Mark the cached copy so a cache hit is visible
+    let marker = OcspCachedResponse { der: b"cached".to_vec(), ..cached };
+    assert!(store.store_ocsp_response("t1", &marker).unwrap());
+    let hit = handle_ocsp(&ctx, &request(&ids, None, None));
+    assert_eq!(hit.body, b"cached");
+    assert!(hit.max_age_secs <= 600);
+
+    // Nonce requests and other CertIDs for the serial are signed fresh
+    let with_nonce = handle_ocsp(&ctx, &request(&ids, Some(&[1; 8]), None));
+    assert_eq!(parse_response(&with_nonce.body).status, 0);
+    let mut other_id = ids[0].clone();
+    let hash_at = 2 + 11 + 2; // first issuerNameHash byte, after the SHA-1 AlgorithmIdentifier
+    assert_eq!(other_id[hash_at], 0x11);
+    other_id[hash_at] = 0x33;
+    assert_ne!(handle_ocsp(&ctx, &request(&[other_id], None, None)).body, b"cached");
+
+    store.mark_revoked("t1", &serial.to_string(), RevocationReason::KeyCompromise,
+        OffsetDateTime::now_utc()).unwrap();
+    let after = handle_ocsp(&ctx, &request(&ids, None, None));
+    assert_eq!(parse_response(&after.body).singles[0].1, REVOKED);
+
+    // A Good response signed before the revoke loses the race to cache it
+    assert!(!store.store_ocsp_response("t1", &marker).unwrap());
+    let again = handle_ocsp(&ctx, &request(&ids, None, None));
+    assert_eq!(again.body, after.body, "the revoked response is served from cache");
+
+    // Unknown serials are not cached
+    let unknown = Uuid::new_v4();
+    handle_ocsp(&ctx, &request(&[cert_id(unknown)], None, None));
+    assert!(store.get_ocsp_response("t1", &unknown.to_string()).unwrap().is_none());
+}
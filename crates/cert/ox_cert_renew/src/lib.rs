@@ -1,12 +1,13 @@
 use ox_cert_core::{
     issuer_params_from_cert_pem,
     model::{
-        AuditAction, AuditEvent, CertStatus, CertStoreConfig,
+        AuditAction, AuditEvent, CertStatus, CertStoreConfig, CrlPartitionConfig, CtConfig,
         KeyStoreConfig, RevocationReason, SanType,
     },
     store::{CertStore, OxPersistenceCertStore},
-    open_keystore,
+    ct, open_keystore,
     sign_csr,
+    CertError, IssuanceOptions, KeyStoreSigner,
 };
 use serde::Deserialize;
 use time::OffsetDateTime;
@@ -27,8 +28,13 @@ pub struct CertRenewConfig {
     pub auto_revoke_on_renew: bool,
     pub ca_intermediate_key_id: String,
     pub ca_intermediate_cert_path: String,
-    #[allow(dead_code)]
     pub ca_root_cert_path: String,
+    /// Certificate Transparency for renewed certificates.
+    #[serde(default)]
+    pub ct: Option<CtConfig>,
+    /// Partitioned CRLs for renewed certificates; must match `ox_cert_crl`.
+    #[serde(default)]
+    pub crl_partitions: Option<CrlPartitionConfig>,
     #[allow(dead_code)]
     pub extensions: ExtensionsConfig,
 }
@@ -98,22 +104,30 @@ pub fn handle_renew(config: &CertRenewConfig, serial: &str, body: &str) -> Renew
         Ok(k) => k,
         Err(e) => err!(503, "CA_NOT_READY", e.to_string()),
     };
-    let ca_key_pem = match ks.load_key_pem(tenant, &config.ca_intermediate_key_id) {
-        Ok(p) => p,
+    let ca_keypair = match KeyStoreSigner::new(ks.as_ref(), tenant, &config.ca_intermediate_key_id) {
+        Ok(k) => k,
         Err(e) => err!(503, "CA_NOT_READY", e.to_string()),
     };
-    let ca_keypair = match rcgen::KeyPair::from_pem(&ca_key_pem) {
-        Ok(k) => k,
+
+    let ct_chain = match ct::issuer_chain(config.ct.as_ref(), &ca_cert_pem, &config.ca_root_cert_path) {
+        Ok(c) => c,
         Err(e) => err!(503, "CA_NOT_READY", e.to_string()),
     };
+    let options = IssuanceOptions {
+        ct: config.ct.as_ref(),
+        ct_issuer_chain: &ct_chain,
+        crl_partitions: config.crl_partitions.as_ref(),
+        ..Default::default()
+    };
 
     let new_serial = Uuid::new_v4().to_string();
 
     // Build new cert: use provided CSR or synthesize from existing cert fields
     let new_record = if let Some(csr_pem) = &req.csr {
         // Sign the provided CSR
-        match sign_csr(csr_pem, tenant, &existing.profile, validity_secs, None, &issuer_params, &ca_keypair) {
+        match sign_csr(csr_pem, tenant, &existing.profile, validity_secs, None, &issuer_params, &ca_keypair, &options) {
             Ok(mut r) => { r.serial = new_serial.clone(); r }
+            Err(CertError::CtFailure(msg)) => err!(502, "CT_FAILURE", msg),
             Err(e) => err!(400, "INVALID_CSR", e.to_string()),
         }
     } else {
@@ -125,8 +139,9 @@ pub fn handle_renew(config: &CertRenewConfig, serial: &str, body: &str) -> Renew
         let override_sans: Vec<SanType> = existing.sans.iter()
             .filter_map(|s| san_from_str(s))
             .collect();
-        match sign_csr(&csr_pem, tenant, &existing.profile, validity_secs, Some(&override_sans), &issuer_params, &ca_keypair) {
+        match sign_csr(&csr_pem, tenant, &existing.profile, validity_secs, Some(&override_sans), &issuer_params, &ca_keypair, &options) {
             Ok(mut r) => { r.serial = new_serial.clone(); r }
+            Err(CertError::CtFailure(msg)) => err!(502, "CT_FAILURE", msg),
             Err(e) => err!(400, "INVALID_CSR", e.to_string()),
         }
     };
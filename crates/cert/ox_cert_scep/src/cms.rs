@@ -0,0 +1,621 @@
+//! The subset of CMS (RFC 5652) that SCEP (RFC 8894) needs: RSA-signed
+//! SignedData with signed attributes, EnvelopedData with RSA key transport,
+//! and degenerate certs-only SignedData. Parsing accepts BER because device
+//! clients do not all emit DER; everything written is DER.
+
+use cbc::cipher::block_padding::Pkcs7;
+use cbc::cipher::{BlockCipher, BlockDecryptMut, BlockEncryptMut, KeyInit, KeyIvInit};
+use rand::RngCore;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{Pkcs1v15Encrypt, Pkcs1v15Sign, RsaPrivateKey, RsaPublicKey};
+use yasna::models::{ObjectIdentifier, TaggedDerValue};
+use yasna::tags::TAG_INTEGER;
+use yasna::{ASN1Error, ASN1ErrorKind, ASN1Result, BERReader, Tag};
+
+pub mod oid {
+    pub const DATA: &[u64] = &[1, 2, 840, 113549, 1, 7, 1];
+    pub const SIGNED_DATA: &[u64] = &[1, 2, 840, 113549, 1, 7, 2];
+    pub const ENVELOPED_DATA: &[u64] = &[1, 2, 840, 113549, 1, 7, 3];
+
+    pub const CONTENT_TYPE: &[u64] = &[1, 2, 840, 113549, 1, 9, 3];
+    pub const MESSAGE_DIGEST: &[u64] = &[1, 2, 840, 113549, 1, 9, 4];
+    pub const CHALLENGE_PASSWORD: &[u64] = &[1, 2, 840, 113549, 1, 9, 7];
+
+    pub const RSA_ENCRYPTION: &[u64] = &[1, 2, 840, 113549, 1, 1, 1];
+    pub const SHA1_WITH_RSA: &[u64] = &[1, 2, 840, 113549, 1, 1, 5];
+    pub const SHA256_WITH_RSA: &[u64] = &[1, 2, 840, 113549, 1, 1, 11];
+    pub const SHA384_WITH_RSA: &[u64] = &[1, 2, 840, 113549, 1, 1, 12];
+    pub const SHA512_WITH_RSA: &[u64] = &[1, 2, 840, 113549, 1, 1, 13];
+
+    pub const SHA1: &[u64] = &[1, 3, 14, 3, 2, 26];
+    pub const SHA256: &[u64] = &[2, 16, 840, 1, 101, 3, 4, 2, 1];
+    pub const SHA384: &[u64] = &[2, 16, 840, 1, 101, 3, 4, 2, 2];
+    pub const SHA512: &[u64] = &[2, 16, 840, 1, 101, 3, 4, 2, 3];
+
+    pub const DES_EDE3_CBC: &[u64] = &[1, 2, 840, 113549, 3, 7];
+    pub const AES128_CBC: &[u64] = &[2, 16, 840, 1, 101, 3, 4, 1, 2];
+    pub const AES192_CBC: &[u64] = &[2, 16, 840, 1, 101, 3, 4, 1, 22];
+    pub const AES256_CBC: &[u64] = &[2, 16, 840, 1, 101, 3, 4, 1, 42];
+
+    // SCEP signed attributes (RFC 8894 §3.2.1)
+    pub const SCEP_MESSAGE_TYPE: &[u64] = &[2, 16, 840, 1, 113733, 1, 9, 2];
+    pub const SCEP_PKI_STATUS: &[u64] = &[2, 16, 840, 1, 113733, 1, 9, 3];
+    pub const SCEP_FAIL_INFO: &[u64] = &[2, 16, 840, 1, 113733, 1, 9, 4];
+    pub const SCEP_SENDER_NONCE: &[u64] = &[2, 16, 840, 1, 113733, 1, 9, 5];
+    pub const SCEP_RECIPIENT_NONCE: &[u64] = &[2, 16, 840, 1, 113733, 1, 9, 6];
+    pub const SCEP_TRANSACTION_ID: &[u64] = &[2, 16, 840, 1, 113733, 1, 9, 7];
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CmsError(pub String);
+
+impl std::fmt::Display for CmsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+fn err(msg: &str) -> CmsError {
+    CmsError(msg.to_string())
+}
+
+fn invalid() -> ASN1Error {
+    ASN1Error::new(ASN1ErrorKind::Invalid)
+}
+
+fn is(oid: &ObjectIdentifier, expected: &[u64]) -> bool {
+    oid.components().as_slice() == expected
+}
+
+// ---------------------------------------------------------------------------
+// Algorithms
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestAlgorithm {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl DigestAlgorithm {
+    fn from_oid(oid: &ObjectIdentifier) -> Option<Self> {
+        [Self::Sha1, Self::Sha256, Self::Sha384, Self::Sha512].into_iter()
+            .find(|d| is(oid, d.oid()))
+    }
+
+    pub fn oid(self) -> &'static [u64] {
+        match self {
+            Self::Sha1 => oid::SHA1,
+            Self::Sha256 => oid::SHA256,
+            Self::Sha384 => oid::SHA384,
+            Self::Sha512 => oid::SHA512,
+        }
+    }
+
+    pub fn digest(self, data: &[u8]) -> Vec<u8> {
+        use sha2::Digest;
+        match self {
+            Self::Sha1 => sha1::Sha1::digest(data).to_vec(),
+            Self::Sha256 => sha2::Sha256::digest(data).to_vec(),
+            Self::Sha384 => sha2::Sha384::digest(data).to_vec(),
+            Self::Sha512 => sha2::Sha512::digest(data).to_vec(),
+        }
+    }
+
+    fn pkcs1v15(self) -> Pkcs1v15Sign {
+        match self {
+            Self::Sha1 => Pkcs1v15Sign::new::<sha1::Sha1>(),
+            Self::Sha256 => Pkcs1v15Sign::new::<sha2::Sha256>(),
+            Self::Sha384 => Pkcs1v15Sign::new::<sha2::Sha384>(),
+            Self::Sha512 => Pkcs1v15Sign::new::<sha2::Sha512>(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncryption {
+    DesEde3Cbc,
+    Aes128Cbc,
+    Aes192Cbc,
+    Aes256Cbc,
+}
+
+impl ContentEncryption {
+    fn from_oid(oid: &ObjectIdentifier) -> Option<Self> {
+        [Self::DesEde3Cbc, Self::Aes128Cbc, Self::Aes192Cbc, Self::Aes256Cbc].into_iter()
+            .find(|c| is(oid, c.oid()))
+    }
+
+    pub fn oid(self) -> &'static [u64] {
+        match self {
+            Self::DesEde3Cbc => oid::DES_EDE3_CBC,
+            Self::Aes128Cbc => oid::AES128_CBC,
+            Self::Aes192Cbc => oid::AES192_CBC,
+            Self::Aes256Cbc => oid::AES256_CBC,
+        }
+    }
+
+    fn key_len(self) -> usize {
+        match self {
+            Self::DesEde3Cbc | Self::Aes192Cbc => 24,
+            Self::Aes128Cbc => 16,
+            Self::Aes256Cbc => 32,
+        }
+    }
+
+    fn iv_len(self) -> usize {
+        match self {
+            Self::DesEde3Cbc => 8,
+            _ => 16,
+        }
+    }
+
+    fn encrypt(self, key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>, CmsError> {
+        match self {
+            Self::DesEde3Cbc => cbc_encrypt::<des::TdesEde3>(key, iv, data),
+            Self::Aes128Cbc => cbc_encrypt::<aes::Aes128>(key, iv, data),
+            Self::Aes192Cbc => cbc_encrypt::<aes::Aes192>(key, iv, data),
+            Self::Aes256Cbc => cbc_encrypt::<aes::Aes256>(key, iv, data),
+        }
+    }
+
+    fn decrypt(self, key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>, CmsError> {
+        match self {
+            Self::DesEde3Cbc => cbc_decrypt::<des::TdesEde3>(key, iv, data),
+            Self::Aes128Cbc => cbc_decrypt::<aes::Aes128>(key, iv, data),
+            Self::Aes192Cbc => cbc_decrypt::<aes::Aes192>(key, iv, data),
+            Self::Aes256Cbc => cbc_decrypt::<aes::Aes256>(key, iv, data),
+        }
+    }
+}
+
+fn cbc_encrypt<C>(key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>, CmsError>
+where
+    C: BlockCipher + BlockEncryptMut + KeyInit,
+{
+    let enc = cbc::Encryptor::<C>::new_from_slices(key, iv)
+        .map_err(|_| err("bad content-encryption key or IV length"))?;
+    Ok(enc.encrypt_padded_vec_mut::<Pkcs7>(data))
+}
+
+fn cbc_decrypt<C>(key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>, CmsError>
+where
+    C: BlockCipher + BlockDecryptMut + KeyInit,
+{
+    let dec = cbc::Decryptor::<C>::new_from_slices(key, iv)
+        .map_err(|_| err("bad content-encryption key or IV length"))?;
+    dec.decrypt_padded_vec_mut::<Pkcs7>(data)
+        .map_err(|_| err("content decryption failed"))
+}
+
+fn read_algorithm(r: BERReader) -> ASN1Result<(ObjectIdentifier, Option<Vec<u8>>)> {
+    r.read_sequence(|r| {
+        let oid = r.next().read_oid()?;
+        let params = r.read_optional(|r| r.read_der())?;
+        Ok((oid, params))
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Certificates
+// ---------------------------------------------------------------------------
+
+/// DER `IssuerAndSerialNumber` identifying `cert_der`, as used in signer
+/// and recipient identifiers.
+pub fn issuer_and_serial(cert_der: &[u8]) -> Result<Vec<u8>, CmsError> {
+    use x509_parser::prelude::*;
+    let (_, cert) = X509Certificate::from_der(cert_der).map_err(|_| err("invalid certificate"))?;
+    let serial = TaggedDerValue::from_tag_and_bytes(TAG_INTEGER, cert.tbs_certificate.raw_serial().to_vec());
+    Ok(yasna::construct_der(|w| w.write_sequence(|w| {
+        w.next().write_der(cert.tbs_certificate.issuer.as_raw());
+        w.next().write_tagged_der(&serial);
+    })))
+}
+
+pub(crate) fn rsa_public_key(cert_der: &[u8]) -> Result<RsaPublicKey, CmsError> {
+    use x509_parser::prelude::*;
+    let (_, cert) = X509Certificate::from_der(cert_der).map_err(|_| err("invalid certificate"))?;
+    RsaPublicKey::from_public_key_der(cert.public_key().raw)
+        .map_err(|_| err("certificate key is not an RSA key"))
+}
+
+// ---------------------------------------------------------------------------
+// SignedData
+// ---------------------------------------------------------------------------
+
+/// A signed attribute with a single DER-encoded value.
+pub struct Attribute {
+    pub oid: &'static [u64],
+    pub value: Vec<u8>,
+}
+
+impl Attribute {
+    pub fn printable(oid: &'static [u64], s: &str) -> Self {
+        Self { oid, value: yasna::construct_der(|w| w.write_printable_string(s)) }
+    }
+
+    pub fn octets(oid: &'static [u64], b: &[u8]) -> Self {
+        Self { oid, value: yasna::construct_der(|w| w.write_bytes(b)) }
+    }
+}
+
+struct RawSignerInfo {
+    sid: Vec<u8>,
+    digest_oid: ObjectIdentifier,
+    signed_attrs: Vec<u8>,
+    attributes: Vec<(ObjectIdentifier, Vec<u8>)>,
+    signature_oid: ObjectIdentifier,
+    signature: Vec<u8>,
+}
+
+/// A parsed single-signer SignedData.
+pub struct SignedMessage {
+    /// eContent, absent for detached or degenerate messages.
+    pub content: Option<Vec<u8>>,
+    pub certificates: Vec<Vec<u8>>,
+    sid: Vec<u8>,
+    digest: DigestAlgorithm,
+    /// signedAttrs re-tagged as the DER SET OF that was signed.
+    signed_attrs: Vec<u8>,
+    attributes: Vec<(ObjectIdentifier, Vec<u8>)>,
+    signature: Vec<u8>,
+}
+
+type SignedDataParts = (Option<Vec<u8>>, Vec<Vec<u8>>, Vec<RawSignerInfo>);
+
+fn read_signed_data(der: &[u8]) -> Result<SignedDataParts, CmsError> {
+    yasna::parse_ber(der, |r| r.read_sequence(|r| {
+        if !is(&r.next().read_oid()?, oid::SIGNED_DATA) {
+            return Err(invalid());
+        }
+        r.next().read_tagged(Tag::context(0), |r| r.read_sequence(|r| {
+            r.next().read_u32()?;
+            r.next().read_set_of(|r| r.read_der().map(|_| ()))?;
+            let content = r.next().read_sequence(|r| {
+                r.next().read_oid()?;
+                r.read_optional(|r| r.read_tagged(Tag::context(0), |r| r.read_bytes()))
+            })?;
+            let certificates = r.read_optional(|r| {
+                r.read_tagged_implicit(Tag::context(0), |r| r.collect_set_of(|r| r.read_der()))
+            })?.unwrap_or_default();
+            r.read_optional(|r| {
+                r.read_tagged_implicit(Tag::context(1), |r| r.collect_set_of(|r| r.read_der()))
+            })?;
+            let signers = r.next().collect_set_of(read_signer_info)?;
+            Ok((content, certificates, signers))
+        }))
+    })).map_err(|_| err("message is not a CMS SignedData"))
+}
+
+/// The certificates of any SignedData, such as a certs-only bundle.
+pub fn certificates(der: &[u8]) -> Result<Vec<Vec<u8>>, CmsError> {
+    read_signed_data(der).map(|(_, certificates, _)| certificates)
+}
+
+pub fn parse_signed_data(der: &[u8]) -> Result<SignedMessage, CmsError> {
+    let (content, certificates, mut signers) = read_signed_data(der)?;
+    if signers.len() != 1 {
+        return Err(err("SignedData must have exactly one signer"));
+    }
+    let signer = signers.remove(0);
+    let digest = DigestAlgorithm::from_oid(&signer.digest_oid)
+        .ok_or_else(|| err("unsupported digest algorithm"))?;
+    let rsa_sig = [oid::RSA_ENCRYPTION, oid::SHA1_WITH_RSA, oid::SHA256_WITH_RSA,
+                   oid::SHA384_WITH_RSA, oid::SHA512_WITH_RSA];
+    if !rsa_sig.iter().any(|o| is(&signer.signature_oid, o)) {
+        return Err(err("unsupported signature algorithm"));
+    }
+    if signer.signed_attrs.is_empty() {
+        return Err(err("SignerInfo has no signed attributes"));
+    }
+
+    Ok(SignedMessage {
+        content,
+        certificates,
+        sid: signer.sid,
+        digest,
+        signed_attrs: signer.signed_attrs,
+        attributes: signer.attributes,
+        signature: signer.signature,
+    })
+}
+
+fn read_signer_info(r: BERReader) -> ASN1Result<RawSignerInfo> {
+    r.read_sequence(|r| {
+        r.next().read_u32()?;
+        let sid = r.next().read_der()?;
+        let (digest_oid, _) = read_algorithm(r.next())?;
+        let (attributes, raw) = r.read_with_buffer(|r| r.read_optional(|r| {
+            r.read_tagged_implicit(Tag::context(0), |r| r.collect_set_of(read_attribute))
+        }))?;
+        let (signature_oid, _) = read_algorithm(r.next())?;
+        let signature = r.next().read_bytes()?;
+        r.read_optional(|r| {
+            r.read_tagged_implicit(Tag::context(1), |r| r.collect_set_of(|r| r.read_der()))
+        })?;
+
+        // The signature covers the attributes with their universal SET tag (RFC 5652 §5.4)
+        let mut signed_attrs = raw.to_vec();
+        if let Some(tag) = signed_attrs.first_mut() {
+            *tag = 0x31;
+        }
+        Ok(RawSignerInfo {
+            sid,
+            digest_oid,
+            signed_attrs,
+            attributes: attributes.unwrap_or_default(),
+            signature_oid,
+            signature,
+        })
+    })
+}
+
+fn read_attribute(r: BERReader) -> ASN1Result<(ObjectIdentifier, Vec<u8>)> {
+    r.read_sequence(|r| {
+        let oid = r.next().read_oid()?;
+        let values = r.next().collect_set_of(|r| r.read_der())?;
+        let first = values.into_iter().next().ok_or_else(invalid)?;
+        Ok((oid, first))
+    })
+}
+
+impl SignedMessage {
+    fn attribute(&self, attr: &[u64]) -> Option<&[u8]> {
+        self.attributes.iter().find(|(o, _)| is(o, attr)).map(|(_, v)| v.as_slice())
+    }
+
+    /// A PrintableString attribute such as messageType or transactionID.
+    pub fn attribute_string(&self, attr: &[u64]) -> Option<String> {
+        let value = self.attribute(attr)?;
+        yasna::parse_ber(value, |r| r.read_printable_string()).ok()
+    }
+
+    /// An OCTET STRING attribute such as senderNonce.
+    pub fn attribute_octets(&self, attr: &[u64]) -> Option<Vec<u8>> {
+        let value = self.attribute(attr)?;
+        yasna::parse_ber(value, |r| r.read_bytes()).ok()
+    }
+
+    /// The included certificate named by the signer identifier.
+    pub fn signer_certificate(&self) -> Option<&[u8]> {
+        self.certificates.iter()
+            .find(|c| issuer_and_serial(c).is_ok_and(|id| id == self.sid))
+            .map(|c| c.as_slice())
+    }
+
+    /// Check the messageDigest attribute against the content and the
+    /// signature over the signed attributes against `cert_der`'s key.
+    pub fn verify(&self, cert_der: &[u8]) -> Result<(), CmsError> {
+        let message_digest = self.attribute_octets(oid::MESSAGE_DIGEST)
+            .ok_or_else(|| err("missing messageDigest attribute"))?;
+        let content = self.content.as_deref().unwrap_or_default();
+        if message_digest != self.digest.digest(content) {
+            return Err(err("messageDigest does not match the content"));
+        }
+        let key = rsa_public_key(cert_der)?;
+        let hashed = self.digest.digest(&self.signed_attrs);
+        key.verify(self.digest.pkcs1v15(), &hashed, &self.signature)
+            .map_err(|_| err("signature verification failed"))
+    }
+}
+
+/// An RSA key that signs SignedData and unwraps transported content-encryption
+/// keys. The SCEP RA key may be held in an HSM, so only these operations are used.
+pub trait RsaKey {
+    /// PKCS#1 v1.5 signature over `message` hashed with `digest`.
+    fn sign_pkcs1v15(&self, digest: DigestAlgorithm, message: &[u8]) -> Result<Vec<u8>, CmsError>;
+    /// PKCS#1 v1.5 key transport decryption.
+    fn decrypt_pkcs1v15(&self, ciphertext: &[u8]) -> Result<Vec<u8>, CmsError>;
+}
+
+impl RsaKey for RsaPrivateKey {
+    fn sign_pkcs1v15(&self, digest: DigestAlgorithm, message: &[u8]) -> Result<Vec<u8>, CmsError> {
+        self.sign(digest.pkcs1v15(), &digest.digest(message)).map_err(|_| err("signing failed"))
+    }
+
+    fn decrypt_pkcs1v15(&self, ciphertext: &[u8]) -> Result<Vec<u8>, CmsError> {
+        self.decrypt(Pkcs1v15Encrypt, ciphertext).map_err(|_| err("decryption failed"))
+    }
+}
+
+/// Sign `content` (absent for a detached message) as a SignedData whose
+/// signed attributes are contentType, messageDigest and `attributes`.
+pub fn build_signed_data(
+    content: Option<&[u8]>,
+    signer_cert: &[u8],
+    signer_key: &dyn RsaKey,
+    digest: DigestAlgorithm,
+    attributes: &[Attribute],
+) -> Result<Vec<u8>, CmsError> {
+    let sid = issuer_and_serial(signer_cert)?;
+    let mut attrs = vec![
+        Attribute { oid: oid::CONTENT_TYPE, value: yasna::construct_der(|w| {
+            w.write_oid(&ObjectIdentifier::from_slice(oid::DATA))
+        }) },
+        Attribute::octets(oid::MESSAGE_DIGEST, &digest.digest(content.unwrap_or_default())),
+    ];
+    attrs.extend(attributes.iter().map(|a| Attribute { oid: a.oid, value: a.value.clone() }));
+
+    // DER SET OF sorts its elements, so encode once and sign exactly these bytes
+    let mut signed_attrs = yasna::construct_der(|w| w.write_set_of(|w| {
+        for a in &attrs {
+            w.next().write_sequence(|w| {
+                w.next().write_oid(&ObjectIdentifier::from_slice(a.oid));
+                w.next().write_set_of(|w| w.next().write_der(&a.value));
+            });
+        }
+    }));
+    let signature = signer_key.sign_pkcs1v15(digest, &signed_attrs)?;
+    signed_attrs[0] = 0xA0;
+
+    Ok(yasna::construct_der(|w| w.write_sequence(|w| {
+        w.next().write_oid(&ObjectIdentifier::from_slice(oid::SIGNED_DATA));
+        w.next().write_tagged(Tag::context(0), |w| w.write_sequence(|w| {
+            w.next().write_u32(1);
+            w.next().write_set_of(|w| write_algorithm(w.next(), digest.oid(), false));
+            w.next().write_sequence(|w| {
+                w.next().write_oid(&ObjectIdentifier::from_slice(oid::DATA));
+                if let Some(c) = content {
+                    w.next().write_tagged(Tag::context(0), |w| w.write_bytes(c));
+                }
+            });
+            w.next().write_tagged_implicit(Tag::context(0), |w| w.write_set_of(|w| {
+                w.next().write_der(signer_cert);
+            }));
+            w.next().write_set_of(|w| w.next().write_sequence(|w| {
+                w.next().write_u32(1);
+                w.next().write_der(&sid);
+                write_algorithm(w.next(), digest.oid(), false);
+                w.next().write_der(&signed_attrs);
+                write_algorithm(w.next(), oid::RSA_ENCRYPTION, true);
+                w.next().write_bytes(&signature);
+            }));
+        }));
+    })))
+}
+
+/// A degenerate certs-only SignedData (RFC 5652 §5.2), as returned by
+/// GetCACert and inside a successful CertRep.
+pub fn certs_only(certs: &[Vec<u8>]) -> Vec<u8> {
+    yasna::construct_der(|w| w.write_sequence(|w| {
+        w.next().write_oid(&ObjectIdentifier::from_slice(oid::SIGNED_DATA));
+        w.next().write_tagged(Tag::context(0), |w| w.write_sequence(|w| {
+            w.next().write_u32(1);
+            w.next().write_set_of(|_| {});
+            w.next().write_sequence(|w| {
+                w.next().write_oid(&ObjectIdentifier::from_slice(oid::DATA));
+            });
+            w.next().write_tagged_implicit(Tag::context(0), |w| w.write_set_of(|w| {
+                for c in certs {
+                    w.next().write_der(c);
+                }
+            }));
+            w.next().write_set_of(|_| {});
+        }));
+    }))
+}
+
+fn write_algorithm(w: yasna::DERWriter, alg: &[u64], null_params: bool) {
+    w.write_sequence(|w| {
+        w.next().write_oid(&ObjectIdentifier::from_slice(alg));
+        if null_params {
+            w.next().write_null();
+        }
+    });
+}
+
+// ---------------------------------------------------------------------------
+// EnvelopedData
+// ---------------------------------------------------------------------------
+
+struct RawEnvelope {
+    encrypted_keys: Vec<Vec<u8>>,
+    algorithm: ObjectIdentifier,
+    params: Option<Vec<u8>>,
+    ciphertext: Vec<u8>,
+}
+
+/// Decrypt an EnvelopedData whose content-encryption key was transported
+/// to `key` with RSA PKCS#1 v1.5. A key transport that does not decrypt is
+/// not reported as such; decryption then fails on the content instead.
+pub fn decrypt_enveloped(der: &[u8], key: &dyn RsaKey) -> Result<Vec<u8>, CmsError> {
+    let env = yasna::parse_ber(der, |r| r.read_sequence(|r| {
+        if !is(&r.next().read_oid()?, oid::ENVELOPED_DATA) {
+            return Err(invalid());
+        }
+        r.next().read_tagged(Tag::context(0), |r| r.read_sequence(|r| {
+            r.next().read_u32()?;
+            r.read_optional(|r| r.read_tagged_implicit(Tag::context(0), |r| r.read_sequence(|r| {
+                while r.read_optional(|r| r.read_der())?.is_some() {}
+                Ok(())
+            })))?;
+            // Only key transport recipients (untagged SEQUENCE) apply to an RSA key
+            let recipients = r.next().collect_set_of(|r| {
+                if r.lookahead_tag()? != yasna::tags::TAG_SEQUENCE {
+                    r.read_der()?;
+                    return Ok(None);
+                }
+                r.read_sequence(|r| {
+                    r.next().read_u32()?;
+                    r.next().read_der()?;
+                    read_algorithm(r.next())?;
+                    r.next().read_bytes().map(Some)
+                })
+            })?;
+            let (algorithm, params, ciphertext) = r.next().read_sequence(|r| {
+                r.next().read_oid()?;
+                let (algorithm, params) = read_algorithm(r.next())?;
+                let ciphertext = r.next().read_tagged_implicit(Tag::context(0), |r| r.read_bytes())?;
+                Ok((algorithm, params, ciphertext))
+            })?;
+            r.read_optional(|r| {
+                r.read_tagged_implicit(Tag::context(1), |r| r.collect_set_of(|r| r.read_der()))
+            })?;
+            Ok(RawEnvelope {
+                encrypted_keys: recipients.into_iter().flatten().collect(),
+                algorithm,
+                params,
+                ciphertext,
+            })
+        }))
+    })).map_err(|_| err("message is not a CMS EnvelopedData"))?;
+
+    let cipher = ContentEncryption::from_oid(&env.algorithm)
+        .ok_or_else(|| err("unsupported content-encryption algorithm"))?;
+    let iv = env.params.as_deref()
+        .and_then(|p| yasna::parse_ber(p, |r| r.read_bytes()).ok())
+        .ok_or_else(|| err("missing content-encryption IV"))?;
+    // Implicit rejection against Bleichenbacher/Marvin oracles: a key transport
+    // that fails to decrypt, or yields a key of the wrong size, is replaced by a
+    // random CEK drawn up front, so a padding failure surfaces only as a content
+    // decryption failure
+    let mut random_cek = vec![0u8; cipher.key_len()];
+    rand::rngs::OsRng.fill_bytes(&mut random_cek);
+    let cek = env.encrypted_keys.iter()
+        .filter_map(|ek| key.decrypt_pkcs1v15(ek).ok())
+        .find(|k| k.len() == cipher.key_len())
+        .unwrap_or(random_cek);
+    cipher.decrypt(&cek, &iv, &env.ciphertext)
+}
+
+/// Encrypt `content` to the RSA key of `recipient_cert`.
+pub fn encrypt_enveloped(
+    content: &[u8],
+    recipient_cert: &[u8],
+    cipher: ContentEncryption,
+) -> Result<Vec<u8>, CmsError> {
+    let rid = issuer_and_serial(recipient_cert)?;
+    let recipient_key = rsa_public_key(recipient_cert)?;
+
+    let mut cek = vec![0u8; cipher.key_len()];
+    let mut iv = vec![0u8; cipher.iv_len()];
+    rand::rngs::OsRng.fill_bytes(&mut cek);
+    rand::rngs::OsRng.fill_bytes(&mut iv);
+    let ciphertext = cipher.encrypt(&cek, &iv, content)?;
+    let encrypted_key = recipient_key
+        .encrypt(&mut rand::rngs::OsRng, Pkcs1v15Encrypt, &cek)
+        .map_err(|_| err("key transport encryption failed"))?;
+
+    Ok(yasna::construct_der(|w| w.write_sequence(|w| {
+        w.next().write_oid(&ObjectIdentifier::from_slice(oid::ENVELOPED_DATA));
+        w.next().write_tagged(Tag::context(0), |w| w.write_sequence(|w| {
+            w.next().write_u32(0);
+            w.next().write_set_of(|w| w.next().write_sequence(|w| {
+                w.next().write_u32(0);
+                w.next().write_der(&rid);
+                write_algorithm(w.next(), oid::RSA_ENCRYPTION, true);
+                w.next().write_bytes(&encrypted_key);
+            }));
+            w.next().write_sequence(|w| {
+                w.next().write_oid(&ObjectIdentifier::from_slice(oid::DATA));
+                w.next().write_sequence(|w| {
+                    w.next().write_oid(&ObjectIdentifier::from_slice(cipher.oid()));
+                    w.next().write_bytes(&iv);
+                });
+                w.next().write_tagged_implicit(Tag::context(0), |w| w.write_bytes(&ciphertext));
+            });
+        }));
+    })))
+}
@@ -0,0 +1,760 @@
+// ox_cert_scep — Simple Certificate Enrollment Protocol (RFC 8894)
+
+pub mod cms;
+
+#[cfg(test)]
+mod tests;
+
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use cms::{oid, Attribute, CmsError, ContentEncryption, DigestAlgorithm};
+use ox_cert_core::{
+    builder::{issuer_params_from_cert_pem, parse_csr, sign_csr},
+    model::{AuditAction, AuditEvent, CertStatus, CertStoreConfig, CrlPartitionConfig, CtConfig, EnrollmentProtocol,
+            IssuancePolicy, IssuancePolicyConfig, KeyStoreConfig, KeyType, ScepTransaction,
+            SigningAlgorithm},
+    ct, open_keystore,
+    store::{CertStore, OxPersistenceCertStore},
+    CertError, IssuanceOptions, KeyStore, KeyStoreSigner,
+};
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::RsaPublicKey;
+use serde::Deserialize;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+// ---------------------------------------------------------------------------
+// Config
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Deserialize)]
+pub struct ScepConfig {
+    pub tenant_id: String,
+    pub store: CertStoreConfig,
+    pub keystore: KeyStoreConfig,
+    pub ca_intermediate_key_id: String,
+    pub ca_intermediate_cert_path: String,
+    pub ca_root_cert_path: String,
+    /// Profile recorded on issued certificates.
+    #[serde(default = "default_profile")]
+    pub profile: String,
+    #[serde(default = "default_validity_days")]
+    pub validity_days: u32,
+    pub policy: IssuancePolicyConfig,
+    /// Certificate Transparency for issued certificates.
+    #[serde(default)]
+    pub ct: Option<CtConfig>,
+    /// Partitioned CRLs for issued certificates; must match `ox_cert_crl`.
+    #[serde(default)]
+    pub crl_partitions: Option<CrlPartitionConfig>,
+    /// Symmetric encryption algorithm for CMS EnvelopedData responses.
+    #[serde(default)]
+    pub encryption_algorithm: ScepEncryptionAlgorithm,
+    /// Digest algorithm for SCEP response signatures.
+    #[serde(default)]
+    pub signing_algorithm: ScepSigningAlgorithm,
+    /// Key ID for the SCEP RA key (not the CA signing key).
+    /// Generated automatically on first init if missing.
+    #[serde(default = "default_encryption_key_id")]
+    pub encryption_key_id: String,
+    #[serde(default = "default_encryption_key_type")]
+    pub encryption_key_type: KeyType,
+    /// RA certificate for `encryption_key_id`. Issued by the intermediate CA
+    /// on first init if the file is missing.
+    pub encryption_cert_path: String,
+}
+
+fn default_profile() -> String { "scep".to_string() }
+fn default_validity_days() -> u32 { 365 }
+fn default_encryption_key_id() -> String { "scep-ra".to_string() }
+fn default_encryption_key_type() -> KeyType { KeyType::Rsa2048 }
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum ScepEncryptionAlgorithm {
+    #[default]
+    Aes256Cbc,
+    TripleDes,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum ScepSigningAlgorithm {
+    #[default]
+    Sha256,
+    Sha512,
+}
+
+/// Validity of an RA certificate issued at init.
+const RA_CERT_VALIDITY_DAYS: i64 = 730;
+
+const CA_CAPS: &str = "POSTPKIOperation\nRenewal\nSHA-256\nSHA-512\nAES\nDES3\nSCEPStandard\n";
+
+// SCEP messageType values (RFC 8894 §3.2.1.2)
+const MESSAGE_TYPE_CERT_REP: &str = "3";
+const MESSAGE_TYPE_RENEWAL_REQ: &str = "17";
+const MESSAGE_TYPE_PKCS_REQ: &str = "19";
+const MESSAGE_TYPE_CERT_POLL: &str = "20";
+
+/// SCEP failInfo values (RFC 8894 §3.2.1.4).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailInfo {
+    BadAlg = 0,
+    BadMessageCheck = 1,
+    BadRequest = 2,
+    BadTime = 3,
+    BadCertId = 4,
+}
+
+enum Rejection {
+    /// Answered with a signed CertRep FAILURE.
+    Fail(FailInfo),
+    /// Server-side problem, answered with a plain HTTP error.
+    Server(u16, String),
+}
+
+impl From<FailInfo> for Rejection {
+    fn from(f: FailInfo) -> Self { Rejection::Fail(f) }
+}
+
+// ---------------------------------------------------------------------------
+// Context
+// ---------------------------------------------------------------------------
+
+/// The RA key, used through the keystore so that it may be held in an HSM.
+struct RaKey {
+    ks: Box<dyn KeyStore>,
+    tenant_id: String,
+    key_id: String,
+}
+
+impl cms::RsaKey for RaKey {
+    fn sign_pkcs1v15(&self, digest: DigestAlgorithm, message: &[u8]) -> Result<Vec<u8>, CmsError> {
+        let algorithm = match digest {
+            DigestAlgorithm::Sha256 => SigningAlgorithm::Sha256WithRsa,
+            DigestAlgorithm::Sha384 => SigningAlgorithm::Sha384WithRsa,
+            DigestAlgorithm::Sha512 => SigningAlgorithm::Sha512WithRsa,
+            DigestAlgorithm::Sha1 => return Err(CmsError("the RA does not sign with SHA-1".to_string())),
+        };
+        self.ks.sign(&self.tenant_id, &self.key_id, algorithm, message)
+            .map_err(|e| CmsError(format!("signing failed: {}", e)))
+    }
+
+    fn decrypt_pkcs1v15(&self, ciphertext: &[u8]) -> Result<Vec<u8>, CmsError> {
+        self.ks.decrypt(&self.tenant_id, &self.key_id, ciphertext)
+            .map_err(|_| CmsError("decryption failed".to_string()))
+    }
+}
+
+pub struct ScepContext {
+    config: ScepConfig,
+    ra_key: RaKey,
+    ra_cert: Vec<u8>,
+}
+
+impl ScepContext {
+    /// Load the RA key and certificate, creating either if missing.
+    pub fn new(config: ScepConfig) -> Result<Self, CertError> {
+        let ks = open_keystore(&config.keystore)?;
+        let tenant = &config.tenant_id;
+        if !ks.key_exists(tenant, &config.encryption_key_id)? {
+            ks.generate_key(tenant, &config.encryption_key_id, config.encryption_key_type.clone(), false)?;
+        }
+        let not_rsa = || CertError::Crypto("SCEP encryption key must be an RSA key".to_string());
+        if !matches!(ks.key_info(tenant, &config.encryption_key_id)?.key_type,
+                     KeyType::Rsa2048 | KeyType::Rsa3072 | KeyType::Rsa4096) {
+            return Err(not_rsa());
+        }
+        let ra_public = RsaPublicKey::from_pkcs1_der(&ks.public_key(tenant, &config.encryption_key_id)?)
+            .map_err(|_| not_rsa())?;
+
+        if !std::path::Path::new(&config.encryption_cert_path).exists() {
+            let pem = issue_ra_cert(&config, ks.as_ref())?;
+            std::fs::write(&config.encryption_cert_path, pem)
+                .map_err(|e| CertError::Storage(format!("failed to write RA cert: {}", e)))?;
+        }
+        let ra_cert = read_cert_der(&config.encryption_cert_path)?;
+        // Clients encrypt to the certificate, so it must belong to the key we hold
+        if cms::rsa_public_key(&ra_cert).ok() != Some(ra_public) {
+            return Err(CertError::Validation(format!(
+                "{} does not match SCEP key '{}'", config.encryption_cert_path, config.encryption_key_id
+            )));
+        }
+        let ra_key = RaKey { ks, tenant_id: tenant.clone(), key_id: config.encryption_key_id.clone() };
+        Ok(Self { config, ra_key, ra_cert })
+    }
+
+    fn digest(&self) -> DigestAlgorithm {
+        match self.config.signing_algorithm {
+            ScepSigningAlgorithm::Sha256 => DigestAlgorithm::Sha256,
+            ScepSigningAlgorithm::Sha512 => DigestAlgorithm::Sha512,
+        }
+    }
+
+    fn cipher(&self) -> ContentEncryption {
+        match self.config.encryption_algorithm {
+            ScepEncryptionAlgorithm::Aes256Cbc => ContentEncryption::Aes256Cbc,
+            ScepEncryptionAlgorithm::TripleDes => ContentEncryption::DesEde3Cbc,
+        }
+    }
+}
+
+fn read_cert_der(path: &str) -> Result<Vec<u8>, CertError> {
+    let pem_str = std::fs::read_to_string(path)
+        .map_err(|e| CertError::Crypto(format!("failed to read {}: {}", path, e)))?;
+    ::pem::parse(pem_str.as_bytes())
+        .map(|p| p.into_contents())
+        .map_err(|e| CertError::Crypto(format!("invalid PEM in {}: {}", path, e)))
+}
+
+/// Issue the RA certificate from the intermediate CA. SCEP clients encrypt
+/// requests to it and verify responses with it.
+fn issue_ra_cert(config: &ScepConfig, ks: &dyn KeyStore) -> Result<String, CertError> {
+    use rcgen::{DnType, Issuer, KeyUsagePurpose};
+
+    let ca_cert_pem = std::fs::read_to_string(&config.ca_intermediate_cert_path)
+        .map_err(|e| CertError::Crypto(format!("CA cert unavailable: {}", e)))?;
+    let ca_params = issuer_params_from_cert_pem(&ca_cert_pem)?;
+    let ca_key = KeyStoreSigner::new(ks, &config.tenant_id, &config.ca_intermediate_key_id)?;
+    let ra_key = KeyStoreSigner::new(ks, &config.tenant_id, &config.encryption_key_id)?;
+
+    let now = OffsetDateTime::now_utc();
+    let mut params = rcgen::CertificateParams::default();
+    params.distinguished_name.push(DnType::CommonName, format!("{} SCEP RA", config.tenant_id));
+    params.key_usages = vec![KeyUsagePurpose::DigitalSignature, KeyUsagePurpose::KeyEncipherment];
+    params.not_before = now;
+    params.not_after = now + time::Duration::days(RA_CERT_VALIDITY_DAYS);
+    params.serial_number = Some(rcgen::SerialNumber::from_slice(Uuid::new_v4().as_bytes()));
+    params.use_authority_key_identifier_extension = true;
+
+    let cert = params.signed_by(&ra_key, &Issuer::from_params(&ca_params, &ca_key))
+        .map_err(|e| CertError::Crypto(format!("RA cert signing failed: {}", e)))?;
+    Ok(cert.pem())
+}
+
+// ---------------------------------------------------------------------------
+// Response type
+// ---------------------------------------------------------------------------
+
+pub struct ScepOutcome {
+    pub http_status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl ScepOutcome {
+    fn text(status: u16, msg: &str) -> Self {
+        Self { http_status: status, content_type: "text/plain", body: msg.as_bytes().to_vec() }
+    }
+
+    fn pki_message(body: Vec<u8>) -> Self {
+        Self { http_status: 200, content_type: "application/x-pki-message", body }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// Percent-decoded value of `name` in a raw query string. `+` is kept as is
+/// because GET PKIOperation messages are base64, not form-encoded.
+fn query_param(query: &str, name: &str) -> Option<String> {
+    let raw = query.split('&').find_map(|pair| {
+        let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+        (k == name).then_some(v)
+    })?;
+    let bytes = raw.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => { out.push(b); i += 3; }
+            (c, _) => { out.push(c); i += 1; }
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Decode base64, stripping whitespace first.
+fn decode_b64(s: &str) -> Option<Vec<u8>> {
+    let stripped: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+    B64.decode(stripped).ok()
+}
+
+fn der_to_csr_pem(der: &[u8]) -> String {
+    ::pem::encode(&::pem::Pem::new("CERTIFICATE REQUEST", der.to_vec()))
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    use sha2::Digest;
+    sha2::Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn challenge_password(csr: &x509_parser::certification_request::X509CertificationRequest) -> Option<String> {
+    use x509_parser::cri_attributes::ParsedCriAttribute;
+    csr.certification_request_info.iter_attributes().find_map(|a| match a.parsed_attribute() {
+        ParsedCriAttribute::ChallengePassword(p) => Some(p.0.clone()),
+        _ => None,
+    })
+}
+
+/// Whether the CSR asks for exactly the subject and SANs of the certificate.
+fn same_names(csr: &x509_parser::certification_request::X509CertificationRequest, cert_der: &[u8]) -> bool {
+    use x509_parser::prelude::*;
+
+    let Ok((_, cert)) = X509Certificate::from_der(cert_der) else { return false };
+    let mut csr_sans: Vec<String> = csr.requested_extensions().into_iter().flatten()
+        .filter_map(|ext| match ext {
+            ParsedExtension::SubjectAlternativeName(san) => Some(san.general_names.iter()),
+            _ => None,
+        })
+        .flatten()
+        .map(|name| name.to_string())
+        .collect();
+    let mut cert_sans: Vec<String> = match cert.subject_alternative_name() {
+        Ok(Some(san)) => san.value.general_names.iter().map(|name| name.to_string()).collect(),
+        Ok(None) => Vec::new(),
+        Err(_) => return false,
+    };
+    csr_sans.sort();
+    csr_sans.dedup();
+    cert_sans.sort();
+    cert_sans.dedup();
+    csr.certification_request_info.subject.to_string() == cert.subject().to_string() && csr_sans == cert_sans
+}
+
+fn spki_of_cert(der: &[u8]) -> Option<Vec<u8>> {
+    use x509_parser::prelude::*;
+    X509Certificate::from_der(der).ok().map(|(_, c)| c.public_key().raw.to_vec())
+}
+
+/// Store serial of a certificate issued by `sign_csr` (16-byte UUID serial).
+fn record_serial(der: &[u8]) -> Option<String> {
+    use x509_parser::prelude::*;
+    let (_, cert) = X509Certificate::from_der(der).ok()?;
+    let bytes: [u8; 16] = cert.serial.to_bytes_be().as_slice().try_into().ok()?;
+    Some(Uuid::from_bytes(bytes).to_string())
+}
+
+fn open_store(config: &ScepConfig) -> Result<OxPersistenceCertStore, Rejection> {
+    OxPersistenceCertStore::open(config.store.db_path())
+        .map_err(|e| Rejection::Server(500, format!("store error: {}", e)))
+}
+
+// ---------------------------------------------------------------------------
+// Operation handlers
+// ---------------------------------------------------------------------------
+
+fn handle_get_ca_cert(ctx: &ScepContext) -> ScepOutcome {
+    let mut certs = vec![ctx.ra_cert.clone()];
+    for path in [&ctx.config.ca_intermediate_cert_path, &ctx.config.ca_root_cert_path] {
+        match read_cert_der(path) {
+            Ok(der) if !certs.contains(&der) => certs.push(der),
+            Ok(_) => {}
+            Err(e) => return ScepOutcome::text(503, &format!("CA cert unavailable: {}", e)),
+        }
+    }
+    ScepOutcome {
+        http_status: 200,
+        content_type: "application/x-x509-ca-ra-cert",
+        body: cms::certs_only(&certs),
+    }
+}
+
+struct Transaction<'a> {
+    id: String,
+    sender_nonce: Vec<u8>,
+    message_type: String,
+    signer: &'a [u8],
+}
+
+fn handle_pki_operation(ctx: &ScepContext, der: &[u8]) -> ScepOutcome {
+    let msg = match cms::parse_signed_data(der) {
+        Ok(m) => m,
+        Err(e) => return ScepOutcome::text(400, &e.to_string()),
+    };
+    let (Some(id), Some(sender_nonce), Some(message_type)) = (
+        msg.attribute_string(oid::SCEP_TRANSACTION_ID),
+        msg.attribute_octets(oid::SCEP_SENDER_NONCE),
+        msg.attribute_string(oid::SCEP_MESSAGE_TYPE),
+    ) else {
+        return ScepOutcome::text(400, "missing transactionID, senderNonce or messageType");
+    };
+    let Some(signer) = msg.signer_certificate() else {
+        return ScepOutcome::text(400, "signer certificate not included in message");
+    };
+    let tx = Transaction { id, sender_nonce, message_type, signer };
+
+    let result = match msg.verify(signer) {
+        Err(_) => Err(Rejection::Fail(FailInfo::BadMessageCheck)),
+        Ok(()) => match tx.message_type.as_str() {
+            MESSAGE_TYPE_PKCS_REQ | MESSAGE_TYPE_RENEWAL_REQ => enroll(ctx, &tx, msg.content.as_deref()),
+            MESSAGE_TYPE_CERT_POLL => poll(ctx, &tx, msg.content.as_deref()),
+            _ => Err(Rejection::Fail(FailInfo::BadRequest)),
+        },
+    };
+
+    let reply = match result {
+        Ok(cert_der) => cms::encrypt_enveloped(&cms::certs_only(&[cert_der]), tx.signer, ctx.cipher())
+            .and_then(|env| cert_rep(ctx, &tx, Some(&env), None)),
+        Err(Rejection::Fail(fail)) => cert_rep(ctx, &tx, None, Some(fail)),
+        Err(Rejection::Server(status, msg)) => return ScepOutcome::text(status, &msg),
+    };
+    match reply {
+        Ok(body) => ScepOutcome::pki_message(body),
+        Err(e) => ScepOutcome::text(500, &format!("failed to build CertRep: {}", e)),
+    }
+}
+
+/// CertRep signed by the RA key. SUCCESS carries the enveloped certificate;
+/// FAILURE carries `fail_info` and no content.
+fn cert_rep(
+    ctx: &ScepContext,
+    tx: &Transaction,
+    envelope: Option<&[u8]>,
+    fail_info: Option<FailInfo>,
+) -> Result<Vec<u8>, cms::CmsError> {
+    let mut sender_nonce = [0u8; 16];
+    rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut sender_nonce);
+    let mut attrs = vec![
+        Attribute::printable(oid::SCEP_MESSAGE_TYPE, MESSAGE_TYPE_CERT_REP),
+        Attribute::printable(oid::SCEP_TRANSACTION_ID, &tx.id),
+        Attribute::octets(oid::SCEP_SENDER_NONCE, &sender_nonce),
+        Attribute::octets(oid::SCEP_RECIPIENT_NONCE, &tx.sender_nonce),
+    ];
+    match fail_info {
+        Some(fail) => {
+            attrs.push(Attribute::printable(oid::SCEP_PKI_STATUS, "2"));
+            attrs.push(Attribute::printable(oid::SCEP_FAIL_INFO, &(fail as u8).to_string()));
+        }
+        None => attrs.push(Attribute::printable(oid::SCEP_PKI_STATUS, "0")),
+    }
+    cms::build_signed_data(envelope, &ctx.ra_cert, &ctx.ra_key, ctx.digest(), &attrs)
+}
+
+/// PKCSReq and RenewalReq: decrypt the CSR, authorize it, issue and store
+/// the certificate.
+fn enroll(ctx: &ScepContext, tx: &Transaction, content: Option<&[u8]>) -> Result<Vec<u8>, Rejection> {
+    use x509_parser::prelude::*;
+
+    let config = &ctx.config;
+    let tenant = &config.tenant_id;
+    let renewal = tx.message_type == MESSAGE_TYPE_RENEWAL_REQ;
+
+    // Decryption and parsing fail alike, so the reply does not tell a
+    // tampered envelope apart from a bad key transport
+    let envelope = content.ok_or(FailInfo::BadRequest)?;
+    let csr_der = cms::decrypt_enveloped(envelope, &ctx.ra_key).map_err(|_| FailInfo::BadMessageCheck)?;
+    let (_, csr) = X509CertificationRequest::from_der(&csr_der).map_err(|_| FailInfo::BadMessageCheck)?;
+    let csr_pem = der_to_csr_pem(&csr_der);
+    let csr_info = parse_csr(&csr_pem).map_err(|_| FailInfo::BadMessageCheck)?;
+    let policy = IssuancePolicy::from_config(&config.policy)
+        .map_err(|e| Rejection::Server(500, format!("invalid policy: {}", e)))?;
+    policy.validate_csr(&csr_info).map_err(|_| FailInfo::BadRequest)?;
+
+    let store = open_store(config)?;
+    if renewal {
+        // Signed by a certificate this CA issued that is still valid
+        let record = record_serial(tx.signer)
+            .and_then(|serial| store.get_cert_by_serial(tenant, &serial).ok().flatten())
+            .ok_or(FailInfo::BadRequest)?;
+        let issued_der = ::pem::parse(record.pem.as_bytes()).map(|p| p.into_contents()).ok();
+        if issued_der.as_deref() != Some(tx.signer)
+            || record.status != CertStatus::Active
+            || record.not_after <= OffsetDateTime::now_utc()
+        {
+            return Err(FailInfo::BadRequest.into());
+        }
+        // A renewal re-certifies the names of the signing certificate, nothing more
+        if !same_names(&csr, tx.signer) {
+            return Err(FailInfo::BadRequest.into());
+        }
+    } else {
+        // Initial enrollment is signed with the key being certified
+        // (RFC 8894 §2.3) and authorized by a one-time challenge password
+        let csr_spki = csr.certification_request_info.subject_pki.raw;
+        if spki_of_cert(tx.signer).as_deref() != Some(csr_spki) {
+            return Err(FailInfo::BadRequest.into());
+        }
+        let password = challenge_password(&csr).ok_or(FailInfo::BadRequest)?;
+        let hash = format!("sha256:{}", sha256_hex(password.as_bytes()));
+        match store.consume_scep_challenge(tenant, &hash) {
+            Ok(true) => {}
+            Ok(false) => return Err(FailInfo::BadRequest.into()),
+            Err(e) => return Err(Rejection::Server(500, format!("store error: {}", e))),
+        }
+    }
+
+    let unavailable = |e: String| Rejection::Server(503, format!("CA not ready: {}", e));
+    let ca_cert_pem = std::fs::read_to_string(&config.ca_intermediate_cert_path)
+        .map_err(|e| unavailable(e.to_string()))?;
+    let ca_params = issuer_params_from_cert_pem(&ca_cert_pem).map_err(|e| unavailable(e.to_string()))?;
+    let ks = open_keystore(&config.keystore).map_err(|e| unavailable(e.to_string()))?;
+    let ca_key = KeyStoreSigner::new(ks.as_ref(), tenant, &config.ca_intermediate_key_id)
+        .map_err(|e| unavailable(e.to_string()))?;
+
+    let ct_chain = ct::issuer_chain(config.ct.as_ref(), &ca_cert_pem, &config.ca_root_cert_path)
+        .map_err(|e| unavailable(e.to_string()))?;
+    let options = IssuanceOptions {
+        ct: config.ct.as_ref(),
+        ct_issuer_chain: &ct_chain,
+        crl_partitions: config.crl_partitions.as_ref(),
+        ..Default::default()
+    };
+
+    let validity = config.validity_days as u64 * 86400;
+    let mut cert = sign_csr(&csr_pem, tenant, &config.profile, validity, None, &ca_params, &ca_key, &options)
+        .map_err(|e| match e {
+            CertError::CtFailure(msg) => Rejection::Server(500, format!("Certificate Transparency: {}", msg)),
+            _ => FailInfo::BadRequest.into(),
+        })?;
+    cert.enrollment_protocol = Some(EnrollmentProtocol::Scep);
+    store.store_cert(tenant, &cert)
+        .map_err(|e| Rejection::Server(500, format!("store error: {}", e)))?;
+    let requester_key_sha256 = spki_of_cert(tx.signer).map(|spki| sha256_hex(&spki)).unwrap_or_default();
+    store.store_scep_transaction(tenant, &ScepTransaction {
+        transaction_id: tx.id.clone(),
+        tenant_id: tenant.clone(),
+        serial: cert.serial.clone(),
+        requester_key_sha256,
+        created_at: OffsetDateTime::now_utc(),
+    }).map_err(|e| Rejection::Server(500, format!("store error: {}", e)))?;
+    let _ = store.store_audit_event(tenant, &AuditEvent {
+        id: 0,
+        tenant_id: tenant.clone(),
+        timestamp: OffsetDateTime::now_utc(),
+        action: AuditAction::ScepEnroll,
+        serial: Some(cert.serial.clone()),
+        actor: String::new(),
+        details: serde_json::json!({
+            "protocol": "scep",
+            "message_type": if renewal { "RenewalReq" } else { "PKCSReq" },
+            "transaction_id": tx.id,
+            "profile": config.profile,
+        }),
+    });
+
+    ::pem::parse(cert.pem.as_bytes())
+        .map(|p| p.into_contents())
+        .map_err(|e| Rejection::Server(500, format!("cert encoding failed: {}", e)))
+}
+
+/// CertPoll (GetCertInitial): return the certificate issued for the
+/// transaction (RFC 8894 §3.3.3). The poll must be signed by the key that
+/// sent the original request and name the same subject.
+fn poll(ctx: &ScepContext, tx: &Transaction, content: Option<&[u8]>) -> Result<Vec<u8>, Rejection> {
+    use x509_parser::prelude::*;
+
+    let tenant = &ctx.config.tenant_id;
+    let envelope = content.ok_or(FailInfo::BadRequest)?;
+    let body = cms::decrypt_enveloped(envelope, &ctx.ra_key).map_err(|_| FailInfo::BadMessageCheck)?;
+    let subject = yasna::parse_ber(&body, |r| r.read_sequence(|r| {
+        r.next().read_der()?;
+        r.next().read_der()
+    })).map_err(|_| FailInfo::BadMessageCheck)?;
+    let (_, name) = X509Name::from_der(&subject).map_err(|_| FailInfo::BadMessageCheck)?;
+
+    let store = open_store(&ctx.config)?;
+    let transaction = store.get_scep_transaction(tenant, &tx.id)
+        .map_err(|e| Rejection::Server(500, format!("store error: {}", e)))?
+        .ok_or(FailInfo::BadCertId)?;
+    let requester_key_sha256 = spki_of_cert(tx.signer).map(|spki| sha256_hex(&spki));
+    if requester_key_sha256.as_deref() != Some(transaction.requester_key_sha256.as_str()) {
+        return Err(FailInfo::BadCertId.into());
+    }
+    let record = store.get_cert_by_serial(tenant, &transaction.serial)
+        .map_err(|e| Rejection::Server(500, format!("store error: {}", e)))?
+        .filter(|r| r.status == CertStatus::Active)
+        .ok_or(FailInfo::BadCertId)?;
+    let cert_der = ::pem::parse(record.pem.as_bytes())
+        .map(|p| p.into_contents())
+        .map_err(|e| Rejection::Server(500, format!("cert encoding failed: {}", e)))?;
+    let (_, cert) = X509Certificate::from_der(&cert_der)
+        .map_err(|e| Rejection::Server(500, format!("cert encoding failed: {}", e)))?;
+    if cert.subject().to_string() != name.to_string() {
+        return Err(FailInfo::BadCertId.into());
+    }
+    Ok(cert_der)
+}
+
+// ---------------------------------------------------------------------------
+// Dispatcher
+// ---------------------------------------------------------------------------
+
+/// Handle a request to the SCEP endpoint. `query` is the raw query string.
+pub fn handle(ctx: &ScepContext, method: &str, query: &str, body: &[u8]) -> ScepOutcome {
+    let operation = query_param(query, "operation").unwrap_or_default();
+    match (method, operation.as_str()) {
+        ("GET", "GetCACaps") => ScepOutcome::text(200, CA_CAPS),
+        ("GET", "GetCACert") => handle_get_ca_cert(ctx),
+        ("GET", "GetNextCACert") => ScepOutcome::text(501, "GetNextCACert not implemented"),
+        ("GET", "PKIOperation") => match query_param(query, "message").and_then(|m| decode_b64(&m)) {
+            Some(der) => handle_pki_operation(ctx, &der),
+            None => ScepOutcome::text(400, "message parameter is not base64"),
+        },
+        ("POST", "PKIOperation") => {
+            // Binary DER per RFC 8894; some clients base64-encode the body
+            let der = if body.first() == Some(&0x30) {
+                Some(body.to_vec())
+            } else {
+                std::str::from_utf8(body).ok().and_then(decode_b64)
+            };
+            match der {
+                Some(der) => handle_pki_operation(ctx, &der),
+                None => ScepOutcome::text(400, "body is neither DER nor base64"),
+            }
+        }
+        (_, "") => ScepOutcome::text(400, "missing operation parameter"),
+        _ => ScepOutcome::text(400, &format!("unsupported operation '{}'", operation)),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Plugin ABI
+// ---------------------------------------------------------------------------
+
+pub mod plugin {
+    use super::*;
+    use std::ffi::{c_char, c_void, CStr, CString};
+    use std::panic;
+    use std::path::Path;
+    use ox_workflow_abi::{
+        CoreHostApi, FlowControl, FLOW_CONTROL_CONTINUE, OX_LOG_ERROR, OX_LOG_INFO,
+        OX_WORKFLOW_ABI_VERSION,
+    };
+
+    struct PluginState {
+        api: CoreHostApi,
+        ctx: ScepContext,
+    }
+    unsafe impl Send for PluginState {}
+    unsafe impl Sync for PluginState {}
+
+    fn log(api: &CoreHostApi, ctx: *mut c_void, level: u8, msg: &str) {
+        if let Ok(c) = CString::new(msg) { (api.log)(ctx, level, c.as_ptr()); }
+    }
+
+    fn get(api: &CoreHostApi, ctx: *mut c_void, key: &str) -> String {
+        let Ok(k) = CString::new(key) else { return String::new() };
+        let ptr = (api.get_field)(ctx, k.as_ptr());
+        if ptr.is_null() { return String::new(); }
+        unsafe { CStr::from_ptr(ptr).to_string_lossy().into_owned() }
+    }
+
+    fn set(api: &CoreHostApi, ctx: *mut c_void, key: &str, val: &str) {
+        if let (Ok(k), Ok(v)) = (CString::new(key), CString::new(val)) {
+            (api.set_field)(ctx, k.as_ptr(), v.as_ptr());
+        }
+    }
+
+    fn get_bytes(api: &CoreHostApi, ctx: *mut c_void, key: &str) -> Vec<u8> {
+        let Ok(k) = CString::new(key) else { return vec![] };
+        let mut len: usize = 0;
+        let ptr = (api.get_field_bytes)(ctx, k.as_ptr(), &mut len);
+        if ptr.is_null() || len == 0 { return vec![]; }
+        unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec()
+    }
+
+    fn set_bytes(api: &CoreHostApi, ctx: *mut c_void, key: &str, data: &[u8]) {
+        if let Ok(k) = CString::new(key) {
+            (api.set_field_bytes)(ctx, k.as_ptr(), data.as_ptr(), data.len());
+        }
+    }
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn ox_plugin_init(
+        config_ptr: *const c_char,
+        api_ptr: *const CoreHostApi,
+        abi_version: u32,
+    ) -> *mut c_void {
+        if abi_version != OX_WORKFLOW_ABI_VERSION || api_ptr.is_null() {
+            return std::ptr::null_mut();
+        }
+        let api = unsafe { *api_ptr };
+        let params_str = if !config_ptr.is_null() {
+            unsafe { CStr::from_ptr(config_ptr).to_string_lossy().to_string() }
+        } else {
+            String::new()
+        };
+        let params: serde_json::Value =
+            serde_json::from_str(&params_str).unwrap_or(serde_json::Value::Null);
+        let config_path = match params.get("config_file").and_then(|v| v.as_str()) {
+            Some(p) => p.to_string(),
+            None => {
+                log(&api, std::ptr::null_mut(), OX_LOG_ERROR, "ox_cert_scep: missing config_file param");
+                return std::ptr::null_mut();
+            }
+        };
+        let config: ScepConfig = match ox_fileproc::process_file(Path::new(&config_path), 5) {
+            Ok(v) => match serde_json::from_value(v) {
+                Ok(c) => c,
+                Err(e) => {
+                    log(&api, std::ptr::null_mut(), OX_LOG_ERROR,
+                        &format!("ox_cert_scep: config error: {}", e));
+                    return std::ptr::null_mut();
+                }
+            },
+            Err(e) => {
+                log(&api, std::ptr::null_mut(), OX_LOG_ERROR,
+                    &format!("ox_cert_scep: failed to load config: {}", e));
+                return std::ptr::null_mut();
+            }
+        };
+        let tenant_id = config.tenant_id.clone();
+        let ctx = match ScepContext::new(config) {
+            Ok(c) => c,
+            Err(e) => {
+                log(&api, std::ptr::null_mut(), OX_LOG_ERROR,
+                    &format!("ox_cert_scep: RA key setup failed: {}", e));
+                return std::ptr::null_mut();
+            }
+        };
+        log(&api, std::ptr::null_mut(), OX_LOG_INFO,
+            &format!("ox_cert_scep: initialized for tenant '{}'", tenant_id));
+        Box::into_raw(Box::new(PluginState { api, ctx })) as *mut c_void
+    }
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn ox_plugin_process(
+        plugin_ctx: *mut c_void,
+        task_ctx: *mut c_void,
+    ) -> FlowControl {
+        let cont = FlowControl { code: FLOW_CONTROL_CONTINUE, payload: std::ptr::null() };
+        if plugin_ctx.is_null() { return cont; }
+        let state = unsafe { &*(plugin_ctx as *mut PluginState) };
+
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            let method = get(&state.api, task_ctx, "request.method").to_uppercase();
+            let query  = get(&state.api, task_ctx, "request.query");
+            let body   = if method == "POST" {
+                get_bytes(&state.api, task_ctx, "request.body")
+            } else {
+                vec![]
+            };
+
+            let outcome = handle(&state.ctx, &method, &query, &body);
+
+            set(&state.api, task_ctx, "response.status", &outcome.http_status.to_string());
+            set(&state.api, task_ctx, "response.header.Content-Type", outcome.content_type);
+            set_bytes(&state.api, task_ctx, "response.body", &outcome.body);
+            cont
+        }));
+
+        match result {
+            Ok(fc) => fc,
+            Err(_) => {
+                log(&state.api, task_ctx, OX_LOG_ERROR, "ox_cert_scep: panic in process");
+                cont
+            }
+        }
+    }
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn ox_plugin_error(_ctx: *mut c_void, _task: *mut c_void) {}
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn ox_plugin_destroy(plugin_ctx: *mut c_void) {
+        if !plugin_ctx.is_null() {
+            unsafe { drop(Box::from_raw(plugin_ctx as *mut PluginState)); }
+        }
+    }
+}
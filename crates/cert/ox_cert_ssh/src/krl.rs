@@ -0,0 +1,66 @@
+// OpenSSH Key Revocation Lists
+//
+// Binary format from PROTOCOL.krl in the OpenSSH source. Only certificate
+// sections are written: one per CA key, each holding a list of revoked serials.
+// The KRL is not signed; sshd reads it from `RevokedKeys` and ssh from
+// `RevokedHostKeys`, both local files the operator keeps up to date.
+
+const KRL_MAGIC: &[u8; 8] = b"SSHKRL\n\0";
+const KRL_FORMAT_VERSION: u32 = 1;
+const KRL_SECTION_CERTIFICATES: u8 = 1;
+const KRL_SECTION_CERT_SERIAL_LIST: u8 = 0x20;
+
+/// Revoked certificate serials issued by one CA.
+pub struct KrlCaSection {
+    /// The CA public key in SSH wire encoding.
+    pub ca_key: Vec<u8>,
+    pub serials: Vec<u64>,
+}
+
+fn put_u32(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&v.to_be_bytes());
+}
+
+fn put_u64(out: &mut Vec<u8>, v: u64) {
+    out.extend_from_slice(&v.to_be_bytes());
+}
+
+fn put_string(out: &mut Vec<u8>, s: &[u8]) {
+    put_u32(out, s.len() as u32);
+    out.extend_from_slice(s);
+}
+
+/// Encode a KRL. `version` must increase with every KRL published so clients
+/// can tell a newer list from an older one. Sections without serials are left
+/// out; serial 0 is not a valid certificate serial and is dropped.
+pub fn build_krl(version: u64, generated_at: u64, comment: &str, sections: &[KrlCaSection]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(KRL_MAGIC);
+    put_u32(&mut out, KRL_FORMAT_VERSION);
+    put_u64(&mut out, version);
+    put_u64(&mut out, generated_at);
+    put_u64(&mut out, 0); // flags
+    put_string(&mut out, b""); // reserved
+    put_string(&mut out, comment.as_bytes());
+
+    for section in sections {
+        let mut serials: Vec<u64> = section.serials.iter().copied().filter(|s| *s != 0).collect();
+        serials.sort_unstable();
+        serials.dedup();
+        if serials.is_empty() { continue; }
+
+        let mut list = Vec::with_capacity(serials.len() * 8);
+        for serial in serials {
+            put_u64(&mut list, serial);
+        }
+        let mut data = Vec::new();
+        put_string(&mut data, &section.ca_key);
+        put_string(&mut data, b""); // reserved
+        data.push(KRL_SECTION_CERT_SERIAL_LIST);
+        put_string(&mut data, &list);
+
+        out.push(KRL_SECTION_CERTIFICATES);
+        put_string(&mut out, &data);
+    }
+    out
+}
@@ -1,9 +1,16 @@
+pub mod krl;
+
+#[cfg(test)]
+mod tests;
+
 use ox_cert_core::{
     model::{
-        AuditAction, AuditEvent, CertStoreConfig, KeyStoreConfig, SshCertRecord, SshCertType,
+        AuditAction, AuditEvent, CertStoreConfig, KeyStoreConfig, KeyStoreType, KeyType,
+        RevocationReason, SigningAlgorithm, SshCertRecord, SshCertType,
     },
     open_keystore,
     store::{CertStore, OxPersistenceCertStore},
+    KeyStore,
 };
 use serde::Deserialize;
 use std::collections::HashMap;
@@ -50,7 +57,16 @@ pub struct SshOutcome {
     pub body_json: String,
 }
 
-pub fn handle(config: &SshConfig, method: &str, path: &str, body: &str) -> SshOutcome {
+/// Response for `GET /api/v1/ssh/krl`: the binary KRL, or a JSON error.
+pub struct KrlResponse {
+    pub http_status: u16,
+    pub content_type: String,
+    pub body: Vec<u8>,
+}
+
+/// `actor` is the authenticated principal, recorded on the audit events of the
+/// sign and revoke operations.
+pub fn handle(config: &SshConfig, method: &str, path: &str, body: &str, actor: &str) -> SshOutcome {
     let tenant = &config.tenant_id;
     let request_id = Uuid::new_v4().to_string();
 
@@ -74,39 +90,32 @@ pub fn handle(config: &SshConfig, method: &str, path: &str, body: &str) -> SshOu
 
     match (method, segs.get(3), segs.get(4)) {
         ("POST", Some(&"sign"), None) => {
-            handle_ssh_sign(config, body, &request_id)
+            handle_ssh_sign(config, body, &request_id, actor)
         }
         ("POST", Some(&"renew"), None) => {
             handle_ssh_renew(config, body, &request_id)
         }
+        ("POST", Some(&"revoke"), None) => {
+            handle_ssh_revoke(config, body, &request_id, actor)
+        }
         ("GET", Some(&"ca"), Some(&"user")) => {
-            let ks = match open_keystore(&config.keystore) {
-                Ok(k) => k,
-                Err(e) => err!(503, "CA_NOT_READY", e.to_string()),
-            };
-            let pub_key = match ks.public_key(tenant, &config.user_ca.key_id) {
+            let authorized_keys = match format_authorized_keys(config, &config.user_ca, "ox_cert-user-ca") {
                 Ok(k) => k,
-                Err(e) => err!(503, "CA_NOT_READY", e.to_string()),
+                Err(e) => err!(503, "CA_NOT_READY", e),
             };
-            let authorized_keys = format_authorized_keys(&pub_key, "ox_cert-user-ca");
             SshOutcome {
                 http_status: 200,
-                body_json: format!("\"{}\"", authorized_keys.replace('"', "\\\"")),
+                body_json: serde_json::Value::String(authorized_keys).to_string(),
             }
         }
         ("GET", Some(&"ca"), Some(&"host")) => {
-            let ks = match open_keystore(&config.keystore) {
-                Ok(k) => k,
-                Err(e) => err!(503, "CA_NOT_READY", e.to_string()),
-            };
-            let pub_key = match ks.public_key(tenant, &config.host_ca.key_id) {
+            let authorized_keys = match format_authorized_keys(config, &config.host_ca, "ox_cert-host-ca") {
                 Ok(k) => k,
-                Err(e) => err!(503, "CA_NOT_READY", e.to_string()),
+                Err(e) => err!(503, "CA_NOT_READY", e),
             };
-            let authorized_keys = format_authorized_keys(&pub_key, "ox_cert-host-ca");
             SshOutcome {
                 http_status: 200,
-                body_json: format!("\"{}\"", authorized_keys.replace('"', "\\\"")),
+                body_json: serde_json::Value::String(authorized_keys).to_string(),
             }
         }
         ("GET", Some(&"config"), None) => {
@@ -114,11 +123,13 @@ pub fn handle(config: &SshConfig, method: &str, path: &str, body: &str) -> SshOu
                 http_status: 200,
                 body_json: serde_json::json!({
                     "data": {
-                        "sshd_config_snippet": "TrustedUserCAKeys /etc/ssh/trusted_user_cas",
+                        "sshd_config_snippet": "TrustedUserCAKeys /etc/ssh/trusted_user_cas\nRevokedKeys /etc/ssh/revoked_keys",
+                        "ssh_config_snippet": "RevokedHostKeys ~/.ssh/revoked_keys",
                         "known_hosts_snippet": "@cert-authority * <host_ca_public_key>",
                         "notes": {
                             "TrustedUserCAKeys": "Add user_ca_public_key contents to this file on each SSH server",
-                            "HostCertificate": "Host certs must be signed using POST /api/v1/ssh/sign with cert_type=host"
+                            "HostCertificate": "Host certs must be signed using POST /api/v1/ssh/sign with cert_type=host",
+                            "RevokedKeys": "Refresh from GET /api/v1/ssh/krl; covers user and host certificates"
                         }
                     },
                     "meta": { "tenant_id": tenant, "request_id": request_id }
@@ -129,7 +140,7 @@ pub fn handle(config: &SshConfig, method: &str, path: &str, body: &str) -> SshOu
     }
 }
 
-fn handle_ssh_sign(config: &SshConfig, body: &str, request_id: &str) -> SshOutcome {
+fn handle_ssh_sign(config: &SshConfig, body: &str, request_id: &str, actor: &str) -> SshOutcome {
     let tenant = &config.tenant_id;
     let v: serde_json::Value = match serde_json::from_str(body) {
         Ok(v) => v,
@@ -168,22 +179,24 @@ fn handle_ssh_sign(config: &SshConfig, body: &str, request_id: &str) -> SshOutco
         err!(400, "INVALID_REQUEST", "principals is required and must be non-empty");
     }
 
-    let is_user = cert_type_str == "user";
-    let cert_type = if is_user { SshCertType::User } else { SshCertType::Host };
+    let cert_type = match cert_type_str.as_str() {
+        "user" => SshCertType::User,
+        "host" => SshCertType::Host,
+        other => err!(400, "INVALID_REQUEST", format!("cert_type must be 'user' or 'host', not '{}'", other)),
+    };
+    let is_user = cert_type == SshCertType::User;
     let policy = if is_user { &config.user } else { &config.host };
     let ca_cfg = if is_user { &config.user_ca } else { &config.host_ca };
 
     // Validate principals
-    for principal in &principals {
-        let allowed = policy.allowed_principals.iter().any(|pat| {
-            if pat == "*" { true }
-            else if pat.starts_with("*.") { principal.ends_with(&pat[1..]) }
-            else { pat == principal }
-        });
-        if !allowed {
-            err!(403, "POLICY_VIOLATION", format!("principal '{}' not in allowed list", principal));
+    if !is_user {
+        if let Some(bad) = principals.iter().find(|p| !is_hostname(p)) {
+            err!(400, "INVALID_REQUEST", format!("host principal '{}' is not a hostname or IP address", bad));
         }
     }
+    if let Err(msg) = check_principals(policy, &cert_type, &principals) {
+        err!(403, "POLICY_VIOLATION", msg);
+    }
 
     // Parse validity
     let validity_str = v.get("validity")
@@ -250,6 +263,8 @@ fn handle_ssh_sign(config: &SshConfig, body: &str, request_id: &str) -> SshOutco
         critical_options,
         extensions,
         certificate: certificate.clone(),
+        revoked_at: None,
+        revocation_reason: None,
         created_at: now,
     };
 
@@ -260,7 +275,7 @@ fn handle_ssh_sign(config: &SshConfig, body: &str, request_id: &str) -> SshOutco
     let _ = store.store_audit_event(tenant, &AuditEvent {
         id: 0, tenant_id: tenant.clone(), timestamp: now,
         action: AuditAction::SshSign, serial: None,
-        actor: String::new(),
+        actor: actor.to_string(),
         details: serde_json::json!({ "ssh_serial": serial, "cert_type": cert_type_str, "principals": principals }),
     });
 
@@ -308,16 +323,8 @@ fn sign_ssh_cert(
         _ => CertType::User,
     };
 
-    let is_user = cert_type_str == "user";
-    let ca_key_id = if is_user { &config.user_ca.key_id } else { &config.host_ca.key_id };
-    let tenant = &config.tenant_id;
-
-    let ks = open_keystore(&config.keystore).map_err(|e| e.to_string())?;
-    let ca_key_pem = ks.load_key_pem(tenant, ca_key_id).map_err(|e| e.to_string())?;
-
-    // Parse CA key as ssh-key PrivateKey
-    let ca_private_key = ssh_key::PrivateKey::from_openssh(&ca_key_pem)
-        .map_err(|e| format!("CA key parse (must be OpenSSH format): {}", e))?;
+    let ca_cfg = if cert_type_str == "user" { &config.user_ca } else { &config.host_ca };
+    let ca_private_key = load_ca_key(config, ca_cfg)?;
 
     let valid_after_unix = valid_after.unix_timestamp() as u64;
     let valid_before_unix = valid_before.unix_timestamp() as u64;
@@ -386,6 +393,19 @@ fn handle_ssh_renew(config: &SshConfig, body: &str, request_id: &str) -> SshOutc
         Err(e) => err!(500, "INTERNAL_ERROR", e.to_string()),
     };
 
+    if record.revoked_at.is_some() {
+        err!(409, "ALREADY_REVOKED", "cannot renew a revoked certificate");
+    }
+
+    // The policy may have narrowed since the certificate was issued
+    let policy = match record.cert_type {
+        SshCertType::User => &config.user,
+        SshCertType::Host => &config.host,
+    };
+    if let Err(msg) = check_principals(policy, &record.cert_type, &record.principals) {
+        err!(403, "POLICY_VIOLATION", msg);
+    }
+
     let now = OffsetDateTime::now_utc();
     let min_valid = now - ::time::Duration::minutes(5);
     if record.valid_before < min_valid {
@@ -433,6 +453,8 @@ fn handle_ssh_renew(config: &SshConfig, body: &str, request_id: &str) -> SshOutc
         critical_options: record.critical_options.clone(),
         extensions: record.extensions.clone(),
         certificate: new_cert.clone(),
+        revoked_at: None,
+        revocation_reason: None,
         created_at: now,
     };
 
@@ -448,10 +470,308 @@ fn handle_ssh_renew(config: &SshConfig, body: &str, request_id: &str) -> SshOutc
     }
 }
 
-fn format_authorized_keys(pub_key_der: &[u8], comment: &str) -> String {
-    // Format as SSH authorized_keys line
-    let b64 = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, pub_key_der);
-    format!("ssh-key {} {}", b64, comment)
+fn reason_from_str(s: &str) -> Result<RevocationReason, String> {
+    match s {
+        "unspecified" => Ok(RevocationReason::Unspecified),
+        "key_compromise" => Ok(RevocationReason::KeyCompromise),
+        "ca_compromise" => Ok(RevocationReason::CaCompromise),
+        "affiliation_changed" => Ok(RevocationReason::AffiliationChanged),
+        "superseded" => Ok(RevocationReason::Superseded),
+        "cessation_of_operation" => Ok(RevocationReason::CessationOfOperation),
+        "privilege_withdrawn" => Ok(RevocationReason::PrivilegeWithdrawn),
+        other => Err(format!("unknown revocation reason: '{}'", other)),
+    }
+}
+
+fn handle_ssh_revoke(config: &SshConfig, body: &str, request_id: &str, actor: &str) -> SshOutcome {
+    let tenant = &config.tenant_id;
+    let v: serde_json::Value = serde_json::from_str(body).unwrap_or_default();
+
+    macro_rules! err {
+        ($status:expr, $code:expr, $msg:expr) => {
+            return SshOutcome {
+                http_status: $status,
+                body_json: serde_json::json!({
+                    "error": { "code": $code, "message": $msg },
+                    "meta": { "tenant_id": tenant, "request_id": request_id }
+                }).to_string(),
+            }
+        };
+    }
+
+    let serial = match v.get("serial").and_then(|s| s.as_u64()) {
+        Some(s) => s,
+        None => err!(400, "INVALID_REQUEST", "serial is required"),
+    };
+    let reason_str = v.get("reason").and_then(|r| r.as_str()).unwrap_or("unspecified");
+    let reason = match reason_from_str(reason_str) {
+        Ok(r) => r,
+        Err(msg) => err!(400, "INVALID_REQUEST", msg),
+    };
+
+    let store = match OxPersistenceCertStore::open(config.store.db_path()) {
+        Ok(s) => s,
+        Err(e) => err!(500, "INTERNAL_ERROR", e.to_string()),
+    };
+
+    let record = match store.get_ssh_cert_by_serial(tenant, serial) {
+        Ok(Some(r)) => r,
+        Ok(None) => err!(404, "NOT_FOUND", format!("SSH cert {} not found", serial)),
+        Err(e) => err!(500, "INTERNAL_ERROR", e.to_string()),
+    };
+    if record.revoked_at.is_some() {
+        err!(409, "ALREADY_REVOKED", format!("SSH cert {} is already revoked", serial));
+    }
+
+    let now = OffsetDateTime::now_utc();
+    if let Err(e) = store.mark_ssh_revoked(tenant, serial, reason, now) {
+        err!(500, "INTERNAL_ERROR", e.to_string());
+    }
+
+    let _ = store.store_audit_event(tenant, &AuditEvent {
+        id: 0, tenant_id: tenant.clone(), timestamp: now,
+        action: AuditAction::SshRevoke, serial: None,
+        actor: actor.to_string(),
+        details: serde_json::json!({ "ssh_serial": serial, "reason": reason_str }),
+    });
+
+    let revoked_at = now.format(&time::format_description::well_known::Rfc3339).unwrap_or_default();
+
+    SshOutcome {
+        http_status: 200,
+        body_json: serde_json::json!({
+            "data": { "serial": serial, "revoked_at": revoked_at, "reason": reason as u8 },
+            "meta": { "tenant_id": tenant, "request_id": request_id }
+        }).to_string(),
+    }
+}
+
+/// Audit actor for the `security.principal` an authenticating plugin published: the
+/// principal's display name, or its id if it has none. Empty when unauthenticated.
+fn principal_actor(principal_json: &str) -> String {
+    let Ok(principal) = serde_json::from_str::<serde_json::Value>(principal_json) else {
+        return String::new();
+    };
+    ["display_name", "id"].iter()
+        .filter_map(|k| principal.get(k).and_then(|v| v.as_str()))
+        .find(|s| !s.is_empty())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Build the tenant's KRL: revoked certificates that have not yet expired, in one
+/// section per CA.
+pub fn handle_krl(config: &SshConfig) -> KrlResponse {
+    let error = |status: u16, code: &str, msg: String| KrlResponse {
+        http_status: status,
+        content_type: "application/json".to_string(),
+        body: serde_json::json!({ "error": { "code": code, "message": msg } }).to_string().into_bytes(),
+    };
+    let tenant = &config.tenant_id;
+
+    let store = match OxPersistenceCertStore::open(config.store.db_path()) {
+        Ok(s) => s,
+        Err(e) => return error(500, "INTERNAL_ERROR", e.to_string()),
+    };
+    let revoked = match store.list_revoked_ssh_certs(tenant) {
+        Ok(r) => r,
+        Err(e) => return error(500, "INTERNAL_ERROR", e.to_string()),
+    };
+
+    let now = OffsetDateTime::now_utc();
+    let mut sections = Vec::new();
+    for (cert_type, ca_cfg) in [(SshCertType::User, &config.user_ca), (SshCertType::Host, &config.host_ca)] {
+        let ca_key = match load_ca_key(config, ca_cfg).and_then(|k| {
+            ssh_key::PublicKey::from(k.public_key()).to_bytes().map_err(|e| format!("CA public key: {}", e))
+        }) {
+            Ok(k) => k,
+            Err(e) => return error(503, "CA_NOT_READY", e),
+        };
+        let serials = revoked.iter()
+            .filter(|r| r.cert_type == cert_type && r.valid_before > now)
+            .map(|r| r.serial)
+            .collect();
+        sections.push(krl::KrlCaSection { ca_key, serials });
+    }
+
+    // Seconds are fine-grained enough: every KRL generated later is newer
+    let generated_at = now.unix_timestamp() as u64;
+    KrlResponse {
+        http_status: 200,
+        content_type: "application/octet-stream".to_string(),
+        body: krl::build_krl(generated_at, generated_at, &format!("ox_cert {}", tenant), &sections),
+    }
+}
+
+/// `*` allows anything, `*.example.com` any name under example.com, anything else
+/// only itself. Host principals are compared case-insensitively.
+fn check_principals(
+    policy: &SshPrincipalPolicy,
+    cert_type: &SshCertType,
+    principals: &[String],
+) -> Result<(), String> {
+    let fold = |s: &str| match cert_type {
+        SshCertType::Host => s.to_ascii_lowercase(),
+        SshCertType::User => s.to_string(),
+    };
+    for principal in principals {
+        let name = fold(principal);
+        let allowed = policy.allowed_principals.iter().any(|pat| {
+            let pat = fold(pat);
+            if pat == "*" { true }
+            else if let Some(suffix) = pat.strip_prefix('*').filter(|s| s.starts_with('.')) {
+                name.ends_with(suffix)
+            }
+            else { pat == name }
+        });
+        if !allowed {
+            return Err(format!("principal '{}' not in allowed list", principal));
+        }
+    }
+    Ok(())
+}
+
+/// A DNS hostname (letters, digits and hyphens in dot-separated labels) or an IP
+/// address. Wildcards are not accepted in host certificates.
+fn is_hostname(name: &str) -> bool {
+    if name.parse::<std::net::IpAddr>().is_ok() { return true; }
+    !name.is_empty() && name.len() <= 253 && name.split('.').all(|label| {
+        !label.is_empty() && label.len() <= 63
+            && !label.starts_with('-') && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    })
+}
+
+/// An SSH CA signing key. Software keys are parsed into an `ssh_key::PrivateKey`;
+/// PKCS#11 keys cannot leave the token and sign through `KeyStore::sign`.
+enum CaKey {
+    Local(Box<ssh_key::PrivateKey>),
+    Keystore {
+        ks: Box<dyn KeyStore>,
+        tenant_id: String,
+        key_id: String,
+        key_type: SshCaKeyType,
+        public: ssh_key::public::KeyData,
+    },
+}
+
+impl CaKey {
+    fn public_key(&self) -> ssh_key::public::KeyData {
+        match self {
+            CaKey::Local(key) => key.public_key().key_data().clone(),
+            CaKey::Keystore { public, .. } => public.clone(),
+        }
+    }
+}
+
+impl From<&CaKey> for ssh_key::public::KeyData {
+    fn from(key: &CaKey) -> Self {
+        key.public_key()
+    }
+}
+
+impl signature::Signer<ssh_key::Signature> for CaKey {
+    fn try_sign(&self, msg: &[u8]) -> Result<ssh_key::Signature, signature::Error> {
+        let (ks, tenant_id, key_id, key_type) = match self {
+            CaKey::Local(key) => return key.try_sign(msg),
+            CaKey::Keystore { ks, tenant_id, key_id, key_type, .. } => (ks, tenant_id, key_id, key_type),
+        };
+        let algorithm = match key_type {
+            SshCaKeyType::Ed25519 => SigningAlgorithm::Ed25519,
+            SshCaKeyType::EcdsaP256 => SigningAlgorithm::EcdsaWithSha256,
+            SshCaKeyType::EcdsaP384 => SigningAlgorithm::EcdsaWithSha384,
+        };
+        let raw = ks.sign(tenant_id, key_id, algorithm, msg).map_err(|_| signature::Error::new())?;
+        // The keystore returns ECDSA as fixed-width r||s, which SSH encodes as two mpints
+        Ok(match key_type {
+            SshCaKeyType::Ed25519 => ssh_key::Signature::new(ssh_key::Algorithm::Ed25519, raw)?,
+            SshCaKeyType::EcdsaP256 => p256::ecdsa::Signature::from_slice(&raw)?.try_into()?,
+            SshCaKeyType::EcdsaP384 => p384::ecdsa::Signature::from_slice(&raw)?.try_into()?,
+        })
+    }
+}
+
+/// Load a CA signing key; `key_type` says which OpenSSH key type it must be.
+fn load_ca_key(config: &SshConfig, ca_cfg: &SshCaConfig) -> Result<CaKey, String> {
+    let ks = open_keystore(&config.keystore).map_err(|e| e.to_string())?;
+    if matches!(config.keystore.store_type, KeyStoreType::Pkcs11) {
+        return keystore_ca_key(ks, &config.tenant_id, ca_cfg);
+    }
+    load_software_ca_key(ks.as_ref(), &config.tenant_id, ca_cfg).map(|key| CaKey::Local(Box::new(key)))
+}
+
+fn keystore_ca_key(ks: Box<dyn KeyStore>, tenant_id: &str, ca_cfg: &SshCaConfig) -> Result<CaKey, String> {
+    use ssh_key::public::{EcdsaPublicKey, Ed25519PublicKey, KeyData};
+
+    let info = ks.key_info(tenant_id, &ca_cfg.key_id).map_err(|e| e.to_string())?;
+    let raw = ks.public_key(tenant_id, &ca_cfg.key_id).map_err(|e| e.to_string())?;
+    let bad_key = |e: &dyn std::fmt::Display| format!("CA key is not {:?}: {}", ca_cfg.key_type, e);
+    let public = match (ca_cfg.key_type, info.key_type) {
+        (SshCaKeyType::Ed25519, KeyType::Ed25519) => {
+            KeyData::Ed25519(Ed25519PublicKey(raw.as_slice().try_into().map_err(|_| bad_key(&"key length"))?))
+        }
+        (SshCaKeyType::EcdsaP256, KeyType::EcP256) | (SshCaKeyType::EcdsaP384, KeyType::EcP384) => {
+            KeyData::Ecdsa(EcdsaPublicKey::from_sec1_bytes(&raw).map_err(|e| bad_key(&e))?)
+        }
+        (_, other) => return Err(bad_key(&format!("keystore holds {:?}", other))),
+    };
+    Ok(CaKey::Keystore {
+        ks,
+        tenant_id: tenant_id.to_string(),
+        key_id: ca_cfg.key_id.clone(),
+        key_type: ca_cfg.key_type,
+        public,
+    })
+}
+
+/// A software keystore holds an OpenSSH private key (`ssh-keygen`) or PKCS#8;
+/// `key_type` says which OpenSSH key type it must be.
+fn load_software_ca_key(ks: &dyn KeyStore, tenant_id: &str, ca_cfg: &SshCaConfig) -> Result<ssh_key::PrivateKey, String> {
+    use ed25519::pkcs8::KeypairBytes;
+    use p256::pkcs8::DecodePrivateKey;
+    use ssh_key::private::{EcdsaKeypair, Ed25519Keypair};
+    use ssh_key::{Algorithm, EcdsaCurve};
+
+    let pem = ks.load_key_pem(tenant_id, &ca_cfg.key_id).map_err(|e| e.to_string())?;
+    let bad_key = |e: &dyn std::fmt::Display| format!("CA key is not {:?}: {}", ca_cfg.key_type, e);
+
+    if let Ok(key) = ssh_key::PrivateKey::from_openssh(&pem) {
+        if key.is_encrypted() {
+            return Err("CA key is an encrypted OpenSSH key".to_string());
+        }
+        let expected = match ca_cfg.key_type {
+            SshCaKeyType::Ed25519 => Algorithm::Ed25519,
+            SshCaKeyType::EcdsaP256 => Algorithm::Ecdsa { curve: EcdsaCurve::NistP256 },
+            SshCaKeyType::EcdsaP384 => Algorithm::Ecdsa { curve: EcdsaCurve::NistP384 },
+        };
+        if key.algorithm() != expected {
+            return Err(bad_key(&format!("OpenSSH key is {}", key.algorithm())));
+        }
+        return Ok(key);
+    }
+
+    let der = ::pem::parse(pem.as_bytes()).map_err(|e| format!("CA key PEM: {}", e))?.into_contents();
+    let keypair = match ca_cfg.key_type {
+        SshCaKeyType::Ed25519 => {
+            let key = KeypairBytes::from_pkcs8_der(&der).map_err(|e| bad_key(&e))?;
+            Ed25519Keypair::from_seed(&key.secret_key).into()
+        }
+        SshCaKeyType::EcdsaP256 => {
+            let sk = p256::SecretKey::from_pkcs8_der(&der).map_err(|e| bad_key(&e))?;
+            EcdsaKeypair::NistP256 { public: sk.public_key().into(), private: sk.into() }.into()
+        }
+        SshCaKeyType::EcdsaP384 => {
+            let sk = p384::SecretKey::from_pkcs8_der(&der).map_err(|e| bad_key(&e))?;
+            EcdsaKeypair::NistP384 { public: sk.public_key().into(), private: sk.into() }.into()
+        }
+    };
+    Ok(keypair)
+}
+
+/// The CA public key as an authorized_keys / known_hosts line.
+fn format_authorized_keys(config: &SshConfig, ca_cfg: &SshCaConfig, comment: &str) -> Result<String, String> {
+    let public = ssh_key::PublicKey::new(load_ca_key(config, ca_cfg)?.public_key(), comment);
+    public.to_openssh().map_err(|e| format!("CA public key: {}", e))
 }
 
 fn ssh_fingerprint(pub_key_openssh: &str) -> String {
@@ -519,6 +839,12 @@ pub mod plugin {
         }
     }
 
+    fn set_bytes(api: &CoreHostApi, task_ctx: *mut c_void, key: &str, data: &[u8]) {
+        if let Ok(k) = CString::new(key) {
+            (api.set_field_bytes)(task_ctx, k.as_ptr(), data.as_ptr(), data.len());
+        }
+    }
+
     #[unsafe(no_mangle)]
     pub extern "C" fn ox_plugin_init(
         config_ptr: *const c_char,
@@ -576,7 +902,16 @@ pub mod plugin {
 
             if !path.starts_with("/api/v1/ssh/") { return cont; }
 
-            let outcome = handle(&state.config, &method, &path, &body);
+            if method == "GET" && path == "/api/v1/ssh/krl" {
+                let response = handle_krl(&state.config);
+                set(&state.api, task_ctx, "response.status", &response.http_status.to_string());
+                set(&state.api, task_ctx, "response.header.Content-Type", &response.content_type);
+                set_bytes(&state.api, task_ctx, "response.body", &response.body);
+                return cont;
+            }
+
+            let actor = principal_actor(&get(&state.api, task_ctx, "security.principal"));
+            let outcome = handle(&state.config, &method, &path, &body, &actor);
             set(&state.api, task_ctx, "response.status", &outcome.http_status.to_string());
             set(&state.api, task_ctx, "response.body", &outcome.body_json);
             set(&state.api, task_ctx, "response.header.Content-Type", "application/json");
@@ -0,0 +1,214 @@
+//! Host principal policy, SSH revocation and the KRL endpoint, against CA keys
+//! stored as PKCS#8 or OpenSSH private keys in a software keystore.
+
+use crate::*;
+use p256::pkcs8::EncodePrivateKey;
+use serde_json::{json, Value};
+use ssh_key::{Algorithm, Certificate, HashAlg, PublicKey};
+
+/// Ed25519 user CA and P-256 host CA under `keys/t1`.
+fn make_config(dir: &tempfile::TempDir) -> SshConfig {
+    let key_dir = dir.path().join("keys").join("t1");
+    std::fs::create_dir_all(&key_dir).unwrap();
+
+    // RFC 8410 PrivateKeyInfo prefix for an Ed25519 seed
+    let mut ed25519 = vec![0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20];
+    ed25519.extend_from_slice(&[7u8; 32]);
+    std::fs::write(key_dir.join("ssh-user-ca.key.pem"), ::pem::encode(&::pem::Pem::new("PRIVATE KEY", ed25519))).unwrap();
+    let p256_key = p256::SecretKey::random(&mut rand_core::OsRng);
+    std::fs::write(key_dir.join("ssh-host-ca.key.pem"),
+        p256_key.to_pkcs8_pem(p256::pkcs8::LineEnding::LF).unwrap().as_bytes()).unwrap();
+
+    serde_json::from_value(json!({
+        "tenant_id": "t1",
+        "store": { "driver": "sqlite", "path": dir.path().join("cert.db").to_str().unwrap() },
+        "keystore": { "store_type": "software", "key_dir": dir.path().join("keys").to_str().unwrap() },
+        "user_ca": { "key_id": "ssh-user-ca", "key_type": "Ed25519", "default_validity": "8h" },
+        "host_ca": { "key_id": "ssh-host-ca", "key_type": "EcdsaP256", "default_validity": "30d" },
+        "user": { "allowed_principals": ["alice", "bob"], "max_validity": "24h" },
+        "host": { "allowed_principals": ["*.example.com", "bastion.corp"] },
+    })).unwrap()
+}
+
+fn subject_key() -> String {
+    ssh_key::PrivateKey::random(&mut rand_core::OsRng, Algorithm::Ed25519).unwrap()
+        .public_key().to_openssh().unwrap()
+}
+
+fn sign(config: &SshConfig, cert_type: &str, principals: &[&str]) -> (u16, Value) {
+    let body = json!({ "public_key": subject_key(), "cert_type": cert_type, "principals": principals });
+    let out = handle(config, "POST", "/api/v1/ssh/sign", &body.to_string(), "alice");
+    (out.http_status, serde_json::from_str(&out.body_json).unwrap())
+}
+
+fn post(config: &SshConfig, op: &str, body: Value) -> (u16, Value) {
+    let out = handle(config, "POST", &format!("/api/v1/ssh/{}", op), &body.to_string(), "admin");
+    (out.http_status, serde_json::from_str(&out.body_json).unwrap())
+}
+
+fn ca_public(config: &SshConfig, ca_cfg: &SshCaConfig) -> PublicKey {
+    PublicKey::from(load_ca_key(config, ca_cfg).unwrap().public_key())
+}
+
+/// (CA key blob, serials) for each certificate section of a KRL.
+fn parse_krl(krl: &[u8]) -> Vec<(Vec<u8>, Vec<u64>)> {
+    fn take<'a>(buf: &mut &'a [u8], n: usize) -> &'a [u8] {
+        let (head, rest) = buf.split_at(n);
+        *buf = rest;
+        head
+    }
+    fn string<'a>(buf: &mut &'a [u8]) -> &'a [u8] {
+        let len = u32::from_be_bytes(take(buf, 4).try_into().unwrap()) as usize;
+        take(buf, len)
+    }
+    let mut buf = krl;
+    assert_eq!(take(&mut buf, 8), b"SSHKRL\n\0");
+    assert_eq!(take(&mut buf, 4), &[0, 0, 0, 1]);
+    take(&mut buf, 24); // version, generated date, flags
+    string(&mut buf); // reserved
+    string(&mut buf); // comment
+    let mut sections = Vec::new();
+    while !buf.is_empty() {
+        assert_eq!(take(&mut buf, 1), &[1], "certificate section");
+        let mut data = string(&mut buf);
+        let ca_key = string(&mut data).to_vec();
+        string(&mut data); // reserved
+        assert_eq!(take(&mut data, 1), &[0x20], "serial list");
+        let list = string(&mut data);
+        let serials = list.chunks(8).map(|c| u64::from_be_bytes(c.try_into().unwrap())).collect();
+        sections.push((ca_key, serials));
+    }
+    sections
+}
+
+#[test]
+fn test_host_principals_checked_against_policy() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = make_config(&dir);
+
+    let (status, body) = sign(&config, "host", &["web1.example.com", "Bastion.Corp"]);
+    assert_eq!(status, 201, "{}", body);
+    let cert = Certificate::from_openssh(body["data"]["certificate"].as_str().unwrap()).unwrap();
+    assert!(cert.cert_type().is_host());
+    assert_eq!(cert.valid_principals(), ["web1.example.com", "Bastion.Corp"]);
+    let ca = ca_public(&config, &config.host_ca).fingerprint(HashAlg::Sha256);
+    cert.validate(&[ca]).expect("signed by the host CA");
+
+    for (principal, status) in [
+        ("a.b.example.com", 201),
+        ("example.com", 403),
+        ("web1.example.com.evil.net", 403),
+        ("alice", 403),
+        ("*.example.com", 400),
+        ("web_1.example.com", 400),
+        ("-web.example.com", 400),
+    ] {
+        assert_eq!(sign(&config, "host", &[principal]).0, status, "{}", principal);
+    }
+    assert_eq!(sign(&config, "user", &["alice"]).0, 201);
+    assert_eq!(sign(&config, "user", &["Alice"]).0, 403, "user principals are case-sensitive");
+    assert_eq!(sign(&config, "service", &["alice"]).0, 400);
+}
+
+#[test]
+fn test_revoke_blocks_renewal() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = make_config(&dir);
+    let (_, body) = sign(&config, "host", &["web1.example.com"]);
+    let serial = body["data"]["serial"].as_u64().unwrap();
+
+    assert_eq!(post(&config, "revoke", json!({ "serial": serial, "reason": "bogus" })).0, 400);
+    assert_eq!(post(&config, "revoke", json!({ "serial": 999 })).0, 404);
+    assert_eq!(post(&config, "revoke", json!({})).0, 400);
+
+    let (status, body) = post(&config, "revoke", json!({ "serial": serial, "reason": "key_compromise" }));
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["data"]["reason"], 1);
+
+    let (status, body) = post(&config, "revoke", json!({ "serial": serial }));
+    assert_eq!(status, 409);
+    assert_eq!(body["error"]["code"], "ALREADY_REVOKED");
+    assert_eq!(post(&config, "renew", json!({ "serial": serial })).0, 409);
+
+    let store = OxPersistenceCertStore::open(config.store.db_path()).unwrap();
+    let filter = ox_cert_core::model::AuditFilter { action: Some(AuditAction::SshRevoke), ..Default::default() };
+    let events = store.get_audit_log("t1", &filter, &Default::default()).unwrap().items;
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].actor, "admin");
+}
+
+#[test]
+fn test_audit_actor_from_security_principal() {
+    let principal = json!({ "id": "6f1c0c3e-0000-4000-8000-000000000001", "display_name": "alice" });
+    assert_eq!(principal_actor(&principal.to_string()), "alice");
+    let unnamed = json!({ "id": "6f1c0c3e-0000-4000-8000-000000000001", "display_name": "" });
+    assert_eq!(principal_actor(&unnamed.to_string()), "6f1c0c3e-0000-4000-8000-000000000001");
+    assert_eq!(principal_actor(""), "");
+}
+
+#[test]
+fn test_openssh_ca_key_is_accepted() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = make_config(&dir);
+    let ca_key = ssh_key::PrivateKey::random(&mut rand_core::OsRng, Algorithm::Ed25519).unwrap();
+    std::fs::write(dir.path().join("keys/t1/ssh-user-ca.key.pem"),
+        ca_key.to_openssh(ssh_key::LineEnding::LF).unwrap().as_bytes()).unwrap();
+    assert_eq!(ca_public(&config, &config.user_ca), *ca_key.public_key());
+
+    let (status, body) = sign(&config, "user", &["alice"]);
+    assert_eq!(status, 201, "{}", body);
+    let cert = Certificate::from_openssh(body["data"]["certificate"].as_str().unwrap()).unwrap();
+    assert_eq!(cert.signature_key(), ca_key.public_key().key_data());
+
+    // An OpenSSH key of another type is refused rather than used
+    let p256 = ssh_key::PrivateKey::random(&mut rand_core::OsRng,
+        Algorithm::Ecdsa { curve: ssh_key::EcdsaCurve::NistP256 }).unwrap();
+    std::fs::write(dir.path().join("keys/t1/ssh-user-ca.key.pem"),
+        p256.to_openssh(ssh_key::LineEnding::LF).unwrap().as_bytes()).unwrap();
+    assert!(load_ca_key(&config, &config.user_ca).is_err());
+}
+
+#[test]
+fn test_krl_lists_revoked_serials_per_ca() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = make_config(&dir);
+    let serial = |(_, body): (u16, Value)| body["data"]["serial"].as_u64().unwrap();
+    let user_a = serial(sign(&config, "user", &["alice"]));
+    let _user_b = serial(sign(&config, "user", &["bob"]));
+    let host_a = serial(sign(&config, "host", &["web1.example.com"]));
+    let host_b = serial(sign(&config, "host", &["web2.example.com"]));
+    for s in [user_a, host_a, host_b] {
+        assert_eq!(post(&config, "revoke", json!({ "serial": s })).0, 200);
+    }
+
+    let krl = handle_krl(&config);
+    assert_eq!(krl.http_status, 200);
+    assert_eq!(krl.content_type, "application/octet-stream");
+    let sections = parse_krl(&krl.body);
+    assert_eq!(sections, vec![
+        (ca_public(&config, &config.user_ca).to_bytes().unwrap(), vec![user_a]),
+        (ca_public(&config, &config.host_ca).to_bytes().unwrap(), vec![host_a, host_b]),
+    ]);
+}
+
+#[test]
+fn test_krl_empty_without_revocations() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = make_config(&dir);
+    sign(&config, "user", &["alice"]);
+    let krl = handle_krl(&config);
+    assert_eq!(krl.http_status, 200);
+    assert!(parse_krl(&krl.body).is_empty());
+}
+
+#[test]
+fn test_ca_endpoint_returns_openssh_key() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = make_config(&dir);
+    let out = handle(&config, "GET", "/api/v1/ssh/ca/host", "", "");
+    assert_eq!(out.http_status, 200);
+    let line: String = serde_json::from_str(&out.body_json).unwrap();
+    let key = PublicKey::from_openssh(&line).unwrap();
+    assert_eq!(key.algorithm(), Algorithm::Ecdsa { curve: ssh_key::EcdsaCurve::NistP256 });
+    assert_eq!(key.comment(), "ox_cert-host-ca");
+}
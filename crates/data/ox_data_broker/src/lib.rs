@@ -15,10 +15,15 @@ type DestroyFn = unsafe extern "C" fn(*mut c_void);
 type PersistFn = unsafe extern "C" fn(*mut c_void, *const c_char, *const c_char) -> c_int;
 type RestoreFn = unsafe extern "C" fn(*mut c_void, *const c_char, *const c_char) -> OxBuffer;
 type FetchFn = unsafe extern "C" fn(*mut c_void, *const c_char, *const c_char) -> OxBuffer;
-type DeleteFn = unsafe extern "C" fn(*mut c_void, *const c_char, *const c_char) -> c_int;
+type DeleteFn = unsafe extern "C" fn(*mut c_void, *const c_char, *const c_char, *const c_char) -> c_int;
+type UpdateFn = unsafe extern "C" fn(*mut c_void, *const c_char, *const c_char, *const c_char, *const c_char) -> c_int;
+type UpsertFn = unsafe extern "C" fn(*mut c_void, *const c_char, *const c_char, *const c_char) -> c_int;
 type FreeBufferFn = unsafe extern "C" fn(OxBuffer);
 type GetMetadataFn = unsafe extern "C" fn() -> *mut c_char;
 
+/// Field that records are addressed by in `/data/{driver}/record/{id}` and upserts.
+const RECORD_ID_FIELD: &str = "id";
+
 struct LoadedDriver {
     #[allow(dead_code)]
     library: Library,
@@ -28,6 +33,8 @@ struct LoadedDriver {
     restore_fn: RestoreFn,
     fetch_fn: FetchFn,
     delete_fn: Option<DeleteFn>,
+    update_fn: Option<UpdateFn>,
+    upsert_fn: Option<UpsertFn>,
     free_buffer_fn: FreeBufferFn,
     metadata: DriverMetadata,
 }
@@ -45,14 +52,16 @@ impl LoadedDriver {
         let fetch: FetchFn = *lib.get(b"ox_driver_fetch").map_err(|e| OxDataError::InternalError(e.to_string()))?;
         let free_buf: FreeBufferFn = *lib.get(b"ox_driver_free_buffer").map_err(|e| OxDataError::InternalError(e.to_string()))?;
         let get_meta: GetMetadataFn = *lib.get(b"ox_driver_get_driver_metadata").map_err(|e| OxDataError::InternalError(e.to_string()))?;
-        // ox_driver_delete is optional — drivers that don't support it omit the symbol.
+        // ox_driver_delete, _update and _upsert are optional — drivers that don't support them omit the symbols.
         let delete_fn: Option<DeleteFn> = lib.get::<DeleteFn>(b"ox_driver_delete").ok().map(|sym| *sym);
+        let update_fn: Option<UpdateFn> = lib.get::<UpdateFn>(b"ox_driver_update").ok().map(|sym| *sym);
+        let upsert_fn: Option<UpsertFn> = lib.get::<UpsertFn>(b"ox_driver_upsert").ok().map(|sym| *sym);
         let config = CString::new("{}").unwrap();
         let ctx = init(config.as_ptr());
         let meta_ptr = get_meta();
         let meta_str = CStr::from_ptr(meta_ptr).to_string_lossy();
         let metadata: DriverMetadata = serde_json::from_str(&meta_str).map_err(|e| OxDataError::InternalError(e.to_string()))?;
-        Ok(LoadedDriver { library: lib, context: ctx, destroy_fn: destroy, persist_fn: persist, restore_fn: restore, fetch_fn: fetch, delete_fn, free_buffer_fn: free_buf, metadata })
+        Ok(LoadedDriver { library: lib, context: ctx, destroy_fn: destroy, persist_fn: persist, restore_fn: restore, fetch_fn: fetch, delete_fn, update_fn, upsert_fn, free_buffer_fn: free_buf, metadata })
     }
 }
 
@@ -188,6 +197,36 @@ pub unsafe extern "C" fn ox_plugin_process(
         let operation = parts[3];
         let body = get_field(api, task_ctx, "request.body");
 
+        // PUT /data/{driver_name}/record/{id}
+        if operation == "record" && method == "PUT" {
+            let id = parts.get(4).copied().unwrap_or("");
+            if id.is_empty() {
+                json_response(api, task_ctx, 400, r#"{"error":"Record ID required"}"#);
+                return FlowControl { code: FLOW_CONTROL_CONTINUE, payload: std::ptr::null() };
+            }
+            let manager = DRIVER_MANAGER.lock().unwrap();
+            if let Some(driver) = manager.drivers.get(driver_name) {
+                if let Some(update_fn) = driver.update_fn {
+                    let location = CString::new("data.csv").unwrap();
+                    let data_c = CString::new(body).unwrap_or_default();
+                    let id_field = CString::new(RECORD_ID_FIELD).unwrap();
+                    let id_c = CString::new(id).unwrap_or_default();
+                    let result = unsafe { update_fn(driver.context, data_c.as_ptr(), location.as_ptr(), id_field.as_ptr(), id_c.as_ptr()) };
+                    match result {
+                        0 => json_response(api, task_ctx, 200, r#"{"ok":true}"#),
+                        404 => json_response(api, task_ctx, 404, r#"{"error":"Not found"}"#),
+                        -2 => json_response(api, task_ctx, 400, r#"{"error":"Invalid record"}"#),
+                        _ => json_response(api, task_ctx, 500, r#"{"error":"update failed"}"#),
+                    }
+                } else {
+                    json_response(api, task_ctx, 501, r#"{"error":"Driver does not support update"}"#);
+                }
+            } else {
+                json_response(api, task_ctx, 404, &format!("{{\"error\":\"Driver {} not found\"}}", driver_name));
+            }
+            return FlowControl { code: FLOW_CONTROL_CONTINUE, payload: std::ptr::null() };
+        }
+
         // DELETE /data/{driver_name}/record/{id}
         if operation == "record" && method == "DELETE" {
             let id = parts.get(4).copied().unwrap_or("");
@@ -199,8 +238,9 @@ pub unsafe extern "C" fn ox_plugin_process(
             if let Some(driver) = manager.drivers.get(driver_name) {
                 if let Some(delete_fn) = driver.delete_fn {
                     let location = CString::new("data.csv").unwrap();
+                    let id_field = CString::new(RECORD_ID_FIELD).unwrap();
                     let id_c = CString::new(id).unwrap_or_default();
-                    let result = unsafe { delete_fn(driver.context, location.as_ptr(), id_c.as_ptr()) };
+                    let result = unsafe { delete_fn(driver.context, location.as_ptr(), id_field.as_ptr(), id_c.as_ptr()) };
                     match result {
                         0 => json_response(api, task_ctx, 200, r#"{"ok":true}"#),
                         404 => json_response(api, task_ctx, 404, r#"{"error":"Not found"}"#),
@@ -224,6 +264,19 @@ pub unsafe extern "C" fn ox_plugin_process(
                     let result = (driver.persist_fn)(driver.context, data_c.as_ptr(), location.as_ptr());
                     json_response(api, task_ctx, if result == 0 { 200 } else { 500 }, if result == 0 { r#"{"ok":true}"# } else { r#"{"error":"persist failed"}"# });
                 },
+                ("POST", "upsert") => match driver.upsert_fn {
+                    Some(upsert_fn) => unsafe {
+                        let data_c = CString::new(body).unwrap_or_default();
+                        let id_field = CString::new(RECORD_ID_FIELD).unwrap();
+                        let result = upsert_fn(driver.context, data_c.as_ptr(), location.as_ptr(), id_field.as_ptr());
+                        match result {
+                            0 => json_response(api, task_ctx, 200, r#"{"ok":true}"#),
+                            -2 => json_response(api, task_ctx, 400, r#"{"error":"Invalid record"}"#),
+                            _ => json_response(api, task_ctx, 500, r#"{"error":"upsert failed"}"#),
+                        }
+                    },
+                    None => json_response(api, task_ctx, 501, r#"{"error":"Driver does not support upsert"}"#),
+                },
                 ("GET", "restore") => unsafe {
                     let id_c = CString::new(body).unwrap_or_default();
                     let buf = (driver.restore_fn)(driver.context, location.as_ptr(), id_c.as_ptr());
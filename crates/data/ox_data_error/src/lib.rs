@@ -11,6 +11,7 @@ pub enum OxDataError {
     ValidationError(String),
     TransactionError(String),
     CallbackError(String),
+    NotFound(String),
 }
 
 impl fmt::Display for OxDataError {
@@ -26,6 +27,7 @@ impl fmt::Display for OxDataError {
             OxDataError::ValidationError(msg) => write!(f, "Validation Error: {}", msg),
             OxDataError::TransactionError(msg) => write!(f, "Transaction Error: {}", msg),
             OxDataError::CallbackError(msg) => write!(f, "Callback Error: {}", msg),
+            OxDataError::NotFound(msg) => write!(f, "Not Found: {}", msg),
         }
     }
 }
@@ -18,71 +18,79 @@ pub struct MssqlPersistenceDriver {
     connection_string: Option<String>,
 }
 
-impl PersistenceDriver for MssqlPersistenceDriver {
-    fn persist(
-        &self,
-        serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>, 
-        location: &str,
-    ) -> Result<(), OxDataError> {
+impl MssqlPersistenceDriver {
+    /// Connects, runs a write statement and returns the number of affected rows.
+    fn execute(&self, query: tiberius::Query<'_>) -> Result<u64, OxDataError> {
         let conn_str = self.connection_string.as_ref().ok_or_else(|| OxDataError::InternalError("Connection string not set".to_string()))?;
-        // Parse simple connection string: server=host;user=u;password=p;port=1433
-        // For this demo, let's assume we parse it or use a config map if passed.
-        // But here we only have the string.
-        
-        let rt = &self.runtime;
-        
-        rt.block_on(async {
+
+        self.runtime.block_on(async {
             let config = Config::from_ado_string(conn_str).map_err(|e| OxDataError::InternalError(e.to_string()))?;
             let tcp = tokio::net::TcpStream::connect(config.get_addr()).await.map_err(|e| OxDataError::InternalError(e.to_string()))?;
             let tcp = tcp.compat_write();
             let mut client = Client::connect(config, tcp).await.map_err(|e| OxDataError::InternalError(e.to_string()))?;
 
-            use ox_persistence_driver_sql::{SqlBuilder, SqlDialect};
-            let builder = SqlBuilder::new(SqlDialect::Mssql);
-            let keys: Vec<String> = serializable_map.keys().cloned().collect();
-            let query = builder.build_insert(location, &keys);
+            let result = query.execute(&mut client).await.map_err(|e| OxDataError::InternalError(e.to_string()))?;
+            Ok::<u64, OxDataError>(result.rows_affected().iter().sum())
+        })
+    }
+}
 
-            let mut query_obj = tiberius::Query::new(query);
-            for k in &keys {
-                let (v, t, _) = &serializable_map[k];
-                match t {
-                    ValueType::Integer => {
-                        if let Ok(i) = v.parse::<i64>() {
-                             query_obj.bind(i);
-                        } else {
-                             query_obj.bind(v.as_str());
-                        }
-                    },
-                    ValueType::Float => {
-                        if let Ok(f) = v.parse::<f64>() {
-                             query_obj.bind(f);
-                        } else {
-                             query_obj.bind(v.as_str());
-                        }
-                    },
-                    ValueType::Boolean => {
-                         if let Ok(b) = v.parse::<bool>() {
-                             query_obj.bind(b);
-                         } else {
-                             query_obj.bind(v.as_str());
-                         }
-                    },
-                    ValueType::DateTime => {
-                         if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(v) {
-                             query_obj.bind(dt);
-                         } else {
-                             query_obj.bind(v.as_str());
-                         }
-                    },
-                    _ => query_obj.bind(v.as_str()),
+/// Binds the values of `keys` to `query`, in order, using their declared types.
+fn bind_params<'a>(
+    query: &mut tiberius::Query<'a>,
+    map: &'a HashMap<String, (String, ValueType, HashMap<String, String>)>,
+    keys: &[String],
+) {
+    for k in keys {
+        let (v, t, _) = &map[k];
+        match t {
+            ValueType::Integer => {
+                if let Ok(i) = v.parse::<i64>() {
+                     query.bind(i);
+                } else {
+                     query.bind(v.as_str());
                 }
-            }
-
-            query_obj.execute(&mut client).await.map_err(|e| OxDataError::InternalError(e.to_string()))?;
-            
-            Ok::<(), OxDataError>(())
-        })?;
+            },
+            ValueType::Float => {
+                if let Ok(f) = v.parse::<f64>() {
+                     query.bind(f);
+                } else {
+                     query.bind(v.as_str());
+                }
+            },
+            ValueType::Boolean => {
+                 if let Ok(b) = v.parse::<bool>() {
+                     query.bind(b);
+                 } else {
+                     query.bind(v.as_str());
+                 }
+            },
+            ValueType::DateTime => {
+                 if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(v) {
+                     query.bind(dt);
+                 } else {
+                     query.bind(v.as_str());
+                 }
+            },
+            _ => query.bind(v.as_str()),
+        }
+    }
+}
 
+impl PersistenceDriver for MssqlPersistenceDriver {
+    fn persist(
+        &self,
+        serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>, 
+        location: &str,
+    ) -> Result<(), OxDataError> {
+        use ox_persistence_driver_sql::{SqlBuilder, SqlDialect};
+        let builder = SqlBuilder::new(SqlDialect::Mssql);
+        let keys: Vec<String> = serializable_map.keys().cloned().collect();
+        let query = builder.build_insert(location, &keys);
+
+        let mut query_obj = tiberius::Query::new(query);
+        bind_params(&mut query_obj, serializable_map, &keys);
+        self.execute(query_obj)?;
         Ok(())
     }
 
@@ -193,6 +201,63 @@ impl PersistenceDriver for MssqlPersistenceDriver {
         })
     }
 
+    fn update(
+        &self,
+        serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+        location: &str,
+        id_field: &str,
+        id: &str,
+    ) -> Result<(), OxDataError> {
+        use ox_persistence_driver_sql::{SqlBuilder, SqlDialect};
+        let builder = SqlBuilder::new(SqlDialect::Mssql);
+        let keys: Vec<String> = serializable_map.keys().filter(|k| k.as_str() != id_field).cloned().collect();
+        if keys.is_empty() {
+            return Ok(());
+        }
+        let query = builder.build_update(location, id_field, &keys);
+
+        let mut query_obj = tiberius::Query::new(query);
+        bind_params(&mut query_obj, serializable_map, &keys);
+        query_obj.bind(id);
+        if self.execute(query_obj)? == 0 {
+            return Err(OxDataError::NotFound(format!("Object with id {} not found", id)));
+        }
+        Ok(())
+    }
+
+    fn upsert(
+        &self,
+        serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+        location: &str,
+        id_field: &str,
+    ) -> Result<(), OxDataError> {
+        if !serializable_map.contains_key(id_field) {
+            return Err(OxDataError::ValidationError(format!("Upsert requires an '{}' field", id_field)));
+        }
+        use ox_persistence_driver_sql::{SqlBuilder, SqlDialect};
+        let builder = SqlBuilder::new(SqlDialect::Mssql);
+        let keys: Vec<String> = serializable_map.keys().cloned().collect();
+        let query = builder.build_upsert(location, id_field, &keys);
+
+        let mut query_obj = tiberius::Query::new(query);
+        bind_params(&mut query_obj, serializable_map, &keys);
+        self.execute(query_obj)?;
+        Ok(())
+    }
+
+    fn delete(&self, location: &str, id_field: &str, id: &str) -> Result<(), OxDataError> {
+        use ox_persistence_driver_sql::{SqlBuilder, SqlDialect};
+        let builder = SqlBuilder::new(SqlDialect::Mssql);
+        let query = builder.build_delete(location, id_field);
+
+        let mut query_obj = tiberius::Query::new(query);
+        query_obj.bind(id);
+        if self.execute(query_obj)? == 0 {
+            return Err(OxDataError::NotFound(format!("Object with id {} not found", id)));
+        }
+        Ok(())
+    }
+
     fn notify_lock_status_change(&self, lock_status: &str, gdo_id: &str) {
          println!("MssqlDriver: GDO {} lock status changed to {}", gdo_id, lock_status);
     }
@@ -325,6 +390,89 @@ pub unsafe extern "C" fn ox_driver_fetch(
 
 
 
+#[no_mangle]
+pub unsafe extern "C" fn ox_driver_update(
+    ctx: *mut c_void, 
+    data_json: *const c_char, 
+    location: *const c_char,
+    id_field: *const c_char,
+    id: *const c_char
+) -> i32 {
+    let driver = &*(ctx as *mut MssqlPersistenceDriver);
+    let data_str = CStr::from_ptr(data_json).to_string_lossy();
+    let location_str = CStr::from_ptr(location).to_string_lossy();
+    let id_field_str = CStr::from_ptr(id_field).to_string_lossy();
+    let id_str = CStr::from_ptr(id).to_string_lossy();
+
+    match serde_json::from_str::<HashMap<String, (String, ValueType, HashMap<String, String>)>>(&data_str) {
+        Ok(map) => {
+            match driver.update(&map, &location_str, &id_field_str, &id_str) {
+                Ok(_) => 0,
+                Err(OxDataError::NotFound(_)) => 404,
+                Err(e) => {
+                    eprintln!("Update error: {}", e);
+                    -1
+                }
+            }
+        },
+        Err(e) => {
+            eprintln!("JSON parse error: {}", e);
+            -2
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ox_driver_upsert(
+    ctx: *mut c_void, 
+    data_json: *const c_char, 
+    location: *const c_char,
+    id_field: *const c_char
+) -> i32 {
+    let driver = &*(ctx as *mut MssqlPersistenceDriver);
+    let data_str = CStr::from_ptr(data_json).to_string_lossy();
+    let location_str = CStr::from_ptr(location).to_string_lossy();
+    let id_field_str = CStr::from_ptr(id_field).to_string_lossy();
+
+    match serde_json::from_str::<HashMap<String, (String, ValueType, HashMap<String, String>)>>(&data_str) {
+        Ok(map) => {
+            match driver.upsert(&map, &location_str, &id_field_str) {
+                Ok(_) => 0,
+                Err(e) => {
+                    eprintln!("Upsert error: {}", e);
+                    -1
+                }
+            }
+        },
+        Err(e) => {
+            eprintln!("JSON parse error: {}", e);
+            -2
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ox_driver_delete(
+    ctx: *mut c_void, 
+    location: *const c_char, 
+    id_field: *const c_char,
+    id: *const c_char
+) -> i32 {
+    let driver = &*(ctx as *mut MssqlPersistenceDriver);
+    let location_str = CStr::from_ptr(location).to_string_lossy();
+    let id_field_str = CStr::from_ptr(id_field).to_string_lossy();
+    let id_str = CStr::from_ptr(id).to_string_lossy();
+
+    match driver.delete(&location_str, &id_field_str, &id_str) {
+        Ok(_) => 0,
+        Err(OxDataError::NotFound(_)) => 404,
+        Err(e) => {
+            eprintln!("Delete error: {}", e);
+            -1
+        }
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn ox_driver_get_driver_metadata() -> *mut c_char {
     let mut compatible_modules = HashMap::new();
@@ -57,6 +57,55 @@ impl MysqlPersistenceDriver {
     }
 }
 
+/// Converts the values of `keys` to MySQL parameters, in order, using their declared types.
+fn bind_params(
+    map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+    keys: &[String],
+) -> Vec<mysql::Value> {
+    keys.iter().map(|k| {
+        let (v, t, _) = &map[k];
+        match t {
+            ValueType::Integer => {
+                if let Ok(i) = v.parse::<i64>() {
+                    mysql::Value::Int(i)
+                } else {
+                    mysql::Value::from(v)
+                }
+            },
+            ValueType::Float => {
+                if let Ok(f) = v.parse::<f64>() {
+                    mysql::Value::Double(f)
+                } else {
+                    mysql::Value::from(v)
+                }
+            },
+            ValueType::Boolean => {
+                if let Ok(b) = v.parse::<bool>() {
+                    mysql::Value::Int(if b { 1 } else { 0 })
+                } else {
+                    mysql::Value::from(v)
+                }
+            },
+            ValueType::DateTime => {
+                if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(v) {
+                    mysql::Value::Date(
+                        dt.year() as u16,
+                        dt.month() as u8,
+                        dt.day() as u8,
+                        dt.hour() as u8,
+                        dt.minute() as u8,
+                        dt.second() as u8,
+                        dt.timestamp_subsec_micros()
+                    )
+                } else {
+                    mysql::Value::from(v)
+                }
+            },
+            _ => mysql::Value::from(v),
+        }
+    }).collect()
+}
+
 impl PersistenceDriver for MysqlPersistenceDriver {
     fn persist(
         &self,
@@ -72,54 +121,7 @@ impl PersistenceDriver for MysqlPersistenceDriver {
         let keys: Vec<String> = serializable_map.keys().cloned().collect();
         let query = builder.build_insert(location, &keys);
 
-        let params: Vec<mysql::Value> = keys.iter().map(|k| {
-            let (v, t, _) = &serializable_map[k];
-            match t {
-                ValueType::Integer => {
-                    if let Ok(i) = v.parse::<i64>() {
-                        mysql::Value::Int(i)
-                    } else {
-                        mysql::Value::from(v)
-                    }
-                },
-                ValueType::Float => {
-                    if let Ok(f) = v.parse::<f64>() {
-                        mysql::Value::Double(f)
-                    } else {
-                        mysql::Value::from(v)
-                    }
-                },
-                ValueType::Boolean => {
-                    if let Ok(b) = v.parse::<bool>() {
-                        mysql::Value::Int(if b { 1 } else { 0 })
-                    } else {
-                        mysql::Value::from(v)
-                    }
-                },
-                ValueType::DateTime => {
-                     // Parse ISO8601 string to NaiveDateTime or just pass string if MySQL handles it?
-                     // MySQL driver handles NaiveDateTime.
-                     // We need chrono::NaiveDateTime.
-                     if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(v) {
-                         mysql::Value::Date(
-                             dt.year() as u16,
-                             dt.month() as u8,
-                             dt.day() as u8,
-                             dt.hour() as u8,
-                             dt.minute() as u8,
-                             dt.second() as u8,
-                             dt.timestamp_subsec_micros()
-                         )
-                     } else {
-                         mysql::Value::from(v)
-                     }
-                },
-                _ => mysql::Value::from(v),
-            }
-        }).collect();
-
-        use mysql::prelude::Queryable;
-        conn.exec_drop(query, params).map_err(|e| OxDataError::InternalError(e.to_string()))?;
+        conn.exec_drop(query, bind_params(serializable_map, &keys)).map_err(|e| OxDataError::InternalError(e.to_string()))?;
         
         Ok(())
     }
@@ -134,7 +136,7 @@ impl PersistenceDriver for MysqlPersistenceDriver {
 
         use ox_persistence_driver_sql::{SqlBuilder, SqlDialect};
         let builder = SqlBuilder::new(SqlDialect::Mysql);
-        let query = builder.build_select_by_id(location);
+        let query = builder.build_select_by_id(location, "id");
         
         use mysql::prelude::Queryable;
         use mysql::params;
@@ -170,50 +172,7 @@ impl PersistenceDriver for MysqlPersistenceDriver {
         let keys: Vec<String> = filter.keys().cloned().collect();
         let query = builder.build_fetch(location, &keys);
         
-        let mut params_vec: Vec<mysql::Value> = Vec::new();
-
-        for k in &keys {
-            let (v, t, _) = &filter[k];
-             match t {
-                ValueType::Integer => {
-                    if let Ok(i) = v.parse::<i64>() {
-                        params_vec.push(mysql::Value::Int(i));
-                    } else {
-                         params_vec.push(mysql::Value::from(v));
-                    }
-                },
-                ValueType::Float => {
-                    if let Ok(f) = v.parse::<f64>() {
-                         params_vec.push(mysql::Value::Double(f));
-                    } else {
-                         params_vec.push(mysql::Value::from(v));
-                    }
-                },
-                ValueType::Boolean => {
-                    if let Ok(b) = v.parse::<bool>() {
-                         params_vec.push(mysql::Value::Int(if b { 1 } else { 0 }));
-                    } else {
-                         params_vec.push(mysql::Value::from(v));
-                    }
-                },
-                ValueType::DateTime => {
-                     if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(v) {
-                         params_vec.push(mysql::Value::Date(
-                             dt.year() as u16,
-                             dt.month() as u8,
-                             dt.day() as u8,
-                             dt.hour() as u8,
-                             dt.minute() as u8,
-                             dt.second() as u8,
-                             dt.timestamp_subsec_micros()
-                         ));
-                    } else {
-                         params_vec.push(mysql::Value::from(v));
-                    }
-                },
-                _ => params_vec.push(mysql::Value::from(v)),
-            }
-        }
+        let params_vec = bind_params(filter, &keys);
 
         use mysql::prelude::Queryable;
         let ids: Vec<String> = conn.exec(query, params_vec).map_err(|e| OxDataError::InternalError(e.to_string()))?;
@@ -221,6 +180,75 @@ impl PersistenceDriver for MysqlPersistenceDriver {
         Ok(ids)
     }
 
+    fn update(
+        &self,
+        serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+        location: &str,
+        id_field: &str,
+        id: &str,
+    ) -> Result<(), OxDataError> {
+        let pool = self.pool.as_ref().ok_or_else(|| OxDataError::InternalError("MySQL pool not initialized".to_string()))?;
+        let mut conn = pool.get_conn().map_err(|e| OxDataError::InternalError(e.to_string()))?;
+
+        use ox_persistence_driver_sql::{SqlBuilder, SqlDialect};
+        let builder = SqlBuilder::new(SqlDialect::Mysql);
+        let keys: Vec<String> = serializable_map.keys().filter(|k| k.as_str() != id_field).cloned().collect();
+        if !keys.is_empty() {
+            let query = builder.build_update(location, id_field, &keys);
+            let mut params_vec = bind_params(serializable_map, &keys);
+            params_vec.push(mysql::Value::from(id));
+            conn.exec_drop(query, params_vec).map_err(|e| OxDataError::InternalError(e.to_string()))?;
+            if conn.affected_rows() > 0 {
+                return Ok(());
+            }
+        }
+
+        // MySQL counts changed rows, not matched ones, so an update that changes nothing
+        // also reports zero. Only a missing row is an error.
+        let existing: Option<mysql::Row> = conn.exec_first(builder.build_select_by_id(location, id_field), (id,))
+            .map_err(|e| OxDataError::InternalError(e.to_string()))?;
+        match existing {
+            Some(_) => Ok(()),
+            None => Err(OxDataError::NotFound(format!("Object with id {} not found", id))),
+        }
+    }
+
+    fn upsert(
+        &self,
+        serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+        location: &str,
+        id_field: &str,
+    ) -> Result<(), OxDataError> {
+        if !serializable_map.contains_key(id_field) {
+            return Err(OxDataError::ValidationError(format!("Upsert requires an '{}' field", id_field)));
+        }
+        let pool = self.pool.as_ref().ok_or_else(|| OxDataError::InternalError("MySQL pool not initialized".to_string()))?;
+        let mut conn = pool.get_conn().map_err(|e| OxDataError::InternalError(e.to_string()))?;
+
+        use ox_persistence_driver_sql::{SqlBuilder, SqlDialect};
+        let builder = SqlBuilder::new(SqlDialect::Mysql);
+        let keys: Vec<String> = serializable_map.keys().cloned().collect();
+        let query = builder.build_upsert(location, id_field, &keys);
+
+        conn.exec_drop(query, bind_params(serializable_map, &keys)).map_err(|e| OxDataError::InternalError(e.to_string()))?;
+        Ok(())
+    }
+
+    fn delete(&self, location: &str, id_field: &str, id: &str) -> Result<(), OxDataError> {
+        let pool = self.pool.as_ref().ok_or_else(|| OxDataError::InternalError("MySQL pool not initialized".to_string()))?;
+        let mut conn = pool.get_conn().map_err(|e| OxDataError::InternalError(e.to_string()))?;
+
+        use ox_persistence_driver_sql::{SqlBuilder, SqlDialect};
+        let builder = SqlBuilder::new(SqlDialect::Mysql);
+        let query = builder.build_delete(location, id_field);
+
+        conn.exec_drop(query, (id,)).map_err(|e| OxDataError::InternalError(e.to_string()))?;
+        if conn.affected_rows() == 0 {
+            return Err(OxDataError::NotFound(format!("Object with id {} not found", id)));
+        }
+        Ok(())
+    }
+
     fn notify_lock_status_change(&self, lock_status: &str, gdo_id: &str) {
          println!("MysqlDriver: GDO {} lock status changed to {}", gdo_id, lock_status);
     }
@@ -440,6 +468,89 @@ pub unsafe extern "C" fn ox_driver_fetch(
 
 
 
+#[no_mangle]
+pub unsafe extern "C" fn ox_driver_update(
+    ctx: *mut c_void, 
+    data_json: *const c_char, 
+    location: *const c_char,
+    id_field: *const c_char,
+    id: *const c_char
+) -> i32 {
+    let driver = &*(ctx as *mut MysqlPersistenceDriver);
+    let data_str = CStr::from_ptr(data_json).to_string_lossy();
+    let location_str = CStr::from_ptr(location).to_string_lossy();
+    let id_field_str = CStr::from_ptr(id_field).to_string_lossy();
+    let id_str = CStr::from_ptr(id).to_string_lossy();
+
+    match serde_json::from_str::<HashMap<String, (String, ValueType, HashMap<String, String>)>>(&data_str) {
+        Ok(map) => {
+            match driver.update(&map, &location_str, &id_field_str, &id_str) {
+                Ok(_) => 0,
+                Err(OxDataError::NotFound(_)) => 404,
+                Err(e) => {
+                    eprintln!("Update error: {}", e);
+                    -1
+                }
+            }
+        },
+        Err(e) => {
+            eprintln!("JSON parse error: {}", e);
+            -2
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ox_driver_upsert(
+    ctx: *mut c_void, 
+    data_json: *const c_char, 
+    location: *const c_char,
+    id_field: *const c_char
+) -> i32 {
+    let driver = &*(ctx as *mut MysqlPersistenceDriver);
+    let data_str = CStr::from_ptr(data_json).to_string_lossy();
+    let location_str = CStr::from_ptr(location).to_string_lossy();
+    let id_field_str = CStr::from_ptr(id_field).to_string_lossy();
+
+    match serde_json::from_str::<HashMap<String, (String, ValueType, HashMap<String, String>)>>(&data_str) {
+        Ok(map) => {
+            match driver.upsert(&map, &location_str, &id_field_str) {
+                Ok(_) => 0,
+                Err(e) => {
+                    eprintln!("Upsert error: {}", e);
+                    -1
+                }
+            }
+        },
+        Err(e) => {
+            eprintln!("JSON parse error: {}", e);
+            -2
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ox_driver_delete(
+    ctx: *mut c_void, 
+    location: *const c_char, 
+    id_field: *const c_char,
+    id: *const c_char
+) -> i32 {
+    let driver = &*(ctx as *mut MysqlPersistenceDriver);
+    let location_str = CStr::from_ptr(location).to_string_lossy();
+    let id_field_str = CStr::from_ptr(id_field).to_string_lossy();
+    let id_str = CStr::from_ptr(id).to_string_lossy();
+
+    match driver.delete(&location_str, &id_field_str, &id_str) {
+        Ok(_) => 0,
+        Err(OxDataError::NotFound(_)) => 404,
+        Err(e) => {
+            eprintln!("Delete error: {}", e);
+            -1
+        }
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn ox_driver_get_driver_metadata() -> *mut c_char {
     let mut compatible_modules = HashMap::new();
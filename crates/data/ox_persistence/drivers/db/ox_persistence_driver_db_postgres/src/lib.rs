@@ -14,63 +14,75 @@ pub struct PostgresPersistenceDriver {
     client: Mutex<Option<postgres::Client>>,
 }
 
+impl PostgresPersistenceDriver {
+    /// Runs a write statement and returns the number of affected rows.
+    fn execute(&self, query: &str, params_vec: &[Box<dyn postgres::types::ToSql + Sync + Send>]) -> Result<u64, OxDataError> {
+        let mut guard = self.client.lock().map_err(|e| OxDataError::InternalError(e.to_string()))?;
+        let client = guard.as_mut().ok_or_else(|| OxDataError::InternalError("Postgres client not initialized".to_string()))?;
+
+        let params_refs: Vec<&(dyn postgres::types::ToSql + Sync)> = params_vec.iter()
+            .map(|s| s.as_ref() as &(dyn postgres::types::ToSql + Sync))
+            .collect();
+        client.execute(query, &params_refs).map_err(|e| OxDataError::InternalError(e.to_string()))
+    }
+}
+
+/// Converts the values of `keys` to Postgres parameters, in order, using their declared types.
+fn bind_params(
+    map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+    keys: &[String],
+) -> Vec<Box<dyn postgres::types::ToSql + Sync + Send>> {
+    let mut params_vec: Vec<Box<dyn postgres::types::ToSql + Sync + Send>> = Vec::new();
+
+    for k in keys {
+        let (v, t, _) = &map[k];
+        match t {
+            ValueType::Integer => {
+                if let Ok(i) = v.parse::<i64>() {
+                    params_vec.push(Box::new(i));
+                } else {
+                    params_vec.push(Box::new(v.clone()));
+                }
+            },
+            ValueType::Float => {
+                if let Ok(f) = v.parse::<f64>() {
+                    params_vec.push(Box::new(f));
+                } else {
+                    params_vec.push(Box::new(v.clone()));
+                }
+            },
+            ValueType::Boolean => {
+                if let Ok(b) = v.parse::<bool>() {
+                    params_vec.push(Box::new(b));
+                } else {
+                    params_vec.push(Box::new(v.clone()));
+                }
+            },
+            ValueType::DateTime => {
+                if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(v) {
+                    params_vec.push(Box::new(dt));
+                } else {
+                    params_vec.push(Box::new(v.clone()));
+                }
+            },
+            _ => params_vec.push(Box::new(v.clone())),
+        }
+    }
+    params_vec
+}
+
 impl PersistenceDriver for PostgresPersistenceDriver {
     fn persist(
         &self,
         serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>, 
         location: &str,
     ) -> Result<(), OxDataError> {
-        let mut guard = self.client.lock().map_err(|e| OxDataError::InternalError(e.to_string()))?;
-        let client = guard.as_mut().ok_or_else(|| OxDataError::InternalError("Postgres client not initialized".to_string()))?;
-
         use ox_persistence_driver_sql::{SqlBuilder, SqlDialect};
         let builder = SqlBuilder::new(SqlDialect::Postgres);
         let keys: Vec<String> = serializable_map.keys().cloned().collect();
         let query = builder.build_insert(location, &keys);
 
-        let mut params_owned: Vec<Box<dyn postgres::types::ToSql + Sync + Send>> = Vec::new();
-
-        for k in &keys {
-            let (v, t, _) = &serializable_map[k];
-            match t {
-                ValueType::Integer => {
-                    if let Ok(i) = v.parse::<i64>() {
-                        params_owned.push(Box::new(i));
-                    } else {
-                        params_owned.push(Box::new(v.clone()));
-                    }
-                },
-                ValueType::Float => {
-                    if let Ok(f) = v.parse::<f64>() {
-                        params_owned.push(Box::new(f));
-                    } else {
-                        params_owned.push(Box::new(v.clone()));
-                    }
-                },
-                ValueType::Boolean => {
-                    if let Ok(b) = v.parse::<bool>() {
-                        params_owned.push(Box::new(b));
-                    } else {
-                        params_owned.push(Box::new(v.clone()));
-                    }
-                },
-                ValueType::DateTime => {
-                     if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(v) {
-                         params_owned.push(Box::new(dt));
-                    } else {
-                         params_owned.push(Box::new(v.clone()));
-                    }
-                },
-                _ => params_owned.push(Box::new(v.clone())),
-            }
-        }
-        
-        let params_refs: Vec<&(dyn postgres::types::ToSql + Sync)> = params_owned.iter()
-            .map(|s| s.as_ref() as &(dyn postgres::types::ToSql + Sync))
-            .collect();
-
-        client.execute(&query, &params_refs).map_err(|e| OxDataError::InternalError(e.to_string()))?;
-        
+        self.execute(&query, &bind_params(serializable_map, &keys))?;
         Ok(())
     }
 
@@ -84,7 +96,7 @@ impl PersistenceDriver for PostgresPersistenceDriver {
 
         use ox_persistence_driver_sql::{SqlBuilder, SqlDialect};
         let builder = SqlBuilder::new(SqlDialect::Postgres);
-        let query = builder.build_select_by_id(location);
+        let query = builder.build_select_by_id(location, "id");
         
         let rows = client.query(&query, &[&id]).map_err(|e| OxDataError::InternalError(e.to_string()))?;
         
@@ -125,43 +137,7 @@ impl PersistenceDriver for PostgresPersistenceDriver {
         let keys: Vec<String> = filter.keys().cloned().collect();
         let query = builder.build_fetch(location, &keys);
         
-        let mut params_vec: Vec<Box<dyn postgres::types::ToSql + Sync + Send>> = Vec::new();
-
-        for k in &keys {
-            let (v, t, _) = &filter[k];
-            match t {
-                ValueType::Integer => {
-                    if let Ok(i) = v.parse::<i64>() {
-                        params_vec.push(Box::new(i));
-                    } else {
-                        params_vec.push(Box::new(v.clone()));
-                    }
-                },
-                ValueType::Float => {
-                    if let Ok(f) = v.parse::<f64>() {
-                        params_vec.push(Box::new(f));
-                    } else {
-                        params_vec.push(Box::new(v.clone()));
-                    }
-                },
-                ValueType::Boolean => {
-                    if let Ok(b) = v.parse::<bool>() {
-                        params_vec.push(Box::new(b));
-                    } else {
-                        params_vec.push(Box::new(v.clone()));
-                    }
-                },
-                ValueType::DateTime => {
-                     if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(v) {
-                         params_vec.push(Box::new(dt));
-                    } else {
-                         params_vec.push(Box::new(v.clone()));
-                    }
-                },
-                _ => params_vec.push(Box::new(v.clone())),
-            }
-
-        }
+        let params_vec = bind_params(filter, &keys);
 
         let params_refs: Vec<&(dyn postgres::types::ToSql + Sync)> = params_vec.iter()
             .map(|s| s.as_ref() as &(dyn postgres::types::ToSql + Sync))
@@ -185,6 +161,59 @@ impl PersistenceDriver for PostgresPersistenceDriver {
         Ok(ids)
     }
 
+    fn update(
+        &self,
+        serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+        location: &str,
+        id_field: &str,
+        id: &str,
+    ) -> Result<(), OxDataError> {
+        use ox_persistence_driver_sql::{SqlBuilder, SqlDialect};
+        let builder = SqlBuilder::new(SqlDialect::Postgres);
+        let keys: Vec<String> = serializable_map.keys().filter(|k| k.as_str() != id_field).cloned().collect();
+        if keys.is_empty() {
+            return Ok(());
+        }
+        let query = builder.build_update(location, id_field, &keys);
+
+        let mut params_vec = bind_params(serializable_map, &keys);
+        params_vec.push(Box::new(id.to_string()));
+        if self.execute(&query, &params_vec)? == 0 {
+            return Err(OxDataError::NotFound(format!("Object with id {} not found", id)));
+        }
+        Ok(())
+    }
+
+    fn upsert(
+        &self,
+        serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+        location: &str,
+        id_field: &str,
+    ) -> Result<(), OxDataError> {
+        if !serializable_map.contains_key(id_field) {
+            return Err(OxDataError::ValidationError(format!("Upsert requires an '{}' field", id_field)));
+        }
+        use ox_persistence_driver_sql::{SqlBuilder, SqlDialect};
+        let builder = SqlBuilder::new(SqlDialect::Postgres);
+        let keys: Vec<String> = serializable_map.keys().cloned().collect();
+        let query = builder.build_upsert(location, id_field, &keys);
+
+        self.execute(&query, &bind_params(serializable_map, &keys))?;
+        Ok(())
+    }
+
+    fn delete(&self, location: &str, id_field: &str, id: &str) -> Result<(), OxDataError> {
+        use ox_persistence_driver_sql::{SqlBuilder, SqlDialect};
+        let builder = SqlBuilder::new(SqlDialect::Postgres);
+        let query = builder.build_delete(location, id_field);
+
+        let params_vec: Vec<Box<dyn postgres::types::ToSql + Sync + Send>> = vec![Box::new(id.to_string())];
+        if self.execute(&query, &params_vec)? == 0 {
+            return Err(OxDataError::NotFound(format!("Object with id {} not found", id)));
+        }
+        Ok(())
+    }
+
     fn notify_lock_status_change(&self, lock_status: &str, gdo_id: &str) {
          println!("PostgresDriver: GDO {} lock status changed to {}", gdo_id, lock_status);
     }
@@ -320,6 +349,89 @@ pub unsafe extern "C" fn ox_driver_fetch(
 
 
 
+#[no_mangle]
+pub unsafe extern "C" fn ox_driver_update(
+    ctx: *mut c_void, 
+    data_json: *const c_char, 
+    location: *const c_char,
+    id_field: *const c_char,
+    id: *const c_char
+) -> i32 {
+    let driver = &*(ctx as *mut PostgresPersistenceDriver);
+    let data_str = CStr::from_ptr(data_json).to_string_lossy();
+    let location_str = CStr::from_ptr(location).to_string_lossy();
+    let id_field_str = CStr::from_ptr(id_field).to_string_lossy();
+    let id_str = CStr::from_ptr(id).to_string_lossy();
+
+    match serde_json::from_str::<HashMap<String, (String, ValueType, HashMap<String, String>)>>(&data_str) {
+        Ok(map) => {
+            match driver.update(&map, &location_str, &id_field_str, &id_str) {
+                Ok(_) => 0,
+                Err(OxDataError::NotFound(_)) => 404,
+                Err(e) => {
+                    eprintln!("Update error: {}", e);
+                    -1
+                }
+            }
+        },
+        Err(e) => {
+            eprintln!("JSON parse error: {}", e);
+            -2
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ox_driver_upsert(
+    ctx: *mut c_void, 
+    data_json: *const c_char, 
+    location: *const c_char,
+    id_field: *const c_char
+) -> i32 {
+    let driver = &*(ctx as *mut PostgresPersistenceDriver);
+    let data_str = CStr::from_ptr(data_json).to_string_lossy();
+    let location_str = CStr::from_ptr(location).to_string_lossy();
+    let id_field_str = CStr::from_ptr(id_field).to_string_lossy();
+
+    match serde_json::from_str::<HashMap<String, (String, ValueType, HashMap<String, String>)>>(&data_str) {
+        Ok(map) => {
+            match driver.upsert(&map, &location_str, &id_field_str) {
+                Ok(_) => 0,
+                Err(e) => {
+                    eprintln!("Upsert error: {}", e);
+                    -1
+                }
+            }
+        },
+        Err(e) => {
+            eprintln!("JSON parse error: {}", e);
+            -2
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ox_driver_delete(
+    ctx: *mut c_void, 
+    location: *const c_char, 
+    id_field: *const c_char,
+    id: *const c_char
+) -> i32 {
+    let driver = &*(ctx as *mut PostgresPersistenceDriver);
+    let location_str = CStr::from_ptr(location).to_string_lossy();
+    let id_field_str = CStr::from_ptr(id_field).to_string_lossy();
+    let id_str = CStr::from_ptr(id).to_string_lossy();
+
+    match driver.delete(&location_str, &id_field_str, &id_str) {
+        Ok(_) => 0,
+        Err(OxDataError::NotFound(_)) => 404,
+        Err(e) => {
+            eprintln!("Delete error: {}", e);
+            -1
+        }
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn ox_driver_get_driver_metadata() -> *mut c_char {
     let mut compatible_modules = HashMap::new();
@@ -29,6 +29,29 @@ impl PersistenceDriver for SqlPersistenceDriver {
         Err(OxDataError::InternalError("Not implemented".to_string()))
     }
 
+    fn update(
+        &self,
+        _serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+        _location: &str,
+        _id_field: &str,
+        _id: &str,
+    ) -> Result<(), OxDataError> {
+        Err(OxDataError::InternalError("Not implemented".to_string()))
+    }
+
+    fn upsert(
+        &self,
+        _serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+        _location: &str,
+        _id_field: &str,
+    ) -> Result<(), OxDataError> {
+        Err(OxDataError::InternalError("Not implemented".to_string()))
+    }
+
+    fn delete(&self, _location: &str, _id_field: &str, _id: &str) -> Result<(), OxDataError> {
+        Err(OxDataError::InternalError("Not implemented".to_string()))
+    }
+
     fn notify_lock_status_change(&self, _lock_status: &str, _gdo_id: &str) {
         // Not implemented
     }
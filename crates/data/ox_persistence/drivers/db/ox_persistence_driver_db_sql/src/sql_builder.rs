@@ -45,13 +45,10 @@ impl SqlBuilder {
         format!("INSERT INTO {} ({}) VALUES ({})", quoted_table, cols, vals)
     }
 
-    pub fn build_select_by_id(&self, table: &str) -> String {
+    pub fn build_select_by_id(&self, table: &str, id_column: &str) -> String {
         let quoted_table = self.quote_identifier(table);
         let placeholder = self.placeholder(0);
-        
-        // Assuming ID column assumes specific quoting? Usually "id" or [id] or `id`.
-        // Let's assume standard "id" column name, quoted.
-        let quoted_id = self.quote_identifier("id");
+        let quoted_id = self.quote_identifier(id_column);
 
         format!("SELECT * FROM {} WHERE {} = {}", quoted_table, quoted_id, placeholder)
     }
@@ -70,6 +67,90 @@ impl SqlBuilder {
 
         query
     }
+
+    /// `UPDATE ... SET` of `keys`, followed by one more placeholder for the ID.
+    /// `keys` should not contain the ID column itself.
+    pub fn build_update(&self, table: &str, id_column: &str, keys: &[String]) -> String {
+        let quoted_table = self.quote_identifier(table);
+        let quoted_id = self.quote_identifier(id_column);
+
+        let assignments: Vec<String> = keys.iter().enumerate()
+            .map(|(i, k)| format!("{} = {}", self.quote_identifier(k), self.placeholder(i)))
+            .collect();
+
+        format!("UPDATE {} SET {} WHERE {} = {}", quoted_table, assignments.join(", "), quoted_id, self.placeholder(keys.len()))
+    }
+
+    /// Insert that replaces the non-ID columns of an existing row with the same ID.
+    /// `keys` must contain `id_column`.
+    pub fn build_upsert(&self, table: &str, id_column: &str, keys: &[String]) -> String {
+        let quoted_id = self.quote_identifier(id_column);
+        let updates: Vec<&String> = keys.iter().filter(|k| k.as_str() != id_column).collect();
+
+        match self.dialect {
+            SqlDialect::Sqlite | SqlDialect::Postgres => {
+                let action = if updates.is_empty() {
+                    "NOTHING".to_string()
+                } else {
+                    let sets: Vec<String> = updates.iter()
+                        .map(|k| {
+                            let quoted = self.quote_identifier(k);
+                            format!("{} = excluded.{}", quoted, quoted)
+                        })
+                        .collect();
+                    format!("UPDATE SET {}", sets.join(", "))
+                };
+                format!("{} ON CONFLICT ({}) DO {}", self.build_insert(table, keys), quoted_id, action)
+            }
+            SqlDialect::Mysql => {
+                // With nothing to update, assigning the ID to itself turns the duplicate into a no-op.
+                let sets: Vec<String> = if updates.is_empty() {
+                    vec![format!("{} = {}", quoted_id, quoted_id)]
+                } else {
+                    updates.iter()
+                        .map(|k| {
+                            let quoted = self.quote_identifier(k);
+                            format!("{} = VALUES({})", quoted, quoted)
+                        })
+                        .collect()
+                };
+                format!("{} ON DUPLICATE KEY UPDATE {}", self.build_insert(table, keys), sets.join(", "))
+            }
+            SqlDialect::Mssql => {
+                let quoted_table = self.quote_identifier(table);
+                let quoted_keys: Vec<String> = keys.iter().map(|k| self.quote_identifier(k)).collect();
+                let placeholders: Vec<String> = (0..keys.len()).map(|i| self.placeholder(i)).collect();
+                let source_cols: Vec<String> = quoted_keys.iter().map(|k| format!("source.{}", k)).collect();
+
+                let mut query = format!(
+                    "MERGE INTO {} AS target USING (VALUES ({})) AS source ({}) ON target.{} = source.{}",
+                    quoted_table, placeholders.join(", "), quoted_keys.join(", "), quoted_id, quoted_id
+                );
+                if !updates.is_empty() {
+                    let sets: Vec<String> = updates.iter()
+                        .map(|k| {
+                            let quoted = self.quote_identifier(k);
+                            format!("target.{} = source.{}", quoted, quoted)
+                        })
+                        .collect();
+                    query.push_str(&format!(" WHEN MATCHED THEN UPDATE SET {}", sets.join(", ")));
+                }
+                // MERGE must be terminated by a semicolon.
+                query.push_str(&format!(
+                    " WHEN NOT MATCHED THEN INSERT ({}) VALUES ({});",
+                    quoted_keys.join(", "), source_cols.join(", ")
+                ));
+                query
+            }
+        }
+    }
+
+    pub fn build_delete(&self, table: &str, id_column: &str) -> String {
+        let quoted_table = self.quote_identifier(table);
+        let quoted_id = self.quote_identifier(id_column);
+
+        format!("DELETE FROM {} WHERE {} = {}", quoted_table, quoted_id, self.placeholder(0))
+    }
 }
 
 #[cfg(test)]
@@ -99,4 +180,54 @@ mod tests {
         let sql = builder.build_insert("users", &keys);
         assert_eq!(sql, "INSERT INTO [users] ([name], [age]) VALUES (@P1, @P2)");
     }
+
+    #[test]
+    fn test_update() {
+        let keys = vec!["name".to_string(), "age".to_string()];
+        let sql = SqlBuilder::new(SqlDialect::Postgres).build_update("users", "id", &keys);
+        assert_eq!(sql, "UPDATE \"users\" SET \"name\" = $1, \"age\" = $2 WHERE \"id\" = $3");
+        let sql = SqlBuilder::new(SqlDialect::Mysql).build_update("users", "user_id", &keys);
+        assert_eq!(sql, "UPDATE `users` SET `name` = ?, `age` = ? WHERE `user_id` = ?");
+    }
+
+    #[test]
+    fn test_sqlite_upsert() {
+        let builder = SqlBuilder::new(SqlDialect::Sqlite);
+        let keys = vec!["id".to_string(), "name".to_string()];
+        let sql = builder.build_upsert("users", "id", &keys);
+        assert_eq!(sql, "INSERT INTO \"users\" (\"id\", \"name\") VALUES (?, ?) ON CONFLICT (\"id\") DO UPDATE SET \"name\" = excluded.\"name\"");
+
+        let sql = builder.build_upsert("users", "id", &["id".to_string()]);
+        assert_eq!(sql, "INSERT INTO \"users\" (\"id\") VALUES (?) ON CONFLICT (\"id\") DO NOTHING");
+
+        let keys = vec!["username".to_string(), "id".to_string()];
+        let sql = builder.build_upsert("users", "username", &keys);
+        assert_eq!(sql, "INSERT INTO \"users\" (\"username\", \"id\") VALUES (?, ?) ON CONFLICT (\"username\") DO UPDATE SET \"id\" = excluded.\"id\"");
+    }
+
+    #[test]
+    fn test_mysql_upsert() {
+        let builder = SqlBuilder::new(SqlDialect::Mysql);
+        let keys = vec!["id".to_string(), "name".to_string()];
+        let sql = builder.build_upsert("users", "id", &keys);
+        assert_eq!(sql, "INSERT INTO `users` (`id`, `name`) VALUES (?, ?) ON DUPLICATE KEY UPDATE `name` = VALUES(`name`)");
+    }
+
+    #[test]
+    fn test_mssql_upsert() {
+        let builder = SqlBuilder::new(SqlDialect::Mssql);
+        let keys = vec!["id".to_string(), "name".to_string()];
+        let sql = builder.build_upsert("users", "id", &keys);
+        assert_eq!(sql, "MERGE INTO [users] AS target USING (VALUES (@P1, @P2)) AS source ([id], [name]) ON target.[id] = source.[id] \
+            WHEN MATCHED THEN UPDATE SET target.[name] = source.[name] \
+            WHEN NOT MATCHED THEN INSERT ([id], [name]) VALUES (source.[id], source.[name]);");
+    }
+
+    #[test]
+    fn test_delete() {
+        let sql = SqlBuilder::new(SqlDialect::Mssql).build_delete("users", "id");
+        assert_eq!(sql, "DELETE FROM [users] WHERE [id] = @P1");
+        let sql = SqlBuilder::new(SqlDialect::Sqlite).build_delete("users", "username");
+        assert_eq!(sql, "DELETE FROM \"users\" WHERE \"username\" = ?");
+    }
 }
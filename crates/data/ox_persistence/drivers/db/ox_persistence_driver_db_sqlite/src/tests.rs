@@ -8,6 +8,7 @@ mod tests {
     use ox_type_converter::ValueType;
     use std::sync::Mutex;
     use rusqlite::Connection;
+    use ox_data_error::OxDataError;
 
     #[test]
     fn test_sqlite_persist_restore_fetch() {
@@ -54,4 +55,68 @@ mod tests {
         assert_eq!(fetched_ids.len(), 1);
         assert_eq!(fetched_ids[0], "1");
     }
+
+    #[test]
+    fn test_sqlite_upsert_update_delete() {
+        let driver = SqlitePersistenceDriver {
+            conn: Mutex::new(Some(Connection::open_in_memory().unwrap())),
+            connection_string: Mutex::new(":memory:".to_string())
+        };
+        driver.conn.lock().unwrap().as_mut().unwrap()
+            .execute("CREATE TABLE people (id TEXT PRIMARY KEY, name TEXT, age INTEGER)", []).unwrap();
+
+        let mut data = HashMap::new();
+        data.insert("id".to_string(), ("1".to_string(), ValueType::String, HashMap::new()));
+        data.insert("name".to_string(), ("Bob".to_string(), ValueType::String, HashMap::new()));
+        data.insert("age".to_string(), ("40".to_string(), ValueType::Integer, HashMap::new()));
+
+        // Saving the same object twice replaces it instead of failing on the primary key.
+        driver.upsert(&data, "people", "id").expect("First upsert failed");
+        data.insert("age".to_string(), ("41".to_string(), ValueType::Integer, HashMap::new()));
+        driver.upsert(&data, "people", "id").expect("Second upsert failed");
+        assert_eq!(driver.restore("people", "1").unwrap().get("age").unwrap().0, "41");
+
+        let mut changes = HashMap::new();
+        changes.insert("name".to_string(), ("Robert".to_string(), ValueType::String, HashMap::new()));
+        driver.update(&changes, "people", "id", "1").expect("Update failed");
+        let restored = driver.restore("people", "1").unwrap();
+        assert_eq!(restored.get("name").unwrap().0, "Robert");
+        assert_eq!(restored.get("age").unwrap().0, "41");
+        assert!(matches!(driver.update(&changes, "people", "id", "2"), Err(OxDataError::NotFound(_))));
+
+        driver.delete("people", "id", "1").expect("Delete failed");
+        assert!(driver.restore("people", "1").is_err());
+        assert!(matches!(driver.delete("people", "id", "1"), Err(OxDataError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_sqlite_upsert_uses_identifier_column() {
+        let driver = SqlitePersistenceDriver {
+            conn: Mutex::new(Some(Connection::open_in_memory().unwrap())),
+            connection_string: Mutex::new(":memory:".to_string())
+        };
+        driver.conn.lock().unwrap().as_mut().unwrap()
+            .execute("CREATE TABLE users (username TEXT PRIMARY KEY, name TEXT)", []).unwrap();
+        let name_of = |username: &str| -> Option<String> {
+            driver.conn.lock().unwrap().as_ref().unwrap()
+                .query_row("SELECT name FROM users WHERE username = ?", [username], |r| r.get(0))
+                .ok()
+        };
+
+        let mut data = HashMap::new();
+        data.insert("username".to_string(), ("alice".to_string(), ValueType::String, HashMap::new()));
+        data.insert("name".to_string(), ("Alice".to_string(), ValueType::String, HashMap::new()));
+        driver.upsert(&data, "users", "username").expect("First upsert failed");
+        data.insert("name".to_string(), ("Alice Smith".to_string(), ValueType::String, HashMap::new()));
+        driver.upsert(&data, "users", "username").expect("Second upsert failed");
+        assert_eq!(name_of("alice").as_deref(), Some("Alice Smith"));
+
+        let mut changes = HashMap::new();
+        changes.insert("name".to_string(), ("Al".to_string(), ValueType::String, HashMap::new()));
+        driver.update(&changes, "users", "username", "alice").expect("Update failed");
+        assert_eq!(name_of("alice").as_deref(), Some("Al"));
+
+        driver.delete("users", "username", "alice").expect("Delete failed");
+        assert_eq!(name_of("alice"), None);
+    }
 }
@@ -148,6 +148,59 @@ impl PersistenceDriver for FlatfileDriver {
         Ok(matching_ids)
     }
 
+    fn update(
+        &self,
+        serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+        location: &str,
+        id_field: &str,
+        id: &str,
+    ) -> Result<(), OxDataError> {
+        let (headers, mut rows) = read_rows(location)?;
+        let id_idx = id_column(&headers, id_field)?;
+
+        let row = rows.iter_mut()
+            .find(|r| r.get(id_idx) == Some(id))
+            .ok_or_else(|| OxDataError::NotFound(format!("Object with id {} not found in {}", id, location)))?;
+        // The ID itself is not changed by an update.
+        let mut changes = serializable_map.clone();
+        changes.retain(|k, _| !k.eq_ignore_ascii_case(id_field));
+        *row = merge_row(&headers, &changes, Some(&*row));
+
+        write_rows(location, &headers, &rows)
+    }
+
+    fn upsert(
+        &self,
+        serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+        location: &str,
+        id_field: &str,
+    ) -> Result<(), OxDataError> {
+        let (headers, mut rows) = read_rows(location)?;
+        let id_idx = id_column(&headers, id_field)?;
+        let id = serializable_map.get(&headers[id_idx]).map(|(v, _, _)| v.as_str())
+            .ok_or_else(|| OxDataError::ValidationError(format!("Upsert requires a '{}' field", id_field)))?;
+
+        match rows.iter_mut().find(|r| r.get(id_idx) == Some(id)) {
+            Some(row) => *row = merge_row(&headers, serializable_map, Some(&*row)),
+            None => rows.push(merge_row(&headers, serializable_map, None)),
+        }
+
+        write_rows(location, &headers, &rows)
+    }
+
+    fn delete(&self, location: &str, id_field: &str, id: &str) -> Result<(), OxDataError> {
+        let (headers, mut rows) = read_rows(location)?;
+        let id_idx = id_column(&headers, id_field)?;
+
+        let before = rows.len();
+        rows.retain(|r| r.get(id_idx) != Some(id));
+        if rows.len() == before {
+            return Err(OxDataError::NotFound(format!("Object with id {} not found in {}", id, location)));
+        }
+
+        write_rows(location, &headers, &rows)
+    }
+
     fn notify_lock_status_change(&self, lock_status: &str, gdo_id: &str) {
         println!("FlatfileDriver: GDO {} lock status changed to {}", gdo_id, lock_status);
     }
@@ -227,11 +280,134 @@ impl PersistenceDriver for FlatfileDriver {
     }
 }
 
+/// Reads the header and every row of the dataset file at `location`.
+fn read_rows(location: &str) -> Result<(csv::StringRecord, Vec<csv::StringRecord>), OxDataError> {
+    let file = fs::File::open(location).map_err(|e| OxDataError::InternalError(format!("Failed to open file {}: {}", location, e)))?;
+    let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(file);
+    let headers = reader.headers().map_err(|e| OxDataError::InternalError(e.to_string()))?.clone();
+    let rows = reader.records()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| OxDataError::InternalError(e.to_string()))?;
+    Ok((headers, rows))
+}
+
+/// Rewrites the dataset file at `location` with the given header and rows.
+fn write_rows(location: &str, headers: &csv::StringRecord, rows: &[csv::StringRecord]) -> Result<(), OxDataError> {
+    let mut writer = csv::WriterBuilder::new().from_path(location).map_err(|e| OxDataError::InternalError(e.to_string()))?;
+    writer.write_record(headers).map_err(|e| OxDataError::InternalError(e.to_string()))?;
+    for row in rows {
+        writer.write_record(row).map_err(|e| OxDataError::InternalError(e.to_string()))?;
+    }
+    writer.flush().map_err(|e| OxDataError::InternalError(e.to_string()))
+}
+
+fn id_column(headers: &csv::StringRecord, id_field: &str) -> Result<usize, OxDataError> {
+    headers.iter().position(|h| h.eq_ignore_ascii_case(id_field))
+        .ok_or_else(|| OxDataError::InternalError(format!("Dataset does not have an '{}' column", id_field)))
+}
+
+/// Builds a row in header order from `map`, keeping the values of `existing` for columns the map lacks.
+fn merge_row(
+    headers: &csv::StringRecord,
+    map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+    existing: Option<&csv::StringRecord>,
+) -> csv::StringRecord {
+    headers.iter().enumerate()
+        .map(|(i, col_name)| match map.get(col_name) {
+            Some((v, _, _)) => v.clone(),
+            None => existing.and_then(|r| r.get(i)).unwrap_or_default().to_string(),
+        })
+        .collect()
+}
+
 // --- FFI Exports ---
 
 use ox_persistence::OxBuffer;
 use std::ffi::CString;
 
+#[no_mangle]
+pub unsafe extern "C" fn ox_driver_update(
+    ctx: *mut c_void, 
+    data_json: *const c_char, 
+    location: *const c_char,
+    id_field: *const c_char,
+    id: *const c_char
+) -> i32 {
+    let driver = &*(ctx as *mut FlatfileDriver);
+    let data_str = CStr::from_ptr(data_json).to_string_lossy();
+    let location_str = CStr::from_ptr(location).to_string_lossy();
+    let id_field_str = CStr::from_ptr(id_field).to_string_lossy();
+    let id_str = CStr::from_ptr(id).to_string_lossy();
+
+    match serde_json::from_str::<HashMap<String, (String, ValueType, HashMap<String, String>)>>(&data_str) {
+        Ok(map) => {
+            match driver.update(&map, &location_str, &id_field_str, &id_str) {
+                Ok(_) => 0,
+                Err(OxDataError::NotFound(_)) => 404,
+                Err(e) => {
+                    eprintln!("Update error: {}", e);
+                    -1
+                }
+            }
+        },
+        Err(e) => {
+            eprintln!("JSON parse error: {}", e);
+            -2
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ox_driver_upsert(
+    ctx: *mut c_void, 
+    data_json: *const c_char, 
+    location: *const c_char,
+    id_field: *const c_char
+) -> i32 {
+    let driver = &*(ctx as *mut FlatfileDriver);
+    let data_str = CStr::from_ptr(data_json).to_string_lossy();
+    let location_str = CStr::from_ptr(location).to_string_lossy();
+    let id_field_str = CStr::from_ptr(id_field).to_string_lossy();
+
+    match serde_json::from_str::<HashMap<String, (String, ValueType, HashMap<String, String>)>>(&data_str) {
+        Ok(map) => {
+            match driver.upsert(&map, &location_str, &id_field_str) {
+                Ok(_) => 0,
+                Err(e) => {
+                    eprintln!("Upsert error: {}", e);
+                    -1
+                }
+            }
+        },
+        Err(e) => {
+            eprintln!("JSON parse error: {}", e);
+            -2
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ox_driver_delete(
+    ctx: *mut c_void, 
+    location: *const c_char, 
+    id_field: *const c_char,
+    id: *const c_char
+) -> i32 {
+    let driver = &*(ctx as *mut FlatfileDriver);
+    let location_str = CStr::from_ptr(location).to_string_lossy();
+    let id_field_str = CStr::from_ptr(id_field).to_string_lossy();
+    let id_str = CStr::from_ptr(id).to_string_lossy();
+
+    match driver.delete(&location_str, &id_field_str, &id_str) {
+        Ok(_) => 0,
+        Err(OxDataError::NotFound(_)) => 404,
+        Err(e) => {
+            eprintln!("Delete error: {}", e);
+            -1
+        }
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn ox_driver_get_driver_metadata() -> *mut c_char {
     let mut compatible_modules = HashMap::new();
@@ -376,4 +552,46 @@ pub extern "C" fn ox_driver_get_config_schema() -> *mut c_char {
 #[no_mangle]
 pub unsafe extern "C" fn ox_driver_free_buffer(buf: OxBuffer) {
     ox_persistence::free_ox_buffer(buf);
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(username: &str, name: &str) -> HashMap<String, (String, ValueType, HashMap<String, String>)> {
+        HashMap::from([
+            ("username".to_string(), (username.to_string(), ValueType::String, HashMap::new())),
+            ("name".to_string(), (name.to_string(), ValueType::String, HashMap::new())),
+        ])
+    }
+
+    #[test]
+    fn test_upsert_matches_on_identifier_field() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("users.csv");
+        fs::write(&file_path, "username,name\nalice,Alice\n").unwrap();
+        let location = file_path.to_str().unwrap();
+
+        let driver = FlatfileDriver;
+        driver.upsert(&record("alice", "Alice Smith"), location, "username").unwrap();
+        driver.upsert(&record("bob", "Bob"), location, "username").unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "username,name\nalice,Alice Smith\nbob,Bob\n");
+
+        driver.update(&record("mallory", "Bobby"), location, "username", "bob").unwrap();
+        driver.delete(location, "username", "alice").unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "username,name\nbob,Bobby\n");
+
+        let err = driver.delete(location, "username", "alice").unwrap_err();
+        assert!(matches!(err, OxDataError::NotFound(_)));
+    }
+
+    #[test]
+    fn test_upsert_requires_identifier_column() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("users.csv");
+        fs::write(&file_path, "id,name\n1,Alice\n").unwrap();
+
+        let err = FlatfileDriver.upsert(&record("alice", "Alice"), file_path.to_str().unwrap(), "username").unwrap_err();
+        assert!(matches!(err, OxDataError::InternalError(_)));
+    }
+}
@@ -6,6 +6,9 @@ use std::ffi::{c_void, CString, CStr};
 use libc::c_char;
 use std::sync::Arc;
 use ox_fileproc::serde_json;
+use serde_json::{Value, Map, Number};
+use std::fs;
+use std::path::Path;
 
 pub struct JsonPersistenceDriver;
 
@@ -33,57 +36,75 @@ impl JsonPersistenceDriver {
     }
 }
 
+/// Reads the array of records stored at `location`; a missing or empty file has none.
+fn load_records(location: &str) -> Result<Vec<Value>, OxDataError> {
+    let file_path = Path::new(location);
+    if !file_path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(file_path).map_err(|e| OxDataError::InternalError(e.to_string()))?;
+    if content.trim().is_empty() {
+        Ok(Vec::new())
+    } else {
+        serde_json::from_str(&content).map_err(|e| OxDataError::InternalError(e.to_string()))
+    }
+}
+
+fn save_records(location: &str, records: &[Value]) -> Result<(), OxDataError> {
+    let new_content = serde_json::to_string_pretty(records).map_err(|e| OxDataError::InternalError(e.to_string()))?;
+    fs::write(location, new_content).map_err(|e| OxDataError::InternalError(e.to_string()))
+}
+
+/// Converts a serializable map to a JSON object, using the declared types for the values.
+fn to_record(
+    serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+) -> Result<Map<String, Value>, OxDataError> {
+    let mut new_record = Map::new();
+    for (key, (val_str, val_type, _)) in serializable_map {
+        let json_val = match val_type {
+            ValueType::Integer => {
+                let num = val_str.parse::<i64>().map_err(|e| OxDataError::InternalError(e.to_string()))?;
+                Value::Number(Number::from(num))
+            },
+            ValueType::Float => {
+                let num = val_str.parse::<f64>().map_err(|e| OxDataError::InternalError(e.to_string()))?;
+                Number::from_f64(num).map(Value::Number).unwrap_or(Value::Null)
+            },
+            ValueType::Boolean => {
+                let b = val_str.parse::<bool>().map_err(|e| OxDataError::InternalError(e.to_string()))?;
+                Value::Bool(b)
+            },
+            ValueType::List(_) | ValueType::Map => {
+                 serde_json::from_str(val_str).unwrap_or(Value::String(val_str.clone()))
+            },
+            _ => Value::String(val_str.clone()),
+        };
+        new_record.insert(key.clone(), json_val);
+    }
+    Ok(new_record)
+}
+
+fn id_string(id_val: &Value) -> String {
+    match id_val {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        _ => id_val.to_string(),
+    }
+}
+
+fn record_id(record: &Value, id_field: &str) -> Option<String> {
+    record.get(id_field).map(id_string)
+}
+
 impl PersistenceDriver for JsonPersistenceDriver {
     fn persist(
         &self,
         serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>, 
         location: &str,
     ) -> Result<(), OxDataError> {
-        use std::fs;
-        use std::path::Path;
-        use serde_json::{Value, Map, Number};
-
-        let file_path = Path::new(location);
-        let mut records: Vec<Value> = if file_path.exists() {
-            let content = fs::read_to_string(file_path).map_err(|e| OxDataError::InternalError(e.to_string()))?;
-            if content.trim().is_empty() {
-                Vec::new()
-            } else {
-                serde_json::from_str(&content).map_err(|e| OxDataError::InternalError(e.to_string()))?
-            }
-        } else {
-            Vec::new()
-        };
-
-        let mut new_record = Map::new();
-        for (key, (val_str, val_type, _)) in serializable_map {
-            let json_val = match val_type {
-                ValueType::Integer => {
-                    let num = val_str.parse::<i64>().map_err(|e| OxDataError::InternalError(e.to_string()))?;
-                    Value::Number(Number::from(num))
-                },
-                ValueType::Float => {
-                    let num = val_str.parse::<f64>().map_err(|e| OxDataError::InternalError(e.to_string()))?;
-                    Number::from_f64(num).map(Value::Number).unwrap_or(Value::Null)
-                },
-                ValueType::Boolean => {
-                    let b = val_str.parse::<bool>().map_err(|e| OxDataError::InternalError(e.to_string()))?;
-                    Value::Bool(b)
-                },
-                ValueType::List(_) | ValueType::Map => {
-                     serde_json::from_str(val_str).unwrap_or(Value::String(val_str.clone()))
-                },
-                _ => Value::String(val_str.clone()),
-            };
-            new_record.insert(key.clone(), json_val);
-        }
-
-        records.push(Value::Object(new_record));
-
-        let new_content = serde_json::to_string_pretty(&records).map_err(|e| OxDataError::InternalError(e.to_string()))?;
-        fs::write(file_path, new_content).map_err(|e| OxDataError::InternalError(e.to_string()))?;
-
-        Ok(())
+        let mut records = load_records(location)?;
+        records.push(Value::Object(to_record(serializable_map)?));
+        save_records(location, &records)
     }
 
     fn restore(
@@ -210,6 +231,56 @@ impl PersistenceDriver for JsonPersistenceDriver {
         Ok(matching_ids)
     }
 
+    fn update(
+        &self,
+        serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+        location: &str,
+        id_field: &str,
+        id: &str,
+    ) -> Result<(), OxDataError> {
+        let mut records = load_records(location)?;
+        let record = records.iter_mut()
+            .find(|r| record_id(r, id_field).as_deref() == Some(id))
+            .ok_or_else(|| OxDataError::NotFound(format!("Object with id {} not found in {}", id, location)))?;
+
+        if let Value::Object(existing) = record {
+            for (k, v) in to_record(serializable_map)? {
+                if k != id_field {
+                    existing.insert(k, v);
+                }
+            }
+        }
+        save_records(location, &records)
+    }
+
+    fn upsert(
+        &self,
+        serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+        location: &str,
+        id_field: &str,
+    ) -> Result<(), OxDataError> {
+        let new_record = to_record(serializable_map)?;
+        let id = new_record.get(id_field).map(id_string)
+            .ok_or_else(|| OxDataError::ValidationError(format!("Upsert requires an '{}' field", id_field)))?;
+
+        let mut records = load_records(location)?;
+        match records.iter_mut().find(|r| record_id(r, id_field).as_deref() == Some(id.as_str())) {
+            Some(Value::Object(existing)) => existing.extend(new_record),
+            _ => records.push(Value::Object(new_record)),
+        }
+        save_records(location, &records)
+    }
+
+    fn delete(&self, location: &str, id_field: &str, id: &str) -> Result<(), OxDataError> {
+        let mut records = load_records(location)?;
+        let before = records.len();
+        records.retain(|r| record_id(r, id_field).as_deref() != Some(id));
+        if records.len() == before {
+            return Err(OxDataError::NotFound(format!("Object with id {} not found in {}", id, location)));
+        }
+        save_records(location, &records)
+    }
+
     fn notify_lock_status_change(&self, lock_status: &str, gdo_id: &str) {
         println!("JsonDriver: GDO {} lock status changed to {}", gdo_id, lock_status);
     }
@@ -335,6 +406,89 @@ pub unsafe extern "C" fn ox_driver_fetch(
     }
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn ox_driver_update(
+    ctx: *mut c_void, 
+    data_json: *const c_char, 
+    location: *const c_char,
+    id_field: *const c_char,
+    id: *const c_char
+) -> i32 {
+    let driver = &*(ctx as *mut JsonPersistenceDriver);
+    let data_str = CStr::from_ptr(data_json).to_string_lossy();
+    let location_str = CStr::from_ptr(location).to_string_lossy();
+    let id_field_str = CStr::from_ptr(id_field).to_string_lossy();
+    let id_str = CStr::from_ptr(id).to_string_lossy();
+
+    match serde_json::from_str::<HashMap<String, (String, ValueType, HashMap<String, String>)>>(&data_str) {
+        Ok(map) => {
+            match driver.update(&map, &location_str, &id_field_str, &id_str) {
+                Ok(_) => 0,
+                Err(OxDataError::NotFound(_)) => 404,
+                Err(e) => {
+                    eprintln!("Update error: {}", e);
+                    -1
+                }
+            }
+        },
+        Err(e) => {
+            eprintln!("JSON parse error: {}", e);
+            -2
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ox_driver_upsert(
+    ctx: *mut c_void, 
+    data_json: *const c_char, 
+    location: *const c_char,
+    id_field: *const c_char
+) -> i32 {
+    let driver = &*(ctx as *mut JsonPersistenceDriver);
+    let data_str = CStr::from_ptr(data_json).to_string_lossy();
+    let location_str = CStr::from_ptr(location).to_string_lossy();
+    let id_field_str = CStr::from_ptr(id_field).to_string_lossy();
+
+    match serde_json::from_str::<HashMap<String, (String, ValueType, HashMap<String, String>)>>(&data_str) {
+        Ok(map) => {
+            match driver.upsert(&map, &location_str, &id_field_str) {
+                Ok(_) => 0,
+                Err(e) => {
+                    eprintln!("Upsert error: {}", e);
+                    -1
+                }
+            }
+        },
+        Err(e) => {
+            eprintln!("JSON parse error: {}", e);
+            -2
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ox_driver_delete(
+    ctx: *mut c_void, 
+    location: *const c_char, 
+    id_field: *const c_char,
+    id: *const c_char
+) -> i32 {
+    let driver = &*(ctx as *mut JsonPersistenceDriver);
+    let location_str = CStr::from_ptr(location).to_string_lossy();
+    let id_field_str = CStr::from_ptr(id_field).to_string_lossy();
+    let id_str = CStr::from_ptr(id).to_string_lossy();
+
+    match driver.delete(&location_str, &id_field_str, &id_str) {
+        Ok(_) => 0,
+        Err(OxDataError::NotFound(_)) => 404,
+        Err(e) => {
+            eprintln!("Delete error: {}", e);
+            -1
+        }
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn ox_driver_get_driver_metadata() -> *mut c_char {
     let mut compatible_modules = HashMap::new();
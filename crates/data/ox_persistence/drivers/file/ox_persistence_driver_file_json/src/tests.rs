@@ -7,6 +7,7 @@ mod tests {
     use tempfile::NamedTempFile;
     use std::collections::HashMap;
     use ox_type_converter::ValueType;
+    use ox_data_error::OxDataError;
 
     #[test]
     fn test_json_persist_restore_fetch() {
@@ -42,4 +43,57 @@ mod tests {
         let fetched_none = driver.fetch(&filter_none, &path).expect("Fetch failed");
         assert!(fetched_none.is_empty());
     }
+
+    #[test]
+    fn test_json_upsert_update_delete() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let path = temp_file.path().to_str().unwrap().to_string();
+        let driver = JsonPersistenceDriver;
+
+        let mut data = HashMap::new();
+        data.insert("id".to_string(), ("7".to_string(), ValueType::String, HashMap::new()));
+        data.insert("name".to_string(), ("Alice".to_string(), ValueType::String, HashMap::new()));
+        data.insert("age".to_string(), ("30".to_string(), ValueType::Integer, HashMap::new()));
+
+        driver.upsert(&data, &path, "id").expect("First upsert failed");
+        data.insert("age".to_string(), ("31".to_string(), ValueType::Integer, HashMap::new()));
+        driver.upsert(&data, &path, "id").expect("Second upsert failed");
+        let mut filter = HashMap::new();
+        filter.insert("name".to_string(), ("Alice".to_string(), ValueType::String, HashMap::new()));
+        assert_eq!(driver.fetch(&filter, &path).unwrap(), vec!["7".to_string()]);
+        assert_eq!(driver.restore(&path, "7").unwrap().get("age").unwrap().0, "31");
+
+        let mut changes = HashMap::new();
+        changes.insert("name".to_string(), ("Alicia".to_string(), ValueType::String, HashMap::new()));
+        driver.update(&changes, &path, "id", "7").expect("Update failed");
+        let restored = driver.restore(&path, "7").unwrap();
+        assert_eq!(restored.get("name").unwrap().0, "Alicia");
+        assert_eq!(restored.get("age").unwrap().0, "31");
+        assert!(matches!(driver.update(&changes, &path, "id", "8"), Err(OxDataError::NotFound(_))));
+
+        driver.delete(&path, "id", "7").expect("Delete failed");
+        assert!(driver.restore(&path, "7").is_err());
+        assert!(matches!(driver.delete(&path, "id", "7"), Err(OxDataError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_json_upsert_uses_identifier_field() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let path = temp_file.path().to_str().unwrap().to_string();
+        let driver = JsonPersistenceDriver;
+
+        let mut data = HashMap::new();
+        data.insert("username".to_string(), ("alice".to_string(), ValueType::String, HashMap::new()));
+        data.insert("name".to_string(), ("Alice".to_string(), ValueType::String, HashMap::new()));
+        driver.upsert(&data, &path, "username").expect("First upsert failed");
+        data.insert("name".to_string(), ("Alice Smith".to_string(), ValueType::String, HashMap::new()));
+        driver.upsert(&data, &path, "username").expect("Second upsert failed");
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content.matches("\"username\"").count(), 1, "Unexpected records: {}", content);
+        assert!(content.contains("Alice Smith"));
+
+        driver.delete(&path, "username", "alice").expect("Delete failed");
+        assert!(matches!(driver.delete(&path, "username", "alice"), Err(OxDataError::NotFound(_))));
+    }
 }
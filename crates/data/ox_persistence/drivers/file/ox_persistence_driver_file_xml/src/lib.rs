@@ -9,6 +9,40 @@ use ox_fileproc::serde_json;
 
 pub struct XmlPersistenceDriver;
 
+/// Reads the records stored at `location`; a missing or empty file has none.
+fn load_records(location: &str) -> Result<RecordsWrapper, OxDataError> {
+    use std::fs;
+    use std::path::Path;
+
+    let file_path = Path::new(location);
+    if !file_path.exists() {
+        return Ok(RecordsWrapper { record: Vec::new() });
+    }
+    let content = fs::read_to_string(file_path).map_err(|e| OxDataError::InternalError(e.to_string()))?;
+    if content.trim().is_empty() {
+        Ok(RecordsWrapper { record: Vec::new() })
+    } else {
+        quick_xml::de::from_str(&content).map_err(|e| OxDataError::InternalError(format!("XML parse error: {}", e)))
+    }
+}
+
+fn save_records(location: &str, wrapper: &RecordsWrapper) -> Result<(), OxDataError> {
+    let new_content = quick_xml::se::to_string(wrapper).map_err(|e| OxDataError::InternalError(e.to_string()))?;
+    std::fs::write(location, new_content).map_err(|e| OxDataError::InternalError(e.to_string()))
+}
+
+fn to_fields(serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>) -> HashMap<String, FieldData> {
+    let mut fields = HashMap::new();
+    for (k, (v, vt, meta)) in serializable_map {
+        fields.insert(k.clone(), FieldData {
+            value: v.clone(),
+            value_type: vt.as_str().to_string(),
+            metadata: meta.clone(),
+        });
+    }
+    fields
+}
+
 impl PersistenceDriver for XmlPersistenceDriver {
 
     fn persist(
@@ -16,36 +50,9 @@ impl PersistenceDriver for XmlPersistenceDriver {
         serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>, 
         location: &str,
     ) -> Result<(), OxDataError> {
-        use std::fs;
-        use std::path::Path;
-
-        let file_path = Path::new(location);
-        let mut wrapper : RecordsWrapper = if file_path.exists() {
-            let content = fs::read_to_string(file_path).map_err(|e| OxDataError::InternalError(e.to_string()))?;
-            if content.trim().is_empty() {
-                RecordsWrapper { record: Vec::new() }
-            } else {
-                 quick_xml::de::from_str(&content).map_err(|e| OxDataError::InternalError(format!("XML parse error: {}", e)))?
-            }
-        } else {
-            RecordsWrapper { record: Vec::new() }
-        };
-
-        let mut fields = HashMap::new();
-        for (k, (v, vt, meta)) in serializable_map {
-            fields.insert(k.clone(), FieldData {
-                value: v.clone(),
-                value_type: vt.as_str().to_string(),
-                metadata: meta.clone(),
-            });
-        }
-
-        wrapper.record.push(Record { fields });
-
-        let new_content = quick_xml::se::to_string(&wrapper).map_err(|e| OxDataError::InternalError(e.to_string()))?;
-        fs::write(file_path, new_content).map_err(|e| OxDataError::InternalError(e.to_string()))?;
-
-        Ok(())
+        let mut wrapper = load_records(location)?;
+        wrapper.record.push(Record { fields: to_fields(serializable_map) });
+        save_records(location, &wrapper)
     }
 
     fn restore(
@@ -114,6 +121,54 @@ impl PersistenceDriver for XmlPersistenceDriver {
         Ok(matching_ids)
     }
 
+    fn update(
+        &self,
+        serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+        location: &str,
+        id_field: &str,
+        id: &str,
+    ) -> Result<(), OxDataError> {
+        let mut wrapper = load_records(location)?;
+        let record = wrapper.record.iter_mut()
+            .find(|r| r.value(id_field) == Some(id))
+            .ok_or_else(|| OxDataError::NotFound(format!("Object with id {} not found in {}", id, location)))?;
+
+        for (k, fd) in to_fields(serializable_map) {
+            if k != id_field {
+                record.fields.insert(k, fd);
+            }
+        }
+        save_records(location, &wrapper)
+    }
+
+    fn upsert(
+        &self,
+        serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+        location: &str,
+        id_field: &str,
+    ) -> Result<(), OxDataError> {
+        let id = serializable_map.get(id_field).map(|(v, _, _)| v.as_str())
+            .ok_or_else(|| OxDataError::ValidationError(format!("Upsert requires a '{}' field", id_field)))?;
+
+        let mut wrapper = load_records(location)?;
+        let fields = to_fields(serializable_map);
+        match wrapper.record.iter_mut().find(|r| r.value(id_field) == Some(id)) {
+            Some(record) => record.fields.extend(fields),
+            None => wrapper.record.push(Record { fields }),
+        }
+        save_records(location, &wrapper)
+    }
+
+    fn delete(&self, location: &str, id_field: &str, id: &str) -> Result<(), OxDataError> {
+        let mut wrapper = load_records(location)?;
+        let before = wrapper.record.len();
+        wrapper.record.retain(|r| r.value(id_field) != Some(id));
+        if wrapper.record.len() == before {
+            return Err(OxDataError::NotFound(format!("Object with id {} not found in {}", id, location)));
+        }
+        save_records(location, &wrapper)
+    }
+
     fn notify_lock_status_change(&self, lock_status: &str, gdo_id: &str) {
          println!("XmlDriver: GDO {} lock status changed to {}", gdo_id, lock_status);
     }
@@ -237,6 +292,89 @@ pub unsafe extern "C" fn ox_driver_fetch(
     }
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn ox_driver_update(
+    ctx: *mut c_void, 
+    data_json: *const c_char, 
+    location: *const c_char,
+    id_field: *const c_char,
+    id: *const c_char
+) -> i32 {
+    let driver = &*(ctx as *mut XmlPersistenceDriver);
+    let data_str = CStr::from_ptr(data_json).to_string_lossy();
+    let location_str = CStr::from_ptr(location).to_string_lossy();
+    let id_field_str = CStr::from_ptr(id_field).to_string_lossy();
+    let id_str = CStr::from_ptr(id).to_string_lossy();
+
+    match serde_json::from_str::<HashMap<String, (String, ValueType, HashMap<String, String>)>>(&data_str) {
+        Ok(map) => {
+            match driver.update(&map, &location_str, &id_field_str, &id_str) {
+                Ok(_) => 0,
+                Err(OxDataError::NotFound(_)) => 404,
+                Err(e) => {
+                    eprintln!("Update error: {}", e);
+                    -1
+                }
+            }
+        },
+        Err(e) => {
+            eprintln!("JSON parse error: {}", e);
+            -2
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ox_driver_upsert(
+    ctx: *mut c_void, 
+    data_json: *const c_char, 
+    location: *const c_char,
+    id_field: *const c_char
+) -> i32 {
+    let driver = &*(ctx as *mut XmlPersistenceDriver);
+    let data_str = CStr::from_ptr(data_json).to_string_lossy();
+    let location_str = CStr::from_ptr(location).to_string_lossy();
+    let id_field_str = CStr::from_ptr(id_field).to_string_lossy();
+
+    match serde_json::from_str::<HashMap<String, (String, ValueType, HashMap<String, String>)>>(&data_str) {
+        Ok(map) => {
+            match driver.upsert(&map, &location_str, &id_field_str) {
+                Ok(_) => 0,
+                Err(e) => {
+                    eprintln!("Upsert error: {}", e);
+                    -1
+                }
+            }
+        },
+        Err(e) => {
+            eprintln!("JSON parse error: {}", e);
+            -2
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ox_driver_delete(
+    ctx: *mut c_void, 
+    location: *const c_char, 
+    id_field: *const c_char,
+    id: *const c_char
+) -> i32 {
+    let driver = &*(ctx as *mut XmlPersistenceDriver);
+    let location_str = CStr::from_ptr(location).to_string_lossy();
+    let id_field_str = CStr::from_ptr(id_field).to_string_lossy();
+    let id_str = CStr::from_ptr(id).to_string_lossy();
+
+    match driver.delete(&location_str, &id_field_str, &id_str) {
+        Ok(_) => 0,
+        Err(OxDataError::NotFound(_)) => 404,
+        Err(e) => {
+            eprintln!("Delete error: {}", e);
+            -1
+        }
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn ox_driver_get_driver_metadata() -> *mut c_char {
     let mut compatible_modules = HashMap::new();
@@ -304,9 +442,57 @@ struct Record {
     fields: HashMap<String, FieldData>,
 }
 
+impl Record {
+    fn value(&self, field: &str) -> Option<&str> {
+        self.fields.get(field).map(|fd| fd.value.as_str())
+    }
+}
+
 #[derive(serde::Serialize, serde::Deserialize)]
 struct FieldData {
     value: String,
     value_type: String, // String representation of ValueType
     metadata: HashMap<String, String>,
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(username: &str, name: &str) -> HashMap<String, (String, ValueType, HashMap<String, String>)> {
+        HashMap::from([
+            ("username".to_string(), (username.to_string(), ValueType::String, HashMap::new())),
+            ("name".to_string(), (name.to_string(), ValueType::String, HashMap::new())),
+        ])
+    }
+
+    fn names(location: &str) -> Vec<(String, String)> {
+        let mut names: Vec<_> = load_records(location).unwrap().record.iter()
+            .map(|r| (r.value("username").unwrap().to_string(), r.value("name").unwrap().to_string()))
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_upsert_matches_on_identifier_field() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("users.xml");
+        let location = file_path.to_str().unwrap();
+
+        let driver = XmlPersistenceDriver;
+        driver.upsert(&record("alice", "Alice"), location, "username").unwrap();
+        driver.upsert(&record("bob", "Bob"), location, "username").unwrap();
+        driver.upsert(&record("alice", "Alice Smith"), location, "username").unwrap();
+        assert_eq!(names(location), [
+            ("alice".to_string(), "Alice Smith".to_string()),
+            ("bob".to_string(), "Bob".to_string()),
+        ]);
+
+        driver.delete(location, "username", "alice").unwrap();
+        assert_eq!(names(location), [("bob".to_string(), "Bob".to_string())]);
+
+        let err = driver.upsert(&record("carol", "Carol"), location, "id").unwrap_err();
+        assert!(matches!(err, OxDataError::ValidationError(_)));
+    }
+}
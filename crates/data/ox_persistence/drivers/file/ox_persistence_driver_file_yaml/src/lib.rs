@@ -8,17 +8,19 @@ use ox_fileproc::{serde_json, serde_yaml};
 
 pub struct YamlPersistenceDriver;
 
-impl PersistenceDriver for YamlPersistenceDriver {
-    fn persist(
+impl YamlPersistenceDriver {
+    /// Updates the record whose `id_field` matches the map's value in place, or appends a new one.
+    fn write_record(
         &self,
-        serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>, 
+        serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
         location: &str,
+        id_field: &str,
     ) -> Result<(), OxDataError> {
         use std::fs;
         use std::path::Path;
         use ox_fileproc::RawFile;
 
-        let id_val = serializable_map.get("id").ok_or_else(|| OxDataError::InternalError("Missing 'id' in data object".to_string()))?;
+        let id_val = serializable_map.get(id_field).ok_or_else(|| OxDataError::InternalError(format!("Missing '{}' in data object", id_field)))?;
         let id = &id_val.0;
 
         let file_path = Path::new(location);
@@ -38,7 +40,7 @@ impl PersistenceDriver for YamlPersistenceDriver {
 
         // Construct query to find the list item with this ID.
         // Assuming list of objects: "[id=THE_ID]" should find the item.
-        let query = format!("[id={}]", id);
+        let query = format!("[{}={}]", id_field, id);
         
         // We need to collect cursors first because we cannot mutate raw_file while iterating cursors
         let cursors: Vec<_> = raw_file.find(&query).collect();
@@ -63,9 +65,9 @@ impl PersistenceDriver for YamlPersistenceDriver {
             // Safest: One update at a time, finding freshly.
             
             for (key, (val_str, _, _)) in serializable_map {
-                if key == "id" { continue; } // Don't update ID
+                if key == id_field { continue; } // Don't update ID
                 
-                let field_query = format!("[id={}]/{}", id, key);
+                let field_query = format!("[{}={}]/{}", id_field, id, key);
                 
                 // Check if field exists
                 let field_cursors: Vec<_> = raw_file.find(&field_query).collect();
@@ -142,6 +144,16 @@ impl PersistenceDriver for YamlPersistenceDriver {
 
         Ok(())
     }
+}
+
+impl PersistenceDriver for YamlPersistenceDriver {
+    fn persist(
+        &self,
+        serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+        location: &str,
+    ) -> Result<(), OxDataError> {
+        self.write_record(serializable_map, location, "id")
+    }
 
     fn restore(
         &self,
@@ -280,6 +292,57 @@ impl PersistenceDriver for YamlPersistenceDriver {
         Ok(matching_ids)
     }
 
+    fn update(
+        &self,
+        serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+        location: &str,
+        id_field: &str,
+        id: &str,
+    ) -> Result<(), OxDataError> {
+        use std::path::Path;
+        use ox_fileproc::RawFile;
+
+        let not_found = || OxDataError::NotFound(format!("Object with id {} not found in {}", id, location));
+        if !Path::new(location).exists() {
+            return Err(not_found());
+        }
+        let raw_file = RawFile::open(location).map_err(|e| OxDataError::InternalError(e.to_string()))?;
+        if raw_file.find(&format!("[{}={}]", id_field, id)).next().is_none() {
+            return Err(not_found());
+        }
+
+        // write_record() updates the fields of an existing record in place.
+        let mut record = serializable_map.clone();
+        record.insert(id_field.to_string(), (id.to_string(), ValueType::String, HashMap::new()));
+        self.write_record(&record, location, id_field)
+    }
+
+    fn upsert(
+        &self,
+        serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+        location: &str,
+        id_field: &str,
+    ) -> Result<(), OxDataError> {
+        // write_record() already matches records by ID, updating an existing one or appending a new one.
+        self.write_record(serializable_map, location, id_field)
+    }
+
+    fn delete(&self, location: &str, id_field: &str, id: &str) -> Result<(), OxDataError> {
+        use std::path::Path;
+        use ox_fileproc::RawFile;
+
+        let not_found = || OxDataError::NotFound(format!("Object with id {} not found in {}", id, location));
+        if !Path::new(location).exists() {
+            return Err(not_found());
+        }
+        let mut raw_file = RawFile::open(location).map_err(|e| OxDataError::InternalError(e.to_string()))?;
+
+        // The cursor spans the whole list item, so cutting it leaves the other records and their comments untouched.
+        let span = raw_file.find(&format!("[{}={}]", id_field, id)).next().map(|c| c.span.clone()).ok_or_else(not_found)?;
+        raw_file.update(span, "");
+        raw_file.save().map_err(|e| OxDataError::InternalError(e.to_string()))
+    }
+
     fn notify_lock_status_change(&self, lock_status: &str, gdo_id: &str) {
         println!("YamlDriver: GDO {} lock status changed to {}", gdo_id, lock_status);
     }
@@ -406,6 +469,89 @@ pub unsafe extern "C" fn ox_driver_fetch(
 
 
 
+#[no_mangle]
+pub unsafe extern "C" fn ox_driver_update(
+    ctx: *mut c_void, 
+    data_json: *const c_char, 
+    location: *const c_char,
+    id_field: *const c_char,
+    id: *const c_char
+) -> i32 {
+    let driver = &*(ctx as *mut YamlPersistenceDriver);
+    let data_str = CStr::from_ptr(data_json).to_string_lossy();
+    let location_str = CStr::from_ptr(location).to_string_lossy();
+    let id_field_str = CStr::from_ptr(id_field).to_string_lossy();
+    let id_str = CStr::from_ptr(id).to_string_lossy();
+
+    match serde_json::from_str::<HashMap<String, (String, ValueType, HashMap<String, String>)>>(&data_str) {
+        Ok(map) => {
+            match driver.update(&map, &location_str, &id_field_str, &id_str) {
+                Ok(_) => 0,
+                Err(OxDataError::NotFound(_)) => 404,
+                Err(e) => {
+                    eprintln!("Update error: {}", e);
+                    -1
+                }
+            }
+        },
+        Err(e) => {
+            eprintln!("JSON parse error: {}", e);
+            -2
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ox_driver_upsert(
+    ctx: *mut c_void, 
+    data_json: *const c_char, 
+    location: *const c_char,
+    id_field: *const c_char
+) -> i32 {
+    let driver = &*(ctx as *mut YamlPersistenceDriver);
+    let data_str = CStr::from_ptr(data_json).to_string_lossy();
+    let location_str = CStr::from_ptr(location).to_string_lossy();
+    let id_field_str = CStr::from_ptr(id_field).to_string_lossy();
+
+    match serde_json::from_str::<HashMap<String, (String, ValueType, HashMap<String, String>)>>(&data_str) {
+        Ok(map) => {
+            match driver.upsert(&map, &location_str, &id_field_str) {
+                Ok(_) => 0,
+                Err(e) => {
+                    eprintln!("Upsert error: {}", e);
+                    -1
+                }
+            }
+        },
+        Err(e) => {
+            eprintln!("JSON parse error: {}", e);
+            -2
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ox_driver_delete(
+    ctx: *mut c_void, 
+    location: *const c_char, 
+    id_field: *const c_char,
+    id: *const c_char
+) -> i32 {
+    let driver = &*(ctx as *mut YamlPersistenceDriver);
+    let location_str = CStr::from_ptr(location).to_string_lossy();
+    let id_field_str = CStr::from_ptr(id_field).to_string_lossy();
+    let id_str = CStr::from_ptr(id).to_string_lossy();
+
+    match driver.delete(&location_str, &id_field_str, &id_str) {
+        Ok(_) => 0,
+        Err(OxDataError::NotFound(_)) => 404,
+        Err(e) => {
+            eprintln!("Delete error: {}", e);
+            -1
+        }
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn ox_driver_get_driver_metadata() -> *mut c_char {
     let mut compatible_modules = HashMap::new();
@@ -498,4 +644,33 @@ mod tests {
         assert!(new_content.contains("name: updated"), "Value not updated");
         assert!(new_content.contains("# Inline comment"), "Inline comment missing");
     }
+
+    #[test]
+    fn test_upsert_matches_on_identifier_field() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("users.yaml");
+        fs::write(&file_path, "# Users\n- username: alice\n  name: Alice\n").unwrap();
+        let location = file_path.to_str().unwrap();
+
+        let driver = YamlPersistenceDriver;
+        let record = |username: &str, name: &str| {
+            HashMap::from([
+                ("username".to_string(), (username.to_string(), ValueType::String, HashMap::new())),
+                ("name".to_string(), (name.to_string(), ValueType::String, HashMap::new())),
+            ])
+        };
+        driver.upsert(&record("alice", "Alice Smith"), location, "username").unwrap();
+        driver.upsert(&record("bob", "Bob"), location, "username").unwrap();
+
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert!(content.contains("# Users"), "Comment missing");
+        assert!(content.contains("name: Alice Smith"), "Existing record not updated");
+        assert_eq!(content.matches("username:").count(), 2, "Unexpected records:\n{}", content);
+        assert!(content.contains("username: bob"), "New record not appended");
+
+        driver.delete(location, "username", "alice").unwrap();
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert!(!content.contains("alice"));
+        assert!(content.contains("username: bob"));
+    }
 }
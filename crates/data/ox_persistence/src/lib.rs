@@ -170,6 +170,9 @@ pub trait Persistent {
     fn persist(&mut self, driver_name: &str, location: &str) -> Result<(), OxDataError>;
     fn fetch(&self, driver_name: &str, location: &str) -> Result<Vec<GenericDataObject>, OxDataError>;
 
+    /// Removes the object from the datastore.
+    fn delete(&self, driver_name: &str, location: &str) -> Result<(), OxDataError>;
+
     /// Hydrates the object by loading its full data from the datastore.
     fn hydrate_object(&mut self, driver_name: &str, location: &str) -> Result<(), OxDataError>;
 }
@@ -192,6 +195,34 @@ pub trait PersistenceDriver {
     /// Fetches the unique IDs of objects matching a filter.
     fn fetch(&self, filter: &HashMap<String, (String, ValueType, HashMap<String, String>)>, location: &str) -> Result<Vec<String>, OxDataError>;
 
+    /// Overwrites the given fields of the existing object whose `id_field` is `id`.
+    /// Returns `OxDataError::NotFound` if no such object exists.
+    fn update(
+        &self,
+        _serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+        _location: &str,
+        _id_field: &str,
+        _id: &str,
+    ) -> Result<(), OxDataError> {
+        Err(OxDataError::DriverError("Update not supported by this driver".to_string()))
+    }
+
+    /// Inserts the object, or replaces the stored object with the same value in `id_field`.
+    fn upsert(
+        &self,
+        _serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+        _location: &str,
+        _id_field: &str,
+    ) -> Result<(), OxDataError> {
+        Err(OxDataError::DriverError("Upsert not supported by this driver".to_string()))
+    }
+
+    /// Deletes the object whose `id_field` is `id`.
+    /// Returns `OxDataError::NotFound` if no such object exists.
+    fn delete(&self, _location: &str, _id_field: &str, _id: &str) -> Result<(), OxDataError> {
+        Err(OxDataError::DriverError("Delete not supported by this driver".to_string()))
+    }
+
     fn notify_lock_status_change(&self, lock_status: &str, gdo_id: &str);
 
     fn prepare_datastore(&self, connection_info: &HashMap<String, String>) -> Result<(), OxDataError>;
@@ -213,7 +244,8 @@ impl Persistent for GenericDataObject {
         let registry = PERSISTENCE_DRIVER_REGISTRY.lock().unwrap();
         if let Some((driver, _)) = registry.get_driver(driver_name) {
             let map = self.to_serializable_map();
-            driver.persist(&map, location)?;
+            // Upsert so that saving an already-stored object replaces it rather than failing.
+            driver.upsert(&map, location, &self.identifier_name)?;
             // Implicitly consistent after persist
             Ok(())
         } else {
@@ -240,6 +272,17 @@ impl Persistent for GenericDataObject {
         }
     }
 
+    fn delete(&self, driver_name: &str, location: &str) -> Result<(), OxDataError> {
+        let registry = PERSISTENCE_DRIVER_REGISTRY.lock().unwrap();
+        if let Some((driver, _)) = registry.get_driver(driver_name) {
+            let id = self.get::<String>(&self.identifier_name)
+                .ok_or_else(|| OxDataError::InternalError(format!("Object missing identifier '{}'", self.identifier_name)))?;
+            driver.delete(location, &self.identifier_name, &id)
+        } else {
+            Err(OxDataError::DriverError(format!("Driver '{}' not found", driver_name)))
+        }
+    }
+
     /// Hydrates the object by loading its full data from the datastore.
     fn hydrate_object(&mut self, driver_name: &str, location: &str) -> Result<(), OxDataError> {
         let registry = PERSISTENCE_DRIVER_REGISTRY.lock().unwrap();
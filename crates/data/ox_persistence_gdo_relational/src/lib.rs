@@ -53,6 +53,32 @@ impl PersistenceDriver for GdoRelationalDriver {
         driver.fetch(filter, &self.internal_location)
     }
 
+    fn update(
+        &self,
+        serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+        _location: &str, // Location is handled by internal_location
+        id_field: &str,
+        id: &str,
+    ) -> Result<(), OxDataError> {
+        let (driver, _) = self.get_internal_driver()?;
+        driver.update(serializable_map, &self.internal_location, id_field, id)
+    }
+
+    fn upsert(
+        &self,
+        serializable_map: &HashMap<String, (String, ValueType, HashMap<String, String>)>,
+        _location: &str, // Location is handled by internal_location
+        id_field: &str,
+    ) -> Result<(), OxDataError> {
+        let (driver, _) = self.get_internal_driver()?;
+        driver.upsert(serializable_map, &self.internal_location, id_field)
+    }
+
+    fn delete(&self, _location: &str, id_field: &str, id: &str) -> Result<(), OxDataError> {
+        let (driver, _) = self.get_internal_driver()?;
+        driver.delete(&self.internal_location, id_field, id)
+    }
+
     fn notify_lock_status_change(&self, lock_status: &str, gdo_id: &str) {
         println!("GdoRelationalDriver: GDO {} lock status changed to {}", gdo_id, lock_status);
     }
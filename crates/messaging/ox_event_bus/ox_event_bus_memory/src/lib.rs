@@ -0,0 +1,342 @@
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use ox_event_bus::{BusError, EventBus, EventMessage, QueueConfig, PRIORITY_HEADER};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, Notify};
+use uuid::Uuid;
+
+/// Returns true if `topic` matches the subscription `pattern`.
+///
+/// Topics are `/`-separated levels, as on the MQTT bus. `+` matches exactly one level and
+/// a trailing `#` matches any number of remaining levels, including none.
+pub fn topic_matches(pattern: &str, topic: &str) -> bool {
+    let mut pattern_levels = pattern.split('/');
+    let mut topic_levels = topic.split('/');
+    loop {
+        match (pattern_levels.next(), topic_levels.next()) {
+            (Some("#"), _) => return pattern_levels.next().is_none(),
+            (Some("+"), Some(_)) => {}
+            (Some(p), Some(t)) if p == t => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct QueuedMessage {
+    priority: u8,
+    seq: u64,
+    message: EventMessage,
+}
+
+impl PartialEq for QueuedMessage {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for QueuedMessage {}
+impl PartialOrd for QueuedMessage {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for QueuedMessage {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // High priority first, then oldest first
+        self.priority.cmp(&other.priority).then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+/// A point-to-point queue. Every message goes to exactly one of the queue's subscribers.
+struct MemoryQueue {
+    state: Mutex<QueueState>,
+    notify: Notify,
+}
+
+struct QueueState {
+    config: QueueConfig,
+    heap: BinaryHeap<QueuedMessage>,
+    seq: u64,
+}
+
+impl MemoryQueue {
+    fn new(config: QueueConfig) -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(QueueState { config, heap: BinaryHeap::new(), seq: 0 }),
+            notify: Notify::new(),
+        })
+    }
+
+    fn configure(&self, config: QueueConfig) {
+        self.state.lock().unwrap().config = config;
+    }
+
+    fn push(&self, message: EventMessage, priority: u8) -> Result<(), BusError> {
+        let mut state = self.state.lock().unwrap();
+        if state.config.max_messages > 0 && state.heap.len() >= state.config.max_messages {
+            return Err(BusError::PublishError(format!("Queue {} is full", message.topic)));
+        }
+        let priority = priority.min(state.config.priority_levels.saturating_sub(1));
+        state.seq += 1;
+        let seq = state.seq;
+        state.heap.push(QueuedMessage { priority, seq, message });
+        drop(state);
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    async fn pop(&self) -> EventMessage {
+        loop {
+            let notified = self.notify.notified();
+            if let Some(queued) = self.state.lock().unwrap().heap.pop() {
+                return queued.message;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// In-process event bus for single-node deployments and tests.
+///
+/// Topic subscriptions are fan-out and accept `+`/`#` wildcards (see [`topic_matches`]).
+/// Queues are separate from topics: a queue delivers each message to one subscriber,
+/// highest priority first and FIFO within a priority. `max_messages` bounds a queue's
+/// depth; throughput limits are left to the consumer (the workflow scheduler).
+#[derive(Default)]
+pub struct MemoryBus {
+    subscribers: Mutex<Vec<(String, mpsc::UnboundedSender<EventMessage>)>>,
+    queues: Mutex<HashMap<String, Arc<MemoryQueue>>>,
+}
+
+impl MemoryBus {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    fn deliver(&self, message: EventMessage) {
+        let mut subs = self.subscribers.lock().unwrap();
+        subs.retain(|(pattern, tx)| {
+            if !topic_matches(pattern, &message.topic) {
+                return !tx.is_closed();
+            }
+            tx.send(message.clone()).is_ok()
+        });
+    }
+
+    fn queue(&self, name: &str) -> Arc<MemoryQueue> {
+        self.queues
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                log::debug!("Creating queue {} on first use", name);
+                MemoryQueue::new(QueueConfig { priority_levels: u8::MAX, max_messages: 0, max_throughput_per_sec: None })
+            })
+            .clone()
+    }
+}
+
+#[async_trait]
+impl EventBus for MemoryBus {
+    async fn publish(&self, topic: &str, payload: &[u8]) -> Result<(), BusError> {
+        self.deliver(EventMessage {
+            topic: topic.to_string(),
+            payload: payload.to_vec(),
+            headers: HashMap::new(),
+            correlation_id: None,
+            reply_to: None,
+            delivery: None,
+        });
+        Ok(())
+    }
+
+    async fn request(&self, topic: &str, payload: &[u8], timeout: Duration) -> Result<EventMessage, BusError> {
+        let correlation_id = Uuid::new_v4().to_string();
+        let reply_topic = format!("replies/{}", correlation_id);
+
+        let mut stream = self.subscribe(&reply_topic).await?;
+
+        self.deliver(EventMessage {
+            topic: topic.to_string(),
+            payload: payload.to_vec(),
+            headers: HashMap::new(),
+            correlation_id: Some(correlation_id),
+            reply_to: Some(reply_topic),
+            delivery: None,
+        });
+
+        match tokio::time::timeout(timeout, stream.next()).await {
+            Ok(Some(msg)) => Ok(msg),
+            Ok(None) => Err(BusError::ConnectionError("Stream closed".to_string())),
+            Err(_) => Err(BusError::RequestTimeout),
+        }
+    }
+
+    async fn subscribe(&self, topic: &str) -> Result<Pin<Box<dyn Stream<Item = EventMessage> + Send>>, BusError> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.subscribers.lock().unwrap().push((topic.to_string(), tx));
+        Ok(Box::pin(futures::stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|msg| (msg, rx))
+        })))
+    }
+
+    async fn reply(&self, original: &EventMessage, payload: &[u8]) -> Result<(), BusError> {
+        let Some(reply_to) = &original.reply_to else {
+            return Err(BusError::PublishError("No Reply-To topic found".to_string()));
+        };
+        self.deliver(EventMessage {
+            topic: reply_to.clone(),
+            payload: payload.to_vec(),
+            headers: HashMap::new(),
+            correlation_id: original.correlation_id.clone(),
+            reply_to: None,
+            delivery: None,
+        });
+        Ok(())
+    }
+
+    // --- Queue API Extensions ---
+
+    async fn create_queue(&self, name: &str, config: QueueConfig) -> Result<String, BusError> {
+        let mut queues = self.queues.lock().unwrap();
+        match queues.get(name) {
+            // Subscribers may already hold the queue; reconfigure it in place.
+            Some(existing) => existing.configure(config),
+            None => {
+                queues.insert(name.to_string(), MemoryQueue::new(config));
+            }
+        }
+        Ok(name.to_string())
+    }
+
+    async fn publish_to_queue(&self, queue_id: &str, priority: u8, payload: &[u8]) -> Result<(), BusError> {
+        let message = EventMessage {
+            topic: queue_id.to_string(),
+            payload: payload.to_vec(),
+            headers: HashMap::from([(PRIORITY_HEADER.to_string(), priority.to_string())]),
+            correlation_id: None,
+            reply_to: None,
+            delivery: None,
+        };
+        self.queue(queue_id).push(message, priority)
+    }
+
+    async fn subscribe_to_queue(&self, queue_id: &str) -> Result<Pin<Box<dyn Stream<Item = EventMessage> + Send>>, BusError> {
+        let queue = self.queue(queue_id);
+        Ok(Box::pin(futures::stream::unfold(queue, |queue| async move {
+            let msg = queue.pop().await;
+            Some((msg, queue))
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(msg: &EventMessage) -> &str {
+        std::str::from_utf8(&msg.payload).unwrap()
+    }
+
+    async fn next(stream: &mut Pin<Box<dyn Stream<Item = EventMessage> + Send>>) -> Option<EventMessage> {
+        tokio::time::timeout(Duration::from_millis(100), stream.next()).await.ok().flatten()
+    }
+
+    #[test]
+    fn test_topic_wildcards() {
+        assert!(topic_matches("tasks/created", "tasks/created"));
+        assert!(topic_matches("tasks/+", "tasks/created"));
+        assert!(!topic_matches("tasks/+", "tasks/created/1"));
+        assert!(topic_matches("tasks/#", "tasks/created/1"));
+        assert!(topic_matches("tasks/#", "tasks"));
+        assert!(topic_matches("+/+/done", "a/b/done"));
+        assert!(!topic_matches("tasks/created", "tasks"));
+        assert!(!topic_matches("tasks/#/x", "tasks/a/x"));
+    }
+
+    #[tokio::test]
+    async fn test_publish_fans_out_to_matching_subscribers() {
+        let bus = MemoryBus::new();
+        let mut exact = bus.subscribe("orders/eu/created").await.unwrap();
+        let mut wildcard = bus.subscribe("orders/+/created").await.unwrap();
+        let mut other = bus.subscribe("invoices/#").await.unwrap();
+
+        bus.publish("orders/eu/created", b"o1").await.unwrap();
+
+        assert_eq!(payload(&next(&mut exact).await.unwrap()), "o1");
+        assert_eq!(payload(&next(&mut wildcard).await.unwrap()), "o1");
+        assert!(next(&mut other).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_request_reply_correlates() {
+        let bus = MemoryBus::new();
+        let mut requests = bus.subscribe("svc/echo").await.unwrap();
+
+        let responder = bus.clone();
+        tokio::spawn(async move {
+            let req = requests.next().await.unwrap();
+            let reply = format!("echo:{}", payload(&req));
+            responder.reply(&req, reply.as_bytes()).await.unwrap();
+        });
+
+        let reply = bus.request("svc/echo", b"hi", Duration::from_secs(1)).await.unwrap();
+        assert_eq!(payload(&reply), "echo:hi");
+        assert!(reply.correlation_id.is_some());
+
+        let timed_out = bus.request("svc/nobody", b"hi", Duration::from_millis(20)).await;
+        assert!(matches!(timed_out, Err(BusError::RequestTimeout)));
+    }
+
+    #[tokio::test]
+    async fn test_queue_orders_by_priority_and_bounds_depth() {
+        let bus = MemoryBus::new();
+        let config = QueueConfig { priority_levels: 3, max_messages: 4, max_throughput_per_sec: None };
+        bus.create_queue("jobs", config).await.unwrap();
+
+        for (id, priority) in [("low", 0), ("high", 2), ("mid", 1), ("top", 9)] {
+            bus.publish_to_queue("jobs", priority, id.as_bytes()).await.unwrap();
+        }
+        assert!(bus.publish_to_queue("jobs", 0, b"overflow").await.is_err());
+
+        let mut stream = bus.subscribe_to_queue("jobs").await.unwrap();
+        let mut order = Vec::new();
+        for _ in 0..4 {
+            let msg = next(&mut stream).await.unwrap();
+            order.push((payload(&msg).to_string(), msg.headers[PRIORITY_HEADER].clone()));
+        }
+        // "top" is clamped to the highest level and queued behind "high".
+        assert_eq!(order, [
+            ("high".to_string(), "2".to_string()),
+            ("top".to_string(), "9".to_string()),
+            ("mid".to_string(), "1".to_string()),
+            ("low".to_string(), "0".to_string()),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_queue_delivers_each_message_once() {
+        let bus = MemoryBus::new();
+        let mut a = bus.subscribe_to_queue("work").await.unwrap();
+        let mut b = bus.subscribe_to_queue("work").await.unwrap();
+
+        for i in 0..4 {
+            bus.publish_to_queue("work", 0, i.to_string().as_bytes()).await.unwrap();
+        }
+
+        let mut seen = Vec::new();
+        while let Some(msg) = next(&mut a).await {
+            seen.push(payload(&msg).to_string());
+            if let Some(msg) = next(&mut b).await {
+                seen.push(payload(&msg).to_string());
+            }
+        }
+        seen.sort();
+        assert_eq!(seen, ["0", "1", "2", "3"]);
+    }
+}
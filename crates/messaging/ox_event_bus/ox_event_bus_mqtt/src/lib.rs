@@ -7,7 +7,7 @@ use std::time::Duration;
 use std::cmp::Ordering;
 use tokio::sync::{Mutex, Notify};
 use uuid::Uuid;
-use ox_event_bus::{EventBus, EventMessage, BusError, QueueConfig};
+use ox_event_bus::{EventBus, EventMessage, BusError, QueueConfig, PRIORITY_HEADER};
 use serde::{Serialize, Deserialize};
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -97,7 +97,7 @@ impl MqttBus {
                              
                              let (payload, headers, correlation_id, reply_to, priority) = match serde_json::from_slice::<OxEnvelope>(&raw_payload) {
                                  Ok(env) => {
-                                     let prio = env.headers.get("x-priority").and_then(|s| s.parse().ok()).unwrap_or(0);
+                                     let prio = env.headers.get(PRIORITY_HEADER).and_then(|s| s.parse().ok()).unwrap_or(0);
                                      (env.payload, env.headers, env.correlation_id, env.reply_to, prio)
                                  },
                                  Err(_) => {
@@ -111,6 +111,7 @@ impl MqttBus {
                                  headers,
                                  correlation_id, 
                                  reply_to, 
+                                 delivery: None,
                              };
 
                              let subs = subscribers_clone.lock().await;
@@ -224,7 +225,7 @@ impl EventBus for MqttBus {
 
     async fn publish_to_queue(&self, queue_id: &str, priority: u8, payload: &[u8]) -> Result<(), BusError> {
         let mut headers = HashMap::new();
-        headers.insert("x-priority".to_string(), priority.to_string());
+        headers.insert(PRIORITY_HEADER.to_string(), priority.to_string());
         
         let env = OxEnvelope {
             payload: payload.to_vec(),
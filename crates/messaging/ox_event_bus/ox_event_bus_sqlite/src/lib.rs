@@ -0,0 +1,599 @@
+use async_trait::async_trait;
+use futures::Stream;
+use ox_event_bus::{Acknowledger, BusError, Delivery, EventBus, EventMessage, QueueConfig, DELIVERY_COUNT_HEADER, PRIORITY_HEADER};
+use sqlx::{sqlite::SqlitePoolOptions, Row, SqlitePool};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Notify;
+use tokio::task::AbortHandle;
+use uuid::Uuid;
+
+/// Longest a subscriber sleeps before checking the store again. Bounds the delay for
+/// messages published by other processes sharing the database.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Plain `CREATE IF NOT EXISTS` rather than sqlx migrations, so the queues can live in the
+// same database file as `ox_workflow_storage` without clashing with its migration history.
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS bus_queues (
+    name TEXT PRIMARY KEY NOT NULL,
+    priority_levels INTEGER NOT NULL,
+    max_messages INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS bus_queue_messages (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    queue TEXT NOT NULL,
+    priority INTEGER NOT NULL,
+    payload BLOB NOT NULL,
+    headers_json TEXT NOT NULL,
+    deliveries INTEGER NOT NULL DEFAULT 0,
+    owner TEXT,
+    visible_at INTEGER NOT NULL,
+    enqueued_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_bus_queue_messages_next ON bus_queue_messages(queue, visible_at);
+"#;
+
+/// Event bus whose queues are persisted in SQLite and delivered at least once.
+///
+/// A delivered queue message stays in the store, invisible to other subscribers, until it
+/// is acknowledged. The delivering bus holds a lease on it for the visibility timeout and
+/// renews the lease for as long as the delivered message is alive, so a slow consumer keeps
+/// its message. Once the message is dropped unacknowledged, or the process holding it
+/// stops, the lease runs out and the message is delivered again with an incremented
+/// [`DELIVERY_COUNT_HEADER`]. `nack` releases the lease early. Only the current lease
+/// holder can settle a message.
+///
+/// Topics (`publish`, `subscribe`, `request`, `reply`) are not persisted and are passed to
+/// the wrapped bus.
+pub struct DurableBus {
+    inner: Arc<dyn EventBus>,
+    store: Arc<QueueStore>,
+    heartbeat: AbortHandle,
+}
+
+impl DurableBus {
+    /// Opens (creating if needed) the queue store at `db_url`. A lease that is not renewed
+    /// within `visibility_timeout` expires and its message is redelivered.
+    pub async fn new(inner: Arc<dyn EventBus>, db_url: &str, visibility_timeout: Duration) -> Result<Arc<Self>, BusError> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(5)
+            .connect(db_url)
+            .await
+            .map_err(|e| BusError::ConnectionError(e.to_string()))?;
+
+        sqlx::raw_sql(SCHEMA)
+            .execute(&pool)
+            .await
+            .map_err(|e| BusError::ConnectionError(e.to_string()))?;
+
+        let store = Arc::new(QueueStore {
+            pool,
+            visibility_timeout,
+            owner: Uuid::new_v4().to_string(),
+            configs: Mutex::new(HashMap::new()),
+            wakers: Mutex::new(HashMap::new()),
+            leases: Mutex::new(Vec::new()),
+        });
+        let heartbeat = tokio::spawn(renew_leases(Arc::downgrade(&store))).abort_handle();
+        Ok(Arc::new(Self { inner, store, heartbeat }))
+    }
+
+    /// Number of messages in a queue, delivered or not, that have not been acknowledged.
+    pub async fn pending(&self, queue: &str) -> Result<u64, BusError> {
+        self.store.count(queue).await
+    }
+}
+
+impl Drop for DurableBus {
+    fn drop(&mut self) {
+        self.heartbeat.abort();
+    }
+}
+
+struct QueueStore {
+    pool: SqlitePool,
+    visibility_timeout: Duration,
+    /// Identifies this bus as the holder of the leases it takes.
+    owner: String,
+    configs: Mutex<HashMap<String, QueueConfig>>,
+    wakers: Mutex<HashMap<String, Arc<Notify>>>,
+    /// Leases on delivered messages; each lives as long as some copy of its message.
+    leases: Mutex<Vec<Weak<Lease>>>,
+}
+
+/// This bus's claim on one delivery of a message. It is the delivery's acknowledger, so it
+/// is dropped, and stops being renewed, once every copy of the message is gone.
+struct Lease {
+    store: Arc<QueueStore>,
+    queue: String,
+    id: i64,
+    deliveries: i64,
+}
+
+/// Keeps the leases of live deliveries from expiring, until the store is gone or the
+/// owning bus is dropped.
+async fn renew_leases(store: Weak<QueueStore>) {
+    let Some(every) = store.upgrade().map(|s| (s.visibility_timeout / 3).max(Duration::from_millis(1))) else {
+        return;
+    };
+    let mut ticker = tokio::time::interval(every);
+    loop {
+        ticker.tick().await;
+        let Some(store) = store.upgrade() else { return };
+        let held: Vec<(i64, i64)> = {
+            let mut leases = store.leases.lock().unwrap();
+            leases.retain(|lease| lease.strong_count() > 0);
+            leases.iter().filter_map(Weak::upgrade).map(|lease| (lease.id, lease.deliveries)).collect()
+        };
+        if let Err(e) = store.renew(&held).await {
+            log::warn!("Failed to renew queue leases: {}", e);
+        }
+    }
+}
+
+impl QueueStore {
+    fn waker(&self, queue: &str) -> Arc<Notify> {
+        self.wakers.lock().unwrap().entry(queue.to_string()).or_default().clone()
+    }
+
+    fn wake(&self, queue: &str) {
+        if let Some(notify) = self.wakers.lock().unwrap().get(queue) {
+            notify.notify_waiters();
+        }
+    }
+
+    async fn config(&self, queue: &str) -> Result<QueueConfig, BusError> {
+        if let Some(config) = self.configs.lock().unwrap().get(queue) {
+            return Ok(config.clone());
+        }
+        let row = sqlx::query("SELECT priority_levels, max_messages FROM bus_queues WHERE name = ?")
+            .bind(queue)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+        let config = match row {
+            Some(row) => QueueConfig {
+                priority_levels: row.try_get::<i64, _>("priority_levels").map_err(db_error)? as u8,
+                max_messages: row.try_get::<i64, _>("max_messages").map_err(db_error)? as usize,
+                max_throughput_per_sec: None,
+            },
+            None => QueueConfig { priority_levels: u8::MAX, max_messages: 0, max_throughput_per_sec: None },
+        };
+        self.configs.lock().unwrap().insert(queue.to_string(), config.clone());
+        Ok(config)
+    }
+
+    async fn count(&self, queue: &str) -> Result<u64, BusError> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM bus_queue_messages WHERE queue = ?")
+            .bind(queue)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(db_error)?;
+        Ok(count as u64)
+    }
+
+    async fn enqueue(&self, queue: &str, priority: u8, payload: &[u8]) -> Result<(), BusError> {
+        let config = self.config(queue).await?;
+        let headers = HashMap::from([(PRIORITY_HEADER.to_string(), priority.to_string())]);
+        let headers_json = serde_json::to_string(&headers).map_err(|e| BusError::SerializationError(e.to_string()))?;
+        let now = unix_millis();
+        // The depth check and the insert are one statement, so concurrent publishers (in
+        // this process or another) cannot both see room for the last slot.
+        let inserted = sqlx::query(
+            r#"
+            INSERT INTO bus_queue_messages (queue, priority, payload, headers_json, visible_at, enqueued_at)
+            SELECT ?, ?, ?, ?, ?, ?
+            WHERE ? = 0 OR (SELECT COUNT(*) FROM bus_queue_messages WHERE queue = ?) < ?
+            "#
+        )
+        .bind(queue)
+        .bind(priority.min(config.priority_levels.saturating_sub(1)))
+        .bind(payload)
+        .bind(headers_json)
+        .bind(now)
+        .bind(now)
+        .bind(config.max_messages as i64)
+        .bind(queue)
+        .bind(config.max_messages as i64)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BusError::PublishError(e.to_string()))?;
+        if inserted.rows_affected() == 0 {
+            return Err(BusError::PublishError(format!("Queue {} is full", queue)));
+        }
+
+        self.wake(queue);
+        Ok(())
+    }
+
+    /// Takes a lease on the next visible message, hiding it for the visibility timeout.
+    async fn claim(self: &Arc<Self>, queue: &str) -> Result<Option<EventMessage>, BusError> {
+        let now = unix_millis();
+        let row = sqlx::query(
+            r#"
+            UPDATE bus_queue_messages SET deliveries = deliveries + 1, visible_at = ?, owner = ?
+            WHERE id = (
+                SELECT id FROM bus_queue_messages
+                WHERE queue = ? AND visible_at <= ?
+                ORDER BY priority DESC, id ASC
+                LIMIT 1
+            )
+            RETURNING id, payload, headers_json, deliveries
+            "#
+        )
+        .bind(now + self.visibility_timeout.as_millis() as i64)
+        .bind(&self.owner)
+        .bind(queue)
+        .bind(now)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        let Some(row) = row else { return Ok(None) };
+        let id: i64 = row.try_get("id").map_err(db_error)?;
+        let deliveries: i64 = row.try_get("deliveries").map_err(db_error)?;
+        let headers_json: String = row.try_get("headers_json").map_err(db_error)?;
+        let mut headers: HashMap<String, String> =
+            serde_json::from_str(&headers_json).map_err(|e| BusError::SerializationError(e.to_string()))?;
+        headers.insert(DELIVERY_COUNT_HEADER.to_string(), deliveries.to_string());
+
+        let lease = Arc::new(Lease { store: self.clone(), queue: queue.to_string(), id, deliveries });
+        self.leases.lock().unwrap().push(Arc::downgrade(&lease));
+        Ok(Some(EventMessage {
+            topic: queue.to_string(),
+            payload: row.try_get("payload").map_err(db_error)?,
+            headers,
+            correlation_id: None,
+            reply_to: None,
+            delivery: Some(Delivery {
+                acknowledger: lease,
+                receipt: format!("{}:{}:{}", queue, id, deliveries),
+            }),
+        }))
+    }
+
+    /// Pushes back the expiry of the given `(id, deliveries)` leases held by this bus.
+    async fn renew(&self, held: &[(i64, i64)]) -> Result<(), BusError> {
+        if held.is_empty() {
+            return Ok(());
+        }
+        let visible_at = unix_millis() + self.visibility_timeout.as_millis() as i64;
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        for (id, deliveries) in held {
+            sqlx::query("UPDATE bus_queue_messages SET visible_at = ? WHERE id = ? AND deliveries = ? AND owner = ?")
+                .bind(visible_at)
+                .bind(id)
+                .bind(deliveries)
+                .bind(&self.owner)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+        }
+        tx.commit().await.map_err(db_error)
+    }
+
+    /// Time until the earliest hidden message in the queue becomes visible again.
+    async fn next_visible_in(&self, queue: &str) -> Result<Option<Duration>, BusError> {
+        let next: Option<i64> = sqlx::query_scalar("SELECT MIN(visible_at) FROM bus_queue_messages WHERE queue = ?")
+            .bind(queue)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(db_error)?;
+        Ok(next.map(|at| Duration::from_millis((at - unix_millis()).max(0) as u64)))
+    }
+
+    async fn next_message(self: &Arc<Self>, queue: &str) -> EventMessage {
+        let notify = self.waker(queue);
+        loop {
+            let notified = notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            match self.claim(queue).await {
+                Ok(Some(msg)) => return msg,
+                Ok(None) => {}
+                Err(e) => log::error!("Failed to read queue {}: {}", queue, e),
+            }
+
+            let wait = match self.next_visible_in(queue).await {
+                Ok(Some(wait)) => wait.min(POLL_INTERVAL),
+                _ => POLL_INTERVAL,
+            };
+            tokio::select! {
+                _ = notified => {}
+                _ = tokio::time::sleep(wait) => {}
+            }
+        }
+    }
+}
+
+// A lease whose message was redelivered (the lease expired) or taken by another bus no
+// longer matches its row, so settling it has no effect.
+#[async_trait]
+impl Acknowledger for Lease {
+    async fn ack(&self, _receipt: &str) -> Result<(), BusError> {
+        sqlx::query("DELETE FROM bus_queue_messages WHERE id = ? AND deliveries = ? AND owner = ?")
+            .bind(self.id)
+            .bind(self.deliveries)
+            .bind(&self.store.owner)
+            .execute(&self.store.pool)
+            .await
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    async fn nack(&self, _receipt: &str, delay: Duration) -> Result<(), BusError> {
+        sqlx::query("UPDATE bus_queue_messages SET visible_at = ?, owner = NULL WHERE id = ? AND deliveries = ? AND owner = ?")
+            .bind(unix_millis() + delay.as_millis() as i64)
+            .bind(self.id)
+            .bind(self.deliveries)
+            .bind(&self.store.owner)
+            .execute(&self.store.pool)
+            .await
+            .map_err(db_error)?;
+        self.store.wake(&self.queue);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventBus for DurableBus {
+    async fn publish(&self, topic: &str, payload: &[u8]) -> Result<(), BusError> {
+        self.inner.publish(topic, payload).await
+    }
+
+    async fn request(&self, topic: &str, payload: &[u8], timeout: Duration) -> Result<EventMessage, BusError> {
+        self.inner.request(topic, payload, timeout).await
+    }
+
+    async fn subscribe(&self, topic: &str) -> Result<Pin<Box<dyn Stream<Item = EventMessage> + Send>>, BusError> {
+        self.inner.subscribe(topic).await
+    }
+
+    async fn reply(&self, original: &EventMessage, payload: &[u8]) -> Result<(), BusError> {
+        self.inner.reply(original, payload).await
+    }
+
+    // --- Queue API Extensions ---
+
+    async fn create_queue(&self, name: &str, config: QueueConfig) -> Result<String, BusError> {
+        sqlx::query(
+            r#"
+            INSERT INTO bus_queues (name, priority_levels, max_messages) VALUES (?, ?, ?)
+            ON CONFLICT(name) DO UPDATE SET
+                priority_levels = excluded.priority_levels,
+                max_messages = excluded.max_messages
+            "#
+        )
+        .bind(name)
+        .bind(config.priority_levels)
+        .bind(config.max_messages as i64)
+        .execute(&self.store.pool)
+        .await
+        .map_err(db_error)?;
+
+        self.store.configs.lock().unwrap().insert(name.to_string(), config);
+        Ok(name.to_string())
+    }
+
+    async fn publish_to_queue(&self, queue_id: &str, priority: u8, payload: &[u8]) -> Result<(), BusError> {
+        self.store.enqueue(queue_id, priority, payload).await
+    }
+
+    async fn subscribe_to_queue(&self, queue_id: &str) -> Result<Pin<Box<dyn Stream<Item = EventMessage> + Send>>, BusError> {
+        let state = (self.store.clone(), queue_id.to_string());
+        Ok(Box::pin(futures::stream::unfold(state, |(store, queue)| async move {
+            let msg = store.next_message(&queue).await;
+            Some((msg, (store, queue)))
+        })))
+    }
+}
+
+fn db_error(e: sqlx::Error) -> BusError {
+    BusError::ConnectionError(e.to_string())
+}
+
+fn unix_millis() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use ox_event_bus_memory::MemoryBus;
+    use std::path::PathBuf;
+
+    struct TempDb(PathBuf);
+
+    impl TempDb {
+        fn new() -> Self {
+            Self(std::env::temp_dir().join(format!("ox_event_bus_sqlite_{}.db", uuid::Uuid::new_v4())))
+        }
+
+        fn url(&self) -> String {
+            format!("sqlite://{}?mode=rwc", self.0.display())
+        }
+
+        async fn bus(&self, visibility_timeout: Duration) -> Arc<DurableBus> {
+            DurableBus::new(MemoryBus::new(), &self.url(), visibility_timeout).await.unwrap()
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    type Subscription = Pin<Box<dyn Stream<Item = EventMessage> + Send>>;
+
+    async fn next(stream: &mut Subscription, within: Duration) -> Option<EventMessage> {
+        tokio::time::timeout(within, stream.next()).await.ok().flatten()
+    }
+
+    fn payload(msg: &EventMessage) -> &str {
+        std::str::from_utf8(&msg.payload).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_acked_message_is_not_redelivered() {
+        let db = TempDb::new();
+        let bus = db.bus(Duration::from_millis(50)).await;
+        bus.publish_to_queue("jobs", 0, b"a").await.unwrap();
+
+        let mut stream = bus.subscribe_to_queue("jobs").await.unwrap();
+        let msg = next(&mut stream, Duration::from_secs(2)).await.unwrap();
+        assert_eq!(payload(&msg), "a");
+        assert_eq!(msg.delivery_count(), 1);
+        msg.ack().await.unwrap();
+
+        assert!(next(&mut stream, Duration::from_millis(200)).await.is_none());
+        assert_eq!(bus.pending("jobs").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_held_message_keeps_its_lease() {
+        let db = TempDb::new();
+        let bus = db.bus(Duration::from_millis(60)).await;
+        bus.publish_to_queue("jobs", 0, b"a").await.unwrap();
+
+        let mut stream = bus.subscribe_to_queue("jobs").await.unwrap();
+        let msg = next(&mut stream, Duration::from_secs(2)).await.unwrap();
+        // Well past the visibility timeout, but the consumer still holds the message.
+        assert!(next(&mut stream, Duration::from_millis(300)).await.is_none(), "hidden while in flight");
+
+        msg.ack().await.unwrap();
+        assert_eq!(bus.pending("jobs").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_message_is_redelivered_after_lease_expires() {
+        let db = TempDb::new();
+        let bus = db.bus(Duration::from_millis(100)).await;
+        bus.publish_to_queue("jobs", 0, b"a").await.unwrap();
+
+        let mut stream = bus.subscribe_to_queue("jobs").await.unwrap();
+        drop(next(&mut stream, Duration::from_secs(2)).await.unwrap());
+
+        let again = next(&mut stream, Duration::from_secs(2)).await.unwrap();
+        assert_eq!(payload(&again), "a");
+        assert_eq!(again.delivery_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_expired_lease_cannot_settle_redelivered_message() {
+        let db = TempDb::new();
+        let stopped = db.bus(Duration::from_millis(100)).await;
+        stopped.publish_to_queue("jobs", 0, b"a").await.unwrap();
+        let mut stream = stopped.subscribe_to_queue("jobs").await.unwrap();
+        let first = next(&mut stream, Duration::from_secs(2)).await.unwrap();
+        // The bus that took the message stops renewing its lease, as if its process hung.
+        drop(stream);
+        drop(stopped);
+
+        let bus = db.bus(Duration::from_millis(100)).await;
+        let mut stream = bus.subscribe_to_queue("jobs").await.unwrap();
+        let second = next(&mut stream, Duration::from_secs(2)).await.unwrap();
+        assert_eq!(second.delivery_count(), 2);
+
+        first.ack().await.unwrap();
+        first.nack(Duration::ZERO).await.unwrap();
+        assert_eq!(bus.pending("jobs").await.unwrap(), 1);
+        assert!(next(&mut stream, Duration::from_millis(150)).await.is_none(), "still leased to the new holder");
+        second.ack().await.unwrap();
+        assert_eq!(bus.pending("jobs").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_depth_limit_holds_under_concurrent_publishers() {
+        let db = TempDb::new();
+        let bus = db.bus(Duration::from_secs(60)).await;
+        let config = QueueConfig { priority_levels: 1, max_messages: 5, max_throughput_per_sec: None };
+        bus.create_queue("jobs", config).await.unwrap();
+
+        let publishers: Vec<_> = (0..20)
+            .map(|i| {
+                let bus = bus.clone();
+                tokio::spawn(async move { bus.publish_to_queue("jobs", 0, format!("{i}").as_bytes()).await })
+            })
+            .collect();
+        let mut accepted = 0;
+        for publisher in publishers {
+            if publisher.await.unwrap().is_ok() {
+                accepted += 1;
+            }
+        }
+        assert_eq!(accepted, 5);
+        assert_eq!(bus.pending("jobs").await.unwrap(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_nack_redelivers_immediately() {
+        let db = TempDb::new();
+        let bus = db.bus(Duration::from_secs(60)).await;
+        bus.publish_to_queue("jobs", 0, b"a").await.unwrap();
+
+        let mut stream = bus.subscribe_to_queue("jobs").await.unwrap();
+        let msg = next(&mut stream, Duration::from_secs(2)).await.unwrap();
+        msg.nack(Duration::ZERO).await.unwrap();
+
+        let again = next(&mut stream, Duration::from_millis(200)).await.unwrap();
+        assert_eq!(again.delivery_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_priority_order_and_depth_limit() {
+        let db = TempDb::new();
+        let bus = db.bus(Duration::from_secs(60)).await;
+        let config = QueueConfig { priority_levels: 3, max_messages: 3, max_throughput_per_sec: None };
+        bus.create_queue("jobs", config).await.unwrap();
+
+        for (id, priority) in [("low", 0), ("high", 2), ("mid", 1)] {
+            bus.publish_to_queue("jobs", priority, id.as_bytes()).await.unwrap();
+        }
+        assert!(bus.publish_to_queue("jobs", 2, b"overflow").await.is_err());
+
+        let mut stream = bus.subscribe_to_queue("jobs").await.unwrap();
+        let mut order = Vec::new();
+        for _ in 0..3 {
+            let msg = next(&mut stream, Duration::from_secs(2)).await.unwrap();
+            order.push(format!("{}:{}", payload(&msg), msg.headers[PRIORITY_HEADER]));
+        }
+        assert_eq!(order, ["high:2", "mid:1", "low:0"]);
+    }
+
+    #[tokio::test]
+    async fn test_messages_survive_reopening_the_store() {
+        let db = TempDb::new();
+        {
+            let bus = db.bus(Duration::from_millis(50)).await;
+            let config = QueueConfig { priority_levels: 2, max_messages: 0, max_throughput_per_sec: None };
+            bus.create_queue("jobs", config).await.unwrap();
+            bus.publish_to_queue("jobs", 0, b"low").await.unwrap();
+            bus.publish_to_queue("jobs", 1, b"high").await.unwrap();
+
+            // Delivered but never acknowledged, as if the consumer crashed.
+            let mut stream = bus.subscribe_to_queue("jobs").await.unwrap();
+            assert_eq!(payload(&next(&mut stream, Duration::from_secs(2)).await.unwrap()), "high");
+        }
+
+        let bus = db.bus(Duration::from_millis(50)).await;
+        let mut stream = bus.subscribe_to_queue("jobs").await.unwrap();
+        let mut seen = Vec::new();
+        while let Some(msg) = next(&mut stream, Duration::from_secs(2)).await {
+            seen.push((payload(&msg).to_string(), msg.delivery_count()));
+            msg.ack().await.unwrap();
+            if seen.len() == 2 {
+                break;
+            }
+        }
+        seen.sort();
+        assert_eq!(seen, [("high".to_string(), 2), ("low".to_string(), 1)]);
+    }
+}
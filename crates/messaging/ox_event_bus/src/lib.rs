@@ -2,8 +2,15 @@ use async_trait::async_trait;
 use futures::Stream;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 
+/// Header carrying the priority a message was given by `publish_to_queue`.
+pub const PRIORITY_HEADER: &str = "x-priority";
+
+/// Header with the number of times a durable queue message has been delivered, starting at 1.
+pub const DELIVERY_COUNT_HEADER: &str = "x-delivery-count";
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct EventMessage {
     pub topic: String,
@@ -11,6 +18,55 @@ pub struct EventMessage {
     pub headers: HashMap<String, String>,
     pub correlation_id: Option<String>,
     pub reply_to: Option<String>,
+    /// Set on messages from durable queues, which stay pending until acknowledged.
+    #[serde(skip)]
+    pub delivery: Option<Delivery>,
+}
+
+impl EventMessage {
+    /// Confirms the message was processed so it is not delivered again.
+    /// A no-op for messages that do not need acknowledging.
+    pub async fn ack(&self) -> Result<(), BusError> {
+        match &self.delivery {
+            Some(d) => d.acknowledger.ack(&d.receipt).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the message to its queue, to be delivered again after `delay`.
+    /// A no-op for messages that do not need acknowledging.
+    pub async fn nack(&self, delay: Duration) -> Result<(), BusError> {
+        match &self.delivery {
+            Some(d) => d.acknowledger.nack(&d.receipt, delay).await,
+            None => Ok(()),
+        }
+    }
+
+    /// How many times this message has been delivered, including this delivery.
+    pub fn delivery_count(&self) -> u32 {
+        self.headers.get(DELIVERY_COUNT_HEADER).and_then(|c| c.parse().ok()).unwrap_or(1)
+    }
+}
+
+/// Settles a delivered message with the bus that delivered it.
+#[async_trait]
+pub trait Acknowledger: Send + Sync {
+    async fn ack(&self, receipt: &str) -> Result<(), BusError>;
+    async fn nack(&self, receipt: &str, delay: Duration) -> Result<(), BusError>;
+}
+
+/// Pending delivery of a durable queue message. The receipt identifies this particular
+/// delivery, so settling a stale delivery (one that has since been redelivered) has no effect.
+#[derive(Clone)]
+pub struct Delivery {
+    pub acknowledger: Arc<dyn Acknowledger>,
+    pub receipt: String,
+}
+
+impl std::fmt::Debug for Delivery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Delivery").field("receipt", &self.receipt).finish()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,6 +101,8 @@ pub trait EventBus: Send + Sync {
     async fn reply(&self, original: &EventMessage, payload: &[u8]) -> Result<(), BusError>;
 
     // Queue API Extensions
+    // Queue messages are delivered at least once by durable implementations; consumers must
+    // `ack` each message once it has been handled.
     async fn create_queue(&self, name: &str, config: QueueConfig) -> Result<String, BusError>;
     async fn publish_to_queue(&self, queue_id: &str, priority: u8, payload: &[u8]) -> Result<(), BusError>;
     async fn subscribe_to_queue(&self, queue_id: &str) -> Result<std::pin::Pin<Box<dyn Stream<Item = EventMessage> + Send>>, BusError>;
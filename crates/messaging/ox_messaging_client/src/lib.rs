@@ -1,16 +1,31 @@
 use ox_event_bus::{EventBus, QueueConfig as BusQueueConfig};
+use ox_event_bus_memory::MemoryBus;
 use ox_event_bus_mqtt::MqttBus;
+use ox_event_bus_sqlite::DurableBus;
 use ox_workflow_config::{load_config_from_file, QueuesManifest};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
-#[derive(Deserialize)]
+/// Default time a delivered queue message may stay unacknowledged before it is redelivered.
+const DEFAULT_VISIBILITY_TIMEOUT_MS: u64 = 300_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClientConfig {
+    /// `mqtt`, or `memory` for an in-process bus (single node, no broker).
     pub provider: String,
     pub host: Option<String>,
     pub port: Option<u16>,
     pub client_id: Option<String>,
+    /// SQLite URL of a durable queue store. When set, queues are persisted there and
+    /// delivered at least once; topics still go through `provider`.
+    #[serde(default)]
+    pub queue_store: Option<String>,
+    /// How long a delivered queue message may go unacknowledged before it is redelivered.
+    /// Must exceed the longest task run. Only used with `queue_store`.
+    #[serde(default)]
+    pub visibility_timeout_ms: Option<u64>,
 }
 
 pub struct EventBusFactory;
@@ -47,15 +62,27 @@ impl EventBusFactory {
     }
     
     pub async fn create(config: ClientConfig) -> Result<Arc<dyn EventBus>, String> {
-        match config.provider.as_str() {
+        let bus: Arc<dyn EventBus> = match config.provider.as_str() {
             "mqtt" => {
                 let host = config.host.unwrap_or_else(|| "127.0.0.1".to_string());
                 let port = config.port.unwrap_or(1883);
                 let client_id = config.client_id.unwrap_or_else(|| format!("ox_client_{}", uuid::Uuid::new_v4()));
                 
-                Ok(MqttBus::new(&client_id, &host, port).await)
+                MqttBus::new(&client_id, &host, port).await
+            }
+            "memory" => MemoryBus::new(),
+            _ => return Err(format!("Unknown provider: {}", config.provider)),
+        };
+
+        match config.queue_store {
+            Some(db_url) => {
+                let timeout = Duration::from_millis(config.visibility_timeout_ms.unwrap_or(DEFAULT_VISIBILITY_TIMEOUT_MS));
+                let durable = DurableBus::new(bus, &db_url, timeout)
+                    .await
+                    .map_err(|e| format!("Failed to open queue store {}: {}", db_url, e))?;
+                Ok(durable)
             }
-            _ => Err(format!("Unknown provider: {}", config.provider)),
+            None => Ok(bus),
         }
     }
 }
@@ -51,6 +51,7 @@ impl AuthDriver for DbAuthDriver {
                         groups: vec![],
                         tenant_id: self.tenant_id.clone(),
                         session_id: None,
+                        attributes: Default::default(),
                     })
                 } else {
                     AuthResult::Reject(format!("invalid credentials for '{}'", username))
@@ -82,6 +82,7 @@ impl AuthDriver for KerberosAuthDriver {
                 groups: vec![],
                 tenant_id: self.config.tenant_id.clone(),
                 session_id: None,
+                attributes: Default::default(),
             }),
             Err(reason) => AuthResult::Reject(reason),
         }
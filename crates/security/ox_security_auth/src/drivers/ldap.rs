@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 use async_trait::async_trait;
 use futures::future::BoxFuture;
@@ -26,6 +27,32 @@ pub enum LdapBindResult {
     Error(String),
 }
 
+/// Outcome of a service-account search for a single directory entry.
+#[derive(Clone, Debug)]
+pub enum LdapSearchResult {
+    /// Values of the requested attributes, keyed by the requested names.
+    Found(HashMap<String, Vec<String>>),
+    InvalidCredentials,
+    NoSuchEntry,
+    Error(String),
+}
+
+/// A directory entry found by [`LdapAuthDriver::lookup_entry`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DirectoryEntry {
+    /// Values of the configured `group_attr`.
+    pub groups: Vec<String>,
+    /// Values of the other requested attributes.
+    pub attributes: HashMap<String, Vec<String>>,
+}
+
+impl DirectoryEntry {
+    /// First value of `attr`, if the entry has one.
+    pub fn attribute(&self, attr: &str) -> Option<&str> {
+        self.attributes.get(attr).and_then(|v| v.first()).map(String::as_str)
+    }
+}
+
 pub trait LdapAdapter: Send + Sync + 'static {
     fn bind_and_search(
         &self,
@@ -35,6 +62,20 @@ pub trait LdapAdapter: Send + Sync + 'static {
         base_dn: String,
         group_attr: String,
     ) -> BoxFuture<'static, LdapBindResult>;
+
+    /// Bind as `bind_dn` and return the `attrs` values of the entries matching `filter`.
+    /// Used to look up accounts that authenticated by other means.
+    fn search_entry(
+        &self,
+        _url: String,
+        _bind_dn: String,
+        _password: String,
+        _base_dn: String,
+        _filter: String,
+        _attrs: Vec<String>,
+    ) -> BoxFuture<'static, LdapSearchResult> {
+        Box::pin(async { LdapSearchResult::Error("entry search not supported by this adapter".to_string()) })
+    }
 }
 
 fn escape_ldap_filter_value(s: &str) -> String {
@@ -64,7 +105,7 @@ impl LdapAdapter for RealLdapAdapter {
         group_attr: String,
     ) -> BoxFuture<'static, LdapBindResult> {
         Box::pin(async move {
-            use ldap3::{LdapConnAsync, Scope, SearchEntry};
+            use ldap3::LdapConnAsync;
 
             let (conn, mut ldap) = match LdapConnAsync::new(&url).await {
                 Ok(pair) => pair,
@@ -88,42 +129,98 @@ impl LdapAdapter for RealLdapAdapter {
                 .and_then(|rdn| rdn.split('=').nth(1))
                 .unwrap_or("*");
             let filter = format!("(uid={})", escape_ldap_filter_value(username_part));
-            let attr_ref = group_attr.as_str();
-
-            let groups = match ldap.search(&base_dn, Scope::Subtree, &filter, vec![attr_ref]).await {
-                Err(e) => return LdapBindResult::Error(e.to_string()),
-                Ok(res) => match res.success() {
-                    Err(e) => return LdapBindResult::Error(e.to_string()),
-                    Ok((entries, _)) => {
-                        let mut groups = Vec::new();
-                        for entry in entries {
-                            let e = SearchEntry::construct(entry);
-                            if let Some(vals) = e.attrs.get(&group_attr) {
-                                for v in vals {
-                                    groups.push(v.clone());
-                                }
-                            }
-                        }
-                        groups
-                    }
+            match search_attrs(ldap, base_dn, filter, vec![group_attr.clone()]).await {
+                LdapSearchResult::Found(mut attrs) => LdapBindResult::Success {
+                    groups: attrs.remove(&group_attr).unwrap_or_default(),
                 },
+                // The bind succeeded; an entry outside base_dn just has no groups
+                LdapSearchResult::NoSuchEntry => LdapBindResult::Success { groups: vec![] },
+                LdapSearchResult::InvalidCredentials => LdapBindResult::InvalidCredentials,
+                LdapSearchResult::Error(e) => LdapBindResult::Error(e),
+            }
+        })
+    }
+
+    fn search_entry(
+        &self,
+        url: String,
+        bind_dn: String,
+        password: String,
+        base_dn: String,
+        filter: String,
+        attrs: Vec<String>,
+    ) -> BoxFuture<'static, LdapSearchResult> {
+        Box::pin(async move {
+            use ldap3::LdapConnAsync;
+
+            let (conn, mut ldap) = match LdapConnAsync::new(&url).await {
+                Ok(pair) => pair,
+                Err(e) => return LdapSearchResult::Error(e.to_string()),
             };
+            ldap3::drive!(conn);
 
-            let _ = ldap.unbind().await;
-            LdapBindResult::Success { groups }
+            match ldap.simple_bind(&bind_dn, &password).await {
+                Err(e) => return LdapSearchResult::Error(e.to_string()),
+                Ok(res) => match res.rc {
+                    0 => {}
+                    49 => return LdapSearchResult::InvalidCredentials,
+                    code => return LdapSearchResult::Error(format!("LDAP bind returned rc={}", code)),
+                },
+            }
+            search_attrs(ldap, base_dn, filter, attrs).await
         })
     }
 }
 
+/// Collect the `attrs` values of the entries matching `filter`, then unbind. Attribute
+/// names are matched case-insensitively and reported as requested.
+async fn search_attrs(
+    mut ldap: ldap3::Ldap,
+    base_dn: String,
+    filter: String,
+    attrs: Vec<String>,
+) -> LdapSearchResult {
+    use ldap3::{Scope, SearchEntry};
+
+    let attr_refs: Vec<&str> = attrs.iter().map(String::as_str).collect();
+    let found = match ldap.search(&base_dn, Scope::Subtree, &filter, attr_refs).await {
+        Err(e) => LdapSearchResult::Error(e.to_string()),
+        Ok(res) => match res.success() {
+            Err(e) => LdapSearchResult::Error(e.to_string()),
+            Ok((entries, _)) if entries.is_empty() => LdapSearchResult::NoSuchEntry,
+            Ok((entries, _)) => {
+                let mut values: HashMap<String, Vec<String>> = HashMap::new();
+                for entry in entries {
+                    for (name, vals) in SearchEntry::construct(entry).attrs {
+                        if let Some(wanted) = attrs.iter().find(|a| a.eq_ignore_ascii_case(&name)) {
+                            values.entry(wanted.clone()).or_default().extend(vals);
+                        }
+                    }
+                }
+                LdapSearchResult::Found(values)
+            }
+        },
+    };
+
+    let _ = ldap.unbind().await;
+    found
+}
+
 #[cfg(any(test, feature = "test-support"))]
 pub struct MockLdapAdapter {
     result: LdapBindResult,
+    search: Option<LdapSearchResult>,
 }
 
 #[cfg(any(test, feature = "test-support"))]
 impl MockLdapAdapter {
     pub fn new(result: LdapBindResult) -> Self {
-        Self { result }
+        Self { result, search: None }
+    }
+
+    /// A directory that answers service-account entry searches with `search`.
+    pub fn with_search(search: LdapSearchResult) -> Self {
+        Self { result: LdapBindResult::NoSuchEntry, search: Some(search) }
     }
 }
 
@@ -140,6 +237,20 @@ impl LdapAdapter for MockLdapAdapter {
         let result = self.result.clone();
         Box::pin(async move { result })
     }
+
+    fn search_entry(
+        &self,
+        _url: String,
+        _bind_dn: String,
+        _password: String,
+        _base_dn: String,
+        _filter: String,
+        _attrs: Vec<String>,
+    ) -> BoxFuture<'static, LdapSearchResult> {
+        let result = self.search.clone()
+            .unwrap_or_else(|| LdapSearchResult::Error("no search result configured".to_string()));
+        Box::pin(async move { result })
+    }
 }
 
 pub struct LdapAuthDriver {
@@ -181,6 +292,7 @@ impl LdapAuthDriver {
                     groups: group_ids,
                     tenant_id: self.config.tenant_id.clone(),
                     session_id: None,
+                    attributes: Default::default(),
                 })
             }
             LdapBindResult::InvalidCredentials => {
@@ -197,6 +309,42 @@ impl LdapAuthDriver {
             }
         }
     }
+
+    /// The directory entry whose `attr` equals `value`, searched as the service account
+    /// `bind_dn`: its groups plus the `attrs` values. For principals authenticated
+    /// elsewhere (Kerberos, client certificates) that still need directory data.
+    /// Unlike a user bind, a missing entry is an error.
+    pub async fn lookup_entry(
+        &self,
+        bind_dn: &str,
+        password: &str,
+        attr: &str,
+        value: &str,
+        attrs: &[&str],
+    ) -> Result<DirectoryEntry, String> {
+        let filter = format!("({}={})", attr, escape_ldap_filter_value(value));
+        let group_attr = self.config.group_attr.clone();
+        let mut wanted = vec![group_attr.clone()];
+        wanted.extend(attrs.iter().map(|a| a.to_string()));
+        let result = self.adapter.search_entry(
+            self.config.url.clone(),
+            bind_dn.to_string(),
+            password.to_string(),
+            self.config.base_dn.clone(),
+            filter,
+            wanted,
+        ).await;
+
+        match result {
+            LdapSearchResult::Found(mut attributes) => Ok(DirectoryEntry {
+                groups: attributes.remove(&group_attr).unwrap_or_default(),
+                attributes,
+            }),
+            LdapSearchResult::InvalidCredentials => Err(format!("service bind rejected for '{}'", bind_dn)),
+            LdapSearchResult::NoSuchEntry => Err(format!("'{}' not found in directory", value)),
+            LdapSearchResult::Error(msg) => Err(msg),
+        }
+    }
 }
 
 #[async_trait]
@@ -9,6 +9,8 @@ pub mod radius;
 pub mod tacacs;
 pub(crate) mod tacacs_proto;
 pub mod totp;
+pub mod webauthn;
+pub(crate) mod webauthn_proto;
 
 pub use ad::AdAuthDriver;
 pub use api_key::{ApiKeyAuthDriver, ApiKeyLookupFn};
@@ -20,6 +22,10 @@ pub use oidc::{OidcAuthDriver, OidcConfig, JwksFetchFn};
 pub use radius::{RadiusAuthDriver, RadiusConfig, UdpSendFn as RadiusUdpSendFn};
 pub use tacacs::TacacsAuthDriver;
 pub use totp::{TotpAuthDriver, TotpSecretLookupFn};
+pub use webauthn::{
+    InMemoryWebAuthnStore, OxPersistenceWebAuthnStore, WebAuthnAuthDriver, WebAuthnConfig,
+    WebAuthnCredential, WebAuthnCredentialStore,
+};
 
 pub use ldap::{LdapConfig, LdapAdapter, LdapBindResult};
 pub use ad::AdConfig;
@@ -28,3 +34,5 @@ pub use ad::AdConfig;
 pub use ldap::MockLdapAdapter;
 #[cfg(any(test, feature = "test-support"))]
 pub use ad::BindDnCapture;
+#[cfg(any(test, feature = "test-support"))]
+pub use webauthn::SoftAuthenticator;
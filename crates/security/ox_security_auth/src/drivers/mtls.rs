@@ -63,6 +63,7 @@ mod tests {
                     groups: vec![],
                     tenant_id: TenantId::from_str("test").unwrap(),
                     session_id: None,
+                    attributes: Default::default(),
                 })
             } else {
                 Err("certificate validation failed: unknown issuer".to_string())
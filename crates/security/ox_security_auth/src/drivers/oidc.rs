@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -7,7 +8,7 @@ use tokio::sync::Mutex;
 use jsonwebtoken::jwk::JwkSet;
 use ox_security_core::{
     AuthResult, AuthPipelineContext, Credentials, Principal, PrincipalId,
-    AuthSource, TenantId, GroupId, drivers::AuthDriver,
+    AuthSource, TenantId, GroupId, drivers::AuthDriver, USER_ID_ATTRIBUTE,
 };
 
 // ── Public types ─────────────────────────────────────────────────────────────
@@ -259,6 +260,21 @@ impl AuthDriver for OidcAuthDriver {
             g
         };
 
+        // Carry standard profile claims through for downstream claim mapping
+        let mut attributes: HashMap<String, String> = ["email", "email_verified", "name", "given_name", "family_name", "preferred_username"]
+            .iter()
+            .filter_map(|field| {
+                let value = match claims.get(*field)? {
+                    serde_json::Value::String(s) => s.clone(),
+                    serde_json::Value::Bool(b) => b.to_string(),
+                    _ => return None,
+                };
+                Some((field.to_string(), value))
+            })
+            .collect();
+        // The display name can change upstream; `sub` cannot
+        attributes.insert(USER_ID_ATTRIBUTE.to_string(), sub);
+
         // 8. Build Principal
         let principal = Principal {
             id: PrincipalId::new(),
@@ -267,6 +283,7 @@ impl AuthDriver for OidcAuthDriver {
             groups,
             tenant_id: self.config.tenant_id.clone(),
             session_id: None,
+            attributes,
         };
 
         AuthResult::Authenticated(principal)
@@ -410,6 +427,7 @@ mod tests {
                 // sub is used as display_name when preferred_username absent
                 assert_eq!(p.display_name, "user-42");
                 assert_eq!(p.tenant_id.as_str(), "test");
+                assert_eq!(p.stable_id(), "oidc:user-42");
             }
             _ => panic!("expected Authenticated, got something else"),
         }
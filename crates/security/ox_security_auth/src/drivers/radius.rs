@@ -220,6 +220,7 @@ impl AuthDriver for RadiusAuthDriver {
                         groups: vec![],
                         tenant_id: self.config.tenant_id.clone(),
                         session_id: None,
+                        attributes: Default::default(),
                     }),
                     3 => AuthResult::Reject("RADIUS Access-Reject".to_string()),
                     code => AuthResult::Reject(format!("unexpected RADIUS response code: {}", code)),
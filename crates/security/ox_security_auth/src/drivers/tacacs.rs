@@ -110,6 +110,7 @@ impl AuthDriver for TacacsAuthDriver {
                 groups: vec![],
                 tenant_id: self.config.tenant_id.clone(),
                 session_id: None,
+                attributes: Default::default(),
             })
         } else {
             AuthResult::Reject(format!("TACACS+ rejected user '{}' (status={})", username, status))
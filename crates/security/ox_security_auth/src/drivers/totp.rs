@@ -125,6 +125,7 @@ mod tests {
             source: AuthSource::Local,
             groups: vec![GroupId::new("users")],
             tenant_id: TenantId::from_str("test").unwrap(),
+            attributes: Default::default(),
         }
     }
 
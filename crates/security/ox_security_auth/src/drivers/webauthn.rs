@@ -0,0 +1,632 @@
+//! WebAuthn (FIDO2) MFA authentication driver.
+//!
+//! Registration is driven by the application for an already authenticated
+//! principal: `start_registration` returns creation options for
+//! `navigator.credentials.create()` and `finish_registration` verifies the
+//! browser's response and stores the credential. Credentials belong to the
+//! user named by `Principal::stable_id()`, since `PrincipalId` is new for
+//! every login; the WebAuthn user handle is the SHA-256 of that id.
+//!
+//! Authentication runs as the second factor. After a first-factor driver has
+//! set `ctx.partial_principal`, `Credentials::WebAuthnBegin` yields
+//! `MfaRequired(MfaChallenge::WebAuthn)` carrying request options for
+//! `navigator.credentials.get()`; the caller re-submits the result as
+//! `Credentials::WebAuthnAssertion` with the same session token.
+//!
+//! Only ES256 credentials are accepted, attestation statements are not
+//! evaluated and client data from a cross-origin iframe is rejected.
+//! Signature counters must increase on every assertion unless the
+//! authenticator does not implement one (both stored and presented are zero).
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use async_trait::async_trait;
+use base64ct::{Base64UrlUnpadded, Encoding};
+use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+use rand::RngCore;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use ox_security_core::{
+    AuthResult, AuthPipelineContext, Credentials, MfaChallenge, SessionToken,
+    drivers::AuthDriver,
+};
+use super::webauthn_proto::{
+    attestation_auth_data, parse_authenticator_data, AuthenticatorData, CollectedClientData,
+    COSE_ALG_ES256,
+};
+
+/// Configuration for `WebAuthnAuthDriver`.
+#[derive(Debug, Clone)]
+pub struct WebAuthnConfig {
+    /// Relying party id credentials are scoped to, e.g. `"example.com"`.
+    pub rp_id: String,
+    /// Name shown by the browser during registration.
+    pub rp_name: String,
+    /// Origins accepted in client data, e.g. `"https://login.example.com"`.
+    pub origins: Vec<String>,
+    /// Require user verification (PIN or biometric), not just user presence.
+    pub require_user_verification: bool,
+    /// How long a registration or authentication challenge stays valid.
+    pub challenge_ttl_secs: u64,
+}
+
+/// A registered authenticator credential.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebAuthnCredential {
+    pub credential_id: Vec<u8>,
+    /// `Principal::stable_id()` of the owner.
+    pub user_id: String,
+    /// SEC1 uncompressed P-256 public key.
+    pub public_key: Vec<u8>,
+    pub sign_count: u32,
+    pub aaguid: [u8; 16],
+    pub created_at: u64,
+}
+
+/// Where `WebAuthnAuthDriver` keeps registered credentials.
+pub trait WebAuthnCredentialStore: Send + Sync {
+    fn insert(&self, credential: WebAuthnCredential) -> Result<(), String>;
+    fn get(&self, credential_id: &[u8]) -> Result<Option<WebAuthnCredential>, String>;
+    fn list(&self, user_id: &str) -> Result<Vec<WebAuthnCredential>, String>;
+    fn remove(&self, credential_id: &[u8]) -> Result<(), String>;
+    /// Set the signature counter to `new` if it is still `expected`.
+    /// Returns `false` when another assertion updated it first.
+    fn update_sign_count(&self, credential_id: &[u8], expected: u32, new: u32) -> Result<bool, String>;
+}
+
+#[derive(Default)]
+pub struct InMemoryWebAuthnStore {
+    credentials: Mutex<HashMap<Vec<u8>, WebAuthnCredential>>,
+}
+
+impl InMemoryWebAuthnStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<Vec<u8>, WebAuthnCredential>> {
+        self.credentials.lock().unwrap_or_else(|p| p.into_inner())
+    }
+}
+
+impl WebAuthnCredentialStore for InMemoryWebAuthnStore {
+    fn insert(&self, credential: WebAuthnCredential) -> Result<(), String> {
+        let mut credentials = self.lock();
+        if credentials.contains_key(&credential.credential_id) {
+            return Err("credential is already registered".to_string());
+        }
+        credentials.insert(credential.credential_id.clone(), credential);
+        Ok(())
+    }
+
+    fn get(&self, credential_id: &[u8]) -> Result<Option<WebAuthnCredential>, String> {
+        Ok(self.lock().get(credential_id).cloned())
+    }
+
+    fn list(&self, user_id: &str) -> Result<Vec<WebAuthnCredential>, String> {
+        Ok(self.lock().values().filter(|c| c.user_id == user_id).cloned().collect())
+    }
+
+    fn remove(&self, credential_id: &[u8]) -> Result<(), String> {
+        self.lock().remove(credential_id);
+        Ok(())
+    }
+
+    fn update_sign_count(&self, credential_id: &[u8], expected: u32, new: u32) -> Result<bool, String> {
+        match self.lock().get_mut(credential_id) {
+            Some(credential) if credential.sign_count == expected => {
+                credential.sign_count = new;
+                Ok(true)
+            }
+            Some(_) => Ok(false),
+            None => Err("unknown credential".to_string()),
+        }
+    }
+}
+
+/// Store backed by a SQLite database, laid out like the IdP token store: each
+/// credential is a JSON blob beside the columns used for lookups, and the
+/// signature counter lives only in its column so it can be compared and set
+/// atomically. Pass `":memory:"` as the path in unit tests.
+pub struct OxPersistenceWebAuthnStore {
+    conn: Mutex<Connection>,
+}
+
+impl OxPersistenceWebAuthnStore {
+    /// Open (or create) the SQLite database at `db_path` and run migrations.
+    pub fn open(db_path: &str) -> Result<Self, String> {
+        let conn = Connection::open(db_path)
+            .map_err(|e| format!("sqlite open '{}': {}", db_path, e))?;
+        conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA synchronous=NORMAL;")
+            .map_err(|e| format!("PRAGMA: {}", e))?;
+        conn.busy_timeout(Duration::from_secs(5)).map_err(sql)?;
+        conn.execute_batch(r#"
+            CREATE TABLE IF NOT EXISTS webauthn_credential (
+                credential_id BLOB    PRIMARY KEY,
+                user_id       TEXT    NOT NULL,
+                sign_count    INTEGER NOT NULL,
+                data          TEXT    NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_webauthn_credential_user ON webauthn_credential(user_id);
+        "#).map_err(sql)?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|p| p.into_inner())
+    }
+}
+
+fn sql(e: rusqlite::Error) -> String {
+    format!("sqlite: {}", e)
+}
+
+fn credential_from_row(data: &str, sign_count: i64) -> Result<WebAuthnCredential, String> {
+    let mut credential: WebAuthnCredential = serde_json::from_str(data).map_err(|e| e.to_string())?;
+    credential.sign_count = u32::try_from(sign_count).map_err(|e| e.to_string())?;
+    Ok(credential)
+}
+
+impl WebAuthnCredentialStore for OxPersistenceWebAuthnStore {
+    fn insert(&self, credential: WebAuthnCredential) -> Result<(), String> {
+        let data = serde_json::to_string(&credential).map_err(|e| e.to_string())?;
+        let inserted = self.lock().execute(
+            "INSERT OR IGNORE INTO webauthn_credential (credential_id, user_id, sign_count, data)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![credential.credential_id, credential.user_id, credential.sign_count, data],
+        ).map_err(sql)?;
+        if inserted == 0 {
+            return Err("credential is already registered".to_string());
+        }
+        Ok(())
+    }
+
+    fn get(&self, credential_id: &[u8]) -> Result<Option<WebAuthnCredential>, String> {
+        let row = self.lock().query_row(
+            "SELECT data, sign_count FROM webauthn_credential WHERE credential_id = ?1",
+            params![credential_id],
+            |r| Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)?)),
+        ).optional().map_err(sql)?;
+        row.map(|(data, sign_count)| credential_from_row(&data, sign_count)).transpose()
+    }
+
+    fn list(&self, user_id: &str) -> Result<Vec<WebAuthnCredential>, String> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT data, sign_count FROM webauthn_credential WHERE user_id = ?1",
+        ).map_err(sql)?;
+        let rows = stmt.query_map(params![user_id], |r| {
+            Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)?))
+        }).map_err(sql)?;
+        let mut credentials = rows
+            .map(|r| r.map_err(sql).and_then(|(data, sign_count)| credential_from_row(&data, sign_count)))
+            .collect::<Result<Vec<_>, String>>()?;
+        credentials.sort_by_key(|c| c.created_at);
+        Ok(credentials)
+    }
+
+    fn remove(&self, credential_id: &[u8]) -> Result<(), String> {
+        self.lock().execute(
+            "DELETE FROM webauthn_credential WHERE credential_id = ?1",
+            params![credential_id],
+        ).map_err(sql)?;
+        Ok(())
+    }
+
+    fn update_sign_count(&self, credential_id: &[u8], expected: u32, new: u32) -> Result<bool, String> {
+        let conn = self.lock();
+        let updated = conn.execute(
+            "UPDATE webauthn_credential SET sign_count = ?3 WHERE credential_id = ?1 AND sign_count = ?2",
+            params![credential_id, expected, new],
+        ).map_err(sql)?;
+        if updated == 1 {
+            return Ok(true);
+        }
+        let exists = conn.query_row(
+            "SELECT 1 FROM webauthn_credential WHERE credential_id = ?1",
+            params![credential_id],
+            |_| Ok(()),
+        ).optional().map_err(sql)?;
+        match exists {
+            Some(()) => Ok(false),
+            None => Err("unknown credential".to_string()),
+        }
+    }
+}
+
+struct PendingChallenge {
+    user_id: String,
+    challenge: Vec<u8>,
+    expires_at: u64,
+}
+
+/// WebAuthn MFA driver. See the module documentation for the ceremony flow.
+pub struct WebAuthnAuthDriver {
+    config: WebAuthnConfig,
+    store: Arc<dyn WebAuthnCredentialStore>,
+    /// Registration challenges, keyed by user id.
+    registrations: Mutex<HashMap<String, PendingChallenge>>,
+    /// Authentication challenges, keyed by session token.
+    assertions: Mutex<HashMap<String, PendingChallenge>>,
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn b64(bytes: &[u8]) -> String {
+    Base64UrlUnpadded::encode_string(bytes)
+}
+
+/// WebAuthn user handle for `user_id`: opaque, fixed length and stable.
+fn user_handle(user_id: &str) -> Vec<u8> {
+    Sha256::digest(user_id.as_bytes()).to_vec()
+}
+
+fn credential_descriptors(credentials: &[WebAuthnCredential]) -> Vec<serde_json::Value> {
+    credentials.iter()
+        .map(|c| serde_json::json!({ "type": "public-key", "id": b64(&c.credential_id) }))
+        .collect()
+}
+
+impl WebAuthnAuthDriver {
+    pub fn new(config: WebAuthnConfig, store: Arc<dyn WebAuthnCredentialStore>) -> Self {
+        Self {
+            config,
+            store,
+            registrations: Mutex::new(HashMap::new()),
+            assertions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn user_verification(&self) -> &'static str {
+        if self.config.require_user_verification { "required" } else { "preferred" }
+    }
+
+    fn new_challenge(&self, user_id: &str) -> PendingChallenge {
+        let mut challenge = vec![0u8; 32];
+        rand::thread_rng().fill_bytes(&mut challenge);
+        PendingChallenge {
+            user_id: user_id.to_string(),
+            challenge,
+            expires_at: now_secs().saturating_add(self.config.challenge_ttl_secs),
+        }
+    }
+
+    /// Store `pending` under `key`, dropping challenges that have expired.
+    fn remember<K: std::hash::Hash + Eq>(
+        map: &Mutex<HashMap<K, PendingChallenge>>,
+        key: K,
+        pending: PendingChallenge,
+    ) {
+        let now = now_secs();
+        let mut map = map.lock().unwrap_or_else(|p| p.into_inner());
+        map.retain(|_, p| p.expires_at > now);
+        map.insert(key, pending);
+    }
+
+    /// Remove and return the challenge under `key`. Challenges are single-use.
+    fn take<K, Q>(map: &Mutex<HashMap<K, PendingChallenge>>, key: &Q) -> Result<PendingChallenge, String>
+    where
+        K: std::borrow::Borrow<Q> + std::hash::Hash + Eq,
+        Q: std::hash::Hash + Eq + ?Sized,
+    {
+        let pending = map.lock().unwrap_or_else(|p| p.into_inner()).remove(key)
+            .ok_or("no pending WebAuthn challenge")?;
+        if pending.expires_at <= now_secs() {
+            return Err("WebAuthn challenge expired".to_string());
+        }
+        Ok(pending)
+    }
+
+    fn verify_client_data(&self, json: &[u8], ceremony: &str, challenge: &[u8]) -> Result<(), String> {
+        let client_data = CollectedClientData::parse(json)?;
+        if client_data.ceremony != ceremony {
+            return Err(format!("client data type is '{}', expected '{ceremony}'", client_data.ceremony));
+        }
+        if client_data.challenge_bytes()? != challenge {
+            return Err("challenge mismatch".to_string());
+        }
+        if !self.config.origins.contains(&client_data.origin) {
+            return Err(format!("origin '{}' is not allowed", client_data.origin));
+        }
+        if client_data.cross_origin {
+            return Err("cross-origin client data is not accepted".to_string());
+        }
+        Ok(())
+    }
+
+    fn verify_authenticator_data(&self, data: &AuthenticatorData) -> Result<(), String> {
+        if data.rp_id_hash[..] != Sha256::digest(self.config.rp_id.as_bytes())[..] {
+            return Err("RP ID hash mismatch".to_string());
+        }
+        if !data.user_present() {
+            return Err("user presence flag not set".to_string());
+        }
+        if self.config.require_user_verification && !data.user_verified() {
+            return Err("user verification required".to_string());
+        }
+        Ok(())
+    }
+
+    /// Begin registering a new authenticator for the user whose
+    /// `Principal::stable_id()` is `user_id`. Returns the
+    /// `PublicKeyCredentialCreationOptions`, binary fields base64url-encoded.
+    pub fn start_registration(
+        &self,
+        user_id: &str,
+        user_name: &str,
+        display_name: &str,
+    ) -> Result<serde_json::Value, String> {
+        let existing = self.store.list(user_id)?;
+        let pending = self.new_challenge(user_id);
+        let options = serde_json::json!({
+            "challenge": b64(&pending.challenge),
+            "rp": { "id": self.config.rp_id, "name": self.config.rp_name },
+            "user": {
+                "id": b64(&user_handle(user_id)),
+                "name": user_name,
+                "displayName": display_name,
+            },
+            "pubKeyCredParams": [{ "type": "public-key", "alg": COSE_ALG_ES256 }],
+            "timeout": self.config.challenge_ttl_secs.saturating_mul(1000),
+            "attestation": "none",
+            "authenticatorSelection": {
+                "residentKey": "discouraged",
+                "userVerification": self.user_verification(),
+            },
+            "excludeCredentials": credential_descriptors(&existing),
+        });
+        Self::remember(&self.registrations, user_id.to_string(), pending);
+        Ok(options)
+    }
+
+    /// Verify the browser's registration response and store the credential.
+    pub fn finish_registration(
+        &self,
+        user_id: &str,
+        client_data_json: &[u8],
+        attestation_object: &[u8],
+    ) -> Result<WebAuthnCredential, String> {
+        let pending = Self::take(&self.registrations, user_id)?;
+        self.verify_client_data(client_data_json, "webauthn.create", &pending.challenge)?;
+        let auth_data = parse_authenticator_data(&attestation_auth_data(attestation_object)?)?;
+        self.verify_authenticator_data(&auth_data)?;
+        let attested = auth_data.attested_credential.ok_or("no attested credential data")?;
+        if self.store.get(&attested.credential_id)?.is_some() {
+            return Err("credential is already registered".to_string());
+        }
+        let credential = WebAuthnCredential {
+            credential_id: attested.credential_id,
+            user_id: user_id.to_string(),
+            public_key: attested.public_key,
+            sign_count: auth_data.sign_count,
+            aaguid: attested.aaguid,
+            created_at: now_secs(),
+        };
+        self.store.insert(credential.clone())?;
+        Ok(credential)
+    }
+
+    /// Issue an assertion challenge for `user_id`, answered under `session_token`.
+    pub fn start_authentication(
+        &self,
+        user_id: &str,
+        session_token: &SessionToken,
+    ) -> Result<MfaChallenge, String> {
+        let credentials = self.store.list(user_id)?;
+        if credentials.is_empty() {
+            return Err(format!("no WebAuthn credentials registered for '{user_id}'"));
+        }
+        let pending = self.new_challenge(user_id);
+        let options = serde_json::json!({
+            "challenge": b64(&pending.challenge),
+            "rpId": self.config.rp_id,
+            "timeout": self.config.challenge_ttl_secs.saturating_mul(1000),
+            "userVerification": self.user_verification(),
+            "allowCredentials": credential_descriptors(&credentials),
+        });
+        Self::remember(&self.assertions, session_token.as_str().to_string(), pending);
+        Ok(MfaChallenge::WebAuthn { session_token: session_token.clone(), options })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn finish_authentication(
+        &self,
+        user_id: &str,
+        session_token: &SessionToken,
+        credential_id: &[u8],
+        client_data_json: &[u8],
+        authenticator_data: &[u8],
+        signature: &[u8],
+        user_handle: Option<&[u8]>,
+    ) -> Result<(), String> {
+        let pending = Self::take(&self.assertions, session_token.as_str())?;
+        if pending.user_id != user_id {
+            return Err("challenge was issued to another user".to_string());
+        }
+        let credential = self.store.get(credential_id)?
+            .filter(|c| c.user_id == user_id)
+            .ok_or("credential is not registered to this user")?;
+        if user_handle.is_some_and(|h| h != self::user_handle(user_id)) {
+            return Err("user handle mismatch".to_string());
+        }
+        self.verify_client_data(client_data_json, "webauthn.get", &pending.challenge)?;
+        let auth_data = parse_authenticator_data(authenticator_data)?;
+        self.verify_authenticator_data(&auth_data)?;
+
+        let key = VerifyingKey::from_sec1_bytes(&credential.public_key)
+            .map_err(|_| "stored public key is invalid".to_string())?;
+        let signature = Signature::from_der(signature).map_err(|_| "malformed signature".to_string())?;
+        let mut signed = authenticator_data.to_vec();
+        signed.extend(Sha256::digest(client_data_json));
+        key.verify(&signed, &signature).map_err(|_| "signature verification failed".to_string())?;
+
+        if auth_data.sign_count == 0 && credential.sign_count == 0 {
+            return Ok(());
+        }
+        if auth_data.sign_count <= credential.sign_count {
+            return Err("signature counter did not increase; the authenticator may be cloned".to_string());
+        }
+        if !self.store.update_sign_count(credential_id, credential.sign_count, auth_data.sign_count)? {
+            return Err("credential was used concurrently".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AuthDriver for WebAuthnAuthDriver {
+    async fn authenticate(
+        &self,
+        credentials: &Credentials,
+        ctx: &mut AuthPipelineContext,
+    ) -> AuthResult {
+        match credentials {
+            Credentials::WebAuthnBegin { session_token } => {
+                // The partial principal stays in the context for the assertion.
+                let Some(partial) = ctx.partial_principal.as_ref() else {
+                    return AuthResult::Continue;
+                };
+                match self.start_authentication(&partial.stable_id(), session_token) {
+                    Ok(challenge) => AuthResult::MfaRequired(challenge),
+                    Err(e) => AuthResult::Reject(e),
+                }
+            }
+            Credentials::WebAuthnAssertion {
+                session_token,
+                credential_id,
+                client_data_json,
+                authenticator_data,
+                signature,
+                user_handle,
+            } => {
+                let Some(partial) = ctx.partial_principal.take() else {
+                    return AuthResult::Continue;
+                };
+                match self.finish_authentication(
+                    &partial.stable_id(),
+                    session_token,
+                    credential_id,
+                    client_data_json,
+                    authenticator_data,
+                    signature,
+                    user_handle.as_deref(),
+                ) {
+                    Ok(()) => AuthResult::Authenticated(partial.into_principal(None)),
+                    Err(e) => AuthResult::Reject(format!("WebAuthn assertion rejected: {e}")),
+                }
+            }
+            _ => AuthResult::Continue,
+        }
+    }
+}
+
+/// A software authenticator for tests: one ES256 credential, `none` attestation.
+#[cfg(any(test, feature = "test-support"))]
+#[derive(Clone)]
+pub struct SoftAuthenticator {
+    rp_id: String,
+    origin: String,
+    credential_id: Vec<u8>,
+    key: p256::ecdsa::SigningKey,
+    user_handle: Option<Vec<u8>>,
+    pub sign_count: u32,
+    /// Added to `sign_count` on each assertion; 0 emulates an authenticator without a counter.
+    pub counter_step: u32,
+    /// Authenticator data flags; user present and verified by default.
+    pub flags: u8,
+    /// Reported as `crossOrigin` in client data.
+    pub cross_origin: bool,
+}
+
+#[cfg(any(test, feature = "test-support"))]
+impl SoftAuthenticator {
+    pub fn new(rp_id: &str, origin: &str) -> Self {
+        let mut credential_id = vec![0u8; 16];
+        rand::thread_rng().fill_bytes(&mut credential_id);
+        Self {
+            rp_id: rp_id.to_string(),
+            origin: origin.to_string(),
+            credential_id,
+            key: p256::ecdsa::SigningKey::random(&mut rand::rngs::OsRng),
+            user_handle: None,
+            sign_count: 0,
+            counter_step: 1,
+            flags: super::webauthn_proto::FLAG_USER_PRESENT | super::webauthn_proto::FLAG_USER_VERIFIED,
+            cross_origin: false,
+        }
+    }
+
+    pub fn credential_id(&self) -> &[u8] {
+        &self.credential_id
+    }
+
+    fn client_data(&self, ceremony: &str, options: &serde_json::Value) -> Vec<u8> {
+        serde_json::to_vec(&serde_json::json!({
+            "type": ceremony,
+            "challenge": options["challenge"],
+            "origin": self.origin,
+            "crossOrigin": self.cross_origin,
+        }))
+        .unwrap_or_default()
+    }
+
+    fn authenticator_data(&self, flags: u8) -> Vec<u8> {
+        let mut data = Sha256::digest(self.rp_id.as_bytes()).to_vec();
+        data.push(flags);
+        data.extend(self.sign_count.to_be_bytes());
+        data
+    }
+
+    /// Answer creation options; returns `(client_data_json, attestation_object)`.
+    pub fn create(&mut self, options: &serde_json::Value) -> (Vec<u8>, Vec<u8>) {
+        use super::webauthn_proto::{encode, es256_cose_key, Cbor, FLAG_ATTESTED_CREDENTIAL};
+
+        self.user_handle = options["user"]["id"].as_str()
+            .and_then(|id| Base64UrlUnpadded::decode_vec(id).ok());
+        let mut auth_data = self.authenticator_data(self.flags | FLAG_ATTESTED_CREDENTIAL);
+        auth_data.extend([0u8; 16]);
+        auth_data.extend((self.credential_id.len() as u16).to_be_bytes());
+        auth_data.extend(&self.credential_id);
+        let point = self.key.verifying_key().to_encoded_point(false);
+        encode(&es256_cose_key(point.as_bytes()), &mut auth_data);
+
+        let mut attestation_object = Vec::new();
+        encode(&Cbor::Map(vec![
+            (Cbor::Text("fmt".to_string()), Cbor::Text("none".to_string())),
+            (Cbor::Text("attStmt".to_string()), Cbor::Map(vec![])),
+            (Cbor::Text("authData".to_string()), Cbor::Bytes(auth_data)),
+        ]), &mut attestation_object);
+        (self.client_data("webauthn.create", options), attestation_object)
+    }
+
+    /// Answer the request options in a `MfaChallenge::WebAuthn`.
+    pub fn get(&mut self, challenge: &MfaChallenge) -> Credentials {
+        use p256::ecdsa::signature::Signer;
+
+        let (session_token, options) = match challenge {
+            MfaChallenge::WebAuthn { session_token, options } => (session_token.clone(), options),
+            _ => (SessionToken::new(), &serde_json::Value::Null),
+        };
+        self.sign_count = self.sign_count.wrapping_add(self.counter_step);
+        let client_data_json = self.client_data("webauthn.get", options);
+        let authenticator_data = self.authenticator_data(self.flags);
+        let mut signed = authenticator_data.clone();
+        signed.extend(Sha256::digest(&client_data_json));
+        let signature: Signature = self.key.sign(&signed);
+        Credentials::WebAuthnAssertion {
+            session_token,
+            credential_id: self.credential_id.clone(),
+            client_data_json,
+            authenticator_data,
+            signature: signature.to_der().as_bytes().to_vec(),
+            user_handle: self.user_handle.clone(),
+        }
+    }
+}
@@ -0,0 +1,384 @@
+//! WebAuthn wire formats: the CBOR subset used by attestation objects and
+//! COSE keys, authenticator data, and collected client data.
+//!
+//! Reference: W3C Web Authentication Level 2, RFC 8949 (CBOR), RFC 9053 (COSE).
+//! Only ES256 (ECDSA P-256 with SHA-256) credential keys are supported.
+
+use base64ct::{Base64UrlUnpadded, Encoding};
+use serde::Deserialize;
+
+// ── Constants ────────────────────────────────────────────────────────────────
+
+pub const FLAG_USER_PRESENT: u8       = 0x01;
+pub const FLAG_USER_VERIFIED: u8      = 0x04;
+pub const FLAG_ATTESTED_CREDENTIAL: u8 = 0x40;
+
+pub const COSE_ALG_ES256: i64 = -7;
+
+const COSE_KEY_KTY: i64 = 1;
+const COSE_KEY_ALG: i64 = 3;
+const COSE_KEY_CRV: i64 = -1;
+const COSE_KEY_X: i64 = -2;
+const COSE_KEY_Y: i64 = -3;
+const COSE_KTY_EC2: i64 = 2;
+const COSE_CRV_P256: i64 = 1;
+
+/// Nested CBOR deeper than this is rejected.
+const MAX_DEPTH: usize = 16;
+
+// ── CBOR ─────────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cbor {
+    Int(i64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Cbor>),
+    Map(Vec<(Cbor, Cbor)>),
+    Bool(bool),
+    Null,
+}
+
+impl Cbor {
+    /// Look up a map entry by text or integer key.
+    pub fn get(&self, key: &Cbor) -> Option<&Cbor> {
+        match self {
+            Cbor::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn get_text(&self, key: &str) -> Option<&Cbor> {
+        self.get(&Cbor::Text(key.to_string()))
+    }
+}
+
+/// Decode one CBOR item, returning it and the bytes that follow it.
+/// Indefinite lengths, tags and floats are not used by WebAuthn and are rejected.
+pub fn decode(input: &[u8]) -> Result<(Cbor, &[u8]), String> {
+    decode_item(input, 0)
+}
+
+fn decode_item(input: &[u8], depth: usize) -> Result<(Cbor, &[u8]), String> {
+    if depth > MAX_DEPTH {
+        return Err("CBOR nested too deeply".to_string());
+    }
+    let (&initial, rest) = input.split_first().ok_or("truncated CBOR")?;
+    let major = initial >> 5;
+    let info = initial & 0x1f;
+    if major == 7 {
+        return match info {
+            20 => Ok((Cbor::Bool(false), rest)),
+            21 => Ok((Cbor::Bool(true), rest)),
+            22 => Ok((Cbor::Null, rest)),
+            _ => Err(format!("unsupported CBOR simple value {info}")),
+        };
+    }
+    let (arg, rest) = read_argument(info, rest)?;
+    match major {
+        0 => Ok((Cbor::Int(i64::try_from(arg).map_err(|_| "CBOR integer out of range")?), rest)),
+        1 => {
+            let n = i64::try_from(arg).map_err(|_| "CBOR integer out of range")?;
+            Ok((Cbor::Int(-1 - n), rest))
+        }
+        2 | 3 => {
+            let len = usize::try_from(arg).map_err(|_| "CBOR length out of range")?;
+            if rest.len() < len {
+                return Err("truncated CBOR string".to_string());
+            }
+            let (data, rest) = rest.split_at(len);
+            if major == 2 {
+                Ok((Cbor::Bytes(data.to_vec()), rest))
+            } else {
+                let text = std::str::from_utf8(data).map_err(|_| "CBOR text is not UTF-8")?;
+                Ok((Cbor::Text(text.to_string()), rest))
+            }
+        }
+        4 | 5 => {
+            // Every item takes at least one byte, which bounds the allocation.
+            let count = usize::try_from(arg).ok().filter(|&n| n <= rest.len())
+                .ok_or("truncated CBOR container")?;
+            let mut rest = rest;
+            if major == 4 {
+                let mut items = Vec::with_capacity(count);
+                for _ in 0..count {
+                    let (item, next) = decode_item(rest, depth + 1)?;
+                    items.push(item);
+                    rest = next;
+                }
+                Ok((Cbor::Array(items), rest))
+            } else {
+                let mut entries = Vec::with_capacity(count);
+                for _ in 0..count {
+                    let (key, next) = decode_item(rest, depth + 1)?;
+                    let (value, next) = decode_item(next, depth + 1)?;
+                    entries.push((key, value));
+                    rest = next;
+                }
+                Ok((Cbor::Map(entries), rest))
+            }
+        }
+        _ => Err(format!("unsupported CBOR major type {major}")),
+    }
+}
+
+fn read_argument(info: u8, input: &[u8]) -> Result<(u64, &[u8]), String> {
+    let width = match info {
+        0..=23 => return Ok((u64::from(info), input)),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        _ => return Err("indefinite-length CBOR is not supported".to_string()),
+    };
+    if input.len() < width {
+        return Err("truncated CBOR argument".to_string());
+    }
+    let (bytes, rest) = input.split_at(width);
+    Ok((bytes.iter().fold(0u64, |acc, &b| (acc << 8) | u64::from(b)), rest))
+}
+
+/// Encode a CBOR item. Used by the software authenticator in tests.
+#[cfg(any(test, feature = "test-support"))]
+pub fn encode(value: &Cbor, out: &mut Vec<u8>) {
+    fn head(major: u8, arg: u64, out: &mut Vec<u8>) {
+        let major = major << 5;
+        match arg {
+            0..=23 => out.push(major | arg as u8),
+            24..=0xff => out.extend([major | 24, arg as u8]),
+            0x100..=0xffff => {
+                out.push(major | 25);
+                out.extend((arg as u16).to_be_bytes());
+            }
+            0x1_0000..=0xffff_ffff => {
+                out.push(major | 26);
+                out.extend((arg as u32).to_be_bytes());
+            }
+            _ => {
+                out.push(major | 27);
+                out.extend(arg.to_be_bytes());
+            }
+        }
+    }
+    match value {
+        Cbor::Int(n) if *n >= 0 => head(0, *n as u64, out),
+        Cbor::Int(n) => head(1, (-1 - *n) as u64, out),
+        Cbor::Bytes(b) => {
+            head(2, b.len() as u64, out);
+            out.extend(b);
+        }
+        Cbor::Text(t) => {
+            head(3, t.len() as u64, out);
+            out.extend(t.as_bytes());
+        }
+        Cbor::Array(items) => {
+            head(4, items.len() as u64, out);
+            for item in items {
+                encode(item, out);
+            }
+        }
+        Cbor::Map(entries) => {
+            head(5, entries.len() as u64, out);
+            for (k, v) in entries {
+                encode(k, out);
+                encode(v, out);
+            }
+        }
+        Cbor::Bool(false) => out.push(0xf4),
+        Cbor::Bool(true) => out.push(0xf5),
+        Cbor::Null => out.push(0xf6),
+    }
+}
+
+// ── Authenticator data ───────────────────────────────────────────────────────
+
+#[derive(Debug)]
+pub struct AttestedCredential {
+    pub aaguid: [u8; 16],
+    pub credential_id: Vec<u8>,
+    /// SEC1 uncompressed P-256 point.
+    pub public_key: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct AuthenticatorData {
+    pub rp_id_hash: [u8; 32],
+    pub flags: u8,
+    pub sign_count: u32,
+    pub attested_credential: Option<AttestedCredential>,
+}
+
+impl AuthenticatorData {
+    pub fn user_present(&self) -> bool {
+        self.flags & FLAG_USER_PRESENT != 0
+    }
+
+    pub fn user_verified(&self) -> bool {
+        self.flags & FLAG_USER_VERIFIED != 0
+    }
+}
+
+/// Parse authenticator data; extension data after the credential is ignored.
+pub fn parse_authenticator_data(data: &[u8]) -> Result<AuthenticatorData, String> {
+    if data.len() < 37 {
+        return Err("authenticator data too short".to_string());
+    }
+    let mut rp_id_hash = [0u8; 32];
+    rp_id_hash.copy_from_slice(&data[..32]);
+    let flags = data[32];
+    let sign_count = u32::from_be_bytes([data[33], data[34], data[35], data[36]]);
+
+    let attested_credential = if flags & FLAG_ATTESTED_CREDENTIAL != 0 {
+        let rest = &data[37..];
+        if rest.len() < 18 {
+            return Err("attested credential data too short".to_string());
+        }
+        let mut aaguid = [0u8; 16];
+        aaguid.copy_from_slice(&rest[..16]);
+        let id_len = usize::from(u16::from_be_bytes([rest[16], rest[17]]));
+        let rest = &rest[18..];
+        if rest.len() < id_len {
+            return Err("credential id truncated".to_string());
+        }
+        let (credential_id, rest) = rest.split_at(id_len);
+        let (cose_key, _extensions) = decode(rest)?;
+        Some(AttestedCredential {
+            aaguid,
+            credential_id: credential_id.to_vec(),
+            public_key: es256_public_key(&cose_key)?,
+        })
+    } else {
+        None
+    };
+
+    Ok(AuthenticatorData { rp_id_hash, flags, sign_count, attested_credential })
+}
+
+/// Extract the SEC1 point from an ES256 COSE_Key.
+pub fn es256_public_key(cose_key: &Cbor) -> Result<Vec<u8>, String> {
+    let int = |label: i64| match cose_key.get(&Cbor::Int(label)) {
+        Some(Cbor::Int(n)) => Some(*n),
+        _ => None,
+    };
+    let coordinate = |label: i64| match cose_key.get(&Cbor::Int(label)) {
+        Some(Cbor::Bytes(b)) if b.len() == 32 => Ok(b.as_slice()),
+        _ => Err("COSE key coordinate missing or malformed".to_string()),
+    };
+    if int(COSE_KEY_KTY) != Some(COSE_KTY_EC2)
+        || int(COSE_KEY_ALG) != Some(COSE_ALG_ES256)
+        || int(COSE_KEY_CRV) != Some(COSE_CRV_P256)
+    {
+        return Err("only ES256 credentials are supported".to_string());
+    }
+    let mut point = vec![0x04];
+    point.extend(coordinate(COSE_KEY_X)?);
+    point.extend(coordinate(COSE_KEY_Y)?);
+    Ok(point)
+}
+
+/// The COSE_Key for a SEC1 uncompressed P-256 point.
+#[cfg(any(test, feature = "test-support"))]
+pub fn es256_cose_key(point: &[u8]) -> Cbor {
+    Cbor::Map(vec![
+        (Cbor::Int(COSE_KEY_KTY), Cbor::Int(COSE_KTY_EC2)),
+        (Cbor::Int(COSE_KEY_ALG), Cbor::Int(COSE_ALG_ES256)),
+        (Cbor::Int(COSE_KEY_CRV), Cbor::Int(COSE_CRV_P256)),
+        (Cbor::Int(COSE_KEY_X), Cbor::Bytes(point[1..33].to_vec())),
+        (Cbor::Int(COSE_KEY_Y), Cbor::Bytes(point[33..65].to_vec())),
+    ])
+}
+
+/// Pull `authData` out of an attestation object. The attestation statement is
+/// not evaluated: registration requests `none` conveyance.
+pub fn attestation_auth_data(attestation_object: &[u8]) -> Result<Vec<u8>, String> {
+    let (object, _) = decode(attestation_object)?;
+    match object.get_text("authData") {
+        Some(Cbor::Bytes(auth_data)) => Ok(auth_data.clone()),
+        _ => Err("attestation object has no authData".to_string()),
+    }
+}
+
+// ── Client data ──────────────────────────────────────────────────────────────
+
+#[derive(Debug, Deserialize)]
+pub struct CollectedClientData {
+    #[serde(rename = "type")]
+    pub ceremony: String,
+    pub challenge: String,
+    pub origin: String,
+    /// Set when the ceremony ran in an iframe of another origin.
+    #[serde(default, rename = "crossOrigin")]
+    pub cross_origin: bool,
+}
+
+impl CollectedClientData {
+    pub fn parse(json: &[u8]) -> Result<Self, String> {
+        serde_json::from_slice(json).map_err(|e| format!("invalid client data: {e}"))
+    }
+
+    pub fn challenge_bytes(&self) -> Result<Vec<u8>, String> {
+        Base64UrlUnpadded::decode_vec(&self.challenge).map_err(|_| "invalid challenge encoding".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cbor_round_trips() {
+        let value = Cbor::Map(vec![
+            (Cbor::Text("fmt".to_string()), Cbor::Text("none".to_string())),
+            (Cbor::Int(-3), Cbor::Bytes(vec![7; 300])),
+            (Cbor::Int(70_000), Cbor::Array(vec![Cbor::Bool(true), Cbor::Null, Cbor::Int(-70_000)])),
+        ]);
+        let mut bytes = Vec::new();
+        encode(&value, &mut bytes);
+        bytes.push(0xaa);
+        let (decoded, rest) = decode(&bytes).unwrap();
+        assert_eq!(decoded, value);
+        assert_eq!(rest, &[0xaa]);
+    }
+
+    #[test]
+    fn cbor_rejects_malformed_input() {
+        assert!(decode(&[]).is_err());
+        // Byte string claiming 5 bytes with only 2 present.
+        assert!(decode(&[0x45, 1, 2]).is_err());
+        // Indefinite-length map.
+        assert!(decode(&[0xbf, 0xff]).is_err());
+        // Array claiming far more items than there are bytes.
+        assert!(decode(&[0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).is_err());
+        // Deep nesting.
+        assert!(decode(&[0x81; 64]).is_err());
+    }
+
+    #[test]
+    fn authenticator_data_parses_attested_credential() {
+        let point: Vec<u8> = std::iter::once(4).chain(1..=64).collect();
+        let mut data = vec![9u8; 32];
+        data.push(FLAG_USER_PRESENT | FLAG_ATTESTED_CREDENTIAL);
+        data.extend(5u32.to_be_bytes());
+        data.extend([0u8; 16]);
+        data.extend(3u16.to_be_bytes());
+        data.extend([0xc1, 0xc2, 0xc3]);
+        encode(&es256_cose_key(&point), &mut data);
+
+        let parsed = parse_authenticator_data(&data).unwrap();
+        assert!(parsed.user_present());
+        assert!(!parsed.user_verified());
+        assert_eq!(parsed.sign_count, 5);
+        let credential = parsed.attested_credential.unwrap();
+        assert_eq!(credential.credential_id, vec![0xc1, 0xc2, 0xc3]);
+        assert_eq!(credential.public_key, point);
+    }
+
+    #[test]
+    fn cose_key_rejects_other_algorithms() {
+        let key = Cbor::Map(vec![
+            (Cbor::Int(COSE_KEY_KTY), Cbor::Int(1)),
+            (Cbor::Int(COSE_KEY_ALG), Cbor::Int(-8)),
+        ]);
+        assert!(es256_public_key(&key).is_err());
+    }
+}
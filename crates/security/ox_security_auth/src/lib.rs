@@ -9,5 +9,7 @@ pub use drivers::{
     OidcAuthDriver, OidcConfig, JwksFetchFn,
     RadiusAuthDriver, RadiusConfig, RadiusUdpSendFn,
     TacacsAuthDriver, TotpAuthDriver, TotpSecretLookupFn,
+    InMemoryWebAuthnStore, OxPersistenceWebAuthnStore, WebAuthnAuthDriver, WebAuthnConfig,
+    WebAuthnCredential, WebAuthnCredentialStore,
 };
 pub use drivers::tacacs::{TacacsConfig, TcpSendFn as TacacsTcpSendFn};
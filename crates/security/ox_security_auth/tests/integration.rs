@@ -76,6 +76,7 @@ async fn pipeline_continues_through_misses() {
                 groups: vec![],
                 tenant_id: TenantId::from_str("test").unwrap(),
                 session_id: None,
+                attributes: Default::default(),
             })
         }
     }
@@ -302,6 +303,33 @@ async fn ldap_driver_continues_on_adapter_error() {
     assert!(matches!(result, AuthResult::Continue));
 }
 
+#[tokio::test]
+async fn ldap_driver_looks_up_entries_as_service_account() {
+    use ox_security_auth::drivers::ldap::LdapSearchResult;
+    use std::collections::HashMap;
+
+    let groups = vec!["CN=Autoenroll Machines,OU=Groups,DC=example,DC=com".to_string()];
+    let attrs = HashMap::from([
+        ("memberOf".to_string(), groups.clone()),
+        ("dNSHostName".to_string(), vec!["host1.example.com".to_string()]),
+    ]);
+    let driver = LdapAuthDriver::with_mock(
+        ldap_config(),
+        MockLdapAdapter::with_search(LdapSearchResult::Found(attrs)),
+    );
+    let found = driver
+        .lookup_entry("cn=svc,dc=example,dc=com", "pw", "sAMAccountName", "HOST1$", &["dNSHostName"])
+        .await
+        .unwrap();
+    assert_eq!(found.groups, groups);
+    assert_eq!(found.attribute("dNSHostName"), Some("host1.example.com"));
+
+    // A missing entry is an error here, unlike a user bind
+    let driver = LdapAuthDriver::with_mock(ldap_config(), MockLdapAdapter::with_search(LdapSearchResult::NoSuchEntry));
+    let missing = driver.lookup_entry("cn=svc,dc=example,dc=com", "pw", "sAMAccountName", "ghost$", &[]).await;
+    assert!(missing.unwrap_err().contains("ghost$"));
+}
+
 #[tokio::test]
 async fn ad_driver_returns_last_reject_when_all_forms_fail() {
     use ox_security_auth::drivers::ad::BindDnCapture;
@@ -52,6 +52,7 @@ impl AuthDriver for StubFirstFactorDriver {
                     source: AuthSource::Local,
                     groups: vec![GroupId::new("users")],
                     tenant_id: self.tenant_id.clone(),
+                    attributes: Default::default(),
                 });
                 AuthResult::MfaRequired(MfaChallenge::CodeRequired {
                     session_token: SessionToken::new(),
@@ -0,0 +1,384 @@
+//! WebAuthn ceremonies against `SoftAuthenticator` fixtures.
+//!
+//! Registration goes straight through the driver; authentication runs in an
+//! `AuthPipeline` behind a stub first-factor driver:
+//!   1. UsernamePassword → stub sets partial_principal and returns MfaRequired.
+//!   2. WebAuthnBegin → WebAuthnAuthDriver returns MfaRequired(WebAuthn).
+//!   3. WebAuthnAssertion → WebAuthnAuthDriver verifies → Authenticated.
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::str::FromStr;
+use std::sync::Arc;
+use async_trait::async_trait;
+use ox_security_auth::drivers::SoftAuthenticator;
+use ox_security_auth::{
+    AuthPipeline, InMemoryWebAuthnStore, OxPersistenceWebAuthnStore, WebAuthnAuthDriver,
+    WebAuthnConfig, WebAuthnCredentialStore,
+};
+use ox_security_core::{
+    drivers::AuthDriver, AuthPipelineContext, AuthResult, AuthSource, Credentials, GroupId,
+    MfaChallenge, PartialPrincipal, PrincipalId, SessionToken, TenantId,
+};
+use secrecy::SecretString;
+
+const RP_ID: &str = "example.com";
+const ORIGIN: &str = "https://login.example.com";
+
+fn config() -> WebAuthnConfig {
+    WebAuthnConfig {
+        rp_id: RP_ID.to_string(),
+        rp_name: "Example".to_string(),
+        origins: vec![ORIGIN.to_string()],
+        require_user_verification: true,
+        challenge_ttl_secs: 300,
+    }
+}
+
+fn driver_with_store() -> (Arc<WebAuthnAuthDriver>, Arc<InMemoryWebAuthnStore>) {
+    let store = Arc::new(InMemoryWebAuthnStore::new());
+    (Arc::new(WebAuthnAuthDriver::new(config(), store.clone())), store)
+}
+
+fn register(driver: &WebAuthnAuthDriver, user_id: &str) -> SoftAuthenticator {
+    let mut authenticator = SoftAuthenticator::new(RP_ID, ORIGIN);
+    let options = driver.start_registration(user_id, "alice", "Alice").unwrap();
+    let (client_data, attestation) = authenticator.create(&options);
+    driver.finish_registration(user_id, &client_data, &attestation).unwrap();
+    authenticator
+}
+
+/// What the first factor knows about `username`. Like a real login, every
+/// call gets a new `PrincipalId`.
+fn partial_principal(username: &str, tenant_id: &TenantId) -> PartialPrincipal {
+    PartialPrincipal {
+        id: PrincipalId::new(),
+        display_name: username.to_string(),
+        source: AuthSource::Local,
+        groups: vec![GroupId::new("users")],
+        tenant_id: tenant_id.clone(),
+        attributes: Default::default(),
+    }
+}
+
+/// The stable id credentials are registered under for "alice".
+fn alice() -> String {
+    partial_principal("alice", &TenantId::from_str("webauthn-test").unwrap()).stable_id()
+}
+
+/// Accepts any password and hands the user to the MFA step.
+struct StubFirstFactorDriver;
+
+#[async_trait]
+impl AuthDriver for StubFirstFactorDriver {
+    async fn authenticate(
+        &self,
+        credentials: &Credentials,
+        ctx: &mut AuthPipelineContext,
+    ) -> AuthResult {
+        match credentials {
+            Credentials::UsernamePassword { username, .. } => {
+                ctx.partial_principal = Some(partial_principal(username, &ctx.tenant_id));
+                AuthResult::MfaRequired(MfaChallenge::CodeRequired {
+                    session_token: SessionToken::new(),
+                })
+            }
+            _ => AuthResult::Continue,
+        }
+    }
+}
+
+fn pipeline(driver: Arc<WebAuthnAuthDriver>) -> AuthPipeline {
+    AuthPipeline::new(vec![Arc::new(StubFirstFactorDriver), driver])
+}
+
+fn new_ctx() -> AuthPipelineContext {
+    AuthPipelineContext {
+        partial_principal: None,
+        tenant_id: TenantId::from_str("webauthn-test").unwrap(),
+        source_ip: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+    }
+}
+
+/// Run the password step and `WebAuthnBegin`, returning the WebAuthn challenge.
+async fn begin(pipeline: &AuthPipeline, ctx: &mut AuthPipelineContext) -> MfaChallenge {
+    let password = Credentials::UsernamePassword {
+        username: "alice".to_string(),
+        password: SecretString::new("hunter2".to_string()),
+    };
+    let session_token = match pipeline.authenticate(&password, ctx).await {
+        AuthResult::MfaRequired(MfaChallenge::CodeRequired { session_token }) => session_token,
+        _ => panic!("expected MfaRequired from the first factor"),
+    };
+    match pipeline.authenticate(&Credentials::WebAuthnBegin { session_token }, ctx).await {
+        AuthResult::MfaRequired(challenge @ MfaChallenge::WebAuthn { .. }) => challenge,
+        _ => panic!("expected a WebAuthn challenge"),
+    }
+}
+
+// ── Registration ─────────────────────────────────────────────────────────────
+
+#[test]
+fn registration_stores_credential() {
+    let (driver, store) = driver_with_store();
+    let user_id = alice();
+    let authenticator = register(&driver, &user_id);
+
+    let stored = store.list(&user_id).unwrap();
+    assert_eq!(stored.len(), 1);
+    assert_eq!(stored[0].credential_id, authenticator.credential_id());
+    assert_eq!(stored[0].public_key.len(), 65);
+
+    // A second registration excludes the existing credential, and the same
+    // authenticator cannot be registered twice.
+    let options = driver.start_registration(&user_id, "alice", "Alice").unwrap();
+    assert_eq!(options["excludeCredentials"].as_array().unwrap().len(), 1);
+    let (client_data, attestation) = authenticator.clone().create(&options);
+    assert!(driver.finish_registration(&user_id, &client_data, &attestation).is_err());
+}
+
+#[test]
+fn registration_rejects_foreign_origin_and_rp() {
+    let (driver, _) = driver_with_store();
+    let user_id = alice();
+
+    let options = driver.start_registration(&user_id, "alice", "Alice").unwrap();
+    let (client_data, attestation) = SoftAuthenticator::new(RP_ID, "https://evil.example.net").create(&options);
+    let err = driver.finish_registration(&user_id, &client_data, &attestation).unwrap_err();
+    assert!(err.contains("origin"), "{err}");
+
+    let options = driver.start_registration(&user_id, "alice", "Alice").unwrap();
+    let (client_data, attestation) = SoftAuthenticator::new("evil.example.net", ORIGIN).create(&options);
+    let err = driver.finish_registration(&user_id, &client_data, &attestation).unwrap_err();
+    assert!(err.contains("RP ID"), "{err}");
+}
+
+#[test]
+fn registration_rejects_cross_origin_client_data() {
+    let (driver, _) = driver_with_store();
+    let user_id = alice();
+    let mut authenticator = SoftAuthenticator::new(RP_ID, ORIGIN);
+    authenticator.cross_origin = true;
+    let options = driver.start_registration(&user_id, "alice", "Alice").unwrap();
+    let (client_data, attestation) = authenticator.create(&options);
+    let err = driver.finish_registration(&user_id, &client_data, &attestation).unwrap_err();
+    assert!(err.contains("cross-origin"), "{err}");
+}
+
+#[test]
+fn registration_challenge_is_single_use() {
+    let (driver, _) = driver_with_store();
+    let user_id = alice();
+    let options = driver.start_registration(&user_id, "alice", "Alice").unwrap();
+    let (client_data, attestation) = SoftAuthenticator::new(RP_ID, ORIGIN).create(&options);
+    driver.finish_registration(&user_id, &client_data, &attestation).unwrap();
+    assert!(driver.finish_registration(&user_id, &client_data, &attestation).is_err());
+}
+
+#[test]
+fn registration_requires_user_verification() {
+    let (driver, _) = driver_with_store();
+    let user_id = alice();
+    let mut authenticator = SoftAuthenticator::new(RP_ID, ORIGIN);
+    authenticator.flags = 0x01; // user present only
+    let options = driver.start_registration(&user_id, "alice", "Alice").unwrap();
+    let (client_data, attestation) = authenticator.create(&options);
+    let err = driver.finish_registration(&user_id, &client_data, &attestation).unwrap_err();
+    assert!(err.contains("verification"), "{err}");
+}
+
+// ── Authentication ───────────────────────────────────────────────────────────
+
+#[tokio::test]
+async fn password_then_security_key_authenticates() {
+    let (driver, store) = driver_with_store();
+    let user_id = alice();
+    let mut authenticator = register(&driver, &user_id);
+    let pipeline = pipeline(driver);
+
+    let mut ctx = new_ctx();
+    let challenge = begin(&pipeline, &mut ctx).await;
+    if let MfaChallenge::WebAuthn { options, .. } = &challenge {
+        assert_eq!(options["rpId"], RP_ID);
+        assert_eq!(options["allowCredentials"].as_array().unwrap().len(), 1);
+    }
+    let first = match pipeline.authenticate(&authenticator.get(&challenge), &mut ctx).await {
+        AuthResult::Authenticated(principal) => principal,
+        _ => panic!("expected Authenticated"),
+    };
+    assert_eq!(first.stable_id(), user_id);
+    assert_eq!(first.display_name, "alice");
+    assert_eq!(store.get(authenticator.credential_id()).unwrap().unwrap().sign_count, 1);
+
+    // The next login has a new PrincipalId and still finds the credential.
+    let mut ctx = new_ctx();
+    let challenge = begin(&pipeline, &mut ctx).await;
+    match pipeline.authenticate(&authenticator.get(&challenge), &mut ctx).await {
+        AuthResult::Authenticated(second) => {
+            assert_ne!(second.id, first.id);
+            assert_eq!(second.stable_id(), user_id);
+        }
+        _ => panic!("expected Authenticated"),
+    }
+}
+
+#[tokio::test]
+async fn cloned_authenticator_is_rejected() {
+    let (driver, _) = driver_with_store();
+    let user_id = alice();
+    let mut original = register(&driver, &user_id);
+    let mut clone = original.clone();
+    let pipeline = pipeline(driver);
+
+    let mut ctx = new_ctx();
+    let challenge = begin(&pipeline, &mut ctx).await;
+    let result = pipeline.authenticate(&original.get(&challenge), &mut ctx).await;
+    assert!(matches!(result, AuthResult::Authenticated(_)));
+
+    // The clone's counter lags behind the original's.
+    let mut ctx = new_ctx();
+    let challenge = begin(&pipeline, &mut ctx).await;
+    match pipeline.authenticate(&clone.get(&challenge), &mut ctx).await {
+        AuthResult::Reject(reason) => assert!(reason.contains("counter"), "{reason}"),
+        _ => panic!("expected Reject"),
+    }
+}
+
+#[tokio::test]
+async fn authenticator_without_counter_is_accepted() {
+    let (driver, _) = driver_with_store();
+    let user_id = alice();
+    let mut authenticator = register(&driver, &user_id);
+    authenticator.counter_step = 0;
+    let pipeline = pipeline(driver);
+
+    for _ in 0..2 {
+        let mut ctx = new_ctx();
+        let challenge = begin(&pipeline, &mut ctx).await;
+        let result = pipeline.authenticate(&authenticator.get(&challenge), &mut ctx).await;
+        assert!(matches!(result, AuthResult::Authenticated(_)));
+    }
+}
+
+#[tokio::test]
+async fn tampered_assertion_is_rejected() {
+    let (driver, _) = driver_with_store();
+    let user_id = alice();
+    let mut authenticator = register(&driver, &user_id);
+    let pipeline = pipeline(driver);
+
+    let mut ctx = new_ctx();
+    let challenge = begin(&pipeline, &mut ctx).await;
+    let mut assertion = authenticator.get(&challenge);
+    if let Credentials::WebAuthnAssertion { authenticator_data, .. } = &mut assertion {
+        authenticator_data[36] ^= 0x01; // alter the signed counter
+    }
+    match pipeline.authenticate(&assertion, &mut ctx).await {
+        AuthResult::Reject(reason) => assert!(reason.contains("signature"), "{reason}"),
+        _ => panic!("expected Reject"),
+    }
+}
+
+#[tokio::test]
+async fn assertion_for_another_session_is_rejected() {
+    let (driver, _) = driver_with_store();
+    let user_id = alice();
+    let mut authenticator = register(&driver, &user_id);
+    let pipeline = pipeline(driver);
+
+    let mut first = new_ctx();
+    let first_challenge = begin(&pipeline, &mut first).await;
+    let mut second = new_ctx();
+    let second_challenge = begin(&pipeline, &mut second).await;
+
+    // Answer the second challenge but present it under the first session.
+    let mut assertion = authenticator.get(&second_challenge);
+    if let (
+        Credentials::WebAuthnAssertion { session_token, .. },
+        MfaChallenge::WebAuthn { session_token: first_token, .. },
+    ) = (&mut assertion, &first_challenge)
+    {
+        *session_token = first_token.clone();
+    }
+    match pipeline.authenticate(&assertion, &mut first).await {
+        AuthResult::Reject(reason) => assert!(reason.contains("challenge"), "{reason}"),
+        _ => panic!("expected Reject"),
+    }
+}
+
+#[tokio::test]
+async fn begin_without_partial_principal_continues() {
+    let (driver, _) = driver_with_store();
+    let result = driver
+        .authenticate(&Credentials::WebAuthnBegin { session_token: SessionToken::new() }, &mut new_ctx())
+        .await;
+    assert!(matches!(result, AuthResult::Continue));
+}
+
+#[tokio::test]
+async fn begin_without_registered_credentials_rejects() {
+    let (driver, _) = driver_with_store();
+    let pipeline = pipeline(driver);
+    let mut ctx = new_ctx();
+    let password = Credentials::UsernamePassword {
+        username: "alice".to_string(),
+        password: SecretString::new("hunter2".to_string()),
+    };
+    let session_token = match pipeline.authenticate(&password, &mut ctx).await {
+        AuthResult::MfaRequired(MfaChallenge::CodeRequired { session_token }) => session_token,
+        _ => panic!("expected MfaRequired"),
+    };
+    let result = pipeline.authenticate(&Credentials::WebAuthnBegin { session_token }, &mut ctx).await;
+    assert!(matches!(result, AuthResult::Reject(_)));
+}
+
+#[tokio::test]
+async fn cross_origin_assertion_is_rejected() {
+    let (driver, _) = driver_with_store();
+    let mut authenticator = register(&driver, &alice());
+    authenticator.cross_origin = true;
+    let pipeline = pipeline(driver);
+
+    let mut ctx = new_ctx();
+    let challenge = begin(&pipeline, &mut ctx).await;
+    match pipeline.authenticate(&authenticator.get(&challenge), &mut ctx).await {
+        AuthResult::Reject(reason) => assert!(reason.contains("cross-origin"), "{reason}"),
+        _ => panic!("expected Reject"),
+    }
+}
+
+// ── SQLite store ─────────────────────────────────────────────────────────────
+
+#[tokio::test]
+async fn sqlite_store_keeps_credentials_across_restarts() {
+    let path = std::env::temp_dir().join(format!("webauthn-{}.db", PrincipalId::new().as_uuid()));
+    let path = path.to_str().unwrap().to_string();
+    let user_id = alice();
+    let mut authenticator = {
+        let store = Arc::new(OxPersistenceWebAuthnStore::open(&path).unwrap());
+        let authenticator = register(&WebAuthnAuthDriver::new(config(), store.clone()), &user_id);
+        let stored = store.get(authenticator.credential_id()).unwrap().unwrap();
+        assert!(store.insert(stored).is_err(), "a credential is registered once");
+        authenticator
+    };
+
+    // A restarted node finds the credential and keeps its counter.
+    let store = Arc::new(OxPersistenceWebAuthnStore::open(&path).unwrap());
+    assert_eq!(store.list(&user_id).unwrap().len(), 1);
+    let pipeline = pipeline(Arc::new(WebAuthnAuthDriver::new(config(), store.clone())));
+    let mut ctx = new_ctx();
+    let challenge = begin(&pipeline, &mut ctx).await;
+    let result = pipeline.authenticate(&authenticator.get(&challenge), &mut ctx).await;
+    assert!(matches!(result, AuthResult::Authenticated(_)));
+
+    let id = authenticator.credential_id().to_vec();
+    assert_eq!(store.get(&id).unwrap().unwrap().sign_count, 1);
+    assert!(!store.update_sign_count(&id, 0, 5).unwrap());
+    assert!(store.update_sign_count(&id, 1, 5).unwrap());
+    assert_eq!(store.get(&id).unwrap().unwrap().sign_count, 5);
+
+    store.remove(&id).unwrap();
+    assert!(store.get(&id).unwrap().is_none());
+    assert!(store.update_sign_count(&id, 5, 6).is_err());
+    drop((pipeline, store));
+    let _ = std::fs::remove_file(&path);
+}
@@ -2,8 +2,10 @@ pub(crate) mod ad;
 pub(crate) mod ldap;
 pub(crate) mod local_db;
 pub(crate) mod okta;
+pub(crate) mod policy;
 
 pub use ad::AdAuthzDriver;
 pub use ldap::{LdapAuthzDriver, GroupResolverFn};
 pub use local_db::LocalDbAuthzDriver;
 pub use okta::{OktaAuthzDriver, OktaApiFn, OktaConfig, OktaGrantMapperFn};
+pub use policy::PolicyAuthzDriver;
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::path::Path;
+use async_trait::async_trait;
+use ox_security_core::{
+    AuthzResult,
+    drivers::{AuthzDriver, AuthzRequest},
+    error::SecurityError,
+    principal::Principal,
+    registration::ContextDefinition,
+};
+use crate::policy::{flatten_roles, Effect, Policy, Rule, Strictness, ANY_OPERATION};
+
+/// A role's permissions, inherited ones included, indexed by operation.
+#[derive(Default)]
+struct CompiledRole {
+    by_operation: HashMap<String, Vec<Rule>>,
+}
+
+impl CompiledRole {
+    fn rules_for<'a>(&'a self, operation: &str) -> impl Iterator<Item = &'a Rule> {
+        let exact = self.by_operation.get(operation).into_iter().flatten();
+        let any = self.by_operation.get(ANY_OPERATION).into_iter().flatten();
+        exact.chain(any)
+    }
+}
+
+/// Evaluates a role-based `Policy`. A matching deny permission overrides any
+/// allow; when no permission of the principal's roles matches, the next driver
+/// in the pipeline decides.
+pub struct PolicyAuthzDriver {
+    roles: HashMap<String, CompiledRole>,
+    group_roles: HashMap<String, Vec<String>>,
+    principal_roles: HashMap<String, Vec<String>>,
+}
+
+impl PolicyAuthzDriver {
+    pub fn new(policy: &Policy) -> Result<Self, SecurityError> {
+        let mut roles = HashMap::new();
+        for (name, permissions) in flatten_roles(policy)? {
+            let mut compiled = CompiledRole::default();
+            for permission in permissions {
+                for op in &permission.operations {
+                    compiled.by_operation.entry(op.clone()).or_insert_with(Vec::new)
+                        .push(Rule::compile(&name, permission)?);
+                }
+            }
+            roles.insert(name, compiled);
+        }
+
+        let mut group_roles: HashMap<String, Vec<String>> = HashMap::new();
+        let mut principal_roles: HashMap<String, Vec<String>> = HashMap::new();
+        for binding in &policy.bindings {
+            if !roles.contains_key(&binding.role) {
+                return Err(SecurityError::Config(format!(
+                    "binding references unknown role '{}'",
+                    binding.role
+                )));
+            }
+            for group in &binding.groups {
+                group_roles.entry(group.clone()).or_default().push(binding.role.clone());
+            }
+            for principal in &binding.principals {
+                principal_roles.entry(principal.clone()).or_default().push(binding.role.clone());
+            }
+        }
+
+        Ok(Self { roles, group_roles, principal_roles })
+    }
+
+    /// Load the policy at `path` (YAML or JSON), validate it against the
+    /// registered `contexts` and compile it.
+    pub fn from_file(
+        path: &Path,
+        contexts: &[ContextDefinition],
+        strictness: Strictness,
+    ) -> Result<Self, SecurityError> {
+        let policy = Policy::load(path)?;
+        policy.validate(contexts, strictness)?;
+        Self::new(&policy)
+    }
+
+    fn roles_of<'a>(&'a self, principal: &'a Principal) -> Vec<&'a str> {
+        let mut names: Vec<&str> = Vec::new();
+        let bound = self.principal_roles.get(&principal.stable_id()).into_iter().flatten()
+            .chain(principal.groups.iter()
+                .filter_map(|g| self.group_roles.get(g.as_str()))
+                .flatten());
+        for name in bound {
+            if !names.contains(&name.as_str()) {
+                names.push(name);
+            }
+        }
+        names
+    }
+}
+
+#[async_trait]
+impl AuthzDriver for PolicyAuthzDriver {
+    async fn check(
+        &self,
+        principal: &Principal,
+        path: &str,
+        operation: &str,
+    ) -> AuthzResult {
+        self.check_request(&AuthzRequest::new(principal, path, operation)).await
+    }
+
+    async fn check_request(&self, request: &AuthzRequest<'_>) -> AuthzResult {
+        let mut allowed = false;
+        for name in self.roles_of(request.principal) {
+            let Some(role) = self.roles.get(name) else { continue };
+            for rule in role.rules_for(request.operation) {
+                if !rule.matches(request) {
+                    continue;
+                }
+                match rule.effect {
+                    Effect::Deny => {
+                        return AuthzResult::Deny(format!(
+                            "operation '{}' at '{}' denied by role '{name}'",
+                            request.operation, request.path
+                        ));
+                    }
+                    Effect::Allow => allowed = true,
+                }
+            }
+        }
+        if allowed {
+            AuthzResult::Allow
+        } else {
+            AuthzResult::Continue
+        }
+    }
+}
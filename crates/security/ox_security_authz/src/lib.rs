@@ -1,7 +1,9 @@
 pub(crate) mod drivers;
 pub(crate) mod grant;
 pub(crate) mod pipeline;
+pub(crate) mod policy;
 
-pub use drivers::{AdAuthzDriver, GroupResolverFn, LdapAuthzDriver, LocalDbAuthzDriver, OktaAuthzDriver, OktaApiFn, OktaConfig, OktaGrantMapperFn};
+pub use drivers::{AdAuthzDriver, GroupResolverFn, LdapAuthzDriver, LocalDbAuthzDriver, OktaAuthzDriver, OktaApiFn, OktaConfig, OktaGrantMapperFn, PolicyAuthzDriver};
 pub use grant::PermissionGrant;
 pub use pipeline::AuthzPipeline;
+pub use policy::{Conditions, Effect, Permission, Policy, Role, RoleBinding, Strictness, TimeWindow, ANY_OPERATION};
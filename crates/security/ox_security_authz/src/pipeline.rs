@@ -1,5 +1,5 @@
 use std::sync::Arc;
-use ox_security_core::{AuthzResult, drivers::AuthzDriver, principal::Principal};
+use ox_security_core::{AuthzResult, drivers::{AuthzDriver, AuthzRequest}, principal::Principal};
 
 pub struct AuthzPipeline {
     drivers: Vec<Arc<dyn AuthzDriver>>,
@@ -16,8 +16,12 @@ impl AuthzPipeline {
         path: &str,
         operation: &str,
     ) -> AuthzResult {
+        self.check_request(&AuthzRequest::new(principal, path, operation)).await
+    }
+
+    pub async fn check_request(&self, request: &AuthzRequest<'_>) -> AuthzResult {
         for driver in &self.drivers {
-            match driver.check(principal, path, operation).await {
+            match driver.check_request(request).await {
                 AuthzResult::Continue => continue,
                 result => return result,
             }
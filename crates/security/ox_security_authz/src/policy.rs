@@ -0,0 +1,316 @@
+//! Role-based policy documents with attribute conditions.
+//!
+//! A policy defines roles, each holding allow and deny permissions and
+//! optionally inheriting the permissions of other roles, and binds roles to
+//! groups or principals. `PolicyAuthzDriver` evaluates a loaded policy.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use chrono::{Datelike, NaiveTime, Weekday};
+use ox_security_core::{
+    drivers::AuthzRequest,
+    error::SecurityError,
+    registration::ContextDefinition,
+};
+use serde::Deserialize;
+
+/// Matches every operation in a permission's `operations`.
+pub const ANY_OPERATION: &str = "*";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Policy {
+    #[serde(default)]
+    pub roles: Vec<Role>,
+    #[serde(default)]
+    pub bindings: Vec<RoleBinding>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Role {
+    pub name: String,
+    /// Roles whose permissions this role also holds.
+    #[serde(default)]
+    pub inherits: Vec<String>,
+    #[serde(default)]
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Effect {
+    #[default]
+    Allow,
+    Deny,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Permission {
+    #[serde(default)]
+    pub effect: Effect,
+    /// Operation names, or `*` for every operation.
+    pub operations: Vec<String>,
+    /// Path the permission is granted at; it cascades to every descendant.
+    /// `None` applies to every path.
+    #[serde(default)]
+    pub resource: Option<String>,
+    #[serde(default)]
+    pub conditions: Conditions,
+}
+
+/// Request attributes a permission requires. Every condition that is set
+/// must hold; an empty list places no restriction. A condition on data the
+/// request lacks (an unset principal attribute or source address) fails for
+/// an allow and holds for a deny, so missing data never lifts a deny.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Conditions {
+    #[serde(default)]
+    pub tenants: Vec<String>,
+    /// Addresses or CIDR blocks.
+    #[serde(default)]
+    pub source_ips: Vec<String>,
+    #[serde(default)]
+    pub time: Option<TimeWindow>,
+    /// Principal attribute name to the values it may hold.
+    #[serde(default)]
+    pub attributes: HashMap<String, Vec<String>>,
+}
+
+/// A daily window in UTC. When `end` is before `start` the window runs past
+/// midnight; `days` is checked against the day the request is made.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TimeWindow {
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RoleBinding {
+    pub role: String,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    /// `Principal::stable_id()` values such as `ldap:alice`. `PrincipalId`
+    /// is new for every login, so it cannot be bound.
+    #[serde(default)]
+    pub principals: Vec<String>,
+}
+
+/// How `Policy::validate` treats resources outside the registered context tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strictness {
+    /// Resources must be registered paths or their ancestors, and each
+    /// operation must be registered at or below its resource.
+    Strict,
+    /// Unregistered resources are accepted; operations must still be
+    /// registered somewhere.
+    Permissive,
+}
+
+impl Policy {
+    /// Load a YAML or JSON policy file through `ox_fileproc`.
+    pub fn load(path: &Path) -> Result<Self, SecurityError> {
+        let value = ox_fileproc::process_file(path, 5).map_err(|e| {
+            SecurityError::Config(format!("failed to load policy {}: {e}", path.display()))
+        })?;
+        serde_json::from_value(value).map_err(|e| {
+            SecurityError::Config(format!("invalid policy {}: {e}", path.display()))
+        })
+    }
+
+    /// Check every permission against the registered context tree.
+    pub fn validate(
+        &self,
+        contexts: &[ContextDefinition],
+        strictness: Strictness,
+    ) -> Result<(), SecurityError> {
+        let mut nodes = Vec::new();
+        for def in contexts {
+            collect_nodes(def, "", &mut nodes);
+        }
+        let mut problems = Vec::new();
+        for role in &self.roles {
+            for permission in &role.permissions {
+                let resource = permission.resource.as_deref();
+                let in_scope: Vec<&(String, Vec<&str>)> = nodes.iter()
+                    .filter(|(path, _)| resource.is_none_or(|r| covers(r, path)))
+                    .collect();
+                if let (Strictness::Strict, Some(r), true) = (strictness, resource, in_scope.is_empty()) {
+                    problems.push(format!("role '{}': resource '{r}' is not registered", role.name));
+                    continue;
+                }
+                for op in &permission.operations {
+                    if op == ANY_OPERATION {
+                        continue;
+                    }
+                    let registered_at = |node: &(String, Vec<&str>)| node.1.contains(&op.as_str());
+                    let registered = match strictness {
+                        Strictness::Strict => in_scope.iter().any(|node| registered_at(node)),
+                        Strictness::Permissive => nodes.iter().any(registered_at),
+                    };
+                    if !registered {
+                        problems.push(match (strictness, resource) {
+                            (Strictness::Strict, Some(r)) => format!(
+                                "role '{}': operation '{op}' is not registered at or below '{r}'",
+                                role.name,
+                            ),
+                            _ => format!("role '{}': operation '{op}' is not registered", role.name),
+                        });
+                    }
+                }
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(SecurityError::Config(problems.join("; ")))
+        }
+    }
+}
+
+fn collect_nodes<'a>(def: &'a ContextDefinition, parent: &str, out: &mut Vec<(String, Vec<&'a str>)>) {
+    let path = if parent.is_empty() {
+        def.root.to_string()
+    } else {
+        format!("{parent}.{}", def.root)
+    };
+    for child in def.children {
+        collect_nodes(child, &path, out);
+    }
+    out.push((path, def.operations.iter().map(|op| op.name).collect()));
+}
+
+/// Whether a permission granted at `resource` applies to `path`.
+pub(crate) fn covers(resource: &str, path: &str) -> bool {
+    path.strip_prefix(resource)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+/// A permission with its source addresses parsed, ready to evaluate.
+pub(crate) struct Rule {
+    pub(crate) effect: Effect,
+    resource: Option<String>,
+    tenants: Vec<String>,
+    source_ips: Vec<ipnet::IpNet>,
+    time: Option<TimeWindow>,
+    attributes: HashMap<String, Vec<String>>,
+}
+
+impl Rule {
+    pub(crate) fn compile(role: &str, permission: &Permission) -> Result<Self, SecurityError> {
+        let conditions = &permission.conditions;
+        let source_ips = conditions.source_ips.iter()
+            .map(|s| {
+                s.parse::<ipnet::IpNet>()
+                    .or_else(|_| s.parse::<std::net::IpAddr>().map(ipnet::IpNet::from))
+                    .map_err(|_| SecurityError::Config(format!(
+                        "role '{role}': invalid source address '{s}'"
+                    )))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            effect: permission.effect,
+            resource: permission.resource.clone(),
+            tenants: conditions.tenants.clone(),
+            source_ips,
+            time: conditions.time.clone(),
+            attributes: conditions.attributes.clone(),
+        })
+    }
+
+    pub(crate) fn matches(&self, request: &AuthzRequest<'_>) -> bool {
+        if let Some(resource) = &self.resource {
+            if !covers(resource, request.path) {
+                return false;
+            }
+        }
+        if !self.tenants.is_empty()
+            && !self.tenants.iter().any(|t| t == request.tenant_id.as_str())
+        {
+            return false;
+        }
+        // Conditions on data the request lacks hold only for a deny.
+        let unknown = self.effect == Effect::Deny;
+        if !self.source_ips.is_empty() {
+            let matched = match request.source_ip {
+                Some(ip) => self.source_ips.iter().any(|net| net.contains(&ip)),
+                None => unknown,
+            };
+            if !matched {
+                return false;
+            }
+        }
+        if let Some(window) = &self.time {
+            if !window.contains(request) {
+                return false;
+            }
+        }
+        self.attributes.iter().all(|(name, values)| {
+            request.principal.attributes.get(name).map_or(unknown, |v| values.contains(v))
+        })
+    }
+}
+
+impl TimeWindow {
+    fn contains(&self, request: &AuthzRequest<'_>) -> bool {
+        if !self.days.is_empty() && !self.days.contains(&request.time.weekday()) {
+            return false;
+        }
+        let t = request.time.time();
+        if self.start <= self.end {
+            self.start <= t && t < self.end
+        } else {
+            t >= self.start || t < self.end
+        }
+    }
+}
+
+/// Every role's permissions with inherited permissions included, keyed by role.
+pub(crate) fn flatten_roles(
+    policy: &Policy,
+) -> Result<HashMap<String, Vec<&Permission>>, SecurityError> {
+    let mut by_name: HashMap<&str, &Role> = HashMap::new();
+    for role in &policy.roles {
+        if by_name.insert(&role.name, role).is_some() {
+            return Err(SecurityError::Config(format!("role '{}' is defined twice", role.name)));
+        }
+    }
+    let mut flattened = HashMap::new();
+    for role in &policy.roles {
+        let mut permissions = Vec::new();
+        let mut seen = HashSet::new();
+        collect_permissions(&by_name, role, &mut Vec::new(), &mut seen, &mut permissions)?;
+        flattened.insert(role.name.clone(), permissions);
+    }
+    Ok(flattened)
+}
+
+fn collect_permissions<'a>(
+    by_name: &HashMap<&str, &'a Role>,
+    role: &'a Role,
+    stack: &mut Vec<&'a str>,
+    seen: &mut HashSet<&'a str>,
+    out: &mut Vec<&'a Permission>,
+) -> Result<(), SecurityError> {
+    if stack.contains(&role.name.as_str()) {
+        stack.push(&role.name);
+        return Err(SecurityError::Config(format!(
+            "role inheritance cycle: {}",
+            stack.join(" -> ")
+        )));
+    }
+    if !seen.insert(&role.name) {
+        return Ok(());
+    }
+    stack.push(&role.name);
+    out.extend(role.permissions.iter());
+    for parent in &role.inherits {
+        let parent_role = by_name.get(parent.as_str()).ok_or_else(|| {
+            SecurityError::Config(format!("role '{}' inherits unknown role '{parent}'", role.name))
+        })?;
+        collect_permissions(by_name, parent_role, stack, seen, out)?;
+    }
+    stack.pop();
+    Ok(())
+}
@@ -17,6 +17,7 @@ fn test_principal() -> Principal {
         groups: vec![],
         tenant_id: TenantId::from_str("test").unwrap(),
         session_id: None,
+        attributes: Default::default(),
     }
 }
 
@@ -113,6 +114,7 @@ fn principal_with_groups(groups: Vec<&str>) -> Principal {
         groups: groups.into_iter().map(|g| GroupId::new(g)).collect(),
         tenant_id: TenantId::from_str("test").unwrap(),
         session_id: None,
+        attributes: Default::default(),
     }
 }
 
@@ -440,6 +442,7 @@ fn okta_principal() -> Principal {
         groups: vec![],
         tenant_id: TenantId::from_str("test").unwrap(),
         session_id: None,
+        attributes: Default::default(),
     }
 }
 
@@ -451,6 +454,7 @@ fn local_principal_okta() -> Principal {
         groups: vec![],
         tenant_id: TenantId::from_str("test").unwrap(),
         session_id: None,
+        attributes: Default::default(),
     }
 }
 
@@ -521,3 +525,292 @@ async fn okta_authz_continues_on_api_error() {
     let result = driver.check(&okta_principal(), "any/resource", "read").await;
     assert!(matches!(result, AuthzResult::Continue));
 }
+
+// ─── PolicyAuthzDriver tests ────────────────────────────────────────────────
+
+use chrono::{TimeZone, Utc};
+use ox_security_authz::{Policy, PolicyAuthzDriver, Strictness};
+use ox_security_core::{
+    drivers::AuthzRequest,
+    registration::ContextDefinition,
+    OP_CHANGE, OP_DELETE, OP_READ, OP_WRITE,
+};
+
+const POLICY_YAML: &str = r#"
+roles:
+  - name: viewer
+    permissions:
+      - operations: [read]
+        resource: com.justlikeef.data
+  - name: editor
+    inherits: [viewer]
+    permissions:
+      - operations: [write, change]
+        resource: com.justlikeef.data
+      - effect: deny
+        operations: ["*"]
+        resource: com.justlikeef.data.payroll
+  - name: ops
+    permissions:
+      - operations: [delete]
+        resource: com.justlikeef.data
+        conditions:
+          tenants: [acme]
+          source_ips: [10.0.0.0/8, 192.168.1.7]
+          time:
+            days: [Mon, Tue, Wed, Thu, Fri]
+            start: "08:00"
+            end: "18:00"
+          attributes:
+            department: [it, sre]
+bindings:
+  - role: editor
+    groups: [editors]
+  - role: ops
+    groups: [ops]
+"#;
+
+fn load_policy(yaml: &str) -> Policy {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("policy.yaml");
+    std::fs::write(&path, yaml).unwrap();
+    Policy::load(&path).unwrap()
+}
+
+fn policy_driver() -> PolicyAuthzDriver {
+    PolicyAuthzDriver::new(&load_policy(POLICY_YAML)).unwrap()
+}
+
+fn ops_principal() -> Principal {
+    let mut principal = principal_with_groups(vec!["ops"]);
+    principal.tenant_id = TenantId::from_str("acme").unwrap();
+    principal.attributes.insert("department".to_string(), "sre".to_string());
+    principal
+}
+
+fn ops_request(principal: &Principal) -> AuthzRequest<'_> {
+    // Wednesday 2026-10-14, 09:30 UTC.
+    AuthzRequest::new(principal, "com.justlikeef.data.orders", "delete")
+        .with_source_ip("10.1.2.3".parse().unwrap())
+        .at(Utc.with_ymd_and_hms(2026, 10, 14, 9, 30, 0).unwrap())
+}
+
+#[tokio::test]
+async fn policy_allows_inherited_permission_on_descendant() {
+    let driver = policy_driver();
+    let editor = principal_with_groups(vec!["editors"]);
+    assert_eq!(driver.check(&editor, "com.justlikeef.data.orders.total", "read").await, AuthzResult::Allow);
+    assert_eq!(driver.check(&editor, "com.justlikeef.data", "write").await, AuthzResult::Allow);
+    assert_eq!(driver.check(&editor, "com.justlikeef.database", "read").await, AuthzResult::Continue);
+}
+
+#[tokio::test]
+async fn policy_deny_overrides_allow() {
+    let driver = policy_driver();
+    let editor = principal_with_groups(vec!["editors"]);
+    assert!(matches!(
+        driver.check(&editor, "com.justlikeef.data.payroll.salary", "read").await,
+        AuthzResult::Deny(_)
+    ));
+}
+
+#[tokio::test]
+async fn policy_continues_for_unbound_principal() {
+    let driver = policy_driver();
+    assert_eq!(driver.check(&test_principal(), "com.justlikeef.data", "read").await, AuthzResult::Continue);
+}
+
+#[tokio::test]
+async fn policy_binds_roles_to_principals() {
+    let yaml = "roles:\n  - name: viewer\n    permissions:\n      - operations: [read]\nbindings:\n  - role: viewer\n    principals: [\"local:Test User\"]\n";
+    let driver = PolicyAuthzDriver::new(&load_policy(yaml)).unwrap();
+    let first_login = test_principal();
+    assert_eq!(driver.check(&first_login, "anything", "read").await, AuthzResult::Allow);
+
+    // The next login of the same user has a new PrincipalId and still matches.
+    let second_login = test_principal();
+    assert_ne!(second_login.id, first_login.id);
+    assert_eq!(driver.check(&second_login, "anything", "read").await, AuthzResult::Allow);
+
+    let other = Principal { display_name: "Other User".to_string(), ..test_principal() };
+    assert_eq!(driver.check(&other, "anything", "read").await, AuthzResult::Continue);
+    let other_source = Principal { source: AuthSource::Ldap, ..test_principal() };
+    assert_eq!(driver.check(&other_source, "anything", "read").await, AuthzResult::Continue);
+}
+
+#[tokio::test]
+async fn policy_conditions_all_hold() {
+    let driver = policy_driver();
+    let principal = ops_principal();
+    assert_eq!(driver.check_request(&ops_request(&principal)).await, AuthzResult::Allow);
+    let single_address = ops_request(&principal).with_source_ip("192.168.1.7".parse().unwrap());
+    assert_eq!(driver.check_request(&single_address).await, AuthzResult::Allow);
+}
+
+#[tokio::test]
+async fn policy_conditions_reject_each_attribute() {
+    let driver = policy_driver();
+    let principal = ops_principal();
+
+    let other_tenant = TenantId::from_str("globex").unwrap();
+    let request = ops_request(&principal).with_tenant(&other_tenant);
+    assert_eq!(driver.check_request(&request).await, AuthzResult::Continue);
+
+    let request = ops_request(&principal).with_source_ip("172.16.0.1".parse().unwrap());
+    assert_eq!(driver.check_request(&request).await, AuthzResult::Continue);
+
+    let request = AuthzRequest { source_ip: None, ..ops_request(&principal) };
+    assert_eq!(driver.check_request(&request).await, AuthzResult::Continue);
+
+    let request = ops_request(&principal).at(Utc.with_ymd_and_hms(2026, 10, 14, 18, 0, 0).unwrap());
+    assert_eq!(driver.check_request(&request).await, AuthzResult::Continue);
+
+    // Saturday.
+    let request = ops_request(&principal).at(Utc.with_ymd_and_hms(2026, 10, 17, 9, 30, 0).unwrap());
+    assert_eq!(driver.check_request(&request).await, AuthzResult::Continue);
+
+    let mut other_department = principal.clone();
+    other_department.attributes.insert("department".to_string(), "finance".to_string());
+    assert_eq!(driver.check_request(&ops_request(&other_department)).await, AuthzResult::Continue);
+}
+
+#[tokio::test]
+async fn policy_time_window_wraps_midnight() {
+    let yaml = r#"
+roles:
+  - name: night
+    permissions:
+      - operations: [read]
+        conditions:
+          time: { start: "22:00", end: "06:00" }
+bindings:
+  - role: night
+    groups: [night]
+"#;
+    let driver = PolicyAuthzDriver::new(&load_policy(yaml)).unwrap();
+    let principal = principal_with_groups(vec!["night"]);
+    let at = |h| AuthzRequest::new(&principal, "x", "read").at(Utc.with_ymd_and_hms(2026, 10, 14, h, 0, 0).unwrap());
+    assert_eq!(driver.check_request(&at(23)).await, AuthzResult::Allow);
+    assert_eq!(driver.check_request(&at(5)).await, AuthzResult::Allow);
+    assert_eq!(driver.check_request(&at(12)).await, AuthzResult::Continue);
+}
+
+#[tokio::test]
+async fn pipeline_stops_at_policy_deny() {
+    // A policy deny stops the pipeline even when a later driver would allow.
+    let pipeline = AuthzPipeline::new(vec![Arc::new(policy_driver()), Arc::new(AlwaysAllowDriver)]);
+    let editor = principal_with_groups(vec!["editors"]);
+    assert!(matches!(
+        pipeline.check(&editor, "com.justlikeef.data.payroll", "write").await,
+        AuthzResult::Deny(_)
+    ));
+    assert_eq!(pipeline.check(&editor, "elsewhere", "write").await, AuthzResult::Allow);
+}
+
+#[tokio::test]
+async fn policy_deny_holds_when_request_lacks_condition_data() {
+    let yaml = r#"
+roles:
+  - name: editor
+    permissions:
+      - operations: [read]
+        resource: com.justlikeef.data
+      - effect: deny
+        operations: [read]
+        resource: com.justlikeef.data.payroll
+        conditions:
+          attributes: { employment: [contractor] }
+      - effect: deny
+        operations: [read]
+        resource: com.justlikeef.data.orders
+        conditions:
+          source_ips: [203.0.113.0/24]
+bindings:
+  - role: editor
+    groups: [editors]
+"#;
+    let driver = PolicyAuthzDriver::new(&load_policy(yaml)).unwrap();
+    let mut principal = principal_with_groups(vec!["editors"]);
+    assert!(matches!(
+        driver.check(&principal, "com.justlikeef.data.payroll", "read").await,
+        AuthzResult::Deny(_)
+    ));
+    principal.attributes.insert("employment".to_string(), "staff".to_string());
+    assert_eq!(driver.check(&principal, "com.justlikeef.data.payroll", "read").await, AuthzResult::Allow);
+
+    let orders = AuthzRequest::new(&principal, "com.justlikeef.data.orders", "read");
+    assert!(matches!(driver.check_request(&orders).await, AuthzResult::Deny(_)));
+    let internal = orders.with_source_ip("10.0.0.1".parse().unwrap());
+    assert_eq!(driver.check_request(&internal).await, AuthzResult::Allow);
+}
+
+#[test]
+fn policy_rejects_inheritance_cycle() {
+    let yaml = "roles:\n  - name: a\n    inherits: [b]\n  - name: b\n    inherits: [a]\n";
+    let err = PolicyAuthzDriver::new(&load_policy(yaml)).err().unwrap();
+    assert!(err.to_string().contains("cycle"), "{err}");
+}
+
+#[test]
+fn policy_rejects_unknown_roles() {
+    let yaml = "roles:\n  - name: a\n    inherits: [missing]\n";
+    assert!(PolicyAuthzDriver::new(&load_policy(yaml)).is_err());
+    let yaml = "roles: []\nbindings:\n  - role: missing\n    groups: [g]\n";
+    assert!(PolicyAuthzDriver::new(&load_policy(yaml)).is_err());
+}
+
+#[test]
+fn policy_rejects_invalid_source_address() {
+    let yaml = "roles:\n  - name: a\n    permissions:\n      - operations: [read]\n        conditions:\n          source_ips: [not-an-ip]\n";
+    assert!(PolicyAuthzDriver::new(&load_policy(yaml)).is_err());
+}
+
+const DATA_CONTEXT: ContextDefinition = ContextDefinition {
+    root: "com.justlikeef.data",
+    operations: &[],
+    children: &[
+        ContextDefinition { root: "orders", operations: &[OP_READ, OP_WRITE, OP_CHANGE, OP_DELETE], children: &[] },
+        ContextDefinition { root: "payroll", operations: &[OP_READ], children: &[] },
+    ],
+};
+
+#[test]
+fn policy_validates_against_registered_contexts() {
+    let policy = load_policy(POLICY_YAML);
+    policy.validate(&[DATA_CONTEXT], Strictness::Strict).unwrap();
+    policy.validate(&[DATA_CONTEXT], Strictness::Permissive).unwrap();
+}
+
+#[test]
+fn policy_from_file_validates_before_compiling() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("policy.yaml");
+    std::fs::write(&path, POLICY_YAML).unwrap();
+    PolicyAuthzDriver::from_file(&path, &[DATA_CONTEXT], Strictness::Strict).unwrap();
+
+    std::fs::write(&path, "roles:\n  - name: a\n    permissions:\n      - operations: [ddl]\n").unwrap();
+    let err = PolicyAuthzDriver::from_file(&path, &[DATA_CONTEXT], Strictness::Permissive).err().unwrap();
+    assert!(err.to_string().contains("'ddl'"), "{err}");
+}
+
+#[test]
+fn policy_validation_rejects_unregistered_operation() {
+    let yaml = "roles:\n  - name: a\n    permissions:\n      - operations: [read, ddl]\n";
+    let err = load_policy(yaml).validate(&[DATA_CONTEXT], Strictness::Permissive).unwrap_err();
+    assert!(err.to_string().contains("'ddl'"), "{err}");
+}
+
+#[test]
+fn policy_validation_strictness_for_resources() {
+    // write is registered, but not under payroll.
+    let yaml = "roles:\n  - name: a\n    permissions:\n      - operations: [write]\n        resource: com.justlikeef.data.payroll\n";
+    let policy = load_policy(yaml);
+    policy.validate(&[DATA_CONTEXT], Strictness::Permissive).unwrap();
+    assert!(policy.validate(&[DATA_CONTEXT], Strictness::Strict).is_err());
+
+    let yaml = "roles:\n  - name: a\n    permissions:\n      - operations: [read]\n        resource: com.justlikeef.billing\n";
+    let policy = load_policy(yaml);
+    policy.validate(&[DATA_CONTEXT], Strictness::Permissive).unwrap();
+    let err = policy.validate(&[DATA_CONTEXT], Strictness::Strict).unwrap_err();
+    assert!(err.to_string().contains("not registered"), "{err}");
+}
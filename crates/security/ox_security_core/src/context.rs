@@ -1,6 +1,6 @@
 use std::net::IpAddr;
 use std::sync::Arc;
-use crate::drivers::AuthzDriver;
+use crate::drivers::{AuthzDriver, AuthzRequest};
 use crate::error::AuthzError;
 use crate::principal::{PartialPrincipal, Principal};
 use crate::types::TenantId;
@@ -41,7 +41,10 @@ impl SecurityContext {
         } else {
             format!("{}.{}", self.call_context, object_fragment)
         };
-        match driver.check(principal, &path, operation).await {
+        let request = AuthzRequest::new(principal, &path, operation)
+            .with_tenant(&self.tenant_id)
+            .with_source_ip(self.source_ip);
+        match driver.check_request(&request).await {
             crate::drivers::AuthzResult::Allow => Ok(()),
             crate::drivers::AuthzResult::Deny(_reason) => Err(AuthzError::Denied {
                 path,
@@ -30,10 +30,26 @@ pub enum Credentials {
     KerberosTicket {
         ticket: Vec<u8>,
     },
+    /// Asks the WebAuthn driver for an assertion challenge after a first factor.
+    WebAuthnBegin {
+        session_token: SessionToken,
+    },
+    /// A WebAuthn assertion answering `MfaChallenge::WebAuthn`.
+    WebAuthnAssertion {
+        session_token: SessionToken,
+        credential_id: Vec<u8>,
+        client_data_json: Vec<u8>,
+        authenticator_data: Vec<u8>,
+        signature: Vec<u8>,
+        user_handle: Option<Vec<u8>>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum MfaChallenge {
     PushSent { session_token: SessionToken },
     CodeRequired { session_token: SessionToken },
+    /// `options` are the `PublicKeyCredentialRequestOptions` for
+    /// `navigator.credentials.get()`, with binary fields base64url-encoded.
+    WebAuthn { session_token: SessionToken, options: serde_json::Value },
 }
@@ -1,8 +1,11 @@
+use std::net::IpAddr;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use crate::accounting::AccountingEvent;
 use crate::context::AuthPipelineContext;
 use crate::credentials::{Credentials, MfaChallenge};
 use crate::principal::Principal;
+use crate::types::TenantId;
 
 pub enum AuthResult {
     Authenticated(Principal),
@@ -20,6 +23,48 @@ pub enum AuthzResult {
     Continue,
 }
 
+/// An authorization check together with the request attributes that
+/// attribute-based drivers evaluate.
+#[derive(Debug, Clone)]
+pub struct AuthzRequest<'a> {
+    pub principal: &'a Principal,
+    pub path: &'a str,
+    pub operation: &'a str,
+    pub tenant_id: &'a TenantId,
+    /// `None` when the caller has no client address, e.g. background jobs.
+    pub source_ip: Option<IpAddr>,
+    pub time: DateTime<Utc>,
+}
+
+impl<'a> AuthzRequest<'a> {
+    /// A check made now, in the principal's tenant, with no source address.
+    pub fn new(principal: &'a Principal, path: &'a str, operation: &'a str) -> Self {
+        Self {
+            principal,
+            path,
+            operation,
+            tenant_id: &principal.tenant_id,
+            source_ip: None,
+            time: Utc::now(),
+        }
+    }
+
+    pub fn with_tenant(mut self, tenant_id: &'a TenantId) -> Self {
+        self.tenant_id = tenant_id;
+        self
+    }
+
+    pub fn with_source_ip(mut self, source_ip: IpAddr) -> Self {
+        self.source_ip = Some(source_ip);
+        self
+    }
+
+    pub fn at(mut self, time: DateTime<Utc>) -> Self {
+        self.time = time;
+        self
+    }
+}
+
 #[async_trait]
 pub trait AuthDriver: Send + Sync {
     async fn authenticate(
@@ -37,6 +82,12 @@ pub trait AuthzDriver: Send + Sync {
         path: &str,
         operation: &str,
     ) -> AuthzResult;
+
+    /// Evaluate a check with its request attributes. Drivers that only look at
+    /// the principal keep the default, which forwards to `check`.
+    async fn check_request(&self, request: &AuthzRequest<'_>) -> AuthzResult {
+        self.check(request.principal, request.path, request.operation).await
+    }
 }
 
 #[async_trait]
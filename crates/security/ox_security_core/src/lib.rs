@@ -11,11 +11,11 @@ pub mod types;
 pub use accounting::{AccountingEvent, AuthOutcome, AuthzOutcome};
 pub use context::{AuthPipelineContext, SecurityContext};
 pub use credentials::{Credentials, MfaChallenge};
-pub use drivers::{AccountingDriver, AuthDriver, AuthResult, AuthzDriver, AuthzResult};
+pub use drivers::{AccountingDriver, AuthDriver, AuthResult, AuthzDriver, AuthzRequest, AuthzResult};
 pub use error::{AuthzError, SecurityError};
 pub use operations::{
     OperationDef, OP_CHANGE, OP_CREATE, OP_DDL, OP_DELETE, OP_EXECUTE, OP_LIST, OP_READ, OP_WRITE,
 };
-pub use principal::{PartialPrincipal, Principal};
+pub use principal::{PartialPrincipal, Principal, USER_ID_ATTRIBUTE};
 pub use registration::{ContextDefinition, ContextRegistrar, SecurityRegistration};
 pub use types::{AuthSource, GroupId, PrincipalId, SessionId, SessionToken, TenantId};
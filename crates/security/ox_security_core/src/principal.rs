@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 use crate::types::{AuthSource, GroupId, PrincipalId, SessionId, TenantId};
 
@@ -9,6 +10,28 @@ pub struct Principal {
     pub groups: Vec<GroupId>,
     pub tenant_id: TenantId,
     pub session_id: Option<SessionId>,
+    /// Directory or upstream identity attributes (e.g. `email`), keyed by claim name.
+    #[serde(default)]
+    pub attributes: HashMap<String, String>,
+}
+
+/// Attribute holding the user's immutable identifier in its source, e.g. the
+/// upstream `sub` of an OIDC login.
+pub const USER_ID_ATTRIBUTE: &str = "user_id";
+
+impl Principal {
+    /// Identifies the user across logins, unlike `id`, which is new for every
+    /// authentication: `{source}:{user}`, where `user` is the `user_id` attribute
+    /// if the driver set one (for sources whose display name can change) and the
+    /// display name otherwise.
+    pub fn stable_id(&self) -> String {
+        stable_id(&self.source, &self.display_name, &self.attributes)
+    }
+}
+
+fn stable_id(source: &AuthSource, display_name: &str, attributes: &HashMap<String, String>) -> String {
+    let user = attributes.get(USER_ID_ATTRIBUTE).map_or(display_name, String::as_str);
+    format!("{}:{}", source, user)
 }
 
 /// Produced by credential drivers before MFA is complete.
@@ -20,9 +43,15 @@ pub struct PartialPrincipal {
     pub source: AuthSource,
     pub groups: Vec<GroupId>,
     pub tenant_id: TenantId,
+    pub attributes: HashMap<String, String>,
 }
 
 impl PartialPrincipal {
+    /// The `Principal::stable_id` of the principal this one promotes to.
+    pub fn stable_id(&self) -> String {
+        stable_id(&self.source, &self.display_name, &self.attributes)
+    }
+
     pub fn into_principal(self, session_id: Option<SessionId>) -> Principal {
         Principal {
             id: self.id,
@@ -31,6 +60,7 @@ impl PartialPrincipal {
             groups: self.groups,
             tenant_id: self.tenant_id,
             session_id,
+            attributes: self.attributes,
         }
     }
 }
@@ -84,11 +84,30 @@ fn principal_constructed_with_groups() {
         groups: vec![GroupId::new("it"), GroupId::new("dataadmins")],
         tenant_id: TenantId::from_str("acme").unwrap(),
         session_id: None,
+        attributes: Default::default(),
     };
     assert_eq!(p.groups.len(), 2);
     assert_eq!(p.display_name, "John Smith");
 }
 
+#[test]
+fn principal_stable_id_survives_relogin() {
+    let login = |attributes: Vec<(&str, &str)>| Principal {
+        id: PrincipalId::new(),
+        display_name: "alice".to_string(),
+        source: AuthSource::Ldap,
+        groups: vec![],
+        tenant_id: TenantId::from_str("acme").unwrap(),
+        session_id: None,
+        attributes: attributes.into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+    };
+    let (first, second) = (login(vec![]), login(vec![]));
+    assert_ne!(first.id, second.id);
+    assert_eq!(first.stable_id(), "ldap:alice");
+    assert_eq!(first.stable_id(), second.stable_id());
+    assert_eq!(login(vec![("user_id", "u-42")]).stable_id(), "ldap:u-42");
+}
+
 #[test]
 fn partial_principal_promotes_to_principal() {
     let partial = PartialPrincipal {
@@ -97,6 +116,7 @@ fn partial_principal_promotes_to_principal() {
         source: AuthSource::Local,
         groups: vec![GroupId::new("finance")],
         tenant_id: TenantId::from_str("acme").unwrap(),
+        attributes: Default::default(),
     };
     let principal: Principal = partial.into_principal(None);
     assert_eq!(principal.display_name, "Jane");
@@ -262,6 +282,7 @@ async fn authz_driver_trait_object() {
         groups: vec![],
         tenant_id: TenantId::from_str("acme").unwrap(),
         session_id: None,
+        attributes: Default::default(),
     };
     let result = driver.check(&principal, "com.justlikeef.data.obj1", "read").await;
     assert!(matches!(result, AuthzResult::Allow));
@@ -17,13 +17,52 @@ pub struct SamlSpDef {
     pub acs_url: String,
     pub slo_url: Option<String>,
     pub name_id_format: Option<String>,
+    /// SP encryption certificate (PEM). When set, assertions are sent encrypted.
+    #[serde(default)]
+    pub encryption_cert_pem: Option<String>,
+    /// SP signing certificate (PEM). LogoutRequests must be signed by its key.
+    #[serde(default)]
+    pub signing_cert_pem: Option<String>,
+}
+
+/// Maps a principal field or attribute to an OIDC claim, released when the
+/// client was granted `scope`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ClaimMapping {
+    pub claim: String,
+    /// `display_name`, `groups`, `tenant_id`, or `attributes.<name>`.
+    pub source: String,
+    pub scope: String,
+}
+
+/// Where tokens, authorization codes and signing keys are kept.
+#[derive(Debug, Deserialize, Clone)]
+pub struct TokenStoreConfig {
+    /// `sqlite` or `memory`.
+    pub driver: String,
+    pub path: Option<String>,
+    /// Environment variable holding the passphrase that encrypts signing keys
+    /// at rest. Required by the `sqlite` driver.
+    pub passphrase_env: Option<String>,
+}
+
+impl TokenStoreConfig {
+    pub fn db_path(&self) -> &str {
+        self.path.as_deref().unwrap_or("/var/lib/ox_webservice/idp.db")
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct IdpConfig {
     pub tenant_id: String,
     pub issuer: String,
+    /// Initial JWT signing key, and the SAML signing key. A key not yet in the
+    /// store becomes the active JWT key at startup.
     pub rsa_private_key_pem: String,
+    /// Certificate for `rsa_private_key_pem`, published in SAML metadata and
+    /// `ds:KeyInfo`. Required when `saml_sps` is not empty.
+    #[serde(default)]
+    pub saml_signing_cert_pem: Option<String>,
     #[serde(default = "default_token_ttl")]
     pub access_token_ttl_secs: u64,
     #[serde(default = "default_refresh_ttl")]
@@ -32,10 +71,45 @@ pub struct IdpConfig {
     pub clients: Vec<OAuthClientDef>,
     #[serde(default)]
     pub saml_sps: Vec<SamlSpDef>,
+    #[serde(default = "default_id_token_ttl")]
+    pub id_token_ttl_secs: u64,
+    #[serde(default = "default_claim_mappings")]
+    pub claim_mappings: Vec<ClaimMapping>,
+    /// Tokens are kept in memory when omitted.
+    #[serde(default)]
+    pub store: Option<TokenStoreConfig>,
+}
+
+impl IdpConfig {
+    /// How long a rotated-out signing key stays in the JWKS: until every token
+    /// it signed has expired.
+    pub fn key_retention_secs(&self) -> u64 {
+        self.access_token_ttl_secs.max(self.id_token_ttl_secs)
+    }
 }
 
 fn default_token_ttl() -> u64 { 3600 }
 fn default_refresh_ttl() -> u64 { 86400 }
+fn default_id_token_ttl() -> u64 { 3600 }
+
+fn default_claim_mappings() -> Vec<ClaimMapping> {
+    [
+        ("name", "display_name", "profile"),
+        ("preferred_username", "attributes.preferred_username", "profile"),
+        ("given_name", "attributes.given_name", "profile"),
+        ("family_name", "attributes.family_name", "profile"),
+        ("email", "attributes.email", "email"),
+        ("email_verified", "attributes.email_verified", "email"),
+        ("groups", "groups", "groups"),
+    ]
+    .into_iter()
+    .map(|(claim, source, scope)| ClaimMapping {
+        claim: claim.to_string(),
+        source: source.to_string(),
+        scope: scope.to_string(),
+    })
+    .collect()
+}
 
 #[cfg(test)]
 mod tests {
@@ -46,5 +120,8 @@ mod tests {
         let cfg: IdpConfig = serde_json::from_str(json).unwrap();
         assert_eq!(cfg.tenant_id, "t1");
         assert_eq!(cfg.access_token_ttl_secs, 3600);
+        assert_eq!(cfg.id_token_ttl_secs, 3600);
+        assert!(cfg.claim_mappings.iter().any(|m| m.claim == "email" && m.scope == "email"));
+        assert!(cfg.store.is_none());
     }
 }
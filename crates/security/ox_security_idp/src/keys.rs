@@ -0,0 +1,190 @@
+//! JWT signing keys. The key set lives in the `TokenStore`, so every node
+//! sharing a store signs with the same active key and publishes the same JWKS.
+
+use aes_gcm::aead::OsRng;
+use base64ct::{Base64UrlUnpadded, Encoding};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use rsa::pkcs8::{EncodePrivateKey, LineEnding};
+use rsa::traits::PublicKeyParts;
+use rsa::RsaPrivateKey;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::oauth2::read_rsa_key;
+use crate::store::{SigningKeyRecord, SigningKeyStatus, TokenStore};
+
+/// A private key ready to sign JWTs, which carry its `kid`.
+pub struct SigningKey {
+    pub kid: String,
+    key: EncodingKey,
+}
+
+impl SigningKey {
+    pub fn from_pem(pem: &str) -> Result<Self, String> {
+        Ok(Self {
+            kid: jwk_thumbprint(&read_rsa_key(pem)?),
+            key: EncodingKey::from_rsa_pem(pem.as_bytes()).map_err(|e| e.to_string())?,
+        })
+    }
+
+    pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String, String> {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(self.kid.clone());
+        encode(&header, claims, &self.key).map_err(|e| e.to_string())
+    }
+}
+
+fn public_components(key: &RsaPrivateKey) -> (String, String) {
+    (
+        Base64UrlUnpadded::encode_string(&key.n().to_bytes_be()),
+        Base64UrlUnpadded::encode_string(&key.e().to_bytes_be()),
+    )
+}
+
+/// RFC 7638 JWK thumbprint: SHA-256 over the required members in lexical order.
+pub fn jwk_thumbprint(key: &RsaPrivateKey) -> String {
+    let (n, e) = public_components(key);
+    thumbprint(&n, &e)
+}
+
+fn thumbprint(n: &str, e: &str) -> String {
+    let canonical = format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, e, n);
+    Base64UrlUnpadded::encode_string(&Sha256::digest(canonical.as_bytes()))
+}
+
+/// A fresh 2048-bit RSA key as PKCS#8 PEM.
+pub fn generate_key_pem() -> Result<String, String> {
+    let key = RsaPrivateKey::new(&mut OsRng, 2048).map_err(|e| e.to_string())?;
+    key.to_pkcs8_pem(LineEnding::LF).map(|pem| pem.to_string()).map_err(|e| e.to_string())
+}
+
+/// The key new tokens are signed with.
+pub fn active_signing_key(store: &dyn TokenStore) -> Result<SigningKey, String> {
+    let keys = store.list_signing_keys()?;
+    let active = keys.iter().rev()
+        .find(|k| k.status == SigningKeyStatus::Active)
+        .ok_or("no active signing key")?;
+    SigningKey::from_pem(&active.private_key_pem)
+}
+
+/// Make `pem` the active key and return its `kid`. The key it replaces is
+/// published for `retention_secs` more; keys rotated out longer ago than that
+/// are retired.
+pub fn rotate_signing_key(
+    store: &dyn TokenStore,
+    pem: &str,
+    retention_secs: u64,
+    now: u64,
+) -> Result<String, String> {
+    let kid = jwk_thumbprint(&read_rsa_key(pem)?);
+    store.activate_signing_key(&SigningKeyRecord {
+        kid: kid.clone(),
+        private_key_pem: pem.to_string(),
+        status: SigningKeyStatus::Active,
+        created_at: now,
+        rotated_at: None,
+    })?;
+    store.retire_signing_keys(now.saturating_sub(retention_secs))?;
+    Ok(kid)
+}
+
+/// Activate the configured key unless the store already knows it, so a new
+/// `rsa_private_key_pem` rotates the key set at startup while a key rotated out
+/// through the admin API stays rotated out.
+pub fn ensure_signing_key(
+    store: &dyn TokenStore,
+    pem: &str,
+    retention_secs: u64,
+    now: u64,
+) -> Result<(), String> {
+    let kid = jwk_thumbprint(&read_rsa_key(pem)?);
+    if store.list_signing_keys()?.iter().any(|k| k.kid == kid) {
+        return Ok(());
+    }
+    rotate_signing_key(store, pem, retention_secs, now).map(|_| ())
+}
+
+/// JWK Set of the active key and of retiring keys still within `retention_secs`.
+pub fn build_jwks(keys: &[SigningKeyRecord], retention_secs: u64, now: u64) -> Result<String, String> {
+    let mut jwks = Vec::new();
+    for record in keys {
+        let published = match record.status {
+            SigningKeyStatus::Active => true,
+            SigningKeyStatus::Retiring => record.rotated_at
+                .is_some_and(|t| t.saturating_add(retention_secs) >= now),
+            SigningKeyStatus::Retired => false,
+        };
+        if !published {
+            continue;
+        }
+        let (n, e) = public_components(&read_rsa_key(&record.private_key_pem)?);
+        jwks.push(serde_json::json!({
+            "kty": "RSA",
+            "use": "sig",
+            "alg": "RS256",
+            "kid": record.kid,
+            "n": n,
+            "e": e,
+        }));
+    }
+    Ok(serde_json::json!({ "keys": jwks }).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::InMemoryTokenStore;
+    use serde_json::Value;
+
+    fn test_rsa_pem() -> String {
+        include_str!("../tests/test_rsa_private.pem").to_string()
+    }
+
+    fn published_kids(store: &dyn TokenStore, now: u64) -> Vec<String> {
+        let jwks: Value = serde_json::from_str(
+            &build_jwks(&store.list_signing_keys().unwrap(), 3600, now).unwrap()).unwrap();
+        jwks["keys"].as_array().unwrap().iter()
+            .map(|k| k["kid"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_thumbprint_matches_rfc7638_example() {
+        // RFC 7638 section 3.1.
+        let n = "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw";
+        assert_eq!(thumbprint(n, "AQAB"), "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs");
+    }
+
+    #[test]
+    fn test_signed_token_carries_kid() {
+        let key = SigningKey::from_pem(&test_rsa_pem()).unwrap();
+        let token = key.sign(&serde_json::json!({ "sub": "alice" })).unwrap();
+        let header = jsonwebtoken::decode_header(&token).unwrap();
+        assert_eq!(header.kid.as_deref(), Some(key.kid.as_str()));
+    }
+
+    #[test]
+    fn test_ensure_is_idempotent() {
+        let store = InMemoryTokenStore::new();
+        ensure_signing_key(&store, &test_rsa_pem(), 3600, 100).unwrap();
+        ensure_signing_key(&store, &test_rsa_pem(), 3600, 200).unwrap();
+        assert_eq!(store.list_signing_keys().unwrap().len(), 1);
+        assert_eq!(active_signing_key(&store).unwrap().kid, published_kids(&store, 200)[0]);
+    }
+
+    #[test]
+    fn test_rotation_publishes_old_key_until_retention_ends() {
+        let store = InMemoryTokenStore::new();
+        ensure_signing_key(&store, &test_rsa_pem(), 3600, 100).unwrap();
+        let old_kid = active_signing_key(&store).unwrap().kid;
+        let new_kid = rotate_signing_key(&store, &generate_key_pem().unwrap(), 3600, 1_000).unwrap();
+
+        assert_eq!(active_signing_key(&store).unwrap().kid, new_kid);
+        assert_eq!(published_kids(&store, 1_000), vec![old_kid.clone(), new_kid.clone()]);
+        assert_eq!(published_kids(&store, 4_601), vec![new_kid.clone()]);
+
+        // The configured key is not reactivated once rotated out.
+        ensure_signing_key(&store, &test_rsa_pem(), 3600, 5_000).unwrap();
+        assert_eq!(active_signing_key(&store).unwrap().kid, new_kid);
+    }
+}
@@ -1,5 +1,7 @@
 pub mod config;
+pub mod keys;
 pub mod oauth2;
 pub mod plugin;
 pub mod saml;
 pub mod store;
+pub mod xmldsig;
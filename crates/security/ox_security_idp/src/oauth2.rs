@@ -1,12 +1,19 @@
-use base64ct::{Base64Url, Encoding};
-use jsonwebtoken::{encode, EncodingKey, Header, Algorithm};
+use base64ct::{Base64Url, Base64UrlUnpadded, Encoding};
+use jsonwebtoken::EncodingKey;
+use ox_security_core::Principal;
+use rsa::pkcs1::DecodeRsaPrivateKey;
+use rsa::pkcs8::DecodePrivateKey;
+use rsa::RsaPrivateKey;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use sha2::{Digest, Sha256};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::config::{IdpConfig, OAuthClientDef};
-use crate::store::{AuthCodeEntry, AuthCodeStore, RefreshTokenStore, TokenEntry, TokenStore};
+use crate::config::{ClaimMapping, IdpConfig, OAuthClientDef};
+use crate::keys::SigningKey;
+use crate::store::{AuthCodeEntry, TokenEntry, TokenStore};
 
+#[derive(Debug)]
 pub struct Oauth2Error {
     pub status: u16,
     pub error: &'static str,
@@ -20,12 +27,28 @@ impl Oauth2Error {
             "error_description": self.description,
         }).to_string()
     }
+
+    /// RFC 6750 challenge for errors from resource endpoints such as userinfo.
+    pub fn www_authenticate(&self) -> String {
+        format!("Bearer error=\"{}\", error_description=\"{}\"", self.error, self.description)
+    }
 }
 
+const STORE_UNAVAILABLE: Oauth2Error = Oauth2Error {
+    status: 500, error: "server_error", description: "token store unavailable",
+};
+
 pub fn build_encoding_key(pem: &str) -> Result<EncodingKey, String> {
     EncodingKey::from_rsa_pem(pem.as_bytes()).map_err(|e| e.to_string())
 }
 
+/// The signing key from PKCS#1 or PKCS#8 PEM.
+pub fn read_rsa_key(pem: &str) -> Result<RsaPrivateKey, String> {
+    RsaPrivateKey::from_pkcs1_pem(pem)
+        .or_else(|_| RsaPrivateKey::from_pkcs8_pem(pem))
+        .map_err(|e| e.to_string())
+}
+
 pub fn now_secs() -> u64 {
     SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
 }
@@ -42,7 +65,7 @@ struct JwtClaims {
 }
 
 pub fn issue_access_token(
-    enc_key: &EncodingKey,
+    key: &SigningKey,
     issuer: &str,
     client_id: &str,
     principal_id: Option<&str>,
@@ -60,8 +83,113 @@ pub fn issue_access_token(
         jti: jti.to_string(),
         scope: scope.to_string(),
     };
-    let header = Header::new(Algorithm::RS256);
-    encode(&header, &claims, enc_key).map_err(|e| e.to_string())
+    key.sign(&claims)
+}
+
+#[derive(Serialize, Deserialize)]
+struct IdTokenClaims {
+    iss: String,
+    sub: String,
+    aud: String,
+    exp: u64,
+    iat: u64,
+    auth_time: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nonce: Option<String>,
+    at_hash: String,
+    #[serde(flatten)]
+    claims: Map<String, Value>,
+}
+
+/// OIDC `at_hash`: base64url of the left half of the SHA-256 of the access token.
+pub fn at_hash(access_token: &str) -> String {
+    let digest = Sha256::digest(access_token.as_bytes());
+    Base64UrlUnpadded::encode_string(&digest[..16])
+}
+
+/// Sign an ID token for the user behind `access`, the stored access token entry.
+pub fn issue_id_token(
+    key: &SigningKey,
+    config: &IdpConfig,
+    access: &TokenEntry,
+    access_token: &str,
+    nonce: Option<&str>,
+) -> Result<String, String> {
+    let sub = access.principal_id.as_deref().ok_or("ID token requires an end user")?;
+    let now = now_secs();
+    let claims = IdTokenClaims {
+        iss: config.issuer.clone(),
+        sub: sub.to_string(),
+        aud: access.client_id.clone(),
+        exp: now + config.id_token_ttl_secs,
+        iat: now,
+        auth_time: access.auth_time.unwrap_or(now),
+        nonce: nonce.map(str::to_string),
+        at_hash: at_hash(access_token),
+        claims: access.claims.clone(),
+    };
+    key.sign(&claims)
+}
+
+fn has_scope(scope: &str, name: &str) -> bool {
+    scope.split_whitespace().any(|s| s == name)
+}
+
+/// The end user at the authorization endpoint.
+pub struct AuthenticatedUser {
+    pub subject: String,
+    pub auth_time: u64,
+    /// Every mapped claim the user has; narrowed to the granted scopes at authorize.
+    pub claims: Map<String, Value>,
+}
+
+impl AuthenticatedUser {
+    /// A user known only by ID, e.g. from a bearer token this IdP issued.
+    pub fn subject_only(subject: &str, auth_time: u64) -> Self {
+        Self { subject: subject.to_string(), auth_time, claims: Map::new() }
+    }
+
+    /// Map principal fields and attributes to claims. Attribute values `true`
+    /// and `false` become JSON booleans; missing attributes and empty groups are
+    /// left out. The subject is the principal's `stable_id`, so a user keeps
+    /// the same `sub` across logins.
+    pub fn from_principal(principal: &Principal, mappings: &[ClaimMapping], auth_time: u64) -> Self {
+        let mut claims = Map::new();
+        for mapping in mappings {
+            let value = match mapping.source.as_str() {
+                "display_name" => Value::from(principal.display_name.as_str()),
+                "tenant_id" => Value::from(principal.tenant_id.as_str()),
+                "groups" => {
+                    if principal.groups.is_empty() { continue; }
+                    principal.groups.iter().map(|g| Value::from(g.as_str())).collect()
+                }
+                source => {
+                    let attr = source.strip_prefix("attributes.")
+                        .and_then(|name| principal.attributes.get(name));
+                    match attr.map(String::as_str) {
+                        Some("true") => Value::Bool(true),
+                        Some("false") => Value::Bool(false),
+                        Some(v) => Value::from(v),
+                        None => continue,
+                    }
+                }
+            };
+            claims.insert(mapping.claim.clone(), value);
+        }
+        Self { subject: principal.stable_id(), auth_time, claims }
+    }
+}
+
+/// Claims whose mapping scope was granted. Nothing is released without `openid`.
+fn release_claims(mappings: &[ClaimMapping], claims: &Map<String, Value>, scope: &str) -> Map<String, Value> {
+    if !has_scope(scope, "openid") {
+        return Map::new();
+    }
+    claims.iter()
+        .filter(|(claim, _)| mappings.iter()
+            .any(|m| &m.claim == *claim && has_scope(scope, &m.scope)))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect()
 }
 
 /// Verify PKCE S256 challenge
@@ -94,7 +222,7 @@ pub fn authenticate_client(client: &OAuthClientDef, secret: Option<&str>) -> boo
     }
 }
 
-fn percent_decode(s: &str) -> String {
+pub(crate) fn percent_decode(s: &str) -> String {
     let mut out = String::with_capacity(s.len());
     let bytes = s.as_bytes();
     let mut i = 0;
@@ -119,7 +247,7 @@ fn percent_decode(s: &str) -> String {
     out
 }
 
-fn parse_form(input: &str) -> std::collections::HashMap<String, String> {
+pub(crate) fn parse_form(input: &str) -> std::collections::HashMap<String, String> {
     input.split('&')
         .filter_map(|p| {
             let mut kv = p.splitn(2, '=');
@@ -145,9 +273,9 @@ fn urlencoding_encode(s: &str) -> String {
 
 pub fn handle_authorize(
     config: &IdpConfig,
-    code_store: &AuthCodeStore,
+    store: &dyn TokenStore,
     query: &str,
-    authenticated_principal_id: Option<&str>,
+    user: Option<&AuthenticatedUser>,
 ) -> Result<String, Oauth2Error> {
     let params = parse_form(query);
 
@@ -162,6 +290,7 @@ pub fn handle_authorize(
     let state = params.get("state").map(String::as_str).unwrap_or("");
     let code_challenge = params.get("code_challenge").map(String::as_str);
     let code_challenge_method = params.get("code_challenge_method").map(String::as_str);
+    let nonce = params.get("nonce").map(String::as_str).filter(|n| !n.is_empty());
 
     if let Some(method) = code_challenge_method {
         if method != "S256" {
@@ -186,7 +315,7 @@ pub fn handle_authorize(
         return Err(Oauth2Error { status: 400, error: "invalid_request", description: "PKCE required for public clients" });
     }
 
-    let principal_id = authenticated_principal_id.ok_or(Oauth2Error {
+    let user = user.ok_or(Oauth2Error {
         status: 401, error: "login_required", description: "user not authenticated",
     })?;
 
@@ -196,12 +325,15 @@ pub fn handle_authorize(
         client_id: client_id.to_string(),
         redirect_uri: redirect_uri.to_string(),
         scope: scope.to_string(),
-        principal_id: principal_id.to_string(),
+        principal_id: user.subject.clone(),
         code_challenge: code_challenge.map(|s| s.to_string()),
         code_challenge_method: code_challenge_method.map(|s| s.to_string()),
         expires_at: now_secs() + 600,
+        nonce: nonce.map(|s| s.to_string()),
+        auth_time: user.auth_time,
+        claims: release_claims(&config.claim_mappings, &user.claims, scope),
     };
-    code_store.insert(entry);
+    store.insert_code(&entry).map_err(|_| STORE_UNAVAILABLE)?;
 
     let redirect = if state.is_empty() {
         format!("{}?code={}", redirect_uri, urlencoding_encode(&code))
@@ -213,10 +345,8 @@ pub fn handle_authorize(
 
 pub fn handle_token(
     config: &IdpConfig,
-    enc_key: &EncodingKey,
-    code_store: &AuthCodeStore,
-    token_store: &TokenStore,
-    refresh_store: &RefreshTokenStore,
+    key: &SigningKey,
+    store: &dyn TokenStore,
     body: &str,
 ) -> (u16, String) {
     let params = parse_form(body);
@@ -235,19 +365,17 @@ pub fn handle_token(
     }
 
     match grant_type {
-        "authorization_code" => handle_token_auth_code(config, enc_key, code_store, token_store, refresh_store, &params, client),
-        "client_credentials" => handle_token_client_credentials(config, enc_key, token_store, &params, client),
-        "refresh_token" => handle_token_refresh(config, enc_key, token_store, refresh_store, &params),
+        "authorization_code" => handle_token_auth_code(config, key, store, &params, client),
+        "client_credentials" => handle_token_client_credentials(config, key, store, &params, client),
+        "refresh_token" => handle_token_refresh(config, key, store, &params),
         _ => (400, serde_json::json!({"error":"unsupported_grant_type"}).to_string()),
     }
 }
 
 fn handle_token_auth_code(
     config: &IdpConfig,
-    enc_key: &EncodingKey,
-    code_store: &AuthCodeStore,
-    token_store: &TokenStore,
-    refresh_store: &RefreshTokenStore,
+    key: &SigningKey,
+    store: &dyn TokenStore,
     params: &std::collections::HashMap<String, String>,
     client: &OAuthClientDef,
 ) -> (u16, String) {
@@ -257,9 +385,10 @@ fn handle_token_auth_code(
     };
     let redirect_uri = params.get("redirect_uri").map(String::as_str).unwrap_or("");
 
-    let entry = match code_store.consume(code) {
-        Some(e) => e,
-        None => return (400, serde_json::json!({"error":"invalid_grant","error_description":"code not found or expired"}).to_string()),
+    let entry = match store.consume_code(code) {
+        Ok(Some(e)) => e,
+        Ok(None) => return (400, serde_json::json!({"error":"invalid_grant","error_description":"code not found or expired"}).to_string()),
+        Err(_) => return (500, STORE_UNAVAILABLE.to_json()),
     };
 
     if entry.client_id != client.client_id {
@@ -283,7 +412,7 @@ fn handle_token_auth_code(
     }
 
     let jti = uuid::Uuid::new_v4().to_string();
-    let access_token = match issue_access_token(enc_key, &config.issuer,
+    let access_token = match issue_access_token(key, &config.issuer,
         &client.client_id, Some(&entry.principal_id), &entry.scope,
         config.access_token_ttl_secs, &jti)
     {
@@ -291,7 +420,9 @@ fn handle_token_auth_code(
         Err(e) => return (500, serde_json::json!({"error":"server_error","error_description":e}).to_string()),
     };
 
-    token_store.insert(TokenEntry {
+    // Every token descended from this code shares a family
+    let family_id = uuid::Uuid::new_v4().to_string();
+    let access = TokenEntry {
         jti: jti.clone(),
         client_id: client.client_id.clone(),
         principal_id: Some(entry.principal_id.clone()),
@@ -299,44 +430,67 @@ fn handle_token_auth_code(
         expires_at: now_secs() + config.access_token_ttl_secs,
         revoked: false,
         raw_jwt: Some(access_token.clone()),
-    });
+        auth_time: Some(entry.auth_time),
+        claims: entry.claims.clone(),
+        family_id: Some(family_id.clone()),
+        used: false,
+    };
+    let id_token = if has_scope(&entry.scope, "openid") {
+        match issue_id_token(key, config, &access, &access_token, entry.nonce.as_deref()) {
+            Ok(t) => Some(t),
+            Err(e) => return (500, serde_json::json!({"error":"server_error","error_description":e}).to_string()),
+        }
+    } else {
+        None
+    };
 
     let refresh_token = uuid::Uuid::new_v4().to_string();
-    refresh_store.insert(TokenEntry {
-        jti: refresh_token.clone(),
+    let refresh = TokenEntry {
+        jti: sha256_hex(&refresh_token),
         client_id: client.client_id.clone(),
         principal_id: Some(entry.principal_id.clone()),
         scope: entry.scope.clone(),
         expires_at: now_secs() + config.refresh_token_ttl_secs,
         revoked: false,
         raw_jwt: None,
-    });
+        auth_time: Some(entry.auth_time),
+        claims: entry.claims,
+        family_id: Some(family_id),
+        used: false,
+    };
+    if store.insert_token(&access).and_then(|_| store.insert_refresh_token(&refresh)).is_err() {
+        return (500, STORE_UNAVAILABLE.to_json());
+    }
 
-    (200, serde_json::json!({
+    let mut resp = serde_json::json!({
         "access_token": access_token,
         "token_type": "Bearer",
         "expires_in": config.access_token_ttl_secs,
         "refresh_token": refresh_token,
         "scope": entry.scope,
-    }).to_string())
+    });
+    if let Some(id_token) = id_token {
+        resp["id_token"] = Value::from(id_token);
+    }
+    (200, resp.to_string())
 }
 
 fn handle_token_client_credentials(
     config: &IdpConfig,
-    enc_key: &EncodingKey,
-    token_store: &TokenStore,
+    key: &SigningKey,
+    store: &dyn TokenStore,
     params: &std::collections::HashMap<String, String>,
     client: &OAuthClientDef,
 ) -> (u16, String) {
     let scope = params.get("scope").map(String::as_str).unwrap_or("");
     let jti = uuid::Uuid::new_v4().to_string();
-    let access_token = match issue_access_token(enc_key, &config.issuer,
+    let access_token = match issue_access_token(key, &config.issuer,
         &client.client_id, None, scope, config.access_token_ttl_secs, &jti)
     {
         Ok(t) => t,
         Err(e) => return (500, serde_json::json!({"error":"server_error","error_description":e}).to_string()),
     };
-    token_store.insert(TokenEntry {
+    let access = TokenEntry {
         jti,
         client_id: client.client_id.clone(),
         principal_id: None,
@@ -344,7 +498,14 @@ fn handle_token_client_credentials(
         expires_at: now_secs() + config.access_token_ttl_secs,
         revoked: false,
         raw_jwt: Some(access_token.clone()),
-    });
+        auth_time: None,
+        claims: Map::new(),
+        family_id: None,
+        used: false,
+    };
+    if store.insert_token(&access).is_err() {
+        return (500, STORE_UNAVAILABLE.to_json());
+    }
     (200, serde_json::json!({
         "access_token": access_token,
         "token_type": "Bearer",
@@ -352,37 +513,54 @@ fn handle_token_client_credentials(
     }).to_string())
 }
 
+/// Refresh tokens rotate: each is redeemable once and is replaced by a new one
+/// in the same family. Presenting a spent token means one of the two copies
+/// leaked, so the whole family is revoked.
 fn handle_token_refresh(
     config: &IdpConfig,
-    enc_key: &EncodingKey,
-    token_store: &TokenStore,
-    refresh_store: &RefreshTokenStore,
+    key: &SigningKey,
+    store: &dyn TokenStore,
     params: &std::collections::HashMap<String, String>,
 ) -> (u16, String) {
     let rt = match params.get("refresh_token").map(String::as_str) {
         Some(t) => t,
         None => return (400, serde_json::json!({"error":"invalid_request"}).to_string()),
     };
-    let entry = match refresh_store.consume(rt) {
-        Some(e) => e,
-        None => return (400, serde_json::json!({"error":"invalid_grant","error_description":"refresh token not found"}).to_string()),
+    let token_hash = sha256_hex(rt);
+    let entry = match store.get_refresh_token(&token_hash) {
+        Ok(Some(e)) => e,
+        Ok(None) => return (400, serde_json::json!({"error":"invalid_grant","error_description":"refresh token not found"}).to_string()),
+        Err(_) => return (500, STORE_UNAVAILABLE.to_json()),
     };
     let requesting_client_id = params.get("client_id").map(String::as_str).unwrap_or("");
     if !requesting_client_id.is_empty() && entry.client_id != requesting_client_id {
         return (401, serde_json::json!({"error":"invalid_grant","error_description":"refresh token was not issued to this client"}).to_string());
     }
+    let entry = match store.use_refresh_token(&token_hash) {
+        Ok(Some(e)) => e,
+        Ok(None) => return (400, serde_json::json!({"error":"invalid_grant","error_description":"refresh token not found"}).to_string()),
+        Err(_) => return (500, STORE_UNAVAILABLE.to_json()),
+    };
+    if entry.used {
+        if let Some(family_id) = &entry.family_id {
+            if store.revoke_family(family_id).is_err() {
+                return (500, STORE_UNAVAILABLE.to_json());
+            }
+        }
+        return (400, serde_json::json!({"error":"invalid_grant","error_description":"refresh token reused; grant revoked"}).to_string());
+    }
     if entry.revoked || entry.expires_at < now_secs() {
         return (400, serde_json::json!({"error":"invalid_grant","error_description":"refresh token expired or revoked"}).to_string());
     }
     let jti = uuid::Uuid::new_v4().to_string();
-    let access_token = match issue_access_token(enc_key, &config.issuer,
+    let access_token = match issue_access_token(key, &config.issuer,
         &entry.client_id, entry.principal_id.as_deref(), &entry.scope,
         config.access_token_ttl_secs, &jti)
     {
         Ok(t) => t,
         Err(e) => return (500, serde_json::json!({"error":"server_error","error_description":e}).to_string()),
     };
-    token_store.insert(TokenEntry {
+    let access = TokenEntry {
         jti,
         client_id: entry.client_id.clone(),
         principal_id: entry.principal_id.clone(),
@@ -390,26 +568,93 @@ fn handle_token_refresh(
         expires_at: now_secs() + config.access_token_ttl_secs,
         revoked: false,
         raw_jwt: Some(access_token.clone()),
-    });
+        auth_time: entry.auth_time,
+        claims: entry.claims.clone(),
+        family_id: entry.family_id.clone(),
+        used: false,
+    };
+    // A refreshed ID token keeps the original auth_time and carries no nonce.
+    let id_token = if has_scope(&entry.scope, "openid") && entry.principal_id.is_some() {
+        match issue_id_token(key, config, &access, &access_token, None) {
+            Ok(t) => Some(t),
+            Err(e) => return (500, serde_json::json!({"error":"server_error","error_description":e}).to_string()),
+        }
+    } else {
+        None
+    };
     let new_rt = uuid::Uuid::new_v4().to_string();
-    refresh_store.insert(TokenEntry {
-        jti: new_rt.clone(),
+    let refresh = TokenEntry {
+        jti: sha256_hex(&new_rt),
         client_id: entry.client_id,
         principal_id: entry.principal_id,
         scope: entry.scope.clone(),
         expires_at: now_secs() + config.refresh_token_ttl_secs,
         revoked: false,
         raw_jwt: None,
-    });
-    (200, serde_json::json!({
+        auth_time: entry.auth_time,
+        claims: entry.claims,
+        family_id: entry.family_id,
+        used: false,
+    };
+    if store.insert_token(&access).and_then(|_| store.insert_refresh_token(&refresh)).is_err() {
+        return (500, STORE_UNAVAILABLE.to_json());
+    }
+    let mut resp = serde_json::json!({
         "access_token": access_token,
         "token_type": "Bearer",
         "expires_in": config.access_token_ttl_secs,
         "refresh_token": new_rt,
-    }).to_string())
+    });
+    if let Some(id_token) = id_token {
+        resp["id_token"] = Value::from(id_token);
+    }
+    (200, resp.to_string())
+}
+
+/// RFC 7009 revocation. Revoking a refresh token revokes its whole family,
+/// access tokens included; unknown tokens are not an error.
+pub fn handle_revoke(store: &dyn TokenStore, body: &str) -> Result<(), Oauth2Error> {
+    let params = parse_form(body);
+    let token = params.get("token").map(String::as_str).unwrap_or("");
+    if let Some(access) = store.find_active_token(token).map_err(|_| STORE_UNAVAILABLE)? {
+        return store.revoke_token(&access.jti).map_err(|_| STORE_UNAVAILABLE);
+    }
+    let refresh = store.get_refresh_token(&sha256_hex(token)).map_err(|_| STORE_UNAVAILABLE)?;
+    if let Some(family_id) = refresh.and_then(|r| r.family_id) {
+        store.revoke_family(&family_id).map_err(|_| STORE_UNAVAILABLE)?;
+    }
+    Ok(())
+}
+
+/// OIDC userinfo: `sub` plus the claims released when the access token was
+/// granted. `authorization` is the request's Authorization header.
+pub fn handle_userinfo(store: &dyn TokenStore, authorization: &str) -> Result<String, Oauth2Error> {
+    let token = authorization.strip_prefix("Bearer ").ok_or(Oauth2Error {
+        status: 401, error: "invalid_token", description: "bearer access token required",
+    })?;
+    let entry = store.find_active_token(token)
+        .map_err(|_| STORE_UNAVAILABLE)?
+        .ok_or(Oauth2Error {
+            status: 401, error: "invalid_token", description: "access token unknown, expired or revoked",
+        })?;
+    let sub = match &entry.principal_id {
+        Some(sub) if has_scope(&entry.scope, "openid") => sub,
+        _ => return Err(Oauth2Error {
+            status: 403, error: "insufficient_scope", description: "access token was not granted the openid scope",
+        }),
+    };
+    let mut body = entry.claims.clone();
+    body.insert("sub".to_string(), Value::from(sub.as_str()));
+    Ok(Value::Object(body).to_string())
 }
 
 pub fn handle_oidc_discovery(config: &IdpConfig) -> String {
+    let mut scopes = vec!["openid"];
+    let mut claims = vec!["iss", "sub", "aud", "exp", "iat", "auth_time", "nonce", "at_hash"];
+    for m in &config.claim_mappings {
+        if !scopes.contains(&m.scope.as_str()) { scopes.push(&m.scope); }
+        if !claims.contains(&m.claim.as_str()) { claims.push(&m.claim); }
+    }
     serde_json::json!({
         "issuer": config.issuer,
         "authorization_endpoint": format!("{}/oauth2/authorize", config.issuer),
@@ -417,7 +662,12 @@ pub fn handle_oidc_discovery(config: &IdpConfig) -> String {
         "introspection_endpoint": format!("{}/oauth2/introspect", config.issuer),
         "revocation_endpoint": format!("{}/oauth2/revoke", config.issuer),
         "jwks_uri": format!("{}/oidc/jwks.json", config.issuer),
+        "userinfo_endpoint": format!("{}/oidc/userinfo", config.issuer),
         "response_types_supported": ["code"],
+        "subject_types_supported": ["public"],
+        "id_token_signing_alg_values_supported": ["RS256"],
+        "scopes_supported": scopes,
+        "claims_supported": claims,
         "grant_types_supported": ["authorization_code", "client_credentials", "refresh_token"],
         "token_endpoint_auth_methods_supported": ["client_secret_post"],
         "code_challenge_methods_supported": ["S256"],
@@ -427,6 +677,8 @@ pub fn handle_oidc_discovery(config: &IdpConfig) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::keys::{active_signing_key, build_jwks, ensure_signing_key};
+    use crate::store::InMemoryTokenStore;
 
     fn test_rsa_pem() -> String {
         include_str!("../tests/test_rsa_private.pem").to_string()
@@ -441,12 +693,12 @@ mod tests {
 
     #[test]
     fn test_issue_and_verify_access_token() {
-        let pem = test_rsa_pem();
-        let enc_key = build_encoding_key(&pem).unwrap();
-        let token = issue_access_token(&enc_key, "https://auth.example.com",
+        let key = SigningKey::from_pem(&test_rsa_pem()).unwrap();
+        let token = issue_access_token(&key, "https://auth.example.com",
             "client-1", Some("user-1"), "openid profile", 3600, "jti-test").unwrap();
         let header = jsonwebtoken::decode_header(&token).unwrap();
         assert_eq!(header.alg, jsonwebtoken::Algorithm::RS256);
+        assert_eq!(header.kid, Some(key.kid));
     }
 
     #[test]
@@ -463,6 +715,178 @@ mod tests {
         assert!(!verify_pkce_challenge("wrong", "does_not_match"));
     }
 
+    fn oidc_config() -> IdpConfig {
+        serde_json::from_value(serde_json::json!({
+            "tenant_id": "t1",
+            "issuer": "https://auth.example.com",
+            "rsa_private_key_pem": test_rsa_pem(),
+            "clients": [{
+                "client_id": "app",
+                "client_secret_hash": null,
+                "redirect_uris": ["https://app.example.com/cb"],
+            }],
+        })).unwrap()
+    }
+
+    fn alice(config: &IdpConfig) -> AuthenticatedUser {
+        use ox_security_core::{AuthSource, GroupId, PrincipalId, TenantId};
+        use std::str::FromStr;
+        let principal = Principal {
+            id: PrincipalId::new(),
+            display_name: "Alice Example".to_string(),
+            source: AuthSource::Ldap,
+            groups: vec![GroupId::new("admins"), GroupId::new("dev")],
+            tenant_id: TenantId::from_str("t1").unwrap(),
+            session_id: None,
+            attributes: [("email", "alice@example.com"), ("email_verified", "true")]
+                .into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        };
+        AuthenticatedUser::from_principal(&principal, &config.claim_mappings, 1_700_000_000)
+    }
+
+    /// A store holding the configured signing key.
+    fn new_store(config: &IdpConfig) -> InMemoryTokenStore {
+        let store = InMemoryTokenStore::new();
+        ensure_signing_key(&store, &config.rsa_private_key_pem, config.key_retention_secs(), now_secs()).unwrap();
+        store
+    }
+
+    fn token_request(config: &IdpConfig, store: &dyn TokenStore, body: &str) -> (u16, Value) {
+        let key = active_signing_key(store).unwrap();
+        let (status, resp) = handle_token(config, &key, store, body);
+        (status, serde_json::from_str(&resp).unwrap())
+    }
+
+    /// Run authorize and the code exchange; returns the token response.
+    fn code_flow(config: &IdpConfig, store: &dyn TokenStore, scope: &str, nonce: &str) -> Value {
+        let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        let challenge = Base64Url::encode_string(&Sha256::digest(verifier.as_bytes()));
+        let query = format!(
+            "response_type=code&client_id=app&redirect_uri={}&scope={}&nonce={}&code_challenge={}&code_challenge_method=S256",
+            urlencoding_encode("https://app.example.com/cb"), urlencoding_encode(scope),
+            nonce, urlencoding_encode(&challenge));
+        let location = handle_authorize(config, store, &query, Some(&alice(config))).unwrap();
+        let code = location.split("code=").nth(1).unwrap();
+        let body = format!("grant_type=authorization_code&client_id=app&code={}&redirect_uri={}&code_verifier={}",
+            code, urlencoding_encode("https://app.example.com/cb"), verifier);
+        let (status, resp) = token_request(config, store, &body);
+        assert_eq!(status, 200, "{}", resp);
+        resp
+    }
+
+    /// Verify an ID token against the published JWKS key named by its `kid`.
+    fn decode_id_token(config: &IdpConfig, store: &dyn TokenStore, id_token: &str) -> Value {
+        let jwks = build_jwks(&store.list_signing_keys().unwrap(), config.key_retention_secs(), now_secs()).unwrap();
+        let jwks: Value = serde_json::from_str(&jwks).unwrap();
+        let kid = jsonwebtoken::decode_header(id_token).unwrap().kid.unwrap();
+        let jwk = jwks["keys"].as_array().unwrap().iter().find(|k| k["kid"] == kid.as_str()).unwrap();
+        let key = jsonwebtoken::DecodingKey::from_rsa_components(
+            jwk["n"].as_str().unwrap(), jwk["e"].as_str().unwrap()).unwrap();
+        let mut validation = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::RS256);
+        validation.set_audience(&["app"]);
+        validation.set_issuer(&["https://auth.example.com"]);
+        jsonwebtoken::decode::<Value>(id_token, &key, &validation).unwrap().claims
+    }
+
+    #[test]
+    fn test_code_flow_issues_id_token() {
+        let config = oidc_config();
+        let store = new_store(&config);
+        let resp = code_flow(&config, &store, "openid email groups", "n-0S6_WzA2Mj");
+        let claims = decode_id_token(&config, &store, resp["id_token"].as_str().unwrap());
+
+        assert_eq!(claims["nonce"], "n-0S6_WzA2Mj");
+        assert_eq!(claims["auth_time"], 1_700_000_000u64);
+        assert_eq!(claims["at_hash"], at_hash(resp["access_token"].as_str().unwrap()));
+        assert_eq!(claims["email"], "alice@example.com");
+        assert_eq!(claims["email_verified"], true);
+        assert_eq!(claims["groups"], serde_json::json!(["admins", "dev"]));
+        assert!(claims.get("name").is_none(), "profile scope was not granted");
+
+        // Every login has a new principal ID; the subject stays the same
+        assert_eq!(claims["sub"], "ldap:Alice Example");
+        let again = code_flow(&config, &store, "openid", "n2");
+        assert_eq!(decode_id_token(&config, &store, again["id_token"].as_str().unwrap())["sub"], claims["sub"]);
+    }
+
+    #[test]
+    fn test_no_id_token_without_openid_scope() {
+        let config = oidc_config();
+        let tokens = new_store(&config);
+        let resp = code_flow(&config, &tokens, "email", "n1");
+        assert!(resp.get("id_token").is_none());
+
+        let auth = format!("Bearer {}", resp["access_token"].as_str().unwrap());
+        let err = handle_userinfo(&tokens, &auth).unwrap_err();
+        assert_eq!((err.status, err.error), (403, "insufficient_scope"));
+    }
+
+    #[test]
+    fn test_userinfo_returns_granted_claims() {
+        let config = oidc_config();
+        let tokens = new_store(&config);
+        let resp = code_flow(&config, &tokens, "openid profile", "n1");
+        let sub = decode_id_token(&config, &tokens, resp["id_token"].as_str().unwrap())["sub"].clone();
+
+        let auth = format!("Bearer {}", resp["access_token"].as_str().unwrap());
+        let info: Value = serde_json::from_str(&handle_userinfo(&tokens, &auth).unwrap()).unwrap();
+        assert_eq!(info["sub"], sub);
+        assert_eq!(info["name"], "Alice Example");
+        assert!(info.get("email").is_none());
+
+        let err = handle_userinfo(&tokens, "Bearer not-a-token").unwrap_err();
+        assert_eq!((err.status, err.error), (401, "invalid_token"));
+        assert!(err.www_authenticate().starts_with("Bearer error=\"invalid_token\""));
+    }
+
+    #[test]
+    fn test_refresh_token_rotates_and_reuse_revokes_family() {
+        let config = oidc_config();
+        let store = new_store(&config);
+        let first = code_flow(&config, &store, "openid", "n1");
+        let refresh = |rt: &Value| token_request(&config, &store,
+            &format!("grant_type=refresh_token&client_id=app&refresh_token={}", rt.as_str().unwrap()));
+
+        let (status, second) = refresh(&first["refresh_token"]);
+        assert_eq!(status, 200, "{}", second);
+        assert_ne!(second["refresh_token"], first["refresh_token"]);
+        let claims = decode_id_token(&config, &store, second["id_token"].as_str().unwrap());
+        assert!(claims.get("nonce").is_none());
+
+        // Replaying the first refresh token revokes everything in the family
+        let (status, err) = refresh(&first["refresh_token"]);
+        assert_eq!((status, err["error"].as_str()), (400, Some("invalid_grant")));
+        let (status, _) = refresh(&second["refresh_token"]);
+        assert_eq!(status, 400);
+        for resp in [&first, &second] {
+            let auth = format!("Bearer {}", resp["access_token"].as_str().unwrap());
+            assert_eq!(handle_userinfo(&store, &auth).unwrap_err().status, 401);
+        }
+    }
+
+    #[test]
+    fn test_revoking_refresh_token_revokes_family() {
+        let config = oidc_config();
+        let store = new_store(&config);
+        let other = code_flow(&config, &store, "openid", "n1");
+        let resp = code_flow(&config, &store, "openid", "n2");
+        handle_revoke(&store, &format!("token={}", resp["refresh_token"].as_str().unwrap())).unwrap();
+        handle_revoke(&store, "token=unknown").unwrap();
+
+        let auth = format!("Bearer {}", resp["access_token"].as_str().unwrap());
+        assert_eq!(handle_userinfo(&store, &auth).unwrap_err().status, 401);
+        let auth = format!("Bearer {}", other["access_token"].as_str().unwrap());
+        assert!(handle_userinfo(&store, &auth).is_ok(), "other grants are untouched");
+    }
+
+    #[test]
+    fn test_discovery_advertises_userinfo() {
+        let discovery: Value = serde_json::from_str(&handle_oidc_discovery(&oidc_config())).unwrap();
+        assert_eq!(discovery["userinfo_endpoint"], "https://auth.example.com/oidc/userinfo");
+        assert_eq!(discovery["id_token_signing_alg_values_supported"], serde_json::json!(["RS256"]));
+        assert!(discovery["scopes_supported"].as_array().unwrap().contains(&Value::from("email")));
+    }
+
     #[test]
     fn test_urlencoding_roundtrip() {
         let s = "abc123-_~";
@@ -1,30 +1,42 @@
+use std::collections::HashMap;
 use std::ffi::{c_char, c_void, CStr, CString};
 use std::panic;
 use std::path::Path;
 use std::ptr::null;
 
-use jsonwebtoken::EncodingKey;
+use ox_security_core::Principal;
+use rsa::RsaPublicKey;
 use ox_workflow_abi::{
     CoreHostApi, FlowControl, FLOW_CONTROL_CONTINUE, OX_LOG_ERROR, OX_LOG_INFO,
     OX_WORKFLOW_ABI_VERSION,
 };
 
 use crate::config::IdpConfig;
+use crate::keys::{active_signing_key, build_jwks, ensure_signing_key, generate_key_pem, rotate_signing_key};
 use crate::oauth2::{
-    build_encoding_key, handle_authorize, handle_oidc_discovery, handle_token, now_secs,
+    handle_authorize, handle_oidc_discovery, handle_revoke, handle_token, handle_userinfo,
+    now_secs, parse_form, read_rsa_key, AuthenticatedUser,
 };
-use crate::saml::{build_assertion_xml, build_metadata_xml, build_saml_post_form};
-use crate::store::{AuthCodeStore, RefreshTokenStore, SamlSessionEntry, SamlSessionStore, TokenStore};
+use crate::saml::{
+    build_assertion_xml, build_logout_response_xml, build_metadata_xml, build_response_xml,
+    build_saml_post_form, decode_saml_param, encrypted_assertion, new_id, parse_saml_request,
+    resolve_name_id, AssertionParams, SamlRequest, BINDING_POST, STATUS_INVALID_NAME_ID_POLICY,
+    STATUS_NO_PASSIVE, STATUS_SUCCESS,
+};
+use crate::store::{open_token_store, SamlSessionEntry, SamlSessionStore, TokenEntry, TokenStore};
+use crate::xmldsig::{encrypt_element, read_rsa_cert, verify_enveloped, verify_redirect_query, SamlSigner};
 
 struct PluginState {
     api: CoreHostApi,
     config: IdpConfig,
-    enc_key: EncodingKey,
-    code_store: AuthCodeStore,
-    token_store: TokenStore,
-    refresh_store: RefreshTokenStore,
+    store: Box<dyn TokenStore>,
     saml_sessions: SamlSessionStore,
-    cert_b64: String,
+    /// `None` when no SAML SPs are configured.
+    saml_signer: Option<SamlSigner>,
+    /// Encryption keys of SPs that want encrypted assertions, by entity ID.
+    sp_keys: HashMap<String, RsaPublicKey>,
+    /// Keys that verify SP LogoutRequests, by entity ID.
+    sp_signing_keys: HashMap<String, RsaPublicKey>,
 }
 unsafe impl Send for PluginState {}
 unsafe impl Sync for PluginState {}
@@ -84,14 +96,230 @@ fn redirect_response(api: &CoreHostApi, task_ctx: *mut c_void, location: &str) {
 // Auth helpers
 // ---------------------------------------------------------------------------
 
-fn extract_bearer_principal(state: &PluginState, task_ctx: *mut c_void) -> Option<String> {
+fn extract_bearer_token(state: &PluginState, task_ctx: *mut c_void) -> Option<TokenEntry> {
     let auth_header = get_field(&state.api, task_ctx, "request.header.Authorization");
-    let token = auth_header.strip_prefix("Bearer ")?.to_string();
-    let active = state.token_store.list_active();
-    active
-        .into_iter()
-        .find(|e| e.raw_jwt.as_deref() == Some(token.as_str()))
-        .and_then(|e| e.principal_id)
+    let token = auth_header.strip_prefix("Bearer ")?;
+    state.store.find_active_token(token).ok().flatten()
+}
+
+fn server_error(api: &CoreHostApi, task_ctx: *mut c_void, context: &str, e: &str) {
+    log(api, task_ctx, OX_LOG_ERROR, &format!("ox_security_idp: {}: {}", context, e));
+    json_response(api, task_ctx, 500,
+        r#"{"error":"server_error","error_description":"internal error"}"#);
+}
+
+/// The end user for the authorization endpoint. The security pipeline publishes
+/// `security.principal` (a JSON `Principal`) and `security.auth_time` (Unix seconds)
+/// for an authenticated request; otherwise the user of a bearer token this IdP
+/// issued is used, with the claims that token was granted. Without a known
+/// authentication time there is no user: `auth_time` is never invented.
+fn authenticated_user(state: &PluginState, task_ctx: *mut c_void) -> Option<AuthenticatedUser> {
+    let auth_time = get_field(&state.api, task_ctx, "security.auth_time").parse().ok();
+    let principal_json = get_field(&state.api, task_ctx, "security.principal");
+    if let Ok(principal) = serde_json::from_str::<Principal>(&principal_json) {
+        return Some(AuthenticatedUser::from_principal(
+            &principal,
+            &state.config.claim_mappings,
+            auth_time?,
+        ));
+    }
+    let entry = extract_bearer_token(state, task_ctx)?;
+    let mut user = AuthenticatedUser::subject_only(
+        entry.principal_id.as_deref()?,
+        entry.auth_time.or(auth_time)?,
+    );
+    user.claims = entry.claims;
+    Some(user)
+}
+
+// ---------------------------------------------------------------------------
+// SAML
+// ---------------------------------------------------------------------------
+
+fn saml_error(api: &CoreHostApi, task_ctx: *mut c_void, status: u16, error: &str, description: &str) {
+    let body = serde_json::json!({ "error": error, "error_description": description });
+    json_response(api, task_ctx, status, &body.to_string());
+}
+
+/// Decode and parse the `SAMLRequest` parameter, which must be of `kind`.
+/// Returns the decoded XML along with the parsed request.
+fn read_saml_request(
+    params: &HashMap<String, String>,
+    redirect_binding: bool,
+    kind: &str,
+) -> Option<Result<(String, SamlRequest), String>> {
+    let value = params.get("SAMLRequest")?;
+    Some(decode_saml_param(value, redirect_binding)
+        .and_then(|xml| parse_saml_request(&xml).map(|req| (xml, req)))
+        .and_then(|(xml, req)| if req.kind == kind { Ok((xml, req)) } else { Err(format!("expected a {}", kind)) }))
+}
+
+/// SP-initiated SSO answers an AuthnRequest; without one (HTTP-POST only) the
+/// SP is named by `sp_entity_id` for IdP-initiated SSO. Either way the user
+/// must already be authenticated (see `authenticated_user`), and the signed,
+/// optionally encrypted, assertion is POSTed to the SP's registered ACS URL.
+fn handle_saml_sso(
+    state: &PluginState,
+    task_ctx: *mut c_void,
+    tenant: &str,
+    params: &HashMap<String, String>,
+    redirect_binding: bool,
+) {
+    let api = &state.api;
+    let Some(signer) = &state.saml_signer else {
+        return saml_error(api, task_ctx, 404, "unknown_sp", "no SAML service providers configured");
+    };
+    let request = match read_saml_request(params, redirect_binding, "AuthnRequest") {
+        Some(Ok((_, req))) => Some(req),
+        Some(Err(e)) => return saml_error(api, task_ctx, 400, "invalid_request", &e),
+        None if redirect_binding => {
+            return saml_error(api, task_ctx, 400, "invalid_request", "SAMLRequest required");
+        }
+        None => None,
+    };
+    let sp_entity_id = match &request {
+        Some(req) => req.issuer.as_str(),
+        None => params.get("sp_entity_id").map(String::as_str).unwrap_or(""),
+    };
+
+    // Only issue assertions for registered SPs
+    let Some(sp) = state.config.saml_sps.iter().find(|s| s.entity_id == sp_entity_id) else {
+        return saml_error(api, task_ctx, 400, "unknown_sp", "SP not registered");
+    };
+    if let Some(req) = &request {
+        let sso_url = format!("{}/saml/{}/sso", state.config.issuer, tenant);
+        if req.destination.as_deref().is_some_and(|d| d != sso_url) {
+            return saml_error(api, task_ctx, 400, "invalid_request", "Destination does not match this endpoint");
+        }
+        // Never send an assertion anywhere but the registered ACS
+        if req.acs_url.as_deref().is_some_and(|u| u != sp.acs_url) {
+            return saml_error(api, task_ctx, 400, "invalid_request", "AssertionConsumerServiceURL is not registered for this SP");
+        }
+        if req.protocol_binding.as_deref().is_some_and(|b| b != BINDING_POST) {
+            return saml_error(api, task_ctx, 400, "invalid_request", "only the HTTP-POST response binding is supported");
+        }
+    }
+    let in_response_to = request.as_ref().map(|r| r.id.as_str());
+    let relay_state = params.get("RelayState").map(String::as_str).unwrap_or("");
+    let reply = |response: &str| {
+        html_response(api, task_ctx, 200, &build_saml_post_form(&sp.acs_url, response, relay_state));
+    };
+
+    let Some(user) = authenticated_user(state, task_ctx) else {
+        if request.as_ref().is_some_and(|r| r.is_passive) {
+            return reply(&build_response_xml(&state.config.issuer, &sp.acs_url, in_response_to, Err(STATUS_NO_PASSIVE)));
+        }
+        return saml_error(api, task_ctx, 401, "login_required", "user not authenticated");
+    };
+    let format = request.as_ref().and_then(|r| r.name_id_format.as_deref())
+        .or(sp.name_id_format.as_deref());
+    let email = user.claims.get("email").and_then(|v| v.as_str());
+    let Some((name_id_format, name_id)) = resolve_name_id(format, &user.subject, email) else {
+        return reply(&build_response_xml(&state.config.issuer, &sp.acs_url, in_response_to, Err(STATUS_INVALID_NAME_ID_POLICY)));
+    };
+
+    let session_id = new_id();
+    let assertion_id = new_id();
+    let assertion_xml = build_assertion_xml(&AssertionParams {
+        assertion_id: &assertion_id,
+        issuer: &state.config.issuer,
+        sp_entity_id,
+        acs_url: &sp.acs_url,
+        name_id: &name_id,
+        name_id_format: &name_id_format,
+        session_id: &session_id,
+        in_response_to,
+        ttl_secs: 3600,
+    });
+    let assertion = signer.sign(&assertion_xml, &assertion_id).and_then(|signed| {
+        match state.sp_keys.get(&sp.entity_id) {
+            Some(key) => encrypt_element(&signed, key).map(|e| encrypted_assertion(&e)),
+            None => Ok(signed),
+        }
+    });
+    let assertion = match assertion {
+        Ok(a) => a,
+        Err(e) => {
+            log(api, task_ctx, OX_LOG_ERROR, &format!("ox_security_idp: SAML assertion failed: {}", e));
+            return saml_error(api, task_ctx, 500, "server_error", "could not sign the assertion");
+        }
+    };
+
+    // Store SAML session
+    state.saml_sessions.insert(SamlSessionEntry {
+        session_id,
+        sp_entity_id: sp_entity_id.to_string(),
+        principal_id: user.subject.clone(),
+        name_id,
+        created_at: now_secs(),
+    });
+
+    reply(&build_response_xml(&state.config.issuer, &sp.acs_url, in_response_to, Ok(&assertion)));
+}
+
+/// A LogoutRequest ends the SP's sessions named by its SessionIndex values (or
+/// all of the NameID's sessions with that SP) and is answered with a signed
+/// LogoutResponse POSTed to the SP's SLO URL. The request must be signed by
+/// the SP's `signing_cert_pem` key: in `query` for HTTP-Redirect, enveloped
+/// otherwise. Without a SAMLRequest, the `session_id` parameter names one
+/// session to drop.
+fn handle_saml_slo(
+    state: &PluginState,
+    task_ctx: *mut c_void,
+    params: &HashMap<String, String>,
+    query: &str,
+    redirect_binding: bool,
+) {
+    let api = &state.api;
+    let (xml, req) = match read_saml_request(params, redirect_binding, "LogoutRequest") {
+        Some(Ok(request)) => request,
+        Some(Err(e)) => return saml_error(api, task_ctx, 400, "invalid_request", &e),
+        None => {
+            let session_id = params.get("session_id").map(String::as_str).unwrap_or("");
+            state.saml_sessions.remove(session_id);
+            return json_response(api, task_ctx, 200, "{}");
+        }
+    };
+    let (Some(signer), Some(sp)) = (
+        &state.saml_signer,
+        state.config.saml_sps.iter().find(|s| s.entity_id == req.issuer),
+    ) else {
+        return saml_error(api, task_ctx, 400, "unknown_sp", "SP not registered");
+    };
+    let Some(key) = state.sp_signing_keys.get(&sp.entity_id) else {
+        return saml_error(api, task_ctx, 400, "invalid_request", "SP has no signing certificate for LogoutRequests");
+    };
+    let verified = if redirect_binding { verify_redirect_query(query, key) } else { verify_enveloped(&xml, key) };
+    if let Err(e) = verified {
+        return saml_error(api, task_ctx, 400, "invalid_request", &format!("LogoutRequest signature: {}", e));
+    }
+    let Some(slo_url) = &sp.slo_url else {
+        return saml_error(api, task_ctx, 400, "invalid_request", "SP has no SingleLogoutService");
+    };
+
+    for session in state.saml_sessions.list() {
+        let ended = session.sp_entity_id == sp.entity_id && if req.session_indexes.is_empty() {
+            req.name_id.as_deref() == Some(session.name_id.as_str())
+        } else {
+            req.session_indexes.contains(&session.session_id)
+        };
+        if ended {
+            state.saml_sessions.remove(&session.session_id);
+        }
+    }
+
+    let response_id = new_id();
+    let response = build_logout_response_xml(&response_id, &state.config.issuer, slo_url, &req.id, STATUS_SUCCESS);
+    match signer.sign(&response, &response_id) {
+        Ok(signed) => {
+            let relay_state = params.get("RelayState").map(String::as_str).unwrap_or("");
+            html_response(api, task_ctx, 200, &build_saml_post_form(slo_url, &signed, relay_state));
+        }
+        Err(e) => {
+            log(api, task_ctx, OX_LOG_ERROR, &format!("ox_security_idp: LogoutResponse failed: {}", e));
+            saml_error(api, task_ctx, 500, "server_error", "could not sign the LogoutResponse");
+        }
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -108,9 +336,6 @@ fn dispatch(state: &PluginState, task_ctx: *mut c_void) {
 
     let segs: Vec<&str> = path.trim_start_matches('/').split('/').collect();
 
-    // Extract bearer principal once for reuse in routes that need it.
-    let principal_id = extract_bearer_principal(state, task_ctx);
-
     match (
         method.as_str(),
         segs.get(0).copied(),
@@ -120,7 +345,8 @@ fn dispatch(state: &PluginState, task_ctx: *mut c_void) {
     ) {
         // GET /oauth2/authorize
         ("GET", Some("oauth2"), Some("authorize"), None, None) => {
-            match handle_authorize(&state.config, &state.code_store, &query, principal_id.as_deref()) {
+            let user = authenticated_user(state, task_ctx);
+            match handle_authorize(&state.config, &*state.store, &query, user.as_ref()) {
                 Ok(location) => redirect_response(api, task_ctx, &location),
                 Err(e) => json_response(api, task_ctx, e.status, &e.to_json()),
             }
@@ -128,14 +354,11 @@ fn dispatch(state: &PluginState, task_ctx: *mut c_void) {
 
         // POST /oauth2/token
         ("POST", Some("oauth2"), Some("token"), None, None) => {
-            let (status, resp_body) = handle_token(
-                &state.config,
-                &state.enc_key,
-                &state.code_store,
-                &state.token_store,
-                &state.refresh_store,
-                &body,
-            );
+            let key = match active_signing_key(&*state.store) {
+                Ok(k) => k,
+                Err(e) => return server_error(api, task_ctx, "signing key", &e),
+            };
+            let (status, resp_body) = handle_token(&state.config, &key, &*state.store, &body);
             json_response(api, task_ctx, status, &resp_body);
         }
 
@@ -149,10 +372,10 @@ fn dispatch(state: &PluginState, task_ctx: *mut c_void) {
                 })
                 .collect();
             let token = params.get("token").copied().unwrap_or("");
-            let active_tokens = state.token_store.list_active();
-            let found = active_tokens
-                .iter()
-                .find(|e| e.raw_jwt.as_deref() == Some(token));
+            let found = match state.store.find_active_token(token) {
+                Ok(found) => found,
+                Err(e) => return server_error(api, task_ctx, "token store", &e),
+            };
             let resp = match found {
                 Some(e) => serde_json::json!({
                     "active": true,
@@ -169,19 +392,10 @@ fn dispatch(state: &PluginState, task_ctx: *mut c_void) {
 
         // POST /oauth2/revoke
         ("POST", Some("oauth2"), Some("revoke"), None, None) => {
-            let params: std::collections::HashMap<&str, &str> = body
-                .split('&')
-                .filter_map(|p| {
-                    let mut kv = p.splitn(2, '=');
-                    Some((kv.next()?, kv.next()?))
-                })
-                .collect();
-            let token = params.get("token").copied().unwrap_or("");
-            let active_tokens = state.token_store.list_active();
-            if let Some(e) = active_tokens.iter().find(|e| e.raw_jwt.as_deref() == Some(token)) {
-                state.token_store.revoke(&e.jti);
+            match handle_revoke(&*state.store, &body) {
+                Ok(()) => json_response(api, task_ctx, 200, "{}"),
+                Err(e) => json_response(api, task_ctx, e.status, &e.to_json()),
             }
-            json_response(api, task_ctx, 200, "{}");
         }
 
         // GET /oidc/.well-known/openid-configuration
@@ -192,8 +406,24 @@ fn dispatch(state: &PluginState, task_ctx: *mut c_void) {
 
         // GET /oidc/jwks.json
         ("GET", Some("oidc"), Some("jwks.json"), None, None) => {
-            // TODO: derive public key JWK (n, e) from config.rsa_private_key_pem using the rsa crate
-            json_response(api, task_ctx, 200, r#"{"keys":[]}"#);
+            let jwks = state.store.list_signing_keys()
+                .and_then(|keys| build_jwks(&keys, state.config.key_retention_secs(), now_secs()));
+            match jwks {
+                Ok(jwks) => json_response(api, task_ctx, 200, &jwks),
+                Err(e) => server_error(api, task_ctx, "jwks", &e),
+            }
+        }
+
+        // GET|POST /oidc/userinfo
+        ("GET" | "POST", Some("oidc"), Some("userinfo"), None, None) => {
+            let auth_header = get_field(api, task_ctx, "request.header.Authorization");
+            match handle_userinfo(&*state.store, &auth_header) {
+                Ok(claims) => json_response(api, task_ctx, 200, &claims),
+                Err(e) => {
+                    json_response(api, task_ctx, e.status, &e.to_json());
+                    set_field(api, task_ctx, "response.header.WWW-Authenticate", &e.www_authenticate());
+                }
+            }
         }
 
         // GET /saml/{tenant}/metadata
@@ -202,78 +432,23 @@ fn dispatch(state: &PluginState, task_ctx: *mut c_void) {
             let tenant_seg = segs.get(1).copied().unwrap_or("");
             let sso_url = format!("{}/saml/{}/sso", issuer, tenant_seg);
             let slo_url = format!("{}/saml/{}/slo", issuer, tenant_seg);
-            let xml = build_metadata_xml(issuer, &sso_url, &slo_url, &state.cert_b64);
+            let cert_b64 = state.saml_signer.as_ref().map(|s| s.cert_b64.as_str()).unwrap_or("");
+            let xml = build_metadata_xml(issuer, &sso_url, &slo_url, cert_b64);
             xml_response(api, task_ctx, 200, &xml);
         }
 
-        // POST /saml/{tenant}/sso
-        ("POST", Some("saml"), Some(_tenant), Some("sso"), None) => {
-            let params: std::collections::HashMap<&str, &str> = body
-                .split('&')
-                .filter_map(|p| {
-                    let mut kv = p.splitn(2, '=');
-                    Some((kv.next()?, kv.next()?))
-                })
-                .collect();
-            let sp_entity_id = params.get("sp_entity_id").copied().unwrap_or("");
-            let name_id = params.get("name_id").copied().unwrap_or("");
-            let relay_state = params.get("RelayState").copied().unwrap_or("");
-
-            // Reject unauthenticated requests
-            if name_id.is_empty() {
-                json_response(&state.api, task_ctx, 401,
-                    r#"{"error":"unauthenticated","error_description":"name_id required"}"#);
-                return;
-            }
-
-            // Only issue assertions for registered SPs
-            let sp = match state.config.saml_sps.iter().find(|s| s.entity_id == sp_entity_id) {
-                Some(s) => s,
-                None => {
-                    json_response(&state.api, task_ctx, 400,
-                        r#"{"error":"unknown_sp","error_description":"SP not registered"}"#);
-                    return;
-                }
-            };
-            let acs_url = sp.acs_url.as_str();
-
-            let session_id = uuid::Uuid::new_v4().to_string();
-            let assertion_id = uuid::Uuid::new_v4().to_string();
-            let assertion_xml = build_assertion_xml(
-                &assertion_id,
-                &state.config.issuer,
-                sp_entity_id,
-                acs_url,
-                name_id,
-                &session_id,
-                3600,
-            );
-
-            // Store SAML session
-            state.saml_sessions.insert(SamlSessionEntry {
-                session_id: session_id.clone(),
-                sp_entity_id: sp_entity_id.to_string(),
-                principal_id: principal_id.clone().unwrap_or_default(),
-                name_id: name_id.to_string(),
-                created_at: now_secs(),
-            });
-
-            let form = build_saml_post_form(acs_url, &assertion_xml, relay_state);
-            html_response(api, task_ctx, 200, &form);
+        // GET /saml/{tenant}/sso (HTTP-Redirect), POST /saml/{tenant}/sso (HTTP-POST)
+        ("GET" | "POST", Some("saml"), Some(tenant), Some("sso"), None) => {
+            let redirect_binding = method == "GET";
+            let params = parse_form(if redirect_binding { &query } else { &body });
+            handle_saml_sso(state, task_ctx, tenant, &params, redirect_binding);
         }
 
-        // POST /saml/{tenant}/slo
-        ("POST", Some("saml"), Some(_tenant), Some("slo"), None) => {
-            let params: std::collections::HashMap<&str, &str> = body
-                .split('&')
-                .filter_map(|p| {
-                    let mut kv = p.splitn(2, '=');
-                    Some((kv.next()?, kv.next()?))
-                })
-                .collect();
-            let session_id = params.get("session_id").copied().unwrap_or("");
-            state.saml_sessions.remove(session_id);
-            json_response(api, task_ctx, 200, "{}");
+        // GET /saml/{tenant}/slo (HTTP-Redirect), POST /saml/{tenant}/slo (HTTP-POST)
+        ("GET" | "POST", Some("saml"), Some(_tenant), Some("slo"), None) => {
+            let redirect_binding = method == "GET";
+            let params = parse_form(if redirect_binding { &query } else { &body });
+            handle_saml_slo(state, task_ctx, &params, &query, redirect_binding);
         }
 
         // Admin routes — perimeter authentication is enforced by the ox_security_pipeline plugin
@@ -291,8 +466,13 @@ fn dispatch(state: &PluginState, task_ctx: *mut c_void) {
         ("GET", Some("api"), Some("v1"), Some("admin"), Some("idp"))
             if segs.get(4).copied() == Some("tokens") =>
         {
-            let data = serde_json::json!({ "data": state.token_store.list_active() });
-            json_response(api, task_ctx, 200, &data.to_string());
+            match state.store.list_active_tokens() {
+                Ok(tokens) => {
+                    let data = serde_json::json!({ "data": tokens });
+                    json_response(api, task_ctx, 200, &data.to_string());
+                }
+                Err(e) => server_error(api, task_ctx, "token store", &e),
+            }
         }
 
         // Admin routes: DELETE /api/v1/admin/idp/tokens/{jti}
@@ -303,13 +483,52 @@ fn dispatch(state: &PluginState, task_ctx: *mut c_void) {
             if jti.is_empty() {
                 json_response(api, task_ctx, 400,
                     r#"{"error":{"code":"INVALID_REQUEST","message":"missing token jti"}}"#);
+            } else if let Err(e) = state.store.revoke_token(jti) {
+                server_error(api, task_ctx, "token store", &e);
             } else {
-                state.token_store.revoke(jti);
                 let data = serde_json::json!({ "data": { "revoked": true } });
                 json_response(api, task_ctx, 200, &data.to_string());
             }
         }
 
+        // Admin routes: GET /api/v1/admin/idp/keys
+        ("GET", Some("api"), Some("v1"), Some("admin"), Some("idp"))
+            if segs.get(4).copied() == Some("keys") && segs.get(5).is_none() =>
+        {
+            match state.store.list_signing_keys() {
+                Ok(keys) => {
+                    // Never the private key
+                    let keys: Vec<_> = keys.iter().map(|k| serde_json::json!({
+                        "kid": k.kid,
+                        "status": k.status,
+                        "created_at": k.created_at,
+                        "rotated_at": k.rotated_at,
+                    })).collect();
+                    let data = serde_json::json!({ "data": keys });
+                    json_response(api, task_ctx, 200, &data.to_string());
+                }
+                Err(e) => server_error(api, task_ctx, "token store", &e),
+            }
+        }
+
+        // Admin routes: POST /api/v1/admin/idp/keys/rotate
+        ("POST", Some("api"), Some("v1"), Some("admin"), Some("idp"))
+            if segs.get(4).copied() == Some("keys") && segs.get(5).copied() == Some("rotate") =>
+        {
+            let rotated = generate_key_pem().and_then(|pem| {
+                rotate_signing_key(&*state.store, &pem, state.config.key_retention_secs(), now_secs())
+            });
+            match rotated {
+                Ok(kid) => {
+                    log(api, task_ctx, OX_LOG_INFO,
+                        &format!("ox_security_idp: rotated JWT signing key, new kid '{}'", kid));
+                    let data = serde_json::json!({ "data": { "kid": kid } });
+                    json_response(api, task_ctx, 200, &data.to_string());
+                }
+                Err(e) => server_error(api, task_ctx, "key rotation", &e),
+            }
+        }
+
         // Admin routes: GET /api/v1/admin/idp/sessions
         ("GET", Some("api"), Some("v1"), Some("admin"), Some("idp"))
             if segs.get(4).copied() == Some("sessions") =>
@@ -384,7 +603,7 @@ pub extern "C" fn ox_plugin_init(
         }
     };
 
-    let enc_key = match build_encoding_key(&config.rsa_private_key_pem) {
+    let rsa_key = match read_rsa_key(&config.rsa_private_key_pem) {
         Ok(k) => k,
         Err(e) => {
             log(
@@ -397,8 +616,84 @@ pub extern "C" fn ox_plugin_init(
         }
     };
 
-    // Extract base64-encoded cert for SAML metadata (stub: empty string if not extractable)
-    let cert_b64 = extract_cert_b64_from_pem(&config.rsa_private_key_pem);
+    let store = match open_token_store(&config.tenant_id, config.store.as_ref()) {
+        Ok(s) => s,
+        Err(e) => {
+            log(
+                &api,
+                std::ptr::null_mut(),
+                OX_LOG_ERROR,
+                &format!("ox_security_idp: token store: {}", e),
+            );
+            return std::ptr::null_mut();
+        }
+    };
+
+    if let Err(e) = ensure_signing_key(
+        &*store,
+        &config.rsa_private_key_pem,
+        config.key_retention_secs(),
+        now_secs(),
+    ) {
+        log(
+            &api,
+            std::ptr::null_mut(),
+            OX_LOG_ERROR,
+            &format!("ox_security_idp: signing key: {}", e),
+        );
+        return std::ptr::null_mut();
+    }
+
+    let saml_signer = match &config.saml_signing_cert_pem {
+        Some(cert_pem) => match SamlSigner::new(rsa_key, cert_pem) {
+            Ok(s) => Some(s),
+            Err(e) => {
+                log(
+                    &api,
+                    std::ptr::null_mut(),
+                    OX_LOG_ERROR,
+                    &format!("ox_security_idp: saml_signing_cert_pem: {}", e),
+                );
+                return std::ptr::null_mut();
+            }
+        },
+        None if !config.saml_sps.is_empty() => {
+            log(
+                &api,
+                std::ptr::null_mut(),
+                OX_LOG_ERROR,
+                "ox_security_idp: saml_signing_cert_pem is required when saml_sps are configured",
+            );
+            return std::ptr::null_mut();
+        }
+        None => None,
+    };
+
+    let mut sp_keys = HashMap::new();
+    let mut sp_signing_keys = HashMap::new();
+    for sp in &config.saml_sps {
+        let certs = [
+            ("encryption_cert_pem", &sp.encryption_cert_pem, &mut sp_keys),
+            ("signing_cert_pem", &sp.signing_cert_pem, &mut sp_signing_keys),
+        ];
+        for (field, cert_pem, keys) in certs {
+            let Some(cert_pem) = cert_pem else { continue };
+            match read_rsa_cert(cert_pem) {
+                Ok((_, key)) => {
+                    keys.insert(sp.entity_id.clone(), key);
+                }
+                Err(e) => {
+                    log(
+                        &api,
+                        std::ptr::null_mut(),
+                        OX_LOG_ERROR,
+                        &format!("ox_security_idp: {} for SP '{}': {}", field, sp.entity_id, e),
+                    );
+                    return std::ptr::null_mut();
+                }
+            }
+        }
+    }
 
     log(
         &api,
@@ -413,12 +708,11 @@ pub extern "C" fn ox_plugin_init(
     let state = PluginState {
         api,
         config,
-        enc_key,
-        code_store: AuthCodeStore::new(),
-        token_store: TokenStore::new(),
-        refresh_store: RefreshTokenStore::new(),
+        store,
         saml_sessions: SamlSessionStore::new(),
-        cert_b64,
+        saml_signer,
+        sp_keys,
+        sp_signing_keys,
     };
 
     Box::into_raw(Box::new(state)) as *mut c_void
@@ -460,14 +754,3 @@ pub extern "C" fn ox_plugin_destroy(plugin_ctx: *mut c_void) {
         }
     }
 }
-
-// ---------------------------------------------------------------------------
-// Internal helpers
-// ---------------------------------------------------------------------------
-
-/// Attempt to extract the public-key DER as base64 from a PEM-encoded RSA private key.
-/// Returns an empty string on any failure (graceful degradation for SAML metadata).
-fn extract_cert_b64_from_pem(_pem: &str) -> String {
-    // TODO: extract Base64-encoded DER public cert from RSA key for SAML metadata
-    String::new()
-}
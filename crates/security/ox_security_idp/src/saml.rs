@@ -1,5 +1,22 @@
+use std::io::Read;
 use std::time::{SystemTime, UNIX_EPOCH};
 use base64ct::{Base64, Encoding};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use crate::xmldsig::{c14n_attr, c14n_text};
+
+pub const NS_ASSERTION: &str = "urn:oasis:names:tc:SAML:2.0:assertion";
+pub const NS_PROTOCOL: &str = "urn:oasis:names:tc:SAML:2.0:protocol";
+pub const BINDING_POST: &str = "urn:oasis:names:tc:SAML:2.0:bindings:HTTP-POST";
+pub const BINDING_REDIRECT: &str = "urn:oasis:names:tc:SAML:2.0:bindings:HTTP-Redirect";
+pub const STATUS_SUCCESS: &str = "urn:oasis:names:tc:SAML:2.0:status:Success";
+pub const STATUS_REQUESTER: &str = "urn:oasis:names:tc:SAML:2.0:status:Requester";
+pub const STATUS_NO_PASSIVE: &str = "urn:oasis:names:tc:SAML:2.0:status:NoPassive";
+pub const STATUS_INVALID_NAME_ID_POLICY: &str = "urn:oasis:names:tc:SAML:2.0:status:InvalidNameIDPolicy";
+pub const NAMEID_EMAIL: &str = "urn:oasis:names:tc:SAML:1.1:nameid-format:emailAddress";
+pub const NAMEID_PERSISTENT: &str = "urn:oasis:names:tc:SAML:2.0:nameid-format:persistent";
+pub const NAMEID_UNSPECIFIED: &str = "urn:oasis:names:tc:SAML:1.1:nameid-format:unspecified";
 
 pub fn xml_escape(s: &str) -> String {
     let mut out = String::with_capacity(s.len());
@@ -16,15 +33,15 @@ pub fn xml_escape(s: &str) -> String {
     out
 }
 
-fn now_iso8601() -> String {
-    let secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
-    let days_since_epoch = secs / 86400;
+fn iso8601(secs: u64) -> String {
+    let (year, month, day) = epoch_days_to_ymd(secs / 86400);
     let time_of_day = secs % 86400;
-    let (year, month, day) = epoch_days_to_ymd(days_since_epoch);
-    let h = time_of_day / 3600;
-    let m = (time_of_day % 3600) / 60;
-    let s = time_of_day % 60;
-    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, h, m, s)
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day,
+        time_of_day / 3600, (time_of_day % 3600) / 60, time_of_day % 60)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
 }
 
 fn epoch_days_to_ymd(days: u64) -> (u32, u32, u32) {
@@ -46,6 +63,11 @@ fn epoch_days_to_ymd(days: u64) -> (u32, u32, u32) {
     (year as u32, (month + 1) as u32, (d + 1) as u32)
 }
 
+/// A fresh message ID. xs:ID values may not start with a digit.
+pub fn new_id() -> String {
+    format!("_{}", uuid::Uuid::new_v4().simple())
+}
+
 pub fn build_metadata_xml(entity_id: &str, sso_url: &str, slo_url: &str, cert_b64: &str) -> String {
     format!(
         r#"<?xml version="1.0" encoding="UTF-8"?>
@@ -60,9 +82,13 @@ pub fn build_metadata_xml(entity_id: &str, sso_url: &str, slo_url: &str, cert_b6
         </ds:X509Data>
       </ds:KeyInfo>
     </md:KeyDescriptor>
-    <md:SingleLogoutService Binding="urn:oasis:names:tc:SAML:2.0:bindings:HTTP-POST"
+    <md:SingleLogoutService Binding="{post}"
+        Location="{slo_url}"/>
+    <md:SingleLogoutService Binding="{redirect}"
         Location="{slo_url}"/>
-    <md:SingleSignOnService Binding="urn:oasis:names:tc:SAML:2.0:bindings:HTTP-POST"
+    <md:SingleSignOnService Binding="{post}"
+        Location="{sso_url}"/>
+    <md:SingleSignOnService Binding="{redirect}"
         Location="{sso_url}"/>
   </md:IDPSSODescriptor>
 </md:EntityDescriptor>"#,
@@ -70,63 +96,131 @@ pub fn build_metadata_xml(entity_id: &str, sso_url: &str, slo_url: &str, cert_b6
         cert_b64 = cert_b64,
         sso_url = xml_escape(sso_url),
         slo_url = xml_escape(slo_url),
+        post = BINDING_POST,
+        redirect = BINDING_REDIRECT,
+    )
+}
+
+pub struct AssertionParams<'a> {
+    pub assertion_id: &'a str,
+    pub issuer: &'a str,
+    pub sp_entity_id: &'a str,
+    pub acs_url: &'a str,
+    pub name_id: &'a str,
+    pub name_id_format: &'a str,
+    pub session_id: &'a str,
+    /// ID of the AuthnRequest answered; `None` for IdP-initiated SSO.
+    pub in_response_to: Option<&'a str>,
+    pub ttl_secs: u64,
+}
+
+/// The assertion in exclusive canonical form, ready for `SamlSigner::sign`.
+pub fn build_assertion_xml(p: &AssertionParams) -> String {
+    let secs = now_secs();
+    let now = iso8601(secs);
+    let not_after = iso8601(secs + p.ttl_secs);
+    let in_response_to = p.in_response_to
+        .map(|id| format!(" InResponseTo=\"{}\"", c14n_attr(id)))
+        .unwrap_or_default();
+    format!(
+        "<saml:Assertion xmlns:saml=\"{ns}\" ID=\"{id}\" IssueInstant=\"{now}\" Version=\"2.0\">\
+         <saml:Issuer>{issuer}</saml:Issuer>\
+         <saml:Subject>\
+         <saml:NameID Format=\"{format}\">{name_id}</saml:NameID>\
+         <saml:SubjectConfirmation Method=\"urn:oasis:names:tc:SAML:2.0:cm:bearer\">\
+         <saml:SubjectConfirmationData{in_response_to} NotOnOrAfter=\"{not_after}\" Recipient=\"{acs_url}\"></saml:SubjectConfirmationData>\
+         </saml:SubjectConfirmation>\
+         </saml:Subject>\
+         <saml:Conditions NotBefore=\"{now}\" NotOnOrAfter=\"{not_after}\">\
+         <saml:AudienceRestriction><saml:Audience>{audience}</saml:Audience></saml:AudienceRestriction>\
+         </saml:Conditions>\
+         <saml:AuthnStatement AuthnInstant=\"{now}\" SessionIndex=\"{session_id}\">\
+         <saml:AuthnContext>\
+         <saml:AuthnContextClassRef>urn:oasis:names:tc:SAML:2.0:ac:classes:PasswordProtectedTransport</saml:AuthnContextClassRef>\
+         </saml:AuthnContext>\
+         </saml:AuthnStatement>\
+         </saml:Assertion>",
+        ns = NS_ASSERTION,
+        id = c14n_attr(p.assertion_id),
+        now = now,
+        issuer = c14n_text(p.issuer),
+        format = c14n_attr(p.name_id_format),
+        name_id = c14n_text(p.name_id),
+        in_response_to = in_response_to,
+        not_after = not_after,
+        acs_url = c14n_attr(p.acs_url),
+        audience = c14n_text(p.sp_entity_id),
+        session_id = c14n_attr(p.session_id),
     )
 }
 
-pub fn build_assertion_xml(
-    assertion_id: &str,
+/// A `samlp:Response` to `destination` carrying the (signed or encrypted)
+/// assertion element, or without one a `Requester` error whose second-level
+/// status is `failure`.
+pub fn build_response_xml(
     issuer: &str,
-    sp_entity_id: &str,
-    acs_url: &str,
-    name_id: &str,
-    session_id: &str,
-    ttl_secs: u64,
+    destination: &str,
+    in_response_to: Option<&str>,
+    assertion: Result<&str, &str>,
 ) -> String {
-    let now = now_iso8601();
-    let secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
-    let not_on_or_after_secs = secs + ttl_secs;
-    let not_after = {
-        let days = not_on_or_after_secs / 86400;
-        let time = not_on_or_after_secs % 86400;
-        let (y, mo, d) = epoch_days_to_ymd(days);
-        format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", y, mo, d, time/3600, (time%3600)/60, time%60)
+    let (status, assertion) = match assertion {
+        Ok(a) => (format!("<samlp:StatusCode Value=\"{}\"/>", STATUS_SUCCESS), a),
+        Err(failure) => (format!(
+            "<samlp:StatusCode Value=\"{}\"><samlp:StatusCode Value=\"{}\"/></samlp:StatusCode>",
+            STATUS_REQUESTER, xml_escape(failure)), ""),
     };
-
+    let in_response_to = in_response_to
+        .map(|id| format!(" InResponseTo=\"{}\"", xml_escape(id)))
+        .unwrap_or_default();
     format!(
-        r#"<saml:Assertion xmlns:saml="urn:oasis:names:tc:SAML:2.0:assertion"
-    ID="{assertion_id}" Version="2.0" IssueInstant="{now}">
-  <saml:Issuer>{issuer}</saml:Issuer>
-  <saml:Subject>
-    <saml:NameID Format="urn:oasis:names:tc:SAML:1.1:nameid-format:emailAddress">{name_id}</saml:NameID>
-    <saml:SubjectConfirmation Method="urn:oasis:names:tc:SAML:2.0:cm:bearer">
-      <saml:SubjectConfirmationData NotOnOrAfter="{not_after}"
-          Recipient="{acs_url}"/>
-    </saml:SubjectConfirmation>
-  </saml:Subject>
-  <saml:Conditions NotBefore="{now}" NotOnOrAfter="{not_after}">
-    <saml:AudienceRestriction>
-      <saml:Audience>{sp_entity_id}</saml:Audience>
-    </saml:AudienceRestriction>
-  </saml:Conditions>
-  <saml:AuthnStatement AuthnInstant="{now}" SessionIndex="{session_id}">
-    <saml:AuthnContext>
-      <saml:AuthnContextClassRef>urn:oasis:names:tc:SAML:2.0:ac:classes:Password</saml:AuthnContextClassRef>
-    </saml:AuthnContext>
-  </saml:AuthnStatement>
-</saml:Assertion>"#,
-        assertion_id = xml_escape(assertion_id),
-        now = now,
+        "<samlp:Response xmlns:samlp=\"{ns_p}\" xmlns:saml=\"{ns_a}\" Destination=\"{dest}\" ID=\"{id}\"{irt} IssueInstant=\"{now}\" Version=\"2.0\">\
+         <saml:Issuer>{issuer}</saml:Issuer>\
+         <samlp:Status>{status}</samlp:Status>\
+         {assertion}</samlp:Response>",
+        ns_p = NS_PROTOCOL,
+        ns_a = NS_ASSERTION,
+        dest = xml_escape(destination),
+        id = new_id(),
+        irt = in_response_to,
+        now = iso8601(now_secs()),
         issuer = xml_escape(issuer),
-        name_id = xml_escape(name_id),
-        acs_url = xml_escape(acs_url),
-        sp_entity_id = xml_escape(sp_entity_id),
-        not_after = not_after,
-        session_id = xml_escape(session_id),
+        status = status,
+        assertion = assertion,
     )
 }
 
-pub fn build_saml_post_form(acs_url: &str, assertion_xml: &str, relay_state: &str) -> String {
-    let encoded = Base64::encode_string(assertion_xml.as_bytes());
+/// Wrap an `xenc:EncryptedData` for a response.
+pub fn encrypted_assertion(encrypted_data: &str) -> String {
+    format!("<saml:EncryptedAssertion>{}</saml:EncryptedAssertion>", encrypted_data)
+}
+
+/// A `samlp:LogoutResponse` in exclusive canonical form, ready for
+/// `SamlSigner::sign` with `response_id`.
+pub fn build_logout_response_xml(
+    response_id: &str,
+    issuer: &str,
+    destination: &str,
+    in_response_to: &str,
+    status: &str,
+) -> String {
+    format!(
+        "<samlp:LogoutResponse xmlns:samlp=\"{ns_p}\" Destination=\"{dest}\" ID=\"{id}\" InResponseTo=\"{irt}\" IssueInstant=\"{now}\" Version=\"2.0\">\
+         <saml:Issuer xmlns:saml=\"{ns_a}\">{issuer}</saml:Issuer>\
+         <samlp:Status><samlp:StatusCode Value=\"{status}\"></samlp:StatusCode></samlp:Status>\
+         </samlp:LogoutResponse>",
+        ns_p = NS_PROTOCOL,
+        ns_a = NS_ASSERTION,
+        dest = c14n_attr(destination),
+        id = c14n_attr(response_id),
+        irt = c14n_attr(in_response_to),
+        now = iso8601(now_secs()),
+        issuer = c14n_text(issuer),
+        status = c14n_attr(status),
+    )
+}
+
+pub fn build_saml_post_form(acs_url: &str, message_xml: &str, relay_state: &str) -> String {
+    let encoded = Base64::encode_string(message_xml.as_bytes());
     format!(
         r#"<!DOCTYPE html><html><body>
 <form method="post" action="{acs_url}">
@@ -146,6 +240,155 @@ pub fn build_saml_post_form(acs_url: &str, assertion_xml: &str, relay_state: &st
     )
 }
 
+/// NameID format and value for a user. An explicit format (requested by the SP
+/// or configured for it) must be satisfiable; without one, or with
+/// `unspecified`, the email address is used when known and the persistent
+/// subject otherwise.
+pub fn resolve_name_id(format: Option<&str>, subject: &str, email: Option<&str>) -> Option<(String, String)> {
+    match format {
+        Some(NAMEID_EMAIL) => email.map(|e| (NAMEID_EMAIL.to_string(), e.to_string())),
+        Some(NAMEID_PERSISTENT) => Some((NAMEID_PERSISTENT.to_string(), subject.to_string())),
+        Some(NAMEID_UNSPECIFIED) | None => Some(match email {
+            Some(e) => (NAMEID_EMAIL.to_string(), e.to_string()),
+            None => (NAMEID_PERSISTENT.to_string(), subject.to_string()),
+        }),
+        Some(_) => None,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Incoming requests
+// ---------------------------------------------------------------------------
+
+/// Largest inflated HTTP-Redirect message accepted.
+const MAX_REQUEST_BYTES: u64 = 64 * 1024;
+
+/// Decode a `SAMLRequest` parameter: base64, and for the HTTP-Redirect binding
+/// raw DEFLATE on top.
+pub fn decode_saml_param(value: &str, redirect_binding: bool) -> Result<String, String> {
+    let compact: String = value.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    let raw = Base64::decode_vec(&compact).map_err(|_| "SAMLRequest is not valid base64".to_string())?;
+    let xml = if redirect_binding {
+        let mut out = Vec::new();
+        flate2::read::DeflateDecoder::new(raw.as_slice())
+            .take(MAX_REQUEST_BYTES + 1)
+            .read_to_end(&mut out)
+            .map_err(|_| "SAMLRequest is not DEFLATE-compressed".to_string())?;
+        if out.len() as u64 > MAX_REQUEST_BYTES {
+            return Err("SAMLRequest too large".to_string());
+        }
+        out
+    } else {
+        raw
+    };
+    String::from_utf8(xml).map_err(|_| "SAMLRequest is not UTF-8".to_string())
+}
+
+/// The fields of an `AuthnRequest` or `LogoutRequest` the IdP acts on.
+/// Elements are matched by local name, so SP prefix choices do not matter.
+#[derive(Debug, Default)]
+pub struct SamlRequest {
+    /// Local name of the root element: `AuthnRequest` or `LogoutRequest`.
+    pub kind: String,
+    pub id: String,
+    pub issuer: String,
+    pub destination: Option<String>,
+    pub acs_url: Option<String>,
+    pub protocol_binding: Option<String>,
+    /// `NameIDPolicy` `Format` (AuthnRequest).
+    pub name_id_format: Option<String>,
+    pub is_passive: bool,
+    /// `NameID` (LogoutRequest).
+    pub name_id: Option<String>,
+    /// `SessionIndex` values (LogoutRequest).
+    pub session_indexes: Vec<String>,
+}
+
+fn local_name(e: &BytesStart) -> String {
+    String::from_utf8_lossy(e.local_name().as_ref()).into_owned()
+}
+
+fn attribute(e: &BytesStart, name: &str) -> Result<Option<String>, String> {
+    for attr in e.attributes() {
+        let attr = attr.map_err(|e| format!("malformed attribute: {}", e))?;
+        if attr.key.as_ref() == name.as_bytes() {
+            let value = attr.unescape_value().map_err(|e| format!("malformed attribute: {}", e))?;
+            return Ok(Some(value.into_owned()));
+        }
+    }
+    Ok(None)
+}
+
+pub fn parse_saml_request(xml: &str) -> Result<SamlRequest, String> {
+    let malformed = |e: quick_xml::Error| format!("malformed XML: {}", e);
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut req = SamlRequest::default();
+    let mut depth = 0usize;
+    let mut text = String::new();
+
+    loop {
+        let event = reader.read_event().map_err(malformed)?;
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let name = local_name(e);
+                if depth == 0 {
+                    if attribute(e, "Version")?.as_deref() != Some("2.0") {
+                        return Err("unsupported SAML version".to_string());
+                    }
+                    req.id = attribute(e, "ID")?.unwrap_or_default();
+                    req.destination = attribute(e, "Destination")?;
+                    req.acs_url = attribute(e, "AssertionConsumerServiceURL")?;
+                    req.protocol_binding = attribute(e, "ProtocolBinding")?;
+                    req.is_passive = matches!(attribute(e, "IsPassive")?.as_deref(), Some("true" | "1"));
+                    req.kind = name.clone();
+                } else if depth == 1 && name == "NameIDPolicy" {
+                    req.name_id_format = attribute(e, "Format")?;
+                }
+                if matches!(event, Event::Start(_)) {
+                    depth += 1;
+                    text.clear();
+                }
+            }
+            Event::Text(t) => {
+                text.push_str(&t.decode().map_err(|e| format!("malformed text: {}", e))?);
+            }
+            Event::GeneralRef(r) => {
+                if let Some(ch) = r.resolve_char_ref().map_err(|e| format!("malformed reference: {}", e))? {
+                    text.push(ch);
+                } else {
+                    let name = r.decode().map_err(|e| format!("malformed reference: {}", e))?;
+                    let resolved = quick_xml::escape::resolve_predefined_entity(&name)
+                        .ok_or_else(|| format!("unknown entity '&{};'", name))?;
+                    text.push_str(resolved);
+                }
+            }
+            Event::End(ref e) => {
+                depth = depth.saturating_sub(1);
+                let value = text.trim().to_string();
+                if depth == 1 {
+                    match String::from_utf8_lossy(e.local_name().as_ref()).as_ref() {
+                        "Issuer" => req.issuer = value,
+                        "NameID" => req.name_id = Some(value),
+                        "SessionIndex" => req.session_indexes.push(value),
+                        _ => {}
+                    }
+                }
+                text.clear();
+            }
+            Event::DocType(_) => return Err("DTDs are not allowed".to_string()),
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if req.kind.is_empty() || req.id.is_empty() || req.issuer.is_empty() {
+        return Err("request needs an ID and an Issuer".to_string());
+    }
+    Ok(req)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -173,12 +416,109 @@ mod tests {
         assert!(meta.contains("EntityDescriptor"));
     }
 
+    fn params<'a>(name_id: &'a str, in_response_to: Option<&'a str>) -> AssertionParams<'a> {
+        AssertionParams {
+            assertion_id: "_id1",
+            issuer: "https://idp.example.com",
+            sp_entity_id: "urn:sp",
+            acs_url: "https://sp.example.com/acs",
+            name_id,
+            name_id_format: NAMEID_EMAIL,
+            session_id: "s1",
+            in_response_to,
+            ttl_secs: 3600,
+        }
+    }
+
     #[test]
     fn test_assertion_xml_escapes_name_id() {
-        let xml = build_assertion_xml("id1", "https://idp.example.com",
-            "urn:sp", "https://sp.example.com/acs", "<injected>", "s1", 3600);
+        let xml = build_assertion_xml(&params("<injected>", None));
         assert!(xml.contains("&lt;injected&gt;"));
         assert!(!xml.contains("<injected>"));
+        assert!(!xml.contains("InResponseTo"));
+    }
+
+    #[test]
+    fn test_assertion_answers_authn_request() {
+        let xml = build_assertion_xml(&params("alice@example.com", Some("_req1")));
+        assert!(xml.starts_with("<saml:Assertion xmlns:saml=\"urn:oasis:names:tc:SAML:2.0:assertion\" ID=\"_id1\""));
+        assert!(xml.contains("<saml:SubjectConfirmationData InResponseTo=\"_req1\" NotOnOrAfter="));
+        assert!(!xml.contains("/>"), "canonical form has no empty-element tags");
+    }
+
+    #[test]
+    fn test_error_response_nests_status() {
+        let xml = build_response_xml("https://idp.example.com", "https://sp.example.com/acs",
+            Some("_req1"), Err(STATUS_NO_PASSIVE));
+        assert!(xml.contains(&format!("<samlp:StatusCode Value=\"{}\"><samlp:StatusCode Value=\"{}\"/>",
+            STATUS_REQUESTER, STATUS_NO_PASSIVE)));
+        assert!(xml.contains("InResponseTo=\"_req1\""));
+        assert!(!xml.contains("Assertion"));
+    }
+
+    fn deflate(xml: &str) -> String {
+        use std::io::Write;
+        let mut enc = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        enc.write_all(xml.as_bytes()).unwrap();
+        Base64::encode_string(&enc.finish().unwrap())
+    }
+
+    const AUTHN_REQUEST: &str = r#"<samlp:AuthnRequest xmlns:samlp="urn:oasis:names:tc:SAML:2.0:protocol"
+        xmlns:saml="urn:oasis:names:tc:SAML:2.0:assertion" ID="_abc" Version="2.0"
+        IssueInstant="2026-01-01T00:00:00Z" Destination="https://idp.example.com/saml/t1/sso"
+        AssertionConsumerServiceURL="https://sp.example.com/acs&amp;x"
+        ProtocolBinding="urn:oasis:names:tc:SAML:2.0:bindings:HTTP-POST" IsPassive="true">
+      <saml:Issuer>urn:sp</saml:Issuer>
+      <samlp:NameIDPolicy Format="urn:oasis:names:tc:SAML:2.0:nameid-format:persistent" AllowCreate="true"/>
+    </samlp:AuthnRequest>"#;
+
+    #[test]
+    fn test_parse_authn_request_both_bindings() {
+        for (value, redirect) in [(deflate(AUTHN_REQUEST), true), (Base64::encode_string(AUTHN_REQUEST.as_bytes()), false)] {
+            let req = parse_saml_request(&decode_saml_param(&value, redirect).unwrap()).unwrap();
+            assert_eq!(req.kind, "AuthnRequest");
+            assert_eq!(req.id, "_abc");
+            assert_eq!(req.issuer, "urn:sp");
+            assert_eq!(req.destination.as_deref(), Some("https://idp.example.com/saml/t1/sso"));
+            assert_eq!(req.acs_url.as_deref(), Some("https://sp.example.com/acs&x"));
+            assert_eq!(req.protocol_binding.as_deref(), Some(BINDING_POST));
+            assert_eq!(req.name_id_format.as_deref(), Some(NAMEID_PERSISTENT));
+            assert!(req.is_passive);
+        }
+        assert!(decode_saml_param("not base64!", false).is_err());
+        assert!(decode_saml_param(&Base64::encode_string(b"plain"), true).is_err());
+    }
+
+    #[test]
+    fn test_parse_logout_request() {
+        let xml = r#"<samlp:LogoutRequest xmlns:samlp="urn:oasis:names:tc:SAML:2.0:protocol" ID="_l1" Version="2.0">
+            <Issuer xmlns="urn:oasis:names:tc:SAML:2.0:assertion">urn:sp</Issuer>
+            <NameID xmlns="urn:oasis:names:tc:SAML:2.0:assertion">alice@example.com</NameID>
+            <samlp:SessionIndex>s1</samlp:SessionIndex><samlp:SessionIndex>s2</samlp:SessionIndex>
+        </samlp:LogoutRequest>"#;
+        let req = parse_saml_request(xml).unwrap();
+        assert_eq!((req.kind.as_str(), req.id.as_str(), req.issuer.as_str()), ("LogoutRequest", "_l1", "urn:sp"));
+        assert_eq!(req.name_id.as_deref(), Some("alice@example.com"));
+        assert_eq!(req.session_indexes, ["s1", "s2"]);
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_requests() {
+        let doctype = format!("<!DOCTYPE x [<!ENTITY e \"boom\">]>{}", AUTHN_REQUEST);
+        assert!(parse_saml_request(&doctype).is_err());
+        assert!(parse_saml_request(&AUTHN_REQUEST.replace("Version=\"2.0\"", "Version=\"1.1\"")).is_err());
+        assert!(parse_saml_request(&AUTHN_REQUEST.replace("ID=\"_abc\"", "")).is_err());
+    }
+
+    #[test]
+    fn test_resolve_name_id() {
+        let email = Some("alice@example.com");
+        assert_eq!(resolve_name_id(None, "u1", email).unwrap().1, "alice@example.com");
+        assert_eq!(resolve_name_id(None, "u1", None).unwrap(), (NAMEID_PERSISTENT.to_string(), "u1".to_string()));
+        assert_eq!(resolve_name_id(Some(NAMEID_PERSISTENT), "u1", email).unwrap().1, "u1");
+        assert_eq!(resolve_name_id(Some(NAMEID_UNSPECIFIED), "u1", email).unwrap().0, NAMEID_EMAIL);
+        assert!(resolve_name_id(Some(NAMEID_EMAIL), "u1", None).is_none());
+        assert!(resolve_name_id(Some("urn:oasis:names:tc:SAML:2.0:nameid-format:transient"), "u1", email).is_none());
     }
 
     #[test]
@@ -1,6 +1,17 @@
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::Aes256Gcm;
+use base64ct::{Base64, Encoding};
+use hkdf::Hkdf;
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::TokenStoreConfig;
+use crate::oauth2::now_secs;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenEntry {
@@ -10,76 +21,609 @@ pub struct TokenEntry {
     pub scope: String,
     pub expires_at: u64,
     pub revoked: bool,
-    pub raw_jwt: Option<String>, // The original signed JWT string, for introspect/revoke lookup
+    /// The original signed JWT string, for introspect/revoke lookup. Not
+    /// persisted: `OxPersistenceTokenStore` indexes its SHA-256 instead.
+    pub raw_jwt: Option<String>,
+    /// When the end user authenticated; `None` for client credentials.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_time: Option<u64>,
+    /// OIDC claims released for `scope`, served from userinfo.
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub claims: serde_json::Map<String, serde_json::Value>,
+    /// Access and refresh tokens descended from one authorization code share a
+    /// family, which is revoked as a whole when a refresh token is replayed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub family_id: Option<String>,
+    /// Refresh tokens only: set once the token has been exchanged.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub used: bool,
 }
 
-#[derive(Clone)]
-pub struct TokenStore {
-    inner: Arc<Mutex<HashMap<String, TokenEntry>>>,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthCodeEntry {
+    pub code: String,
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub scope: String,
+    pub principal_id: String,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    pub expires_at: u64,
+    pub nonce: Option<String>,
+    pub auth_time: u64,
+    pub claims: serde_json::Map<String, serde_json::Value>,
 }
 
-impl TokenStore {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SigningKeyStatus {
+    /// Signs new tokens.
+    Active,
+    /// Rotated out; still published so the tokens it signed verify.
+    Retiring,
+    /// No longer published.
+    Retired,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigningKeyRecord {
+    /// RFC 7638 thumbprint, published as the JWK and JWT header `kid`.
+    pub kid: String,
+    pub private_key_pem: String,
+    pub status: SigningKeyStatus,
+    pub created_at: u64,
+    /// When the key stopped being the active key.
+    pub rotated_at: Option<u64>,
+}
+
+// ---------------------------------------------------------------------------
+// TokenStore trait
+// ---------------------------------------------------------------------------
+
+/// Tokens, authorization codes and JWT signing keys for one tenant. Several
+/// nodes may share a store, so `use_refresh_token`, `consume_code` and
+/// `activate_signing_key` must be atomic.
+pub trait TokenStore: Send + Sync {
+    /// Apply schema migrations. Safe to call on every startup (idempotent).
+    fn migrate(&self) -> Result<(), String>;
+
+    // --- Access tokens ---
+    fn insert_token(&self, entry: &TokenEntry) -> Result<(), String>;
+    fn get_token(&self, jti: &str) -> Result<Option<TokenEntry>, String>;
+    /// The unrevoked, unexpired access token whose signed form is `raw_jwt`.
+    fn find_active_token(&self, raw_jwt: &str) -> Result<Option<TokenEntry>, String>;
+    fn revoke_token(&self, jti: &str) -> Result<(), String>;
+    fn list_active_tokens(&self) -> Result<Vec<TokenEntry>, String>;
+
+    // --- Refresh tokens, keyed by the SHA-256 of the token ---
+    fn insert_refresh_token(&self, entry: &TokenEntry) -> Result<(), String>;
+    fn get_refresh_token(&self, token_hash: &str) -> Result<Option<TokenEntry>, String>;
+    /// Mark the refresh token used and return it as it was before. One that
+    /// comes back already `used` has been replayed.
+    fn use_refresh_token(&self, token_hash: &str) -> Result<Option<TokenEntry>, String>;
+    /// Revoke every access and refresh token in `family_id`.
+    fn revoke_family(&self, family_id: &str) -> Result<(), String>;
+
+    // --- Authorization codes ---
+    fn insert_code(&self, entry: &AuthCodeEntry) -> Result<(), String>;
+    /// Remove and return the code, so it can be redeemed only once.
+    fn consume_code(&self, code: &str) -> Result<Option<AuthCodeEntry>, String>;
+
+    // --- Signing keys ---
+    fn list_signing_keys(&self) -> Result<Vec<SigningKeyRecord>, String>;
+    /// Store `key` as the active key. The previously active key becomes
+    /// `Retiring`, with `rotated_at` set to `key.created_at`.
+    fn activate_signing_key(&self, key: &SigningKeyRecord) -> Result<(), String>;
+    /// Retire keys that stopped signing before `before`.
+    fn retire_signing_keys(&self, before: u64) -> Result<(), String>;
+}
+
+// ---------------------------------------------------------------------------
+// In-memory implementation
+// ---------------------------------------------------------------------------
+
+/// Process-local store, used when no `store` is configured. Everything is lost
+/// on restart and nothing is shared between nodes.
+#[derive(Default)]
+pub struct InMemoryTokenStore {
+    tokens: Mutex<HashMap<String, TokenEntry>>,
+    refresh_tokens: Mutex<HashMap<String, TokenEntry>>,
+    codes: Mutex<HashMap<String, AuthCodeEntry>>,
+    keys: Mutex<Vec<SigningKeyRecord>>,
+}
+
+impl InMemoryTokenStore {
     pub fn new() -> Self {
-        Self { inner: Arc::new(Mutex::new(HashMap::new())) }
+        Self::default()
+    }
+}
+
+fn lock<T>(m: &Mutex<T>) -> MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(|p| p.into_inner())
+}
+
+impl TokenStore for InMemoryTokenStore {
+    fn migrate(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn insert_token(&self, entry: &TokenEntry) -> Result<(), String> {
+        lock(&self.tokens).insert(entry.jti.clone(), entry.clone());
+        Ok(())
     }
 
-    pub fn insert(&self, entry: TokenEntry) {
-        self.inner.lock().unwrap_or_else(|p| p.into_inner()).insert(entry.jti.clone(), entry);
+    fn get_token(&self, jti: &str) -> Result<Option<TokenEntry>, String> {
+        Ok(lock(&self.tokens).get(jti).cloned())
     }
 
-    pub fn get(&self, jti: &str) -> Option<TokenEntry> {
-        self.inner.lock().unwrap_or_else(|p| p.into_inner()).get(jti).cloned()
+    fn find_active_token(&self, raw_jwt: &str) -> Result<Option<TokenEntry>, String> {
+        let now = now_secs();
+        Ok(lock(&self.tokens).values()
+            .find(|e| !e.revoked && e.expires_at > now && e.raw_jwt.as_deref() == Some(raw_jwt))
+            .cloned())
     }
 
-    pub fn revoke(&self, jti: &str) {
-        if let Some(e) = self.inner.lock().unwrap_or_else(|p| p.into_inner()).get_mut(jti) {
+    fn revoke_token(&self, jti: &str) -> Result<(), String> {
+        if let Some(e) = lock(&self.tokens).get_mut(jti) {
             e.revoked = true;
         }
+        Ok(())
     }
 
-    pub fn list_active(&self) -> Vec<TokenEntry> {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .map(|d| d.as_secs())
-            .unwrap_or(0);
-        self.inner.lock().unwrap_or_else(|p| p.into_inner()).values()
+    fn list_active_tokens(&self) -> Result<Vec<TokenEntry>, String> {
+        let now = now_secs();
+        Ok(lock(&self.tokens).values()
             .filter(|e| !e.revoked && e.expires_at > now)
             .cloned()
-            .collect()
+            .collect())
+    }
+
+    fn insert_refresh_token(&self, entry: &TokenEntry) -> Result<(), String> {
+        lock(&self.refresh_tokens).insert(entry.jti.clone(), entry.clone());
+        Ok(())
+    }
+
+    fn get_refresh_token(&self, token_hash: &str) -> Result<Option<TokenEntry>, String> {
+        Ok(lock(&self.refresh_tokens).get(token_hash).cloned())
+    }
+
+    fn use_refresh_token(&self, token_hash: &str) -> Result<Option<TokenEntry>, String> {
+        Ok(lock(&self.refresh_tokens).get_mut(token_hash).map(|e| {
+            let before = e.clone();
+            e.used = true;
+            before
+        }))
+    }
+
+    fn revoke_family(&self, family_id: &str) -> Result<(), String> {
+        for map in [&self.tokens, &self.refresh_tokens] {
+            for e in lock(map).values_mut().filter(|e| e.family_id.as_deref() == Some(family_id)) {
+                e.revoked = true;
+            }
+        }
+        Ok(())
+    }
+
+    fn insert_code(&self, entry: &AuthCodeEntry) -> Result<(), String> {
+        lock(&self.codes).insert(entry.code.clone(), entry.clone());
+        Ok(())
+    }
+
+    fn consume_code(&self, code: &str) -> Result<Option<AuthCodeEntry>, String> {
+        Ok(lock(&self.codes).remove(code))
+    }
+
+    fn list_signing_keys(&self) -> Result<Vec<SigningKeyRecord>, String> {
+        Ok(lock(&self.keys).clone())
+    }
+
+    fn activate_signing_key(&self, key: &SigningKeyRecord) -> Result<(), String> {
+        let mut keys = lock(&self.keys);
+        for k in keys.iter_mut().filter(|k| k.status == SigningKeyStatus::Active) {
+            k.status = SigningKeyStatus::Retiring;
+            k.rotated_at = Some(key.created_at);
+        }
+        keys.retain(|k| k.kid != key.kid);
+        keys.push(SigningKeyRecord { status: SigningKeyStatus::Active, rotated_at: None, ..key.clone() });
+        Ok(())
+    }
+
+    fn retire_signing_keys(&self, before: u64) -> Result<(), String> {
+        for k in lock(&self.keys).iter_mut() {
+            if k.status == SigningKeyStatus::Retiring && k.rotated_at.is_some_and(|t| t < before) {
+                k.status = SigningKeyStatus::Retired;
+            }
+        }
+        Ok(())
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AuthCodeEntry {
-    pub code: String,
-    pub client_id: String,
-    pub redirect_uri: String,
-    pub scope: String,
-    pub principal_id: String,
-    pub code_challenge: Option<String>,
-    pub code_challenge_method: Option<String>,
-    pub expires_at: u64,
+// ---------------------------------------------------------------------------
+// SQLite-backed implementation
+// ---------------------------------------------------------------------------
+
+/// Store backed by a SQLite database, shared by every node that opens the same
+/// file and scoped to one tenant. As in `ox_cert_core`, each record is a JSON
+/// blob alongside the indexed columns used for lookups; mutable flags live only
+/// in their columns. Signing keys are sealed with AES-256-GCM under a key
+/// derived from the store passphrase, bound to the tenant and `kid`. Pass
+/// `":memory:"` as the path in unit tests.
+pub struct OxPersistenceTokenStore {
+    conn: Mutex<Connection>,
+    tenant_id: String,
+    kek: Aes256Gcm,
 }
 
-#[derive(Clone)]
-pub struct AuthCodeStore {
-    inner: Arc<Mutex<HashMap<String, AuthCodeEntry>>>,
+/// Marks a `private_key_pem` sealed for storage: the prefix, then base64 of
+/// nonce[12] || ciphertext || tag[16].
+const SEALED_KEY_PREFIX: &str = "aes256gcm:";
+
+impl OxPersistenceTokenStore {
+    /// Open (or create) the SQLite database at `db_path` and run migrations.
+    /// Signing keys are encrypted with a key derived from `passphrase`.
+    pub fn open(db_path: &str, tenant_id: &str, passphrase: &str) -> Result<Self, String> {
+        if passphrase.is_empty() {
+            return Err("signing key passphrase is empty".to_string());
+        }
+        let conn = Connection::open(db_path)
+            .map_err(|e| format!("sqlite open '{}': {}", db_path, e))?;
+        conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA synchronous=NORMAL;")
+            .map_err(|e| format!("PRAGMA: {}", e))?;
+        conn.busy_timeout(Duration::from_secs(5)).map_err(sql)?;
+        let store = Self {
+            conn: Mutex::new(conn),
+            tenant_id: tenant_id.to_string(),
+            kek: derive_kek(passphrase, tenant_id),
+        };
+        store.migrate()?;
+        Ok(store)
+    }
+
+    fn key_aad(&self, kid: &str) -> String {
+        format!("{}/{}", self.tenant_id, kid)
+    }
+
+    fn seal_key(&self, key: &SigningKeyRecord) -> Result<SigningKeyRecord, String> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let aad = self.key_aad(&key.kid);
+        let ciphertext = self.kek
+            .encrypt(&nonce, Payload { msg: key.private_key_pem.as_bytes(), aad: aad.as_bytes() })
+            .map_err(|_| "signing key encryption failed".to_string())?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(SigningKeyRecord {
+            private_key_pem: format!("{}{}", SEALED_KEY_PREFIX, Base64::encode_string(&sealed)),
+            ..key.clone()
+        })
+    }
+
+    fn open_key(&self, mut key: SigningKeyRecord) -> Result<SigningKeyRecord, String> {
+        let sealed = key.private_key_pem.strip_prefix(SEALED_KEY_PREFIX)
+            .and_then(|b64| Base64::decode_vec(b64).ok())
+            .filter(|sealed| sealed.len() > 12)
+            .ok_or_else(|| format!("signing key '{}' is not sealed", key.kid))?;
+        let (nonce, ciphertext) = sealed.split_at(12);
+        let aad = self.key_aad(&key.kid);
+        let pem = self.kek
+            .decrypt(nonce.into(), Payload { msg: ciphertext, aad: aad.as_bytes() })
+            .map_err(|_| format!("cannot decrypt signing key '{}': wrong passphrase?", key.kid))?;
+        key.private_key_pem = String::from_utf8(pem).map_err(|e| e.to_string())?;
+        Ok(key)
+    }
 }
 
-impl AuthCodeStore {
-    pub fn new() -> Self {
-        Self { inner: Arc::new(Mutex::new(HashMap::new())) }
+fn derive_kek(passphrase: &str, tenant_id: &str) -> Aes256Gcm {
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(tenant_id.as_bytes()), passphrase.as_bytes())
+        .expand(b"ox_security_idp:signing_key_v1", &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    Aes256Gcm::new(&key.into())
+}
+
+// ─── internal helpers ────────────────────────────────────────────────────────
+
+macro_rules! db {
+    ($self:expr) => {
+        $self.conn.lock().map_err(|e| format!("mutex: {}", e))?
+    };
+}
+
+fn sql(e: rusqlite::Error) -> String {
+    format!("sqlite: {}", e)
+}
+
+fn ser<T: Serialize>(v: &T) -> Result<String, String> {
+    serde_json::to_string(v).map_err(|e| e.to_string())
+}
+
+fn de<T: serde::de::DeserializeOwned>(s: &str) -> Result<T, String> {
+    serde_json::from_str(s).map_err(|e| e.to_string())
+}
+
+/// Unix seconds as an SQLite integer; `u64::MAX` means "never" and saturates.
+fn ts(secs: u64) -> i64 {
+    i64::try_from(secs).unwrap_or(i64::MAX)
+}
+
+fn token_hash(raw: &str) -> String {
+    hex::encode(Sha256::digest(raw.as_bytes()))
+}
+
+fn token_from_row(data: &str, revoked: bool, used: bool) -> Result<TokenEntry, String> {
+    let mut entry: TokenEntry = de(data)?;
+    entry.revoked = revoked;
+    entry.used = used;
+    Ok(entry)
+}
+
+fn key_status_str(s: SigningKeyStatus) -> &'static str {
+    match s {
+        SigningKeyStatus::Active => "Active",
+        SigningKeyStatus::Retiring => "Retiring",
+        SigningKeyStatus::Retired => "Retired",
     }
+}
 
-    pub fn insert(&self, entry: AuthCodeEntry) {
-        self.inner.lock().unwrap_or_else(|p| p.into_inner()).insert(entry.code.clone(), entry);
+fn parse_key_status(s: &str) -> Result<SigningKeyStatus, String> {
+    match s {
+        "Active" => Ok(SigningKeyStatus::Active),
+        "Retiring" => Ok(SigningKeyStatus::Retiring),
+        "Retired" => Ok(SigningKeyStatus::Retired),
+        other => Err(format!("unknown signing key status '{}'", other)),
     }
+}
+
+impl TokenStore for OxPersistenceTokenStore {
+    fn migrate(&self) -> Result<(), String> {
+        let conn = db!(self);
+        conn.execute_batch(r#"
+            CREATE TABLE IF NOT EXISTS idp_token (
+                jti        TEXT    PRIMARY KEY,
+                tenant_id  TEXT    NOT NULL,
+                token_hash TEXT    NOT NULL,
+                family_id  TEXT,
+                expires_at INTEGER NOT NULL,
+                revoked    INTEGER NOT NULL,
+                data       TEXT    NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_idp_token_hash ON idp_token(tenant_id, token_hash);
+            CREATE INDEX IF NOT EXISTS idx_idp_token_family ON idp_token(tenant_id, family_id);
+
+            CREATE TABLE IF NOT EXISTS idp_refresh_token (
+                jti        TEXT    PRIMARY KEY,
+                tenant_id  TEXT    NOT NULL,
+                family_id  TEXT,
+                expires_at INTEGER NOT NULL,
+                used       INTEGER NOT NULL,
+                revoked    INTEGER NOT NULL,
+                data       TEXT    NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_idp_refresh_family
+                ON idp_refresh_token(tenant_id, family_id);
+
+            CREATE TABLE IF NOT EXISTS idp_auth_code (
+                code       TEXT    PRIMARY KEY,
+                tenant_id  TEXT    NOT NULL,
+                expires_at INTEGER NOT NULL,
+                data       TEXT    NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS idp_signing_key (
+                kid        TEXT    NOT NULL,
+                tenant_id  TEXT    NOT NULL,
+                status     TEXT    NOT NULL,
+                rotated_at INTEGER,
+                data       TEXT    NOT NULL,
+                PRIMARY KEY (tenant_id, kid)
+            );
+        "#).map_err(sql)?;
 
-    pub fn consume(&self, code: &str) -> Option<AuthCodeEntry> {
-        self.inner.lock().unwrap_or_else(|p| p.into_inner()).remove(code)
+        // Seal signing keys written before they were encrypted at rest.
+        let mut stmt = conn.prepare("SELECT data FROM idp_signing_key WHERE tenant_id = ?1").map_err(sql)?;
+        let plaintext = stmt.query_map(params![self.tenant_id], |r| r.get::<_, String>(0)).map_err(sql)?
+            .map(|data| de::<SigningKeyRecord>(&data.map_err(sql)?))
+            .filter(|key| key.as_ref().map_or(true, |k| !k.private_key_pem.starts_with(SEALED_KEY_PREFIX)))
+            .collect::<Result<Vec<_>, String>>()?;
+        for key in plaintext {
+            conn.execute(
+                "UPDATE idp_signing_key SET data = ?3 WHERE tenant_id = ?1 AND kid = ?2",
+                params![self.tenant_id, key.kid, ser(&self.seal_key(&key)?)?],
+            ).map_err(sql)?;
+        }
+        Ok(())
+    }
+
+    fn insert_token(&self, entry: &TokenEntry) -> Result<(), String> {
+        let hash = entry.raw_jwt.as_deref().map(token_hash).unwrap_or_default();
+        let stored = TokenEntry { raw_jwt: None, ..entry.clone() };
+        db!(self).execute(
+            "INSERT OR REPLACE INTO idp_token
+                 (jti, tenant_id, token_hash, family_id, expires_at, revoked, data)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![entry.jti, self.tenant_id, hash, entry.family_id,
+                    ts(entry.expires_at), entry.revoked, ser(&stored)?],
+        ).map_err(sql)?;
+        Ok(())
+    }
+
+    fn get_token(&self, jti: &str) -> Result<Option<TokenEntry>, String> {
+        let row = db!(self).query_row(
+            "SELECT data, revoked FROM idp_token WHERE tenant_id = ?1 AND jti = ?2",
+            params![self.tenant_id, jti],
+            |r| Ok((r.get::<_, String>(0)?, r.get::<_, bool>(1)?)),
+        ).optional().map_err(sql)?;
+        row.map(|(data, revoked)| token_from_row(&data, revoked, false)).transpose()
+    }
+
+    fn find_active_token(&self, raw_jwt: &str) -> Result<Option<TokenEntry>, String> {
+        let row = db!(self).query_row(
+            "SELECT data FROM idp_token
+             WHERE tenant_id = ?1 AND token_hash = ?2 AND revoked = 0 AND expires_at > ?3",
+            params![self.tenant_id, token_hash(raw_jwt), ts(now_secs())],
+            |r| r.get::<_, String>(0),
+        ).optional().map_err(sql)?;
+        row.map(|data| {
+            let mut entry = token_from_row(&data, false, false)?;
+            entry.raw_jwt = Some(raw_jwt.to_string());
+            Ok(entry)
+        }).transpose()
+    }
+
+    fn revoke_token(&self, jti: &str) -> Result<(), String> {
+        db!(self).execute(
+            "UPDATE idp_token SET revoked = 1 WHERE tenant_id = ?1 AND jti = ?2",
+            params![self.tenant_id, jti],
+        ).map_err(sql)?;
+        Ok(())
+    }
+
+    fn list_active_tokens(&self) -> Result<Vec<TokenEntry>, String> {
+        let conn = db!(self);
+        let mut stmt = conn.prepare(
+            "SELECT data FROM idp_token WHERE tenant_id = ?1 AND revoked = 0 AND expires_at > ?2",
+        ).map_err(sql)?;
+        let rows = stmt.query_map(params![self.tenant_id, ts(now_secs())], |r| r.get::<_, String>(0))
+            .map_err(sql)?;
+        rows.map(|r| token_from_row(&r.map_err(sql)?, false, false)).collect()
+    }
+
+    fn insert_refresh_token(&self, entry: &TokenEntry) -> Result<(), String> {
+        db!(self).execute(
+            "INSERT OR REPLACE INTO idp_refresh_token
+                 (jti, tenant_id, family_id, expires_at, used, revoked, data)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![entry.jti, self.tenant_id, entry.family_id, ts(entry.expires_at),
+                    entry.used, entry.revoked, ser(entry)?],
+        ).map_err(sql)?;
+        Ok(())
+    }
+
+    fn get_refresh_token(&self, token_hash: &str) -> Result<Option<TokenEntry>, String> {
+        let row = db!(self).query_row(
+            "SELECT data, used, revoked FROM idp_refresh_token WHERE tenant_id = ?1 AND jti = ?2",
+            params![self.tenant_id, token_hash],
+            |r| Ok((r.get::<_, String>(0)?, r.get::<_, bool>(1)?, r.get::<_, bool>(2)?)),
+        ).optional().map_err(sql)?;
+        row.map(|(data, used, revoked)| token_from_row(&data, revoked, used)).transpose()
+    }
+
+    fn use_refresh_token(&self, token_hash: &str) -> Result<Option<TokenEntry>, String> {
+        let mut conn = db!(self);
+        // IMMEDIATE takes the write lock up front, so two nodes redeeming the
+        // same token cannot both read it as unused.
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(sql)?;
+        let row = tx.query_row(
+            "SELECT data, used, revoked FROM idp_refresh_token WHERE tenant_id = ?1 AND jti = ?2",
+            params![self.tenant_id, token_hash],
+            |r| Ok((r.get::<_, String>(0)?, r.get::<_, bool>(1)?, r.get::<_, bool>(2)?)),
+        ).optional().map_err(sql)?;
+        let Some((data, used, revoked)) = row else {
+            return Ok(None);
+        };
+        tx.execute(
+            "UPDATE idp_refresh_token SET used = 1 WHERE tenant_id = ?1 AND jti = ?2",
+            params![self.tenant_id, token_hash],
+        ).map_err(sql)?;
+        tx.commit().map_err(sql)?;
+        token_from_row(&data, revoked, used).map(Some)
+    }
+
+    fn revoke_family(&self, family_id: &str) -> Result<(), String> {
+        let conn = db!(self);
+        for table in ["idp_token", "idp_refresh_token"] {
+            conn.execute(
+                &format!("UPDATE {} SET revoked = 1 WHERE tenant_id = ?1 AND family_id = ?2", table),
+                params![self.tenant_id, family_id],
+            ).map_err(sql)?;
+        }
+        Ok(())
+    }
+
+    fn insert_code(&self, entry: &AuthCodeEntry) -> Result<(), String> {
+        db!(self).execute(
+            "INSERT OR REPLACE INTO idp_auth_code (code, tenant_id, expires_at, data)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![entry.code, self.tenant_id, ts(entry.expires_at), ser(entry)?],
+        ).map_err(sql)?;
+        Ok(())
+    }
+
+    fn consume_code(&self, code: &str) -> Result<Option<AuthCodeEntry>, String> {
+        let row = db!(self).query_row(
+            "DELETE FROM idp_auth_code WHERE tenant_id = ?1 AND code = ?2 RETURNING data",
+            params![self.tenant_id, code],
+            |r| r.get::<_, String>(0),
+        ).optional().map_err(sql)?;
+        row.map(|data| de(&data)).transpose()
+    }
+
+    fn list_signing_keys(&self) -> Result<Vec<SigningKeyRecord>, String> {
+        let conn = db!(self);
+        let mut stmt = conn.prepare(
+            "SELECT data, status, rotated_at FROM idp_signing_key WHERE tenant_id = ?1",
+        ).map_err(sql)?;
+        let rows = stmt.query_map(params![self.tenant_id], |r| {
+            Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?, r.get::<_, Option<i64>>(2)?))
+        }).map_err(sql)?;
+        let mut keys = rows.map(|r| {
+            let (data, status, rotated_at) = r.map_err(sql)?;
+            let mut key = self.open_key(de(&data)?)?;
+            key.status = parse_key_status(&status)?;
+            key.rotated_at = rotated_at.map(|t| t as u64);
+            Ok(key)
+        }).collect::<Result<Vec<_>, String>>()?;
+        keys.sort_by_key(|k| k.created_at);
+        Ok(keys)
+    }
+
+    fn activate_signing_key(&self, key: &SigningKeyRecord) -> Result<(), String> {
+        let mut conn = db!(self);
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(sql)?;
+        tx.execute(
+            "UPDATE idp_signing_key SET status = 'Retiring', rotated_at = ?2
+             WHERE tenant_id = ?1 AND status = 'Active'",
+            params![self.tenant_id, ts(key.created_at)],
+        ).map_err(sql)?;
+        tx.execute(
+            "INSERT OR REPLACE INTO idp_signing_key (kid, tenant_id, status, rotated_at, data)
+             VALUES (?1, ?2, ?3, NULL, ?4)",
+            params![key.kid, self.tenant_id, key_status_str(SigningKeyStatus::Active), ser(&self.seal_key(key)?)?],
+        ).map_err(sql)?;
+        tx.commit().map_err(sql)
+    }
+
+    fn retire_signing_keys(&self, before: u64) -> Result<(), String> {
+        db!(self).execute(
+            "UPDATE idp_signing_key SET status = 'Retired'
+             WHERE tenant_id = ?1 AND status = 'Retiring' AND rotated_at < ?2",
+            params![self.tenant_id, ts(before)],
+        ).map_err(sql)?;
+        Ok(())
+    }
+}
+
+/// The store `config` names, or an in-memory one when there is none.
+pub fn open_token_store(
+    tenant_id: &str,
+    config: Option<&TokenStoreConfig>,
+) -> Result<Box<dyn TokenStore>, String> {
+    match config.map(|c| c.driver.as_str()) {
+        None | Some("memory") => Ok(Box::new(InMemoryTokenStore::new())),
+        Some("sqlite") => {
+            let path = config.map(|c| c.db_path()).unwrap_or_default();
+            let passphrase = config.and_then(|c| c.passphrase_env.as_deref())
+                .and_then(|env| std::env::var(env).ok())
+                .filter(|p| !p.is_empty())
+                .ok_or("the sqlite store needs passphrase_env naming a set variable, to encrypt signing keys")?;
+            Ok(Box::new(OxPersistenceTokenStore::open(path, tenant_id, &passphrase)?))
+        }
+        Some(other) => Err(format!("unknown token store driver '{}'", other)),
     }
 }
 
+// ---------------------------------------------------------------------------
+// SAML sessions
+// ---------------------------------------------------------------------------
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SamlSessionEntry {
     pub session_id: String,
@@ -116,78 +660,174 @@ impl SamlSessionStore {
     }
 }
 
-#[derive(Clone)]
-pub struct RefreshTokenStore {
-    inner: Arc<Mutex<HashMap<String, TokenEntry>>>,
-}
-
-impl RefreshTokenStore {
-    pub fn new() -> Self {
-        Self { inner: Arc::new(Mutex::new(HashMap::new())) }
-    }
-
-    pub fn insert(&self, entry: TokenEntry) {
-        self.inner.lock().unwrap_or_else(|p| p.into_inner()).insert(entry.jti.clone(), entry);
-    }
-
-    pub fn consume(&self, token: &str) -> Option<TokenEntry> {
-        self.inner.lock().unwrap_or_else(|p| p.into_inner()).remove(token)
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_token_store_insert_and_lookup() {
-        let store = TokenStore::new();
-        let entry = TokenEntry {
-            jti: "jti-1".to_string(),
+    /// Every test runs against both implementations.
+    fn stores() -> Vec<Box<dyn TokenStore>> {
+        vec![
+            Box::new(InMemoryTokenStore::new()),
+            Box::new(OxPersistenceTokenStore::open(":memory:", "t1", "passphrase").unwrap()),
+        ]
+    }
+
+    fn token(jti: &str, family_id: Option<&str>) -> TokenEntry {
+        TokenEntry {
+            jti: jti.to_string(),
             client_id: "client-1".to_string(),
             principal_id: Some("user-1".to_string()),
             scope: "openid".to_string(),
             expires_at: u64::MAX,
             revoked: false,
-            raw_jwt: None,
-        };
-        store.insert(entry);
-        let found = store.get("jti-1").unwrap();
-        assert_eq!(found.client_id, "client-1");
-        assert!(!found.revoked);
+            raw_jwt: Some(format!("jwt-{}", jti)),
+            auth_time: None,
+            claims: Default::default(),
+            family_id: family_id.map(str::to_string),
+            used: false,
+        }
+    }
+
+    fn signing_key(kid: &str, created_at: u64) -> SigningKeyRecord {
+        SigningKeyRecord {
+            kid: kid.to_string(),
+            private_key_pem: "PEM".to_string(),
+            status: SigningKeyStatus::Active,
+            created_at,
+            rotated_at: None,
+        }
+    }
+
+    #[test]
+    fn test_token_store_insert_and_lookup() {
+        for store in stores() {
+            store.insert_token(&token("jti-1", None)).unwrap();
+            let found = store.get_token("jti-1").unwrap().unwrap();
+            assert_eq!(found.client_id, "client-1");
+            assert!(!found.revoked);
+            let active = store.find_active_token("jwt-jti-1").unwrap().unwrap();
+            assert_eq!(active.jti, "jti-1");
+            assert_eq!(active.raw_jwt.as_deref(), Some("jwt-jti-1"));
+            assert!(store.find_active_token("jwt-other").unwrap().is_none());
+        }
     }
 
     #[test]
     fn test_token_store_revoke() {
-        let store = TokenStore::new();
-        store.insert(TokenEntry {
-            jti: "jti-2".to_string(),
-            client_id: "c".to_string(),
-            principal_id: None,
-            scope: "".to_string(),
-            expires_at: u64::MAX,
-            revoked: false,
-            raw_jwt: None,
-        });
-        store.revoke("jti-2");
-        assert!(store.get("jti-2").unwrap().revoked);
+        for store in stores() {
+            store.insert_token(&token("jti-2", None)).unwrap();
+            store.revoke_token("jti-2").unwrap();
+            assert!(store.get_token("jti-2").unwrap().unwrap().revoked);
+            assert!(store.find_active_token("jwt-jti-2").unwrap().is_none());
+            assert!(store.list_active_tokens().unwrap().is_empty());
+        }
     }
 
     #[test]
     fn test_auth_code_consume() {
-        let store = AuthCodeStore::new();
-        store.insert(AuthCodeEntry {
-            code: "abc123".to_string(),
-            client_id: "c".to_string(),
-            redirect_uri: "https://app.example.com/cb".to_string(),
-            scope: "openid".to_string(),
-            principal_id: "u1".to_string(),
-            code_challenge: None,
-            code_challenge_method: None,
-            expires_at: u64::MAX,
-        });
-        let entry = store.consume("abc123");
-        assert!(entry.is_some());
-        assert!(store.consume("abc123").is_none());
+        for store in stores() {
+            store.insert_code(&AuthCodeEntry {
+                code: "abc123".to_string(),
+                client_id: "c".to_string(),
+                redirect_uri: "https://app.example.com/cb".to_string(),
+                scope: "openid".to_string(),
+                principal_id: "u1".to_string(),
+                code_challenge: None,
+                code_challenge_method: None,
+                expires_at: u64::MAX,
+                nonce: None,
+                auth_time: 0,
+                claims: Default::default(),
+            }).unwrap();
+            let entry = store.consume_code("abc123").unwrap();
+            assert!(entry.is_some());
+            assert!(store.consume_code("abc123").unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn test_refresh_token_used_once_and_family_revoked() {
+        for store in stores() {
+            store.insert_token(&token("access-1", Some("fam"))).unwrap();
+            store.insert_token(&token("access-other", Some("other"))).unwrap();
+            store.insert_refresh_token(&token("rt-hash", Some("fam"))).unwrap();
+
+            assert!(!store.use_refresh_token("rt-hash").unwrap().unwrap().used);
+            assert!(store.use_refresh_token("rt-hash").unwrap().unwrap().used, "second use is a replay");
+            assert!(store.use_refresh_token("missing").unwrap().is_none());
+
+            store.revoke_family("fam").unwrap();
+            assert!(store.get_refresh_token("rt-hash").unwrap().unwrap().revoked);
+            assert!(store.get_token("access-1").unwrap().unwrap().revoked);
+            assert!(!store.get_token("access-other").unwrap().unwrap().revoked);
+        }
+    }
+
+    #[test]
+    fn test_activating_signing_key_retires_previous() {
+        for store in stores() {
+            store.activate_signing_key(&signing_key("k1", 100)).unwrap();
+            store.activate_signing_key(&signing_key("k2", 200)).unwrap();
+            let keys = store.list_signing_keys().unwrap();
+            assert_eq!(keys.len(), 2);
+            assert_eq!((keys[0].status, keys[0].rotated_at), (SigningKeyStatus::Retiring, Some(200)));
+            assert_eq!(keys[1].status, SigningKeyStatus::Active);
+
+            store.retire_signing_keys(200).unwrap();
+            assert_eq!(store.list_signing_keys().unwrap()[0].status, SigningKeyStatus::Retiring);
+            store.retire_signing_keys(201).unwrap();
+            assert_eq!(store.list_signing_keys().unwrap()[0].status, SigningKeyStatus::Retired);
+        }
+    }
+
+    #[test]
+    fn test_sqlite_store_survives_reopen_and_isolates_tenants() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("idp.db");
+        let path = path.to_str().unwrap();
+        {
+            let store = OxPersistenceTokenStore::open(path, "t1", "passphrase").unwrap();
+            store.insert_token(&token("jti-1", None)).unwrap();
+            store.insert_refresh_token(&token("rt-hash", Some("fam"))).unwrap();
+            store.revoke_family("fam").unwrap();
+        }
+        let store = OxPersistenceTokenStore::open(path, "t1", "passphrase").unwrap();
+        let found = store.get_token("jti-1").unwrap().unwrap();
+        assert_eq!(found.raw_jwt, None, "the signed JWT is not persisted");
+        assert!(store.find_active_token("jwt-jti-1").unwrap().is_some());
+        assert!(store.get_refresh_token("rt-hash").unwrap().unwrap().revoked);
+
+        let other = OxPersistenceTokenStore::open(path, "t2", "passphrase").unwrap();
+        assert!(other.get_token("jti-1").unwrap().is_none());
+        assert!(other.list_active_tokens().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_signing_keys_are_encrypted_at_rest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("idp.db");
+        let path = path.to_str().unwrap();
+        let stored_data = |store: &OxPersistenceTokenStore| -> String {
+            store.conn.lock().unwrap()
+                .query_row("SELECT data FROM idp_signing_key", [], |r| r.get(0)).unwrap()
+        };
+        {
+            let store = OxPersistenceTokenStore::open(path, "t1", "passphrase").unwrap();
+            store.activate_signing_key(&signing_key("k1", 100)).unwrap();
+            assert!(!stored_data(&store).contains("\"PEM\""), "the private key is not stored in plaintext");
+        }
+        let store = OxPersistenceTokenStore::open(path, "t1", "passphrase").unwrap();
+        assert_eq!(store.list_signing_keys().unwrap()[0].private_key_pem, "PEM");
+
+        let wrong = OxPersistenceTokenStore::open(path, "t1", "other passphrase").unwrap();
+        assert!(wrong.list_signing_keys().unwrap_err().contains("cannot decrypt"));
+
+        // Keys stored before encryption are sealed when the store is opened.
+        store.conn.lock().unwrap().execute(
+            "UPDATE idp_signing_key SET data = ?1", params![ser(&signing_key("k1", 100)).unwrap()],
+        ).unwrap();
+        let reopened = OxPersistenceTokenStore::open(path, "t1", "passphrase").unwrap();
+        assert!(!stored_data(&reopened).contains("\"PEM\""));
+        assert_eq!(reopened.list_signing_keys().unwrap()[0].private_key_pem, "PEM");
     }
 }
@@ -0,0 +1,670 @@
+// XML Signature and XML Encryption for SAML messages
+//
+// Messages are generated by this crate already in exclusive canonical form
+// (http://www.w3.org/2001/10/xml-exc-c14n#): no XML declaration, namespace
+// declarations on the element that first uses the prefix, attributes sorted,
+// empty elements written as start/end pairs, and C14N escaping. The digest is
+// then taken over the generated bytes as they are, so no general-purpose
+// canonicalizer is needed for signing. Anything parsed from the wire is never
+// signed; SP messages are verified after an exclusive C14N of the parsed tree,
+// which supports what SAML messages use (no DTDs or processing instructions).
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::Aes256Gcm;
+use base64ct::{Base64, Encoding};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use rsa::pkcs1v15::{Signature, SigningKey, VerifyingKey};
+use rsa::pkcs8::DecodePublicKey;
+use rsa::signature::{SignatureEncoding, Signer, Verifier};
+use rsa::{Oaep, RsaPrivateKey, RsaPublicKey};
+use sha2::{Digest, Sha256};
+
+pub const NS_DSIG: &str = "http://www.w3.org/2000/09/xmldsig#";
+pub const NS_XENC: &str = "http://www.w3.org/2001/04/xmlenc#";
+pub const ALG_EXC_C14N: &str = "http://www.w3.org/2001/10/xml-exc-c14n#";
+pub const ALG_ENVELOPED: &str = "http://www.w3.org/2000/09/xmldsig#enveloped-signature";
+pub const ALG_RSA_SHA256: &str = "http://www.w3.org/2001/04/xmldsig-more#rsa-sha256";
+pub const ALG_SHA256: &str = "http://www.w3.org/2001/04/xmlenc#sha256";
+pub const NS_XML: &str = "http://www.w3.org/XML/1998/namespace";
+pub const ALG_SHA1: &str = "http://www.w3.org/2000/09/xmldsig#sha1";
+pub const ALG_AES256_GCM: &str = "http://www.w3.org/2009/xmlenc11#aes256-gcm";
+pub const ALG_RSA_OAEP: &str = "http://www.w3.org/2001/04/xmlenc#rsa-oaep-mgf1p";
+pub const TYPE_ELEMENT: &str = "http://www.w3.org/2001/04/xmlenc#Element";
+
+/// Escape character data as C14N does: `&`, `<`, `>` and CR.
+pub fn c14n_text(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '&'  => out.push_str("&amp;"),
+            '<'  => out.push_str("&lt;"),
+            '>'  => out.push_str("&gt;"),
+            '\r' => out.push_str("&#xD;"),
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Escape an attribute value as C14N does: `&`, `<`, `"`, TAB, LF and CR.
+pub fn c14n_attr(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '&'  => out.push_str("&amp;"),
+            '<'  => out.push_str("&lt;"),
+            '"'  => out.push_str("&quot;"),
+            '\t' => out.push_str("&#x9;"),
+            '\n' => out.push_str("&#xA;"),
+            '\r' => out.push_str("&#xD;"),
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Signing key and certificate advertised in the IdP metadata.
+pub struct SamlSigner {
+    key: RsaPrivateKey,
+    /// Base64 DER of the certificate, as in `ds:X509Certificate`.
+    pub cert_b64: String,
+}
+
+impl SamlSigner {
+    /// `key` must be the private key of the certificate in `cert_pem`.
+    pub fn new(key: RsaPrivateKey, cert_pem: &str) -> Result<Self, String> {
+        let (der, public) = read_rsa_cert(cert_pem)?;
+        if public != key.to_public_key() {
+            return Err("SAML signing certificate does not match the RSA private key".to_string());
+        }
+        Ok(Self { key, cert_b64: Base64::encode_string(&der) })
+    }
+
+    /// Insert an enveloped RSA-SHA256 signature over `element`, whose `ID` is
+    /// `id`, directly after its first `</saml:Issuer>` as the SAML schemas
+    /// require. `element` must be canonical (see the module comment) and must not
+    /// be nested in another element when its canonical form is taken.
+    pub fn sign(&self, element: &str, id: &str) -> Result<String, String> {
+        let anchor = "</saml:Issuer>";
+        let at = element.find(anchor)
+            .ok_or("element to sign has no saml:Issuer")? + anchor.len();
+        let digest = Base64::encode_string(&Sha256::digest(element.as_bytes()));
+        let signed_info = format!(
+            "<ds:SignedInfo xmlns:ds=\"{ds}\">\
+             <ds:CanonicalizationMethod Algorithm=\"{c14n}\"></ds:CanonicalizationMethod>\
+             <ds:SignatureMethod Algorithm=\"{sig}\"></ds:SignatureMethod>\
+             <ds:Reference URI=\"#{id}\"><ds:Transforms>\
+             <ds:Transform Algorithm=\"{env}\"></ds:Transform>\
+             <ds:Transform Algorithm=\"{c14n}\"></ds:Transform>\
+             </ds:Transforms>\
+             <ds:DigestMethod Algorithm=\"{dig}\"></ds:DigestMethod>\
+             <ds:DigestValue>{digest}</ds:DigestValue>\
+             </ds:Reference></ds:SignedInfo>",
+            ds = NS_DSIG, c14n = ALG_EXC_C14N, sig = ALG_RSA_SHA256, env = ALG_ENVELOPED,
+            dig = ALG_SHA256, id = c14n_attr(id), digest = digest,
+        );
+        let signer = SigningKey::<Sha256>::new(self.key.clone());
+        let signature = signer.try_sign(signed_info.as_bytes()).map_err(|e| e.to_string())?;
+        let block = format!(
+            "<ds:Signature xmlns:ds=\"{}\">{}<ds:SignatureValue>{}</ds:SignatureValue>\
+             <ds:KeyInfo><ds:X509Data><ds:X509Certificate>{}</ds:X509Certificate>\
+             </ds:X509Data></ds:KeyInfo></ds:Signature>",
+            NS_DSIG, signed_info, Base64::encode_string(&signature.to_bytes()), self.cert_b64,
+        );
+        Ok(format!("{}{}{}", &element[..at], block, &element[at..]))
+    }
+}
+
+/// DER and RSA public key of a PEM certificate.
+pub fn read_rsa_cert(cert_pem: &str) -> Result<(Vec<u8>, RsaPublicKey), String> {
+    let (_, pem) = x509_parser::pem::parse_x509_pem(cert_pem.as_bytes())
+        .map_err(|e| format!("invalid certificate PEM: {}", e))?;
+    let cert = pem.parse_x509().map_err(|e| format!("invalid certificate: {}", e))?;
+    let public = RsaPublicKey::from_public_key_der(cert.public_key().raw)
+        .map_err(|_| "certificate key is not RSA".to_string())?;
+    Ok((pem.contents.clone(), public))
+}
+
+/// Verify the enveloped RSA-SHA256 signature of `xml`, a message signed by the
+/// holder of `key`. The signature must be a child of the root element, reference
+/// it by its `ID` and use the transforms `SamlSigner::sign` writes; the root is
+/// canonicalized with exclusive C14N before it is digested, so the signature
+/// covers exactly the element the caller goes on to parse.
+pub fn verify_enveloped(xml: &str, key: &RsaPublicKey) -> Result<(), String> {
+    let root = parse_tree(xml)?;
+    let id = root.attr("ID").ok_or("message has no ID")?;
+    let mut signatures = root.elements().filter(|e| e.is(NS_DSIG, "Signature"));
+    let signature = match (signatures.next(), signatures.next()) {
+        (Some(signature), None) => signature,
+        (None, _) => return Err("message is not signed".to_string()),
+        _ => return Err("message has more than one signature".to_string()),
+    };
+    let signed_info = signature.child("SignedInfo")?;
+    let c14n_method = signed_info.child("CanonicalizationMethod")?;
+    if c14n_method.attr("Algorithm") != Some(ALG_EXC_C14N) {
+        return Err("unsupported canonicalization method".to_string());
+    }
+    if signed_info.child("SignatureMethod")?.attr("Algorithm") != Some(ALG_RSA_SHA256) {
+        return Err("unsupported signature method".to_string());
+    }
+    let reference = signed_info.child("Reference")?;
+    if reference.attr("URI") != Some(format!("#{}", id).as_str()) {
+        return Err("signature does not reference the message".to_string());
+    }
+    let (mut enveloped, mut inclusive) = (false, None);
+    for transform in reference.child("Transforms")?.elements() {
+        match (transform.is(NS_DSIG, "Transform"), transform.attr("Algorithm")) {
+            (true, Some(ALG_ENVELOPED)) => enveloped = true,
+            (true, Some(ALG_EXC_C14N)) => inclusive = Some(transform.prefix_list()),
+            _ => return Err("unsupported signature transform".to_string()),
+        }
+    }
+    let (true, Some(inclusive)) = (enveloped, inclusive) else {
+        return Err("signature must use the enveloped and exclusive C14N transforms".to_string());
+    };
+    if reference.child("DigestMethod")?.attr("Algorithm") != Some(ALG_SHA256) {
+        return Err("unsupported digest method".to_string());
+    }
+
+    let mut canonical = String::new();
+    exc_c14n(&root, Some(signature), &Scope::new(), &Scope::new(), &inclusive, &mut canonical);
+    let digest = decode_base64(&reference.child("DigestValue")?.text())?;
+    if Sha256::digest(canonical.as_bytes())[..] != digest[..] {
+        return Err("message digest does not match".to_string());
+    }
+
+    // SignedInfo inherits the namespaces declared on its ancestors.
+    let scope = signature.scope(&root.scope(&Scope::new()));
+    let mut canonical = String::new();
+    exc_c14n(signed_info, None, &scope, &Scope::new(), &c14n_method.prefix_list(), &mut canonical);
+    let value = decode_base64(&signature.child("SignatureValue")?.text())?;
+    let value = Signature::try_from(value.as_slice()).map_err(|_| "malformed signature value".to_string())?;
+    VerifyingKey::<Sha256>::new(key.clone())
+        .verify(canonical.as_bytes(), &value)
+        .map_err(|_| "signature does not verify".to_string())
+}
+
+/// Verify the signature of an HTTP-Redirect binding message (SAML bindings
+/// 3.4.4.1): RSA-SHA256 over `SAMLRequest`, `RelayState` when present and
+/// `SigAlg`, exactly as they appear URL-encoded in `query`. Each of those
+/// parameters may appear only once, so the signed values are the ones parsed.
+pub fn verify_redirect_query(query: &str, key: &RsaPublicKey) -> Result<(), String> {
+    let mut params: BTreeMap<&str, &str> = BTreeMap::new();
+    for pair in query.split('&') {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        if ["SAMLRequest", "RelayState", "SigAlg", "Signature"].contains(&name)
+            && params.insert(name, value).is_some()
+        {
+            return Err(format!("duplicate {} parameter", name));
+        }
+    }
+    let (Some(request), Some(sig_alg), Some(signature)) =
+        (params.get("SAMLRequest"), params.get("SigAlg"), params.get("Signature"))
+    else {
+        return Err("message is not signed".to_string());
+    };
+    if crate::oauth2::percent_decode(sig_alg) != ALG_RSA_SHA256 {
+        return Err("unsupported signature method".to_string());
+    }
+    let mut signed = format!("SAMLRequest={}", request);
+    if let Some(relay_state) = params.get("RelayState") {
+        signed.push_str("&RelayState=");
+        signed.push_str(relay_state);
+    }
+    signed.push_str("&SigAlg=");
+    signed.push_str(sig_alg);
+    let value = decode_base64(&crate::oauth2::percent_decode(signature))?;
+    let value = Signature::try_from(value.as_slice()).map_err(|_| "malformed signature value".to_string())?;
+    VerifyingKey::<Sha256>::new(key.clone())
+        .verify(signed.as_bytes(), &value)
+        .map_err(|_| "signature does not verify".to_string())
+}
+
+fn decode_base64(value: &str) -> Result<Vec<u8>, String> {
+    let compact: String = value.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    Base64::decode_vec(&compact).map_err(|_| "malformed base64 in signature".to_string())
+}
+
+/// Namespace prefix (`""` for the default namespace) to URI.
+type Scope = BTreeMap<String, String>;
+
+/// An element parsed for signature verification, with names as written and
+/// their prefixes resolved against the declarations in scope.
+struct XmlElement {
+    qname: String,
+    ns: String,
+    local: String,
+    /// Namespace declarations on this element, `""` for the default namespace.
+    decls: Vec<(String, String)>,
+    attrs: Vec<XmlAttr>,
+    children: Vec<XmlNode>,
+}
+
+struct XmlAttr {
+    qname: String,
+    ns: String,
+    local: String,
+    value: String,
+}
+
+enum XmlNode {
+    Element(XmlElement),
+    Text(String),
+}
+
+impl XmlElement {
+    fn is(&self, ns: &str, local: &str) -> bool {
+        self.ns == ns && self.local == local
+    }
+
+    /// Unqualified attribute `name`.
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.iter().find(|a| a.qname == name).map(|a| a.value.as_str())
+    }
+
+    fn elements(&self) -> impl Iterator<Item = &XmlElement> {
+        self.children.iter().filter_map(|c| match c {
+            XmlNode::Element(e) => Some(e),
+            XmlNode::Text(_) => None,
+        })
+    }
+
+    /// The only `ds:` child element named `local`.
+    fn child(&self, local: &str) -> Result<&XmlElement, String> {
+        let mut found = self.elements().filter(|e| e.is(NS_DSIG, local));
+        match (found.next(), found.next()) {
+            (Some(e), None) => Ok(e),
+            _ => Err(format!("signature needs exactly one ds:{}", local)),
+        }
+    }
+
+    fn text(&self) -> String {
+        self.children.iter().filter_map(|c| match c {
+            XmlNode::Text(t) => Some(t.as_str()),
+            XmlNode::Element(_) => None,
+        }).collect()
+    }
+
+    /// `PrefixList` of the `InclusiveNamespaces` child of a C14N method or transform.
+    fn prefix_list(&self) -> Vec<String> {
+        self.elements()
+            .filter(|e| e.is(ALG_EXC_C14N, "InclusiveNamespaces"))
+            .filter_map(|e| e.attr("PrefixList"))
+            .flat_map(str::split_whitespace)
+            .map(|p| if p == "#default" { String::new() } else { p.to_string() })
+            .collect()
+    }
+
+    /// Namespaces in scope at this element, given those in scope at its parent.
+    fn scope(&self, parent: &Scope) -> Scope {
+        let mut scope = parent.clone();
+        scope.extend(self.decls.iter().cloned());
+        scope
+    }
+}
+
+fn prefix(qname: &str) -> &str {
+    qname.split_once(':').map_or("", |(prefix, _)| prefix)
+}
+
+/// Namespace URI and local part of `qname`. Unprefixed attributes are in no namespace.
+fn resolve(qname: &str, scope: &Scope, element: bool) -> Result<(String, String), String> {
+    match qname.split_once(':') {
+        Some(("xml", local)) => Ok((NS_XML.to_string(), local.to_string())),
+        Some((prefix, local)) => scope.get(prefix)
+            .map(|ns| (ns.clone(), local.to_string()))
+            .ok_or_else(|| format!("undeclared namespace prefix '{}'", prefix)),
+        None if element => Ok((scope.get("").cloned().unwrap_or_default(), qname.to_string())),
+        None => Ok((String::new(), qname.to_string())),
+    }
+}
+
+fn open_element(e: &BytesStart, parent: &Scope) -> Result<(XmlElement, Scope), String> {
+    let utf8 = |b: &[u8]| String::from_utf8(b.to_vec()).map_err(|_| "malformed XML: invalid UTF-8".to_string());
+    let mut decls = Vec::new();
+    let mut raw_attrs = Vec::new();
+    for attr in e.attributes() {
+        let attr = attr.map_err(|e| format!("malformed attribute: {}", e))?;
+        let name = utf8(attr.key.as_ref())?;
+        // Attribute-value normalization; character references are expanded after it.
+        let raw: String = utf8(&attr.value)?.replace("\r\n", " ")
+            .chars().map(|c| if matches!(c, '\t' | '\n' | '\r') { ' ' } else { c }).collect();
+        let value = quick_xml::escape::unescape(&raw)
+            .map_err(|e| format!("malformed attribute: {}", e))?.into_owned();
+        if name == "xmlns" {
+            decls.push((String::new(), value));
+        } else if let Some(prefix) = name.strip_prefix("xmlns:") {
+            decls.push((prefix.to_string(), value));
+        } else {
+            raw_attrs.push((name, value));
+        }
+    }
+    let mut scope = parent.clone();
+    scope.extend(decls.iter().cloned());
+    let qname = utf8(e.name().as_ref())?;
+    let (ns, local) = resolve(&qname, &scope, true)?;
+    let attrs = raw_attrs.into_iter()
+        .map(|(qname, value)| {
+            let (ns, local) = resolve(&qname, &scope, false)?;
+            Ok(XmlAttr { qname, ns, local, value })
+        })
+        .collect::<Result<_, String>>()?;
+    Ok((XmlElement { qname, ns, local, decls, attrs, children: Vec::new() }, scope))
+}
+
+fn push_text(stack: &mut [(XmlElement, Scope)], text: &str) -> Result<(), String> {
+    let Some((parent, _)) = stack.last_mut() else {
+        return if text.trim().is_empty() { Ok(()) } else { Err("text outside the root element".to_string()) };
+    };
+    match parent.children.last_mut() {
+        Some(XmlNode::Text(t)) => t.push_str(text),
+        _ => parent.children.push(XmlNode::Text(text.to_string())),
+    }
+    Ok(())
+}
+
+/// Parse `xml` into a tree keeping all character data. Comments and anything
+/// outside the root element are dropped, as exclusive C14N does.
+fn parse_tree(xml: &str) -> Result<XmlElement, String> {
+    let malformed = |e: quick_xml::Error| format!("malformed XML: {}", e);
+    let text_error = |e: quick_xml::encoding::EncodingError| format!("malformed text: {}", e);
+    let mut reader = Reader::from_str(xml);
+    let mut stack: Vec<(XmlElement, Scope)> = Vec::new();
+    let mut root = None;
+
+    loop {
+        let closed = match reader.read_event().map_err(malformed)? {
+            Event::Start(ref e) => {
+                let parent = stack.last().map(|(_, scope)| scope.clone()).unwrap_or_default();
+                stack.push(open_element(e, &parent)?);
+                None
+            }
+            Event::Empty(ref e) => {
+                let parent = stack.last().map(|(_, scope)| scope.clone()).unwrap_or_default();
+                Some(open_element(e, &parent)?.0)
+            }
+            Event::End(_) => Some(stack.pop().ok_or("malformed XML: unexpected end tag")?.0),
+            Event::Text(t) => {
+                push_text(&mut stack, &t.xml10_content().map_err(text_error)?)?;
+                None
+            }
+            Event::CData(t) => {
+                push_text(&mut stack, &t.xml10_content().map_err(text_error)?)?;
+                None
+            }
+            Event::GeneralRef(r) => {
+                let resolved = match r.resolve_char_ref().map_err(|e| format!("malformed reference: {}", e))? {
+                    Some(ch) => ch.to_string(),
+                    None => {
+                        let name = r.decode().map_err(|e| format!("malformed reference: {}", e))?;
+                        quick_xml::escape::resolve_predefined_entity(&name)
+                            .ok_or_else(|| format!("unknown entity '&{};'", name))?
+                            .to_string()
+                    }
+                };
+                push_text(&mut stack, &resolved)?;
+                None
+            }
+            Event::PI(_) if !stack.is_empty() => {
+                return Err("processing instructions are not supported".to_string());
+            }
+            Event::DocType(_) => return Err("DTDs are not allowed".to_string()),
+            Event::Eof => break,
+            _ => None,
+        };
+        if let Some(element) = closed {
+            match stack.last_mut() {
+                Some((parent, _)) => parent.children.push(XmlNode::Element(element)),
+                None if root.is_none() => root = Some(element),
+                None => return Err("malformed XML: more than one root element".to_string()),
+            }
+        }
+    }
+
+    if !stack.is_empty() {
+        return Err("malformed XML: unclosed element".to_string());
+    }
+    root.ok_or_else(|| "malformed XML: no root element".to_string())
+}
+
+/// Exclusive XML canonicalization, without comments, of `element` minus the
+/// `omit` subtree. `parent` holds the namespaces in scope at the element's
+/// parent, `rendered` those already output by its ancestors, and `inclusive`
+/// the `InclusiveNamespaces` prefixes, which are rendered wherever in scope.
+fn exc_c14n(
+    element: &XmlElement,
+    omit: Option<&XmlElement>,
+    parent: &Scope,
+    rendered: &Scope,
+    inclusive: &[String],
+    out: &mut String,
+) {
+    let scope = element.scope(parent);
+    let mut used: BTreeSet<&str> = BTreeSet::new();
+    used.insert(prefix(&element.qname));
+    used.extend(element.attrs.iter().map(|a| prefix(&a.qname)).filter(|p| !p.is_empty()));
+    used.extend(inclusive.iter().map(String::as_str).filter(|p| scope.contains_key(*p)));
+    used.remove("xml");
+
+    let mut rendered = rendered.clone();
+    out.push('<');
+    out.push_str(&element.qname);
+    for prefix in used {
+        let uri = scope.get(prefix).map(String::as_str).unwrap_or("");
+        let current = rendered.get(prefix).map(String::as_str);
+        let unchanged = if prefix.is_empty() { current.unwrap_or("") == uri } else { current == Some(uri) };
+        if unchanged {
+            continue;
+        }
+        if prefix.is_empty() {
+            out.push_str(" xmlns=\"");
+        } else {
+            out.push_str(" xmlns:");
+            out.push_str(prefix);
+            out.push_str("=\"");
+        }
+        out.push_str(&c14n_attr(uri));
+        out.push('"');
+        rendered.insert(prefix.to_string(), uri.to_string());
+    }
+    let mut attrs: Vec<&XmlAttr> = element.attrs.iter().collect();
+    attrs.sort_by(|a, b| (&a.ns, &a.local).cmp(&(&b.ns, &b.local)));
+    for attr in attrs {
+        out.push(' ');
+        out.push_str(&attr.qname);
+        out.push_str("=\"");
+        out.push_str(&c14n_attr(&attr.value));
+        out.push('"');
+    }
+    out.push('>');
+    for child in &element.children {
+        match child {
+            XmlNode::Text(text) => out.push_str(&c14n_text(text)),
+            XmlNode::Element(e) if omit.is_some_and(|o| std::ptr::eq(o, e)) => {}
+            XmlNode::Element(e) => exc_c14n(e, omit, &scope, &rendered, inclusive, out),
+        }
+    }
+    out.push_str("</");
+    out.push_str(&element.qname);
+    out.push('>');
+}
+
+/// Encrypt `element` for `recipient` as an `xenc:EncryptedData` of type
+/// Element: AES-256-GCM content encryption, the key wrapped with RSA-OAEP.
+pub fn encrypt_element(element: &str, recipient: &RsaPublicKey) -> Result<String, String> {
+    let key = Aes256Gcm::generate_key(&mut OsRng);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = Aes256Gcm::new(&key).encrypt(&nonce, element.as_bytes())
+        .map_err(|_| "content encryption failed".to_string())?;
+    let mut cipher_value = nonce.to_vec();
+    cipher_value.extend_from_slice(&ciphertext);
+    let wrapped = recipient.encrypt(&mut OsRng, Oaep::new::<sha1::Sha1>(), &key[..])
+        .map_err(|e| e.to_string())?;
+    Ok(format!(
+        "<xenc:EncryptedData xmlns:xenc=\"{xenc}\" Type=\"{ty}\">\
+         <xenc:EncryptionMethod Algorithm=\"{aes}\"></xenc:EncryptionMethod>\
+         <ds:KeyInfo xmlns:ds=\"{ds}\"><xenc:EncryptedKey>\
+         <xenc:EncryptionMethod Algorithm=\"{oaep}\"><ds:DigestMethod Algorithm=\"{sha1}\"></ds:DigestMethod></xenc:EncryptionMethod>\
+         <xenc:CipherData><xenc:CipherValue>{wrapped}</xenc:CipherValue></xenc:CipherData>\
+         </xenc:EncryptedKey></ds:KeyInfo>\
+         <xenc:CipherData><xenc:CipherValue>{data}</xenc:CipherValue></xenc:CipherData>\
+         </xenc:EncryptedData>",
+        xenc = NS_XENC, ty = TYPE_ELEMENT, aes = ALG_AES256_GCM, ds = NS_DSIG,
+        oaep = ALG_RSA_OAEP, sha1 = ALG_SHA1,
+        wrapped = Base64::encode_string(&wrapped),
+        data = Base64::encode_string(&cipher_value),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsa::pkcs8::DecodePrivateKey;
+
+    pub(crate) fn test_key() -> RsaPrivateKey {
+        RsaPrivateKey::from_pkcs8_pem(include_str!("../tests/test_rsa_private.pem")).unwrap()
+    }
+
+    /// Self-signed certificate for the test key.
+    pub(crate) fn test_cert_pem() -> String {
+        let key_pem = include_str!("../tests/test_rsa_private.pem");
+        let key = rcgen::KeyPair::from_pem_and_sign_algo(key_pem, &rcgen::PKCS_RSA_SHA256).unwrap();
+        rcgen::CertificateParams::new(vec!["idp.example.com".to_string()]).unwrap()
+            .self_signed(&key).unwrap()
+            .pem()
+    }
+
+    fn between<'a>(s: &'a str, start: &str, end: &str) -> &'a str {
+        let from = s.find(start).unwrap() + start.len();
+        &s[from..from + s[from..].find(end).unwrap()]
+    }
+
+    #[test]
+    fn test_c14n_escaping() {
+        assert_eq!(c14n_text("a<b>&'\"\r"), "a&lt;b&gt;&amp;'\"&#xD;");
+        assert_eq!(c14n_attr("a<b>&'\"\t\n"), "a&lt;b>&amp;'&quot;&#x9;&#xA;");
+    }
+
+    #[test]
+    fn test_signature_verifies_after_removing_it() {
+        let signer = SamlSigner::new(test_key(), &test_cert_pem()).unwrap();
+        let element = "<saml:Assertion xmlns:saml=\"urn:oasis:names:tc:SAML:2.0:assertion\" ID=\"_a1\">\
+                       <saml:Issuer>https://idp.example.com</saml:Issuer><saml:Subject></saml:Subject></saml:Assertion>";
+        let signed = signer.sign(element, "_a1").unwrap();
+        assert!(signed.contains("</saml:Issuer><ds:Signature"));
+
+        // Enveloped-signature transform: the digest covers the element without the signature.
+        let sig_start = signed.find("<ds:Signature").unwrap();
+        let sig_end = signed.find("</ds:Signature>").unwrap() + "</ds:Signature>".len();
+        let stripped = format!("{}{}", &signed[..sig_start], &signed[sig_end..]);
+        assert_eq!(stripped, element);
+        let digest = Base64::encode_string(&Sha256::digest(stripped.as_bytes()));
+        assert_eq!(between(&signed, "<ds:DigestValue>", "</ds:DigestValue>"), digest);
+
+        let signed_info = format!("<ds:SignedInfo{}</ds:SignedInfo>",
+            between(&signed, "<ds:SignedInfo", "</ds:SignedInfo>"));
+        let value = Base64::decode_vec(between(&signed, "<ds:SignatureValue>", "</ds:SignatureValue>")).unwrap();
+        VerifyingKey::<Sha256>::new(test_key().to_public_key())
+            .verify(signed_info.as_bytes(), &Signature::try_from(value.as_slice()).unwrap())
+            .expect("signature over canonical SignedInfo");
+    }
+
+    fn signed_logout_request() -> String {
+        let signer = SamlSigner::new(test_key(), &test_cert_pem()).unwrap();
+        let element = "<samlp:LogoutRequest xmlns:samlp=\"urn:oasis:names:tc:SAML:2.0:protocol\" ID=\"_l1\" Version=\"2.0\">\
+                       <saml:Issuer xmlns:saml=\"urn:oasis:names:tc:SAML:2.0:assertion\">urn:sp</saml:Issuer>\
+                       <saml:NameID xmlns:saml=\"urn:oasis:names:tc:SAML:2.0:assertion\">alice</saml:NameID>\
+                       <samlp:SessionIndex>_s1</samlp:SessionIndex></samlp:LogoutRequest>";
+        signer.sign(element, "_l1").unwrap()
+    }
+
+    #[test]
+    fn test_exc_c14n_of_parsed_tree() {
+        let xml = "<?xml version=\"1.0\"?>\n<a:r xmlns:u=\"urn:u\" xmlns:b=\"urn:b\" xmlns:a=\"urn:a\" z='1&#x41;' b:y=\"2\" >\
+                   <!-- note --><a:c/><d xmlns=\"urn:d\">t&amp;<![CDATA[<x>]]></d><e/></a:r>";
+        let mut out = String::new();
+        exc_c14n(&parse_tree(xml).unwrap(), None, &Scope::new(), &Scope::new(), &[], &mut out);
+        assert_eq!(out, "<a:r xmlns:a=\"urn:a\" xmlns:b=\"urn:b\" z=\"1A\" b:y=\"2\"><a:c></a:c>\
+                         <d xmlns=\"urn:d\">t&amp;&lt;x&gt;</d><e></e></a:r>");
+
+        let mut out = String::new();
+        exc_c14n(&parse_tree(xml).unwrap(), None, &Scope::new(), &Scope::new(), &["u".to_string()], &mut out);
+        assert!(out.starts_with("<a:r xmlns:a=\"urn:a\" xmlns:b=\"urn:b\" xmlns:u=\"urn:u\" "));
+        assert!(parse_tree("<!DOCTYPE r []><r></r>").is_err());
+    }
+
+    #[test]
+    fn test_verify_enveloped_signature() {
+        let key = test_key().to_public_key();
+        let signed = signed_logout_request();
+        verify_enveloped(&signed, &key).expect("own signature verifies");
+
+        // Serialization differences that canonicalize away still verify.
+        let reformatted = format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{}",
+            signed.replace("ID=\"_l1\" Version=\"2.0\"", "Version='2.0'  ID='_l1'")
+                .replace("></ds:Transform>", "/>"));
+        verify_enveloped(&reformatted, &key).expect("equivalent serialization verifies");
+
+        let tampered = signed.replace(">alice<", ">bob<");
+        assert!(verify_enveloped(&tampered, &key).unwrap_err().contains("digest"));
+        let other = RsaPrivateKey::new(&mut OsRng, 2048).unwrap().to_public_key();
+        assert!(verify_enveloped(&signed, &other).unwrap_err().contains("does not verify"));
+
+        let sig_start = signed.find("<ds:Signature").unwrap();
+        let sig_end = signed.find("</ds:Signature>").unwrap() + "</ds:Signature>".len();
+        let unsigned = format!("{}{}", &signed[..sig_start], &signed[sig_end..]);
+        assert!(verify_enveloped(&unsigned, &key).unwrap_err().contains("not signed"));
+
+        // A signature over another element of the message is not accepted.
+        let rewrapped = signed.replace("ID=\"_l1\"", "ID=\"_l2\"");
+        assert!(verify_enveloped(&rewrapped, &key).unwrap_err().contains("reference"));
+    }
+
+    fn query_escape(s: &str) -> String {
+        s.replace('%', "%25").replace('+', "%2B").replace('/', "%2F").replace('=', "%3D")
+            .replace(':', "%3A").replace('#', "%23").replace(' ', "%20")
+    }
+
+    #[test]
+    fn test_verify_redirect_query() {
+        let key = test_key();
+        let unsigned = format!("SAMLRequest={}&RelayState={}&SigAlg={}",
+            query_escape("fZBBa+MwEIX/ir/Jb0="), query_escape("/app home"), query_escape(ALG_RSA_SHA256));
+        let signature = SigningKey::<Sha256>::new(key.clone()).sign(unsigned.as_bytes());
+        let query = format!("{}&Signature={}", unsigned, query_escape(&Base64::encode_string(&signature.to_bytes())));
+        verify_redirect_query(&query, &key.to_public_key()).expect("signed query verifies");
+
+        let tampered = query.replace("app%20home", "evil");
+        assert!(verify_redirect_query(&tampered, &key.to_public_key()).is_err());
+        let duplicated = format!("{}&SAMLRequest=other", query);
+        assert!(verify_redirect_query(&duplicated, &key.to_public_key()).unwrap_err().contains("duplicate"));
+        assert!(verify_redirect_query(&unsigned, &key.to_public_key()).unwrap_err().contains("not signed"));
+    }
+
+    #[test]
+    fn test_signer_rejects_mismatched_certificate() {
+        let other = rcgen::KeyPair::generate().unwrap();
+        let cert = rcgen::CertificateParams::new(vec!["x".to_string()]).unwrap().self_signed(&other).unwrap();
+        assert!(SamlSigner::new(test_key(), &cert.pem()).is_err());
+    }
+
+    #[test]
+    fn test_encrypted_element_decrypts_with_recipient_key() {
+        let key = test_key();
+        let encrypted = encrypt_element("<saml:Assertion>x</saml:Assertion>", &key.to_public_key()).unwrap();
+        assert!(encrypted.contains(ALG_AES256_GCM) && encrypted.contains(ALG_RSA_OAEP));
+
+        let values: Vec<&str> = encrypted.split("<xenc:CipherValue>").skip(1)
+            .map(|s| &s[..s.find('<').unwrap()]).collect();
+        let wrapped = Base64::decode_vec(values[0]).unwrap();
+        let aes_key = key.decrypt(Oaep::new::<sha1::Sha1>(), &wrapped).unwrap();
+        let data = Base64::decode_vec(values[1]).unwrap();
+        let (nonce, ciphertext) = data.split_at(12);
+        let plain = Aes256Gcm::new_from_slice(&aes_key).unwrap()
+            .decrypt(nonce.into(), ciphertext).unwrap();
+        assert_eq!(plain, b"<saml:Assertion>x</saml:Assertion>");
+    }
+}
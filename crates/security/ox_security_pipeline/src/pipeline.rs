@@ -59,6 +59,9 @@ impl SecurityPipeline {
                     ox_security_core::MfaChallenge::CodeRequired { .. } => {
                         "code required".to_string()
                     }
+                    ox_security_core::MfaChallenge::WebAuthn { .. } => {
+                        "security key required".to_string()
+                    }
                 };
                 Err(SecurityError::MfaRequired(description))
             }
@@ -13,9 +13,9 @@ use uuid::Uuid;
 use ox_security_accounting::MemoryAccountingDriver;
 use ox_security_core::{
     AccountingDriver, AuthDriver, AuthPipelineContext, AuthResult, AuthzDriver, AuthzResult,
-    Credentials, GroupId, Principal, PrincipalId, TenantId, AuthSource,
+    Credentials, GroupId, Principal, PrincipalId, TenantId, AuthSource, USER_ID_ATTRIBUTE,
 };
-use crate::{SecurityPipeline, SecurityPipelineBuilder};
+use crate::{SecurityError, SecurityPipeline, SecurityPipelineBuilder};
 use ox_workflow_abi::{
     CoreHostApi, FlowControl, FLOW_CONTROL_CONTINUE, OX_LOG_ERROR, OX_LOG_INFO,
     OX_WORKFLOW_ABI_VERSION,
@@ -62,6 +62,8 @@ impl AuthDriver for ApiKeyAuthDriverSimple {
                 groups: groups.iter().map(|g| GroupId::new(g)).collect(),
                 tenant_id: TenantId::from(tenant.as_str()),
                 session_id: None,
+                // The configured ID is the key holder's; display names may be renamed
+                attributes: [(USER_ID_ATTRIBUTE.to_string(), pid.to_string())].into(),
             }),
             None => AuthResult::Continue,
         }
@@ -141,6 +143,40 @@ fn json_response(api: &CoreHostApi, task_ctx: *mut c_void, status: u16, body: &s
     set_field(api, task_ctx, "response.header.Content-Type", "application/json");
 }
 
+fn authenticate_api_key(state: &PluginState, key: String) -> Result<Principal, SecurityError> {
+    let creds = Credentials::ApiKey { key: secrecy::SecretString::new(key) };
+    let mut ctx = AuthPipelineContext {
+        partial_principal: None,
+        tenant_id: state.config.tenant_id.parse().unwrap_or_else(|_| TenantId::from("default")),
+        source_ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+    };
+    state.runtime.block_on(state.pipeline.authenticate(&creds, &mut ctx))
+}
+
+/// TaskState fields that tell later plugins who made the request:
+/// `security.principal` (JSON `Principal`) and `security.auth_time` (Unix seconds).
+fn principal_fields(principal: &Principal, auth_time: u64) -> [(&'static str, String); 2] {
+    [
+        ("security.principal", serde_json::to_string(principal).unwrap_or_default()),
+        ("security.auth_time", auth_time.to_string()),
+    ]
+}
+
+/// Authenticate a request carrying an `X-API-Key` header and publish its principal.
+/// A request without a valid key reaches later plugins unauthenticated.
+fn publish_principal(state: &PluginState, task_ctx: *mut c_void) {
+    let api_key = get_field(&state.api, task_ctx, "request.header.x-api-key");
+    if api_key.is_empty() { return; }
+    let Ok(principal) = authenticate_api_key(state, api_key) else { return };
+    let auth_time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    for (key, value) in principal_fields(&principal, auth_time) {
+        set_field(&state.api, task_ctx, key, &value);
+    }
+}
+
 fn handle_authenticate(state: &PluginState, task_ctx: *mut c_void, body: &str) {
     #[derive(Deserialize)]
     struct AuthBody { api_key: String }
@@ -152,13 +188,7 @@ fn handle_authenticate(state: &PluginState, task_ctx: *mut c_void, body: &str) {
             return;
         }
     };
-    let creds = Credentials::ApiKey { key: secrecy::SecretString::new(parsed.api_key) };
-    let mut ctx = AuthPipelineContext {
-        partial_principal: None,
-        tenant_id: state.config.tenant_id.parse().unwrap_or_else(|_| TenantId::from("default")),
-        source_ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
-    };
-    match state.runtime.block_on(state.pipeline.authenticate(&creds, &mut ctx)) {
+    match authenticate_api_key(state, parsed.api_key) {
         Ok(principal) => {
             let resp = serde_json::json!({
                 "data": {
@@ -304,6 +334,7 @@ pub extern "C" fn ox_plugin_process(plugin_ctx: *mut c_void, task_ctx: *mut c_vo
         let path   = get_field(&state.api, task_ctx, "request.path");
         let body   = get_field(&state.api, task_ctx, "request.body");
         let segs: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+        publish_principal(state, task_ctx);
         // Admin routes (/api/v1/admin/*) rely on the host workflow engine for perimeter
         // authentication — the persona YAML routes admin URLs only after the pipeline
         // authenticates the request. No per-handler auth check is needed here.
@@ -377,6 +408,12 @@ mod tests {
     extern "C" fn stub_get_keys(_: *mut c_void) -> *const c_char { std::ptr::null() }
     extern "C" fn stub_unset_field(_: *mut c_void, _: *const c_char) -> bool { false }
     extern "C" fn stub_has_field(_: *mut c_void, _: *const c_char) -> bool { false }
+    extern "C" fn stub_read_body(_: *mut c_void, _: *mut u8, _: usize) -> i64 { ox_workflow_abi::BODY_READ_UNAVAILABLE }
+    extern "C" fn stub_open_response_stream(_: *mut c_void) -> *mut c_void { std::ptr::null_mut() }
+    extern "C" fn stub_write_response_stream(_: *mut c_void, _: *const u8, _: usize) -> i64 { ox_workflow_abi::RESPONSE_WRITE_CLOSED }
+    extern "C" fn stub_close_response_stream(_: *mut c_void) {}
+    extern "C" fn stub_ws_send(_: *mut c_void, _: *const c_char, _: *const u8, _: usize, _: bool) -> bool { false }
+    extern "C" fn stub_ws_close(_: *mut c_void, _: *const c_char) -> bool { false }
 
     fn dummy_api() -> CoreHostApi {
         CoreHostApi {
@@ -395,6 +432,12 @@ mod tests {
             get_keys: stub_get_keys,
             unset_field: stub_unset_field,
             has_field: stub_has_field,
+            read_body: stub_read_body,
+            open_response_stream: stub_open_response_stream,
+            write_response_stream: stub_write_response_stream,
+            close_response_stream: stub_close_response_stream,
+            ws_send: stub_ws_send,
+            ws_close: stub_ws_close,
         }
     }
 
@@ -418,6 +461,7 @@ mod tests {
             groups: vec![],
             tenant_id: TenantId::from("test"),
             session_id: None,
+            attributes: Default::default(),
         }
     }
 
@@ -466,6 +510,21 @@ mod tests {
         assert_eq!(result.unwrap().display_name, "Test User");
     }
 
+    #[test]
+    fn test_published_principal_is_stable_across_logins() {
+        let state = make_state();
+        let first = authenticate_api_key(&state, "testkey".to_string()).unwrap();
+        let second = authenticate_api_key(&state, "testkey".to_string()).unwrap();
+        assert_eq!(first.stable_id(), "local:00000000-0000-0000-0000-000000000001");
+        assert_eq!(first.stable_id(), second.stable_id());
+
+        let [(principal_key, principal_json), (time_key, auth_time)] = principal_fields(&first, 1_700_000_000);
+        assert_eq!((principal_key, time_key), ("security.principal", "security.auth_time"));
+        let published: Principal = serde_json::from_str(&principal_json).unwrap();
+        assert_eq!(published.stable_id(), first.stable_id());
+        assert_eq!(auth_time, "1700000000");
+    }
+
     #[test]
     fn test_pipeline_rejects_unknown_api_key() {
         let state = make_state();
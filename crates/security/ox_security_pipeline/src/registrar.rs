@@ -1,4 +1,7 @@
+use std::path::Path;
 use std::sync::Mutex;
+use ox_security_authz::{PolicyAuthzDriver, Strictness};
+use ox_security_core::error::SecurityError;
 use ox_security_core::registration::{ContextDefinition, ContextRegistrar};
 use crate::pipeline::SecurityPipeline;
 
@@ -32,6 +35,14 @@ impl PipelineContextRegistrar {
     pub fn stored_registrations(&self) -> Vec<ContextDefinition> {
         self.registrations.lock().unwrap_or_else(|p| p.into_inner()).clone()
     }
+
+    /// Load the policy file at `path` into a `PolicyAuthzDriver`, validated
+    /// against the application root and every registration stored so far.
+    pub fn load_policy(&self, path: &Path, strictness: Strictness) -> Result<PolicyAuthzDriver, SecurityError> {
+        let mut contexts = vec![self.context_def];
+        contexts.extend(self.stored_registrations());
+        PolicyAuthzDriver::from_file(path, &contexts, strictness)
+    }
 }
 
 impl ContextRegistrar for PipelineContextRegistrar {
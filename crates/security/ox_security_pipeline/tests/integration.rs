@@ -60,6 +60,7 @@ impl ox_security_core::AuthDriver for AcceptsAliceDriver {
                     groups: vec![],
                     tenant_id: "test".parse().unwrap(),
                     session_id: None,
+                    attributes: Default::default(),
                 })
             }
             _ => AuthResult::Reject("bad credentials".to_string()),
@@ -118,6 +119,7 @@ fn test_principal() -> Principal {
         groups: vec![],
         tenant_id: test_tenant(),
         session_id: None,
+        attributes: Default::default(),
     }
 }
 
@@ -399,3 +401,24 @@ fn registrar_stores_registration() {
 
     assert_eq!(registrar.context_definition().root, "com.justlikeef.test");
 }
+
+#[test]
+fn registrar_validates_policy_against_registrations() {
+    use ox_security_authz::Strictness;
+
+    let pipeline = SecurityPipelineBuilder::new().build();
+    let registrar = PipelineContextRegistrar::new(pipeline, TEST_CONTEXT_DEF);
+    let path = std::env::temp_dir().join(format!("policy-{}.yaml", uuid::Uuid::new_v4()));
+    std::fs::write(&path, "roles:\n  - name: reader\n    permissions:\n      - operations: [read]\n        resource: com.justlikeef.test.objects\n").unwrap();
+
+    // The resource only exists once the object has registered its context.
+    assert!(registrar.load_policy(&path, Strictness::Strict).is_err());
+    registrar.load_policy(&path, Strictness::Permissive).unwrap();
+    registrar.register_context(REGISTERED_DEF);
+    registrar.load_policy(&path, Strictness::Strict).unwrap();
+
+    std::fs::write(&path, "roles:\n  - name: writer\n    permissions:\n      - operations: [write]\n").unwrap();
+    let err = registrar.load_policy(&path, Strictness::Permissive).err().unwrap();
+    assert!(err.to_string().contains("'write'"), "{err}");
+    std::fs::remove_file(&path).unwrap();
+}
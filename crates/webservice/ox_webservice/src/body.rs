@@ -0,0 +1,259 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use axum::body::{Body, Bytes};
+use axum::response::Response;
+use futures::StreamExt;
+use regex::Regex;
+use tokio::sync::{mpsc, oneshot};
+use ox_webservice_api::{BodyMode, DEFAULT_MAX_BODY_SIZE};
+use ox_workflow_core::{BodyReader, BodyReadError, ResponseStream, ResponseWriteError};
+
+/// Number of body chunks buffered between the connection and a streaming plugin.
+/// The connection is not read further until the plugin drains the channel.
+const STREAM_CHANNEL_DEPTH: usize = 8;
+
+/// Number of response chunks buffered between a streaming plugin and the connection.
+/// Writes block once the client falls this far behind.
+const RESPONSE_CHANNEL_DEPTH: usize = 8;
+
+/// Body settings declared by one route.
+/// The request body has to be dealt with before the workflow (and therefore the router
+/// plugin) runs, so the host keeps its own copy of the matcher fields needed to pick it.
+pub struct BodyPolicy {
+    priority: u16,
+    path: Option<Regex>,
+    method: Option<String>,
+    protocol: Option<String>,
+    hostname: Option<Regex>,
+    max_body_size: Option<u64>,
+    body_mode: Option<BodyMode>,
+}
+
+impl BodyPolicy {
+    pub fn new(
+        priority: u16,
+        path: &str,
+        method: Option<&str>,
+        protocol: Option<&str>,
+        hostname: Option<&str>,
+        max_body_size: Option<u64>,
+        body_mode: Option<BodyMode>,
+    ) -> Result<Self, String> {
+        let path = if path.is_empty() {
+            None
+        } else {
+            Some(Regex::new(path).map_err(|e| format!("Invalid path regex '{}': {}", path, e))?)
+        };
+        let hostname = match hostname {
+            Some(h) => Some(Regex::new(h).map_err(|e| format!("Invalid hostname regex '{}': {}", h, e))?),
+            None => None,
+        };
+        Ok(Self {
+            priority,
+            path,
+            method: method.map(|m| m.to_uppercase()),
+            protocol: protocol.map(|p| p.to_string()),
+            hostname,
+            max_body_size,
+            body_mode,
+        })
+    }
+
+    /// Same matching rules as `ox_webservice_router` for the fields known before the body is read.
+    fn matches(&self, protocol: &str, method: &str, host: &str, path: &str) -> bool {
+        self.protocol.as_ref().is_none_or(|p| p.eq_ignore_ascii_case(protocol))
+            && self.method.as_ref().is_none_or(|m| m.eq_ignore_ascii_case(method))
+            && self.hostname.as_ref().is_none_or(|re| re.is_match(host))
+            && self.path.as_ref().is_none_or(|re| re.is_match(path))
+    }
+}
+
+/// Effective body handling for a single request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodySettings {
+    pub mode: BodyMode,
+    pub max_body_size: u64,
+}
+
+impl Default for BodySettings {
+    fn default() -> Self {
+        Self { mode: BodyMode::Buffered, max_body_size: DEFAULT_MAX_BODY_SIZE }
+    }
+}
+
+/// All body policies of a flow, ordered by route priority.
+#[derive(Default)]
+pub struct BodyPolicies {
+    policies: Vec<BodyPolicy>,
+}
+
+impl BodyPolicies {
+    pub fn new(mut policies: Vec<BodyPolicy>) -> Self {
+        policies.sort_by_key(|p| p.priority);
+        Self { policies }
+    }
+
+    /// Resolves the settings for a request. The mode and the limit are each taken from the
+    /// lowest-priority matching route that declares them; anything undeclared uses the default.
+    pub fn resolve(&self, protocol: &str, method: &str, host: &str, path: &str) -> BodySettings {
+        let mut mode = None;
+        let mut max_body_size = None;
+        for policy in self.policies.iter().filter(|p| p.matches(protocol, method, host, path)) {
+            mode = mode.or(policy.body_mode);
+            max_body_size = max_body_size.or(policy.max_body_size);
+            if mode.is_some() && max_body_size.is_some() { break; }
+        }
+        let defaults = BodySettings::default();
+        BodySettings {
+            mode: mode.unwrap_or(defaults.mode),
+            max_body_size: max_body_size.unwrap_or(defaults.max_body_size),
+        }
+    }
+}
+
+/// Reads the whole body, failing with `TooLarge` as soon as more than `limit` bytes arrive.
+pub async fn collect_body(body: Body, limit: u64) -> Result<Bytes, BodyReadError> {
+    let mut stream = body.into_data_stream();
+    let mut buf: Vec<u8> = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| BodyReadError::Io(e.to_string()))?;
+        if (buf.len() + chunk.len()) as u64 > limit {
+            return Err(BodyReadError::TooLarge);
+        }
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(Bytes::from(buf))
+}
+
+/// Starts pumping `body` into a bounded channel and returns the reader handed to plugins.
+/// Must be called from within the tokio runtime.
+pub fn spawn_body_stream(body: Body, limit: u64) -> ChannelBodyReader {
+    let (tx, rx) = mpsc::channel(STREAM_CHANNEL_DEPTH);
+    tokio::spawn(async move {
+        let mut stream = body.into_data_stream();
+        let mut total: u64 = 0;
+        while let Some(chunk) = stream.next().await {
+            let item = match chunk {
+                Ok(bytes) => {
+                    total += bytes.len() as u64;
+                    if total > limit { Err(BodyReadError::TooLarge) } else { Ok(bytes) }
+                }
+                Err(e) => Err(BodyReadError::Io(e.to_string())),
+            };
+            let failed = item.is_err();
+            // A closed channel means the task finished without draining the body.
+            if tx.send(item).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+    ChannelBodyReader {
+        rx,
+        pending: Bytes::new(),
+        finished: false,
+        failure: None,
+        too_large: Arc::new(AtomicBool::new(false)),
+    }
+}
+
+/// Plugin-facing end of a streamed request body.
+/// Reads block the calling thread, so it must only be used from `spawn_blocking` contexts.
+pub struct ChannelBodyReader {
+    rx: mpsc::Receiver<Result<Bytes, BodyReadError>>,
+    pending: Bytes,
+    finished: bool,
+    failure: Option<BodyReadError>,
+    too_large: Arc<AtomicBool>,
+}
+
+impl ChannelBodyReader {
+    /// Flag raised once a plugin has read past the size limit; the host then answers 413.
+    pub fn too_large_flag(&self) -> Arc<AtomicBool> {
+        self.too_large.clone()
+    }
+}
+
+impl BodyReader for ChannelBodyReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, BodyReadError> {
+        while self.pending.is_empty() {
+            if let Some(e) = &self.failure {
+                return Err(e.clone());
+            }
+            if self.finished {
+                return Ok(0);
+            }
+            match self.rx.blocking_recv() {
+                Some(Ok(bytes)) => self.pending = bytes,
+                Some(Err(e)) => {
+                    if e == BodyReadError::TooLarge {
+                        self.too_large.store(true, Ordering::Release);
+                    }
+                    self.failure = Some(e);
+                }
+                None => self.finished = true,
+            }
+        }
+        let n = self.pending.len().min(buf.len());
+        buf[..n].copy_from_slice(&self.pending[..n]);
+        self.pending = self.pending.slice(n..);
+        Ok(n)
+    }
+}
+
+type ResponseHead = (u16, Vec<(String, String)>);
+
+/// Creates the response sink attached to a task, and the host-side end that turns it into
+/// an HTTP response once a plugin opens it.
+pub fn response_channel() -> (ChannelResponseStream, PendingResponse) {
+    let (head_tx, head_rx) = oneshot::channel();
+    let (body_tx, body_rx) = mpsc::channel(RESPONSE_CHANNEL_DEPTH);
+    (
+        ChannelResponseStream { head: Some(head_tx), body: body_tx },
+        PendingResponse { head: head_rx, body: body_rx },
+    )
+}
+
+/// Plugin-facing end of a streamed response.
+/// Writes block the calling thread, so it must only be used off the async worker threads.
+pub struct ChannelResponseStream {
+    head: Option<oneshot::Sender<ResponseHead>>,
+    body: mpsc::Sender<Bytes>,
+}
+
+impl ResponseStream for ChannelResponseStream {
+    fn open(&mut self, status: u16, headers: Vec<(String, String)>) -> Result<(), ResponseWriteError> {
+        let head = self.head.take().ok_or(ResponseWriteError::Closed)?;
+        head.send((status, headers)).map_err(|_| ResponseWriteError::Closed)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), ResponseWriteError> {
+        if self.head.is_some() {
+            return Err(ResponseWriteError::Closed);
+        }
+        self.body.blocking_send(Bytes::copy_from_slice(data)).map_err(|_| ResponseWriteError::Closed)
+    }
+}
+
+/// Host end of a streamed response.
+pub struct PendingResponse {
+    head: oneshot::Receiver<ResponseHead>,
+    body: mpsc::Receiver<Bytes>,
+}
+
+impl PendingResponse {
+    /// Resolves once the stream is opened, with a response whose body follows the plugin's
+    /// writes and ends when the stream is closed. `None` if the sink was dropped unopened.
+    pub async fn opened(self) -> Option<Response> {
+        let (status, headers) = self.head.await.ok()?;
+        let chunks = futures::stream::unfold(self.body, |mut rx| async move {
+            rx.recv().await.map(|chunk| (Ok::<_, std::convert::Infallible>(chunk), rx))
+        });
+        let mut res = Response::builder().status(status);
+        for (k, v) in headers {
+            res = res.header(k, v);
+        }
+        Some(res.body(Body::from_stream(chunks)).unwrap_or_else(|_| {
+            Response::builder().status(500).body(Body::from("Internal Error")).unwrap()
+        }))
+    }
+}
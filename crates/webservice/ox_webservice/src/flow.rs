@@ -2,14 +2,18 @@ use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::net::SocketAddr;
 use axum::http::Request;
-use axum::body::{Body, to_bytes};
+use axum::body::Body;
 use axum::response::Response;
 use ox_workflow_core::Task;
 use ox_workflow_core::{FlowDef, PluginDef};
 use ox_workflow_executor::{FlowManager, create_host_api, FlowRunner};
 use ox_workflow_executor::plugin_registry::PluginInstance;
-use ox_workflow_abi::{CoreHostApi, FLOW_CONTROL_STREAM_FILE};
+use ox_workflow_abi::{CoreHostApi, FLOW_CONTROL_STREAM_FILE, FLOW_CONTROL_STREAM_RESPONSE};
+use ox_webservice_api::BodyMode;
+use ox_workflow_core::BodyReadError;
 use crate::ServerConfig;
+use crate::body::{BodyPolicies, BodyPolicy, collect_body, response_channel, spawn_body_stream};
+use crate::ws::{Outbound, WsRegistry};
 use tokio::sync::RwLock;
 
 pub struct Flow {
@@ -20,12 +24,27 @@ pub struct Flow {
     /// Limits the number of plugin pipelines executing concurrently.
     /// Excess requests wait as cheap async futures rather than spawning threads.
     pub plugin_semaphore: Arc<tokio::sync::Semaphore>,
+    /// Per-route body mode and size limits, resolved before the workflow runs.
+    pub body_policies: BodyPolicies,
+    /// Runs `websocket.open_stages` when a WebSocket connection is established.
+    pub ws_open_flow: Option<Arc<FlowRunner>>,
+    /// Runs `websocket.close_stages` after a WebSocket connection ends.
+    pub ws_close_flow: Option<Arc<FlowRunner>>,
+    /// Live WebSocket connections, shared across reloads.
+    pub connections: Arc<WsRegistry>,
     /// Holds initialized contexts for phase=Init modules (startup-only plugins).
     /// Kept alive until the Flow is dropped so ox_plugin_destroy is called on shutdown.
     #[allow(dead_code)]
     init_plugins: Vec<PluginInstance>,
 }
 
+/// Upgrade request details carried into a WebSocket session.
+pub struct WsHandshake {
+    pub path: String,
+    pub query: String,
+    pub headers: axum::http::HeaderMap,
+}
+
 /// Normalized route entry used during flow construction.
 struct EffectiveRoute {
     stage: String,
@@ -38,6 +57,8 @@ struct EffectiveRoute {
     protocol: Option<String>,
     hostname: Option<String>,
     status_code: Option<String>,
+    max_body_size: Option<u64>,
+    body_mode: Option<BodyMode>,
 }
 
 impl Flow {
@@ -69,6 +90,12 @@ impl Flow {
             (id, stage)
         }).collect();
 
+        // module_id → module-level body settings, used when a route does not set its own.
+        let module_body_map: HashMap<String, (Option<u64>, Option<BodyMode>)> = config.modules.iter().map(|m| {
+            let id = m.id.clone().unwrap_or_else(|| m.name.clone());
+            (id, (m.max_body_size, m.body_mode))
+        }).collect();
+
         // 1. Top-level routes
         for r in &config.routes {
             let module_id = r.module_id.clone().unwrap_or_default();
@@ -77,6 +104,7 @@ impl Flow {
             let route_stage = r.stage.as_deref();
             let module_stage = module_stage_map.get(&module_id).and_then(|p| p.as_deref());
             let stage = route_stage.or(module_stage).unwrap_or("Content").to_string();
+            let (module_max_body, module_body_mode) = module_body_map.get(&module_id).copied().unwrap_or_default();
 
             all_routes.push(EffectiveRoute {
                 stage,
@@ -89,6 +117,8 @@ impl Flow {
                 protocol: r.protocol.clone(),
                 hostname: r.hostname.clone(),
                 status_code: r.status_code.clone(),
+                max_body_size: r.max_body_size.or(module_max_body),
+                body_mode: r.body_mode.or(module_body_mode),
             });
         }
 
@@ -117,11 +147,28 @@ impl Flow {
                         protocol: r.protocol.clone(),
                         hostname: r.hostname.clone(),
                         status_code: r.status_code.clone(),
+                        max_body_size: r.max_body_size.or(mod_cfg.max_body_size),
+                        body_mode: r.body_mode.or(mod_cfg.body_mode),
                     });
                 }
             }
         }
 
+        // Routes that declare body settings. The body is read (or handed off for streaming)
+        // before any stage runs, so these are matched by the host rather than the router.
+        let mut body_policies: Vec<BodyPolicy> = Vec::new();
+        for r in all_routes.iter().filter(|r| r.max_body_size.is_some() || r.body_mode.is_some()) {
+            body_policies.push(BodyPolicy::new(
+                r.priority,
+                &r.path,
+                r.method.as_deref(),
+                r.protocol.as_deref(),
+                r.hostname.as_deref(),
+                r.max_body_size,
+                r.body_mode,
+            ).map_err(|e| format!("Route for module '{}': {}", r.module_id, e))?);
+        }
+
         // ------------------------------------------------------------------
         // Group routes by stage and sort each group by priority (ascending).
         // Lower priority number = higher precedence = checked first by router.
@@ -152,6 +199,18 @@ impl Flow {
             .map(|w| w.stages.clone())
             .unwrap_or_default();
 
+        // WebSocket lifecycle stages only run for open/close events, never in the HTTP pipeline.
+        let ws_config = config.websocket.clone().unwrap_or_default();
+        let workflow_stage_names: HashSet<&str> = config.workflow.as_ref()
+            .map(|w| w.stages.iter().map(|s| s.name.as_str()).collect())
+            .unwrap_or_default();
+        for name in ws_config.open_stages.iter().chain(&ws_config.close_stages) {
+            if !workflow_stage_names.contains(name.as_str()) {
+                return Err(format!("WebSocket lifecycle stage '{}' is not defined in workflow.stages", name));
+            }
+        }
+        let lifecycle_stages: HashSet<&String> = ws_config.open_stages.iter().chain(&ws_config.close_stages).collect();
+
         let mut final_stages: Vec<String> = Vec::new();
         // Collect router configs per stage so we can build the post-injection config JSON later.
         let mut stage_router_configs: HashMap<String, serde_json::Value> = HashMap::new();
@@ -263,7 +322,9 @@ impl Flow {
                 });
             }
 
-            final_stages.push(stage.name.clone());
+            if !lifecycle_stages.contains(&stage.name) {
+                final_stages.push(stage.name.clone());
+            }
             manager.stage_defs.insert(stage.name.clone(), stage);
         }
 
@@ -319,6 +380,9 @@ impl Flow {
             name: "http_flow".to_string(),
             persistent: false,
             stages: final_stages,
+            retry: None,
+            deadline_ms: None,
+            dead_letter_queue: None,
         };
 
         let main_flow = unsafe {
@@ -326,6 +390,25 @@ impl Flow {
                 .map_err(|e| format!("Failed to build flow: {:?}", e))?
         };
 
+        let mut build_lifecycle_flow = |name: &str, stages: &[String]| -> Result<Option<Arc<FlowRunner>>, String> {
+            if stages.is_empty() {
+                return Ok(None);
+            }
+            let def = FlowDef {
+                name: name.to_string(),
+                persistent: false,
+                stages: stages.to_vec(),
+                retry: None,
+                deadline_ms: None,
+                dead_letter_queue: None,
+            };
+            unsafe { manager.build_flow(&def, &api, &plugin_paths) }
+                .map(Some)
+                .map_err(|e| format!("Failed to build {} flow: {:?}", name, e))
+        };
+        let ws_open_flow = build_lifecycle_flow("ws_open", &ws_config.open_stages)?;
+        let ws_close_flow = build_lifecycle_flow("ws_close", &ws_config.close_stages)?;
+
         // Run phase=Init modules: load and initialize them once at startup.
         // Their ox_plugin_process is a no-op; all work is done in ox_plugin_init.
         // Contexts are kept alive until Flow is dropped so ox_plugin_destroy fires.
@@ -383,6 +466,10 @@ impl Flow {
             main_flow,
             api,
             plugin_semaphore: Arc::new(tokio::sync::Semaphore::new(concurrency)),
+            body_policies: BodyPolicies::new(body_policies),
+            ws_open_flow,
+            ws_close_flow,
+            connections: crate::ws::connections(),
             init_plugins,
         })
     }
@@ -394,7 +481,7 @@ impl Flow {
 
         {
             let mut w = task.state.write();
-            w.fields.insert("request.protocol".to_string(), ox_workflow_core::state::FieldValue::String(protocol));
+            w.fields.insert("request.protocol".to_string(), ox_workflow_core::state::FieldValue::String(protocol.clone()));
             w.fields.insert("request.method".to_string(), ox_workflow_core::state::FieldValue::String(parts.method.to_string()));
             w.fields.insert("request.path".to_string(), ox_workflow_core::state::FieldValue::String(parts.uri.path().to_string()));
             w.fields.insert("request.query".to_string(), ox_workflow_core::state::FieldValue::String(parts.uri.query().unwrap_or("").to_string()));
@@ -416,38 +503,79 @@ impl Flow {
             w.fields.insert("response.body".to_string(), ox_workflow_core::state::FieldValue::String("".to_string()));
         }
 
-        // Read body bytes. Write to a temp file for binary-safe access (file uploads etc),
-        // and also store as a lossy UTF-8 string for text-based modules.
-        let body_tmp_path: Option<std::path::PathBuf> = if let Ok(bytes) = to_bytes(body, 1024 * 1024 * 10).await {
-            task.state.write().fields.insert(
-                "request.body".to_string(),
-                ox_workflow_core::state::FieldValue::String(String::from_utf8_lossy(&bytes).to_string()),
-            );
-            if !bytes.is_empty() {
-                let tmp_path = std::env::temp_dir().join(format!(
-                    "ox_body_{}_{}",
-                    std::process::id(),
-                    std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .map(|d| d.as_nanos())
-                        .unwrap_or(0)
-                ));
-                if std::fs::write(&tmp_path, &bytes).is_ok() {
-                    task.state.write().fields.insert(
-                        "request.body_path".to_string(),
-                        ox_workflow_core::state::FieldValue::String(tmp_path.to_string_lossy().to_string()),
-                    );
-                    Some(tmp_path)
-                } else {
-                    None
-                }
-            } else {
+        let host = parts.headers.get(axum::http::header::HOST)
+            .and_then(|v| v.to_str().ok())
+            .map(|h| h.to_string())
+            .or_else(|| parts.uri.authority().map(|a| a.to_string()))
+            .unwrap_or_default();
+        let body_settings = self.body_policies.resolve(&protocol, parts.method.as_str(), &host, parts.uri.path());
+
+        // Reject early when the client announces a body larger than the route allows.
+        let declared_len = parts.headers.get(axum::http::header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+        if declared_len.is_some_and(|len| len > body_settings.max_body_size) {
+            return payload_too_large();
+        }
+
+        let mut body_too_large = None;
+        let body_tmp_path: Option<std::path::PathBuf> = match body_settings.mode {
+            BodyMode::Stream => {
+                // Plugins pull the body through CoreHostApi::read_body; nothing is buffered here.
+                let reader = spawn_body_stream(body, body_settings.max_body_size);
+                body_too_large = Some(reader.too_large_flag());
+                task.state.write().fields.insert(
+                    "request.body_mode".to_string(),
+                    ox_workflow_core::state::FieldValue::String("stream".to_string()),
+                );
+                task.body_reader = Some(Box::new(reader));
                 None
             }
-        } else {
-            None
+            BodyMode::Buffered => {
+                // Read body bytes. Write to a temp file for binary-safe access (file uploads etc),
+                // and also store as a lossy UTF-8 string for text-based modules.
+                match collect_body(body, body_settings.max_body_size).await {
+                    Ok(bytes) => {
+                        task.state.write().fields.insert(
+                            "request.body".to_string(),
+                            ox_workflow_core::state::FieldValue::String(String::from_utf8_lossy(&bytes).to_string()),
+                        );
+                        if !bytes.is_empty() {
+                            let tmp_path = std::env::temp_dir().join(format!(
+                                "ox_body_{}_{}",
+                                std::process::id(),
+                                std::time::SystemTime::now()
+                                    .duration_since(std::time::UNIX_EPOCH)
+                                    .map(|d| d.as_nanos())
+                                    .unwrap_or(0)
+                            ));
+                            if std::fs::write(&tmp_path, &bytes).is_ok() {
+                                task.state.write().fields.insert(
+                                    "request.body_path".to_string(),
+                                    ox_workflow_core::state::FieldValue::String(tmp_path.to_string_lossy().to_string()),
+                                );
+                                Some(tmp_path)
+                            } else {
+                                None
+                            }
+                        } else {
+                            None
+                        }
+                    }
+                    Err(BodyReadError::TooLarge) => return payload_too_large(),
+                    Err(BodyReadError::Io(e)) => {
+                        log::debug!("Failed to read request body from {}: {}", addr, e);
+                        None
+                    }
+                }
+            }
         };
 
+        // Plugins may take over the response with CoreHostApi::open_response_stream.
+        let (response_stream, pending_response) = response_channel();
+        task.response_stream = Some(Box::new(response_stream));
+        task.connections = Some(self.connections.clone());
+
         // Acquire a concurrency slot before dispatching. Excess requests wait here as
         // cheap async futures rather than spawning unbounded blocking threads.
         let permit = Arc::clone(&self.plugin_semaphore).acquire_owned().await
@@ -458,13 +586,42 @@ impl Flow {
         enum PluginResult {
             StreamFile { file_path: String, status_code: u16, response_headers: Vec<(String, String)> },
             Response { status_code: u16, response_headers: HashMap<String, String>, body_bytes: axum::body::Bytes },
+            /// A plugin opened the response stream; the response was already handed to the client.
+            Streamed,
         }
 
         let flow = Arc::clone(&self);
-        let plugin_result = tokio::task::spawn_blocking(move || {
+        let pipeline = tokio::task::spawn_blocking(move || {
             let _permit = permit; // released when this closure returns
             let last_fc = flow.main_flow.run(&mut task, &flow.api);
 
+            // A plugin read past the route's body limit: whatever it produced is discarded.
+            if body_too_large.is_some_and(|f| f.load(std::sync::atomic::Ordering::Acquire)) {
+                if last_fc.code == FLOW_CONTROL_STREAM_FILE && !last_fc.payload.is_null() {
+                    let _ = unsafe { std::ffi::CString::from_raw(last_fc.payload as *mut std::ffi::c_char) };
+                }
+                return PluginResult::Response {
+                    status_code: 413,
+                    response_headers: HashMap::new(),
+                    body_bytes: axum::body::Bytes::from("Payload Too Large"),
+                };
+            }
+
+            if last_fc.code == FLOW_CONTROL_STREAM_RESPONSE && task.response_stream.is_some() {
+                log::warn!("Flow ended with STREAM_RESPONSE but no plugin opened the response stream");
+                return PluginResult::Response {
+                    status_code: 500,
+                    response_headers: HashMap::new(),
+                    body_bytes: axum::body::Bytes::from("Internal Error"),
+                };
+            }
+            if task.response_stream.is_none() {
+                if let Some(path) = body_tmp_path {
+                    let _ = std::fs::remove_file(path);
+                }
+                return PluginResult::Streamed;
+            }
+
             if last_fc.code == FLOW_CONTROL_STREAM_FILE && !last_fc.payload.is_null() {
                 // Extract and free the heap-allocated file path before leaving this thread.
                 let file_path = unsafe { std::ffi::CStr::from_ptr(last_fc.payload) }.to_string_lossy().to_string();
@@ -528,7 +685,17 @@ impl Flow {
             }
 
             PluginResult::Response { status_code, response_headers, body_bytes }
-        }).await.unwrap_or_else(|_| PluginResult::Response {
+        });
+
+        // A streamed response starts as soon as the plugin opens it, while the pipeline is still
+        // running; the pipeline then finishes in the background.
+        let opened = pending_response.opened();
+        tokio::pin!(opened, pipeline);
+        let plugin_result = tokio::select! {
+            biased;
+            Some(res) = &mut opened => return res,
+            result = &mut pipeline => result,
+        }.unwrap_or_else(|_| PluginResult::Response {
             status_code: 500,
             response_headers: HashMap::new(),
             body_bytes: axum::body::Bytes::from("Internal Error"),
@@ -562,50 +729,138 @@ impl Flow {
                     Response::builder().status(500).body(Body::from("Internal Error")).unwrap()
                 })
             }
+            PluginResult::Streamed => {
+                // Opening the stream queues the head before the pipeline can finish, and the
+                // select above prefers it, so this is only reached if the head was lost.
+                Response::builder().status(500).body(Body::from("Internal Error")).unwrap()
+            }
         }
     }
 
-    pub async fn handle_socket(self: Arc<Self>, mut socket: axum::extract::ws::WebSocket, addr: SocketAddr, path: String, ws_protocol: String) {
+    /// Runs one WebSocket session. Every text or binary frame becomes a task on the main flow
+    /// (`ws.event` = "message"); a non-empty `response.body` is sent back as a frame of the
+    /// same type. Plugins and the event bus can push at any time via the connection registry.
+    pub async fn handle_socket(self: Arc<Self>, socket: axum::extract::ws::WebSocket, addr: SocketAddr, handshake: WsHandshake, ws_protocol: String) {
         use axum::extract::ws::Message;
+        use futures::{SinkExt, StreamExt};
+
+        let connection_id = uuid::Uuid::new_v4().to_string();
+        let mut outbound = self.connections.register(&connection_id);
+        let (mut sink, mut inbound) = socket.split();
+
+        // Single writer: replies, plugin pushes and bus pushes are all serialized here.
+        let writer = tokio::spawn(async move {
+            while let Some(item) = outbound.recv().await {
+                match item {
+                    Outbound::Frame(frame) => {
+                        if sink.send(frame).await.is_err() { break; }
+                    }
+                    Outbound::Close => {
+                        let _ = sink.send(Message::Close(None)).await;
+                        break;
+                    }
+                }
+            }
+        });
 
-        while let Some(Ok(msg)) = socket.recv().await {
-            let text = match msg {
-                Message::Text(t) => t,
-                Message::Close(_) => break,
-                _ => continue,
-            };
+        let session = WsSession { connection_id, addr, handshake, ws_protocol };
 
-            let mut task = Task::new(1);
-            {
-                let mut w = task.state.write();
-                w.fields.insert("request.protocol".to_string(), ox_workflow_core::state::FieldValue::String(ws_protocol.clone()));
-                w.fields.insert("request.method".to_string(), ox_workflow_core::state::FieldValue::String("GET".to_string()));
-                w.fields.insert("request.path".to_string(), ox_workflow_core::state::FieldValue::String(format!("/{}", path)));
-                w.fields.insert("request.query".to_string(), ox_workflow_core::state::FieldValue::String(String::new()));
-                w.fields.insert("request.source_ip".to_string(), ox_workflow_core::state::FieldValue::String(addr.ip().to_string()));
-                w.fields.insert("request.header.accept".to_string(), ox_workflow_core::state::FieldValue::String("application/json".to_string()));
-                w.fields.insert("request.body".to_string(), ox_workflow_core::state::FieldValue::String(text));
-                w.fields.insert("response.status".to_string(), ox_workflow_core::state::FieldValue::String("500".to_string()));
-                w.fields.insert("response.body".to_string(), ox_workflow_core::state::FieldValue::String(String::new()));
+        let mut accepted = true;
+        if let Some(open_flow) = self.ws_open_flow.clone() {
+            let task = self.ws_task(&session, "open", None);
+            let (code, _) = self.run_ws_task(open_flow, task).await;
+            if code == ox_workflow_abi::FLOW_CONTROL_ERROR {
+                log::info!("WebSocket {} rejected by open stages", session.connection_id);
+                self.connections.push(&session.connection_id, Outbound::Close);
+                accepted = false;
             }
+        }
 
-            let permit = Arc::clone(&self.plugin_semaphore).acquire_owned().await
-                .unwrap_or_else(|_| panic!("plugin semaphore closed"));
-
-            let flow = Arc::clone(&self);
-            let body = tokio::task::spawn_blocking(move || {
-                let _permit = permit;
-                let _last_fc = flow.main_flow.run(&mut task, &flow.api);
-                let r = task.state.read();
-                r.fields.get("response.body")
-                    .map(|f| match f {
-                        ox_workflow_core::state::FieldValue::String(s) => s.clone(),
-                        ox_workflow_core::state::FieldValue::Bytes(b) => String::from_utf8_lossy(b).into_owned(),
-                    })
-                    .unwrap_or_default()
-            }).await.unwrap_or_default();
-
-            let _ = socket.send(Message::Text(body)).await;
+        if accepted {
+            loop {
+                let body = match inbound.next().await {
+                    Some(Ok(Message::Text(t))) => ox_workflow_core::state::FieldValue::String(t),
+                    Some(Ok(Message::Binary(b))) => ox_workflow_core::state::FieldValue::Bytes(b),
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue, // ping/pong are answered by axum
+                };
+                // Stop reading once the writer is gone (ws_close or a failed send).
+                if writer.is_finished() { break; }
+
+                let task = self.ws_task(&session, "message", Some(body));
+                let (_, reply) = self.run_ws_task(self.main_flow.clone(), task).await;
+                let frame = match reply {
+                    Some(ox_workflow_core::state::FieldValue::String(s)) if !s.is_empty() => Message::Text(s),
+                    Some(ox_workflow_core::state::FieldValue::Bytes(b)) if !b.is_empty() => Message::Binary(b),
+                    _ => continue,
+                };
+                if !self.connections.push(&session.connection_id, Outbound::Frame(frame)) {
+                    break;
+                }
+            }
         }
+
+        // Dropping the registry entry closes the queue; the writer flushes what is left and exits.
+        self.connections.unregister(&session.connection_id);
+        let _ = writer.await;
+
+        if let Some(close_flow) = self.ws_close_flow.clone() {
+            let task = self.ws_task(&session, "close", None);
+            let _ = self.run_ws_task(close_flow, task).await;
+        }
+    }
+
+    /// Builds the task for one WebSocket event, carrying the upgrade request's path and headers.
+    fn ws_task(&self, session: &WsSession, event: &str, body: Option<ox_workflow_core::state::FieldValue>) -> Task {
+        use ox_workflow_core::state::FieldValue;
+        let mut task = Task::new(1);
+        task.connections = Some(self.connections.clone());
+        {
+            let mut w = task.state.write();
+            w.fields.insert("request.protocol".to_string(), FieldValue::String(session.ws_protocol.clone()));
+            w.fields.insert("request.method".to_string(), FieldValue::String("GET".to_string()));
+            w.fields.insert("request.path".to_string(), FieldValue::String(session.handshake.path.clone()));
+            w.fields.insert("request.query".to_string(), FieldValue::String(session.handshake.query.clone()));
+            w.fields.insert("request.source_ip".to_string(), FieldValue::String(session.addr.ip().to_string()));
+            for (k, v) in session.handshake.headers.iter() {
+                w.fields.insert(format!("request.header.{}", k.as_str()), FieldValue::String(v.to_str().unwrap_or("").to_string()));
+            }
+            w.fields.insert("ws.connection_id".to_string(), FieldValue::String(session.connection_id.clone()));
+            w.fields.insert("ws.event".to_string(), FieldValue::String(event.to_string()));
+            if let Some(body) = body {
+                let message_type = if matches!(body, FieldValue::Bytes(_)) { "binary" } else { "text" };
+                w.fields.insert("ws.message_type".to_string(), FieldValue::String(message_type.to_string()));
+                w.fields.insert("request.body".to_string(), body);
+            }
+            w.fields.insert("response.status".to_string(), FieldValue::String("500".to_string()));
+            w.fields.insert("response.body".to_string(), FieldValue::String(String::new()));
+        }
+        task
     }
+
+    /// Runs a WebSocket task off the async workers and returns its final code and `response.body`.
+    async fn run_ws_task(self: &Arc<Self>, runner: Arc<FlowRunner>, mut task: Task) -> (u8, Option<ox_workflow_core::state::FieldValue>) {
+        let permit = Arc::clone(&self.plugin_semaphore).acquire_owned().await
+            .unwrap_or_else(|_| panic!("plugin semaphore closed"));
+
+        let flow = Arc::clone(self);
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            let fc = runner.run(&mut task, &flow.api);
+            let body = task.state.read().fields.get("response.body").cloned();
+            (fc.code, body)
+        }).await.unwrap_or((ox_workflow_abi::FLOW_CONTROL_ERROR, None))
+    }
+}
+
+/// Identity of one WebSocket session.
+struct WsSession {
+    connection_id: String,
+    addr: SocketAddr,
+    handshake: WsHandshake,
+    ws_protocol: String,
+}
+
+fn payload_too_large() -> Response {
+    Response::builder().status(413).body(Body::from("Payload Too Large")).unwrap()
 }
@@ -1,10 +1,12 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::Path;
-pub use ox_webservice_api::{ModuleConfig, UriMatcher};
+pub use ox_webservice_api::{BodyMode, ModuleConfig, UriMatcher};
 pub use ox_workflow_core::StageDef;
 
+pub mod body;
 pub mod flow;
+pub mod ws;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct HostConfig {
@@ -43,6 +45,28 @@ pub struct WorkflowConfig {
     pub stages: Vec<StageDef>,
 }
 
+/// WebSocket session settings.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct WebSocketConfig {
+    /// Workflow stages run once when a connection opens (`ws.event` = "open").
+    /// They are removed from the HTTP pipeline. A flow error closes the connection.
+    #[serde(default)]
+    pub open_stages: Vec<String>,
+    /// Workflow stages run once after a connection closes (`ws.event` = "close").
+    #[serde(default)]
+    pub close_stages: Vec<String>,
+    /// Event bus subscription whose messages are pushed to connected clients.
+    pub push: Option<WebSocketPushConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebSocketPushConfig {
+    pub topic: String,
+    /// Event bus to subscribe on (`provider`, `host`, `port`, ...), built by `EventBusFactory`.
+    #[serde(flatten)]
+    pub bus: ox_messaging_client::ClientConfig,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UrlRoute {
     pub protocol: Option<String>,
@@ -58,6 +82,10 @@ pub struct UrlRoute {
     pub stage: Option<String>,
     pub module_id: Option<String>,
     pub status_code: Option<String>,
+    #[serde(default)]
+    pub max_body_size: Option<u64>,
+    #[serde(default)]
+    pub body_mode: Option<BodyMode>,
 }
 
 
@@ -77,6 +105,8 @@ pub struct ServerConfig {
     #[serde(default)]
     pub merge_recursive: Option<Vec<String>>,
     pub enable_metrics: Option<bool>,
+    #[serde(default)]
+    pub websocket: Option<WebSocketConfig>,
 }
 
 pub fn load_config_from_path(path: &Path, _log_level: &str) -> Result<(ServerConfig, String), String> {
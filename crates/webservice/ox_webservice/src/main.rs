@@ -25,7 +25,7 @@ use log4rs::config::{Appender, Config as LogConfig, Root};
 use log4rs::append::console::ConsoleAppender;
 use log4rs::encode::pattern::PatternEncoder;
 
-use ox_webservice::{ServerConfig, load_config_from_path, flow::Flow};
+use ox_webservice::{ServerConfig, load_config_from_path, flow::{Flow, WsHandshake}};
 
 #[derive(Debug)]
 struct CustomCertResolver {
@@ -132,6 +132,24 @@ async fn start_server(initial_config: ServerConfig, config_path: PathBuf, config
     };
 
     let flow_holder = Arc::new(RwLock::new(flow));
+
+    // Push event bus messages to WebSocket clients. The registry is process-wide, so this
+    // keeps working across configuration reloads.
+    if let Some(push) = initial_config.websocket.as_ref().and_then(|w| w.push.clone()) {
+        tokio::spawn(async move {
+            let bus = match ox_messaging_client::EventBusFactory::create(push.bus).await {
+                Ok(bus) => bus,
+                Err(e) => {
+                    error!("WebSocket push bridge could not connect to the event bus: {}", e);
+                    return;
+                }
+            };
+            info!("Pushing '{}' events to WebSocket clients", push.topic);
+            if let Err(e) = ox_webservice::ws::run_push_bridge(bus, &push.topic, ox_webservice::ws::connections()).await {
+                error!("WebSocket push bridge stopped: {}", e);
+            }
+        });
+    }
     let flow_holder_clone = flow_holder.clone();
     let config_path_clone = config_path.clone();
 
@@ -184,8 +202,13 @@ async fn start_server(initial_config: ServerConfig, config_path: PathBuf, config
                     
                     if let Some(ws_upgrade) = ws {
                         let ws_protocol = if protocol_clone == "https" { "WSS".to_string() } else { "WS".to_string() };
+                        let handshake = WsHandshake {
+                            path: req.uri().path().to_string(),
+                            query: req.uri().query().unwrap_or("").to_string(),
+                            headers: req.headers().clone(),
+                        };
                         return ws_upgrade.on_upgrade(move |socket| async move {
-                            flow_arc.handle_socket(socket, connect_info, "".to_string(), ws_protocol).await;
+                            flow_arc.handle_socket(socket, connect_info, handshake, ws_protocol).await;
                         });
                     }
                     
@@ -195,15 +218,20 @@ async fn start_server(initial_config: ServerConfig, config_path: PathBuf, config
             .route("/*path", axum::routing::any({
                 let flow_holder_server = flow_holder_server.clone();
                 let protocol_clone = protocol.clone();
-                move |ws: Option<axum::extract::ws::WebSocketUpgrade>, axum::extract::Path(path): axum::extract::Path<String>, req: Request<Body>| async move {
+                move |ws: Option<axum::extract::ws::WebSocketUpgrade>, req: Request<Body>| async move {
                     let flow_arc = flow_holder_server.read().await.clone();
                     let connect_info = req.extensions().get::<ConnectInfo<SocketAddr>>().map(|ci| ci.0).unwrap_or(SocketAddr::from(([0, 0, 0, 0], 0)));
                     let protocol_clone = protocol_clone.clone();
                     
                     if let Some(ws_upgrade) = ws {
                         let ws_protocol = if protocol_clone == "https" { "WSS".to_string() } else { "WS".to_string() };
+                        let handshake = WsHandshake {
+                            path: req.uri().path().to_string(),
+                            query: req.uri().query().unwrap_or("").to_string(),
+                            headers: req.headers().clone(),
+                        };
                         return ws_upgrade.on_upgrade(move |socket| async move {
-                            flow_arc.handle_socket(socket, connect_info, path, ws_protocol).await;
+                            flow_arc.handle_socket(socket, connect_info, handshake, ws_protocol).await;
                         });
                     }
                     
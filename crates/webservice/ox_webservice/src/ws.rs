@@ -0,0 +1,110 @@
+use std::sync::Arc;
+use axum::extract::ws::Message;
+use dashmap::DashMap;
+use futures::StreamExt;
+use once_cell::sync::Lazy;
+use ox_event_bus::{BusError, EventBus};
+use ox_workflow_core::ConnectionHub;
+use tokio::sync::mpsc;
+
+/// Bus message header naming the target connection. Messages without it are broadcast.
+pub const PUSH_CONNECTION_HEADER: &str = "connection_id";
+/// Bus message header; `"true"` sends the payload as a binary frame instead of text.
+pub const PUSH_BINARY_HEADER: &str = "binary";
+
+/// Work queued for a connection's writer by plugins or the event bus.
+#[derive(Debug)]
+pub enum Outbound {
+    Frame(Message),
+    Close,
+}
+
+/// Live WebSocket connections keyed by `ws.connection_id`.
+/// Queues are unbounded so a plugin pushing to its own connection can never block on it.
+#[derive(Default)]
+pub struct WsRegistry {
+    connections: DashMap<String, mpsc::UnboundedSender<Outbound>>,
+}
+
+impl WsRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a connection and returns the queue its writer drains.
+    pub fn register(&self, connection_id: &str) -> mpsc::UnboundedReceiver<Outbound> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.connections.insert(connection_id.to_string(), tx);
+        rx
+    }
+
+    pub fn unregister(&self, connection_id: &str) {
+        self.connections.remove(connection_id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.connections.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.connections.is_empty()
+    }
+
+    /// Queues an item for one connection. Returns false if it is unknown or its writer is gone.
+    pub fn push(&self, connection_id: &str, item: Outbound) -> bool {
+        self.connections.get(connection_id).is_some_and(|c| c.value().send(item).is_ok())
+    }
+
+    /// Sends a frame to every open connection and returns how many accepted it.
+    pub fn broadcast(&self, data: &[u8], binary: bool) -> usize {
+        self.connections.iter()
+            .filter(|c| c.value().send(Outbound::Frame(frame(data, binary))).is_ok())
+            .count()
+    }
+}
+
+impl ConnectionHub for WsRegistry {
+    fn send(&self, connection_id: &str, data: &[u8], binary: bool) -> bool {
+        self.push(connection_id, Outbound::Frame(frame(data, binary)))
+    }
+
+    fn close(&self, connection_id: &str) -> bool {
+        self.push(connection_id, Outbound::Close)
+    }
+}
+
+fn frame(data: &[u8], binary: bool) -> Message {
+    if binary {
+        Message::Binary(data.to_vec())
+    } else {
+        Message::Text(String::from_utf8_lossy(data).into_owned())
+    }
+}
+
+static CONNECTIONS: Lazy<Arc<WsRegistry>> = Lazy::new(|| Arc::new(WsRegistry::new()));
+
+/// The process-wide registry. Shared by every `Flow`, so sessions opened before a
+/// configuration reload stay reachable afterwards.
+pub fn connections() -> Arc<WsRegistry> {
+    Arc::clone(&CONNECTIONS)
+}
+
+/// Forwards messages published on `topic` to WebSocket clients until the subscription ends.
+/// See [`PUSH_CONNECTION_HEADER`] and [`PUSH_BINARY_HEADER`] for addressing.
+pub async fn run_push_bridge(bus: Arc<dyn EventBus>, topic: &str, registry: Arc<WsRegistry>) -> Result<(), BusError> {
+    let mut messages = bus.subscribe(topic).await?;
+    while let Some(msg) = messages.next().await {
+        let binary = msg.headers.get(PUSH_BINARY_HEADER).is_some_and(|v| v == "true");
+        match msg.headers.get(PUSH_CONNECTION_HEADER) {
+            Some(id) => {
+                if !registry.send(id, &msg.payload, binary) {
+                    log::debug!("WebSocket push for unknown connection {}", id);
+                }
+            }
+            None => {
+                registry.broadcast(&msg.payload, binary);
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use std::sync::atomic::Ordering;
+use axum::body::Body;
+use ox_webservice::BodyMode;
+use ox_webservice::body::{BodyPolicies, BodyPolicy, collect_body, spawn_body_stream};
+use ox_webservice_api::DEFAULT_MAX_BODY_SIZE;
+use ox_workflow_core::{BodyReader, BodyReadError};
+
+fn chunked_body(chunks: Vec<&'static [u8]>) -> Body {
+    let stream = futures::stream::iter(chunks.into_iter().map(Ok::<_, std::io::Error>));
+    Body::from_stream(stream)
+}
+
+#[test]
+fn test_body_policy_resolution() {
+    let policies = BodyPolicies::new(vec![
+        BodyPolicy::new(10, "^/upload/", Some("post"), None, None, Some(5_000_000_000), Some(BodyMode::Stream)).unwrap(),
+        BodyPolicy::new(1, "^/upload/small", None, None, None, Some(1024), None).unwrap(),
+    ]);
+
+    // Lower priority number wins for the limit; the mode comes from the route that declares one.
+    let s = policies.resolve("http", "POST", "localhost", "/upload/small");
+    assert_eq!(s.mode, BodyMode::Stream);
+    assert_eq!(s.max_body_size, 1024);
+
+    let s = policies.resolve("http", "POST", "localhost", "/upload/big.iso");
+    assert_eq!(s.mode, BodyMode::Stream);
+    assert_eq!(s.max_body_size, 5_000_000_000);
+
+    // Method mismatch and unrelated paths fall back to buffered defaults.
+    let s = policies.resolve("http", "GET", "localhost", "/upload/big.iso");
+    assert_eq!(s.mode, BodyMode::Buffered);
+    assert_eq!(s.max_body_size, DEFAULT_MAX_BODY_SIZE);
+}
+
+#[test]
+fn test_body_policy_rejects_invalid_regex() {
+    assert!(BodyPolicy::new(0, "([", None, None, None, Some(1), None).is_err());
+}
+
+#[tokio::test]
+async fn test_collect_body_enforces_limit() {
+    let bytes = collect_body(chunked_body(vec![b"abc", b"def"]), 6).await.unwrap();
+    assert_eq!(&bytes[..], b"abcdef");
+
+    let err = collect_body(chunked_body(vec![b"abc", b"defg"]), 6).await.unwrap_err();
+    assert_eq!(err, BodyReadError::TooLarge);
+}
+
+#[tokio::test]
+async fn test_streamed_body_reader() {
+    let mut reader = spawn_body_stream(chunked_body(vec![b"hello ", b"world"]), 64);
+    let collected = tokio::task::spawn_blocking(move || {
+        let mut out = Vec::new();
+        let mut buf = [0u8; 4];
+        loop {
+            let n = reader.read(&mut buf).unwrap();
+            if n == 0 { break; }
+            out.extend_from_slice(&buf[..n]);
+        }
+        out
+    }).await.unwrap();
+    assert_eq!(collected, b"hello world");
+}
+
+#[tokio::test]
+async fn test_streamed_body_over_limit_raises_flag() {
+    let mut reader = spawn_body_stream(chunked_body(vec![b"0123456789", b"0123456789"]), 15);
+    let flag = reader.too_large_flag();
+    let result = tokio::task::spawn_blocking(move || {
+        let mut buf = [0u8; 64];
+        let first = reader.read(&mut buf);
+        let second = reader.read(&mut buf);
+        (first, second)
+    }).await.unwrap();
+
+    assert_eq!(result.0, Ok(10));
+    assert_eq!(result.1, Err(BodyReadError::TooLarge));
+    assert!(flag.load(Ordering::Acquire));
+}
+
+#[tokio::test]
+async fn test_response_stream_delivers_head_then_chunks() {
+    use ox_webservice::body::response_channel;
+    use ox_workflow_core::ResponseStream;
+
+    let (mut stream, pending) = response_channel();
+    let writer = tokio::task::spawn_blocking(move || {
+        stream.open(202, vec![("Content-Type".to_string(), "text/event-stream".to_string())]).unwrap();
+        stream.write(b"data: one\n\n").unwrap();
+        stream.write(b"data: two\n\n").unwrap();
+        // Dropping the stream ends the body.
+    });
+
+    let res = pending.opened().await.expect("stream should be opened");
+    assert_eq!(res.status(), 202);
+    assert_eq!(res.headers()["content-type"], "text/event-stream");
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(&body[..], b"data: one\n\ndata: two\n\n");
+    writer.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_response_stream_reports_disconnect() {
+    use ox_webservice::body::response_channel;
+    use ox_workflow_core::{ResponseStream, ResponseWriteError};
+
+    let (mut stream, pending) = response_channel();
+    let result = tokio::task::spawn_blocking(move || {
+        stream.open(200, Vec::new()).unwrap();
+        // Keep writing until the client side goes away.
+        (0..1000).map(|_| stream.write(b"tick")).find(|r| r.is_err())
+    });
+
+    let res = pending.opened().await.unwrap();
+    drop(res);
+    assert_eq!(result.await.unwrap(), Some(Err(ResponseWriteError::Closed)));
+}
+
+#[tokio::test]
+async fn test_response_stream_dropped_unopened() {
+    let (stream, pending) = ox_webservice::body::response_channel();
+    drop(stream);
+    assert!(pending.opened().await.is_none());
+}
@@ -42,6 +42,7 @@ fn test_dynamic_module_loading_custom_path() {
         servers: vec![],
         merge: None,
         merge_recursive: None,
+        websocket: None,
     };
 
     // 4. Initialize Flow
@@ -86,6 +87,7 @@ fn test_dynamic_router_loading_custom_path() {
         servers: vec![],
         merge: None,
         merge_recursive: None,
+        websocket: None,
     };
     
     // 4. Initialize
@@ -118,6 +120,7 @@ fn test_dynamic_loading_failure() {
         servers: vec![],
         merge: None,
         merge_recursive: None,
+        websocket: None,
     };
 
     let result = Flow::new(&server_config, "{}".to_string());
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+use axum::extract::ws::Message;
+use futures::Stream;
+use ox_event_bus::{BusError, EventBus, EventMessage, QueueConfig};
+use ox_webservice::ws::{Outbound, WsRegistry, run_push_bridge, PUSH_BINARY_HEADER, PUSH_CONNECTION_HEADER};
+use ox_webservice::{ServerConfig, StageDef, WebSocketConfig, WorkflowConfig, flow::Flow};
+use ox_workflow_core::ConnectionHub;
+
+fn frame_of(item: Option<Outbound>) -> Message {
+    match item {
+        Some(Outbound::Frame(m)) => m,
+        other => panic!("expected a frame, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_registry_send_close_and_broadcast() {
+    let registry = WsRegistry::new();
+    let mut a = registry.register("a");
+    let mut b = registry.register("b");
+    assert_eq!(registry.len(), 2);
+
+    assert!(registry.send("a", b"hi", false));
+    assert!(registry.send("b", &[1, 2, 3], true));
+    assert!(!registry.send("missing", b"x", false));
+    assert_eq!(frame_of(a.recv().await), Message::Text("hi".to_string()));
+    assert_eq!(frame_of(b.recv().await), Message::Binary(vec![1, 2, 3]));
+
+    assert_eq!(registry.broadcast(b"all", false), 2);
+    assert_eq!(frame_of(a.recv().await), Message::Text("all".to_string()));
+    assert_eq!(frame_of(b.recv().await), Message::Text("all".to_string()));
+
+    assert!(registry.close("a"));
+    assert!(matches!(a.recv().await, Some(Outbound::Close)));
+
+    registry.unregister("a");
+    assert!(!registry.send("a", b"gone", false));
+    // A dropped writer makes the connection unreachable even before it is unregistered.
+    drop(b);
+    assert!(!registry.send("b", b"gone", false));
+}
+
+/// Replays a fixed set of messages to the first subscriber.
+struct ScriptedBus {
+    messages: std::sync::Mutex<Vec<EventMessage>>,
+}
+
+#[async_trait]
+impl EventBus for ScriptedBus {
+    async fn publish(&self, _topic: &str, _payload: &[u8]) -> Result<(), BusError> { Ok(()) }
+    async fn request(&self, _topic: &str, _payload: &[u8], _timeout: Duration) -> Result<EventMessage, BusError> {
+        Err(BusError::RequestTimeout)
+    }
+    async fn subscribe(&self, _topic: &str) -> Result<Pin<Box<dyn Stream<Item = EventMessage> + Send>>, BusError> {
+        let messages = std::mem::take(&mut *self.messages.lock().unwrap());
+        Ok(Box::pin(futures::stream::iter(messages)))
+    }
+    async fn reply(&self, _original: &EventMessage, _payload: &[u8]) -> Result<(), BusError> { Ok(()) }
+    async fn create_queue(&self, name: &str, _config: QueueConfig) -> Result<String, BusError> { Ok(name.to_string()) }
+    async fn publish_to_queue(&self, _queue_id: &str, _priority: u8, _payload: &[u8]) -> Result<(), BusError> { Ok(()) }
+    async fn subscribe_to_queue(&self, _queue_id: &str) -> Result<Pin<Box<dyn Stream<Item = EventMessage> + Send>>, BusError> {
+        Err(BusError::SubscriptionError("unsupported".to_string()))
+    }
+}
+
+fn message(payload: &[u8], headers: &[(&str, &str)]) -> EventMessage {
+    EventMessage {
+        topic: "ws/push".to_string(),
+        payload: payload.to_vec(),
+        headers: headers.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<HashMap<_, _>>(),
+        correlation_id: None,
+        reply_to: None,
+        delivery: None,
+    }
+}
+
+#[tokio::test]
+async fn test_push_bridge_targets_and_broadcasts() {
+    let registry = Arc::new(WsRegistry::new());
+    let mut a = registry.register("a");
+    let mut b = registry.register("b");
+
+    let bus = Arc::new(ScriptedBus {
+        messages: std::sync::Mutex::new(vec![
+            message(b"only a", &[(PUSH_CONNECTION_HEADER, "a")]),
+            message(&[9, 9], &[(PUSH_CONNECTION_HEADER, "b"), (PUSH_BINARY_HEADER, "true")]),
+            message(b"everyone", &[]),
+        ]),
+    });
+    run_push_bridge(bus, "ws/push", registry.clone()).await.unwrap();
+
+    assert_eq!(frame_of(a.recv().await), Message::Text("only a".to_string()));
+    assert_eq!(frame_of(a.recv().await), Message::Text("everyone".to_string()));
+    assert_eq!(frame_of(b.recv().await), Message::Binary(vec![9, 9]));
+    assert_eq!(frame_of(b.recv().await), Message::Text("everyone".to_string()));
+}
+
+fn stage(name: &str) -> StageDef {
+    StageDef {
+        name: name.to_string(),
+        runner: "default".to_string(),
+        plugins: vec![],
+        on_error: None,
+        retry: None,
+        deadline_ms: None,
+    }
+}
+
+fn config_with(websocket: WebSocketConfig) -> ServerConfig {
+    ServerConfig {
+        routes: vec![],
+        modules: vec![],
+        log4rs_config: "log4rs.yaml".to_string(),
+        enable_metrics: Some(false),
+        workflow: Some(WorkflowConfig {
+            name: "test".to_string(),
+            stages: vec![stage("Content"), stage("WsOpen"), stage("WsClose")],
+        }),
+        servers: vec![],
+        merge: None,
+        merge_recursive: None,
+        websocket: Some(websocket),
+    }
+}
+
+#[test]
+fn test_lifecycle_stages_are_separate_flows() {
+    let flow = Flow::new(&config_with(WebSocketConfig {
+        open_stages: vec!["WsOpen".to_string()],
+        close_stages: vec!["WsClose".to_string()],
+        push: None,
+    }), "{}".to_string()).unwrap();
+
+    let names = |stages: &[ox_workflow_executor::StageRunner]| stages.iter().map(|s| s.name.clone()).collect::<Vec<_>>();
+    assert_eq!(names(&flow.main_flow.stages), vec!["Content"]);
+    assert_eq!(names(&flow.ws_open_flow.as_ref().unwrap().stages), vec!["WsOpen"]);
+    assert_eq!(names(&flow.ws_close_flow.as_ref().unwrap().stages), vec!["WsClose"]);
+}
+
+#[test]
+fn test_unknown_lifecycle_stage_is_rejected() {
+    let result = Flow::new(&config_with(WebSocketConfig {
+        open_stages: vec!["Missing".to_string()],
+        ..Default::default()
+    }), "{}".to_string());
+    assert!(result.err().unwrap().contains("Missing"));
+}
@@ -8,13 +8,30 @@ pub use ox_workflow_abi::{
     OxPluginErrorFn, OxPluginDestroyFn, OxPluginNegotiateFn, PluginCapabilities,
     FLOW_CONTROL_CONTINUE, FLOW_CONTROL_END, FLOW_CONTROL_ERROR,
     FLOW_CONTROL_JUMP, FLOW_CONTROL_SKIP, FLOW_CONTROL_SUSPEND, FLOW_CONTROL_YIELD, FLOW_CONTROL_STREAM_FILE,
+    FLOW_CONTROL_STREAM_RESPONSE,
     FLAG_SCOPE_STAGE, FLAG_SCOPE_TASK,
     OX_LOG_ERROR, OX_LOG_WARN, OX_LOG_INFO, OX_LOG_DEBUG, OX_LOG_TRACE,
-    OX_WORKFLOW_ABI_VERSION, OX_WORKFLOW_ABI_MIN_VERSION,
+    OX_WORKFLOW_ABI_VERSION, OX_WORKFLOW_ABI_MIN_VERSION, OX_WORKFLOW_ABI_STREAMING_VERSION,
     FEATURE_NONE, FEATURE_BINARY_DATA, FEATURE_METADATA, FEATURE_FLAGS,
-    FEATURE_FLOW_INSERT, FEATURE_TASK_PAUSE, FEATURE_ASYNC, FEATURE_WASM,
+    FEATURE_FLOW_INSERT, FEATURE_TASK_PAUSE, FEATURE_ASYNC, FEATURE_WASM, FEATURE_STREAMING_BODY,
+    FEATURE_STREAMING_RESPONSE,
+    BODY_READ_UNAVAILABLE, BODY_READ_TOO_LARGE, BODY_READ_ERROR, RESPONSE_WRITE_CLOSED,
 };
 
+/// Default request body limit when neither the route nor the module sets `max_body_size`.
+pub const DEFAULT_MAX_BODY_SIZE: u64 = 10 * 1024 * 1024;
+
+/// How the host hands the request body to plugins.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyMode {
+    /// Read the whole body up front into `request.body` and `request.body_path`.
+    #[default]
+    Buffered,
+    /// Leave the body on the connection; plugins pull it with `CoreHostApi::read_body`.
+    Stream,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UriMatcher {
     #[serde(default)]
@@ -35,6 +52,12 @@ pub struct UriMatcher {
     pub stage: Option<String>,
     #[serde(default)]
     pub status_code: Option<String>,
+    /// Request body limit in bytes for this route. Overrides the module setting.
+    #[serde(default)]
+    pub max_body_size: Option<u64>,
+    /// Body delivery mode for this route. Overrides the module setting.
+    #[serde(default)]
+    pub body_mode: Option<BodyMode>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -54,6 +77,12 @@ pub struct ModuleConfig {
     pub stage: Option<String>,
     #[serde(default)]
     pub params: Option<Value>,
+    /// Request body limit in bytes for every route of this module.
+    #[serde(default)]
+    pub max_body_size: Option<u64>,
+    /// Body delivery mode for every route of this module.
+    #[serde(default)]
+    pub body_mode: Option<BodyMode>,
     #[serde(flatten)]
     pub extra_params: HashMap<String, Value>,
 }
@@ -69,6 +98,8 @@ impl Default for ModuleConfig {
             path: None,
             stage: None,
             params: None,
+            max_body_size: None,
+            body_mode: None,
             extra_params: HashMap::new(),
         }
     }
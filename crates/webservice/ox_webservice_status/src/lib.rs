@@ -1,18 +1,23 @@
-use std::sync::{Mutex};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use sysinfo::{System, Disks, Pid};
 use serde::Serialize;
 use std::ffi::{c_char, c_void, CStr, CString};
 use ox_workflow_abi::{
-    CoreHostApi, FlowControl, FLOW_CONTROL_CONTINUE, OX_LOG_INFO, OX_LOG_ERROR
+    CoreHostApi, FlowControl, FLOW_CONTROL_CONTINUE, FLOW_CONTROL_STREAM_RESPONSE, OX_LOG_INFO, OX_LOG_ERROR,
+    OX_WORKFLOW_ABI_STREAMING_VERSION,
 };
 
 const MODULE_NAME: &str = "ox_webservice_status";
+const DEFAULT_SSE_INTERVAL_MS: u64 = 2000;
 
 pub struct ModuleContext {
-    system: Mutex<System>,
-    disks: Mutex<Disks>,
+    // Shared with the event-stream threads, which outlive the request that started them.
+    system: Arc<Mutex<System>>,
+    disks: Arc<Mutex<Disks>>,
     api: CoreHostApi,
     server_config_json: Option<String>,
+    sse_interval: Duration,
 }
 
 #[derive(Serialize)]
@@ -137,36 +142,91 @@ fn build_config_data(config_json: &str) -> (Option<serde_json::Value>, Option<se
     (workflow_routing, configurations)
 }
 
+/// Takes a fresh system and process snapshot. Config-derived sections are left empty.
+fn collect_status(system: &Mutex<System>, disks: &Mutex<Disks>) -> StatusOutput {
+    let pid = Pid::from_u32(std::process::id());
+    let mut sys = system.lock().unwrap();
+    // Refresh only what we need — refresh_all() scans every /proc entry which
+    // can take several seconds on WSL2, blocking the tokio runtime.
+    sys.refresh_memory();
+    sys.refresh_cpu_usage();
+    sys.refresh_process(pid);
+    let load_avg = System::load_average();
+    let process_info = sys.process(pid).map(|p| ProcessInfo {
+        uptime_seconds: p.run_time(),
+        memory_bytes: p.memory(),
+        virtual_memory_bytes: p.virtual_memory(),
+        cpu_usage: p.cpu_usage(),
+    });
+    let cpu_count = sys.cpus().len();
+    let memory = MemoryInfo {
+        total: sys.total_memory(),
+        used: sys.used_memory(),
+        swap_total: sys.total_swap(),
+        swap_used: sys.used_swap(),
+    };
+    drop(sys); // release system lock before acquiring disks lock
+
+    let mut disks = disks.lock().unwrap();
+    disks.refresh_list();
+    let disk_infos: Vec<DiskInfo> = disks.list().iter().map(|d| DiskInfo {
+        name: d.name().to_string_lossy().to_string(),
+        mount_point: d.mount_point().to_string_lossy().to_string(),
+        total_space: d.total_space(),
+        available_space: d.available_space(),
+    }).collect();
+    drop(disks);
+    StatusOutput {
+        system: SystemInfo {
+            host_name: System::host_name(),
+            kernel_version: System::kernel_version(),
+            os_version: System::os_version(),
+            uptime: System::uptime(),
+            cpu_count,
+            load_average: LoadAvg { one: load_avg.one, five: load_avg.five, fifteen: load_avg.fifteen },
+            memory,
+            disks: disk_infos,
+        },
+        server: ServerInfo { process: process_info },
+        workflow_routing: None,
+        configurations: None,
+    }
+}
+
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn ox_plugin_init(
     plugin_config_ctx: *const c_char,
     api_ptr: *const CoreHostApi,
-    _abi_version: u32,
+    abi_version: u32,
 ) -> *mut c_void {
-    if api_ptr.is_null() { return std::ptr::null_mut(); }
+    // Older hosts' CoreHostApi lacks the response stream functions copied below.
+    if abi_version < OX_WORKFLOW_ABI_STREAMING_VERSION || api_ptr.is_null() { return std::ptr::null_mut(); }
     let api = unsafe { *api_ptr };
     if let Ok(c) = CString::new(format!("{} initialized", MODULE_NAME)) {
         (api.log)(std::ptr::null_mut(), OX_LOG_INFO, c.as_ptr());
     }
 
-    let server_config_json = if !plugin_config_ctx.is_null() {
+    let params = if !plugin_config_ctx.is_null() {
         let params_str = unsafe { CStr::from_ptr(plugin_config_ctx).to_string_lossy().to_string() };
-        if let Ok(v) = serde_json::from_str::<serde_json::Value>(&params_str) {
-            v.get("_server_config_json")
-                .and_then(|s| s.as_str())
-                .map(|s| s.to_string())
-        } else {
-            None
-        }
+        serde_json::from_str::<serde_json::Value>(&params_str).ok()
     } else {
         None
     };
+    let server_config_json = params.as_ref()
+        .and_then(|v| v.get("_server_config_json"))
+        .and_then(|s| s.as_str())
+        .map(|s| s.to_string());
+    let sse_interval_ms = params.as_ref()
+        .and_then(|v| v.get("sse_interval_ms"))
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_SSE_INTERVAL_MS);
 
     let ctx = Box::new(ModuleContext {
-        system: Mutex::new(System::new_all()),
-        disks: Mutex::new(Disks::new_with_refreshed_list()),
+        system: Arc::new(Mutex::new(System::new_all())),
+        disks: Arc::new(Mutex::new(Disks::new_with_refreshed_list())),
         api,
         server_config_json,
+        sse_interval: Duration::from_millis(sse_interval_ms),
     });
     Box::into_raw(ctx) as *mut c_void
 }
@@ -184,7 +244,17 @@ pub unsafe extern "C" fn ox_plugin_process(
 
     let query_val = get_field(api, task_ctx, "request.query");
     let accept_val = get_field(api, task_ctx, "request.header.accept");
-    let return_json = query_val.contains("format=json") || accept_val.contains("application/json");
+
+    if accept_val.contains("text/event-stream") {
+        if let Some(fc) = start_event_stream(context, task_ctx) {
+            return fc;
+        }
+        log(api, task_ctx, OX_LOG_INFO, "Status: Host cannot stream responses, falling back to JSON");
+    }
+
+    let return_json = query_val.contains("format=json")
+        || accept_val.contains("application/json")
+        || accept_val.contains("text/event-stream");
 
     if !return_json {
         log(api, task_ctx, OX_LOG_INFO, "Status: Non-JSON request, skipping");
@@ -193,55 +263,7 @@ pub unsafe extern "C" fn ox_plugin_process(
 
     log(api, task_ctx, OX_LOG_INFO, "Status: Returning JSON status report");
 
-    let mut status_output = {
-        let pid = Pid::from_u32(std::process::id());
-        let mut sys = context.system.lock().unwrap();
-        // Refresh only what we need — refresh_all() scans every /proc entry which
-        // can take several seconds on WSL2, blocking the tokio runtime.
-        sys.refresh_memory();
-        sys.refresh_cpu_usage();
-        sys.refresh_process(pid);
-        let load_avg = System::load_average();
-        let process_info = sys.process(pid).map(|p| ProcessInfo {
-            uptime_seconds: p.run_time(),
-            memory_bytes: p.memory(),
-            virtual_memory_bytes: p.virtual_memory(),
-            cpu_usage: p.cpu_usage(),
-        });
-        let cpu_count = sys.cpus().len();
-        let memory = MemoryInfo {
-            total: sys.total_memory(),
-            used: sys.used_memory(),
-            swap_total: sys.total_swap(),
-            swap_used: sys.used_swap(),
-        };
-        drop(sys); // release system lock before acquiring disks lock
-
-        let mut disks = context.disks.lock().unwrap();
-        disks.refresh_list();
-        let disk_infos: Vec<DiskInfo> = disks.list().iter().map(|d| DiskInfo {
-            name: d.name().to_string_lossy().to_string(),
-            mount_point: d.mount_point().to_string_lossy().to_string(),
-            total_space: d.total_space(),
-            available_space: d.available_space(),
-        }).collect();
-        drop(disks);
-        StatusOutput {
-            system: SystemInfo {
-                host_name: System::host_name(),
-                kernel_version: System::kernel_version(),
-                os_version: System::os_version(),
-                uptime: System::uptime(),
-                cpu_count,
-                load_average: LoadAvg { one: load_avg.one, five: load_avg.five, fifteen: load_avg.fifteen },
-                memory,
-                disks: disk_infos,
-            },
-            server: ServerInfo { process: process_info },
-            workflow_routing: None,
-            configurations: None,
-        }
-    };
+    let mut status_output = collect_status(&context.system, &context.disks);
 
     let (workflow_routing, configurations) = context.server_config_json.as_deref()
         .map(build_config_data)
@@ -264,6 +286,44 @@ pub unsafe extern "C" fn ox_plugin_process(
     FlowControl { code: FLOW_CONTROL_CONTINUE, payload: std::ptr::null() }
 }
 
+/// Opens a Server-Sent Events stream and pushes a status snapshot every `sse_interval`
+/// from a background thread until the client disconnects.
+/// Returns `None` when the host does not support streamed responses.
+fn start_event_stream(context: &ModuleContext, task_ctx: *mut c_void) -> Option<FlowControl> {
+    let api = &context.api;
+    set_field(api, task_ctx, "response.status", "200");
+    set_field(api, task_ctx, "response.header.Content-Type", "text/event-stream");
+    set_field(api, task_ctx, "response.header.Cache-Control", "no-cache");
+
+    let stream = (api.open_response_stream)(task_ctx);
+    if stream.is_null() {
+        return None;
+    }
+    log(api, task_ctx, OX_LOG_INFO, "Status: Streaming status events");
+
+    let api = *api;
+    let system = Arc::clone(&context.system);
+    let disks = Arc::clone(&context.disks);
+    let interval = context.sse_interval;
+    // Raw pointers are not Send; the handle is owned by this thread from here on.
+    let stream_addr = stream as usize;
+    std::thread::spawn(move || {
+        let stream = stream_addr as *mut c_void;
+        loop {
+            let status = collect_status(&system, &disks);
+            let Ok(json) = serde_json::to_string(&status) else { break };
+            let event = format!("event: status\ndata: {}\n\n", json);
+            if (api.write_response_stream)(stream, event.as_ptr(), event.len()) < 0 {
+                break;
+            }
+            std::thread::sleep(interval);
+        }
+        (api.close_response_stream)(stream);
+    });
+
+    Some(FlowControl { code: FLOW_CONTROL_STREAM_RESPONSE, payload: std::ptr::null() })
+}
+
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn ox_plugin_error(
     _plugin_config_ctx: *mut c_void,
@@ -2,7 +2,15 @@ use crate::{ox_plugin_init, ox_plugin_process};
 use ox_webservice_test_utils::{
     create_mock_api, create_task_state, drop_task_state, get_mock_field, set_mock_field, PluginHandle,
 };
-use ox_workflow_abi::FLOW_CONTROL_CONTINUE;
+use ox_workflow_abi::{FLOW_CONTROL_CONTINUE, OX_WORKFLOW_ABI_STREAMING_VERSION};
+
+#[test]
+fn test_init_rejects_host_without_streaming_api() {
+    let api = create_mock_api();
+    let config = std::ffi::CString::new("{}").unwrap();
+    let ctx = unsafe { ox_plugin_init(config.as_ptr(), &api, OX_WORKFLOW_ABI_STREAMING_VERSION - 1) };
+    assert!(ctx.is_null());
+}
 
 #[test]
 fn test_status_json_accept() {
@@ -39,3 +47,21 @@ fn test_status_json_query() {
 
     unsafe { drop_task_state(task_ctx); }
 }
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn test_status_event_stream_falls_back_to_json() {
+    // The mock host cannot stream responses, so an event-stream request gets a plain JSON report.
+    let api = create_mock_api();
+    let handle = PluginHandle::init(ox_plugin_init, "{\"sse_interval_ms\": 50}", &api).expect("Failed to load status module");
+
+    let task_ctx = create_task_state();
+    set_mock_field(task_ctx, "request.header.accept", "text/event-stream");
+    let flow = handle.process(ox_plugin_process, task_ctx);
+    assert_eq!(flow.code, FLOW_CONTROL_CONTINUE);
+
+    let body = get_mock_field(task_ctx, "response.body").unwrap_or_default();
+    assert!(body.starts_with('{'), "Expected JSON body, got: {}", body);
+
+    unsafe { drop_task_state(task_ctx); }
+}
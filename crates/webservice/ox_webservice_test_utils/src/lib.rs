@@ -69,6 +69,12 @@ pub extern "C" fn mock_clear_flag(_task_ctx: *mut c_void, _flag: *const c_char,
 pub extern "C" fn mock_get_keys(_task_ctx: *mut c_void) -> *const c_char { std::ptr::null() }
 pub extern "C" fn mock_unset_field(_task_ctx: *mut c_void, _key: *const c_char) -> bool { false }
 pub extern "C" fn mock_has_field(_task_ctx: *mut c_void, _key: *const c_char) -> bool { false }
+pub extern "C" fn mock_read_body(_task_ctx: *mut c_void, _buf: *mut u8, _cap: usize) -> i64 { ox_workflow_abi::BODY_READ_UNAVAILABLE }
+pub extern "C" fn mock_open_response_stream(_task_ctx: *mut c_void) -> *mut c_void { std::ptr::null_mut() }
+pub extern "C" fn mock_write_response_stream(_stream: *mut c_void, _data: *const u8, _len: usize) -> i64 { ox_workflow_abi::RESPONSE_WRITE_CLOSED }
+pub extern "C" fn mock_close_response_stream(_stream: *mut c_void) {}
+pub extern "C" fn mock_ws_send(_task_ctx: *mut c_void, _connection_id: *const c_char, _data: *const u8, _len: usize, _binary: bool) -> bool { false }
+pub extern "C" fn mock_ws_close(_task_ctx: *mut c_void, _connection_id: *const c_char) -> bool { false }
 
 /// Create a `CoreHostApi` pointing to mock functions.
 pub fn create_mock_api() -> CoreHostApi {
@@ -88,6 +94,12 @@ pub fn create_mock_api() -> CoreHostApi {
         get_keys: mock_get_keys,
         unset_field: mock_unset_field,
         has_field: mock_has_field,
+        read_body: mock_read_body,
+        open_response_stream: mock_open_response_stream,
+        write_response_stream: mock_write_response_stream,
+        close_response_stream: mock_close_response_stream,
+        ws_send: mock_ws_send,
+        ws_close: mock_ws_close,
     }
 }
 
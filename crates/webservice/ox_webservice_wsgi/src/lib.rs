@@ -1,7 +1,8 @@
 use std::ffi::{c_char, c_void, CStr, CString};
 use serde::{Deserialize, Serialize};
 use ox_workflow_abi::{
-    CoreHostApi, FlowControl, FLOW_CONTROL_CONTINUE, FLOW_CONTROL_ERROR, OX_LOG_INFO, OX_LOG_ERROR,
+    CoreHostApi, FlowControl, FLOW_CONTROL_CONTINUE, FLOW_CONTROL_ERROR, FLOW_CONTROL_STREAM_RESPONSE,
+    OX_LOG_INFO, OX_LOG_ERROR, OX_WORKFLOW_ABI_STREAMING_VERSION,
 };
 use pyo3::prelude::*;
 use pyo3::types::{PyDict, PyBytes, PyModule};
@@ -23,12 +24,16 @@ struct AppConfig {
     #[serde(default = "default_callable")]
     #[prost(string, tag = "3")]
     callable: String,
+    /// Forward each chunk of the app's iterable to the client as it is produced instead of
+    /// joining them into `response.body`. Needed for long-polling and event-stream apps.
+    #[serde(default)]
+    #[prost(bool, tag = "4")]
+    stream: bool,
 }
 
 fn default_callable() -> String { "application".to_string() }
 
 struct WsgiContext {
-    #[allow(dead_code)]
     config: AppConfig,
     app: PyObject,
     api: CoreHostApi,
@@ -70,9 +75,10 @@ fn log(api: &CoreHostApi, task_ctx: *mut c_void, level: u8, msg: &str) {
 pub unsafe extern "C" fn ox_plugin_init(
     plugin_config_ctx: *const c_char,
     api_ptr: *const CoreHostApi,
-    _abi_version: u32,
+    abi_version: u32,
 ) -> *mut c_void {
-    if api_ptr.is_null() { return std::ptr::null_mut(); }
+    // Older hosts' CoreHostApi lacks the response stream functions copied below.
+    if abi_version < OX_WORKFLOW_ABI_STREAMING_VERSION || api_ptr.is_null() { return std::ptr::null_mut(); }
     let api = unsafe { *api_ptr };
 
     let params_str = if !plugin_config_ctx.is_null() {
@@ -116,6 +122,149 @@ pub unsafe extern "C" fn ox_plugin_init(
     Box::into_raw(ctx) as *mut c_void
 }
 
+fn build_environ<'py>(py: Python<'py>, api: &CoreHostApi, task_ctx: *mut c_void) -> PyResult<Bound<'py, PyDict>> {
+    let environ = PyDict::new(py);
+    environ.set_item("REQUEST_METHOD", get_field(api, task_ctx, "request.method").to_uppercase())?;
+    environ.set_item("SCRIPT_NAME", "")?;
+    environ.set_item("PATH_INFO", get_field(api, task_ctx, "request.path"))?;
+    environ.set_item("QUERY_STRING", get_field(api, task_ctx, "request.query"))?;
+    environ.set_item("SERVER_NAME", "oxidizer")?;
+    environ.set_item("SERVER_PORT", "80")?;
+    environ.set_item("SERVER_PROTOCOL", "HTTP/1.1")?;
+    environ.set_item("wsgi.version", (1, 0))?;
+    environ.set_item("wsgi.url_scheme", get_field(api, task_ctx, "request.protocol"))?;
+    environ.set_item("wsgi.multithread", true)?;
+    environ.set_item("wsgi.multiprocess", false)?;
+    environ.set_item("wsgi.run_once", false)?;
+
+    let io_module = py.import("io")?;
+    let body_str = get_field(api, task_ctx, "request.body");
+    let body_bytes = body_str.as_bytes();
+    let stream = io_module.call_method1("BytesIO", (PyBytes::new(py, body_bytes),))?;
+    environ.set_item("wsgi.input", stream)?;
+
+    let sys_mod = py.import("sys")?;
+    environ.set_item("wsgi.errors", sys_mod.getattr("stderr")?)?;
+    Ok(environ)
+}
+
+/// Sets status and headers from `start_response` and opens the response stream.
+fn open_stream(api: &CoreHostApi, task_ctx: *mut c_void, locals: &Bound<'_, PyDict>) -> PyResult<*mut c_void> {
+    let sc: i32 = locals.get_item("status_code")?.ok_or_else(|| PyErr::new::<pyo3::exceptions::PyValueError, _>("Missing status"))?.extract::<Vec<i32>>()?[0];
+    let hd: Vec<(String, String)> = locals.get_item("headers")?.ok_or_else(|| PyErr::new::<pyo3::exceptions::PyValueError, _>("Missing headers"))?.extract()?;
+    set_field(api, task_ctx, "response.status", &sc.to_string());
+    for (k, v) in hd {
+        set_field(api, task_ctx, &format!("response.header.{}", k), &v);
+    }
+    Ok((api.open_response_stream)(task_ctx))
+}
+
+/// Streaming variant of `ox_plugin_process`: chunks are written to the client while the app
+/// is still producing them, with the GIL released during each (possibly blocking) write.
+/// Falls back to a buffered `response.body` when the host cannot stream.
+fn process_streaming(context: &WsgiContext, task_ctx: *mut c_void) -> FlowControl {
+    let api = &context.api;
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        Python::with_gil(|py| -> PyResult<FlowControl> {
+            let environ = build_environ(py, api, task_ctx)?;
+
+            let locals = PyDict::new(py);
+            locals.set_item("environ", environ)?;
+            locals.set_item("app", &context.app)?;
+
+            let code = CString::new(r#"
+status_code = [500]
+headers = []
+written = []
+
+def start_response(status, response_headers, exc_info=None):
+    status_code[0] = int(status.split(' ')[0])
+    headers[:] = response_headers
+    return written.append
+
+iterable = app(environ, start_response)
+"#).unwrap();
+            py.run(&code, Some(&locals), Some(&locals))?;
+
+            let iterable = locals.get_item("iterable")?.ok_or_else(|| PyErr::new::<pyo3::exceptions::PyValueError, _>("Missing iterable"))?;
+            let written = locals.get_item("written")?.ok_or_else(|| PyErr::new::<pyo3::exceptions::PyValueError, _>("Missing write buffer"))?;
+
+            let mut stream: *mut c_void = std::ptr::null_mut();
+            let mut buffered: Option<Vec<u8>> = None;
+            let outcome = (|| -> PyResult<()> {
+                for item in iterable.try_iter()? {
+                    // Data passed to the legacy write() callable goes out ahead of the yielded chunk.
+                    let mut chunks: Vec<Vec<u8>> = written.extract()?;
+                    written.call_method0("clear")?;
+                    chunks.push(item?.extract()?);
+
+                    if stream.is_null() && buffered.is_none() {
+                        stream = open_stream(api, task_ctx, &locals)?;
+                        if stream.is_null() {
+                            buffered = Some(Vec::new());
+                        }
+                    }
+                    if let Some(buf) = buffered.as_mut() {
+                        chunks.iter().for_each(|c| buf.extend_from_slice(c));
+                        continue;
+                    }
+                    let stream_addr = stream as usize;
+                    let client_gone = py.allow_threads(|| {
+                        chunks.iter().any(|c| {
+                            (api.write_response_stream)(stream_addr as *mut c_void, c.as_ptr(), c.len()) < 0
+                        })
+                    });
+                    if client_gone {
+                        break;
+                    }
+                }
+                // An empty iterable still has a status line and headers to send.
+                if stream.is_null() && buffered.is_none() {
+                    stream = open_stream(api, task_ctx, &locals)?;
+                    if stream.is_null() {
+                        buffered = Some(Vec::new());
+                    }
+                }
+                Ok(())
+            })();
+
+            if iterable.hasattr("close")? {
+                iterable.call_method0("close")?;
+            }
+            (api.close_response_stream)(stream);
+
+            match (outcome, buffered) {
+                (Err(e), _) if stream.is_null() => Err(e),
+                (Err(e), _) => {
+                    // Headers are already out; all we can do is cut the body short.
+                    log(api, task_ctx, OX_LOG_ERROR, &format!("WSGI Error after response started: {}", e));
+                    Ok(FlowControl { code: FLOW_CONTROL_STREAM_RESPONSE, payload: std::ptr::null() })
+                }
+                (Ok(()), Some(body)) => {
+                    set_field(api, task_ctx, "response.body", &String::from_utf8_lossy(&body));
+                    Ok(FlowControl { code: FLOW_CONTROL_CONTINUE, payload: std::ptr::null() })
+                }
+                (Ok(()), None) => Ok(FlowControl { code: FLOW_CONTROL_STREAM_RESPONSE, payload: std::ptr::null() }),
+            }
+        })
+    }));
+
+    match result {
+        Ok(Ok(fc)) => fc,
+        Ok(Err(e)) => {
+            log(api, task_ctx, OX_LOG_ERROR, &format!("WSGI Error: {}", e));
+            set_field(api, task_ctx, "response.status", "500");
+            FlowControl { code: FLOW_CONTROL_ERROR, payload: std::ptr::null() }
+        }
+        Err(e) => {
+            log(api, task_ctx, OX_LOG_ERROR, &format!("WSGI Panic: {:?}", e));
+            set_field(api, task_ctx, "response.status", "500");
+            FlowControl { code: FLOW_CONTROL_ERROR, payload: std::ptr::null() }
+        }
+    }
+}
+
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn ox_plugin_process(
     plugin_config_ctx: *mut c_void,
@@ -127,30 +276,13 @@ pub unsafe extern "C" fn ox_plugin_process(
     let context = unsafe { &*(plugin_config_ctx as *mut WsgiContext) };
     let api = &context.api;
 
+    if context.config.stream {
+        return process_streaming(context, task_ctx);
+    }
+
     let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
         Python::with_gil(|py| -> PyResult<(i32, Vec<u8>, Vec<(String, String)>)> {
-            let environ = PyDict::new(py);
-            environ.set_item("REQUEST_METHOD", get_field(api, task_ctx, "request.method").to_uppercase())?;
-            environ.set_item("SCRIPT_NAME", "")?;
-            environ.set_item("PATH_INFO", get_field(api, task_ctx, "request.path"))?;
-            environ.set_item("QUERY_STRING", get_field(api, task_ctx, "request.query"))?;
-            environ.set_item("SERVER_NAME", "oxidizer")?;
-            environ.set_item("SERVER_PORT", "80")?;
-            environ.set_item("SERVER_PROTOCOL", "HTTP/1.1")?;
-            environ.set_item("wsgi.version", (1, 0))?;
-            environ.set_item("wsgi.url_scheme", get_field(api, task_ctx, "request.protocol"))?;
-            environ.set_item("wsgi.multithread", true)?;
-            environ.set_item("wsgi.multiprocess", false)?;
-            environ.set_item("wsgi.run_once", false)?;
-
-            let io_module = py.import("io")?;
-            let body_str = get_field(api, task_ctx, "request.body");
-            let body_bytes = body_str.as_bytes();
-            let stream = io_module.call_method1("BytesIO", (PyBytes::new(py, body_bytes),))?;
-            environ.set_item("wsgi.input", stream)?;
-
-            let sys_mod = py.import("sys")?;
-            environ.set_item("wsgi.errors", sys_mod.getattr("stderr")?)?;
+            let environ = build_environ(py, api, task_ctx)?;
 
             let locals = PyDict::new(py);
             locals.set_item("environ", environ)?;
@@ -220,3 +352,6 @@ pub unsafe extern "C" fn ox_plugin_destroy(plugin_config_ctx: *mut c_void) {
         let _ = Box::from_raw(plugin_config_ctx as *mut WsgiContext);
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,160 @@
+use std::ffi::c_void;
+use std::sync::Mutex;
+use crate::{ox_plugin_init, ox_plugin_process};
+use ox_webservice_test_utils::{
+    create_mock_api, create_task_state, drop_task_state, get_mock_field, PluginHandle,
+};
+use ox_workflow_abi::{CoreHostApi, FLOW_CONTROL_CONTINUE, FLOW_CONTROL_STREAM_RESPONSE};
+use pyo3::prelude::*;
+
+/// Everything the host saw, in order: stream writes and the app's own progress marks.
+static EVENTS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+/// The tests share `EVENTS` and the interpreter's module cache.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+fn record(event: String) {
+    EVENTS.lock().unwrap().push(event);
+}
+
+#[pyfunction]
+fn mark(label: &str) {
+    record(format!("mark:{label}"));
+}
+
+extern "C" fn open_stream(_task_ctx: *mut c_void) -> *mut c_void {
+    record("open".to_string());
+    std::ptr::NonNull::<u8>::dangling().as_ptr() as *mut c_void
+}
+
+extern "C" fn write_stream(_stream: *mut c_void, data: *const u8, len: usize) -> i64 {
+    let chunk = unsafe { std::slice::from_raw_parts(data, len) };
+    record(format!("write:{}", String::from_utf8_lossy(chunk)));
+    len as i64
+}
+
+extern "C" fn close_stream(stream: *mut c_void) {
+    if !stream.is_null() {
+        record("close".to_string());
+    }
+}
+
+fn streaming_api() -> CoreHostApi {
+    CoreHostApi {
+        open_response_stream: open_stream,
+        write_response_stream: write_stream,
+        close_response_stream: close_stream,
+        ..create_mock_api()
+    }
+}
+
+/// Write `source` as module `name` and return the plugin params loading it.
+fn app_params(dir: &std::path::Path, name: &str, source: &str) -> String {
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let probe = PyModule::new(py, "ox_wsgi_probe").unwrap();
+        probe.add_function(wrap_pyfunction!(mark, &probe).unwrap()).unwrap();
+        py.import("sys").unwrap().getattr("modules").unwrap().set_item("ox_wsgi_probe", probe).unwrap();
+    });
+    std::fs::write(dir.join(format!("{name}.py")), source).unwrap();
+    let config = dir.join(format!("{name}.json"));
+    std::fs::write(&config, serde_json::json!({
+        "python_path": dir.to_string_lossy(),
+        "module": name,
+        "stream": true,
+    }).to_string()).unwrap();
+    serde_json::json!({ "config_file": config.to_string_lossy() }).to_string()
+}
+
+#[test]
+fn test_streams_generator_chunks_as_produced() {
+    let _serial = SERIAL.lock().unwrap_or_else(|p| p.into_inner());
+    EVENTS.lock().unwrap().clear();
+    let dir = tempfile::tempdir().unwrap();
+    let params = app_params(dir.path(), "gen_app", r#"
+from ox_wsgi_probe import mark
+
+def application(environ, start_response):
+    start_response('200 OK', [('Content-Type', 'text/plain')])
+    def body():
+        yield b"one"
+        mark("two")
+        yield b"two"
+        mark("done")
+    return body()
+"#);
+    let api = streaming_api();
+    let handle = PluginHandle::init(ox_plugin_init, &params, &api).expect("Failed to load WSGI app");
+
+    let task_ctx = create_task_state();
+    let flow = handle.process(ox_plugin_process, task_ctx);
+    assert_eq!(flow.code, FLOW_CONTROL_STREAM_RESPONSE);
+    assert_eq!(get_mock_field(task_ctx, "response.status").as_deref(), Some("200"));
+    assert_eq!(get_mock_field(task_ctx, "response.body"), None);
+
+    // Each chunk reaches the host before the app produces the next one.
+    assert_eq!(*EVENTS.lock().unwrap(), ["open", "write:one", "mark:two", "write:two", "mark:done", "close"]);
+    unsafe { drop_task_state(task_ctx); }
+}
+
+#[test]
+fn test_streams_iterator_and_write_callable() {
+    let _serial = SERIAL.lock().unwrap_or_else(|p| p.into_inner());
+    EVENTS.lock().unwrap().clear();
+    let dir = tempfile::tempdir().unwrap();
+    let params = app_params(dir.path(), "iter_app", r#"
+from ox_wsgi_probe import mark
+
+class Body:
+    def __init__(self, write):
+        self.write = write
+        self.chunks = [b"a", b"b"]
+    def __iter__(self):
+        return self
+    def __next__(self):
+        if not self.chunks:
+            raise StopIteration
+        chunk = self.chunks.pop(0)
+        mark(chunk.decode())
+        if chunk == b"b":
+            self.write(b"legacy")
+        return chunk
+    def close(self):
+        mark("closed")
+
+def application(environ, start_response):
+    return Body(start_response('200 OK', []))
+"#);
+    let api = streaming_api();
+    let handle = PluginHandle::init(ox_plugin_init, &params, &api).expect("Failed to load WSGI app");
+
+    let task_ctx = create_task_state();
+    let flow = handle.process(ox_plugin_process, task_ctx);
+    assert_eq!(flow.code, FLOW_CONTROL_STREAM_RESPONSE);
+    // write() output goes out ahead of the chunk yielded with it; close() runs before the stream closes.
+    assert_eq!(
+        *EVENTS.lock().unwrap(),
+        ["mark:a", "open", "write:a", "mark:b", "write:legacy", "write:b", "mark:closed", "close"]
+    );
+    unsafe { drop_task_state(task_ctx); }
+}
+
+#[test]
+fn test_buffers_when_host_cannot_stream() {
+    let _serial = SERIAL.lock().unwrap_or_else(|p| p.into_inner());
+    let dir = tempfile::tempdir().unwrap();
+    let params = app_params(dir.path(), "buffered_app", r#"
+def application(environ, start_response):
+    start_response('201 Created', [])
+    return iter([b"x", b"y"])
+"#);
+    // The stock mock API cannot open a response stream.
+    let api = create_mock_api();
+    let handle = PluginHandle::init(ox_plugin_init, &params, &api).expect("Failed to load WSGI app");
+
+    let task_ctx = create_task_state();
+    let flow = handle.process(ox_plugin_process, task_ctx);
+    assert_eq!(flow.code, FLOW_CONTROL_CONTINUE);
+    assert_eq!(get_mock_field(task_ctx, "response.status").as_deref(), Some("201"));
+    assert_eq!(get_mock_field(task_ctx, "response.body").as_deref(), Some("xy"));
+    unsafe { drop_task_state(task_ctx); }
+}
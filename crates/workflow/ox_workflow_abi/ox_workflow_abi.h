@@ -6,13 +6,19 @@
 /**
  * Current ABI version for the workflow engine and plugins
  */
-#define OX_WORKFLOW_ABI_VERSION 3
+#define OX_WORKFLOW_ABI_VERSION 4
 
 /**
  * Minimum ABI version this crate supports
  */
 #define OX_WORKFLOW_ABI_MIN_VERSION 3
 
+/**
+ * First ABI version whose `CoreHostApi` carries `read_body`, the response stream
+ * functions and `ws_send`/`ws_close`. A version 3 host's table ends at `has_field`.
+ */
+#define OX_WORKFLOW_ABI_STREAMING_VERSION 4
+
 /**
  * Feature flags for plugin capabilities
  */
@@ -32,6 +38,12 @@
 
 #define FEATURE_WASM (1 << 6)
 
+#define FEATURE_STREAMING_BODY (1 << 7)
+
+#define FEATURE_STREAMING_RESPONSE (1 << 8)
+
+#define FEATURE_CONNECTIONS (1 << 9)
+
 /**
  * Flow control code: Continue to the next plugin or stage.
  */
@@ -69,6 +81,32 @@
  */
 #define FLOW_CONTROL_STREAM_FILE 7
 
+/**
+ * Flow control code: The plugin opened a response stream with `open_response_stream` and
+ * owns the response body from here on. Ends the flow; `payload` is unused.
+ */
+#define FLOW_CONTROL_STREAM_RESPONSE 8
+
+/**
+ * Body read result: the task has no streamed request body attached.
+ */
+#define BODY_READ_UNAVAILABLE -1
+
+/**
+ * Body read result: the body exceeded the route's size limit. The host answers 413.
+ */
+#define BODY_READ_TOO_LARGE -2
+
+/**
+ * Body read result: the client connection failed mid-body.
+ */
+#define BODY_READ_ERROR -3
+
+/**
+ * Response write result: the client went away or the stream was already closed.
+ */
+#define RESPONSE_WRITE_CLOSED -1
+
 /**
  * Flag scope: Cleared at each stage boundary.
  */
@@ -104,7 +142,7 @@
  */
 #define OX_LOG_TRACE 5
 
-#define PLUGIN_ABI_VERSION 3
+#define PLUGIN_ABI_VERSION 4
 
 typedef struct Option_OxPluginNegotiateFn Option_OxPluginNegotiateFn;
 
@@ -182,6 +220,42 @@ typedef struct CoreHostApi {
    * Check if key exists. Returns 1 if exists, 0 if not.
    */
   bool (*has_field)(void *task_ctx, const char *key);
+  /**
+   * Read the next chunk of a streamed request body into `buf` (at most `cap` bytes).
+   * Blocks until data is available. Returns the number of bytes written, 0 at end of
+   * body, or a negative `BODY_READ_*` code.
+   */
+  int64_t (*read_body)(void *task_ctx, uint8_t *buf, uintptr_t cap);
+  /**
+   * Send the status line and headers (taken from `response.status` and `response.header.*`)
+   * and return a stream handle, or null if the host cannot stream or the stream was already
+   * opened. The handle is not tied to the task: it may be moved to another thread and stays
+   * valid until passed to `close_response_stream`.
+   */
+  void *(*open_response_stream)(void *task_ctx);
+  /**
+   * Write `len` bytes to an open response stream. Blocks while the client is slower than
+   * the writer. Returns `len`, or `RESPONSE_WRITE_CLOSED` once the client has disconnected.
+   */
+  int64_t (*write_response_stream)(void *stream, const uint8_t *data, uintptr_t len);
+  /**
+   * Finish the response body and free the handle. Null is ignored.
+   */
+  void (*close_response_stream)(void *stream);
+  /**
+   * Push a frame to the WebSocket connection `connection_id` (see the `ws.connection_id`
+   * field). Sent as a binary frame if `binary`, else as text. Returns false if the
+   * connection is unknown or closed. Never blocks.
+   */
+  bool (*ws_send)(void *task_ctx,
+                  const char *connection_id,
+                  const uint8_t *data,
+                  uintptr_t len,
+                  bool binary);
+  /**
+   * Close the WebSocket connection `connection_id`. Returns false if it is unknown.
+   */
+  bool (*ws_close)(void *task_ctx, const char *connection_id);
 } CoreHostApi;
 
 /**
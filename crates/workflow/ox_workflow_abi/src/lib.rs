@@ -9,9 +9,12 @@ pub use sdk::{
 };
 
 /// Current ABI version for the workflow engine and plugins
-pub const OX_WORKFLOW_ABI_VERSION: u32 = 3;
+pub const OX_WORKFLOW_ABI_VERSION: u32 = 4;
 /// Minimum ABI version this crate supports
 pub const OX_WORKFLOW_ABI_MIN_VERSION: u32 = 3;
+/// First ABI version whose `CoreHostApi` carries `read_body`, the response stream
+/// functions and `ws_send`/`ws_close`. A version 3 host's table ends at `has_field`.
+pub const OX_WORKFLOW_ABI_STREAMING_VERSION: u32 = 4;
 
 /// Feature flags for plugin capabilities
 pub const FEATURE_NONE: u64 = 0;
@@ -22,6 +25,9 @@ pub const FEATURE_FLOW_INSERT: u64 = 1 << 3;
 pub const FEATURE_TASK_PAUSE: u64 = 1 << 4;
 pub const FEATURE_ASYNC: u64 = 1 << 5;
 pub const FEATURE_WASM: u64 = 1 << 6;
+pub const FEATURE_STREAMING_BODY: u64 = 1 << 7;
+pub const FEATURE_STREAMING_RESPONSE: u64 = 1 << 8;
+pub const FEATURE_CONNECTIONS: u64 = 1 << 9;
 
 /// Plugin capabilities structure returned during version negotiation.
 /// This allows the host to understand what features a plugin supports.
@@ -103,6 +109,19 @@ pub const FLOW_CONTROL_SUSPEND: u8 = 5;
 pub const FLOW_CONTROL_YIELD: u8 = 6;
 /// Flow control code: Stream a file from the path in `payload` (a c_char path string).
 pub const FLOW_CONTROL_STREAM_FILE: u8 = 7;
+/// Flow control code: The plugin opened a response stream with `open_response_stream` and
+/// owns the response body from here on. Ends the flow; `payload` is unused.
+pub const FLOW_CONTROL_STREAM_RESPONSE: u8 = 8;
+
+/// Body read result: the task has no streamed request body attached.
+pub const BODY_READ_UNAVAILABLE: i64 = -1;
+/// Body read result: the body exceeded the route's size limit. The host answers 413.
+pub const BODY_READ_TOO_LARGE: i64 = -2;
+/// Body read result: the client connection failed mid-body.
+pub const BODY_READ_ERROR: i64 = -3;
+
+/// Response write result: the client went away or the stream was already closed.
+pub const RESPONSE_WRITE_CLOSED: i64 = -1;
 
 /// Flag scope: Cleared at each stage boundary.
 pub const FLAG_SCOPE_STAGE: u8 = 0;
@@ -159,6 +178,35 @@ pub struct CoreHostApi {
     pub unset_field: extern "C" fn(task_ctx: *mut c_void, key: *const c_char) -> bool,
     /// Check if key exists. Returns 1 if exists, 0 if not.
     pub has_field: extern "C" fn(task_ctx: *mut c_void, key: *const c_char) -> bool,
+
+    // Fields below require host ABI version >= OX_WORKFLOW_ABI_STREAMING_VERSION.
+    // Check the `abi_version` passed to `ox_plugin_init` before touching them.
+
+    // Streamed request body
+    /// Read the next chunk of a streamed request body into `buf` (at most `cap` bytes).
+    /// Blocks until data is available. Returns the number of bytes written, 0 at end of
+    /// body, or a negative `BODY_READ_*` code.
+    pub read_body: extern "C" fn(task_ctx: *mut c_void, buf: *mut u8, cap: usize) -> i64,
+
+    // Streamed response body
+    /// Send the status line and headers (taken from `response.status` and `response.header.*`)
+    /// and return a stream handle, or null if the host cannot stream or the stream was already
+    /// opened. The handle is not tied to the task: it may be moved to another thread and stays
+    /// valid until passed to `close_response_stream`.
+    pub open_response_stream: extern "C" fn(task_ctx: *mut c_void) -> *mut c_void,
+    /// Write `len` bytes to an open response stream. Blocks while the client is slower than
+    /// the writer. Returns `len`, or `RESPONSE_WRITE_CLOSED` once the client has disconnected.
+    pub write_response_stream: extern "C" fn(stream: *mut c_void, data: *const u8, len: usize) -> i64,
+    /// Finish the response body and free the handle. Null is ignored.
+    pub close_response_stream: extern "C" fn(stream: *mut c_void),
+
+    // Live client connections
+    /// Push a frame to the WebSocket connection `connection_id` (see the `ws.connection_id`
+    /// field). Sent as a binary frame if `binary`, else as text. Returns false if the
+    /// connection is unknown or closed. Never blocks.
+    pub ws_send: extern "C" fn(task_ctx: *mut c_void, connection_id: *const c_char, data: *const u8, len: usize, binary: bool) -> bool,
+    /// Close the WebSocket connection `connection_id`. Returns false if it is unknown.
+    pub ws_close: extern "C" fn(task_ctx: *mut c_void, connection_id: *const c_char) -> bool,
 }
 
 /// Type representing the plugin initialization function
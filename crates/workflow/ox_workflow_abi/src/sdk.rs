@@ -1,7 +1,7 @@
 use crate::{FlowControl, PluginCapabilities};
 use libc::{c_char, c_void};
 
-pub const PLUGIN_ABI_VERSION: u32 = 3;
+pub const PLUGIN_ABI_VERSION: u32 = 4;
 
 pub type PluginInitFn = extern "C" fn(
     plugin_config: *const c_char,
@@ -6,6 +6,7 @@ pub mod routes {
         Json, Router,
     };
     use ox_workflow_core::{Task, TaskStatus};
+    use ox_workflow_core::state::FieldValue;
     use ox_workflow_storage::WorkflowStorage;
     use ox_event_bus::EventBus;
     use serde::{Deserialize, Serialize};
@@ -48,6 +49,7 @@ pub mod routes {
             .route("/tasks/:id/history", get(get_task_history))
             .route("/tasks/:id/cancel", post(cancel_task))
             .route("/tasks/:id/resume", post(resume_task))
+            .route("/tasks/:id/requeue", post(requeue_task))
     }
 
     pub async fn start_server(state: ApiState, addr: &str) -> std::io::Result<()> {
@@ -149,19 +151,49 @@ pub mod routes {
     #[derive(Deserialize)]
     pub struct ResumeTaskRequest {
         pub signal: String,
+        /// Data delivered with the signal. Strings are stored as-is, anything else as JSON.
+        #[serde(default)]
+        pub payload: Option<serde_json::Value>,
     }
 
+    /// Task field holding the signal a suspended task was resumed with.
+    pub const RESUME_SIGNAL_FIELD: &str = "resume.signal";
+    /// Task field holding the payload of the resume request, if any.
+    pub const RESUME_PAYLOAD_FIELD: &str = "resume.payload";
+
+    /// Resumes a paused task from its checkpoint. If the task paused on a signal key
+    /// (`pause_task`), only that signal is accepted.
     #[axum::debug_handler]
     async fn resume_task(
         State(state): State<ApiState>,
         Path(id): Path<Uuid>,
-        Json(_payload): Json<ResumeTaskRequest>,
+        Json(payload): Json<ResumeTaskRequest>,
     ) -> Result<StatusCode, StatusCode> {
         let task = state.storage.load_task(id).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
         if let Some(mut t) = task {
             if t.status == TaskStatus::Paused {
+                if t.metadata.get("pause_signal_key").is_some_and(|key| *key != payload.signal) {
+                    return Err(StatusCode::CONFLICT);
+                }
+                t.metadata.remove("pause_signal_key");
+                {
+                    let mut w = t.state.write();
+                    w.fields.insert(RESUME_SIGNAL_FIELD.to_string(), FieldValue::String(payload.signal));
+                    match payload.payload {
+                        Some(serde_json::Value::String(s)) => {
+                            w.fields.insert(RESUME_PAYLOAD_FIELD.to_string(), FieldValue::String(s));
+                        }
+                        Some(v) => {
+                            w.fields.insert(RESUME_PAYLOAD_FIELD.to_string(), FieldValue::String(v.to_string()));
+                        }
+                        None => {
+                            w.fields.remove(RESUME_PAYLOAD_FIELD);
+                        }
+                    }
+                }
                 t.status = TaskStatus::Queued;
-                state.storage.update_task_status(id, TaskStatus::Queued).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+                let flow_name = t.metadata.get("flow_name").cloned();
+                state.storage.save_task(&t, flow_name.as_deref(), None).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
                 
                 state.event_bus.publish_to_queue("tasks.pending", t.priority as u8, id.to_string().as_bytes())
                     .await
@@ -176,6 +208,59 @@ pub mod routes {
         }
     }
 
+    #[derive(Deserialize, Default)]
+    pub struct RequeueTaskRequest {
+        /// Queue to publish to. Defaults to the queue the task last ran from.
+        #[serde(default)]
+        pub queue: Option<String>,
+        /// Start the flow over instead of retrying the stage that failed.
+        #[serde(default)]
+        pub restart: bool,
+    }
+
+    /// Puts an errored task (typically one parked on a dead-letter queue) back in line
+    /// with a fresh retry budget.
+    #[axum::debug_handler]
+    async fn requeue_task(
+        State(state): State<ApiState>,
+        Path(id): Path<Uuid>,
+        payload: Option<Json<RequeueTaskRequest>>,
+    ) -> Result<StatusCode, StatusCode> {
+        let Json(payload) = payload.unwrap_or_default();
+        let task = state.storage.load_task(id).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let Some(mut t) = task else { return Err(StatusCode::NOT_FOUND) };
+        if t.status != TaskStatus::Errored {
+            return Err(StatusCode::CONFLICT);
+        }
+
+        let queue = payload.queue
+            .or_else(|| t.metadata.get("queue").cloned())
+            .unwrap_or_else(|| "tasks.pending".to_string());
+        let from = t.metadata.remove("dead_letter_queue");
+        if payload.restart {
+            t.checkpoint = None;
+        }
+        t.attempt = 1;
+        t.status = TaskStatus::Queued;
+        let flow_name = t.metadata.get("flow_name").cloned();
+        state.storage.save_task(&t, flow_name.as_deref(), None).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let message = match from {
+            Some(dlq) => format!("Requeued from {} to {}", dlq, queue),
+            None => format!("Requeued to {}", queue),
+        };
+        let stage = t.checkpoint.as_ref().map(|c| c.stage.as_str()).unwrap_or("");
+        state.storage.append_history(id, stage, None, "Requeued", Some(&message), t.attempt)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        state.event_bus.publish_to_queue(&queue, t.priority as u8, id.to_string().as_bytes())
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(StatusCode::OK)
+    }
+
     #[axum::debug_handler]
     async fn cancel_task(
         State(state): State<ApiState>,
@@ -5,6 +5,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::ffi::{c_char, CString};
 use std::sync::Arc;
+use std::time::Duration;
 use indexmap::IndexSet;
 use uuid::Uuid;
 
@@ -25,6 +26,13 @@ pub struct HistoryRecord {
     pub plugin_name: Option<String>,
     pub status: String,
     pub message: Option<String>,
+    /// Run attempt of the task that produced this record (see `Task::attempt`).
+    #[serde(default = "first_attempt")]
+    pub attempt: u32,
+}
+
+fn first_attempt() -> u32 {
+    1
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,6 +50,67 @@ impl TaskFlags {
     }
 }
 
+/// Position a suspended task resumes from. Recorded by the flow runner when a plugin
+/// returns `FLOW_CONTROL_SUSPEND` and persisted with the task.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Stage that was running when the task suspended.
+    pub stage: String,
+    /// Index of the plugin that suspended. It runs again first on resume, so it can
+    /// inspect the resume signal and decide whether to continue or suspend again.
+    pub plugin_index: usize,
+    /// The stage was reached through a JUMP, so the flow ends after it.
+    #[serde(default)]
+    pub jumped: bool,
+}
+
+/// Notified by the flow runner whenever a task enters a stage. The scheduler uses it to
+/// enforce stage deadlines on tasks that are running on another thread.
+pub trait StageObserver: Send + Sync {
+    fn stage_started(&self, stage: &str);
+}
+
+/// Failure reported by a [`BodyReader`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BodyReadError {
+    /// The body exceeded the configured size limit.
+    TooLarge,
+    /// The underlying connection failed.
+    Io(String),
+}
+
+/// Pull-based source for a request body that is streamed to plugins rather than buffered.
+/// Reads may block; plugins are always executed off the async worker threads.
+pub trait BodyReader: Send {
+    /// Reads up to `buf.len()` bytes. `Ok(0)` marks the end of the body.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, BodyReadError>;
+}
+
+/// Failure reported by a [`ResponseStream`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResponseWriteError {
+    /// The client is gone; nothing more will be delivered.
+    Closed,
+}
+
+/// Push-based sink for a response body written incrementally by a plugin.
+/// `open` is called once, before any `write`. Writes may block to apply backpressure.
+pub trait ResponseStream: Send {
+    /// Sends the status line and headers.
+    fn open(&mut self, status: u16, headers: Vec<(String, String)>) -> Result<(), ResponseWriteError>;
+    /// Sends one chunk of the body.
+    fn write(&mut self, data: &[u8]) -> Result<(), ResponseWriteError>;
+}
+
+/// Registry of live client connections (WebSocket sessions) that plugins can push to,
+/// independently of the task that accepted the connection.
+pub trait ConnectionHub: Send + Sync {
+    /// Queues a frame for the connection. Returns false if it is unknown or closed.
+    fn send(&self, connection_id: &str, data: &[u8], binary: bool) -> bool;
+    /// Asks the connection to close. Returns false if it is unknown.
+    fn close(&self, connection_id: &str) -> bool;
+}
+
 /// The core task object that is passed around and orchestrated.
 pub struct Task {
     pub id: Uuid,
@@ -67,6 +136,18 @@ pub struct Task {
     pub api_call_counts: HashMap<String, u32>,
     /// Per-function call limits (function name → max, 0 = unlimited)
     pub api_limits: HashMap<String, u32>,
+    /// Streamed request body, consumed by plugins through `CoreHostApi::read_body`
+    pub body_reader: Option<Box<dyn BodyReader>>,
+    /// Streamed response sink, taken by plugins through `CoreHostApi::open_response_stream`
+    pub response_stream: Option<Box<dyn ResponseStream>>,
+    /// Live connections reachable through `CoreHostApi::ws_send` / `ws_close`
+    pub connections: Option<Arc<dyn ConnectionHub>>,
+    /// Where a suspended task continues; consumed by the next `FlowRunner::run`
+    pub checkpoint: Option<Checkpoint>,
+    /// 1-based run attempt; incremented by the scheduler each time a failed task is retried
+    pub attempt: u32,
+    /// Told about every stage the task enters
+    pub stage_observer: Option<Arc<dyn StageObserver>>,
 }
 
 impl Drop for Task {
@@ -98,6 +179,12 @@ impl Task {
             ffi_bytes_arena: Vec::new(),
             api_call_counts: HashMap::new(),
             api_limits: HashMap::new(),
+            body_reader: None,
+            response_stream: None,
+            connections: None,
+            checkpoint: None,
+            attempt: 1,
+            stage_observer: None,
         }
     }
 }
@@ -109,6 +196,12 @@ pub struct StageDef {
     pub runner: String,
     pub plugins: Vec<PluginDef>,
     pub on_error: Option<String>,
+    /// Retries for a task whose run fails in this stage. Overrides the flow's policy.
+    #[serde(default)]
+    pub retry: Option<RetryPolicy>,
+    /// Wall-clock limit for one pass through this stage. Enforced by the scheduler.
+    #[serde(default)]
+    pub deadline_ms: Option<u64>,
 }
 
 /// Config for a specific plugin library in a stage
@@ -124,4 +217,56 @@ pub struct FlowDef {
     pub name: String,
     pub persistent: bool,
     pub stages: Vec<String>,
+    /// Retries for failed runs of this flow's tasks, unless the failing stage has its own.
+    #[serde(default)]
+    pub retry: Option<RetryPolicy>,
+    /// Wall-clock limit for one run (attempt) of a task. Enforced by the scheduler.
+    #[serde(default)]
+    pub deadline_ms: Option<u64>,
+    /// Queue that receives the IDs of tasks that failed for good. Defaults to the
+    /// scheduler's `DeadLetter` queue.
+    #[serde(default)]
+    pub dead_letter_queue: Option<String>,
+}
+
+/// How often and how soon a failed task is run again. Retries resume at the start of
+/// the stage that failed.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RetryPolicy {
+    /// Attempts after the first one; 0 disables retries.
+    pub max_retries: u32,
+    /// Delay before the first retry.
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    /// Factor applied to the delay after each retry.
+    #[serde(default = "default_backoff_multiplier")]
+    pub backoff_multiplier: f64,
+    /// Upper bound on the delay.
+    #[serde(default)]
+    pub max_backoff_ms: Option<u64>,
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    1000
+}
+
+fn default_backoff_multiplier() -> f64 {
+    2.0
+}
+
+impl RetryPolicy {
+    /// Whether a task whose attempt `attempt` just failed may run again.
+    pub fn allows_retry(&self, attempt: u32) -> bool {
+        attempt <= self.max_retries
+    }
+
+    /// Delay before the retry following failed attempt `attempt` (1-based).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = self.backoff_multiplier.max(1.0).powi(attempt.saturating_sub(1) as i32);
+        let mut ms = (self.initial_backoff_ms as f64 * factor).min(u64::MAX as f64) as u64;
+        if let Some(max) = self.max_backoff_ms {
+            ms = ms.min(max);
+        }
+        Duration::from_millis(ms)
+    }
 }
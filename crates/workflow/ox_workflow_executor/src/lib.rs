@@ -3,9 +3,9 @@ pub mod wasm_plugin;
 
 use ox_workflow_abi::{
     CoreHostApi, FlowControl, FLOW_CONTROL_CONTINUE, FLOW_CONTROL_END, FLOW_CONTROL_ERROR,
-    FLOW_CONTROL_JUMP, FLOW_CONTROL_SKIP, FLOW_CONTROL_YIELD,
+    FLOW_CONTROL_JUMP, FLOW_CONTROL_SKIP, FLOW_CONTROL_SUSPEND, FLOW_CONTROL_YIELD,
 };
-use ox_workflow_core::{Task, TaskStatus, FlowDef, StageDef};
+use ox_workflow_core::{Checkpoint, Task, TaskStatus, FlowDef, StageDef};
 use std::collections::HashMap;
 use std::ffi::{c_char, c_void, CStr, CString};
 use std::sync::Arc;
@@ -19,11 +19,16 @@ pub struct StageRunner {
 }
 
 impl StageRunner {
-    pub fn run(&self, task: &mut Task, _api: &CoreHostApi) -> FlowControl {
+    pub fn run(&self, task: &mut Task, api: &CoreHostApi) -> FlowControl {
+        self.run_from(task, api, 0)
+    }
+
+    /// Runs the stage starting at plugin `start`; earlier plugins are not executed.
+    pub fn run_from(&self, task: &mut Task, _api: &CoreHostApi, start: usize) -> FlowControl {
         // Clear stage-scoped flags at the start of each stage (spec requirement)
         task.flags.stage.clear();
 
-        let mut i = 0;
+        let mut i = start;
         let mut last_fc = FlowControl {
             code: FLOW_CONTROL_CONTINUE,
             payload: std::ptr::null(),
@@ -51,6 +56,7 @@ impl StageRunner {
                 plugin_name: Some(plugin.name.clone()),
                 status: if fc.code == FLOW_CONTROL_ERROR { "Error".to_string() } else { "Completed".to_string() },
                 message: error_msg,
+                attempt: task.attempt,
             });
 
             if fc.code == FLOW_CONTROL_ERROR {
@@ -109,6 +115,7 @@ impl StageRunner {
                                     plugin_name: Some(target_plugin.name.clone()),
                                     status: if target_fc.code == FLOW_CONTROL_ERROR { "Error".to_string() } else { "Completed".to_string() },
                                     message: target_error_msg,
+                                    attempt: task.attempt,
                                 });
                                 if target_fc.code == FLOW_CONTROL_ERROR {
                                     let task_ptr2 = task as *mut Task as *mut c_void;
@@ -151,8 +158,11 @@ pub struct FlowRunner {
 }
 
 impl FlowRunner {
+    /// Runs the task from the first stage, or from its checkpoint if it was suspended.
+    /// A SUSPEND records a new checkpoint at the suspending plugin.
     pub fn run(&self, task: &mut Task, api: &CoreHostApi) -> FlowControl {
         let mut current_stage_idx = 0;
+        let mut start_plugin = 0;
         let mut last_fc = FlowControl {
             code: FLOW_CONTROL_CONTINUE,
             payload: std::ptr::null(),
@@ -160,14 +170,36 @@ impl FlowRunner {
 
         task.metadata.insert("flow_name".to_string(), self.flow_name.clone());
 
-        while current_stage_idx < self.stages.len() {
-            task.metadata.insert(
-                "current_stage".to_string(),
-                self.stages[current_stage_idx].name.clone(),
-            );
+        if let Some(checkpoint) = task.checkpoint.take() {
+            match self.stages.iter().position(|s| s.name == checkpoint.stage) {
+                Some(idx) if checkpoint.jumped => {
+                    // Finish the JUMP target, then stop — as the original JUMP would have.
+                    let stage = &self.stages[idx];
+                    Self::enter_stage(task, stage);
+                    last_fc = stage.run_from(task, api, checkpoint.plugin_index);
+                    if last_fc.code == FLOW_CONTROL_SUSPEND {
+                        Self::checkpoint(task, stage, true);
+                    }
+                    return last_fc;
+                }
+                Some(idx) => {
+                    current_stage_idx = idx;
+                    start_plugin = checkpoint.plugin_index;
+                }
+                None => {
+                    log::warn!(
+                        "Task {}: checkpoint stage '{}' is not part of flow '{}', restarting the flow",
+                        task.id, checkpoint.stage, self.flow_name
+                    );
+                }
+            }
+        }
 
+        while current_stage_idx < self.stages.len() {
             let stage = &self.stages[current_stage_idx];
-            last_fc = stage.run(task, api);
+            Self::enter_stage(task, stage);
+
+            last_fc = stage.run_from(task, api, std::mem::take(&mut start_plugin));
 
             match last_fc.code {
                 FLOW_CONTROL_CONTINUE | FLOW_CONTROL_SKIP => {
@@ -186,8 +218,11 @@ impl FlowRunner {
                         let target_name = unsafe { CStr::from_ptr(last_fc.payload) }.to_string_lossy();
                         if let Some(idx) = self.stages.iter().position(|s| s.name == target_name) {
                             let target_stage = &self.stages[idx];
-                            task.metadata.insert("current_stage".to_string(), target_stage.name.clone());
+                            Self::enter_stage(task, target_stage);
                             last_fc = target_stage.run(task, api);
+                            if last_fc.code == FLOW_CONTROL_SUSPEND {
+                                Self::checkpoint(task, target_stage, true);
+                            }
                         } else {
                             last_fc.code = FLOW_CONTROL_ERROR;
                             if let Some(cb) = &task.error_callback { cb(); }
@@ -214,8 +249,12 @@ impl FlowRunner {
                     if let Some(cb) = &task.error_callback { cb(); }
                     return last_fc;
                 }
+                FLOW_CONTROL_SUSPEND => {
+                    Self::checkpoint(task, stage, false);
+                    return last_fc;
+                }
                 _ => {
-                    // SUSPEND, END
+                    // END
                     return last_fc;
                 }
             }
@@ -228,6 +267,22 @@ impl FlowRunner {
 
         last_fc
     }
+
+    fn enter_stage(task: &mut Task, stage: &StageRunner) {
+        task.metadata.insert("current_stage".to_string(), stage.name.clone());
+        if let Some(observer) = &task.stage_observer {
+            observer.stage_started(&stage.name);
+        }
+    }
+
+    /// Records the plugin that just suspended (tracked in `current_plugin_index`) as the
+    /// resume point.
+    fn checkpoint(task: &mut Task, stage: &StageRunner, jumped: bool) {
+        let plugin_index = task.metadata.get("current_plugin_index")
+            .and_then(|i| i.parse().ok())
+            .unwrap_or(0);
+        task.checkpoint = Some(Checkpoint { stage: stage.name.clone(), plugin_index, jumped });
+    }
 }
 
 /// Creates the CoreHostApi static function table.
@@ -436,6 +491,93 @@ pub fn create_host_api() -> CoreHostApi {
         false
     }
 
+    extern "C" fn read_body_impl(task_ctx: *mut c_void, buf: *mut u8, cap: usize) -> i64 {
+        let task = unsafe { &mut *(task_ctx as *mut Task) };
+        let Some(reader) = task.body_reader.as_mut() else {
+            return ox_workflow_abi::BODY_READ_UNAVAILABLE;
+        };
+        if buf.is_null() || cap == 0 {
+            return 0;
+        }
+        let out = unsafe { std::slice::from_raw_parts_mut(buf, cap) };
+        match reader.read(out) {
+            Ok(n) => n as i64,
+            Err(ox_workflow_core::BodyReadError::TooLarge) => ox_workflow_abi::BODY_READ_TOO_LARGE,
+            Err(ox_workflow_core::BodyReadError::Io(e)) => {
+                log::warn!("Task {}: request body read failed: {}", task.id, e);
+                ox_workflow_abi::BODY_READ_ERROR
+            }
+        }
+    }
+
+    extern "C" fn open_response_stream_impl(task_ctx: *mut c_void) -> *mut c_void {
+        let task = unsafe { &mut *(task_ctx as *mut Task) };
+        let Some(mut stream) = task.response_stream.take() else {
+            return std::ptr::null_mut();
+        };
+        let (status, headers) = {
+            let r = task.state.read();
+            let status = match r.fields.get("response.status") {
+                Some(ox_workflow_core::state::FieldValue::String(s)) => s.parse::<u16>().unwrap_or(200),
+                _ => 200,
+            };
+            let headers = r.fields.iter()
+                .filter_map(|(k, v)| match (k.strip_prefix("response.header."), v) {
+                    (Some(name), ox_workflow_core::state::FieldValue::String(s)) => Some((name.to_string(), s.clone())),
+                    _ => None,
+                })
+                .collect();
+            (status, headers)
+        };
+        if stream.open(status, headers).is_err() {
+            return std::ptr::null_mut();
+        }
+        // Double-boxed so the handle is a thin pointer.
+        Box::into_raw(Box::new(stream)) as *mut c_void
+    }
+
+    extern "C" fn write_response_stream_impl(stream: *mut c_void, data: *const u8, len: usize) -> i64 {
+        if stream.is_null() {
+            return ox_workflow_abi::RESPONSE_WRITE_CLOSED;
+        }
+        if data.is_null() || len == 0 {
+            return 0;
+        }
+        let stream = unsafe { &mut *(stream as *mut Box<dyn ox_workflow_core::ResponseStream>) };
+        let chunk = unsafe { std::slice::from_raw_parts(data, len) };
+        match stream.write(chunk) {
+            Ok(()) => len as i64,
+            Err(ox_workflow_core::ResponseWriteError::Closed) => ox_workflow_abi::RESPONSE_WRITE_CLOSED,
+        }
+    }
+
+    extern "C" fn close_response_stream_impl(stream: *mut c_void) {
+        if !stream.is_null() {
+            drop(unsafe { Box::from_raw(stream as *mut Box<dyn ox_workflow_core::ResponseStream>) });
+        }
+    }
+
+    extern "C" fn ws_send_impl(task_ctx: *mut c_void, connection_id: *const c_char, data: *const u8, len: usize, binary: bool) -> bool {
+        let task = unsafe { &mut *(task_ctx as *mut Task) };
+        let Some(hub) = task.connections.as_ref() else { return false };
+        if connection_id.is_null() || (data.is_null() && len > 0) {
+            return false;
+        }
+        let id = unsafe { CStr::from_ptr(connection_id) }.to_string_lossy();
+        let payload = if len == 0 { &[][..] } else { unsafe { std::slice::from_raw_parts(data, len) } };
+        hub.send(&id, payload, binary)
+    }
+
+    extern "C" fn ws_close_impl(task_ctx: *mut c_void, connection_id: *const c_char) -> bool {
+        let task = unsafe { &mut *(task_ctx as *mut Task) };
+        let Some(hub) = task.connections.as_ref() else { return false };
+        if connection_id.is_null() {
+            return false;
+        }
+        let id = unsafe { CStr::from_ptr(connection_id) }.to_string_lossy();
+        hub.close(&id)
+    }
+
     CoreHostApi {
         get_field: get_field_impl,
         set_field: set_field_impl,
@@ -452,6 +594,12 @@ pub fn create_host_api() -> CoreHostApi {
         get_keys: get_keys_impl,
         unset_field: unset_field_impl,
         has_field: has_field_impl,
+        read_body: read_body_impl,
+        open_response_stream: open_response_stream_impl,
+        write_response_stream: write_response_stream_impl,
+        close_response_stream: close_response_stream_impl,
+        ws_send: ws_send_impl,
+        ws_close: ws_close_impl,
     }
 }
 
@@ -459,6 +607,8 @@ pub struct FlowManager {
     pub flows: HashMap<String, Arc<FlowRunner>>,
     pub registry: HashMap<String, Arc<LoadedPlugin>>,
     pub stage_defs: HashMap<String, StageDef>,
+    /// Definitions of the built flows, for their retry and deadline settings.
+    pub flow_defs: HashMap<String, FlowDef>,
 }
 
 impl FlowManager {
@@ -467,6 +617,7 @@ impl FlowManager {
             flows: HashMap::new(),
             registry: HashMap::new(),
             stage_defs: HashMap::new(),
+            flow_defs: HashMap::new(),
         }
     }
 
@@ -571,6 +722,7 @@ impl FlowManager {
         });
 
         self.flows.insert(flow_def.name.clone(), runner.clone());
+        self.flow_defs.insert(flow_def.name.clone(), flow_def.clone());
 
         Ok(runner)
     }
@@ -708,4 +860,321 @@ mod tests {
         assert!(!(api.insert_into_flow)(task_ptr, flow.as_ptr())); // 3 — should be blocked
         assert_eq!(task.child_workflows.len(), 2);
     }
+
+    struct ChunkedBody {
+        chunks: Vec<Vec<u8>>,
+        fail_with: Option<ox_workflow_core::BodyReadError>,
+    }
+
+    impl ox_workflow_core::BodyReader for ChunkedBody {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, ox_workflow_core::BodyReadError> {
+            if self.chunks.is_empty() {
+                return match self.fail_with.take() {
+                    Some(e) => Err(e),
+                    None => Ok(0),
+                };
+            }
+            let chunk = self.chunks.remove(0);
+            let n = chunk.len().min(buf.len());
+            buf[..n].copy_from_slice(&chunk[..n]);
+            if n < chunk.len() {
+                self.chunks.insert(0, chunk[n..].to_vec());
+            }
+            Ok(n)
+        }
+    }
+
+    /// read_body drains the attached reader chunk by chunk and reports EOF as 0.
+    #[test]
+    fn test_read_body_streams_chunks() {
+        let api = make_api();
+        let mut task = Task::new(1);
+        task.body_reader = Some(Box::new(ChunkedBody {
+            chunks: vec![b"hello ".to_vec(), b"world".to_vec()],
+            fail_with: None,
+        }));
+        let task_ptr = &mut task as *mut Task as *mut c_void;
+
+        let mut collected = Vec::new();
+        let mut buf = [0u8; 4];
+        loop {
+            let n = (api.read_body)(task_ptr, buf.as_mut_ptr(), buf.len());
+            assert!(n >= 0);
+            if n == 0 { break; }
+            collected.extend_from_slice(&buf[..n as usize]);
+        }
+        assert_eq!(collected, b"hello world");
+    }
+
+    /// read_body maps reader failures and a missing reader to the BODY_READ_* codes.
+    #[test]
+    fn test_read_body_error_codes() {
+        let api = make_api();
+        let mut buf = [0u8; 8];
+
+        let mut task = Task::new(1);
+        let task_ptr = &mut task as *mut Task as *mut c_void;
+        assert_eq!((api.read_body)(task_ptr, buf.as_mut_ptr(), buf.len()), ox_workflow_abi::BODY_READ_UNAVAILABLE);
+
+        let mut task = Task::new(1);
+        task.body_reader = Some(Box::new(ChunkedBody {
+            chunks: vec![],
+            fail_with: Some(ox_workflow_core::BodyReadError::TooLarge),
+        }));
+        let task_ptr = &mut task as *mut Task as *mut c_void;
+        assert_eq!((api.read_body)(task_ptr, buf.as_mut_ptr(), buf.len()), ox_workflow_abi::BODY_READ_TOO_LARGE);
+    }
+
+    type ResponseHead = (u16, Vec<(String, String)>);
+
+    #[derive(Default)]
+    struct RecordingStream {
+        head: Arc<parking_lot::Mutex<Option<ResponseHead>>>,
+        body: Arc<parking_lot::Mutex<Vec<u8>>>,
+        closed_after: Option<usize>,
+    }
+
+    impl ox_workflow_core::ResponseStream for RecordingStream {
+        fn open(&mut self, status: u16, headers: Vec<(String, String)>) -> Result<(), ox_workflow_core::ResponseWriteError> {
+            *self.head.lock() = Some((status, headers));
+            Ok(())
+        }
+
+        fn write(&mut self, data: &[u8]) -> Result<(), ox_workflow_core::ResponseWriteError> {
+            let mut body = self.body.lock();
+            if self.closed_after.is_some_and(|limit| body.len() >= limit) {
+                return Err(ox_workflow_core::ResponseWriteError::Closed);
+            }
+            body.extend_from_slice(data);
+            Ok(())
+        }
+    }
+
+    /// open_response_stream snapshots status and headers, and the handle outlives the task.
+    #[test]
+    fn test_response_stream_open_write_close() {
+        let api = make_api();
+        let stream = RecordingStream::default();
+        let head = stream.head.clone();
+        let body = stream.body.clone();
+
+        let mut task = Task::new(1);
+        task.response_stream = Some(Box::new(stream));
+        let task_ptr = &mut task as *mut Task as *mut c_void;
+        let status_key = CString::new("response.status").unwrap();
+        let status_val = CString::new("201").unwrap();
+        (api.set_field)(task_ptr, status_key.as_ptr(), status_val.as_ptr());
+        let ct_key = CString::new("response.header.Content-Type").unwrap();
+        let ct_val = CString::new("text/event-stream").unwrap();
+        (api.set_field)(task_ptr, ct_key.as_ptr(), ct_val.as_ptr());
+
+        let handle = (api.open_response_stream)(task_ptr);
+        assert!(!handle.is_null());
+        // The stream can only be opened once.
+        assert!((api.open_response_stream)(task_ptr).is_null());
+        drop(task);
+
+        let handle_addr = handle as usize;
+        let writer = std::thread::spawn(move || {
+            let handle = handle_addr as *mut c_void;
+            let chunk = b"data: 1\n\n";
+            let n = (api.write_response_stream)(handle, chunk.as_ptr(), chunk.len());
+            (api.close_response_stream)(handle);
+            n
+        });
+        assert_eq!(writer.join().unwrap(), 9);
+
+        let (status, headers) = head.lock().clone().unwrap();
+        assert_eq!(status, 201);
+        assert_eq!(headers, vec![("Content-Type".to_string(), "text/event-stream".to_string())]);
+        assert_eq!(&body.lock()[..], b"data: 1\n\n");
+    }
+
+    /// Writes report RESPONSE_WRITE_CLOSED once the sink is gone; no sink means no handle.
+    #[test]
+    fn test_response_stream_closed_and_unavailable() {
+        let api = make_api();
+        let mut task = Task::new(1);
+        let task_ptr = &mut task as *mut Task as *mut c_void;
+        assert!((api.open_response_stream)(task_ptr).is_null());
+
+        task.response_stream = Some(Box::new(RecordingStream { closed_after: Some(0), ..Default::default() }));
+        let task_ptr = &mut task as *mut Task as *mut c_void;
+        let handle = (api.open_response_stream)(task_ptr);
+        let chunk = b"late";
+        assert_eq!((api.write_response_stream)(handle, chunk.as_ptr(), chunk.len()), ox_workflow_abi::RESPONSE_WRITE_CLOSED);
+        assert_eq!((api.write_response_stream)(std::ptr::null_mut(), chunk.as_ptr(), chunk.len()), ox_workflow_abi::RESPONSE_WRITE_CLOSED);
+        (api.close_response_stream)(handle);
+    }
+
+    #[derive(Default)]
+    struct RecordingHub {
+        sent: parking_lot::Mutex<Vec<(String, Vec<u8>, bool)>>,
+    }
+
+    impl ox_workflow_core::ConnectionHub for RecordingHub {
+        fn send(&self, connection_id: &str, data: &[u8], binary: bool) -> bool {
+            if connection_id != "conn-1" { return false; }
+            self.sent.lock().push((connection_id.to_string(), data.to_vec(), binary));
+            true
+        }
+
+        fn close(&self, connection_id: &str) -> bool {
+            connection_id == "conn-1"
+        }
+    }
+
+    /// ws_send/ws_close forward to the task's connection hub and fail without one.
+    #[test]
+    fn test_ws_send_and_close() {
+        let api = make_api();
+        let id = CString::new("conn-1").unwrap();
+        let unknown = CString::new("conn-2").unwrap();
+        let frame = [0u8, 1, 2];
+
+        let mut task = Task::new(1);
+        let task_ptr = &mut task as *mut Task as *mut c_void;
+        assert!(!(api.ws_send)(task_ptr, id.as_ptr(), frame.as_ptr(), frame.len(), true));
+
+        let hub = Arc::new(RecordingHub::default());
+        task.connections = Some(hub.clone());
+        let task_ptr = &mut task as *mut Task as *mut c_void;
+        assert!((api.ws_send)(task_ptr, id.as_ptr(), frame.as_ptr(), frame.len(), true));
+        assert!(!(api.ws_send)(task_ptr, unknown.as_ptr(), frame.as_ptr(), frame.len(), false));
+        assert!((api.ws_close)(task_ptr, id.as_ptr()));
+        assert!(!(api.ws_close)(task_ptr, unknown.as_ptr()));
+        assert_eq!(*hub.sent.lock(), vec![("conn-1".to_string(), vec![0, 1, 2], true)]);
+    }
+
+    /// A `.wasm` plugin path is loaded into the sandbox and runs as a normal stage plugin.
+    #[test]
+    fn test_wasm_plugin_in_stage() {
+        let wasm = wat::parse_str(r#"
+            (module
+              (import "ox" "set_flag" (func $set_flag (param i32 i32 i32)))
+              (memory (export "memory") 1)
+              (data (i32.const 0) "wasm_ran")
+              (func (export "alloc") (param i32) (result i32) (i32.const 64))
+              (func (export "ox_plugin_init") (param i32 i32) (result i32) (i32.const 1))
+              (func (export "ox_plugin_process") (param i32) (result i32)
+                (call $set_flag (i32.const 0) (i32.const 8) (i32.const 1))
+                (i32.const 0)))
+        "#).unwrap();
+        let path = std::env::temp_dir().join(format!("ox_wasm_{}.wasm", uuid::Uuid::new_v4()));
+        std::fs::write(&path, wasm).unwrap();
+
+        let api = make_api();
+        let mut manager = FlowManager::new();
+        manager.stage_defs.insert("s1".to_string(), StageDef {
+            name: "s1".to_string(),
+            runner: "default".to_string(),
+            plugins: vec![ox_workflow_core::PluginDef {
+                name: "flagger".to_string(),
+                config: Some(serde_json::json!({ "wasm": { "memory_limit_pages": 2 } })),
+            }],
+            on_error: None,
+            retry: None,
+            deadline_ms: None,
+        });
+        let paths = HashMap::from([("flagger".to_string(), path.to_string_lossy().into_owned())]);
+        let flow = FlowDef {
+            name: "f".to_string(),
+            persistent: false,
+            stages: vec!["s1".to_string()],
+            retry: None,
+            deadline_ms: None,
+            dead_letter_queue: None,
+        };
+        let runner = unsafe { manager.build_flow(&flow, &api, &paths) }.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut task = Task::new(1);
+        assert_eq!(runner.run(&mut task, &api).code, FLOW_CONTROL_END);
+        assert!(task.flags.persistent.contains("wasm_ran"));
+    }
+
+    fn wasm_instance(name: &str, wat: &str) -> PluginInstance {
+        let module = crate::wasm_plugin::WasmModule::from_wasm_bytes(name, &wat::parse_str(wat).unwrap()).unwrap();
+        let plugin = Arc::new(LoadedPlugin::Wasm(module));
+        let ctx = plugin.init("{}", &make_api()).unwrap();
+        PluginInstance { name: name.to_string(), plugin, ctx }
+    }
+
+    const PASS: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (func (export "alloc") (param i32) (result i32) (i32.const 64))
+          (func (export "ox_plugin_init") (param i32 i32) (result i32) (i32.const 1))
+          (func (export "ox_plugin_process") (param i32) (result i32) (i32.const 0)))
+    "#;
+
+    /// Suspends until the task has a `resume.signal` field.
+    const GATE: &str = r#"
+        (module
+          (import "ox" "get_field" (func $get_field (param i32 i32) (result i64)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "resume.signal")
+          (func (export "alloc") (param i32) (result i32) (i32.const 64))
+          (func (export "ox_plugin_init") (param i32 i32) (result i32) (i32.const 1))
+          (func (export "ox_plugin_process") (param i32) (result i32)
+            (if (result i32) (i64.lt_s (call $get_field (i32.const 0) (i32.const 13)) (i64.const 0))
+              (then (i32.const 5))
+              (else (i32.const 0)))))
+    "#;
+
+    fn ran(task: &Task) -> Vec<String> {
+        task.history.iter().filter_map(|h| h.plugin_name.clone()).collect()
+    }
+
+    /// A resumed task continues at the suspending plugin instead of re-running the flow.
+    #[test]
+    fn test_suspend_records_checkpoint_and_resumes() {
+        let api = make_api();
+        let runner = FlowRunner {
+            flow_name: "approval".to_string(),
+            stages: vec![
+                StageRunner { name: "prepare".to_string(), on_error_target: None, plugins: vec![wasm_instance("first", PASS)] },
+                StageRunner {
+                    name: "approve".to_string(),
+                    on_error_target: None,
+                    plugins: vec![wasm_instance("before", PASS), wasm_instance("gate", GATE)],
+                },
+                StageRunner { name: "finish".to_string(), on_error_target: None, plugins: vec![wasm_instance("last", PASS)] },
+            ],
+        };
+
+        let mut task = Task::new(1);
+        assert_eq!(runner.run(&mut task, &api).code, FLOW_CONTROL_SUSPEND);
+        assert_eq!(task.checkpoint, Some(Checkpoint { stage: "approve".to_string(), plugin_index: 1, jumped: false }));
+        assert_eq!(ran(&task), vec!["first", "before", "gate"]);
+
+        // Still no signal: suspends again at the same point.
+        task.history.clear();
+        assert_eq!(runner.run(&mut task, &api).code, FLOW_CONTROL_SUSPEND);
+        assert_eq!(ran(&task), vec!["gate"]);
+        assert!(task.checkpoint.is_some());
+
+        task.history.clear();
+        task.state.write().fields.insert(
+            "resume.signal".to_string(),
+            ox_workflow_core::state::FieldValue::String("approved".to_string()),
+        );
+        assert_eq!(runner.run(&mut task, &api).code, FLOW_CONTROL_END);
+        assert_eq!(ran(&task), vec!["gate", "last"]);
+        assert_eq!(task.checkpoint, None);
+    }
+
+    /// A checkpoint naming a stage the flow no longer has restarts the flow.
+    #[test]
+    fn test_stale_checkpoint_restarts_flow() {
+        let api = make_api();
+        let runner = FlowRunner {
+            flow_name: "f".to_string(),
+            stages: vec![StageRunner { name: "only".to_string(), on_error_target: None, plugins: vec![wasm_instance("p", PASS)] }],
+        };
+        let mut task = Task::new(1);
+        task.checkpoint = Some(Checkpoint { stage: "removed".to_string(), plugin_index: 3, jumped: false });
+        assert_eq!(runner.run(&mut task, &api).code, FLOW_CONTROL_END);
+        assert_eq!(ran(&task), vec!["p"]);
+    }
 }
@@ -8,6 +8,7 @@ use std::collections::HashSet;
 use std::ffi::CString;
 use std::sync::Arc;
 use thiserror::Error;
+use crate::wasm_plugin::{WasmModule, WasmPlugin, WasmPluginConfig, WasmPluginError};
 
 #[derive(Error, Debug)]
 pub enum PluginError {
@@ -21,6 +22,8 @@ pub enum PluginError {
     MissingDependency(String),
     #[error("Capability not supported: {0}")]
     UnsupportedCapability(String),
+    #[error("WASM plugin error: {0}")]
+    Wasm(#[from] WasmPluginError),
 }
 
 #[derive(Debug, Clone, Default)]
@@ -49,7 +52,67 @@ impl PluginConfig {
     }
 }
 
-pub struct LoadedPlugin {
+/// A plugin loaded from a native shared library or, for `.wasm` paths, a sandboxed WASM module.
+/// Both are driven through the same init/process/error/destroy interface.
+pub enum LoadedPlugin {
+    Native(NativePlugin),
+    Wasm(WasmModule),
+}
+
+impl LoadedPlugin {
+    /// # Safety
+    /// Native libraries run their initializers on load; `path` must point to a trusted plugin.
+    pub unsafe fn new(path: &str) -> Result<Self, PluginError> {
+        if path.ends_with(".wasm") {
+            Ok(LoadedPlugin::Wasm(WasmModule::from_file(path)?))
+        } else {
+            Ok(LoadedPlugin::Native(NativePlugin::new(path)?))
+        }
+    }
+
+    pub fn check_dependencies(&self, loaded_plugins: &HashSet<String>) -> Result<(), PluginError> {
+        match self {
+            LoadedPlugin::Native(p) => p.check_dependencies(loaded_plugins),
+            LoadedPlugin::Wasm(_) => Ok(()),
+        }
+    }
+
+    /// For WASM plugins the returned context is a boxed [`WasmPlugin`], configured from the
+    /// `wasm` object of `config_json`; the rest of the config is passed to the guest.
+    pub fn init(&self, config_json: &str, api: &CoreHostApi) -> Result<*mut std::ffi::c_void, PluginError> {
+        match self {
+            LoadedPlugin::Native(p) => p.init(config_json, api),
+            LoadedPlugin::Wasm(m) => {
+                let (config, guest_config) = WasmPluginConfig::from_plugin_config(m.name(), config_json);
+                let plugin = m.instantiate(config, &guest_config, api)?;
+                Ok(Box::into_raw(Box::new(plugin)) as *mut std::ffi::c_void)
+            }
+        }
+    }
+
+    pub fn process(&self, plugin_ctx: *mut std::ffi::c_void, task_ctx: *mut std::ffi::c_void) -> FlowControl {
+        match self {
+            LoadedPlugin::Native(p) => p.process(plugin_ctx, task_ctx),
+            LoadedPlugin::Wasm(_) => unsafe { &*(plugin_ctx as *const WasmPlugin) }.process(task_ctx),
+        }
+    }
+
+    pub fn error(&self, plugin_ctx: *mut std::ffi::c_void, task_ctx: *mut std::ffi::c_void) {
+        match self {
+            LoadedPlugin::Native(p) => p.error(plugin_ctx, task_ctx),
+            LoadedPlugin::Wasm(_) => unsafe { &*(plugin_ctx as *const WasmPlugin) }.error(task_ctx),
+        }
+    }
+
+    pub fn destroy(&self, plugin_ctx: *mut std::ffi::c_void) {
+        match self {
+            LoadedPlugin::Native(p) => p.destroy(plugin_ctx),
+            LoadedPlugin::Wasm(_) => drop(unsafe { Box::from_raw(plugin_ctx as *mut WasmPlugin) }),
+        }
+    }
+}
+
+pub struct NativePlugin {
     _lib: Library,
     init_fn: OxPluginInitFn,
     #[allow(dead_code)]
@@ -60,7 +123,7 @@ pub struct LoadedPlugin {
     capabilities: Option<PluginCapabilities>,
 }
 
-impl LoadedPlugin {
+impl NativePlugin {
     pub unsafe fn new(path: &str) -> Result<Self, PluginError> {
         let lib = Library::new(path)?;
 
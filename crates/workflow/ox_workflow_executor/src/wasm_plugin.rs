@@ -1,5 +1,24 @@
-use ox_workflow_abi::{FlowControl, FLOW_CONTROL_CONTINUE, FLOW_CONTROL_ERROR};
+use ox_workflow_abi::{CoreHostApi, FlowControl, FLOW_CONTROL_ERROR};
+use ox_workflow_core::Task;
+use parking_lot::Mutex;
+use std::ffi::{c_void, CStr, CString};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
 use thiserror::Error;
+use wasmtime::{
+    Caller, Config, Engine, Extern, Linker, Memory, Module, Store, StoreLimits,
+    StoreLimitsBuilder, Trap, TypedFunc,
+};
+
+/// Size of a WASM linear memory page.
+const WASM_PAGE_SIZE: u64 = 65536;
+
+/// Import module name under which the host API is exposed to guests.
+const HOST_MODULE: &str = "ox";
+
+/// Key of the plugin config object holding sandbox limits. It is stripped before the
+/// config is passed to the guest's `ox_plugin_init`.
+pub const WASM_CONFIG_KEY: &str = "wasm";
 
 #[derive(Error, Debug)]
 pub enum WasmPluginError {
@@ -11,14 +30,31 @@ pub enum WasmPluginError {
     MemoryLimitExceeded,
     #[error("Init failed")]
     InitFailed,
+    #[error("Fuel exhausted")]
+    FuelExhausted,
     #[error("Plugin trap: {0}")]
     Trap(String),
 }
 
+impl From<wasmtime::Error> for WasmPluginError {
+    fn from(e: wasmtime::Error) -> Self {
+        match e.downcast_ref::<Trap>() {
+            Some(Trap::OutOfFuel) => WasmPluginError::FuelExhausted,
+            _ => WasmPluginError::Trap(format!("{:#}", e)),
+        }
+    }
+}
+
+/// Sandbox limits for one plugin instance, read from the `wasm` object of its config.
+#[derive(Debug, Clone)]
 pub struct WasmPluginConfig {
     pub name: String,
+    /// Upper bound on linear memory, in 64 KiB pages.
     pub memory_limit_pages: u32,
+    /// Host API calls allowed per `ox_plugin_process` invocation (i.e. per task).
     pub max_calls_per_task: u64,
+    /// Fuel granted to every guest call. Roughly one unit per executed instruction.
+    pub fuel_per_call: u64,
 }
 
 impl Default for WasmPluginConfig {
@@ -27,100 +63,291 @@ impl Default for WasmPluginConfig {
             name: String::new(),
             memory_limit_pages: 64,
             max_calls_per_task: 1000,
+            fuel_per_call: 10_000_000,
         }
     }
 }
 
-pub struct WasmPluginInstance {
-    config: WasmPluginConfig,
-    #[allow(dead_code)]
-    compiled: bool,
+impl WasmPluginConfig {
+    /// Splits a stage plugin config into the sandbox limits and the JSON handed to the guest.
+    pub fn from_plugin_config(name: &str, config_json: &str) -> (Self, String) {
+        let mut config = Self { name: name.to_string(), ..Self::default() };
+        let mut value: serde_json::Value = match serde_json::from_str(config_json) {
+            Ok(v) => v,
+            Err(_) => return (config, config_json.to_string()),
+        };
+        if let Some(limits) = value.as_object_mut().and_then(|o| o.remove(WASM_CONFIG_KEY)) {
+            if let Some(v) = limits.get("memory_limit_pages").and_then(|v| v.as_u64()) {
+                config.memory_limit_pages = v.min(u32::MAX as u64) as u32;
+            }
+            if let Some(v) = limits.get("max_calls_per_task").and_then(|v| v.as_u64()) {
+                config.max_calls_per_task = v;
+            }
+            if let Some(v) = limits.get("fuel_per_call").and_then(|v| v.as_u64()) {
+                config.fuel_per_call = v;
+            }
+        }
+        (config, value.to_string())
+    }
+}
+
+fn engine() -> &'static Engine {
+    static ENGINE: OnceLock<Engine> = OnceLock::new();
+    ENGINE.get_or_init(|| {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        Engine::new(&config).expect("WASM engine configuration is valid")
+    })
 }
 
-impl WasmPluginInstance {
-    pub fn from_wasm_bytes(
-        wasm_bytes: &[u8],
+/// A compiled WASM plugin. Cheap to clone; every stage using it gets its own [`WasmPlugin`].
+#[derive(Clone)]
+pub struct WasmModule {
+    name: String,
+    module: Module,
+}
+
+impl WasmModule {
+    pub fn from_wasm_bytes(name: &str, wasm_bytes: &[u8]) -> Result<Self, WasmPluginError> {
+        let module = Module::new(engine(), wasm_bytes)
+            .map_err(|e| WasmPluginError::CompileError(format!("{:#}", e)))?;
+        Ok(Self { name: name.to_string(), module })
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, WasmPluginError> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| WasmPluginError::CompileError(format!("{}: {}", path, e)))?;
+        let name = std::path::Path::new(path)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Self::from_wasm_bytes(&name, &bytes)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Creates a sandboxed plugin and runs the guest's `ox_plugin_init` once to validate it.
+    pub fn instantiate(
+        &self,
         config: WasmPluginConfig,
-    ) -> Result<Self, WasmPluginError> {
-        let _ = wasm_bytes;
-        Ok(Self {
+        guest_config: &str,
+        api: &CoreHostApi,
+    ) -> Result<WasmPlugin, WasmPluginError> {
+        for export in self.module.exports() {
+            if let Some(memory) = export.ty().memory() {
+                if memory.minimum() > config.memory_limit_pages as u64 {
+                    return Err(WasmPluginError::MemoryLimitExceeded);
+                }
+            }
+        }
+        let plugin = WasmPlugin {
+            linker: host_linker()?,
+            module: self.module.clone(),
             config,
-            compiled: false,
-        })
+            guest_config: guest_config.to_string(),
+            api: *api,
+            idle: Mutex::new(Vec::new()),
+            calls: AtomicU64::new(0),
+        };
+        let slot = plugin.new_slot()?;
+        plugin.idle.lock().push(slot);
+        Ok(plugin)
+    }
+}
+
+/// Per-store host state seen by the imported functions.
+struct HostState {
+    api: CoreHostApi,
+    /// Task being processed, or 0 outside `ox_plugin_process` / `ox_plugin_error`.
+    task_ctx: usize,
+    host_calls: u64,
+    max_host_calls: u64,
+    payload: Option<CString>,
+    limits: StoreLimits,
+}
+
+impl HostState {
+    fn task(&self) -> Option<*mut c_void> {
+        (self.task_ctx != 0).then_some(self.task_ctx as *mut c_void)
+    }
+
+    fn begin(&mut self, task_ctx: *mut c_void, max_host_calls: u64) {
+        self.task_ctx = task_ctx as usize;
+        self.host_calls = 0;
+        self.max_host_calls = max_host_calls;
+        self.payload = None;
     }
 
-    pub fn instantiate(self) -> Result<WasmPlugin, WasmPluginError> {
-        let memory_limit = (self.config.memory_limit_pages as u64) * 65536;
-        Ok(WasmPlugin {
-            config: self.config,
-            ctx: None,
-            call_count: 0,
-            memory_allocated: 0,
-            memory_limit,
-        })
+    fn finish(&mut self) -> Option<CString> {
+        self.task_ctx = 0;
+        self.payload.take()
     }
 }
 
+/// One guest instance with its initialized plugin context.
+struct Slot {
+    store: Store<HostState>,
+    memory: Memory,
+    ctx: u32,
+    process: TypedFunc<u32, u32>,
+    error: Option<TypedFunc<u32, ()>>,
+    destroy: Option<TypedFunc<u32, ()>>,
+}
+
+/// An initialized WASM plugin for one stage.
+///
+/// WASM instances are single-threaded, so concurrent tasks each check out their own instance
+/// from a pool. Every instance runs `ox_plugin_init` with the same config, which keeps the
+/// stateless-context contract of native plugins. An instance that traps is discarded.
 pub struct WasmPlugin {
+    linker: Linker<HostState>,
+    module: Module,
     config: WasmPluginConfig,
-    ctx: Option<i32>,
-    call_count: u64,
-    memory_allocated: u64,
-    memory_limit: u64,
+    guest_config: String,
+    api: CoreHostApi,
+    idle: Mutex<Vec<Slot>>,
+    calls: AtomicU64,
 }
 
 impl WasmPlugin {
-    fn alloc(&mut self, size: u32) -> Result<u32, WasmPluginError> {
-        let size_u64 = size as u64;
-        if self.memory_allocated + size_u64 > self.memory_limit {
-            return Err(WasmPluginError::MemoryLimitExceeded);
-        }
-        let offset = self.memory_allocated;
-        self.memory_allocated += size_u64;
-        Ok(offset as u32)
-    }
-
-    pub fn init(&mut self, config_json: &str) -> Result<i32, WasmPluginError> {
-        let offset = self.alloc(config_json.len() as u32 + 1)?;
-
-        self.ctx = Some(offset as i32);
-        self.call_count = 0;
+    fn new_slot(&self) -> Result<Slot, WasmPluginError> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size((self.config.memory_limit_pages as u64 * WASM_PAGE_SIZE) as usize)
+            .instances(1)
+            .build();
+        let mut store = Store::new(
+            engine(),
+            HostState {
+                api: self.api,
+                task_ctx: 0,
+                host_calls: 0,
+                max_host_calls: self.config.max_calls_per_task,
+                payload: None,
+                limits,
+            },
+        );
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(self.config.fuel_per_call)?;
 
-        Ok(offset as i32)
-    }
+        let instance = self
+            .linker
+            .instantiate(&mut store, &self.module)
+            .map_err(|e| WasmPluginError::InstantiationError(format!("{:#}", e)))?;
+        let missing = |what: &str| WasmPluginError::InstantiationError(format!("missing export '{}'", what));
+        let memory = instance.get_memory(&mut store, "memory").ok_or_else(|| missing("memory"))?;
+        let alloc = instance
+            .get_typed_func::<u32, u32>(&mut store, "alloc")
+            .map_err(|_| missing("alloc"))?;
+        let init = instance
+            .get_typed_func::<(u32, u32), u32>(&mut store, "ox_plugin_init")
+            .map_err(|_| missing("ox_plugin_init"))?;
+        let process = instance
+            .get_typed_func::<u32, u32>(&mut store, "ox_plugin_process")
+            .map_err(|_| missing("ox_plugin_process"))?;
+        let error = instance.get_typed_func::<u32, ()>(&mut store, "ox_plugin_error").ok();
+        let destroy = instance.get_typed_func::<u32, ()>(&mut store, "ox_plugin_destroy").ok();
 
-    pub fn process(&mut self, _task_ctx: i32) -> Result<FlowControl, WasmPluginError> {
-        if self.ctx.is_none() {
+        let config = self.guest_config.as_bytes();
+        let ptr = alloc.call(&mut store, config.len() as u32)?;
+        memory
+            .write(&mut store, ptr as usize, config)
+            .map_err(|e| WasmPluginError::Trap(e.to_string()))?;
+        let ctx = init.call(&mut store, (ptr, config.len() as u32))?;
+        if ctx == 0 {
             return Err(WasmPluginError::InitFailed);
         }
+        Ok(Slot { store, memory, ctx, process, error, destroy })
+    }
 
-        if self.call_count >= self.config.max_calls_per_task {
-            return Ok(FlowControl {
-                code: FLOW_CONTROL_ERROR,
-                payload: std::ptr::null(),
-            });
+    fn checkout(&self) -> Result<Slot, WasmPluginError> {
+        let pooled = self.idle.lock().pop();
+        match pooled {
+            Some(slot) => Ok(slot),
+            None => self.new_slot(),
         }
+    }
 
-        self.call_count += 1;
-
-        Ok(FlowControl {
-            code: FLOW_CONTROL_CONTINUE,
-            payload: std::ptr::null(),
-        })
+    /// Runs `ox_plugin_process` against the task. Traps, fuel exhaustion and host call limit
+    /// violations are logged and reported as `FLOW_CONTROL_ERROR`.
+    pub fn process(&self, task_ctx: *mut c_void) -> FlowControl {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        let error = FlowControl { code: FLOW_CONTROL_ERROR, payload: std::ptr::null() };
+        let mut slot = match self.checkout() {
+            Ok(slot) => slot,
+            Err(e) => {
+                log::error!("WASM plugin '{}' could not be instantiated: {}", self.config.name, e);
+                return error;
+            }
+        };
+        let result = self.call_guest(&mut slot, task_ctx, |slot| {
+            let (process, ctx) = (slot.process.clone(), slot.ctx);
+            process.call(&mut slot.store, ctx)
+        });
+        let payload = slot.store.data_mut().finish();
+        match result {
+            Ok(code) => {
+                self.idle.lock().push(slot);
+                FlowControl {
+                    code: u8::try_from(code).unwrap_or(FLOW_CONTROL_ERROR),
+                    payload: payload.map_or(std::ptr::null(), |p| into_task_arena(task_ctx, p)),
+                }
+            }
+            Err(e) => {
+                log::error!("WASM plugin '{}' failed: {}", self.config.name, e);
+                error
+            }
+        }
     }
 
-    pub fn error(&self, _task_ctx: i32) {}
+    /// Runs the guest's optional `ox_plugin_error` callback.
+    pub fn error(&self, task_ctx: *mut c_void) {
+        let Ok(mut slot) = self.checkout() else { return };
+        let Some(error) = slot.error.clone() else {
+            self.idle.lock().push(slot);
+            return;
+        };
+        let result = self.call_guest(&mut slot, task_ctx, |slot| error.call(&mut slot.store, slot.ctx));
+        slot.store.data_mut().finish();
+        match result {
+            Ok(()) => self.idle.lock().push(slot),
+            Err(e) => log::error!("WASM plugin '{}' error callback failed: {}", self.config.name, e),
+        }
+    }
 
-    pub fn destroy(&mut self) {
-        self.ctx = None;
+    fn call_guest<T>(
+        &self,
+        slot: &mut Slot,
+        task_ctx: *mut c_void,
+        call: impl FnOnce(&mut Slot) -> wasmtime::Result<T>,
+    ) -> Result<T, WasmPluginError> {
+        slot.store.data_mut().begin(task_ctx, self.config.max_calls_per_task);
+        slot.store.set_fuel(self.config.fuel_per_call)?;
+        Ok(call(slot)?)
     }
 
     pub fn stats(&self) -> WasmPluginStats {
+        let idle = self.idle.lock();
         WasmPluginStats {
             name: self.config.name.clone(),
-            calls: self.call_count,
-            memory_used: self.memory_allocated,
-            memory_limit: self.memory_limit,
+            calls: self.calls.load(Ordering::Relaxed),
+            instances: idle.len(),
+            memory_used: idle.iter().map(|s| s.memory.data_size(&s.store) as u64).sum(),
+            memory_limit: self.config.memory_limit_pages as u64 * WASM_PAGE_SIZE,
+        }
+    }
+}
+
+impl Drop for WasmPlugin {
+    fn drop(&mut self) {
+        for mut slot in self.idle.get_mut().drain(..) {
+            if let Some(destroy) = slot.destroy.clone() {
+                let _ = slot.store.set_fuel(self.config.fuel_per_call);
+                if let Err(e) = destroy.call(&mut slot.store, slot.ctx) {
+                    log::warn!("WASM plugin '{}' destroy failed: {:#}", self.config.name, e);
+                }
+            }
         }
     }
 }
@@ -128,7 +355,381 @@ impl WasmPlugin {
 #[derive(Debug, Clone)]
 pub struct WasmPluginStats {
     pub name: String,
+    /// Number of `ox_plugin_process` invocations.
     pub calls: u64,
+    /// Instances currently idle in the pool.
+    pub instances: usize,
+    /// Linear memory held by the idle instances, in bytes.
     pub memory_used: u64,
+    /// Per-instance memory limit, in bytes.
     pub memory_limit: u64,
-}
\ No newline at end of file
+}
+
+/// Hands a flow control payload to the task so it outlives the guest call,
+/// the same way `get_field` results are kept alive.
+fn into_task_arena(task_ctx: *mut c_void, payload: CString) -> *const std::ffi::c_char {
+    let ptr = payload.into_raw();
+    if task_ctx.is_null() {
+        // No task to own it; only reachable when called outside a flow.
+        drop(unsafe { CString::from_raw(ptr) });
+        return std::ptr::null();
+    }
+    let task = unsafe { &mut *(task_ctx as *mut Task) };
+    task.ffi_arena.push(ptr);
+    ptr
+}
+
+fn memory_of(caller: &mut Caller<'_, HostState>) -> wasmtime::Result<Memory> {
+    match caller.get_export("memory") {
+        Some(Extern::Memory(m)) => Ok(m),
+        _ => Err(wasmtime::Error::msg("guest does not export 'memory'")),
+    }
+}
+
+/// Counts a host API call against the per-task budget.
+fn charge(caller: &mut Caller<'_, HostState>) -> wasmtime::Result<()> {
+    let state = caller.data_mut();
+    state.host_calls += 1;
+    if state.host_calls > state.max_host_calls {
+        return Err(wasmtime::Error::msg(format!(
+            "host API call limit of {} exceeded",
+            state.max_host_calls
+        )));
+    }
+    Ok(())
+}
+
+/// Copies `len` bytes at `ptr` out of guest memory. The range is checked against the
+/// guest's memory before anything is allocated, so `len` cannot exceed the memory limit.
+fn read_bytes(caller: &mut Caller<'_, HostState>, ptr: u32, len: u32) -> wasmtime::Result<Vec<u8>> {
+    let memory = memory_of(caller)?;
+    let range = (ptr as usize)..(ptr as usize).saturating_add(len as usize);
+    match memory.data(&*caller).get(range) {
+        Some(bytes) => Ok(bytes.to_vec()),
+        None => Err(wasmtime::Error::msg(format!(
+            "guest buffer {}+{} is outside its memory",
+            ptr, len
+        ))),
+    }
+}
+
+fn read_cstring(caller: &mut Caller<'_, HostState>, ptr: u32, len: u32) -> wasmtime::Result<CString> {
+    Ok(CString::new(read_bytes(caller, ptr, len)?)?)
+}
+
+/// Copies `data` into a guest buffer obtained from its `alloc` export and returns
+/// `(ptr << 32) | len`. The guest releases it with `dealloc`.
+fn write_guest(caller: &mut Caller<'_, HostState>, data: &[u8]) -> wasmtime::Result<i64> {
+    let alloc = match caller.get_export("alloc") {
+        Some(Extern::Func(f)) => f.typed::<u32, u32>(&*caller)?,
+        _ => return Err(wasmtime::Error::msg("guest does not export 'alloc'")),
+    };
+    let ptr = alloc.call(&mut *caller, data.len() as u32)?;
+    memory_of(caller)?.write(&mut *caller, ptr as usize, data)?;
+    Ok((((ptr as u64) << 32) | data.len() as u64) as i64)
+}
+
+fn host_linker() -> Result<Linker<HostState>, WasmPluginError> {
+    let mut linker = Linker::new(engine());
+    link_host_api(&mut linker).map_err(|e| WasmPluginError::InstantiationError(format!("{:#}", e)))?;
+    Ok(linker)
+}
+
+/// Exposes the field, flag and log functions of `CoreHostApi` as `ox.*` imports.
+/// Strings are passed as `(ptr, len)` pairs in guest memory. Getters return a packed
+/// `(ptr << 32) | len` buffer allocated with the guest's `alloc`, or -1 if absent.
+fn link_host_api(linker: &mut Linker<HostState>) -> wasmtime::Result<()> {
+    linker.func_wrap(HOST_MODULE, "get_field", |mut caller: Caller<'_, HostState>, kp: u32, kl: u32| -> wasmtime::Result<i64> {
+        charge(&mut caller)?;
+        let key = read_cstring(&mut caller, kp, kl)?;
+        let (api, Some(task)) = (caller.data().api, caller.data().task()) else { return Ok(-1) };
+        let value = (api.get_field)(task, key.as_ptr());
+        if value.is_null() {
+            return Ok(-1);
+        }
+        let bytes = unsafe { CStr::from_ptr(value) }.to_bytes().to_vec();
+        write_guest(&mut caller, &bytes)
+    })?;
+    linker.func_wrap(HOST_MODULE, "set_field", |mut caller: Caller<'_, HostState>, kp: u32, kl: u32, vp: u32, vl: u32| -> wasmtime::Result<()> {
+        charge(&mut caller)?;
+        let key = read_cstring(&mut caller, kp, kl)?;
+        let value = read_cstring(&mut caller, vp, vl)?;
+        if let (api, Some(task)) = (caller.data().api, caller.data().task()) {
+            (api.set_field)(task, key.as_ptr(), value.as_ptr());
+        }
+        Ok(())
+    })?;
+    linker.func_wrap(HOST_MODULE, "get_field_bytes", |mut caller: Caller<'_, HostState>, kp: u32, kl: u32| -> wasmtime::Result<i64> {
+        charge(&mut caller)?;
+        let key = read_cstring(&mut caller, kp, kl)?;
+        let (api, Some(task)) = (caller.data().api, caller.data().task()) else { return Ok(-1) };
+        let mut len = 0usize;
+        let value = (api.get_field_bytes)(task, key.as_ptr(), &mut len);
+        if value.is_null() {
+            return Ok(-1);
+        }
+        let bytes = unsafe { std::slice::from_raw_parts(value, len) }.to_vec();
+        write_guest(&mut caller, &bytes)
+    })?;
+    linker.func_wrap(HOST_MODULE, "set_field_bytes", |mut caller: Caller<'_, HostState>, kp: u32, kl: u32, vp: u32, vl: u32| -> wasmtime::Result<()> {
+        charge(&mut caller)?;
+        let key = read_cstring(&mut caller, kp, kl)?;
+        let value = read_bytes(&mut caller, vp, vl)?;
+        if let (api, Some(task)) = (caller.data().api, caller.data().task()) {
+            (api.set_field_bytes)(task, key.as_ptr(), value.as_ptr(), value.len());
+        }
+        Ok(())
+    })?;
+    linker.func_wrap(HOST_MODULE, "has_field", |mut caller: Caller<'_, HostState>, kp: u32, kl: u32| -> wasmtime::Result<u32> {
+        charge(&mut caller)?;
+        let key = read_cstring(&mut caller, kp, kl)?;
+        let (api, Some(task)) = (caller.data().api, caller.data().task()) else { return Ok(0) };
+        Ok((api.has_field)(task, key.as_ptr()) as u32)
+    })?;
+    linker.func_wrap(HOST_MODULE, "unset_field", |mut caller: Caller<'_, HostState>, kp: u32, kl: u32| -> wasmtime::Result<u32> {
+        charge(&mut caller)?;
+        let key = read_cstring(&mut caller, kp, kl)?;
+        let (api, Some(task)) = (caller.data().api, caller.data().task()) else { return Ok(0) };
+        Ok((api.unset_field)(task, key.as_ptr()) as u32)
+    })?;
+    linker.func_wrap(HOST_MODULE, "get_metadata", |mut caller: Caller<'_, HostState>, kp: u32, kl: u32| -> wasmtime::Result<i64> {
+        charge(&mut caller)?;
+        let key = read_cstring(&mut caller, kp, kl)?;
+        let (api, Some(task)) = (caller.data().api, caller.data().task()) else { return Ok(-1) };
+        let value = (api.get_metadata)(task, key.as_ptr());
+        if value.is_null() {
+            return Ok(-1);
+        }
+        let bytes = unsafe { CStr::from_ptr(value) }.to_bytes().to_vec();
+        write_guest(&mut caller, &bytes)
+    })?;
+    linker.func_wrap(HOST_MODULE, "set_flag", |mut caller: Caller<'_, HostState>, fp: u32, fl: u32, scope: u32| -> wasmtime::Result<()> {
+        charge(&mut caller)?;
+        let flag = read_cstring(&mut caller, fp, fl)?;
+        if let (api, Some(task)) = (caller.data().api, caller.data().task()) {
+            (api.set_flag)(task, flag.as_ptr(), scope as u8);
+        }
+        Ok(())
+    })?;
+    linker.func_wrap(HOST_MODULE, "has_flag", |mut caller: Caller<'_, HostState>, fp: u32, fl: u32, scope: u32| -> wasmtime::Result<u32> {
+        charge(&mut caller)?;
+        let flag = read_cstring(&mut caller, fp, fl)?;
+        let (api, Some(task)) = (caller.data().api, caller.data().task()) else { return Ok(0) };
+        Ok((api.has_flag)(task, flag.as_ptr(), scope as u8) as u32)
+    })?;
+    linker.func_wrap(HOST_MODULE, "clear_flag", |mut caller: Caller<'_, HostState>, fp: u32, fl: u32, scope: u32| -> wasmtime::Result<()> {
+        charge(&mut caller)?;
+        let flag = read_cstring(&mut caller, fp, fl)?;
+        if let (api, Some(task)) = (caller.data().api, caller.data().task()) {
+            (api.clear_flag)(task, flag.as_ptr(), scope as u8);
+        }
+        Ok(())
+    })?;
+    linker.func_wrap(HOST_MODULE, "log", |mut caller: Caller<'_, HostState>, level: u32, mp: u32, ml: u32| -> wasmtime::Result<()> {
+        charge(&mut caller)?;
+        let message = read_cstring(&mut caller, mp, ml)?;
+        // log accepts a null task_ctx (e.g. during ox_plugin_init).
+        let task = caller.data().task().unwrap_or(std::ptr::null_mut());
+        (caller.data().api.log)(task, level as u8, message.as_ptr());
+        Ok(())
+    })?;
+    linker.func_wrap(HOST_MODULE, "set_payload", |mut caller: Caller<'_, HostState>, pp: u32, pl: u32| -> wasmtime::Result<()> {
+        charge(&mut caller)?;
+        let payload = read_cstring(&mut caller, pp, pl)?;
+        caller.data_mut().payload = Some(payload);
+        Ok(())
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_host_api;
+    use ox_workflow_abi::{FLOW_CONTROL_CONTINUE, FLOW_CONTROL_JUMP};
+    use ox_workflow_core::state::FieldValue;
+
+    /// Bump allocator, config captured at init, and a process function that copies
+    /// field `in` to `out` and the guest config to `config`.
+    const ECHO: &str = r#"
+        (module
+          (import "ox" "get_field" (func $get_field (param i32 i32) (result i64)))
+          (import "ox" "set_field" (func $set_field (param i32 i32 i32 i32)))
+          (import "ox" "set_payload" (func $set_payload (param i32 i32)))
+          (memory (export "memory") 1)
+          (global $heap (mut i32) (i32.const 1024))
+          (global $cfg_ptr (mut i32) (i32.const 0))
+          (global $cfg_len (mut i32) (i32.const 0))
+          (data (i32.const 0) "in")
+          (data (i32.const 16) "out")
+          (data (i32.const 32) "config")
+          (data (i32.const 48) "Next")
+          (func $alloc (export "alloc") (param $n i32) (result i32)
+            (local $p i32)
+            (local.set $p (global.get $heap))
+            (global.set $heap (i32.add (global.get $heap) (local.get $n)))
+            (local.get $p))
+          (func (export "dealloc") (param i32))
+          (func (export "ox_plugin_init") (param $p i32) (param $n i32) (result i32)
+            (global.set $cfg_ptr (local.get $p))
+            (global.set $cfg_len (local.get $n))
+            (i32.const 1))
+          (func (export "ox_plugin_process") (param $ctx i32) (result i32)
+            (local $v i64)
+            (call $set_field (i32.const 32) (i32.const 6) (global.get $cfg_ptr) (global.get $cfg_len))
+            (local.set $v (call $get_field (i32.const 0) (i32.const 2)))
+            (if (i64.lt_s (local.get $v) (i64.const 0))
+              (then
+                (call $set_payload (i32.const 48) (i32.const 4))
+                (return (i32.const 3))))
+            (call $set_field (i32.const 16) (i32.const 3)
+              (i32.wrap_i64 (i64.shr_u (local.get $v) (i64.const 32)))
+              (i32.wrap_i64 (local.get $v)))
+            (i32.const 0)))
+    "#;
+
+    fn module(wat: &str) -> WasmModule {
+        WasmModule::from_wasm_bytes("test", &wat::parse_str(wat).unwrap()).unwrap()
+    }
+
+    fn field(task: &Task, key: &str) -> Option<String> {
+        match task.state.read().fields.get(key) {
+            Some(FieldValue::String(s)) => Some(s.clone()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_process_uses_host_fields() {
+        let api = create_host_api();
+        let (config, guest) = WasmPluginConfig::from_plugin_config(
+            "echo",
+            r#"{"greeting":"hi","wasm":{"fuel_per_call":50000}}"#,
+        );
+        assert_eq!(config.fuel_per_call, 50000);
+        let plugin = module(ECHO).instantiate(config, &guest, &api).unwrap();
+
+        let mut task = Task::new(1);
+        task.state.write().fields.insert("in".to_string(), FieldValue::String("hello".to_string()));
+        let fc = plugin.process(&mut task as *mut Task as *mut c_void);
+        assert_eq!(fc.code, FLOW_CONTROL_CONTINUE);
+        assert_eq!(field(&task, "out").as_deref(), Some("hello"));
+        assert_eq!(field(&task, "config").as_deref(), Some(r#"{"greeting":"hi"}"#));
+
+        // A missing field makes the guest JUMP with a payload owned by the task.
+        let mut task = Task::new(2);
+        let fc = plugin.process(&mut task as *mut Task as *mut c_void);
+        assert_eq!(fc.code, FLOW_CONTROL_JUMP);
+        assert_eq!(unsafe { CStr::from_ptr(fc.payload) }.to_str().unwrap(), "Next");
+
+        let stats = plugin.stats();
+        assert_eq!(stats.calls, 2);
+        assert_eq!(stats.instances, 1);
+    }
+
+    #[test]
+    fn test_fuel_limit_stops_runaway_guest() {
+        let wat = r#"
+            (module
+              (memory (export "memory") 1)
+              (func (export "alloc") (param i32) (result i32) (i32.const 0))
+              (func (export "ox_plugin_init") (param i32 i32) (result i32) (i32.const 1))
+              (func (export "ox_plugin_process") (param i32) (result i32)
+                (loop $spin (br $spin))
+                (i32.const 0)))
+        "#;
+        let api = create_host_api();
+        let config = WasmPluginConfig { fuel_per_call: 10_000, ..Default::default() };
+        let plugin = module(wat).instantiate(config, "{}", &api).unwrap();
+        let mut task = Task::new(1);
+        let fc = plugin.process(&mut task as *mut Task as *mut c_void);
+        assert_eq!(fc.code, FLOW_CONTROL_ERROR);
+        // The trapped instance is not reused.
+        assert_eq!(plugin.stats().instances, 0);
+    }
+
+    #[test]
+    fn test_memory_limit_is_enforced() {
+        let grows = r#"
+            (module
+              (memory (export "memory") 1)
+              (func (export "alloc") (param i32) (result i32) (i32.const 0))
+              (func (export "ox_plugin_init") (param i32 i32) (result i32) (i32.const 1))
+              (func (export "ox_plugin_process") (param i32) (result i32)
+                (if (i32.lt_s (memory.grow (i32.const 4)) (i32.const 0))
+                  (then (return (i32.const 2))))
+                (i32.const 0)))
+        "#;
+        let api = create_host_api();
+        let config = WasmPluginConfig { memory_limit_pages: 2, ..Default::default() };
+        let plugin = module(grows).instantiate(config.clone(), "{}", &api).unwrap();
+        let mut task = Task::new(1);
+        assert_eq!(plugin.process(&mut task as *mut Task as *mut c_void).code, FLOW_CONTROL_ERROR);
+
+        let large = r#"
+            (module
+              (memory (export "memory") 4)
+              (func (export "alloc") (param i32) (result i32) (i32.const 0))
+              (func (export "ox_plugin_init") (param i32 i32) (result i32) (i32.const 1))
+              (func (export "ox_plugin_process") (param i32) (result i32) (i32.const 0)))
+        "#;
+        assert!(matches!(
+            module(large).instantiate(config, "{}", &api),
+            Err(WasmPluginError::MemoryLimitExceeded)
+        ));
+    }
+
+    #[test]
+    fn test_oversized_guest_buffer_traps() {
+        // len = u32::MAX must fail the bounds check, not allocate 4 GiB on the host.
+        let wat = r#"
+            (module
+              (import "ox" "set_field" (func $set_field (param i32 i32 i32 i32)))
+              (memory (export "memory") 1)
+              (data (i32.const 0) "out")
+              (func (export "alloc") (param i32) (result i32) (i32.const 0))
+              (func (export "ox_plugin_init") (param i32 i32) (result i32) (i32.const 1))
+              (func (export "ox_plugin_process") (param i32) (result i32)
+                (call $set_field (i32.const 0) (i32.const 3) (i32.const 16) (i32.const -1))
+                (i32.const 0)))
+        "#;
+        let api = create_host_api();
+        let plugin = module(wat).instantiate(WasmPluginConfig::default(), "{}", &api).unwrap();
+        let mut task = Task::new(1);
+        assert_eq!(plugin.process(&mut task as *mut Task as *mut c_void).code, FLOW_CONTROL_ERROR);
+        assert_eq!(field(&task, "out"), None);
+    }
+
+    #[test]
+    fn test_host_call_limit_per_task() {
+        let api = create_host_api();
+        let config = WasmPluginConfig { max_calls_per_task: 1, ..Default::default() };
+        let plugin = module(ECHO).instantiate(config, "{}", &api).unwrap();
+        let mut task = Task::new(1);
+        task.state.write().fields.insert("in".to_string(), FieldValue::String("x".to_string()));
+        // ECHO makes three host calls per task.
+        assert_eq!(plugin.process(&mut task as *mut Task as *mut c_void).code, FLOW_CONTROL_ERROR);
+
+        // The JUMP path's third call is set_payload, which counts too.
+        let config = WasmPluginConfig { max_calls_per_task: 2, ..Default::default() };
+        let plugin = module(ECHO).instantiate(config, "{}", &api).unwrap();
+        let mut task = Task::new(2);
+        assert_eq!(plugin.process(&mut task as *mut Task as *mut c_void).code, FLOW_CONTROL_ERROR);
+    }
+
+    #[test]
+    fn test_init_failure_is_reported() {
+        let wat = r#"
+            (module
+              (memory (export "memory") 1)
+              (func (export "alloc") (param i32) (result i32) (i32.const 0))
+              (func (export "ox_plugin_init") (param i32 i32) (result i32) (i32.const 0))
+              (func (export "ox_plugin_process") (param i32) (result i32) (i32.const 0)))
+        "#;
+        let api = create_host_api();
+        assert!(matches!(
+            module(wat).instantiate(WasmPluginConfig::default(), "{}", &api),
+            Err(WasmPluginError::InitFailed)
+        ));
+    }
+}
@@ -1,15 +1,33 @@
+pub mod queue;
+pub mod watchdog;
+
 use ox_event_bus::EventBus;
-use ox_workflow_config::EngineConfig;
-use ox_workflow_core::TaskStatus;
+use ox_workflow_config::{EngineConfig, QueueConfig, QueueDiscipline};
+use ox_workflow_core::{Checkpoint, TaskStatus};
 use ox_workflow_storage::WorkflowStorage;
 use ox_workflow_executor::FlowManager;
 use ox_workflow_abi::CoreHostApi;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::time::{interval, Duration};
-use futures::stream::StreamExt;
 use tokio::sync::Semaphore;
 use std::ffi::{c_char, c_void};
 use ox_workflow_core::Task;
+use watchdog::Watchdog;
+
+/// Task metadata key recording the queue a task was last dispatched from; retries go back to it.
+pub const QUEUE_METADATA_KEY: &str = "queue";
+/// Task metadata key naming the dead-letter queue a task was parked on.
+pub const DEAD_LETTER_METADATA_KEY: &str = "dead_letter_queue";
+/// Queue used when a task has no recorded queue.
+pub const DEFAULT_QUEUE: &str = "tasks.pending";
+
+/// How a run ended when the scheduler had to abandon it.
+enum Abandoned {
+    Timeout,
+    Panicked(String),
+}
 
 pub struct WorkflowScheduler {
     pub config: EngineConfig,
@@ -17,7 +35,9 @@ pub struct WorkflowScheduler {
     pub event_bus: Arc<dyn EventBus>,
     pub flow_manager: Arc<FlowManager>,
     pub api_ptr: *const CoreHostApi,
-    pub queues: Vec<String>,
+    pub queues: Vec<QueueConfig>,
+    /// Tasks executing in this process.
+    running: Mutex<HashSet<uuid::Uuid>>,
 }
 
 unsafe impl Send for WorkflowScheduler {}
@@ -30,7 +50,7 @@ impl WorkflowScheduler {
         event_bus: Arc<dyn EventBus>, 
         flow_manager: Arc<FlowManager>, 
         api_ptr: *const CoreHostApi,
-        queues: Vec<String>,
+        queues: Vec<QueueConfig>,
     ) -> Self {
         Self {
             config,
@@ -39,6 +59,7 @@ impl WorkflowScheduler {
             flow_manager,
             api_ptr,
             queues,
+            running: Mutex::new(HashSet::new()),
         }
     }
 
@@ -51,49 +72,78 @@ impl WorkflowScheduler {
 
         let mut handles = Vec::new();
 
-        for queue_name in &self.queues {
-            let q_name = queue_name.clone();
+        for queue_config in &self.queues {
+            if !queue::is_dispatched(&queue_config.discipline) {
+                log::info!("Queue {} ({:?}) is not dispatched by the scheduler", queue_config.name, queue_config.discipline);
+                continue;
+            }
+            let q_config = queue_config.clone();
             let scheduler = self.clone();
             let sem_clone = semaphore.clone();
-            
+
             let handle = tokio::spawn(async move {
-                let mut stream = match scheduler.event_bus.subscribe_to_queue(&q_name).await {
+                let stream = match scheduler.event_bus.subscribe_to_queue(&q_config.name).await {
                     Ok(s) => s,
                     Err(e) => {
-                        log::error!("Failed to subscribe to queue {}: {:?}", q_name, e);
+                        log::error!("Failed to subscribe to queue {}: {:?}", q_config.name, e);
                         return;
                     }
                 };
-                    
-                log::info!("Listening on queue: {}", q_name);
-
-                while let Some(msg) = stream.next().await {
-                    // Backpressure: wait for a permit before processing
-                    let permit = sem_clone.clone().acquire_owned().await.unwrap();
-
-                    if let Ok(task_id_str) = String::from_utf8(msg.payload.clone()) {
-                        if let Ok(task_id) = uuid::Uuid::parse_str(&task_id_str) {
-                            let sched_clone = scheduler.clone();
-                            tokio::spawn(async move {
-                                sched_clone.spawn_task(task_id).await;
-                                drop(permit);
-                            });
-                        } else {
+
+                log::info!("Listening on queue: {} ({:?})", q_config.name, q_config.discipline);
+
+                let dispatcher = scheduler.clone();
+                let rejecter = scheduler.clone();
+                let queue_name = q_config.name.clone();
+                let rejected_queue = q_config.name.clone();
+                queue::drain_queue(
+                    q_config,
+                    stream,
+                    sem_clone,
+                    move |msg, permit| {
+                        let sched_clone = dispatcher.clone();
+                        let queue_name = queue_name.clone();
+                        tokio::spawn(async move {
+                            let handled = match task_id_of(&msg.payload) {
+                                Some(task_id) => sched_clone.spawn_task(task_id, &queue_name).await,
+                                None => {
+                                    log::warn!("Dropping message without a task ID from queue {}", queue_name);
+                                    true
+                                }
+                            };
+                            // Unacknowledged messages are redelivered by durable queues.
+                            if handled {
+                                if let Err(e) = msg.ack().await {
+                                    log::warn!("Failed to acknowledge message on {}: {}", queue_name, e);
+                                }
+                            }
                             drop(permit);
-                        }
-                    } else {
-                        drop(permit);
-                    }
-                }
+                        });
+                    },
+                    move |msg| {
+                        let storage = rejecter.storage.clone();
+                        let queue_name = rejected_queue.clone();
+                        tokio::spawn(async move {
+                            if let Some(task_id) = task_id_of(&msg.payload) {
+                                if let Ok(Some(task)) = storage.load_task(task_id).await {
+                                    let _ = storage.update_task_status(task_id, TaskStatus::Errored).await;
+                                    let _ = storage.append_history(task_id, &queue_name, None, "Rejected", Some("Queue is full"), task.attempt).await;
+                                }
+                            }
+                            let _ = msg.ack().await;
+                        });
+                    },
+                ).await;
             });
             handles.push(handle);
         }
 
         let mut ticker = interval(Duration::from_millis(self.config.tick_interval_ms));
+        let maintainer = self.clone();
         let handle = tokio::spawn(async move {
             loop {
                 ticker.tick().await;
-                // Periodic maintenance: retry dead letters, check timeouts
+                maintainer.publish_due_retries().await;
             }
         });
         handles.push(handle);
@@ -105,63 +155,234 @@ impl WorkflowScheduler {
         Ok(())
     }
 
-    async fn spawn_task(&self, task_id: uuid::Uuid) {
+    /// Runs one task to completion, suspension, failure or deadline. Returns false if the
+    /// queue message should stay pending: the task could not be loaded, or it is still
+    /// running here from an earlier delivery.
+    ///
+    /// The flow runs on a blocking thread watched by a [`Watchdog`]. When a deadline passes
+    /// the run is abandoned: the slot is released and the task is failed from its last saved
+    /// state, while the plugin call that hung finishes (or not) in the background and its
+    /// result is discarded.
+    async fn spawn_task(&self, task_id: uuid::Uuid, queue_name: &str) -> bool {
         let storage = self.storage.clone();
         let event_bus = self.event_bus.clone();
-        
-        if let Ok(Some(mut task)) = storage.load_task(task_id).await {
-            
-            // Flow name from task metadata or default
-            let flow_name = task.metadata.get("flow_name").cloned().unwrap_or_else(|| "default".to_string());
-            let flow_runner_opt = self.flow_manager.flows.get(&flow_name).cloned();
-            let api_ptr_addr = self.api_ptr as usize;
-
-            tokio::spawn(async move {
-                log::debug!("Executing Task {}", task.id);
-                
-                if let Some(runner) = flow_runner_opt {
-                    let api = unsafe { &*(api_ptr_addr as *const CoreHostApi) };
-                    let fc = runner.run(&mut task, api);
-                    
-                    if fc.code == ox_workflow_abi::FLOW_CONTROL_SUSPEND {
-                        task.status = TaskStatus::Paused;
-                    } else if fc.code == ox_workflow_abi::FLOW_CONTROL_ERROR {
-                        task.status = TaskStatus::Errored;
-                    } else {
-                        task.status = TaskStatus::Completed;
-                    }
-                    
-                    let _ = storage.save_task(&task, Some(&flow_name), None).await;
-
-                    for record in task.history.drain(..) {
-                        let _ = storage.append_history(
-                            task.id, 
-                            &record.stage_name, 
-                            record.plugin_name.as_deref(), 
-                            &record.status, 
-                            record.message.as_deref()
-                        ).await;
+
+        let mut task = match storage.load_task(task_id).await {
+            Ok(Some(task)) => task,
+            Ok(None) => return true,
+            Err(e) => {
+                log::error!("Failed to load task {}: {:?}", task_id, e);
+                return false;
+            }
+        };
+        match task.status {
+            TaskStatus::Queued => {}
+            TaskStatus::Running if self.running.lock().unwrap().contains(&task_id) => return false,
+            TaskStatus::Running => {
+                // Marked running but not running here: the scheduler stopped mid-run and the
+                // message was redelivered.
+                log::warn!("Task {} was interrupted while running, running it again", task_id);
+            }
+            _ => {
+                // Cancelled while queued, or a duplicate delivery of a task that already ran.
+                log::debug!("Skipping task {} in state {:?}", task_id, task.status);
+                return true;
+            }
+        }
+
+        // Flow name from task metadata or default
+        let flow_name = task.metadata.get("flow_name").cloned().unwrap_or_else(|| "default".to_string());
+        let Some(runner) = self.flow_manager.flows.get(&flow_name).cloned() else {
+            log::error!("Flow {} not found for task {}", flow_name, task.id);
+            let _ = storage.update_task_status(task_id, TaskStatus::Errored).await;
+            return true;
+        };
+
+        let _running = RunningGuard::new(&self.running, task_id);
+        task.status = TaskStatus::Running;
+        task.metadata.insert(QUEUE_METADATA_KEY.to_string(), queue_name.to_string());
+        let _ = storage.save_task(&task, Some(&flow_name), None).await;
+
+        let task_limit = self.flow_manager.flow_defs.get(&flow_name)
+            .and_then(|f| f.deadline_ms)
+            .map(Duration::from_millis);
+        let stage_limits: HashMap<String, Duration> = self.flow_manager.stage_defs.iter()
+            .filter_map(|(name, def)| def.deadline_ms.map(|ms| (name.clone(), Duration::from_millis(ms))))
+            .collect();
+        let watchdog = Arc::new(Watchdog::new(task_limit, stage_limits));
+        task.stage_observer = Some(watchdog.clone());
+
+        let api_ptr_addr = self.api_ptr as usize;
+        let run = tokio::task::spawn_blocking(move || {
+            log::debug!("Executing Task {} (attempt {})", task.id, task.attempt);
+            let api = unsafe { &*(api_ptr_addr as *const CoreHostApi) };
+            let code = runner.run(&mut task, api).code;
+            (code, task)
+        });
+
+        let outcome = tokio::select! {
+            joined = run => joined.map_err(|e| Abandoned::Panicked(e.to_string())),
+            _ = watchdog.expired() => Err(Abandoned::Timeout),
+        };
+
+        match outcome {
+            Ok((code, mut task)) => {
+                task.stage_observer = None;
+
+                for record in task.history.drain(..) {
+                    let _ = storage.append_history(
+                        task.id,
+                        &record.stage_name,
+                        record.plugin_name.as_deref(),
+                        &record.status,
+                        record.message.as_deref(),
+                        record.attempt,
+                    ).await;
+                }
+
+                for child_flow in task.child_workflows.drain(..) {
+                    let mut child = Task::new(task.priority);
+                    child.metadata.insert("flow_name".to_string(), child_flow.clone());
+                    child.metadata.insert("parent_task_id".to_string(), task.id.to_string());
+
+                    if let Ok(_) = storage.save_task(&child, Some(&child_flow), None).await {
+                        let _ = event_bus.publish_to_queue(DEFAULT_QUEUE, child.priority as u8, child.id.to_string().as_bytes()).await;
                     }
+                }
 
-                    for child_flow in task.child_workflows.drain(..) {
-                        let mut child = Task::new(task.priority);
-                        child.metadata.insert("flow_name".to_string(), child_flow.clone());
-                        child.metadata.insert("parent_task_id".to_string(), task.id.to_string());
-                        
-                        if let Ok(_) = storage.save_task(&child, Some(&child_flow), None).await {
-                            let _ = event_bus.publish_to_queue("tasks.pending", child.priority as u8, child.id.to_string().as_bytes()).await;
-                        }
+                if code == ox_workflow_abi::FLOW_CONTROL_ERROR {
+                    // Retry from the start of the stage that failed.
+                    let stage = task.metadata.get("current_stage").cloned();
+                    if let Some(stage) = &stage {
+                        task.checkpoint = Some(Checkpoint { stage: stage.clone(), plugin_index: 0, jumped: false });
                     }
-                } else {
-                    log::error!("Flow {} not found for task {}", flow_name, task.id);
-                    task.status = TaskStatus::Errored;
-                    let _ = storage.update_task_status(task_id, TaskStatus::Errored).await;
+                    self.handle_failure(task, &flow_name, stage).await;
+                    return true;
                 }
-            });
+
+                task.status = if code == ox_workflow_abi::FLOW_CONTROL_SUSPEND {
+                    TaskStatus::Paused
+                } else {
+                    TaskStatus::Completed
+                };
+                let _ = storage.save_task(&task, Some(&flow_name), None).await;
+            }
+            Err(abandoned) => {
+                let stage = watchdog.current_stage();
+                let (status, message) = match abandoned {
+                    Abandoned::Timeout => ("Timeout", "Deadline exceeded".to_string()),
+                    Abandoned::Panicked(e) => ("Panicked", e),
+                };
+                log::warn!("Task {} abandoned in stage {:?}: {}", task_id, stage, message);
+
+                // The run's in-memory state is lost with it; continue from the last saved
+                // state, which still holds the checkpoint this attempt started from.
+                let Ok(Some(task)) = storage.load_task(task_id).await else { return true };
+                let _ = storage.append_history(
+                    task_id,
+                    stage.as_deref().unwrap_or(""),
+                    None,
+                    status,
+                    Some(&message),
+                    task.attempt,
+                ).await;
+                self.handle_failure(task, &flow_name, stage).await;
+            }
+        }
+        true
+    }
+
+    /// Schedules a retry for a failed task if the failing stage's retry policy (or else the
+    /// flow's) allows one. Otherwise the task is errored and parked on a dead-letter queue.
+    async fn handle_failure(&self, mut task: Task, flow_name: &str, stage: Option<String>) {
+        let flow_def = self.flow_manager.flow_defs.get(flow_name);
+        let policy = stage.as_ref()
+            .and_then(|s| self.flow_manager.stage_defs.get(s))
+            .and_then(|d| d.retry.clone())
+            .or_else(|| flow_def.and_then(|f| f.retry.clone()));
+        let stage_name = stage.as_deref().unwrap_or("");
+
+        if let Some(policy) = policy.filter(|p| p.allows_retry(task.attempt)) {
+            let delay = policy.backoff(task.attempt);
+            let message = format!("Retry {} of {} in {}ms", task.attempt, policy.max_retries, delay.as_millis());
+            let _ = self.storage.append_history(task.id, stage_name, None, "Retrying", Some(&message), task.attempt).await;
+
+            task.attempt += 1;
+            task.status = TaskStatus::Queued;
+            let _ = self.storage.save_task(&task, Some(flow_name), None).await;
+            let _ = self.storage.schedule_retry(task.id, unix_millis() + delay.as_millis() as i64).await;
+            return;
+        }
+
+        task.status = TaskStatus::Errored;
+        let dead_letter_queue = flow_def
+            .and_then(|f| f.dead_letter_queue.clone())
+            .or_else(|| self.queues.iter()
+                .find(|q| matches!(q.discipline, QueueDiscipline::DeadLetter))
+                .map(|q| q.name.clone()));
+        if let Some(dlq) = &dead_letter_queue {
+            task.metadata.insert(DEAD_LETTER_METADATA_KEY.to_string(), dlq.clone());
+        }
+        let _ = self.storage.save_task(&task, Some(flow_name), None).await;
+
+        if let Some(dlq) = dead_letter_queue {
+            let message = format!("Moved to {} after {} attempt(s)", dlq, task.attempt);
+            let _ = self.storage.append_history(task.id, stage_name, None, "DeadLettered", Some(&message), task.attempt).await;
+            if let Err(e) = self.event_bus.publish_to_queue(&dlq, task.priority as u8, task.id.to_string().as_bytes()).await {
+                log::error!("Failed to publish task {} to dead-letter queue {}: {:?}", task.id, dlq, e);
+            }
+        }
+    }
+
+    /// Re-publishes tasks whose retry backoff has elapsed to the queue they came from.
+    async fn publish_due_retries(&self) {
+        let now = unix_millis();
+        let due = match self.storage.take_due_retries(now).await {
+            Ok(due) => due,
+            Err(e) => {
+                log::error!("Failed to load due retries: {:?}", e);
+                return;
+            }
+        };
+
+        for retry in due {
+            let queue = retry.metadata.get(QUEUE_METADATA_KEY).map(String::as_str).unwrap_or(DEFAULT_QUEUE);
+            if let Err(e) = self.event_bus.publish_to_queue(queue, retry.priority as u8, retry.id.to_string().as_bytes()).await {
+                log::error!("Failed to publish retry of task {} to {}: {:?}", retry.id, queue, e);
+                // Try again on the next tick.
+                let _ = self.storage.schedule_retry(retry.id, now).await;
+            }
         }
     }
 }
 
+/// Keeps a task in the running set for as long as it is alive.
+struct RunningGuard<'a> {
+    set: &'a Mutex<HashSet<uuid::Uuid>>,
+    id: uuid::Uuid,
+}
+
+impl<'a> RunningGuard<'a> {
+    fn new(set: &'a Mutex<HashSet<uuid::Uuid>>, id: uuid::Uuid) -> Self {
+        set.lock().unwrap().insert(id);
+        Self { set, id }
+    }
+}
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        self.set.lock().unwrap().remove(&self.id);
+    }
+}
+
+fn unix_millis() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or(0)
+}
+
+fn task_id_of(payload: &[u8]) -> Option<uuid::Uuid> {
+    std::str::from_utf8(payload).ok().and_then(|s| uuid::Uuid::parse_str(s).ok())
+}
+
 // Global shutdown channel for cdylib
 static SHUTDOWN_TX: std::sync::OnceLock<tokio::sync::broadcast::Sender<()>> = std::sync::OnceLock::new();
 
@@ -0,0 +1,286 @@
+use futures::{FutureExt, Stream, StreamExt};
+use ox_event_bus::{EventMessage, PRIORITY_HEADER};
+use ox_workflow_config::{QueueConfig, QueueDiscipline};
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::{Duration, Instant};
+
+/// Upper bound on messages pulled from the bus between two dispatch decisions,
+/// so a flooding publisher cannot starve dispatching.
+const INTAKE_BATCH: usize = 256;
+
+/// Whether the scheduler consumes a queue. `EventOnly` queues carry plain events for
+/// subscribers and `DeadLetter` queues park messages until an operator requeues them.
+pub fn is_dispatched(discipline: &QueueDiscipline) -> bool {
+    !matches!(discipline, QueueDiscipline::EventOnly | QueueDiscipline::DeadLetter)
+}
+
+/// Local buffer between the bus and the scheduler, ordered by the queue's discipline.
+///
+/// - `Fifo`: arrival order; priorities are ignored.
+/// - `Priority`: strict priority — the highest level is always served first, FIFO within a level.
+/// - `Hybrid`: weighted round robin over the non-empty levels, level `n` weighing `n + 1`,
+///   so higher priorities get proportionally more slots without starving lower ones.
+pub struct DispatchQueue {
+    discipline: QueueDiscipline,
+    levels: Vec<VecDeque<EventMessage>>,
+    /// Smooth weighted round robin state for `Hybrid`.
+    credit: Vec<i64>,
+    max_messages: usize,
+    len: usize,
+}
+
+impl DispatchQueue {
+    pub fn new(config: &QueueConfig) -> Self {
+        let levels = match config.discipline {
+            QueueDiscipline::Priority | QueueDiscipline::Hybrid => config.priority_levels.max(1) as usize,
+            _ => 1,
+        };
+        Self {
+            discipline: config.discipline.clone(),
+            levels: (0..levels).map(|_| VecDeque::new()).collect(),
+            credit: vec![0; levels],
+            max_messages: config.max_messages,
+            len: 0,
+        }
+    }
+
+    /// True once the queue holds `max_messages` (0 means unbounded). Further messages
+    /// must be rejected rather than pushed.
+    pub fn is_full(&self) -> bool {
+        self.max_messages > 0 && self.len >= self.max_messages
+    }
+
+    pub fn push(&mut self, msg: EventMessage) {
+        let level = self.level_of(&msg);
+        self.levels[level].push_back(msg);
+        self.len += 1;
+    }
+
+    pub fn pop(&mut self) -> Option<EventMessage> {
+        let level = match self.discipline {
+            QueueDiscipline::Hybrid => self.next_weighted()?,
+            _ => self.levels.iter().rposition(|l| !l.is_empty())?,
+        };
+        let msg = self.levels[level].pop_front()?;
+        self.len -= 1;
+        Some(msg)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn level_of(&self, msg: &EventMessage) -> usize {
+        let priority = msg.headers.get(PRIORITY_HEADER).and_then(|p| p.parse::<usize>().ok()).unwrap_or(0);
+        priority.min(self.levels.len() - 1)
+    }
+
+    fn next_weighted(&mut self) -> Option<usize> {
+        let mut total = 0;
+        let mut best: Option<usize> = None;
+        for (level, queue) in self.levels.iter().enumerate() {
+            if queue.is_empty() {
+                continue;
+            }
+            let weight = level as i64 + 1;
+            self.credit[level] += weight;
+            total += weight;
+            if best.is_none_or(|b| self.credit[level] >= self.credit[b]) {
+                best = Some(level);
+            }
+        }
+        let best = best?;
+        self.credit[best] -= total;
+        Some(best)
+    }
+}
+
+/// Token bucket enforcing `max_throughput_per_sec`. Holds up to one second of tokens,
+/// so an idle queue may dispatch a burst of that size.
+pub struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(per_sec: u32) -> Self {
+        Self { rate: per_sec.max(1) as f64, tokens: per_sec.max(1) as f64, last: Instant::now() }
+    }
+
+    /// Returns how long to wait until a token is available, or `None` if one is.
+    pub fn wait_time(&mut self) -> Option<Duration> {
+        let now = Instant::now();
+        self.tokens = (self.tokens + now.duration_since(self.last).as_secs_f64() * self.rate).min(self.rate);
+        self.last = now;
+        (self.tokens < 1.0).then(|| Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+    }
+
+    /// Consumes a token. Call after `wait_time` returned `None`.
+    pub fn take(&mut self) {
+        self.tokens -= 1.0;
+    }
+}
+
+/// Feeds a queue subscription through a [`DispatchQueue`] until the subscription ends and
+/// the buffer is drained.
+///
+/// Each message is handed to `dispatch` together with a permit from `semaphore`, which the
+/// callee holds for as long as the task runs. Messages arriving while the buffer is full go
+/// to `reject`.
+pub async fn drain_queue<D, R>(
+    config: QueueConfig,
+    mut stream: Pin<Box<dyn Stream<Item = EventMessage> + Send>>,
+    semaphore: Arc<Semaphore>,
+    dispatch: D,
+    reject: R,
+) where
+    D: Fn(EventMessage, OwnedSemaphorePermit),
+    R: Fn(EventMessage),
+{
+    let mut queue = DispatchQueue::new(&config);
+    let mut bucket = config.max_throughput_per_sec.map(TokenBucket::new);
+    let mut open = true;
+
+    let accept = |queue: &mut DispatchQueue, msg: EventMessage| {
+        if queue.is_full() {
+            log::warn!("Queue {} is full ({} messages), rejecting message", config.name, config.max_messages);
+            reject(msg);
+        } else {
+            queue.push(msg);
+        }
+    };
+
+    loop {
+        // Pull in whatever the bus has already delivered so ordering sees all of it.
+        for _ in 0..INTAKE_BATCH {
+            if !open {
+                break;
+            }
+            match stream.next().now_or_never() {
+                Some(Some(msg)) => accept(&mut queue, msg),
+                Some(None) => open = false,
+                None => break,
+            }
+        }
+
+        if queue.is_empty() {
+            if !open {
+                break;
+            }
+            match stream.next().await {
+                Some(msg) => accept(&mut queue, msg),
+                None => open = false,
+            }
+            continue;
+        }
+
+        if let Some(wait) = bucket.as_mut().and_then(TokenBucket::wait_time) {
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                item = stream.next(), if open => match item {
+                    Some(msg) => accept(&mut queue, msg),
+                    None => open = false,
+                },
+            }
+            continue;
+        }
+
+        tokio::select! {
+            permit = semaphore.clone().acquire_owned() => {
+                let Ok(permit) = permit else { break };
+                if let Some(msg) = queue.pop() {
+                    if let Some(b) = bucket.as_mut() { b.take(); }
+                    dispatch(msg, permit);
+                }
+            }
+            item = stream.next(), if open => match item {
+                Some(msg) => accept(&mut queue, msg),
+                None => open = false,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config(discipline: QueueDiscipline, levels: u8, max_messages: usize) -> QueueConfig {
+        QueueConfig {
+            name: "q".to_string(),
+            discipline,
+            run_every: 0,
+            priority_levels: levels,
+            max_messages,
+            max_throughput_per_sec: None,
+        }
+    }
+
+    fn msg(id: &str, priority: u8) -> EventMessage {
+        EventMessage {
+            topic: "q".to_string(),
+            payload: id.as_bytes().to_vec(),
+            headers: HashMap::from([(PRIORITY_HEADER.to_string(), priority.to_string())]),
+            correlation_id: None,
+            reply_to: None,
+            delivery: None,
+        }
+    }
+
+    fn drain(queue: &mut DispatchQueue) -> Vec<String> {
+        std::iter::from_fn(|| queue.pop()).map(|m| String::from_utf8(m.payload).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_fifo_ignores_priority() {
+        let mut q = DispatchQueue::new(&config(QueueDiscipline::Fifo, 3, 0));
+        for (id, p) in [("a", 0), ("b", 2), ("c", 1)] {
+            q.push(msg(id, p));
+        }
+        assert_eq!(drain(&mut q), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_strict_priority_clamps_levels() {
+        let mut q = DispatchQueue::new(&config(QueueDiscipline::Priority, 3, 0));
+        for (id, p) in [("low", 0), ("high1", 2), ("mid", 1), ("high2", 9)] {
+            q.push(msg(id, p));
+        }
+        assert_eq!(drain(&mut q), ["high1", "high2", "mid", "low"]);
+    }
+
+    #[test]
+    fn test_hybrid_is_weighted_and_does_not_starve() {
+        let mut q = DispatchQueue::new(&config(QueueDiscipline::Hybrid, 2, 0));
+        for i in 0..6 {
+            q.push(msg(&format!("h{}", i), 1));
+            q.push(msg(&format!("l{}", i), 0));
+        }
+        let order = drain(&mut q);
+        // Weights 2:1 — every window of three dispatches serves the low level once.
+        let first_nine: Vec<_> = order.iter().take(9).map(|s| &s[..1]).collect();
+        assert_eq!(first_nine.iter().filter(|s| **s == "l").count(), 3);
+        assert_eq!(order.len(), 12);
+    }
+
+    #[test]
+    fn test_bounded_depth_rejects() {
+        let mut q = DispatchQueue::new(&config(QueueDiscipline::Fifo, 1, 2));
+        q.push(msg("a", 0));
+        assert!(!q.is_full());
+        q.push(msg("b", 0));
+        assert!(q.is_full());
+        q.pop();
+        assert!(!q.is_full());
+        assert_eq!(q.len(), 1);
+    }
+}
@@ -0,0 +1,116 @@
+use ox_workflow_core::StageObserver;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// Tracks the deadlines of one task run: the per-task deadline of the whole attempt and
+/// the deadline of whichever stage is currently executing.
+///
+/// The flow runs on a blocking thread and reports stage changes through
+/// [`StageObserver`]; the scheduler waits on [`Watchdog::expired`] alongside the run.
+pub struct Watchdog {
+    task_deadline: Option<Instant>,
+    stage_limits: HashMap<String, Duration>,
+    current: Mutex<Option<(String, Option<Instant>)>>,
+    changed: Notify,
+}
+
+impl Watchdog {
+    pub fn new(task_limit: Option<Duration>, stage_limits: HashMap<String, Duration>) -> Self {
+        Self {
+            task_deadline: task_limit.map(|limit| Instant::now() + limit),
+            stage_limits,
+            current: Mutex::new(None),
+            changed: Notify::new(),
+        }
+    }
+
+    /// The stage that is running, if any has started yet.
+    pub fn current_stage(&self) -> Option<String> {
+        self.current.lock().unwrap().as_ref().map(|(name, _)| name.clone())
+    }
+
+    /// Earliest of the task deadline and the current stage's deadline.
+    pub fn deadline(&self) -> Option<Instant> {
+        let stage_deadline = self.current.lock().unwrap().as_ref().and_then(|(_, d)| *d);
+        match (self.task_deadline, stage_deadline) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Resolves once a deadline passes. Re-arms whenever the run enters a new stage;
+    /// never resolves if no deadline applies.
+    pub async fn expired(&self) {
+        loop {
+            let changed = self.changed.notified();
+            tokio::pin!(changed);
+            changed.as_mut().enable();
+
+            match self.deadline() {
+                Some(deadline) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep_until(deadline.into()) => return,
+                        _ = &mut changed => {}
+                    }
+                }
+                None => changed.await,
+            }
+        }
+    }
+}
+
+impl StageObserver for Watchdog {
+    fn stage_started(&self, stage: &str) {
+        let deadline = self.stage_limits.get(stage).map(|limit| Instant::now() + *limit);
+        *self.current.lock().unwrap() = Some((stage.to_string(), deadline));
+        self.changed.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stage_deadline_fires_only_in_limited_stage() {
+        let limits = HashMap::from([("slow".to_string(), Duration::from_millis(20))]);
+        let watchdog = Watchdog::new(None, limits);
+        assert!(watchdog.deadline().is_none());
+
+        watchdog.stage_started("fast");
+        let expired = tokio::time::timeout(Duration::from_millis(50), watchdog.expired()).await;
+        assert!(expired.is_err(), "no deadline applies to 'fast'");
+
+        watchdog.stage_started("slow");
+        let expired = tokio::time::timeout(Duration::from_millis(500), watchdog.expired()).await;
+        assert!(expired.is_ok());
+        assert_eq!(watchdog.current_stage().as_deref(), Some("slow"));
+    }
+
+    #[tokio::test]
+    async fn test_task_deadline_caps_stage_deadline() {
+        let limits = HashMap::from([("s".to_string(), Duration::from_secs(60))]);
+        let watchdog = Watchdog::new(Some(Duration::from_millis(20)), limits);
+        watchdog.stage_started("s");
+        assert!(watchdog.deadline().unwrap() < Instant::now() + Duration::from_secs(1));
+        let expired = tokio::time::timeout(Duration::from_millis(500), watchdog.expired()).await;
+        assert!(expired.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_stage_change_rearms_the_wait() {
+        let watchdog = std::sync::Arc::new(Watchdog::new(None, HashMap::from([
+            ("b".to_string(), Duration::from_millis(20)),
+        ])));
+        let waiter = tokio::spawn({
+            let watchdog = watchdog.clone();
+            async move { watchdog.expired().await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+        watchdog.stage_started("b");
+        tokio::time::timeout(Duration::from_millis(500), waiter).await.unwrap().unwrap();
+    }
+}
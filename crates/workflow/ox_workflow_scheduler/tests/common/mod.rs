@@ -0,0 +1,94 @@
+#![allow(dead_code)]
+
+use futures::{Stream, StreamExt};
+use ox_event_bus::{EventBus, EventMessage, QueueConfig as BusQueueConfig};
+use ox_event_bus_memory::MemoryBus;
+use ox_workflow_config::{QueueConfig, QueueDiscipline};
+use ox_workflow_executor::plugin_registry::{LoadedPlugin, PluginInstance};
+use ox_workflow_executor::wasm_plugin::WasmModule;
+use ox_workflow_executor::{FlowManager, FlowRunner, StageRunner};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// Always returns `CONTINUE`.
+pub const PASS: &str = r#"
+    (module
+      (memory (export "memory") 1)
+      (func (export "alloc") (param i32) (result i32) (i32.const 64))
+      (func (export "ox_plugin_init") (param i32 i32) (result i32) (i32.const 1))
+      (func (export "ox_plugin_process") (param i32) (result i32) (i32.const 0)))
+"#;
+
+/// Always returns `ERROR`.
+pub const FAIL: &str = r#"
+    (module
+      (memory (export "memory") 1)
+      (func (export "alloc") (param i32) (result i32) (i32.const 64))
+      (func (export "ox_plugin_init") (param i32 i32) (result i32) (i32.const 1))
+      (func (export "ox_plugin_process") (param i32) (result i32) (i32.const 2)))
+"#;
+
+pub fn queue(name: &str, discipline: QueueDiscipline) -> QueueConfig {
+    QueueConfig {
+        name: name.to_string(),
+        discipline,
+        run_every: 0,
+        priority_levels: 1,
+        max_messages: 0,
+        max_throughput_per_sec: None,
+    }
+}
+
+/// Publishes `(payload, priority)` pairs to `queue` and returns a subscription that ends
+/// once they have all been received. The queue has a single priority level, so the bus
+/// delivers in publish order and leaves ordering by the priority header to the scheduler.
+pub async fn published(
+    bus: &MemoryBus,
+    queue: &str,
+    messages: &[(&str, u8)],
+) -> Pin<Box<dyn Stream<Item = EventMessage> + Send>> {
+    let cfg = BusQueueConfig { priority_levels: 1, max_messages: 0, max_throughput_per_sec: None };
+    bus.create_queue(queue, cfg).await.unwrap();
+    for (payload, priority) in messages {
+        bus.publish_to_queue(queue, *priority, payload.as_bytes()).await.unwrap();
+    }
+    bus.subscribe_to_queue(queue).await.unwrap().take(messages.len()).boxed()
+}
+
+/// A flow named `flow` with a single `work` stage running the WAT plugin `wat`.
+pub fn single_stage_flow(api: &ox_workflow_abi::CoreHostApi, flow: &str, wat: &str) -> FlowManager {
+    let module = WasmModule::from_wasm_bytes(flow, &wat::parse_str(wat).unwrap()).unwrap();
+    let plugin = Arc::new(LoadedPlugin::Wasm(module));
+    let ctx = plugin.init("{}", api).unwrap();
+
+    let mut manager = FlowManager::new();
+    manager.flows.insert(flow.to_string(), Arc::new(FlowRunner {
+        flow_name: flow.to_string(),
+        stages: vec![StageRunner {
+            name: "work".to_string(),
+            on_error_target: None,
+            plugins: vec![PluginInstance { name: flow.to_string(), plugin, ctx }],
+        }],
+    }));
+    manager
+}
+
+/// A SQLite database file in the temp dir, removed on drop.
+pub struct TempDb(PathBuf);
+
+impl TempDb {
+    pub fn new(prefix: &str) -> Self {
+        Self(std::env::temp_dir().join(format!("{}_{}.db", prefix, uuid::Uuid::new_v4())))
+    }
+
+    pub fn url(&self) -> String {
+        format!("sqlite://{}?mode=rwc", self.0.display())
+    }
+}
+
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use common::{queue, single_stage_flow, TempDb, PASS};
+use futures::StreamExt;
+use ox_event_bus::EventBus;
+use ox_event_bus_memory::MemoryBus;
+use ox_event_bus_sqlite::DurableBus;
+use ox_workflow_config::{EngineConfig, QueueDiscipline};
+use ox_workflow_core::{Task, TaskStatus};
+use ox_workflow_executor::create_host_api;
+use ox_workflow_scheduler::WorkflowScheduler;
+use ox_workflow_storage::WorkflowStorage;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A scheduler that stopped after receiving a task, without finishing it, does not lose it:
+/// the unacknowledged message comes back and the task runs to completion.
+#[tokio::test]
+async fn test_interrupted_task_is_redelivered_and_completed() {
+    let db = TempDb::new("ox_scheduler_delivery");
+    let url = db.url();
+    let storage = WorkflowStorage::new(&url).await.unwrap();
+    let bus = DurableBus::new(MemoryBus::new(), &url, Duration::from_millis(100)).await.unwrap();
+
+    let mut task = Task::new(0);
+    task.metadata.insert("flow_name".to_string(), "steady".to_string());
+    storage.save_task(&task, Some("steady"), None).await.unwrap();
+    bus.publish_to_queue("tasks.pending", 0, task.id.to_string().as_bytes()).await.unwrap();
+
+    // The previous scheduler took the message and marked the task running, then died.
+    {
+        let mut stream = bus.subscribe_to_queue("tasks.pending").await.unwrap();
+        stream.next().await.unwrap();
+        task.status = TaskStatus::Running;
+        storage.save_task(&task, Some("steady"), None).await.unwrap();
+    }
+
+    let api: &'static _ = Box::leak(Box::new(create_host_api()));
+    let scheduler = Arc::new(WorkflowScheduler::new(
+        EngineConfig { max_concurrent_tasks: 4, ..EngineConfig::default() },
+        storage.clone(),
+        bus.clone(),
+        Arc::new(single_stage_flow(api, "steady", PASS)),
+        api,
+        vec![queue("tasks.pending", QueueDiscipline::Fifo)],
+    ));
+    tokio::spawn(scheduler.run());
+
+    let done = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let status = storage.load_task(task.id).await.unwrap().unwrap().status;
+            if status == TaskStatus::Completed && bus.pending("tasks.pending").await.unwrap() == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }).await;
+    assert!(done.is_ok(), "task was not completed and acknowledged");
+}
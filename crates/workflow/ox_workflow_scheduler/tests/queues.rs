@@ -0,0 +1,112 @@
+mod common;
+
+use common::{published, queue};
+use futures::Stream;
+use ox_event_bus::EventMessage;
+use ox_event_bus_memory::MemoryBus;
+use ox_workflow_config::{QueueConfig, QueueDiscipline};
+use ox_workflow_scheduler::queue::drain_queue;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::time::Instant;
+
+fn pending_queue(discipline: QueueDiscipline, max_messages: usize, max_throughput_per_sec: Option<u32>) -> QueueConfig {
+    QueueConfig { priority_levels: 3, max_messages, max_throughput_per_sec, ..queue("tasks.pending", discipline) }
+}
+
+async fn pending(bus: &MemoryBus, messages: &[(&str, u8)]) -> Pin<Box<dyn Stream<Item = EventMessage> + Send>> {
+    published(bus, "tasks.pending", messages).await
+}
+
+fn payload(msg: &EventMessage) -> String {
+    String::from_utf8(msg.payload.clone()).unwrap()
+}
+
+#[tokio::test]
+async fn test_priority_queue_dispatches_highest_first() {
+    let bus = MemoryBus::default();
+    let stream = pending(&bus, &[("low", 0), ("high", 2), ("mid", 1), ("high2", 2)]).await;
+
+    let dispatched = Mutex::new(Vec::new());
+    drain_queue(
+        pending_queue(QueueDiscipline::Priority, 0, None),
+        stream,
+        Arc::new(Semaphore::new(1)),
+        |msg, _permit| dispatched.lock().unwrap().push(payload(&msg)),
+        |_| panic!("nothing should be rejected"),
+    ).await;
+
+    assert_eq!(*dispatched.lock().unwrap(), ["high", "high2", "mid", "low"]);
+}
+
+#[tokio::test]
+async fn test_bounded_queue_rejects_overflow() {
+    let bus = MemoryBus::default();
+    let stream = pending(&bus, &[("a", 0), ("b", 0), ("c", 0), ("d", 0), ("e", 0)]).await;
+
+    let dispatched = Mutex::new(Vec::new());
+    let rejected = Mutex::new(Vec::new());
+    drain_queue(
+        pending_queue(QueueDiscipline::Fifo, 3, None),
+        stream,
+        Arc::new(Semaphore::new(1)),
+        |msg, _permit| dispatched.lock().unwrap().push(payload(&msg)),
+        |msg| rejected.lock().unwrap().push(payload(&msg)),
+    ).await;
+
+    assert_eq!(*dispatched.lock().unwrap(), ["a", "b", "c"]);
+    assert_eq!(*rejected.lock().unwrap(), ["d", "e"]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_throughput_cap_spaces_dispatches() {
+    let bus = MemoryBus::default();
+    let ids: Vec<String> = (0..10).map(|i| i.to_string()).collect();
+    let messages: Vec<(&str, u8)> = ids.iter().map(|id| (id.as_str(), 0)).collect();
+    let stream = pending(&bus, &messages).await;
+
+    let start = Instant::now();
+    let times = Mutex::new(Vec::new());
+    drain_queue(
+        pending_queue(QueueDiscipline::Fifo, 0, Some(5)),
+        stream,
+        Arc::new(Semaphore::new(10)),
+        |_msg, _permit| times.lock().unwrap().push(start.elapsed()),
+        |_| panic!("nothing should be rejected"),
+    ).await;
+
+    let times = times.into_inner().unwrap();
+    assert_eq!(times.len(), 10);
+    // A full bucket allows a one-second burst, then one dispatch every 200ms.
+    assert!(times[4] < Duration::from_millis(10));
+    assert!(times[5] >= Duration::from_millis(190));
+    assert!(times[9] >= Duration::from_millis(990));
+}
+
+#[tokio::test]
+async fn test_concurrency_permits_are_held_by_running_tasks() {
+    let bus = MemoryBus::default();
+    let stream = pending(&bus, &[("a", 0), ("b", 0), ("c", 0)]).await;
+
+    let semaphore = Arc::new(Semaphore::new(2));
+    let held = Mutex::new(Vec::new());
+    let run = drain_queue(
+        pending_queue(QueueDiscipline::Fifo, 0, None),
+        stream,
+        semaphore.clone(),
+        |msg, permit| held.lock().unwrap().push((payload(&msg), permit)),
+        |_| panic!("nothing should be rejected"),
+    );
+    tokio::pin!(run);
+
+    // Two tasks run and keep their permits; the third waits for a slot.
+    assert!(tokio::time::timeout(Duration::from_millis(50), &mut run).await.is_err());
+    assert_eq!(held.lock().unwrap().len(), 2);
+
+    drop(held.lock().unwrap().remove(0));
+    run.await;
+    let names: Vec<String> = held.lock().unwrap().iter().map(|(n, _)| n.clone()).collect();
+    assert_eq!(names, ["b", "c"]);
+}
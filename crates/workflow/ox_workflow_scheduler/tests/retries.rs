@@ -0,0 +1,124 @@
+mod common;
+
+use common::{queue, single_stage_flow, TempDb, FAIL};
+use futures::StreamExt;
+use ox_event_bus::{EventBus, QueueConfig as BusQueueConfig};
+use ox_event_bus_memory::MemoryBus;
+use ox_workflow_config::{EngineConfig, QueueConfig, QueueDiscipline};
+use ox_workflow_core::{FlowDef, RetryPolicy, Task, TaskStatus};
+use ox_workflow_executor::{create_host_api, FlowManager};
+use ox_workflow_scheduler::{WorkflowScheduler, DEAD_LETTER_METADATA_KEY};
+use ox_workflow_storage::WorkflowStorage;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn failing_flow(api: &ox_workflow_abi::CoreHostApi, retry: RetryPolicy) -> FlowManager {
+    let mut manager = single_stage_flow(api, "flaky", FAIL);
+    manager.flow_defs.insert("flaky".to_string(), FlowDef {
+        name: "flaky".to_string(),
+        persistent: true,
+        stages: vec!["work".to_string()],
+        retry: Some(retry),
+        deadline_ms: None,
+        dead_letter_queue: None,
+    });
+    manager
+}
+
+/// A task that keeps failing is retried with backoff, then parked on the dead-letter queue.
+#[tokio::test]
+async fn test_exhausted_retries_go_to_dead_letter_queue() {
+    let db = TempDb::new("ox_scheduler_retries");
+    let storage = WorkflowStorage::new(&db.url()).await.unwrap();
+
+    let bus = MemoryBus::new();
+    let cfg = BusQueueConfig { priority_levels: 1, max_messages: 0, max_throughput_per_sec: None };
+    bus.create_queue("tasks.pending", cfg.clone()).await.unwrap();
+    bus.create_queue("tasks.dead", cfg).await.unwrap();
+    let mut dead_letters = bus.subscribe_to_queue("tasks.dead").await.unwrap();
+
+    let api: &'static _ = Box::leak(Box::new(create_host_api()));
+    let retry = RetryPolicy { max_retries: 2, initial_backoff_ms: 10, backoff_multiplier: 2.0, max_backoff_ms: None };
+    let scheduler = Arc::new(WorkflowScheduler::new(
+        EngineConfig { tick_interval_ms: 10, max_concurrent_tasks: 4, ..EngineConfig::default() },
+        storage.clone(),
+        bus.clone(),
+        Arc::new(failing_flow(api, retry)),
+        api,
+        vec![queue("tasks.pending", QueueDiscipline::Fifo), queue("tasks.dead", QueueDiscipline::DeadLetter)],
+    ));
+    tokio::spawn(scheduler.run());
+
+    let mut task = Task::new(0);
+    task.metadata.insert("flow_name".to_string(), "flaky".to_string());
+    storage.save_task(&task, Some("flaky"), None).await.unwrap();
+    bus.publish_to_queue("tasks.pending", 0, task.id.to_string().as_bytes()).await.unwrap();
+
+    let parked = tokio::time::timeout(Duration::from_secs(10), dead_letters.next()).await.unwrap().unwrap();
+    assert_eq!(parked.payload, task.id.to_string().as_bytes());
+
+    let stored = storage.load_task(task.id).await.unwrap().unwrap();
+    assert_eq!(stored.status, TaskStatus::Errored);
+    assert_eq!(stored.attempt, 3);
+    assert_eq!(stored.metadata.get(DEAD_LETTER_METADATA_KEY).map(String::as_str), Some("tasks.dead"));
+
+    let history = storage.get_task_history(task.id).await.unwrap();
+    let summary: Vec<(String, u32)> = history.iter().map(|h| (h.status.clone(), h.attempt)).collect();
+    assert_eq!(summary, [
+        ("Error".to_string(), 1),
+        ("Retrying".to_string(), 1),
+        ("Error".to_string(), 2),
+        ("Retrying".to_string(), 2),
+        ("Error".to_string(), 3),
+        ("DeadLettered".to_string(), 3),
+    ]);
+}
+
+/// A retry that finds its queue full is rejected as the attempt it was about to make.
+#[tokio::test]
+async fn test_rejected_retry_records_its_attempt() {
+    let db = TempDb::new("ox_scheduler_rejected");
+    let storage = WorkflowStorage::new(&db.url()).await.unwrap();
+
+    let bus = MemoryBus::new();
+    let cfg = BusQueueConfig { priority_levels: 1, max_messages: 0, max_throughput_per_sec: None };
+    bus.create_queue("tasks.pending", cfg).await.unwrap();
+
+    let mut tasks = Vec::new();
+    for attempt in [1, 2] {
+        let mut task = Task::new(0);
+        task.attempt = attempt;
+        task.metadata.insert("flow_name".to_string(), "flaky".to_string());
+        storage.save_task(&task, Some("flaky"), None).await.unwrap();
+        bus.publish_to_queue("tasks.pending", 0, task.id.to_string().as_bytes()).await.unwrap();
+        tasks.push(task);
+    }
+
+    // No task ever gets a slot, so the first message fills the one-message buffer.
+    let api: &'static _ = Box::leak(Box::new(create_host_api()));
+    let retry = RetryPolicy { max_retries: 2, initial_backoff_ms: 10, backoff_multiplier: 2.0, max_backoff_ms: None };
+    let scheduler = Arc::new(WorkflowScheduler::new(
+        EngineConfig { max_concurrent_tasks: 0, ..EngineConfig::default() },
+        storage.clone(),
+        bus.clone(),
+        Arc::new(failing_flow(api, retry)),
+        api,
+        vec![QueueConfig { max_messages: 1, ..queue("tasks.pending", QueueDiscipline::Fifo) }],
+    ));
+    tokio::spawn(scheduler.run());
+
+    let rejected = &tasks[1];
+    let history = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let history = storage.get_task_history(rejected.id).await.unwrap();
+            if !history.is_empty() {
+                break history;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }).await.expect("overflowing task was not rejected");
+
+    let summary: Vec<(String, u32)> = history.iter().map(|h| (h.status.clone(), h.attempt)).collect();
+    assert_eq!(summary, [("Rejected".to_string(), 2)]);
+    assert_eq!(storage.load_task(rejected.id).await.unwrap().unwrap().status, TaskStatus::Errored);
+}
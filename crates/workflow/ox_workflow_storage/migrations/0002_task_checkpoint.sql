@@ -0,0 +1,3 @@
+-- Resume position of suspended tasks (serialized ox_workflow_core::Checkpoint)
+
+ALTER TABLE tasks ADD COLUMN checkpoint_json TEXT;
@@ -0,0 +1,7 @@
+-- Retry bookkeeping: the attempt a task is on, and when a failed task is due to run again
+
+ALTER TABLE tasks ADD COLUMN attempt INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE tasks ADD COLUMN retry_at INTEGER;
+ALTER TABLE execution_history ADD COLUMN attempt INTEGER NOT NULL DEFAULT 1;
+
+CREATE INDEX IF NOT EXISTS idx_tasks_retry_at ON tasks(retry_at);
@@ -1,4 +1,4 @@
-use ox_workflow_core::{Task, TaskStatus};
+use ox_workflow_core::{Checkpoint, Task, TaskStatus};
 use parking_lot::RwLock;
 use sqlx::{sqlite::SqlitePoolOptions, Row, SqlitePool};
 use std::collections::HashMap;
@@ -46,11 +46,13 @@ impl WorkflowStorage {
 
         let metadata_json = serde_json::to_string(&task.metadata)?;
         let status_str = format!("{:?}", task.status);
+        let checkpoint_json = task.checkpoint.as_ref().map(serde_json::to_string).transpose()?;
+        let stage_name = stage_name.or(task.checkpoint.as_ref().map(|c| c.stage.as_str()));
 
         sqlx::query(
             r#"
-            INSERT INTO tasks (id, priority, status, flow_name, stage_name, state_blob, metadata_json, updated_at)
-            VALUES (?, ?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP)
+            INSERT INTO tasks (id, priority, status, flow_name, stage_name, state_blob, metadata_json, checkpoint_json, attempt, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP)
             ON CONFLICT(id) DO UPDATE SET
                 priority = excluded.priority,
                 status = excluded.status,
@@ -58,6 +60,8 @@ impl WorkflowStorage {
                 stage_name = excluded.stage_name,
                 state_blob = excluded.state_blob,
                 metadata_json = excluded.metadata_json,
+                checkpoint_json = excluded.checkpoint_json,
+                attempt = excluded.attempt,
                 updated_at = CURRENT_TIMESTAMP
             "#
         )
@@ -68,6 +72,8 @@ impl WorkflowStorage {
         .bind(stage_name)
         .bind(state_blob)
         .bind(metadata_json)
+        .bind(checkpoint_json)
+        .bind(task.attempt)
         .execute(&self.pool)
         .await?;
 
@@ -79,7 +85,7 @@ impl WorkflowStorage {
         let id_str = id.to_string();
         let row = sqlx::query(
             r#"
-            SELECT priority, status, state_blob, metadata_json
+            SELECT priority, status, state_blob, metadata_json, checkpoint_json, attempt
             FROM tasks WHERE id = ?
             "#
         )
@@ -105,6 +111,8 @@ impl WorkflowStorage {
 
             let state = TaskState::from_proto_bytes(&state_blob)?;
             let metadata: HashMap<String, String> = serde_json::from_str(&metadata_json)?;
+            let checkpoint = checkpoint_from_row(&row)?;
+            let attempt: i64 = row.try_get("attempt")?;
 
             Ok(Some(Task {
                 id,
@@ -120,6 +128,12 @@ impl WorkflowStorage {
                 ffi_bytes_arena: Vec::new(),
                 api_call_counts: std::collections::HashMap::new(),
                 api_limits: std::collections::HashMap::new(),
+                body_reader: None,
+                response_stream: None,
+                connections: None,
+                checkpoint,
+                attempt: attempt as u32,
+                stage_observer: None,
             }))
         } else {
             Ok(None)
@@ -146,11 +160,11 @@ impl WorkflowStorage {
         Ok(())
     }
 
-    pub async fn append_history(&self, task_id: Uuid, stage_name: &str, plugin_name: Option<&str>, status: &str, message: Option<&str>) -> Result<(), StorageError> {
+    pub async fn append_history(&self, task_id: Uuid, stage_name: &str, plugin_name: Option<&str>, status: &str, message: Option<&str>, attempt: u32) -> Result<(), StorageError> {
         sqlx::query(
             r#"
-            INSERT INTO execution_history (task_id, stage_name, plugin_name, status, message)
-            VALUES (?, ?, ?, ?, ?)
+            INSERT INTO execution_history (task_id, stage_name, plugin_name, status, message, attempt)
+            VALUES (?, ?, ?, ?, ?, ?)
             "#
         )
         .bind(task_id.to_string())
@@ -158,6 +172,7 @@ impl WorkflowStorage {
         .bind(plugin_name)
         .bind(status)
         .bind(message)
+        .bind(attempt)
         .execute(&self.pool)
         .await?;
         Ok(())
@@ -165,7 +180,7 @@ impl WorkflowStorage {
 
     pub async fn get_task_history(&self, task_id: Uuid) -> Result<Vec<ox_workflow_core::HistoryRecord>, StorageError> {
         let rows = sqlx::query(
-            "SELECT stage_name, plugin_name, status, message FROM execution_history WHERE task_id = ? ORDER BY started_at ASC"
+            "SELECT stage_name, plugin_name, status, message, attempt FROM execution_history WHERE task_id = ? ORDER BY started_at ASC, id ASC"
         )
         .bind(task_id.to_string())
         .fetch_all(&self.pool)
@@ -177,17 +192,59 @@ impl WorkflowStorage {
             let plugin_name: Option<String> = row.try_get("plugin_name")?;
             let status: String = row.try_get("status")?;
             let message: Option<String> = row.try_get("message")?;
-            
+            let attempt: i64 = row.try_get("attempt")?;
+
             history.push(ox_workflow_core::HistoryRecord {
                 stage_name,
                 plugin_name,
                 status,
                 message,
+                attempt: attempt as u32,
             });
         }
         Ok(history)
     }
 
+    /// Marks a task to be picked up again by `take_due_retries` once `at_ms`
+    /// (milliseconds since the Unix epoch) has passed.
+    pub async fn schedule_retry(&self, id: Uuid, at_ms: i64) -> Result<(), StorageError> {
+        sqlx::query("UPDATE tasks SET retry_at = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(at_ms)
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Claims every queued task whose retry time is at or before `now_ms`, clearing the
+    /// retry mark so each one is returned exactly once.
+    pub async fn take_due_retries(&self, now_ms: i64) -> Result<Vec<DueRetry>, StorageError> {
+        let rows = sqlx::query(
+            r#"
+            UPDATE tasks SET retry_at = NULL
+            WHERE retry_at IS NOT NULL AND retry_at <= ? AND status = 'Queued'
+            RETURNING id, priority, metadata_json
+            "#
+        )
+        .bind(now_ms)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut due = Vec::new();
+        for row in rows {
+            let id_str: String = row.try_get("id")?;
+            let Ok(id) = Uuid::parse_str(&id_str) else { continue };
+            let priority: i64 = row.try_get("priority")?;
+            let metadata_json: String = row.try_get("metadata_json")?;
+            due.push(DueRetry {
+                id,
+                priority: priority as u32,
+                metadata: serde_json::from_str(&metadata_json)?,
+            });
+        }
+        Ok(due)
+    }
+
     async fn fetch_tasks(&self, query_str: &str, bind_val: &str) -> Result<Vec<Task>, StorageError> {
         let rows = sqlx::query(query_str)
             .bind(bind_val)
@@ -215,6 +272,8 @@ impl WorkflowStorage {
 
             let state = TaskState::from_proto_bytes(&state_blob)?;
             let metadata: HashMap<String, String> = serde_json::from_str(&metadata_json)?;
+            let checkpoint = checkpoint_from_row(&row)?;
+            let attempt: i64 = row.try_get("attempt")?;
 
             tasks.push(Task {
                 id,
@@ -230,6 +289,12 @@ impl WorkflowStorage {
                 ffi_bytes_arena: Vec::new(),
                 api_call_counts: std::collections::HashMap::new(),
                 api_limits: std::collections::HashMap::new(),
+                body_reader: None,
+                response_stream: None,
+                connections: None,
+                checkpoint,
+                attempt: attempt as u32,
+                stage_observer: None,
             });
         }
         Ok(tasks)
@@ -237,10 +302,23 @@ impl WorkflowStorage {
 
     pub async fn list_tasks_by_status(&self, status: TaskStatus) -> Result<Vec<Task>, StorageError> {
         let status_str = format!("{:?}", status);
-        self.fetch_tasks("SELECT id, priority, status, state_blob, metadata_json FROM tasks WHERE status = ?", &status_str).await
+        self.fetch_tasks("SELECT id, priority, status, state_blob, metadata_json, checkpoint_json, attempt FROM tasks WHERE status = ?", &status_str).await
     }
 
     pub async fn list_tasks_by_flow(&self, flow_name: &str) -> Result<Vec<Task>, StorageError> {
-        self.fetch_tasks("SELECT id, priority, status, state_blob, metadata_json FROM tasks WHERE flow_name = ?", flow_name).await
+        self.fetch_tasks("SELECT id, priority, status, state_blob, metadata_json, checkpoint_json, attempt FROM tasks WHERE flow_name = ?", flow_name).await
     }
 }
+
+/// A task whose retry backoff has elapsed, as returned by `take_due_retries`.
+#[derive(Debug, Clone)]
+pub struct DueRetry {
+    pub id: Uuid,
+    pub priority: u32,
+    pub metadata: HashMap<String, String>,
+}
+
+fn checkpoint_from_row(row: &sqlx::sqlite::SqliteRow) -> Result<Option<Checkpoint>, StorageError> {
+    let json: Option<String> = row.try_get("checkpoint_json")?;
+    Ok(json.as_deref().map(serde_json::from_str).transpose()?)
+}